@@ -0,0 +1,263 @@
+//! Machine code generation.
+//!
+//! A backend translates the MIR of each function into instructions of its
+//! target machine (instruction selection), using as many virtual registers
+//! as it likes.  The register allocator then maps the virtual registers to
+//! machine registers and stack slots (see [`regalloc`]), and the backend
+//! writes the result out as assembly text.  The x86-64 backend can also
+//! encode machine code itself, into an ELF object file (see [`elf`]).
+//!
+//! The types here are shared by the backends: a [`MachFunction`] holds the
+//! selected instructions of a function, and each target describes its
+//! instructions to the register allocator through the [`MachInst`] trait.
+//!
+//! WebAssembly has no registers to allocate, and is generated directly from
+//! the MIR (see [`wasm32`]).  Alternatively, a module can be handed to LLVM
+//! as textual IR (see [`llvm`]).
+
+use crate::ctype::{CType, DataModel, TypeTable};
+use crate::mir::{Module, Ty};
+use regalloc::Allocator;
+
+mod data;
+pub mod elf;
+pub mod llvm;
+pub mod regalloc;
+pub mod riscv64;
+pub mod wasm32;
+pub mod x86_64;
+
+/// A target machine.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Target {
+    /// x86-64, with the System V ABI.
+    #[default]
+    X86_64,
+
+    /// 64-bit RISC-V (RV64GC), with the LP64D ABI.
+    Riscv64,
+
+    /// 32-bit WebAssembly, as text.
+    Wasm32,
+}
+
+impl Target {
+    /// The sizes of the C types on the target.
+    pub fn data_model(self) -> DataModel {
+        match self {
+            Self::X86_64 | Self::Riscv64 => DataModel::Lp64,
+            Self::Wasm32 => DataModel::Ilp32,
+        }
+    }
+}
+
+/// Compile a module to assembly text for a target.
+pub fn compile(
+    module: &Module,
+    target: Target,
+    allocator: Allocator,
+) -> String {
+    match target {
+        Target::X86_64 => x86_64::compile(module, allocator),
+        Target::Riscv64 => riscv64::compile(module, allocator),
+        Target::Wasm32 => wasm32::compile(module),
+    }
+}
+
+/// A register operand.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Reg {
+    /// A virtual register, indexing [`MachFunction::vregs`].
+    Virt(u32),
+
+    /// A machine register, as numbered by the target.
+    Phys(u8),
+}
+
+/// A class of registers, which can hold the same kinds of values.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Class {
+    /// General-purpose registers, for integers and pointers.
+    Int,
+
+    /// Floating-point registers.
+    Float,
+}
+
+/// How an instruction accesses a register.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// The register is read.
+    Use,
+
+    /// The register is written.
+    Def,
+
+    /// The register is read, then written.
+    UseDef,
+}
+
+impl Role {
+    /// Whether the register is read.
+    pub fn is_use(self) -> bool {
+        matches!(self, Self::Use | Self::UseDef)
+    }
+
+    /// Whether the register is written.
+    pub fn is_def(self) -> bool {
+        matches!(self, Self::Def | Self::UseDef)
+    }
+}
+
+/// A stack slot in the frame of a function.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Slot(pub u32);
+
+/// The declaration of a [`Slot`].
+#[derive(Clone)]
+pub struct SlotDecl {
+    /// The size of the slot in bytes.
+    pub size: u64,
+
+    /// The alignment of the slot in bytes.
+    pub align: u64,
+}
+
+/// A function of machine instructions.
+pub struct MachFunction<I> {
+    /// The symbol name.
+    pub name: String,
+
+    /// The basic blocks, in the order they are laid out.
+    ///
+    /// These correspond to the blocks of the MIR function.
+    pub blocks: Vec<MachBlock<I>>,
+
+    /// The class of every virtual register.
+    pub vregs: Vec<Class>,
+
+    /// The stack slots of the frame.
+    pub slots: Vec<SlotDecl>,
+
+    /// The size of the area at the bottom of the frame holding the stack
+    /// arguments of calls.
+    pub outgoing: u64,
+}
+
+/// A basic block of machine instructions.
+pub struct MachBlock<I> {
+    /// The instructions, ending with the jumps out of the block.
+    pub insts: Vec<I>,
+
+    /// The blocks control may flow to.
+    pub succs: Vec<usize>,
+}
+
+impl<I> MachFunction<I> {
+    /// Create an empty function with a number of blocks.
+    pub fn new(name: String, blocks: usize) -> Self {
+        Self {
+            name,
+            blocks: (0..blocks)
+                .map(|_| MachBlock {
+                    insts: Vec::new(),
+                    succs: Vec::new(),
+                })
+                .collect(),
+            vregs: Vec::new(),
+            slots: Vec::new(),
+            outgoing: 0,
+        }
+    }
+
+    /// Add a new virtual register.
+    pub fn new_vreg(&mut self, class: Class) -> Reg {
+        self.vregs.push(class);
+        Reg::Virt(self.vregs.len() as u32 - 1)
+    }
+
+    /// Add a new stack slot.
+    pub fn new_slot(&mut self, size: u64, align: u64) -> Slot {
+        self.slots.push(SlotDecl { size, align });
+        Slot(self.slots.len() as u32 - 1)
+    }
+}
+
+/// An instruction of a target machine, as seen by the register allocator.
+pub trait MachInst: Sized {
+    /// The registers accessed by the instruction.
+    fn regs(&self) -> Vec<(Reg, Role)>;
+
+    /// The registers accessed by the instruction, mutably.
+    fn regs_mut(&mut self) -> Vec<(&mut Reg, Role)>;
+
+    /// The machine registers whose values are destroyed by the instruction,
+    /// other than those it defines (e.g. the caller-saved registers across
+    /// a call).
+    fn clobbers(&self) -> &'static [u8];
+
+    /// If the instruction only copies a register to another of the same
+    /// class, the destination and the source.
+    fn as_move(&self) -> Option<(Reg, Reg)>;
+
+    /// The blocks the instruction may jump to, mutably.
+    ///
+    /// Jumps only ever end a block, and must not depend on anything that
+    /// moves between registers and stack slots change.
+    fn targets_mut(&mut self) -> Vec<&mut usize>;
+
+    /// The machine registers of a class available for allocation, in order
+    /// of preference.
+    ///
+    /// The registers that need not be preserved across calls come first,
+    /// so that callee-saved registers are only used where they save spills.
+    fn allocatable(class: Class) -> &'static [u8];
+
+    /// The registers reserved for moving values between stack slots and
+    /// instructions, which are never otherwise used.
+    ///
+    /// Instructions read at most two virtual registers of each class, and
+    /// write at most two.
+    fn scratch(class: Class) -> [u8; 2];
+
+    /// Load a register of a class from a stack slot.
+    fn reload(reg: u8, class: Class, slot: Slot) -> Self;
+
+    /// Store a register of a class to a stack slot.
+    fn spill(reg: u8, class: Class, slot: Slot) -> Self;
+
+    /// Copy a register of a class to another.
+    fn copy(dst: u8, src: u8, class: Class) -> Self;
+
+    /// Jump to a block.
+    fn jump(target: usize) -> Self;
+}
+
+/// Visit the scalars making up a type, with their offsets.
+pub fn scalars(
+    types: &TypeTable,
+    ty: &CType,
+    offset: u64,
+    f: &mut impl FnMut(u64, Ty),
+) {
+    match ty {
+        CType::Struct(id) => {
+            let layout = types.layout(*id);
+            let fields = types.get(*id).fields.iter().flatten();
+            for (field, field_offset) in fields.zip(layout.offsets) {
+                scalars(types, &field.ty, offset + field_offset, f);
+            }
+        }
+        CType::Array { elem, len } => {
+            let size = types.size_of(elem);
+            for i in 0..len.unwrap_or(0) {
+                scalars(types, elem, offset + i * size, f);
+            }
+        }
+        ty => {
+            if let Some(ty) = Ty::of(ty, types.model()) {
+                f(offset, ty);
+            }
+        }
+    }
+}
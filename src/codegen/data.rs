@@ -0,0 +1,85 @@
+//! Writing the data of global variables, shared by the backends.
+
+use std::fmt::Write;
+
+use super::elf::{Object, Section, SymbolKind};
+use crate::ctype::TypeTable;
+use crate::mir::Global;
+
+/// The section a global variable is placed in.
+fn section(global: &Global) -> Section {
+    match &global.init {
+        None => Section::Bss,
+        Some(init) if global.readonly && init.relocs.is_empty() => {
+            Section::Rodata
+        }
+        Some(_) if global.readonly => Section::DataRelRo,
+        Some(_) => Section::Data,
+    }
+}
+
+/// Write out the definition of a global variable.
+pub fn global(out: &mut String, global: &Global, types: &TypeTable) {
+    let name = &global.name;
+    let size = types.size_of(&global.ty);
+    let align = types.align_of(&global.ty).max(1);
+    writeln!(out, "\t.section {}", section(global).name()).unwrap();
+    if !global.local {
+        writeln!(out, "\t.globl {name}").unwrap();
+    }
+    writeln!(out, "\t.type {name}, @object").unwrap();
+    writeln!(out, "\t.size {name}, {size}").unwrap();
+    writeln!(out, "\t.p2align {}", align.trailing_zeros()).unwrap();
+    writeln!(out, "{name}:").unwrap();
+
+    let Some(init) = &global.init else {
+        writeln!(out, "\t.zero {}", size.max(1)).unwrap();
+        return;
+    };
+    let mut relocs: Vec<_> = init.relocs.iter().collect();
+    relocs.sort_by_key(|reloc| reloc.offset);
+    let mut offset = 0;
+    for reloc in relocs {
+        bytes(out, &init.bytes[offset as usize..reloc.offset as usize]);
+        match reloc.addend {
+            0 => writeln!(out, "\t.quad {}", reloc.symbol),
+            addend => writeln!(out, "\t.quad {}{addend:+}", reloc.symbol),
+        }
+        .unwrap();
+        offset = reloc.offset + 8;
+    }
+    bytes(out, &init.bytes[offset as usize..]);
+    let padding =
+        size.saturating_sub(init.bytes.len().max(offset as usize) as u64);
+    if padding > 0 {
+        writeln!(out, "\t.zero {padding}").unwrap();
+    }
+}
+
+/// Write out bytes of data.
+fn bytes(out: &mut String, bytes: &[u8]) {
+    for line in bytes.chunks(16) {
+        let line: Vec<_> = line.iter().map(u8::to_string).collect();
+        writeln!(out, "\t.byte {}", line.join(",")).unwrap();
+    }
+}
+
+/// Place a global variable in an object file, with relocations of the given
+/// type for the 64-bit addresses it holds.
+pub fn place(obj: &mut Object, global: &Global, types: &TypeTable, abs64: u32) {
+    let size = types.size_of(&global.ty);
+    let align = types.align_of(&global.ty).max(1);
+    let mut bytes = global
+        .init
+        .as_ref()
+        .map_or_else(Vec::new, |init| init.bytes.clone());
+    bytes.resize(bytes.len().max(size.max(1) as usize), 0);
+    let section = section(global);
+    let offset = obj.append(section, &bytes, align, 0);
+    let kind = SymbolKind::Object;
+    obj.define(&global.name, section, offset, size, kind, global.local);
+    for reloc in global.init.iter().flat_map(|init| &init.relocs) {
+        let at = offset + reloc.offset;
+        obj.reloc(section, at, abs64, &reloc.symbol, reloc.addend);
+    }
+}
@@ -0,0 +1,533 @@
+//! Writing ELF64 relocatable object files, for `sea -c`.
+//!
+//! An [`Object`] collects the contents of the sections, the symbols defined
+//! in them and the relocations against them, and is then written out as a
+//! little-endian ELF64 file.  Symbols referenced by relocations but never
+//! defined become undefined global symbols, for the linker to resolve.  The
+//! relocation types are those of the machine the object is for.
+
+use std::collections::HashMap;
+
+/// The machine type of x86-64 (`EM_X86_64`).
+pub const EM_X86_64: u16 = 62;
+
+/// A section holding code or data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Section {
+    /// Code.
+    Text,
+
+    /// Initialized variables.
+    Data,
+
+    /// Initialized variables holding addresses, written only by relocation.
+    DataRelRo,
+
+    /// Initialized read-only variables.
+    Rodata,
+
+    /// Zero-initialized variables, which take no space in the file.
+    Bss,
+}
+
+impl Section {
+    /// Every section, in the order they are written.
+    const ALL: [Self; 5] = [
+        Self::Text,
+        Self::Data,
+        Self::DataRelRo,
+        Self::Rodata,
+        Self::Bss,
+    ];
+
+    /// The name of the section.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Text => ".text",
+            Self::Data => ".data",
+            Self::DataRelRo => ".data.rel.ro",
+            Self::Rodata => ".rodata",
+            Self::Bss => ".bss",
+        }
+    }
+
+    /// The `SHF_*` flags of the section.
+    fn flags(self) -> u64 {
+        match self {
+            Self::Text => SHF_ALLOC | SHF_EXECINSTR,
+            Self::Data | Self::DataRelRo | Self::Bss => SHF_ALLOC | SHF_WRITE,
+            Self::Rodata => SHF_ALLOC,
+        }
+    }
+}
+
+/// What a symbol names.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A function.
+    Func,
+
+    /// A variable.
+    Object,
+}
+
+/// A symbol defined in the object.
+struct Symbol {
+    /// The name.
+    name: String,
+
+    /// The section the symbol is defined in.
+    section: Section,
+
+    /// The offset of the symbol in its section.
+    offset: u64,
+
+    /// The size of what the symbol names.
+    size: u64,
+
+    /// What the symbol names.
+    kind: SymbolKind,
+
+    /// Whether the symbol is local to the object.
+    local: bool,
+}
+
+/// A relocation.
+struct Reloc {
+    /// The offset of the relocated field in its section.
+    offset: u64,
+
+    /// The machine-specific relocation type.
+    kind: u32,
+
+    /// The symbol whose address is used.
+    symbol: String,
+
+    /// A constant added to the address.
+    addend: i64,
+}
+
+/// The contents of a section.
+#[derive(Default)]
+struct Contents {
+    /// The bytes of the section, or only their number for `.bss`.
+    bytes: Vec<u8>,
+
+    /// The size of `.bss`.
+    size: u64,
+
+    /// The alignment of the section.
+    align: u64,
+
+    /// The relocations against the section.
+    relocs: Vec<Reloc>,
+}
+
+/// A relocatable object being built.
+pub struct Object {
+    /// The machine type.
+    machine: u16,
+
+    /// The contents of the sections.
+    sections: HashMap<Section, Contents>,
+
+    /// The symbols defined, in order of definition.
+    symbols: Vec<Symbol>,
+}
+
+impl Object {
+    /// Create an empty object for a machine.
+    pub fn new(machine: u16) -> Self {
+        let sections = Section::ALL
+            .into_iter()
+            .map(|section| {
+                let contents = Contents {
+                    align: 1,
+                    ..Contents::default()
+                };
+                (section, contents)
+            })
+            .collect();
+        Self {
+            machine,
+            sections,
+            symbols: Vec::new(),
+        }
+    }
+
+    /// Append bytes to a section at an alignment, returning their offset.
+    ///
+    /// Code is padded with `fill`, and data with zeros.
+    pub fn append(
+        &mut self,
+        section: Section,
+        bytes: &[u8],
+        align: u64,
+        fill: u8,
+    ) -> u64 {
+        let contents = self.sections.get_mut(&section).unwrap();
+        contents.align = contents.align.max(align);
+        if section == Section::Bss {
+            let offset = contents.size.next_multiple_of(align);
+            contents.size = offset + bytes.len() as u64;
+            return offset;
+        }
+        let offset = (contents.bytes.len() as u64).next_multiple_of(align);
+        contents.bytes.resize(offset as usize, fill);
+        contents.bytes.extend_from_slice(bytes);
+        offset
+    }
+
+    /// Define a symbol at an offset in a section.
+    pub fn define(
+        &mut self,
+        name: &str,
+        section: Section,
+        offset: u64,
+        size: u64,
+        kind: SymbolKind,
+        local: bool,
+    ) {
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            section,
+            offset,
+            size,
+            kind,
+            local,
+        });
+    }
+
+    /// Add a relocation of a field in a section.
+    pub fn reloc(
+        &mut self,
+        section: Section,
+        offset: u64,
+        kind: u32,
+        symbol: &str,
+        addend: i64,
+    ) {
+        let contents = self.sections.get_mut(&section).unwrap();
+        contents.relocs.push(Reloc {
+            offset,
+            kind,
+            symbol: symbol.to_string(),
+            addend,
+        });
+    }
+
+    /// Write out the object file.
+    pub fn write(&self) -> Vec<u8> {
+        // The symbol table: the null symbol, local symbols, then global
+        // ones, both defined and undefined.
+        let mut strtab = StringTable::default();
+        let mut symtab = Vec::new();
+        let mut indices = HashMap::new();
+        write_symbol(&mut symtab, 0, 0, 0, 0, 0);
+        let (locals, globals): (Vec<_>, Vec<_>) =
+            self.symbols.iter().partition(|symbol| symbol.local);
+        let first_global = 1 + locals.len() as u32;
+        for (i, symbol) in locals.iter().chain(&globals).enumerate() {
+            let binding = if symbol.local { STB_LOCAL } else { STB_GLOBAL };
+            let kind = match symbol.kind {
+                SymbolKind::Func => STT_FUNC,
+                SymbolKind::Object => STT_OBJECT,
+            };
+            write_symbol(
+                &mut symtab,
+                strtab.add(&symbol.name),
+                binding << 4 | kind,
+                section_index(symbol.section),
+                symbol.offset,
+                symbol.size,
+            );
+            indices.insert(symbol.name.as_str(), i as u64 + 1);
+        }
+        for section in Section::ALL {
+            for reloc in &self.sections[&section].relocs {
+                let count = indices.len() as u64;
+                indices.entry(&reloc.symbol).or_insert_with(|| {
+                    let name = strtab.add(&reloc.symbol);
+                    write_symbol(&mut symtab, name, STB_GLOBAL << 4, 0, 0, 0);
+                    count + 1
+                });
+            }
+        }
+
+        // The relocation sections, one for each section with relocations.
+        let relas: Vec<_> = Section::ALL
+            .into_iter()
+            .filter(|section| !self.sections[section].relocs.is_empty())
+            .map(|section| {
+                let mut rela = Vec::new();
+                for reloc in &self.sections[&section].relocs {
+                    let symbol = indices[reloc.symbol.as_str()];
+                    let info = symbol << 32 | reloc.kind as u64;
+                    rela.extend(reloc.offset.to_le_bytes());
+                    rela.extend(info.to_le_bytes());
+                    rela.extend(reloc.addend.to_le_bytes());
+                }
+                (section, rela)
+            })
+            .collect();
+
+        // The section headers, with the contents laid out after the ELF
+        // header in the same order.
+        let mut shstrtab = StringTable::default();
+        let mut headers = vec![SectionHeader::default()];
+        let mut contents: Vec<&[u8]> = vec![&[]];
+        for section in Section::ALL {
+            let data = &self.sections[&section];
+            headers.push(SectionHeader {
+                name: shstrtab.add(section.name()),
+                kind: match section {
+                    Section::Bss => SHT_NOBITS,
+                    _ => SHT_PROGBITS,
+                },
+                flags: section.flags(),
+                size: match section {
+                    Section::Bss => data.size,
+                    _ => data.bytes.len() as u64,
+                },
+                align: data.align,
+                ..SectionHeader::default()
+            });
+            contents.push(&data.bytes);
+        }
+        // An empty `.note.GNU-stack` section asks for a non-executable
+        // stack.
+        headers.push(SectionHeader {
+            name: shstrtab.add(".note.GNU-stack"),
+            kind: SHT_PROGBITS,
+            align: 1,
+            ..SectionHeader::default()
+        });
+        contents.push(&[]);
+        let symtab_index = (headers.len() + relas.len()) as u32;
+        for (section, rela) in &relas {
+            headers.push(SectionHeader {
+                name: shstrtab.add(&format!(".rela{}", section.name())),
+                kind: SHT_RELA,
+                flags: SHF_INFO_LINK,
+                size: rela.len() as u64,
+                link: symtab_index,
+                info: section_index(*section) as u32,
+                align: 8,
+                entsize: 24,
+                ..SectionHeader::default()
+            });
+            contents.push(rela);
+        }
+        headers.push(SectionHeader {
+            name: shstrtab.add(".symtab"),
+            kind: SHT_SYMTAB,
+            size: symtab.len() as u64,
+            link: symtab_index + 1,
+            info: first_global,
+            align: 8,
+            entsize: 24,
+            ..SectionHeader::default()
+        });
+        contents.push(&symtab);
+        headers.push(SectionHeader {
+            name: shstrtab.add(".strtab"),
+            kind: SHT_STRTAB,
+            size: strtab.0.len() as u64,
+            align: 1,
+            ..SectionHeader::default()
+        });
+        contents.push(&strtab.0);
+        let shstrndx = headers.len() as u16;
+        headers.push(SectionHeader {
+            name: shstrtab.add(".shstrtab"),
+            kind: SHT_STRTAB,
+            size: shstrtab.0.len() as u64,
+            align: 1,
+            ..SectionHeader::default()
+        });
+        let shstrtab = shstrtab.0;
+        contents.push(&shstrtab);
+
+        let mut out = vec![0; EHDR_SIZE];
+        for (header, contents) in headers.iter_mut().zip(contents) {
+            if header.kind == SHT_NULL {
+                continue;
+            }
+            let offset = (out.len() as u64).next_multiple_of(header.align);
+            out.resize(offset as usize, 0);
+            out.extend_from_slice(contents);
+            header.offset = offset;
+        }
+        let shoff = (out.len() as u64).next_multiple_of(8);
+        out.resize(shoff as usize, 0);
+        for header in &headers {
+            header.write(&mut out);
+        }
+
+        let mut ehdr = Vec::with_capacity(EHDR_SIZE);
+        ehdr.extend(b"\x7fELF");
+        // 64-bit, little-endian, version 1, System V ABI.
+        ehdr.extend([2, 1, 1, 0]);
+        ehdr.extend([0; 8]);
+        ehdr.extend(ET_REL.to_le_bytes());
+        ehdr.extend(self.machine.to_le_bytes());
+        ehdr.extend(1u32.to_le_bytes());
+        // No entry point or program headers.
+        ehdr.extend(0u64.to_le_bytes());
+        ehdr.extend(0u64.to_le_bytes());
+        ehdr.extend(shoff.to_le_bytes());
+        ehdr.extend(0u32.to_le_bytes());
+        ehdr.extend((EHDR_SIZE as u16).to_le_bytes());
+        ehdr.extend(0u16.to_le_bytes());
+        ehdr.extend(0u16.to_le_bytes());
+        ehdr.extend((SHDR_SIZE as u16).to_le_bytes());
+        ehdr.extend((headers.len() as u16).to_le_bytes());
+        ehdr.extend(shstrndx.to_le_bytes());
+        out[..EHDR_SIZE].copy_from_slice(&ehdr);
+        out
+    }
+}
+
+//----------- Encoding ---------------------------------------------------------
+
+/// The size of the ELF header.
+const EHDR_SIZE: usize = 64;
+
+/// The size of a section header.
+const SHDR_SIZE: usize = 64;
+
+/// A relocatable file (`ET_REL`).
+const ET_REL: u16 = 1;
+
+/// An unused section header.
+const SHT_NULL: u32 = 0;
+
+/// A section with contents defined by the program.
+const SHT_PROGBITS: u32 = 1;
+
+/// A symbol table.
+const SHT_SYMTAB: u32 = 2;
+
+/// A string table.
+const SHT_STRTAB: u32 = 3;
+
+/// Relocations with addends.
+const SHT_RELA: u32 = 4;
+
+/// A section taking no space in the file.
+const SHT_NOBITS: u32 = 8;
+
+/// A writable section.
+const SHF_WRITE: u64 = 0x1;
+
+/// A section loaded in memory.
+const SHF_ALLOC: u64 = 0x2;
+
+/// A section of code.
+const SHF_EXECINSTR: u64 = 0x4;
+
+/// A section whose `sh_info` is a section index.
+const SHF_INFO_LINK: u64 = 0x40;
+
+/// A symbol local to the object.
+const STB_LOCAL: u8 = 0;
+
+/// A symbol visible to other objects.
+const STB_GLOBAL: u8 = 1;
+
+/// A variable.
+const STT_OBJECT: u8 = 1;
+
+/// A function.
+const STT_FUNC: u8 = 2;
+
+/// The index of the header of a section.
+fn section_index(section: Section) -> u16 {
+    let index = Section::ALL.iter().position(|&s| s == section).unwrap();
+    index as u16 + 1
+}
+
+/// Write an entry of the symbol table.
+fn write_symbol(
+    out: &mut Vec<u8>,
+    name: u32,
+    info: u8,
+    section: u16,
+    value: u64,
+    size: u64,
+) {
+    out.extend(name.to_le_bytes());
+    out.push(info);
+    out.push(0);
+    out.extend(section.to_le_bytes());
+    out.extend(value.to_le_bytes());
+    out.extend(size.to_le_bytes());
+}
+
+/// A string table being built.
+struct StringTable(Vec<u8>);
+
+impl Default for StringTable {
+    fn default() -> Self {
+        // Offset 0 is the empty string.
+        Self(vec![0])
+    }
+}
+
+impl StringTable {
+    /// Add a string, returning its offset.
+    fn add(&mut self, string: &str) -> u32 {
+        let offset = self.0.len() as u32;
+        self.0.extend(string.as_bytes());
+        self.0.push(0);
+        offset
+    }
+}
+
+/// A section header.
+#[derive(Default)]
+struct SectionHeader {
+    /// The offset of the name in the section header string table.
+    name: u32,
+
+    /// The `SHT_*` type.
+    kind: u32,
+
+    /// The `SHF_*` flags.
+    flags: u64,
+
+    /// The offset of the contents in the file.
+    offset: u64,
+
+    /// The size of the contents.
+    size: u64,
+
+    /// A related section.
+    link: u32,
+
+    /// Extra information, depending on the type.
+    info: u32,
+
+    /// The alignment of the contents.
+    align: u64,
+
+    /// The size of the entries of a table.
+    entsize: u64,
+}
+
+impl SectionHeader {
+    /// Write out the header.
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.name.to_le_bytes());
+        out.extend(self.kind.to_le_bytes());
+        out.extend(self.flags.to_le_bytes());
+        // Sections of relocatable files have no address.
+        out.extend(0u64.to_le_bytes());
+        out.extend(self.offset.to_le_bytes());
+        out.extend(self.size.to_le_bytes());
+        out.extend(self.link.to_le_bytes());
+        out.extend(self.info.to_le_bytes());
+        out.extend(self.align.to_le_bytes());
+        out.extend(self.entsize.to_le_bytes());
+    }
+}
@@ -0,0 +1,431 @@
+//! Textual LLVM IR.
+//!
+//! As an alternative to the backends, a module can be written out as LLVM
+//! IR (see `sea --emit=llvm`), for `llc` or `clang` to compile.  The MIR
+//! maps onto it closely: registers are SSA values and phis are phis, memory
+//! locals are `alloca`s, and field and element addresses are
+//! `getelementptr`s over the structure types of the module.  Only function
+//! boundaries need more care, as LLVM leaves part of the calling convention
+//! to the frontend (see [`abi`]).
+//!
+//! Pointers are opaque (`ptr`), as in LLVM 15 and later; LLVM 14 accepts
+//! them with `-opaque-pointers`.  There is no output for WebAssembly, whose
+//! pointers LLVM would make 4 bytes, unlike those of the MIR.
+
+use std::cell::Cell;
+use std::fmt::Write;
+
+use super::Target;
+use crate::ctype::{CType, DataModel, FloatType, FnType, IntRank, TypeTable};
+use crate::mir::{Global, GlobalInit, Module, Ty};
+use abi::{Pass, Signature};
+
+mod abi;
+mod emit;
+
+/// The data model of the targets, which is the same for all of them.
+const MODEL: DataModel = DataModel::Lp64;
+
+/// Compile a module to LLVM IR for a target.
+pub fn compile(module: &Module, target: Target) -> String {
+    let ctx = Context::new(target, &module.types);
+    let mut out = String::new();
+    let triple = match target {
+        Target::X86_64 => "x86_64-unknown-linux-gnu",
+        Target::Riscv64 => "riscv64-unknown-linux-gnu",
+        Target::Wasm32 => unreachable!("no LLVM IR is written for wasm32"),
+    };
+    writeln!(out, "target triple = \"{triple}\"").unwrap();
+
+    if module.types.struct_ids().next().is_some() {
+        out.push('\n');
+    }
+    for id in module.types.struct_ids() {
+        let name = &ctx.names[id.0];
+        match &module.types.get(id).fields {
+            Some(fields) => {
+                let fields: Vec<_> =
+                    fields.iter().map(|field| ctx.ty(&field.ty)).collect();
+                writeln!(out, "{name} = type {{ {} }}", fields.join(", "))
+            }
+            None => writeln!(out, "{name} = type opaque"),
+        }
+        .unwrap();
+    }
+
+    if !module.globals.is_empty() {
+        out.push('\n');
+    }
+    for global in &module.globals {
+        ctx.global(&mut out, global);
+    }
+
+    if !module.externs.is_empty() {
+        out.push('\n');
+    }
+    for ext in &module.externs {
+        let name = &ext.name;
+        let Some(ty) = ext.ty.fn_type() else {
+            let ty = ctx.ty(&ext.ty);
+            writeln!(out, "@{name} = external global {ty}").unwrap();
+            continue;
+        };
+        let sig = ctx.signature(ty, ty.params.len());
+        let params = ctx.params(&sig, ty);
+        let mut params: Vec<_> = params.iter().map(Param::to_string).collect();
+        if ty.variadic {
+            params.push("...".to_string());
+        }
+        writeln!(
+            out,
+            "declare {} @{name}({}){}",
+            ret(&sig),
+            params.join(", "),
+            ctx.attributes()
+        )
+        .unwrap();
+    }
+
+    for func in &module.functions {
+        out.push('\n');
+        emit::function(&mut out, &ctx, func);
+    }
+
+    if ctx.memcpy.get() {
+        out.push('\n');
+        out.push_str(
+            "declare void @llvm.memcpy.p0.p0.i64(ptr, ptr, i64, i1)\n",
+        );
+    }
+    if ctx.va_start.get() {
+        out.push('\n');
+        out.push_str("declare void @llvm.va_start(ptr)\n");
+    }
+    if target == Target::Riscv64 {
+        // Without these, LLVM assumes no floating-point registers and
+        // passes floats in integer registers.
+        out.push_str(concat!(
+            "\nattributes #0 = { \"target-features\"=",
+            "\"+64bit,+a,+c,+d,+f,+m\" }\n",
+            "\n!llvm.module.flags = !{!0}\n",
+            "!0 = !{i32 1, !\"target-abi\", !\"lp64d\"}\n",
+        ));
+    }
+    out
+}
+
+//----------- Context ----------------------------------------------------------
+
+/// What the translation of a module needs to know about it.
+pub struct Context<'a> {
+    /// The target machine.
+    target: Target,
+
+    /// The structure types of the module.
+    types: &'a TypeTable,
+
+    /// The LLVM name of each structure type, indexed by its identity.
+    names: Vec<String>,
+
+    /// Whether `llvm.memcpy` is used, and must be declared.
+    memcpy: Cell<bool>,
+
+    /// Whether `llvm.va_start` is used, and must be declared.
+    va_start: Cell<bool>,
+}
+
+/// A parameter of a lowered function type.
+struct Param {
+    /// The index of the C parameter this passes (a part of), or `None` for
+    /// the address the result is written to.
+    index: Option<usize>,
+
+    /// The LLVM type.
+    ty: String,
+
+    /// The parameter attributes, if any.
+    attrs: String,
+}
+
+impl std::fmt::Display for Param {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.attrs.as_str() {
+            "" => write!(f, "{}", self.ty),
+            attrs => write!(f, "{} {}", self.ty, attrs),
+        }
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Prepare the translation of a module.
+    fn new(target: Target, types: &'a TypeTable) -> Self {
+        // Structures are named after their tags, unless these are missing
+        // or shared by several structures (in different scopes).
+        let tags: Vec<_> =
+            types.struct_ids().map(|id| &types.get(id).name).collect();
+        let names = types.struct_ids().map(|id| match tags[id.0] {
+            Some(tag)
+                if tags.iter().filter(|t| **t == tags[id.0]).count() == 1 =>
+            {
+                format!("%struct.{tag}")
+            }
+            Some(tag) => format!("%struct.{tag}.{}", id.0),
+            None => format!("%struct.anon.{}", id.0),
+        });
+        Self {
+            target,
+            types,
+            names: names.collect(),
+            memcpy: Cell::new(false),
+            va_start: Cell::new(false),
+        }
+    }
+
+    /// The LLVM type of a C type.
+    ///
+    /// `void` and functions become `i8`, as only their addresses are used,
+    /// and GNU C gives them a size of 1 for pointer arithmetic.
+    fn ty(&self, ty: &CType) -> String {
+        match ty {
+            CType::Void | CType::Fn(_) => "i8".to_string(),
+            CType::Int(int) => match int.rank {
+                IntRank::Bool | IntRank::Char => "i8",
+                IntRank::Short => "i16",
+                IntRank::Int => "i32",
+                IntRank::Long | IntRank::LongLong => "i64",
+            }
+            .to_string(),
+            CType::Float(FloatType::Float) => "float".to_string(),
+            CType::Float(_) => "double".to_string(),
+            CType::Pointer(_) => "ptr".to_string(),
+            CType::Array { elem, len } => {
+                format!("[{} x {}]", len.unwrap_or(0), self.ty(elem))
+            }
+            CType::Struct(id) => self.names[id.0].clone(),
+        }
+    }
+
+    /// The alignment of a C type.
+    fn align(&self, ty: &CType) -> u64 {
+        self.types.align_of(ty).max(1)
+    }
+
+    /// Lower the type of a call (see [`abi::signature()`]).
+    fn signature(&self, ty: &FnType, fixed: usize) -> Signature {
+        abi::signature(self.target, self.types, ty, fixed)
+    }
+
+    /// The parameters of a lowered function type.
+    fn params(&self, sig: &Signature, ty: &FnType) -> Vec<Param> {
+        let mut params = Vec::new();
+        if let Some(Pass::Indirect { .. }) = sig.ret {
+            params.push(Param {
+                index: None,
+                ty: "ptr".to_string(),
+                attrs: format!(
+                    "sret({}) align {}",
+                    self.ty(&ty.ret),
+                    self.align(&ty.ret)
+                ),
+            });
+        }
+        for (index, (pass, param)) in
+            sig.params.iter().zip(&ty.params).enumerate()
+        {
+            let index = Some(index);
+            match pass {
+                Pass::Scalar(mty, ext) => params.push(Param {
+                    index,
+                    ty: scalar(*mty).to_string(),
+                    attrs: ext.unwrap_or_default().to_string(),
+                }),
+                Pass::Parts(parts) => {
+                    params.extend(parts.iter().map(|(_, mty)| Param {
+                        index,
+                        ty: scalar(*mty).to_string(),
+                        attrs: String::new(),
+                    }))
+                }
+                Pass::Indirect { byval } => params.push(Param {
+                    index,
+                    ty: "ptr".to_string(),
+                    attrs: match byval {
+                        true => format!(
+                            "byval({}) align {}",
+                            self.ty(param),
+                            self.align(param).max(8)
+                        ),
+                        false => String::new(),
+                    },
+                }),
+            }
+        }
+        params
+    }
+
+    /// The attribute group of functions.
+    fn attributes(&self) -> &'static str {
+        match self.target {
+            Target::X86_64 | Target::Wasm32 => "",
+            Target::Riscv64 => " #0",
+        }
+    }
+}
+
+//----------- Values -----------------------------------------------------------
+
+/// The LLVM type of a machine type.
+fn scalar(ty: Ty) -> &'static str {
+    match ty {
+        Ty::I8 => "i8",
+        Ty::I16 => "i16",
+        Ty::I32 => "i32",
+        Ty::I64 => "i64",
+        Ty::F32 => "float",
+        Ty::F64 => "double",
+        Ty::Ptr => "ptr",
+    }
+}
+
+/// The result type of a lowered function type, with its attributes.
+fn ret(sig: &Signature) -> String {
+    match &sig.ret {
+        None | Some(Pass::Indirect { .. }) => "void".to_string(),
+        Some(Pass::Scalar(ty, None)) => scalar(*ty).to_string(),
+        Some(Pass::Scalar(ty, Some(ext))) => format!("{ext} {}", scalar(*ty)),
+        Some(Pass::Parts(parts)) => parts_ty(parts),
+    }
+}
+
+/// The LLVM type of the parts of an aggregate returned in registers.
+fn parts_ty(parts: &[(u64, Ty)]) -> String {
+    match parts {
+        [(_, ty)] => scalar(*ty).to_string(),
+        parts => {
+            let tys: Vec<_> = parts.iter().map(|(_, ty)| scalar(*ty)).collect();
+            format!("{{ {} }}", tys.join(", "))
+        }
+    }
+}
+
+/// An integer constant of a machine type.
+fn int(value: i64, ty: Ty) -> String {
+    match ty {
+        Ty::Ptr if value == 0 => "null".to_string(),
+        Ty::Ptr => format!("inttoptr (i64 {value} to ptr)"),
+        Ty::F32 | Ty::F64 => float(value as f64, ty),
+        ty => ty.wrap(value).to_string(),
+    }
+}
+
+/// A floating-point constant of a machine type.
+///
+/// LLVM writes floats exactly as the bits of a double, even for `float`.
+fn float(value: f64, ty: Ty) -> String {
+    let value = match ty {
+        Ty::F32 => value as f32 as f64,
+        _ => value,
+    };
+    format!("0x{:016X}", value.to_bits())
+}
+
+//----------- Globals ----------------------------------------------------------
+
+impl Context<'_> {
+    /// Write out the definition of a global variable.
+    fn global(&self, out: &mut String, global: &Global) {
+        let linkage = if global.local { "internal " } else { "" };
+        let kind = if global.readonly {
+            "constant"
+        } else {
+            "global"
+        };
+        let init = match &global.init {
+            Some(init)
+                if !init.relocs.is_empty()
+                    || init.bytes.iter().any(|&byte| byte != 0) =>
+            {
+                self.init(&global.ty, init)
+            }
+            _ => format!("{} zeroinitializer", self.ty(&global.ty)),
+        };
+        writeln!(
+            out,
+            "@{} = {linkage}{kind} {init}, align {}",
+            global.name,
+            self.align(&global.ty)
+        )
+        .unwrap();
+    }
+
+    /// The typed initial value of a global variable.
+    ///
+    /// Scalars are written as such.  Aggregates are written as their bytes,
+    /// in a packed structure with the addresses stored in them if there are
+    /// any.
+    fn init(&self, ty: &CType, init: &GlobalInit) -> String {
+        let size = self.types.size_of(ty) as usize;
+        let mut bytes = init.bytes.clone();
+        bytes.resize(bytes.len().max(size), 0);
+        let mut relocs: Vec<_> = init.relocs.iter().collect();
+        relocs.sort_by_key(|reloc| reloc.offset);
+        let address = |symbol: &str, addend: i64| match addend {
+            0 => format!("@{symbol}"),
+            _ => format!("getelementptr (i8, ptr @{symbol}, i64 {addend})"),
+        };
+
+        if let Some(mty) = Ty::of(ty, MODEL) {
+            if let [reloc] = &relocs[..] {
+                return format!("ptr {}", address(&reloc.symbol, reloc.addend));
+            }
+            let mut raw = [0; 8];
+            raw[..size].copy_from_slice(&bytes[..size]);
+            let raw = i64::from_le_bytes(raw);
+            let value = match mty {
+                Ty::F32 => float(f32::from_bits(raw as u32) as f64, mty),
+                Ty::F64 => float(f64::from_bits(raw as u64), mty),
+                _ => int(raw, mty),
+            };
+            return format!("{} {value}", scalar(mty));
+        }
+
+        if relocs.is_empty() {
+            let (ty, value) = string(&bytes);
+            return format!("{ty} {value}");
+        }
+        // The fields of the packed structure, as types and values.
+        let mut fields = Vec::new();
+        let mut offset = 0;
+        for reloc in relocs {
+            if reloc.offset > offset {
+                let start = offset as usize;
+                fields.push(string(&bytes[start..reloc.offset as usize]));
+            }
+            let value = address(&reloc.symbol, reloc.addend);
+            fields.push(("ptr".to_string(), value));
+            offset = reloc.offset + 8;
+        }
+        if (offset as usize) < bytes.len() {
+            fields.push(string(&bytes[offset as usize..]));
+        }
+        let (tys, values): (Vec<_>, Vec<_>) = fields
+            .into_iter()
+            .map(|(ty, value)| (ty.clone(), format!("{ty} {value}")))
+            .unzip();
+        format!("<{{ {} }}> <{{ {} }}>", tys.join(", "), values.join(", "))
+    }
+}
+
+/// The type and value of an array of bytes.
+fn string(bytes: &[u8]) -> (String, String) {
+    let mut out = String::from("c\"");
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => write!(out, "\\{byte:02X}").unwrap(),
+            b' '..=b'~' => out.push(byte as char),
+            _ => write!(out, "\\{byte:02X}").unwrap(),
+        }
+    }
+    out.push('"');
+    (format!("[{} x i8]", bytes.len()), out)
+}
@@ -0,0 +1,253 @@
+//! How values are passed to and returned from functions.
+//!
+//! LLVM leaves part of the C calling convention to the frontend.  It passes
+//! every IR parameter on its own, so an aggregate travelling in registers
+//! has to be broken up into the scalars the ABI puts in them, and it is the
+//! frontend that must notice when an aggregate no longer fits in the
+//! registers left.  The rules here are those of the backends, spelled the
+//! way Clang spells them.
+
+use super::MODEL;
+use crate::codegen::{Target, scalars};
+use crate::ctype::{CType, FnType, IntRank, TypeTable};
+use crate::mir::Ty;
+
+/// How a value is passed or returned.
+pub enum Pass {
+    /// As a scalar, with the attribute extending it if it is a narrow
+    /// integer.
+    Scalar(Ty, Option<&'static str>),
+
+    /// As the scalars at some offsets in an aggregate.
+    ///
+    /// The last one may extend past the end of the aggregate.
+    Parts(Vec<(u64, Ty)>),
+
+    /// As the address of a copy of an aggregate, which LLVM makes on the
+    /// stack if `byval`, and the caller makes otherwise.
+    ///
+    /// A result returned this way is written to an address the caller
+    /// passes before the arguments.
+    Indirect {
+        /// Whether the copy is passed on the stack.
+        byval: bool,
+    },
+}
+
+/// How the values of a call are passed.
+pub struct Signature {
+    /// The result, or `None` for `void`.
+    pub ret: Option<Pass>,
+
+    /// The arguments.
+    pub params: Vec<Pass>,
+}
+
+/// Lower the type of a call, of which the first `fixed` arguments match
+/// named parameters.
+pub fn signature(
+    target: Target,
+    types: &TypeTable,
+    ty: &FnType,
+    fixed: usize,
+) -> Signature {
+    match target {
+        Target::X86_64 => x86_64(types, ty),
+        Target::Riscv64 => riscv64(types, ty, fixed),
+        Target::Wasm32 => unreachable!("no LLVM IR is written for wasm32"),
+    }
+}
+
+/// The attribute extending a narrow integer to the width the ABI passes it
+/// in.
+fn extension(target: Target, ty: &CType) -> Option<&'static str> {
+    let CType::Int(int) = ty else {
+        return None;
+    };
+    match int.rank {
+        IntRank::Bool => Some("zeroext"),
+        IntRank::Char | IntRank::Short if int.signed => Some("signext"),
+        IntRank::Char | IntRank::Short => Some("zeroext"),
+        // 32-bit integers live sign-extended in 64-bit registers, whatever
+        // their signedness.
+        IntRank::Int if target == Target::Riscv64 => Some("signext"),
+        _ => None,
+    }
+}
+
+//----------- x86-64 -----------------------------------------------------------
+
+/// The number of integer argument registers.
+const X86_64_INTS: usize = 6;
+
+/// The number of SSE argument registers.
+const X86_64_FLOATS: usize = 8;
+
+/// Lower the type of a call for the System V ABI.
+fn x86_64(types: &TypeTable, ty: &FnType) -> Signature {
+    let (mut ints, mut floats) = (0, 0);
+    let ret = match Ty::of(&ty.ret, MODEL) {
+        Some(mty) => {
+            Some(Pass::Scalar(mty, extension(Target::X86_64, &ty.ret)))
+        }
+        None if ty.ret == CType::Void => None,
+        None => Some(match eightbytes(types, &ty.ret) {
+            Some(parts) => Pass::Parts(parts),
+            None => {
+                ints = 1;
+                Pass::Indirect { byval: false }
+            }
+        }),
+    };
+
+    let params = ty.params.iter().map(|param| {
+        if let Some(mty) = Ty::of(param, MODEL) {
+            if mty.is_float() {
+                floats += 1;
+            } else {
+                ints += 1;
+            }
+            return Pass::Scalar(mty, extension(Target::X86_64, param));
+        }
+        // An aggregate goes on the stack as a whole unless all of its
+        // eightbytes fit in registers.
+        if let Some(parts) = eightbytes(types, param) {
+            let float = parts.iter().filter(|(_, ty)| ty.is_float()).count();
+            let int = parts.len() - float;
+            if ints + int <= X86_64_INTS && floats + float <= X86_64_FLOATS {
+                ints += int;
+                floats += float;
+                return Pass::Parts(parts);
+            }
+        }
+        Pass::Indirect { byval: true }
+    });
+    let params = params.collect();
+    Signature { ret, params }
+}
+
+/// The eightbytes of an aggregate passed in registers, or `None` if it is
+/// passed in memory.
+///
+/// An eightbyte only holding floats goes in an SSE register.
+fn eightbytes(types: &TypeTable, ty: &CType) -> Option<Vec<(u64, Ty)>> {
+    let size = types.size_of(ty);
+    if size > 16 {
+        return None;
+    }
+    let mut floats = vec![true; size.div_ceil(8) as usize];
+    scalars(types, ty, 0, &mut |offset, ty| {
+        if !ty.is_float() {
+            floats[(offset / 8) as usize] = false;
+        }
+    });
+    let parts = floats.into_iter().enumerate().map(|(i, float)| {
+        let offset = i as u64 * 8;
+        let ty = match float {
+            false => Ty::I64,
+            true if size - offset <= 4 => Ty::F32,
+            true => Ty::F64,
+        };
+        (offset, ty)
+    });
+    Some(parts.collect())
+}
+
+//----------- RISC-V -----------------------------------------------------------
+
+/// The number of argument registers of each class.
+const RISCV64_REGS: usize = 8;
+
+/// The number of result registers of each class.
+const RISCV64_RET_REGS: usize = 2;
+
+/// The registers of each class used so far.
+#[derive(Default)]
+struct Used {
+    /// The number of integer registers used.
+    ints: usize,
+
+    /// The number of floating-point registers used.
+    floats: usize,
+}
+
+/// Lower the type of a call for the LP64D ABI.
+fn riscv64(types: &TypeTable, ty: &FnType, fixed: usize) -> Signature {
+    let mut used = Used::default();
+    let ret = match Ty::of(&ty.ret, MODEL) {
+        Some(mty) => {
+            Some(Pass::Scalar(mty, extension(Target::Riscv64, &ty.ret)))
+        }
+        None if ty.ret == CType::Void => None,
+        None if types.size_of(&ty.ret) > 16 => {
+            used.ints = 1;
+            Some(Pass::Indirect { byval: false })
+        }
+        None => Some(Pass::Parts(riscv64_parts(
+            types,
+            &ty.ret,
+            &mut Used::default(),
+            RISCV64_RET_REGS,
+            false,
+        ))),
+    };
+
+    let params = ty.params.iter().enumerate().map(|(i, param)| {
+        // Variadic arguments follow the integer convention.
+        let variadic = i >= fixed;
+        if let Some(mty) = Ty::of(param, MODEL) {
+            if mty.is_float() && !variadic && used.floats < RISCV64_REGS {
+                used.floats += 1;
+            } else {
+                used.ints += 1;
+            }
+            return Pass::Scalar(mty, extension(Target::Riscv64, param));
+        }
+        if types.size_of(param) > 16 {
+            used.ints += 1;
+            return Pass::Indirect { byval: false };
+        }
+        Pass::Parts(riscv64_parts(
+            types,
+            param,
+            &mut used,
+            RISCV64_REGS,
+            variadic,
+        ))
+    });
+    let params = params.collect();
+    Signature { ret, params }
+}
+
+/// The parts of an aggregate of up to 16 bytes, given the registers used
+/// and the number of registers of each class.
+///
+/// One float, or two scalars of which one is a float, go in one register
+/// each if there are enough left; other aggregates go in integer registers
+/// or on the stack, an eightbyte at a time.
+fn riscv64_parts(
+    types: &TypeTable,
+    ty: &CType,
+    used: &mut Used,
+    regs: usize,
+    variadic: bool,
+) -> Vec<(u64, Ty)> {
+    let mut fields = Vec::new();
+    scalars(types, ty, 0, &mut |offset, ty| fields.push((offset, ty)));
+    let floats = fields.iter().filter(|(_, ty)| ty.is_float()).count();
+    let ints = fields.len() - floats;
+    if !variadic
+        && floats > 0
+        && fields.len() <= 2
+        && used.floats + floats <= regs
+        && used.ints + ints <= regs
+    {
+        used.floats += floats;
+        used.ints += ints;
+        return fields;
+    }
+
+    let eightbytes = types.size_of(ty).div_ceil(8);
+    used.ints += eightbytes as usize;
+    (0..eightbytes).map(|i| (i * 8, Ty::I64)).collect()
+}
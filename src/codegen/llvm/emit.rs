@@ -0,0 +1,778 @@
+//! Translation of functions.
+//!
+//! Every local is named after its number: `%lN` is the value of a register
+//! local, or the address of a memory local.  Copies are not translated;
+//! their uses refer to the copied value instead.  The allocas and the
+//! unpacking of aggregate parameters go in an entry block of their own,
+//! which jumps to the first block of the function.
+
+use super::*;
+use crate::mir::{
+    BinOp, BlockId, CastOp, Const, Function, Inst, Local, Operand, Rvalue,
+    Terminator, UnaOp,
+};
+
+/// The state of the translation of a function.
+struct Emitter<'a> {
+    /// The module-wide context.
+    ctx: &'a Context<'a>,
+
+    /// The function being translated.
+    func: &'a Function,
+
+    /// How the function is called.
+    sig: Signature,
+
+    /// The allocas, at the start of the entry block.
+    allocas: String,
+
+    /// The translated blocks, after the allocas.
+    body: String,
+
+    /// The number of temporaries used.
+    temps: u32,
+
+    /// The operand each local assigned by a copy stands for.
+    copies: Vec<Option<&'a Operand>>,
+}
+
+/// Write out the definition of a function.
+pub fn function(out: &mut String, ctx: &Context, func: &Function) {
+    let mut emitter = Emitter {
+        ctx,
+        func,
+        sig: ctx.signature(&func.ty, func.ty.params.len()),
+        allocas: String::new(),
+        body: String::new(),
+        temps: 0,
+        copies: vec![None; func.locals.len()],
+    };
+    for block in &func.blocks {
+        for inst in &block.insts {
+            if let Inst::Assign(dest, rvalue) = inst {
+                emitter.copies[dest.0 as usize] = emitter.copy(*dest, rvalue);
+            }
+        }
+    }
+
+    let header = emitter.params();
+    for id in func.block_ids() {
+        emitter.block(id);
+    }
+
+    let linkage = if func.local { "internal " } else { "" };
+    writeln!(
+        out,
+        "define {linkage}{} @{}({}){} {{",
+        ret(&emitter.sig),
+        func.name,
+        header,
+        ctx.attributes()
+    )
+    .unwrap();
+    writeln!(out, "entry:").unwrap();
+    out.push_str(&emitter.allocas);
+    out.push_str(&emitter.body);
+    writeln!(out, "}}").unwrap();
+}
+
+impl<'a> Emitter<'a> {
+    /// The operand a local assigned by an rvalue stands for, if the rvalue
+    /// is a copy.
+    fn copy(&self, dest: Local, rvalue: &'a Rvalue) -> Option<&'a Operand> {
+        match rvalue {
+            Rvalue::Use(operand) => Some(operand),
+            Rvalue::Cast(_, operand)
+                if self.func.operand_ty(operand) == self.func.ty(dest) =>
+            {
+                Some(operand)
+            }
+            _ => None,
+        }
+    }
+
+    /// Add an instruction.
+    fn line(&mut self, inst: String) {
+        writeln!(self.body, "  {inst}").unwrap();
+    }
+
+    /// A new temporary.
+    fn temp(&mut self) -> String {
+        self.temps += 1;
+        format!("%t{}", self.temps)
+    }
+
+    /// A new stack slot of an LLVM type.
+    fn alloca(&mut self, ty: &str, align: u64) -> String {
+        let temp = self.temp();
+        writeln!(self.allocas, "  {temp} = alloca {ty}, align {align}")
+            .unwrap();
+        temp
+    }
+
+    /// The value of an operand.
+    fn value(&self, operand: &Operand) -> String {
+        match operand {
+            Operand::Local(local) => match self.copies[local.0 as usize] {
+                Some(operand) => self.value(operand),
+                None => format!("%l{}", local.0),
+            },
+            Operand::Const(c) => match c {
+                Const::Int(value, ty) => int(*value, *ty),
+                Const::Float(value, ty) => float(*value, *ty),
+                Const::Symbol(name) => format!("@{name}"),
+            },
+            Operand::Addr(local) => format!("%l{}", local.0),
+        }
+    }
+
+    /// The value of an operand used as a machine type, which only differs
+    /// from its own for integer constants.
+    fn value_as(&self, operand: &Operand, ty: Ty) -> String {
+        match operand {
+            Operand::Const(Const::Int(value, _)) => int(*value, ty),
+            operand => self.value(operand),
+        }
+    }
+
+    /// An operand with its type.
+    fn typed(&self, operand: &Operand) -> String {
+        let ty = self.func.operand_ty(operand);
+        format!("{} {}", scalar(ty), self.value(operand))
+    }
+}
+
+//----------- Aggregates -------------------------------------------------------
+
+impl Emitter<'_> {
+    /// The address of a byte offset from an address.
+    fn offset(&mut self, addr: &str, offset: u64) -> String {
+        if offset == 0 {
+            return addr.to_string();
+        }
+        let temp = self.temp();
+        self.line(format!(
+            "{temp} = getelementptr inbounds i8, ptr {addr}, i64 {offset}"
+        ));
+        temp
+    }
+
+    /// Copy an aggregate between addresses.
+    fn memcpy(&mut self, dst: &str, src: &str, ty: &CType) {
+        self.ctx.memcpy.set(true);
+        let size = self.ctx.types.size_of(ty);
+        let align = self.ctx.align(ty);
+        self.line(format!(
+            "call void @llvm.memcpy.p0.p0.i64(ptr align {align} {dst}, \
+             ptr align {align} {src}, i64 {size}, i1 false)"
+        ));
+    }
+
+    /// A stack slot for an aggregate that its parts may not fit in, if the
+    /// last one extends past its end.
+    fn coerce(&mut self, ty: &CType, parts: &[(u64, Ty)]) -> Option<String> {
+        let size = self.ctx.types.size_of(ty);
+        let end = parts
+            .iter()
+            .map(|(offset, ty)| offset + ty.size(MODEL))
+            .max();
+        let end = end.filter(|&end| end > size)?;
+        Some(self.alloca(&format!("[{} x i64]", end.div_ceil(8)), 8))
+    }
+
+    /// Load the parts of an aggregate.
+    fn load_parts(
+        &mut self,
+        addr: &str,
+        ty: &CType,
+        parts: &[(u64, Ty)],
+    ) -> Vec<String> {
+        let mut align = self.ctx.align(ty);
+        let addr = match self.coerce(ty, parts) {
+            Some(temp) => {
+                self.memcpy(&temp, addr, ty);
+                align = 8;
+                temp
+            }
+            None => addr.to_string(),
+        };
+        let parts = parts.iter().map(|&(offset, part)| {
+            let ptr = self.offset(&addr, offset);
+            let temp = self.temp();
+            let align = align.min(part.size(MODEL));
+            let part = scalar(part);
+            self.line(format!(
+                "{temp} = load {part}, ptr {ptr}, align {align}"
+            ));
+            temp
+        });
+        parts.collect()
+    }
+
+    /// Store the parts of an aggregate.
+    fn store_parts(
+        &mut self,
+        addr: &str,
+        ty: &CType,
+        parts: &[(u64, Ty)],
+        values: &[String],
+    ) {
+        let temp = self.coerce(ty, parts);
+        let align = if temp.is_some() {
+            8
+        } else {
+            self.ctx.align(ty)
+        };
+        let base = temp.as_deref().unwrap_or(addr);
+        for (&(offset, part), value) in parts.iter().zip(values) {
+            let ptr = self.offset(base, offset);
+            let align = align.min(part.size(MODEL));
+            let part = scalar(part);
+            self.line(format!(
+                "store {part} {value}, ptr {ptr}, align {align}"
+            ));
+        }
+        if let Some(temp) = temp {
+            self.memcpy(addr, &temp, ty);
+        }
+    }
+}
+
+//----------- Parameters -------------------------------------------------------
+
+impl Emitter<'_> {
+    /// Name the parameters and unpack those passed in parts, returning the
+    /// parameter list of the definition.
+    ///
+    /// Aggregates passed by reference are used where they are, as they are
+    /// copies already.
+    fn params(&mut self) -> String {
+        let func = self.func;
+        let mut by_ref = vec![false; func.locals.len()];
+        let mut header = Vec::new();
+        let mut parts = Vec::new();
+        for param in self.ctx.params(&self.sig, &func.ty) {
+            let name = match param.index {
+                None => "%sret".to_string(),
+                Some(index) => {
+                    let local = func.params[index];
+                    match &self.sig.params[index] {
+                        Pass::Scalar(..) => format!("%l{}", local.0),
+                        Pass::Parts(_) => {
+                            let prefix = format!("%l{}.", local.0);
+                            let part = parts
+                                .iter()
+                                .filter(|name: &&String| {
+                                    name.starts_with(&prefix)
+                                })
+                                .count();
+                            parts.push(format!("{prefix}{part}"));
+                            parts.last().unwrap().clone()
+                        }
+                        Pass::Indirect { .. } => {
+                            by_ref[local.0 as usize] = true;
+                            format!("%l{}", local.0)
+                        }
+                    }
+                }
+            };
+            header.push(format!("{param} {name}"));
+        }
+        if func.ty.variadic {
+            header.push("...".to_string());
+        }
+
+        for (i, decl) in func.locals.iter().enumerate() {
+            if decl.memory && !by_ref[i] {
+                let ty = self.ctx.ty(&decl.ty);
+                let align = self.ctx.align(&decl.ty);
+                writeln!(self.allocas, "  %l{i} = alloca {ty}, align {align}")
+                    .unwrap();
+            }
+        }
+        let mut values = parts.into_iter();
+        for (index, &local) in func.params.iter().enumerate() {
+            if let Pass::Parts(parts) = &self.sig.params[index] {
+                let values: Vec<_> =
+                    values.by_ref().take(parts.len()).collect();
+                let addr = format!("%l{}", local.0);
+                let parts = parts.clone();
+                self.store_parts(
+                    &addr,
+                    &func.ty.params[index],
+                    &parts,
+                    &values,
+                );
+            }
+        }
+        self.line("br label %bb0".to_string());
+        header.join(", ")
+    }
+}
+
+//----------- Instructions -----------------------------------------------------
+
+impl Emitter<'_> {
+    /// Translate a block.
+    fn block(&mut self, id: BlockId) {
+        let block = self.func.block(id);
+        writeln!(self.body, "{id}:").unwrap();
+        for inst in &block.insts {
+            self.inst(id, inst);
+        }
+        self.term(&block.term);
+    }
+
+    /// Translate an instruction.
+    fn inst(&mut self, block: BlockId, inst: &Inst) {
+        match inst {
+            Inst::Assign(dest, _) if self.copies[dest.0 as usize].is_some() => {
+            }
+            Inst::Assign(dest, rvalue) => self.assign(block, *dest, rvalue),
+            Inst::Store { addr, value } => {
+                let ty = self.func.operand_ty(value);
+                self.line(format!(
+                    "store {}, ptr {}, align {}",
+                    self.typed(value),
+                    self.value(addr),
+                    ty.size(MODEL)
+                ));
+            }
+            Inst::Call {
+                dest,
+                callee,
+                args,
+                sig,
+                fixed,
+            } => self.call(*dest, callee, args, sig, *fixed),
+            Inst::VaStart(addr) => {
+                self.ctx.va_start.set(true);
+                let addr = self.value(addr);
+                self.line(format!("call void @llvm.va_start(ptr {addr})"));
+            }
+        }
+    }
+
+    /// Translate an assignment to a register.
+    fn assign(&mut self, block: BlockId, dest: Local, rvalue: &Rvalue) {
+        let ty = self.func.ty(dest);
+        let dest = format!("%l{}", dest.0);
+        match rvalue {
+            Rvalue::Use(_) => unreachable!("copies are not translated"),
+            Rvalue::Bin(op, a, b) => self.bin(&dest, ty, *op, a, b),
+            Rvalue::Una(op, a) => {
+                let a = self.value(a);
+                let ty = scalar(ty);
+                self.line(match op {
+                    UnaOp::Neg => format!("{dest} = sub {ty} 0, {a}"),
+                    UnaOp::Not => format!("{dest} = xor {ty} {a}, -1"),
+                    UnaOp::FNeg => format!("{dest} = fneg {ty} {a}"),
+                });
+            }
+            Rvalue::Cast(op, a) => {
+                let from = self.func.operand_ty(a);
+                let widen = from.size(MODEL) < ty.size(MODEL);
+                let resize = |grow, shrink| if widen { grow } else { shrink };
+                let name = match op {
+                    CastOp::SExt => resize("sext", "trunc"),
+                    CastOp::ZExt | CastOp::Trunc => resize("zext", "trunc"),
+                    CastOp::FToS => "fptosi",
+                    CastOp::FToU => "fptoui",
+                    CastOp::SToF => "sitofp",
+                    CastOp::UToF => "uitofp",
+                    CastOp::FConv => resize("fpext", "fptrunc"),
+                    CastOp::PtrToInt => "ptrtoint",
+                    CastOp::IntToPtr => "inttoptr",
+                };
+                self.line(format!(
+                    "{dest} = {name} {} to {}",
+                    self.typed(a),
+                    scalar(ty)
+                ));
+            }
+            Rvalue::Load(addr) => self.line(format!(
+                "{dest} = load {}, ptr {}, align {}",
+                scalar(ty),
+                self.value(addr),
+                ty.size(MODEL)
+            )),
+            Rvalue::FieldAddr(base, id, index) => self.line(format!(
+                "{dest} = getelementptr inbounds {}, ptr {}, i32 0, i32 {index}",
+                self.ctx.names[id.0],
+                self.value(base)
+            )),
+            Rvalue::ElemAddr(base, index, elem) => self.line(format!(
+                "{dest} = getelementptr inbounds {}, ptr {}, i64 {}",
+                self.ctx.ty(elem),
+                self.value(base),
+                self.value_as(index, Ty::I64)
+            )),
+            Rvalue::Phi(args) => {
+                let mut incoming = Vec::new();
+                // The entry block jumps to the first one.
+                if block == BlockId(0) {
+                    incoming.push("[ undef, %entry ]".to_string());
+                }
+                let mut seen = Vec::new();
+                for (pred, value) in args {
+                    if seen.contains(pred) {
+                        continue;
+                    }
+                    seen.push(*pred);
+                    // LLVM wants an incoming value for every edge, even
+                    // several from the same block.
+                    let term = &self.func.block(*pred).term;
+                    let edges =
+                        edges(term).into_iter().filter(|&t| t == block).count();
+                    let value = self.value_as(value, ty);
+                    for _ in 0..edges {
+                        incoming.push(format!("[ {value}, %{pred} ]"));
+                    }
+                }
+                self.line(format!(
+                    "{dest} = phi {} {}",
+                    scalar(ty),
+                    incoming.join(", ")
+                ));
+            }
+        }
+    }
+
+    /// Translate a binary operation.
+    fn bin(&mut self, dest: &str, ty: Ty, op: BinOp, a: &Operand, b: &Operand) {
+        use BinOp::*;
+        let a_ty = self.func.operand_ty(a);
+        let b_ty = self.func.operand_ty(b);
+        let name = match op {
+            Add | Sub if a_ty == Ty::Ptr || b_ty == Ty::Ptr => {
+                self.ptr_arith(dest, ty, op, a, b);
+                return;
+            }
+            Add => "add",
+            Sub => "sub",
+            Mul => "mul",
+            SDiv => "sdiv",
+            UDiv => "udiv",
+            SRem => "srem",
+            URem => "urem",
+            Shl => "shl",
+            LShr => "lshr",
+            AShr => "ashr",
+            And => "and",
+            Or => "or",
+            Xor => "xor",
+            FAdd => "fadd",
+            FSub => "fsub",
+            FMul => "fmul",
+            FDiv => "fdiv",
+            Eq => "icmp eq",
+            Ne => "icmp ne",
+            SLt => "icmp slt",
+            SLe => "icmp sle",
+            SGt => "icmp sgt",
+            SGe => "icmp sge",
+            ULt => "icmp ult",
+            ULe => "icmp ule",
+            UGt => "icmp ugt",
+            UGe => "icmp uge",
+            FEq => "fcmp oeq",
+            FNe => "fcmp une",
+            FLt => "fcmp olt",
+            FLe => "fcmp ole",
+            FGt => "fcmp ogt",
+            FGe => "fcmp oge",
+        };
+
+        // Comparisons are of the type of their operands, of which constants
+        // may claim another; other operations are of the type of their
+        // result, and only shift amounts may be of another width.
+        let compare = name.starts_with("icmp") || name.starts_with("fcmp");
+        let op_ty = match a {
+            _ if !compare => ty,
+            Operand::Const(Const::Int(..)) => b_ty,
+            _ => a_ty,
+        };
+        let x = self.value_as(a, op_ty);
+        let mut y = self.value_as(b, op_ty);
+        if b_ty != op_ty && !matches!(b, Operand::Const(_)) {
+            y = self.resize(&y, b_ty, op_ty, false);
+        }
+        if compare {
+            let temp = self.temp();
+            self.line(format!("{temp} = {name} {} {x}, {y}", scalar(op_ty)));
+            self.line(format!("{dest} = zext i1 {temp} to {}", scalar(ty)));
+        } else {
+            self.line(format!("{dest} = {name} {} {x}, {y}", scalar(ty)));
+        }
+    }
+
+    /// Translate an addition to or subtraction from a pointer, which is of
+    /// bytes, or the difference of two pointers.
+    fn ptr_arith(
+        &mut self,
+        dest: &str,
+        ty: Ty,
+        op: BinOp,
+        a: &Operand,
+        b: &Operand,
+    ) {
+        let (a_ty, b_ty) = (self.func.operand_ty(a), self.func.operand_ty(b));
+        if ty != Ty::Ptr {
+            let (x, y) = (self.temp(), self.temp());
+            self.line(format!("{x} = ptrtoint {} to i64", self.typed(a)));
+            self.line(format!("{y} = ptrtoint {} to i64", self.typed(b)));
+            self.line(format!("{dest} = sub i64 {x}, {y}"));
+            return;
+        }
+        let (ptr, (operand, offset_ty)) = match a_ty {
+            Ty::Ptr => (a, (b, b_ty)),
+            _ => (b, (a, a_ty)),
+        };
+        let mut offset = self.value_as(operand, Ty::I64);
+        if offset_ty != Ty::I64 && !matches!(operand, Operand::Const(_)) {
+            offset = self.resize(&offset, offset_ty, Ty::I64, true);
+        }
+        if op == BinOp::Sub {
+            let temp = self.temp();
+            self.line(format!("{temp} = sub i64 0, {offset}"));
+            offset = temp;
+        }
+        self.line(format!(
+            "{dest} = getelementptr i8, ptr {}, i64 {offset}",
+            self.value(ptr)
+        ));
+    }
+
+    /// Convert an integer value to another width.
+    fn resize(
+        &mut self,
+        value: &str,
+        from: Ty,
+        to: Ty,
+        signed: bool,
+    ) -> String {
+        let name = match from.size(MODEL) < to.size(MODEL) {
+            true if signed => "sext",
+            true => "zext",
+            false => "trunc",
+        };
+        let temp = self.temp();
+        self.line(format!(
+            "{temp} = {name} {} {value} to {}",
+            scalar(from),
+            scalar(to)
+        ));
+        temp
+    }
+
+    /// Translate a call.
+    fn call(
+        &mut self,
+        dest: Option<Local>,
+        callee: &Operand,
+        args: &[Operand],
+        ty: &FnType,
+        fixed: usize,
+    ) {
+        let sig = self.ctx.signature(ty, fixed);
+        let params = self.ctx.params(&sig, ty);
+
+        // The result is written to the destination, or to a slot of its own
+        // if there is none.
+        let dest_addr = match (&sig.ret, dest) {
+            (Some(Pass::Indirect { .. }), Some(dest)) => {
+                Some(format!("%l{}", dest.0))
+            }
+            (Some(Pass::Indirect { .. }), None) => {
+                let align = self.ctx.align(&ty.ret);
+                Some(self.alloca(&self.ctx.ty(&ty.ret), align))
+            }
+            _ => None,
+        };
+
+        let mut values = Vec::new();
+        if let Some(addr) = &dest_addr {
+            values.push(addr.clone());
+        }
+        for ((arg, pass), param) in args.iter().zip(&sig.params).zip(&ty.params)
+        {
+            match pass {
+                Pass::Scalar(..) => values.push(self.value(arg)),
+                Pass::Parts(parts) => {
+                    let addr = self.value(arg);
+                    values.extend(self.load_parts(&addr, param, parts));
+                }
+                Pass::Indirect { byval: true } => values.push(self.value(arg)),
+                Pass::Indirect { byval: false } => {
+                    let copy =
+                        self.alloca(&self.ctx.ty(param), self.ctx.align(param));
+                    self.memcpy(&copy, &self.value(arg), param);
+                    values.push(copy);
+                }
+            }
+        }
+        let args: Vec<_> = params
+            .iter()
+            .zip(&values)
+            .map(|(param, value)| format!("{param} {value}"))
+            .collect();
+
+        // Variadic calls spell out the type of the callee.
+        let mut ret_ty = ret(&sig);
+        if ty.variadic {
+            let fixed = params
+                .iter()
+                .filter(|param| param.index.is_none_or(|index| index < fixed))
+                .map(|param| param.ty.as_str());
+            let fixed: Vec<_> = fixed.chain(["..."]).collect();
+            ret_ty = format!("{ret_ty} ({})", fixed.join(", "));
+        }
+        let call = format!(
+            "call {ret_ty} {}({})",
+            self.value(callee),
+            args.join(", ")
+        );
+
+        match (&sig.ret, dest) {
+            (Some(Pass::Scalar(..)), Some(dest)) => {
+                self.line(format!("%l{} = {call}", dest.0))
+            }
+            (Some(Pass::Parts(parts)), Some(dest)) => {
+                let result = self.temp();
+                self.line(format!("{result} = {call}"));
+                let values = match parts.len() {
+                    1 => vec![result],
+                    n => (0..n)
+                        .map(|i| {
+                            let temp = self.temp();
+                            self.line(format!(
+                                "{temp} = extractvalue {} {result}, {i}",
+                                parts_ty(parts)
+                            ));
+                            temp
+                        })
+                        .collect(),
+                };
+                let addr = format!("%l{}", dest.0);
+                self.store_parts(&addr, &ty.ret, parts, &values);
+            }
+            _ => self.line(call),
+        }
+    }
+
+    /// Translate a terminator.
+    fn term(&mut self, term: &Terminator) {
+        match term {
+            Terminator::Jump(target) => {
+                self.line(format!("br label %{target}"))
+            }
+            Terminator::Branch(_, then, r#else) if then == r#else => {
+                self.line(format!("br label %{then}"))
+            }
+            Terminator::Branch(cond, then, r#else) => {
+                let ty = self.func.operand_ty(cond);
+                let test = self.temp();
+                let value = self.value(cond);
+                self.line(match ty {
+                    Ty::F32 | Ty::F64 => {
+                        format!("{test} = fcmp une {} {value}, 0.0", scalar(ty))
+                    }
+                    Ty::Ptr => format!("{test} = icmp ne ptr {value}, null"),
+                    _ => format!("{test} = icmp ne {} {value}, 0", scalar(ty)),
+                });
+                self.line(format!(
+                    "br i1 {test}, label %{then}, label %{}",
+                    r#else
+                ));
+            }
+            Terminator::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                let ty = self.func.operand_ty(value);
+                let mut inst =
+                    format!("switch {}, label %{default} [", self.typed(value));
+                for (case, target) in cases {
+                    if target != default {
+                        let case = int(*case, ty);
+                        write!(
+                            inst,
+                            "\n    {} {case}, label %{target}",
+                            scalar(ty)
+                        )
+                        .unwrap();
+                    }
+                }
+                inst.push_str("\n  ]");
+                self.line(inst);
+            }
+            Terminator::Return(value) => self.ret(value.as_ref()),
+            Terminator::Unreachable => self.line("unreachable".to_string()),
+        }
+    }
+
+    /// Translate a return.
+    fn ret(&mut self, value: Option<&Operand>) {
+        let ty = &self.func.ty.ret;
+        match (&self.sig.ret, value) {
+            (None | Some(Pass::Indirect { .. }), None) => {
+                self.line("ret void".to_string())
+            }
+            (Some(Pass::Indirect { .. }), Some(addr)) => {
+                let addr = self.value(addr);
+                self.memcpy("%sret", &addr, ty);
+                self.line("ret void".to_string());
+            }
+            (Some(Pass::Scalar(mty, _)), Some(value)) => {
+                let value = self.value_as(value, *mty);
+                self.line(format!("ret {} {value}", scalar(*mty)));
+            }
+            (Some(Pass::Parts(parts)), Some(addr)) => {
+                let parts = parts.clone();
+                let addr = self.value(addr);
+                let values = self.load_parts(&addr, ty, &parts);
+                let parts_ty = parts_ty(&parts);
+                let mut result = "undef".to_string();
+                if let [value] = &values[..] {
+                    result = value.clone();
+                } else {
+                    for (i, value) in values.iter().enumerate() {
+                        let temp = self.temp();
+                        let part = scalar(parts[i].1);
+                        self.line(format!(
+                            "{temp} = insertvalue {parts_ty} {result}, \
+                             {part} {value}, {i}"
+                        ));
+                        result = temp;
+                    }
+                }
+                self.line(format!("ret {parts_ty} {result}"));
+            }
+            // Falling off the end of a function returning a value.
+            (Some(Pass::Scalar(mty, _)), None) => {
+                self.line(format!("ret {} undef", scalar(*mty)))
+            }
+            (Some(Pass::Parts(parts)), None) => {
+                self.line(format!("ret {} undef", parts_ty(parts)))
+            }
+            (None, Some(_)) => {
+                unreachable!("value returned from void function")
+            }
+        }
+    }
+}
+
+/// The blocks a terminator jumps to, once for every edge of its
+/// translation.
+fn edges(term: &Terminator) -> Vec<BlockId> {
+    match term {
+        Terminator::Branch(_, then, r#else) if then == r#else => vec![*then],
+        Terminator::Switch { cases, default, .. } => {
+            let cases = cases.iter().map(|(_, target)| *target);
+            let cases = cases.filter(|target| target != default);
+            std::iter::once(*default).chain(cases).collect()
+        }
+        term => term.successors(),
+    }
+}
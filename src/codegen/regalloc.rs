@@ -0,0 +1,336 @@
+//! Register allocation.
+//!
+//! The allocators map the virtual registers of a function to machine
+//! registers and stack slots:
+//!
+//! - The naive allocator keeps every virtual register on the stack.  It is
+//!   simple and always succeeds, at the price of a memory access for every
+//!   operand.
+//!
+//! - The linear-scan allocator ([`linear`]) walks the live intervals of the
+//!   virtual registers in order, splitting them where registers run out, so
+//!   that a value may live in different places over its lifetime.
+//!
+//! - The graph-coloring allocator ([`graph`]) colors the interference graph
+//!   of the function after coalescing moves, keeping every value in one place.
+//!
+//! Whatever the allocator, a virtual register left on the stack at an
+//! instruction is accessed through the scratch registers of the target: it
+//! is loaded into one just before the instruction if it is read, and stored
+//! just after if it is written.  Values moving between locations at block
+//! boundaries and split points are moved by inserted instructions, and moves
+//! between the same registers are removed.
+//!
+//! Instructions are numbered in the order of the blocks.  An instruction `n`
+//! reads its operands at position `2n` and writes its results at `2n + 1`.
+
+use std::collections::HashMap;
+
+use super::{Class, MachBlock, MachFunction, MachInst, Reg, Slot};
+
+mod graph;
+mod linear;
+mod liveness;
+
+/// A register allocator.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Allocator {
+    /// Keep every virtual register on the stack.
+    Naive,
+
+    /// Linear scan, with live-range splitting.
+    #[default]
+    Linear,
+
+    /// Graph coloring, with move coalescing.
+    Graph,
+}
+
+/// Where a virtual register lives.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Loc {
+    /// A machine register.
+    Reg(u8),
+
+    /// The stack slot of the virtual register.
+    Stack,
+}
+
+/// The result of an allocator.
+#[derive(Default)]
+struct Allocation {
+    /// The locations of each virtual register, as the positions from which
+    /// they hold, in order.
+    locs: Vec<Vec<(u32, Loc)>>,
+
+    /// The virtual registers whose location changes just before an
+    /// instruction in the middle of a block, by instruction number.
+    splits: HashMap<usize, Vec<u32>>,
+}
+
+impl Allocation {
+    /// The location of a virtual register at a position.
+    fn loc(&self, vreg: u32, pos: u32) -> Loc {
+        let locs = &self.locs[vreg as usize];
+        let index = locs.partition_point(|&(from, _)| from <= pos);
+        locs[index.saturating_sub(1)].1
+    }
+}
+
+/// A location a value is moved between.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Place {
+    /// A machine register.
+    Reg(u8),
+
+    /// A stack slot.
+    Slot(Slot),
+}
+
+/// A move of a value of a class, to a place from another.
+type Move = (Place, Place, Class);
+
+/// Allocate the registers of a function.
+pub fn allocate<I: MachInst>(func: &mut MachFunction<I>, allocator: Allocator) {
+    // The number of the first instruction of every block, and the end.
+    let mut starts = vec![0];
+    for block in &func.blocks {
+        starts.push(starts.last().unwrap() + block.insts.len());
+    }
+
+    let (alloc, liveness) = match allocator {
+        Allocator::Naive => {
+            let alloc = Allocation {
+                locs: vec![vec![(0, Loc::Stack)]; func.vregs.len()],
+                ..Default::default()
+            };
+            (alloc, None)
+        }
+        Allocator::Linear => {
+            let liveness = liveness::analyze(func);
+            (linear::allocate(func, &liveness, &starts), Some(liveness))
+        }
+        Allocator::Graph => {
+            let liveness = liveness::analyze(func);
+            (graph::allocate(func, &liveness), Some(liveness))
+        }
+    };
+
+    let mut slots = Vec::new();
+    for locs in &alloc.locs {
+        let stack = locs.iter().any(|&(_, loc)| loc == Loc::Stack);
+        slots.push(stack.then(|| func.new_slot(8, 8)));
+    }
+    let place = |vreg: u32, loc: Loc| match loc {
+        Loc::Reg(reg) => Place::Reg(reg),
+        Loc::Stack => Place::Slot(slots[vreg as usize].unwrap()),
+    };
+
+    // The moves on the edges between blocks are found before the blocks
+    // change.
+    let mut edges = Vec::new();
+    for (pred, block) in func.blocks.iter().enumerate() {
+        let Some(liveness) = &liveness else {
+            break;
+        };
+        let end = 2 * starts[pred + 1] as u32 - 1;
+        for &succ in &block.succs {
+            let start = 2 * starts[succ] as u32;
+            let mut live: Vec<Reg> =
+                liveness.live_in[succ].iter().copied().collect();
+            live.sort();
+            let mut moves = Vec::new();
+            for reg in live {
+                let Reg::Virt(vreg) = reg else {
+                    continue;
+                };
+                let from = alloc.loc(vreg, end);
+                let to = alloc.loc(vreg, start);
+                if from != to {
+                    let class = func.vregs[vreg as usize];
+                    moves.push((place(vreg, to), place(vreg, from), class));
+                }
+            }
+            if !moves.is_empty() {
+                edges.push((pred, succ, moves));
+            }
+        }
+    }
+
+    let MachFunction { blocks, vregs, .. } = func;
+    let mut index = 0;
+    for block in blocks.iter_mut() {
+        let old = std::mem::take(&mut block.insts);
+        for mut inst in old {
+            let pos = 2 * index as u32;
+            if let Some(split) = alloc.splits.get(&index) {
+                let moves = split.iter().map(|&vreg| {
+                    let from = place(vreg, alloc.loc(vreg, pos - 1));
+                    let to = place(vreg, alloc.loc(vreg, pos));
+                    (to, from, vregs[vreg as usize])
+                });
+                parallel_move(moves.collect(), &mut block.insts);
+            }
+            index += 1;
+
+            // The virtual registers on the stack, and whether they are read
+            // and written.
+            let mut stacked: Vec<(u32, bool, bool)> = Vec::new();
+            for (reg, role) in inst.regs() {
+                let Reg::Virt(vreg) = reg else {
+                    continue;
+                };
+                let at = if role.is_use() { pos } else { pos + 1 };
+                if alloc.loc(vreg, at) != Loc::Stack {
+                    continue;
+                }
+                match stacked.iter_mut().find(|s| s.0 == vreg) {
+                    Some(entry) => {
+                        entry.1 |= role.is_use();
+                        entry.2 |= role.is_def();
+                    }
+                    None => stacked.push((vreg, role.is_use(), role.is_def())),
+                }
+            }
+
+            // Each gets a scratch register, those read first: the registers
+            // only written can share the scratch registers of those only
+            // read, since instructions read all their operands first.
+            let mut assigned: Vec<(u32, u8, bool, bool)> = Vec::new();
+            for reads in [true, false] {
+                for &(vreg, used, defined) in &stacked {
+                    if used != reads {
+                        continue;
+                    }
+                    let class = vregs[vreg as usize];
+                    let phys = I::scratch(class)
+                        .into_iter()
+                        .find(|&phys| {
+                            !assigned
+                                .iter()
+                                .any(|a| a.1 == phys && (reads || a.3))
+                        })
+                        .expect("too many virtual registers on the stack");
+                    assigned.push((vreg, phys, used, defined));
+                }
+            }
+            for (reg, role) in inst.regs_mut() {
+                let Reg::Virt(vreg) = *reg else {
+                    continue;
+                };
+                let at = if role.is_use() { pos } else { pos + 1 };
+                *reg = Reg::Phys(match alloc.loc(vreg, at) {
+                    Loc::Reg(phys) => phys,
+                    Loc::Stack => {
+                        assigned.iter().find(|a| a.0 == vreg).unwrap().1
+                    }
+                });
+            }
+
+            for &(vreg, phys, used, _) in &assigned {
+                if used {
+                    let class = vregs[vreg as usize];
+                    let slot = slots[vreg as usize].unwrap();
+                    block.insts.push(I::reload(phys, class, slot));
+                }
+            }
+            block.insts.push(inst);
+            for &(vreg, phys, _, defined) in &assigned {
+                if defined {
+                    let class = vregs[vreg as usize];
+                    let slot = slots[vreg as usize].unwrap();
+                    block.insts.push(I::spill(phys, class, slot));
+                }
+            }
+        }
+    }
+
+    resolve(func, edges);
+    for block in &mut func.blocks {
+        block.insts.retain(
+            |inst| !matches!(inst.as_move(), Some((dst, src)) if dst == src),
+        );
+    }
+}
+
+/// Insert the moves on the edges between blocks.
+///
+/// Moves go at the end of the predecessor if it has no other successor, at
+/// the start of the successor if it has no other predecessor, and in a new
+/// block on the edge otherwise.
+fn resolve<I: MachInst>(
+    func: &mut MachFunction<I>,
+    edges: Vec<(usize, usize, Vec<Move>)>,
+) {
+    let mut preds = vec![0; func.blocks.len()];
+    for block in &func.blocks {
+        for &succ in &block.succs {
+            preds[succ] += 1;
+        }
+    }
+
+    for (pred, succ, moves) in edges {
+        let mut insts = Vec::new();
+        parallel_move(moves, &mut insts);
+        if func.blocks[pred].succs.len() == 1 {
+            let block = &mut func.blocks[pred].insts;
+            let mut at = block.len();
+            while at > 0 && !block[at - 1].targets_mut().is_empty() {
+                at -= 1;
+            }
+            block.splice(at..at, insts);
+        } else if preds[succ] == 1 {
+            func.blocks[succ].insts.splice(0..0, insts);
+        } else {
+            let edge = func.blocks.len();
+            insts.push(I::jump(succ));
+            func.blocks.push(MachBlock {
+                insts,
+                succs: vec![succ],
+            });
+            let block = &mut func.blocks[pred];
+            let targets = block.insts.iter_mut().flat_map(I::targets_mut);
+            for target in targets.chain(&mut block.succs) {
+                if *target == succ {
+                    *target = edge;
+                }
+            }
+        }
+    }
+}
+
+/// Perform moves all at once, as if every source was read before any
+/// destination is written.
+fn parallel_move<I: MachInst>(mut moves: Vec<Move>, out: &mut Vec<I>) {
+    moves.retain(|(dst, src, _)| dst != src);
+    while !moves.is_empty() {
+        // A move can be done once no other move reads its destination.
+        let ready = (0..moves.len())
+            .find(|&i| moves.iter().all(|&(_, src, _)| src != moves[i].0));
+        let Some(ready) = ready else {
+            // The moves form cycles, which only registers can: one value is
+            // set aside in a scratch register.
+            let (_, src, class) = moves[0];
+            let Place::Reg(reg) = src else {
+                unreachable!("stack slots are not in cycles");
+            };
+            let scratch = I::scratch(class)[0];
+            out.push(I::copy(scratch, reg, class));
+            for (_, other, _) in &mut moves {
+                if *other == src {
+                    *other = Place::Reg(scratch);
+                }
+            }
+            continue;
+        };
+        let (dst, src, class) = moves.remove(ready);
+        out.push(match (dst, src) {
+            (Place::Reg(dst), Place::Reg(src)) => I::copy(dst, src, class),
+            (Place::Reg(dst), Place::Slot(slot)) => I::reload(dst, class, slot),
+            (Place::Slot(slot), Place::Reg(src)) => I::spill(src, class, slot),
+            (Place::Slot(_), Place::Slot(_)) => {
+                unreachable!("a value has a single stack slot")
+            }
+        });
+    }
+}
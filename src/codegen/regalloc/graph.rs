@@ -0,0 +1,273 @@
+//! Graph-coloring register allocation.
+//!
+//! Two registers interfere if one is written where the other is live, so
+//! that they cannot share a machine register.  The interference graph of the
+//! function is colored with the allocatable registers, following Chaitin and
+//! Briggs: nodes with fewer neighbors than there are registers are removed
+//! one by one, since they can always be colored once their neighbors are;
+//! when none is left, a node is removed optimistically, preferring those
+//! with many neighbors and few uses.  The nodes are then colored in the
+//! reverse order, and those finding no color left go to the stack.
+//!
+//! Beforehand, the two sides of a move that do not interfere are merged
+//! into one node, so that the move can be removed.  This is done
+//! conservatively, only where it cannot make the graph harder to color
+//! (Briggs' test, or George's test for machine registers).
+
+use std::collections::{HashMap, HashSet};
+
+use super::liveness::{Liveness, uses_defs};
+use super::{Allocation, Loc};
+use crate::codegen::{Class, MachFunction, MachInst, Reg};
+
+/// The interference graph of a function.
+struct Graph<'a> {
+    /// The class of every virtual register.
+    vregs: &'a [Class],
+
+    /// The neighbors of every node.
+    adj: HashMap<Reg, HashSet<Reg>>,
+
+    /// The node each merged node was merged into.
+    alias: HashMap<Reg, Reg>,
+}
+
+impl Graph<'_> {
+    /// The class of a register.
+    fn class<I: MachInst>(&self, reg: Reg) -> Class {
+        match reg {
+            Reg::Virt(vreg) => self.vregs[vreg as usize],
+            Reg::Phys(reg) if I::allocatable(Class::Int).contains(&reg) => {
+                Class::Int
+            }
+            Reg::Phys(_) => Class::Float,
+        }
+    }
+
+    /// Record that two registers interfere.
+    fn add_edge<I: MachInst>(&mut self, a: Reg, b: Reg) {
+        if a == b
+            || matches!((a, b), (Reg::Phys(_), Reg::Phys(_)))
+            || self.class::<I>(a) != self.class::<I>(b)
+        {
+            return;
+        }
+        self.adj.entry(a).or_default().insert(b);
+        self.adj.entry(b).or_default().insert(a);
+    }
+
+    /// The neighbors of a node.
+    fn neighbors(&self, reg: Reg) -> impl Iterator<Item = Reg> + '_ {
+        self.adj.get(&reg).into_iter().flatten().copied()
+    }
+
+    /// The number of neighbors of a node.
+    ///
+    /// Machine registers are taken to have more neighbors than any other.
+    fn degree(&self, reg: Reg) -> usize {
+        match reg {
+            Reg::Virt(_) => self.adj.get(&reg).map_or(0, HashSet::len),
+            Reg::Phys(_) => usize::MAX,
+        }
+    }
+
+    /// The node a register was merged into.
+    fn find(&self, mut reg: Reg) -> Reg {
+        while let Some(&alias) = self.alias.get(&reg) {
+            reg = alias;
+        }
+        reg
+    }
+
+    /// Merge a virtual register into another node.
+    fn merge(&mut self, gone: Reg, keep: Reg) {
+        let neighbors = self.adj.remove(&gone).unwrap_or_default();
+        for other in neighbors {
+            if let Some(adj) = self.adj.get_mut(&other) {
+                adj.remove(&gone);
+            }
+            if other != keep {
+                self.adj.entry(keep).or_default().insert(other);
+                self.adj.entry(other).or_default().insert(keep);
+            }
+        }
+        self.alias.insert(gone, keep);
+    }
+}
+
+/// Allocate registers by coloring the interference graph.
+pub(super) fn allocate<I: MachInst>(
+    func: &MachFunction<I>,
+    liveness: &Liveness,
+) -> Allocation {
+    let mut graph = Graph {
+        vregs: &func.vregs,
+        adj: HashMap::new(),
+        alias: HashMap::new(),
+    };
+    let mut moves = Vec::new();
+    let mut uses = vec![0usize; func.vregs.len()];
+
+    for (index, block) in func.blocks.iter().enumerate() {
+        let mut live = liveness.live_out[index].clone();
+        for inst in block.insts.iter().rev() {
+            let (reads, writes) = uses_defs(inst);
+            // The source of a move does not interfere with its destination,
+            // which holds the same value.
+            if let Some((dst, src)) = inst.as_move() {
+                live.remove(&src);
+                moves.push((dst, src));
+            }
+            for (i, &def) in writes.iter().enumerate() {
+                for &reg in live.iter().chain(&writes[i + 1..]) {
+                    graph.add_edge::<I>(def, reg);
+                }
+            }
+            for reg in &writes {
+                live.remove(reg);
+            }
+            live.extend(reads.iter().copied());
+            for reg in reads.iter().chain(&writes) {
+                if let Reg::Virt(vreg) = reg {
+                    uses[*vreg as usize] += 1;
+                }
+            }
+        }
+    }
+    for vreg in 0..func.vregs.len() as u32 {
+        graph.adj.entry(Reg::Virt(vreg)).or_default();
+    }
+
+    coalesce::<I>(&mut graph, &moves);
+    let colors = color::<I>(&graph, &uses);
+
+    let locs = (0..func.vregs.len() as u32)
+        .map(|vreg| {
+            let loc = match graph.find(Reg::Virt(vreg)) {
+                Reg::Phys(reg) => Loc::Reg(reg),
+                node => colors[&node],
+            };
+            vec![(0, loc)]
+        })
+        .collect();
+    Allocation {
+        locs,
+        ..Default::default()
+    }
+}
+
+/// Merge the two sides of moves, where it is safe.
+fn coalesce<I: MachInst>(graph: &mut Graph, moves: &[(Reg, Reg)]) {
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &(dst, src) in moves {
+            let (a, b) = (graph.find(dst), graph.find(src));
+            let (keep, gone) = match (a, b) {
+                (Reg::Phys(_), Reg::Phys(_)) => continue,
+                (Reg::Virt(_), Reg::Phys(_)) => (b, a),
+                _ => (a, b),
+            };
+            if keep == gone
+                || graph.class::<I>(keep) != graph.class::<I>(gone)
+                || graph.neighbors(gone).any(|other| other == keep)
+            {
+                continue;
+            }
+            let k = I::allocatable(graph.class::<I>(keep)).len();
+            let safe = match keep {
+                // George: every neighbor of the register merged away either
+                // already interferes with the machine register, or is easy
+                // to color.  Other machine registers differ anyway.
+                Reg::Phys(_) => graph.neighbors(gone).all(|other| {
+                    matches!(other, Reg::Phys(_))
+                        || graph.degree(other) < k
+                        || graph.neighbors(other).any(|n| n == keep)
+                }),
+                // Briggs: the merged node has fewer than `k` neighbors that
+                // are hard to color.
+                Reg::Virt(_) => {
+                    let neighbors: HashSet<Reg> = graph
+                        .neighbors(keep)
+                        .chain(graph.neighbors(gone))
+                        .collect();
+                    let hard = neighbors
+                        .iter()
+                        .filter(|&&other| graph.degree(other) >= k)
+                        .count();
+                    hard < k
+                }
+            };
+            if safe {
+                graph.merge(gone, keep);
+                changed = true;
+            }
+        }
+    }
+}
+
+/// Color the virtual registers of a graph.
+fn color<I: MachInst>(graph: &Graph, uses: &[usize]) -> HashMap<Reg, Loc> {
+    let mut remaining: Vec<Reg> = graph
+        .adj
+        .keys()
+        .copied()
+        .filter(|reg| matches!(reg, Reg::Virt(_)))
+        .collect();
+    remaining.sort();
+    let mut degree: HashMap<Reg, usize> = remaining
+        .iter()
+        .map(|&reg| (reg, graph.degree(reg)))
+        .collect();
+
+    // Remove the nodes one by one.
+    let mut stack = Vec::new();
+    while !remaining.is_empty() {
+        let k = |reg: Reg| I::allocatable(graph.class::<I>(reg)).len();
+        let easy = remaining.iter().position(|&reg| degree[&reg] < k(reg));
+        let index = easy.unwrap_or_else(|| {
+            // The cost of spilling a node is its number of uses, weighed
+            // against how much removing it helps its neighbors.
+            let cost = |reg: Reg| {
+                let Reg::Virt(vreg) = reg else { unreachable!() };
+                let uses = uses[vreg as usize] + 1;
+                uses as f64 / degree[&reg] as f64
+            };
+            (0..remaining.len())
+                .min_by(|&a, &b| {
+                    cost(remaining[a]).total_cmp(&cost(remaining[b]))
+                })
+                .unwrap()
+        });
+        let reg = remaining.remove(index);
+        for other in graph.neighbors(reg) {
+            if let Some(degree) = degree.get_mut(&other) {
+                *degree -= 1;
+            }
+        }
+        degree.remove(&reg);
+        stack.push(reg);
+    }
+
+    // Color them in reverse order.
+    let mut colors = HashMap::new();
+    for reg in stack.into_iter().rev() {
+        let taken: HashSet<u8> = graph
+            .neighbors(reg)
+            .filter_map(|other| match other {
+                Reg::Phys(phys) => Some(phys),
+                _ => match colors.get(&other) {
+                    Some(&Loc::Reg(phys)) => Some(phys),
+                    _ => None,
+                },
+            })
+            .collect();
+        let regs = I::allocatable(graph.class::<I>(reg));
+        let loc = regs
+            .iter()
+            .find(|phys| !taken.contains(phys))
+            .map_or(Loc::Stack, |&phys| Loc::Reg(phys));
+        colors.insert(reg, loc);
+    }
+    colors
+}
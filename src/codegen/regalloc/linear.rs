@@ -0,0 +1,286 @@
+//! Linear-scan register allocation.
+//!
+//! The live interval of a register is the set of positions where it is live,
+//! as a list of ranges; there are holes where the register is dead, as when
+//! a value is only live in some of the blocks laid out between its
+//! definition and its last use.
+//!
+//! The intervals of the virtual registers are visited in order of their
+//! start, and each is given a register that is free over all of it, if there
+//! is one.  Otherwise the interval is split: the part up to where some
+//! register is next taken gets that register, and the rest is visited again
+//! later, maybe ending up in another register.  Where no register is free at
+//! all, the interval stays on the stack up to its next use.  This follows
+//! Wimmer and Mössenböck, "Optimized Interval Splitting in a Linear Scan
+//! Register Allocator" (2005), without evicting intervals already allocated.
+//!
+//! Machine registers have fixed intervals, covering where they carry values
+//! and where calls clobber them.  A value live across a call thus goes to a
+//! callee-saved register, or is split around the call.  Registers are tried
+//! in the order of preference of the target, except that the two sides of a
+//! move prefer each other's register, so that the move can be removed.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use super::liveness::{Liveness, uses_defs};
+use super::{Allocation, Loc};
+use crate::codegen::{MachFunction, MachInst, Reg};
+
+/// The positions where a register is live.
+#[derive(Default)]
+struct Interval {
+    /// Disjoint half-open ranges of positions, in order.
+    ranges: Vec<(u32, u32)>,
+
+    /// The positions where the register is accessed, in order.
+    uses: Vec<u32>,
+}
+
+impl Interval {
+    /// The first position of the interval.
+    fn start(&self) -> u32 {
+        self.ranges[0].0
+    }
+
+    /// The end of the last range of the interval.
+    fn end(&self) -> u32 {
+        self.ranges.last().unwrap().1
+    }
+
+    /// Add the range from `from` to `to` while building the interval
+    /// backwards, with the earliest range last.
+    fn add(&mut self, from: u32, to: u32) {
+        match self.ranges.last_mut() {
+            Some(first) if to >= first.0 => {
+                first.0 = first.0.min(from);
+                first.1 = first.1.max(to);
+            }
+            _ => self.ranges.push((from, to)),
+        }
+    }
+
+    /// Start the interval at a definition while building it backwards.
+    fn define(&mut self, pos: u32) {
+        match self.ranges.last_mut() {
+            Some(first) if first.0 <= pos && pos < first.1 => first.0 = pos,
+            _ => self.ranges.push((pos, pos + 1)),
+        }
+    }
+
+    /// Split off the part of the interval from a position on.
+    ///
+    /// Also returns whether the register is live across the position.
+    fn split(&mut self, pos: u32) -> (Self, bool) {
+        let index = self.ranges.partition_point(|&(_, to)| to <= pos);
+        let mut ranges = self.ranges.split_off(index);
+        let across = ranges[0].0 < pos;
+        if across {
+            self.ranges.push((ranges[0].0, pos));
+            ranges[0].0 = pos;
+        }
+        let index = self.uses.partition_point(|&use_| use_ < pos);
+        let uses = self.uses.split_off(index);
+        (Self { ranges, uses }, across)
+    }
+}
+
+/// The first position in both of two lists of ranges.
+fn intersect(a: &[(u32, u32)], b: &[(u32, u32)]) -> Option<u32> {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let from = a[i].0.max(b[j].0);
+        if from < a[i].1.min(b[j].1) {
+            return Some(from);
+        }
+        if a[i].1 <= b[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    None
+}
+
+/// Build the live intervals of the registers of a function.
+fn build<I: MachInst>(
+    func: &MachFunction<I>,
+    liveness: &Liveness,
+    starts: &[usize],
+) -> HashMap<Reg, Interval> {
+    let mut intervals: HashMap<Reg, Interval> = HashMap::new();
+    for (index, block) in func.blocks.iter().enumerate().rev() {
+        let from = 2 * starts[index] as u32;
+        let to = 2 * starts[index + 1] as u32;
+        for &reg in &liveness.live_out[index] {
+            intervals.entry(reg).or_default().add(from, to);
+        }
+        for (offset, inst) in block.insts.iter().enumerate().rev() {
+            let pos = from + 2 * offset as u32;
+            let (uses, defs) = uses_defs(inst);
+            for reg in defs {
+                let interval = intervals.entry(reg).or_default();
+                interval.define(pos + 1);
+                interval.uses.push(pos + 1);
+            }
+            for reg in uses {
+                let interval = intervals.entry(reg).or_default();
+                interval.add(from, pos + 1);
+                interval.uses.push(pos);
+            }
+        }
+    }
+    for interval in intervals.values_mut() {
+        interval.ranges.reverse();
+        interval.uses.reverse();
+    }
+    intervals
+}
+
+/// A part of the interval of a virtual register, allocated as a whole.
+struct Piece {
+    /// The virtual register.
+    vreg: u32,
+
+    /// The positions covered.
+    interval: Interval,
+
+    /// Where the piece was put, once allocated.
+    loc: Option<Loc>,
+}
+
+/// Allocate registers by linear scan.
+pub(super) fn allocate<I: MachInst>(
+    func: &MachFunction<I>,
+    liveness: &Liveness,
+    starts: &[usize],
+) -> Allocation {
+    let mut fixed = HashMap::new();
+    let mut pieces = Vec::new();
+    let mut unhandled = BinaryHeap::new();
+    let mut intervals: Vec<_> =
+        build(func, liveness, starts).into_iter().collect();
+    intervals.sort_by_key(|&(reg, _)| reg);
+    for (reg, interval) in intervals {
+        match reg {
+            Reg::Virt(vreg) => {
+                unhandled.push(Reverse((interval.start(), pieces.len())));
+                pieces.push(Piece {
+                    vreg,
+                    interval,
+                    loc: None,
+                });
+            }
+            Reg::Phys(reg) => {
+                fixed.insert(reg, interval.ranges);
+            }
+        }
+    }
+
+    // The register on the other side of a move from each virtual register.
+    let mut hints = HashMap::new();
+    for inst in func.blocks.iter().flat_map(|block| &block.insts) {
+        if let Some((dst, src)) = inst.as_move() {
+            for (a, b) in [(dst, src), (src, dst)] {
+                if let Reg::Virt(vreg) = a {
+                    hints.entry(vreg).or_insert(b);
+                }
+            }
+        }
+    }
+
+    let block_starts: HashSet<u32> =
+        starts.iter().map(|&start| 2 * start as u32).collect();
+    let mut of_vreg = vec![Vec::new(); func.vregs.len()];
+    let mut taken: HashMap<u8, Vec<usize>> = HashMap::new();
+    let mut splits: HashMap<usize, Vec<u32>> = HashMap::new();
+    for (index, piece) in pieces.iter().enumerate() {
+        of_vreg[piece.vreg as usize].push(index);
+    }
+
+    while let Some(Reverse((start, index))) = unhandled.pop() {
+        let vreg = pieces[index].vreg;
+        let regs = I::allocatable(func.vregs[vreg as usize]);
+        let cur = &pieces[index].interval;
+        let end = cur.end();
+
+        // How long each register stays free.
+        let free: Vec<u32> = regs
+            .iter()
+            .map(|reg| {
+                let fixed =
+                    fixed.get(reg).and_then(|f| intersect(&cur.ranges, f));
+                let others =
+                    taken.get(reg).into_iter().flatten().filter_map(|&other| {
+                        intersect(&cur.ranges, &pieces[other].interval.ranges)
+                    });
+                others.chain(fixed).min().unwrap_or(u32::MAX)
+            })
+            .collect();
+
+        let hint = match hints.get(&vreg) {
+            Some(&Reg::Phys(reg)) => Some(reg),
+            Some(&Reg::Virt(other)) => of_vreg[other as usize]
+                .iter()
+                .rev()
+                .find_map(|&piece| match pieces[piece].loc {
+                    Some(Loc::Reg(reg)) => Some(reg),
+                    _ => None,
+                }),
+            None => None,
+        };
+        let whole = hint
+            .and_then(|hint| regs.iter().position(|&reg| reg == hint))
+            .filter(|&i| free[i] >= end)
+            .or_else(|| free.iter().position(|&until| until >= end));
+
+        let (loc, split) = if let Some(i) = whole {
+            (Loc::Reg(regs[i]), None)
+        } else {
+            // The register free for longest takes the interval up to where
+            // it is next needed; the rest is allocated later.
+            let (i, until) = free
+                .iter()
+                .enumerate()
+                .max_by_key(|&(i, &until)| (until, Reverse(i)))
+                .unwrap();
+            let at = until & !1;
+            if at > start {
+                (Loc::Reg(regs[i]), Some(at))
+            } else {
+                // No register is free: the interval waits on the stack for
+                // the next instruction using it.
+                let next = cur.uses.iter().find(|&&use_| use_ / 2 > start / 2);
+                (Loc::Stack, next.map(|&use_| use_ & !1))
+            }
+        };
+
+        if let Some(at) = split {
+            let (rest, across) = pieces[index].interval.split(at);
+            if across && !block_starts.contains(&at) {
+                splits.entry(at as usize / 2).or_default().push(vreg);
+            }
+            unhandled.push(Reverse((rest.start(), pieces.len())));
+            of_vreg[vreg as usize].push(pieces.len());
+            pieces.push(Piece {
+                vreg,
+                interval: rest,
+                loc: None,
+            });
+        }
+        pieces[index].loc = Some(loc);
+        if let Loc::Reg(reg) = loc {
+            taken.entry(reg).or_default().push(index);
+        }
+    }
+
+    let mut locs = vec![Vec::new(); func.vregs.len()];
+    for piece in &pieces {
+        let loc = piece.loc.unwrap();
+        locs[piece.vreg as usize].push((piece.interval.start(), loc));
+    }
+    for locs in &mut locs {
+        locs.sort_by_key(|&(from, _)| from);
+    }
+    Allocation { locs, splits }
+}
@@ -0,0 +1,96 @@
+//! Liveness analysis of machine registers.
+//!
+//! A register is live at a point if its value may be read later.  Both
+//! virtual registers and the allocatable machine registers are tracked, so
+//! that the allocators can keep values out of machine registers while those
+//! carry arguments, results and the like.
+
+use std::collections::HashSet;
+
+use crate::codegen::{Class, MachFunction, MachInst, Reg};
+
+/// The registers live around the blocks of a function.
+pub struct Liveness {
+    /// The registers live on entry to each block.
+    pub live_in: Vec<HashSet<Reg>>,
+
+    /// The registers live on exit from each block.
+    pub live_out: Vec<HashSet<Reg>>,
+}
+
+/// Whether a register is tracked: machine registers that are never
+/// allocated, like the stack pointer, are left out.
+pub fn tracked<I: MachInst>(reg: Reg) -> bool {
+    match reg {
+        Reg::Virt(_) => true,
+        Reg::Phys(reg) => [Class::Int, Class::Float]
+            .into_iter()
+            .any(|class| I::allocatable(class).contains(&reg)),
+    }
+}
+
+/// The tracked registers read and written by an instruction, counting the
+/// registers it clobbers as written.
+pub fn uses_defs<I: MachInst>(inst: &I) -> (Vec<Reg>, Vec<Reg>) {
+    let (mut uses, mut defs) = (Vec::new(), Vec::new());
+    for (reg, role) in inst.regs() {
+        if !tracked::<I>(reg) {
+            continue;
+        }
+        if role.is_use() && !uses.contains(&reg) {
+            uses.push(reg);
+        }
+        if role.is_def() && !defs.contains(&reg) {
+            defs.push(reg);
+        }
+    }
+    for &reg in inst.clobbers() {
+        let reg = Reg::Phys(reg);
+        if tracked::<I>(reg) && !defs.contains(&reg) {
+            defs.push(reg);
+        }
+    }
+    (uses, defs)
+}
+
+/// Find the registers live around every block of a function.
+pub fn analyze<I: MachInst>(func: &MachFunction<I>) -> Liveness {
+    let n = func.blocks.len();
+
+    // The registers each block reads before writing them, and writes.
+    let mut reads = vec![HashSet::new(); n];
+    let mut writes = vec![HashSet::new(); n];
+    for (index, block) in func.blocks.iter().enumerate() {
+        for inst in &block.insts {
+            let (uses, defs) = uses_defs(inst);
+            for reg in uses {
+                if !writes[index].contains(&reg) {
+                    reads[index].insert(reg);
+                }
+            }
+            writes[index].extend(defs);
+        }
+    }
+
+    let mut live_in: Vec<HashSet<Reg>> = vec![HashSet::new(); n];
+    let mut live_out: Vec<HashSet<Reg>> = vec![HashSet::new(); n];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in (0..n).rev() {
+            let out: HashSet<Reg> = func.blocks[index]
+                .succs
+                .iter()
+                .flat_map(|&succ| live_in[succ].iter().copied())
+                .collect();
+            let mut r#in = reads[index].clone();
+            r#in.extend(out.difference(&writes[index]));
+            if r#in.len() != live_in[index].len() {
+                changed = true;
+                live_in[index] = r#in;
+            }
+            live_out[index] = out;
+        }
+    }
+    Liveness { live_in, live_out }
+}
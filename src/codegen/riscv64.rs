@@ -0,0 +1,623 @@
+//! The 64-bit RISC-V backend.
+//!
+//! Code is generated for RV64GC with the LP64D ABI used on Linux, and written
+//! as GNU assembler text.  Instruction selection ([`isel`]) translates each
+//! MIR instruction on its own into [`Inst`]s over virtual registers; after
+//! register allocation, the instructions are written out by [`emit`].
+//!
+//! Every function keeps a frame pointer in `s0`, pointing at the incoming
+//! stack arguments.  Below it are the saved argument registers of variadic
+//! functions, the return address and previous frame pointer, the saved
+//! callee-saved registers and the stack slots, and at the bottom of the frame
+//! the stack arguments of calls, addressed relative to `sp`.
+//!
+//! `t0` is left to the assembly writer, for addresses whose offsets do not
+//! fit in an instruction.
+
+use std::collections::HashSet;
+
+use super::regalloc::{self, Allocator};
+use super::{Class, MachInst, Reg, Role, Slot, data};
+use crate::ctype::DataModel;
+use crate::mir::{Module, ssa};
+
+mod emit;
+mod isel;
+
+/// The data model of the target.
+const MODEL: DataModel = DataModel::Lp64;
+
+//----------- Registers --------------------------------------------------------
+
+/// `zero`, always read as zero; integer registers are numbered as `xN`.
+const ZERO: u8 = 0;
+
+/// `ra`, the return address.
+const RA: u8 = 1;
+
+/// `sp`.
+const SP: u8 = 2;
+
+/// `t0`, reserved for the assembly writer.
+const T0: u8 = 5;
+
+/// `s0`, the frame pointer.
+const FP: u8 = 8;
+
+/// `a0`, the first argument register.
+const A0: u8 = 10;
+
+/// `f0`; floating-point register `fN` is numbered `F0 + N`.
+const F0: u8 = 32;
+
+/// `fa0`, the first floating-point argument register.
+const FA0: u8 = F0 + 10;
+
+/// The number of argument registers of each class.
+const ARG_REGS: u8 = 8;
+
+/// The registers a function must preserve, apart from `s0` and `sp`.
+const CALLEE_SAVED: [u8; 23] = [
+    9,
+    18,
+    19,
+    20,
+    21,
+    22,
+    23,
+    24,
+    25,
+    26,
+    27,
+    F0 + 8,
+    F0 + 9,
+    F0 + 18,
+    F0 + 19,
+    F0 + 20,
+    F0 + 21,
+    F0 + 22,
+    F0 + 23,
+    F0 + 24,
+    F0 + 25,
+    F0 + 26,
+    F0 + 27,
+];
+
+/// The registers a call may change.
+const CALLER_SAVED: [u8; 35] = [
+    T0,
+    6,
+    7,
+    A0,
+    A0 + 1,
+    A0 + 2,
+    A0 + 3,
+    A0 + 4,
+    A0 + 5,
+    A0 + 6,
+    A0 + 7,
+    28,
+    29,
+    30,
+    31,
+    F0,
+    F0 + 1,
+    F0 + 2,
+    F0 + 3,
+    F0 + 4,
+    F0 + 5,
+    F0 + 6,
+    F0 + 7,
+    FA0,
+    FA0 + 1,
+    FA0 + 2,
+    FA0 + 3,
+    FA0 + 4,
+    FA0 + 5,
+    FA0 + 6,
+    FA0 + 7,
+    F0 + 28,
+    F0 + 29,
+    F0 + 30,
+    F0 + 31,
+];
+
+/// The size of the save area of the argument registers of variadic
+/// functions, just below the stack arguments.
+const SAVE_AREA: u64 = ARG_REGS as u64 * 8;
+
+//----------- Instructions -----------------------------------------------------
+
+/// The size of an integer operation or memory access.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Size {
+    /// A byte.
+    B,
+
+    /// A 16-bit half word.
+    H,
+
+    /// A 32-bit word.
+    W,
+
+    /// A 64-bit double word.
+    D,
+}
+
+/// The precision of a floating-point operation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Prec {
+    /// Single precision.
+    S,
+
+    /// Double precision.
+    D,
+}
+
+/// A condition comparing two registers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Cond {
+    /// Equal.
+    Eq,
+
+    /// Not equal.
+    Ne,
+}
+
+/// A two-operand integer operation.
+///
+/// Operations on words compute on the low 32 bits of their operands, and
+/// sign-extend the result to 64 bits.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AluOp {
+    /// Addition.
+    Add,
+
+    /// Subtraction.
+    Sub,
+
+    /// Multiplication.
+    Mul,
+
+    /// Signed division.
+    Div,
+
+    /// Unsigned division.
+    Divu,
+
+    /// Signed remainder.
+    Rem,
+
+    /// Unsigned remainder.
+    Remu,
+
+    /// Shift left.
+    Sll,
+
+    /// Logical shift right.
+    Srl,
+
+    /// Arithmetic shift right.
+    Sra,
+
+    /// Bitwise and.
+    And,
+
+    /// Bitwise or.
+    Or,
+
+    /// Bitwise exclusive or.
+    Xor,
+
+    /// Set to whether signed less than.
+    Slt,
+
+    /// Set to whether unsigned less than.
+    Sltu,
+}
+
+/// A two-operand floating-point operation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FAluOp {
+    /// Addition.
+    Add,
+
+    /// Subtraction.
+    Sub,
+
+    /// Multiplication.
+    Mul,
+
+    /// Division.
+    Div,
+}
+
+/// A floating-point comparison, false if unordered.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FCmpOp {
+    /// Equal.
+    Eq,
+
+    /// Less than.
+    Lt,
+
+    /// Less than or equal.
+    Le,
+}
+
+/// A memory operand.
+#[derive(Clone)]
+pub struct Mem {
+    /// What the address is relative to.
+    pub base: Base,
+
+    /// The offset from the base, of any size.
+    pub disp: i64,
+}
+
+/// The base of a memory operand.
+#[derive(Clone)]
+pub enum Base {
+    /// A register holding an address.
+    Reg(Reg),
+
+    /// A stack slot.
+    Slot(Slot),
+}
+
+/// The target of a call.
+#[derive(Clone)]
+pub enum Callee {
+    /// A symbol.
+    Symbol(String),
+
+    /// A register holding the address of the function.
+    Reg(Reg),
+}
+
+/// A RISC-V instruction.
+///
+/// Operands are given destination first.  Registers used implicitly are
+/// listed by [`Inst::implicit`].
+#[derive(Clone)]
+pub enum Inst {
+    /// Load an immediate.
+    Li(Reg, i64),
+
+    /// Load the address of a symbol, from the global offset table if it is
+    /// not defined in the module.
+    La(Reg, String, bool),
+
+    /// Copy an integer register.
+    Mv(Reg, Reg),
+
+    /// An integer operation, of words or double words.
+    Alu(AluOp, Size, Reg, Reg, Reg),
+
+    /// An integer operation with a 12-bit immediate.
+    AluImm(AluOp, Size, Reg, Reg, i64),
+
+    /// Load an integer from memory, sign- or zero-extending it.
+    Load {
+        size: Size,
+        signed: bool,
+        dst: Reg,
+        mem: Mem,
+    },
+
+    /// Store the low bytes of an integer register to memory.
+    Store(Size, Reg, Mem),
+
+    /// Compute an address.
+    Lea(Reg, Mem),
+
+    /// Load a floating-point register from memory.
+    FLoad(Prec, Reg, Mem),
+
+    /// Store a floating-point register to memory.
+    FStore(Prec, Reg, Mem),
+
+    /// Copy a floating-point register.
+    FMv(Prec, Reg, Reg),
+
+    /// A floating-point operation.
+    FAlu(FAluOp, Prec, Reg, Reg, Reg),
+
+    /// Negate a floating-point value.
+    FNeg(Prec, Reg, Reg),
+
+    /// Set an integer register to the result of a floating-point comparison.
+    FCmp(FCmpOp, Prec, Reg, Reg, Reg),
+
+    /// Copy the bits of a floating-point register to an integer register.
+    FMvToInt(Prec, Reg, Reg),
+
+    /// Copy the bits of an integer register to a floating-point register.
+    FMvFromInt(Prec, Reg, Reg),
+
+    /// Convert a word or double word to floating point.
+    CvtIntToFloat {
+        signed: bool,
+        size: Size,
+        prec: Prec,
+        dst: Reg,
+        src: Reg,
+    },
+
+    /// Convert floating point to a word or double word, truncating.
+    CvtFloatToInt {
+        signed: bool,
+        prec: Prec,
+        size: Size,
+        dst: Reg,
+        src: Reg,
+    },
+
+    /// Convert between precisions, from the given one.
+    CvtFloat(Prec, Reg, Reg),
+
+    /// Call a function, with arguments and results in the given registers.
+    Call {
+        callee: Callee,
+        args: Vec<u8>,
+        rets: Vec<u8>,
+    },
+
+    /// Jump to a block.
+    J(usize),
+
+    /// Jump to a block if a condition holds between two registers.
+    Branch(Cond, Reg, Reg, usize),
+
+    /// Return, with the result in the given registers.
+    Ret(Vec<u8>),
+
+    /// Save the argument registers of a variadic function.
+    SaveVarargs,
+
+    /// Trap.
+    Unimp,
+}
+
+impl Mem {
+    /// Collect the registers used by the address.
+    fn regs_mut<'a>(&'a mut self, regs: &mut Vec<(&'a mut Reg, Role)>) {
+        if let Base::Reg(reg) = &mut self.base {
+            regs.push((reg, Role::Use));
+        }
+    }
+
+    /// The same address, moved by a number of bytes.
+    fn offset(&self, offset: u64) -> Self {
+        Self {
+            base: self.base.clone(),
+            disp: self.disp + offset as i64,
+        }
+    }
+}
+
+impl Inst {
+    /// The machine registers used or defined implicitly.
+    pub fn implicit(&self) -> Vec<(u8, Role)> {
+        let mut regs = Vec::new();
+        match self {
+            Self::Call { args, rets, .. } => {
+                regs.extend(args.iter().map(|&reg| (reg, Role::Use)));
+                regs.extend(rets.iter().map(|&reg| (reg, Role::Def)));
+            }
+            Self::Ret(rets) => {
+                regs.extend(rets.iter().map(|&reg| (reg, Role::Use)));
+            }
+            Self::SaveVarargs => {
+                regs.extend((0..ARG_REGS).map(|i| (A0 + i, Role::Use)));
+            }
+            _ => {}
+        }
+        regs
+    }
+}
+
+impl MachInst for Inst {
+    fn regs(&self) -> Vec<(Reg, Role)> {
+        let mut copy = self.clone();
+        let explicit =
+            copy.regs_mut().into_iter().map(|(reg, role)| (*reg, role));
+        let implicit = self
+            .implicit()
+            .into_iter()
+            .map(|(reg, role)| (Reg::Phys(reg), role));
+        explicit.chain(implicit).collect()
+    }
+
+    fn regs_mut(&mut self) -> Vec<(&mut Reg, Role)> {
+        let mut regs = Vec::new();
+        match self {
+            Self::Li(dst, _) | Self::La(dst, ..) => {
+                regs.push((dst, Role::Def));
+            }
+            Self::Mv(dst, src)
+            | Self::AluImm(_, _, dst, src, _)
+            | Self::FMv(_, dst, src)
+            | Self::FNeg(_, dst, src)
+            | Self::FMvToInt(_, dst, src)
+            | Self::FMvFromInt(_, dst, src)
+            | Self::CvtIntToFloat { dst, src, .. }
+            | Self::CvtFloatToInt { dst, src, .. }
+            | Self::CvtFloat(_, dst, src) => {
+                regs.push((src, Role::Use));
+                regs.push((dst, Role::Def));
+            }
+            Self::Alu(_, _, dst, a, b)
+            | Self::FAlu(_, _, dst, a, b)
+            | Self::FCmp(_, _, dst, a, b) => {
+                regs.push((a, Role::Use));
+                regs.push((b, Role::Use));
+                regs.push((dst, Role::Def));
+            }
+            Self::Load { dst, mem, .. }
+            | Self::Lea(dst, mem)
+            | Self::FLoad(_, dst, mem) => {
+                mem.regs_mut(&mut regs);
+                regs.push((dst, Role::Def));
+            }
+            Self::Store(_, src, mem) | Self::FStore(_, src, mem) => {
+                regs.push((src, Role::Use));
+                mem.regs_mut(&mut regs);
+            }
+            Self::Branch(_, a, b, _) => {
+                regs.push((a, Role::Use));
+                regs.push((b, Role::Use));
+            }
+            Self::Call {
+                callee: Callee::Reg(reg),
+                ..
+            } => regs.push((reg, Role::Use)),
+            Self::Call { .. }
+            | Self::J(_)
+            | Self::Ret(_)
+            | Self::SaveVarargs
+            | Self::Unimp => {}
+        }
+        regs
+    }
+
+    fn clobbers(&self) -> &'static [u8] {
+        match self {
+            Self::Call { .. } => &CALLER_SAVED,
+            _ => &[],
+        }
+    }
+
+    fn as_move(&self) -> Option<(Reg, Reg)> {
+        match self {
+            Self::Mv(dst, src) | Self::FMv(_, dst, src)
+                if *src != Reg::Phys(ZERO) =>
+            {
+                Some((*dst, *src))
+            }
+            _ => None,
+        }
+    }
+
+    fn targets_mut(&mut self) -> Vec<&mut usize> {
+        match self {
+            Self::J(target) | Self::Branch(.., target) => vec![target],
+            _ => Vec::new(),
+        }
+    }
+
+    fn allocatable(class: Class) -> &'static [u8] {
+        match class {
+            Class::Int => &[
+                6, 7, 10, 11, 12, 13, 14, 15, 16, 17, 28, 29, 9, 18, 19, 20,
+                21, 22, 23, 24, 25, 26, 27,
+            ],
+            Class::Float => &[
+                F0,
+                F0 + 1,
+                F0 + 2,
+                F0 + 3,
+                F0 + 4,
+                F0 + 5,
+                F0 + 6,
+                F0 + 7,
+                FA0,
+                FA0 + 1,
+                FA0 + 2,
+                FA0 + 3,
+                FA0 + 4,
+                FA0 + 5,
+                FA0 + 6,
+                FA0 + 7,
+                F0 + 28,
+                F0 + 29,
+                F0 + 8,
+                F0 + 9,
+                F0 + 18,
+                F0 + 19,
+                F0 + 20,
+                F0 + 21,
+                F0 + 22,
+                F0 + 23,
+                F0 + 24,
+                F0 + 25,
+                F0 + 26,
+                F0 + 27,
+            ],
+        }
+    }
+
+    fn scratch(class: Class) -> [u8; 2] {
+        match class {
+            Class::Int => [30, 31],
+            Class::Float => [F0 + 30, F0 + 31],
+        }
+    }
+
+    fn reload(reg: u8, class: Class, slot: Slot) -> Self {
+        let mem = Mem {
+            base: Base::Slot(slot),
+            disp: 0,
+        };
+        match class {
+            Class::Int => Self::Load {
+                size: Size::D,
+                signed: true,
+                dst: Reg::Phys(reg),
+                mem,
+            },
+            Class::Float => Self::FLoad(Prec::D, Reg::Phys(reg), mem),
+        }
+    }
+
+    fn spill(reg: u8, class: Class, slot: Slot) -> Self {
+        let mem = Mem {
+            base: Base::Slot(slot),
+            disp: 0,
+        };
+        match class {
+            Class::Int => Self::Store(Size::D, Reg::Phys(reg), mem),
+            Class::Float => Self::FStore(Prec::D, Reg::Phys(reg), mem),
+        }
+    }
+
+    fn copy(dst: u8, src: u8, class: Class) -> Self {
+        let (dst, src) = (Reg::Phys(dst), Reg::Phys(src));
+        match class {
+            Class::Int => Self::Mv(dst, src),
+            Class::Float => Self::FMv(Prec::D, dst, src),
+        }
+    }
+
+    fn jump(target: usize) -> Self {
+        Self::J(target)
+    }
+}
+
+//----------- Compilation ------------------------------------------------------
+
+/// Compile a module to assembly text.
+pub fn compile(module: &Module, allocator: Allocator) -> String {
+    let defined: HashSet<String> = module
+        .functions
+        .iter()
+        .map(|func| func.name.clone())
+        .chain(module.globals.iter().map(|global| global.name.clone()))
+        .collect();
+
+    // Position-independent code loads the addresses of external symbols from
+    // the global offset table.
+    let mut out = String::from("\t.option pic\n");
+    for global in &module.globals {
+        data::global(&mut out, global, &module.types);
+    }
+    for func in &module.functions {
+        let mut func = func.clone();
+        ssa::destruct(&mut func);
+        let mut mach = isel::select(&func, &module.types, &defined);
+        regalloc::allocate(&mut mach, allocator);
+        emit::function(&mut out, &mach, func.local);
+    }
+    out.push_str("\t.section .note.GNU-stack,\"\",@progbits\n");
+    out
+}
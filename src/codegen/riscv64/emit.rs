@@ -0,0 +1,448 @@
+//! Writing RISC-V assembly text.
+
+use std::fmt::Write;
+
+use super::*;
+use crate::codegen::MachFunction;
+
+/// The layout of the frame of a function.
+struct Frame {
+    /// The offset from `s0` of each stack slot.
+    slots: Vec<i64>,
+
+    /// The callee-saved registers written by the function, with the offsets
+    /// from `s0` they are saved at.
+    saved: Vec<(u8, i64)>,
+
+    /// The size of the save area of the argument registers, followed by the
+    /// return address and previous frame pointer.
+    top: u64,
+
+    /// The number of bytes allocated in all.
+    size: u64,
+}
+
+impl Frame {
+    /// Lay out the frame of a function.
+    fn new(func: &MachFunction<Inst>) -> Self {
+        let mut written = Vec::new();
+        let mut variadic = false;
+        for inst in func.blocks.iter().flat_map(|block| &block.insts) {
+            variadic |= matches!(inst, Inst::SaveVarargs);
+            for (reg, role) in inst.regs() {
+                if let Reg::Phys(reg) = reg
+                    && role.is_def()
+                    && CALLEE_SAVED.contains(&reg)
+                    && !written.contains(&reg)
+                {
+                    written.push(reg);
+                }
+            }
+        }
+        written.sort();
+
+        let top = if variadic { SAVE_AREA } else { 0 } + 16;
+        let mut bottom = top;
+        let saved = written
+            .into_iter()
+            .map(|reg| {
+                bottom += 8;
+                (reg, -(bottom as i64))
+            })
+            .collect();
+        let slots = func
+            .slots
+            .iter()
+            .map(|slot| {
+                bottom = (bottom + slot.size).next_multiple_of(slot.align);
+                -(bottom as i64)
+            })
+            .collect();
+        let size =
+            bottom.next_multiple_of(16) + func.outgoing.next_multiple_of(16);
+        Self {
+            slots,
+            saved,
+            top,
+            size,
+        }
+    }
+}
+
+/// Write out the code of a function, after register allocation.
+pub fn function(out: &mut String, func: &MachFunction<Inst>, local: bool) {
+    let name = &func.name;
+    let frame = Frame::new(func);
+    writeln!(out, "\t.text").unwrap();
+    if !local {
+        writeln!(out, "\t.globl {name}").unwrap();
+    }
+    writeln!(out, "\t.p2align 1").unwrap();
+    writeln!(out, "\t.type {name}, @function").unwrap();
+    writeln!(out, "{name}:").unwrap();
+
+    let mut printer = Printer {
+        out,
+        name,
+        frame: &frame,
+    };
+    let top = frame.top as i64;
+    printer.line(format_args!("addi sp, sp, {}", -top));
+    printer.line(format_args!("sd {}, 8(sp)", reg(RA)));
+    printer.line(format_args!("sd {}, 0(sp)", reg(FP)));
+    printer.line(format_args!("addi {}, sp, {top}", reg(FP)));
+    let rest = frame.size as i64 - top;
+    if rest > 0 {
+        printer.add(SP, SP, -rest);
+    }
+    for &(saved, offset) in &frame.saved {
+        let op = if saved >= F0 { "fsd" } else { "sd" };
+        let mem = printer.offset(FP, offset);
+        printer.line(format_args!("{op} {}, {mem}", reg(saved)));
+    }
+
+    for (index, block) in func.blocks.iter().enumerate() {
+        writeln!(printer.out, ".L{name}.{index}:").unwrap();
+        for inst in &block.insts {
+            // Jumps to the next block fall through.
+            if let Inst::J(target) = inst
+                && *target == index + 1
+            {
+                continue;
+            }
+            printer.inst(inst);
+        }
+    }
+    writeln!(out, "\t.size {name}, .-{name}").unwrap();
+}
+
+/// The name of a machine register.
+fn reg(reg: u8) -> &'static str {
+    const NAMES: [&str; 64] = [
+        "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0",
+        "a1", "a2", "a3", "a4", "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6",
+        "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6", "ft0", "ft1",
+        "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "fs0", "fs1", "fa0", "fa1",
+        "fa2", "fa3", "fa4", "fa5", "fa6", "fa7", "fs2", "fs3", "fs4", "fs5",
+        "fs6", "fs7", "fs8", "fs9", "fs10", "fs11", "ft8", "ft9", "ft10",
+        "ft11",
+    ];
+    NAMES[reg as usize]
+}
+
+/// The letter of an integer size in mnemonics.
+fn suffix(size: Size) -> char {
+    match size {
+        Size::B => 'b',
+        Size::H => 'h',
+        Size::W => 'w',
+        Size::D => 'd',
+    }
+}
+
+/// The letter of a precision in mnemonics.
+fn prec_suffix(prec: Prec) -> char {
+    match prec {
+        Prec::S => 's',
+        Prec::D => 'd',
+    }
+}
+
+/// The mnemonic of an integer operation, on words if `size` is
+/// [`Size::W`], and taking an immediate if `imm` is set.
+fn alu(op: AluOp, size: Size, imm: bool) -> String {
+    let name = match op {
+        AluOp::Add if imm => "addi",
+        AluOp::Add => "add",
+        AluOp::Sub => "sub",
+        AluOp::Mul => "mul",
+        AluOp::Div => "div",
+        AluOp::Divu => "divu",
+        AluOp::Rem => "rem",
+        AluOp::Remu => "remu",
+        AluOp::Sll if imm => "slli",
+        AluOp::Sll => "sll",
+        AluOp::Srl if imm => "srli",
+        AluOp::Srl => "srl",
+        AluOp::Sra if imm => "srai",
+        AluOp::Sra => "sra",
+        AluOp::And if imm => "andi",
+        AluOp::And => "and",
+        AluOp::Or if imm => "ori",
+        AluOp::Or => "or",
+        AluOp::Xor if imm => "xori",
+        AluOp::Xor => "xor",
+        AluOp::Slt if imm => "slti",
+        AluOp::Slt => "slt",
+        AluOp::Sltu if imm => "sltiu",
+        AluOp::Sltu => "sltu",
+    };
+    let word = size == Size::W
+        && !matches!(
+            op,
+            AluOp::And | AluOp::Or | AluOp::Xor | AluOp::Slt | AluOp::Sltu
+        );
+    if word {
+        format!("{name}w")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Writes instructions of a function.
+struct Printer<'a> {
+    /// The text written so far.
+    out: &'a mut String,
+
+    /// The name of the function.
+    name: &'a str,
+
+    /// The layout of the frame.
+    frame: &'a Frame,
+}
+
+impl Printer<'_> {
+    /// A machine register.
+    fn phys(reg: Reg) -> u8 {
+        match reg {
+            Reg::Phys(reg) => reg,
+            Reg::Virt(_) => panic!("virtual register after allocation"),
+        }
+    }
+
+    /// The name of a register.
+    fn reg(&self, phys: Reg) -> &'static str {
+        reg(Self::phys(phys))
+    }
+
+    /// A memory operand at an offset from a machine register, computing the
+    /// address into `t0` first if the offset does not fit.
+    fn offset(&mut self, base: u8, offset: i64) -> String {
+        if (-2048..2048).contains(&offset) {
+            return format!("{offset}({})", reg(base));
+        }
+        self.line(format_args!("li {}, {offset}", reg(T0)));
+        self.line(format_args!("add {}, {}, {}", reg(T0), reg(T0), reg(base)));
+        format!("0({})", reg(T0))
+    }
+
+    /// A memory operand.
+    fn mem(&mut self, mem: &Mem) -> String {
+        match &mem.base {
+            Base::Reg(base) => self.offset(Self::phys(*base), mem.disp),
+            Base::Slot(slot) => {
+                let offset = self.frame.slots[slot.0 as usize] + mem.disp;
+                self.offset(FP, offset)
+            }
+        }
+    }
+
+    /// Add a constant to a machine register.
+    fn add(&mut self, dst: u8, src: u8, value: i64) {
+        if (-2048..2048).contains(&value) {
+            self.line(format_args!("addi {}, {}, {value}", reg(dst), reg(src)));
+        } else {
+            self.line(format_args!("li {}, {value}", reg(T0)));
+            self.line(format_args!(
+                "add {}, {}, {}",
+                reg(dst),
+                reg(src),
+                reg(T0)
+            ));
+        }
+    }
+
+    /// The label of a block.
+    fn label(&self, block: usize) -> String {
+        format!(".L{}.{block}", self.name)
+    }
+
+    /// Write a line of assembly.
+    fn line(&mut self, line: std::fmt::Arguments) {
+        writeln!(self.out, "\t{line}").unwrap();
+    }
+
+    /// Write an instruction.
+    fn inst(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Li(dst, value) => {
+                let dst = self.reg(*dst);
+                self.line(format_args!("li {dst}, {value}"));
+            }
+            Inst::La(dst, name, got) => {
+                let op = if *got { "la" } else { "lla" };
+                let dst = self.reg(*dst);
+                self.line(format_args!("{op} {dst}, {name}"));
+            }
+            Inst::Mv(dst, src) => {
+                let (dst, src) = (self.reg(*dst), self.reg(*src));
+                self.line(format_args!("mv {dst}, {src}"));
+            }
+            Inst::Alu(op, size, dst, a, b) => {
+                let op = alu(*op, *size, false);
+                let (dst, a, b) = (self.reg(*dst), self.reg(*a), self.reg(*b));
+                self.line(format_args!("{op} {dst}, {a}, {b}"));
+            }
+            Inst::AluImm(op, size, dst, a, imm) => {
+                let op = alu(*op, *size, true);
+                let (dst, a) = (self.reg(*dst), self.reg(*a));
+                self.line(format_args!("{op} {dst}, {a}, {imm}"));
+            }
+            Inst::Load {
+                size,
+                signed,
+                dst,
+                mem,
+            } => {
+                let u = if *signed || *size == Size::D { "" } else { "u" };
+                let s = suffix(*size);
+                let mem = self.mem(mem);
+                let dst = self.reg(*dst);
+                self.line(format_args!("l{s}{u} {dst}, {mem}"));
+            }
+            Inst::Store(size, src, mem) => {
+                let s = suffix(*size);
+                let mem = self.mem(mem);
+                let src = self.reg(*src);
+                self.line(format_args!("s{s} {src}, {mem}"));
+            }
+            Inst::Lea(dst, mem) => {
+                let (base, offset) = match &mem.base {
+                    Base::Reg(base) => (Self::phys(*base), mem.disp),
+                    Base::Slot(slot) => {
+                        (FP, self.frame.slots[slot.0 as usize] + mem.disp)
+                    }
+                };
+                self.add(Self::phys(*dst), base, offset);
+            }
+            Inst::FLoad(prec, dst, mem) => {
+                let op = if *prec == Prec::S { "flw" } else { "fld" };
+                let mem = self.mem(mem);
+                let dst = self.reg(*dst);
+                self.line(format_args!("{op} {dst}, {mem}"));
+            }
+            Inst::FStore(prec, src, mem) => {
+                let op = if *prec == Prec::S { "fsw" } else { "fsd" };
+                let mem = self.mem(mem);
+                let src = self.reg(*src);
+                self.line(format_args!("{op} {src}, {mem}"));
+            }
+            Inst::FMv(prec, dst, src) => {
+                let p = prec_suffix(*prec);
+                let (dst, src) = (self.reg(*dst), self.reg(*src));
+                self.line(format_args!("fmv.{p} {dst}, {src}"));
+            }
+            Inst::FAlu(op, prec, dst, a, b) => {
+                let op = match op {
+                    FAluOp::Add => "fadd",
+                    FAluOp::Sub => "fsub",
+                    FAluOp::Mul => "fmul",
+                    FAluOp::Div => "fdiv",
+                };
+                let p = prec_suffix(*prec);
+                let (dst, a, b) = (self.reg(*dst), self.reg(*a), self.reg(*b));
+                self.line(format_args!("{op}.{p} {dst}, {a}, {b}"));
+            }
+            Inst::FNeg(prec, dst, src) => {
+                let p = prec_suffix(*prec);
+                let (dst, src) = (self.reg(*dst), self.reg(*src));
+                self.line(format_args!("fneg.{p} {dst}, {src}"));
+            }
+            Inst::FCmp(op, prec, dst, a, b) => {
+                let op = match op {
+                    FCmpOp::Eq => "feq",
+                    FCmpOp::Lt => "flt",
+                    FCmpOp::Le => "fle",
+                };
+                let p = prec_suffix(*prec);
+                let (dst, a, b) = (self.reg(*dst), self.reg(*a), self.reg(*b));
+                self.line(format_args!("{op}.{p} {dst}, {a}, {b}"));
+            }
+            Inst::FMvToInt(prec, dst, src) => {
+                let p = if *prec == Prec::S { 'w' } else { 'd' };
+                let (dst, src) = (self.reg(*dst), self.reg(*src));
+                self.line(format_args!("fmv.x.{p} {dst}, {src}"));
+            }
+            Inst::FMvFromInt(prec, dst, src) => {
+                let p = if *prec == Prec::S { 'w' } else { 'd' };
+                let (dst, src) = (self.reg(*dst), self.reg(*src));
+                self.line(format_args!("fmv.{p}.x {dst}, {src}"));
+            }
+            Inst::CvtIntToFloat {
+                signed,
+                size,
+                prec,
+                dst,
+                src,
+            } => {
+                let s = if *size == Size::W { 'w' } else { 'l' };
+                let u = if *signed { "" } else { "u" };
+                let p = prec_suffix(*prec);
+                let (dst, src) = (self.reg(*dst), self.reg(*src));
+                self.line(format_args!("fcvt.{p}.{s}{u} {dst}, {src}"));
+            }
+            Inst::CvtFloatToInt {
+                signed,
+                prec,
+                size,
+                dst,
+                src,
+            } => {
+                let s = if *size == Size::W { 'w' } else { 'l' };
+                let u = if *signed { "" } else { "u" };
+                let p = prec_suffix(*prec);
+                let (dst, src) = (self.reg(*dst), self.reg(*src));
+                self.line(format_args!("fcvt.{s}{u}.{p} {dst}, {src}, rtz"));
+            }
+            Inst::CvtFloat(from, dst, src) => {
+                let op = match from {
+                    Prec::S => "fcvt.d.s",
+                    Prec::D => "fcvt.s.d",
+                };
+                let (dst, src) = (self.reg(*dst), self.reg(*src));
+                self.line(format_args!("{op} {dst}, {src}"));
+            }
+            Inst::Call { callee, .. } => match callee {
+                Callee::Symbol(name) => self.line(format_args!("call {name}")),
+                Callee::Reg(callee) => {
+                    let callee = self.reg(*callee);
+                    self.line(format_args!("jalr {callee}"));
+                }
+            },
+            Inst::J(target) => {
+                let label = self.label(*target);
+                self.line(format_args!("j {label}"));
+            }
+            Inst::Branch(cond, a, b, target) => {
+                let op = match cond {
+                    Cond::Eq => "beq",
+                    Cond::Ne => "bne",
+                };
+                let label = self.label(*target);
+                let (a, b) = (self.reg(*a), self.reg(*b));
+                self.line(format_args!("{op} {a}, {b}, {label}"));
+            }
+            Inst::Ret(_) => {
+                for &(saved, offset) in &self.frame.saved {
+                    let op = if saved >= F0 { "fld" } else { "ld" };
+                    let mem = self.offset(FP, offset);
+                    self.line(format_args!("{op} {}, {mem}", reg(saved)));
+                }
+                let top = self.frame.top as i64;
+                self.line(format_args!("addi sp, {}, {}", reg(FP), -top));
+                self.line(format_args!("ld {}, 8(sp)", reg(RA)));
+                self.line(format_args!("ld {}, 0(sp)", reg(FP)));
+                self.line(format_args!("addi sp, sp, {top}"));
+                self.line(format_args!("ret"));
+            }
+            Inst::SaveVarargs => {
+                for i in 0..ARG_REGS {
+                    let offset = i as i64 * 8 - SAVE_AREA as i64;
+                    let arg = reg(A0 + i);
+                    self.line(format_args!("sd {arg}, {offset}({})", reg(FP)));
+                }
+            }
+            Inst::Unimp => self.line(format_args!("unimp")),
+        }
+    }
+}
@@ -0,0 +1,1163 @@
+//! Instruction selection for RISC-V.
+//!
+//! Each register local becomes a virtual register, and each memory local a
+//! stack slot.  Integers are kept sign-extended from their width to the
+//! whole register, as the ABI does for 32-bit ones: word operations produce
+//! them that way, narrower results are extended again, and comparisons can
+//! then look at all 64 bits, for signed and unsigned ones alike.
+//!
+//! Aggregates are passed and returned as the LP64D ABI requires: those of up
+//! to 16 bytes made of one or two scalars, at least one a float, travel in
+//! one register for each scalar while there are enough left; others of up to
+//! 16 bytes in one or two integer registers, or on the stack; larger ones
+//! by reference to a copy, or are returned through a hidden pointer.
+
+use std::collections::HashSet;
+
+use super::*;
+use crate::codegen::{Class, MachFunction, Reg, Slot, scalars};
+use crate::ctype::{CType, FnType, TypeTable};
+use crate::mir::{
+    BinOp, CastOp, Const, Function, Inst as MirInst, Local, Operand, Rvalue,
+    Terminator, Ty, UnaOp,
+};
+
+/// The state of instruction selection for a function.
+struct Isel<'a> {
+    /// The structure types of the module.
+    types: &'a TypeTable,
+
+    /// The symbols defined by the module, whose addresses are computed
+    /// directly rather than loaded from the global offset table.
+    defined: &'a HashSet<String>,
+
+    /// The function being translated.
+    func: &'a Function,
+
+    /// The translated function.
+    out: MachFunction<Inst>,
+
+    /// The virtual register of each register local.
+    regs: Vec<Option<Reg>>,
+
+    /// The stack slot of each memory local.
+    slots: Vec<Option<Slot>>,
+
+    /// The block instructions are added to.
+    block: usize,
+
+    /// The register holding the address an aggregate result is returned
+    /// to, if it is returned in memory.
+    sret: Option<Reg>,
+
+    /// The offset from the frame pointer of the first variadic argument, in
+    /// a variadic function.
+    varargs: Option<i64>,
+}
+
+/// Translate a function out of SSA form into RISC-V instructions.
+pub fn select(
+    func: &Function,
+    types: &TypeTable,
+    defined: &HashSet<String>,
+) -> MachFunction<Inst> {
+    let mut isel = Isel {
+        types,
+        defined,
+        func,
+        out: MachFunction::new(func.name.clone(), func.blocks.len()),
+        regs: Vec::new(),
+        slots: Vec::new(),
+        block: 0,
+        sret: None,
+        varargs: None,
+    };
+    for decl in &func.locals {
+        if decl.memory {
+            let size = types.size_of(&decl.ty).next_multiple_of(8).max(8);
+            let align = types.align_of(&decl.ty).max(8);
+            isel.slots.push(Some(isel.out.new_slot(size, align)));
+            isel.regs.push(None);
+        } else {
+            let class = Ty::of(&decl.ty, MODEL).map_or(Class::Int, class);
+            isel.regs.push(Some(isel.out.new_vreg(class)));
+            isel.slots.push(None);
+        }
+    }
+
+    isel.params();
+    for id in func.block_ids() {
+        isel.block = id.0 as usize;
+        let block = func.block(id);
+        for inst in &block.insts {
+            isel.inst(inst);
+        }
+        isel.term(&block.term);
+        let succs = block.term.successors();
+        isel.out.blocks[isel.block].succs =
+            succs.iter().map(|succ| succ.0 as usize).collect();
+    }
+    isel.out
+}
+
+//----------- Types ------------------------------------------------------------
+
+/// The size of a value of a machine type.
+fn size(ty: Ty) -> Size {
+    match ty {
+        Ty::I8 => Size::B,
+        Ty::I16 => Size::H,
+        Ty::I32 | Ty::F32 => Size::W,
+        Ty::I64 | Ty::F64 | Ty::Ptr => Size::D,
+    }
+}
+
+/// The size integer operations on a machine type are computed in.
+fn op_size(ty: Ty) -> Size {
+    match ty {
+        Ty::I64 | Ty::Ptr => Size::D,
+        _ => Size::W,
+    }
+}
+
+/// The register class holding a machine type.
+fn class(ty: Ty) -> Class {
+    if ty.is_float() {
+        Class::Float
+    } else {
+        Class::Int
+    }
+}
+
+/// The precision of a floating-point type.
+fn prec(ty: Ty) -> Prec {
+    match ty {
+        Ty::F32 => Prec::S,
+        _ => Prec::D,
+    }
+}
+
+/// Whether a C type is a signed integer type.
+fn signed(ty: &CType) -> bool {
+    matches!(ty, CType::Int(int) if int.signed)
+}
+
+/// Whether a value fits in a 12-bit immediate.
+fn fits_imm(value: i64) -> bool {
+    (-2048..2048).contains(&value)
+}
+
+/// Where a part of a value is passed.
+#[derive(Copy, Clone)]
+enum Loc {
+    /// In a machine register.
+    Reg(u8),
+
+    /// On the stack, at an offset from the first stack argument.
+    Stack(u64),
+}
+
+/// A part of a value passed on its own.
+struct Part {
+    /// The offset of the part in the value.
+    offset: u64,
+
+    /// The type of the part: a scalar of an aggregate, or an eightbyte of
+    /// it, of which only the first `size` bytes belong to it.
+    ty: Ty,
+
+    /// The size of the part.
+    size: u64,
+
+    /// Where the part is passed.
+    loc: Loc,
+}
+
+/// How a value is passed.
+struct Passed {
+    /// The parts of the value.
+    parts: Vec<Part>,
+
+    /// Whether the value is an aggregate passed by reference, the single
+    /// part being the address of a copy.
+    by_ref: bool,
+}
+
+/// The assignment of arguments to registers and the stack.
+struct Args {
+    /// The number of registers of each class available.
+    regs: u8,
+
+    /// The number of integer registers used.
+    ints: u8,
+
+    /// The number of floating-point registers used.
+    floats: u8,
+
+    /// The size of the stack arguments.
+    stack: u64,
+}
+
+impl Args {
+    /// Start assigning arguments, with a number of registers of each class.
+    fn new(regs: u8) -> Self {
+        Self {
+            regs,
+            ints: 0,
+            floats: 0,
+            stack: 0,
+        }
+    }
+
+    /// Where the next integer is passed.
+    fn int(&mut self) -> Loc {
+        if self.ints < self.regs {
+            self.ints += 1;
+            Loc::Reg(A0 + self.ints - 1)
+        } else {
+            self.stack += 8;
+            Loc::Stack(self.stack - 8)
+        }
+    }
+
+    /// Assign the next argument; variadic arguments follow the integer
+    /// convention.
+    fn place(
+        &mut self,
+        types: &TypeTable,
+        ty: &CType,
+        variadic: bool,
+    ) -> Passed {
+        if let Some(mty) = Ty::of(ty, MODEL) {
+            let loc = if mty.is_float() && !variadic && self.floats < self.regs
+            {
+                self.floats += 1;
+                Loc::Reg(FA0 + self.floats - 1)
+            } else {
+                self.int()
+            };
+            let part = Part {
+                offset: 0,
+                ty: mty,
+                size: mty.size(MODEL),
+                loc,
+            };
+            return Passed {
+                parts: vec![part],
+                by_ref: false,
+            };
+        }
+
+        let size = types.size_of(ty);
+        if size > 16 {
+            let part = Part {
+                offset: 0,
+                ty: Ty::Ptr,
+                size: 8,
+                loc: self.int(),
+            };
+            return Passed {
+                parts: vec![part],
+                by_ref: true,
+            };
+        }
+
+        // One float, or two scalars of which one is a float, go in one
+        // register each if there are enough left.
+        let mut fields = Vec::new();
+        scalars(types, ty, 0, &mut |offset, ty| fields.push((offset, ty)));
+        let floats = fields.iter().filter(|(_, ty)| ty.is_float()).count();
+        let ints = fields.len() - floats;
+        if !variadic
+            && floats > 0
+            && fields.len() <= 2
+            && self.floats as usize + floats <= self.regs as usize
+            && self.ints as usize + ints <= self.regs as usize
+        {
+            let parts = fields.into_iter().map(|(offset, ty)| {
+                let loc = if ty.is_float() {
+                    self.floats += 1;
+                    Loc::Reg(FA0 + self.floats - 1)
+                } else {
+                    self.int()
+                };
+                Part {
+                    offset,
+                    ty,
+                    size: ty.size(MODEL),
+                    loc,
+                }
+            });
+            return Passed {
+                parts: parts.collect(),
+                by_ref: false,
+            };
+        }
+
+        let parts = (0..size.div_ceil(8)).map(|i| Part {
+            offset: i * 8,
+            ty: Ty::I64,
+            size: (size - i * 8).min(8),
+            loc: self.int(),
+        });
+        Passed {
+            parts: parts.collect(),
+            by_ref: false,
+        }
+    }
+}
+
+/// The parts of a value of a type as returned in registers, or `None` if it
+/// is returned in memory.
+fn returned(types: &TypeTable, ty: &CType) -> Option<Vec<Part>> {
+    if *ty == CType::Void {
+        return Some(Vec::new());
+    }
+    if Ty::of(ty, MODEL).is_none() && types.size_of(ty) > 16 {
+        return None;
+    }
+    Some(Args::new(2).place(types, ty, false).parts)
+}
+
+/// A memory operand for a stack argument.
+fn stack_arg(base: u8, offset: u64) -> Mem {
+    Mem {
+        base: Base::Reg(Reg::Phys(base)),
+        disp: offset as i64,
+    }
+}
+
+//----------- Operands ---------------------------------------------------------
+
+impl Isel<'_> {
+    /// Add an instruction to the current block.
+    fn emit(&mut self, inst: Inst) {
+        self.out.blocks[self.block].insts.push(inst);
+    }
+
+    /// The virtual register of a register local.
+    fn vreg(&self, local: Local) -> Reg {
+        self.regs[local.0 as usize].expect("register locals have registers")
+    }
+
+    /// The stack slot of a memory local.
+    fn slot(&self, local: Local) -> Slot {
+        self.slots[local.0 as usize].expect("memory locals have slots")
+    }
+
+    /// A new virtual register.
+    fn temp(&mut self, class: Class) -> Reg {
+        self.out.new_vreg(class)
+    }
+
+    /// An operand as a 12-bit immediate, if it is a small enough integer.
+    fn imm(&self, operand: &Operand) -> Option<i64> {
+        match operand {
+            Operand::Const(Const::Int(value, ty)) => {
+                Some(ty.wrap(*value)).filter(|&value| fits_imm(value))
+            }
+            _ => None,
+        }
+    }
+
+    /// An operand in a register.
+    fn reg(&mut self, operand: &Operand) -> Reg {
+        match operand {
+            Operand::Local(local) => self.vreg(*local),
+            Operand::Const(Const::Int(value, ty)) => match ty.wrap(*value) {
+                0 => Reg::Phys(ZERO),
+                value => {
+                    let reg = self.temp(Class::Int);
+                    self.emit(Inst::Li(reg, value));
+                    reg
+                }
+            },
+            Operand::Const(Const::Float(value, ty)) => {
+                let bits = match ty {
+                    Ty::F32 => (*value as f32).to_bits() as i64,
+                    _ => value.to_bits() as i64,
+                };
+                let bits = self.reg(&Operand::Const(Const::Int(bits, Ty::I64)));
+                let reg = self.temp(Class::Float);
+                self.emit(Inst::FMvFromInt(prec(*ty), reg, bits));
+                reg
+            }
+            Operand::Const(Const::Symbol(name)) => {
+                let reg = self.temp(Class::Int);
+                let got = !self.defined.contains(name);
+                self.emit(Inst::La(reg, name.clone(), got));
+                reg
+            }
+            Operand::Addr(_) => {
+                let reg = self.temp(Class::Int);
+                let mem = self.mem(operand, 0);
+                self.emit(Inst::Lea(reg, mem));
+                reg
+            }
+        }
+    }
+
+    /// A memory operand for an address, moved by a displacement.
+    fn mem(&mut self, addr: &Operand, disp: i64) -> Mem {
+        let base = match addr {
+            Operand::Addr(local) => Base::Slot(self.slot(*local)),
+            _ => Base::Reg(self.reg(addr)),
+        };
+        Mem { base, disp }
+    }
+
+    /// Compute an integer operation, using an immediate for the second
+    /// operand if it has one.
+    fn alu(
+        &mut self,
+        op: AluOp,
+        size: Size,
+        dst: Reg,
+        a: &Operand,
+        b: &Operand,
+    ) {
+        let has_imm = matches!(
+            op,
+            AluOp::Add
+                | AluOp::Sub
+                | AluOp::Sll
+                | AluOp::Srl
+                | AluOp::Sra
+                | AluOp::And
+                | AluOp::Or
+                | AluOp::Xor
+                | AluOp::Slt
+                | AluOp::Sltu
+        );
+        let imm = self.imm(b).filter(|_| has_imm);
+        let a = self.reg(a);
+        match (op, imm) {
+            (AluOp::Sub, Some(imm)) if fits_imm(-imm) => {
+                self.emit(Inst::AluImm(AluOp::Add, size, dst, a, -imm));
+            }
+            (AluOp::Sll | AluOp::Srl | AluOp::Sra, Some(imm)) => {
+                let bits = if size == Size::W { 31 } else { 63 };
+                self.emit(Inst::AluImm(op, size, dst, a, imm & bits));
+            }
+            (AluOp::Sub, _) | (_, None) => {
+                let b = self.reg(b);
+                self.emit(Inst::Alu(op, size, dst, a, b));
+            }
+            (_, Some(imm)) => self.emit(Inst::AluImm(op, size, dst, a, imm)),
+        }
+    }
+
+    /// Sign-extend an integer of a machine type from its width, as values
+    /// of the type are kept.
+    fn normalize(&mut self, dst: Reg, src: Reg, ty: Ty) {
+        match ty {
+            Ty::I8 | Ty::I16 => {
+                let shift = 64 - ty.size(MODEL) as i64 * 8;
+                self.emit(Inst::AluImm(AluOp::Sll, Size::D, dst, src, shift));
+                self.emit(Inst::AluImm(AluOp::Sra, Size::D, dst, dst, shift));
+            }
+            Ty::I32 => {
+                self.emit(Inst::AluImm(AluOp::Add, Size::W, dst, src, 0))
+            }
+            _ => self.emit(Inst::Mv(dst, src)),
+        }
+    }
+
+    /// Zero-extend an integer of a machine type from its width.
+    fn zext(&mut self, dst: Reg, src: Reg, ty: Ty) {
+        match ty {
+            Ty::I8 => {
+                self.emit(Inst::AluImm(AluOp::And, Size::D, dst, src, 255))
+            }
+            Ty::I16 | Ty::I32 => {
+                let shift = 64 - ty.size(MODEL) as i64 * 8;
+                self.emit(Inst::AluImm(AluOp::Sll, Size::D, dst, src, shift));
+                self.emit(Inst::AluImm(AluOp::Srl, Size::D, dst, dst, shift));
+            }
+            _ => self.emit(Inst::Mv(dst, src)),
+        }
+    }
+
+    /// An integer operand zero-extended from its width into a new register.
+    fn unsigned(&mut self, operand: &Operand, ty: Ty) -> Reg {
+        let src = self.reg(operand);
+        let dst = self.temp(Class::Int);
+        self.zext(dst, src, ty);
+        dst
+    }
+
+    /// Load a value of a machine type from memory into a register.
+    fn load(&mut self, dst: Reg, ty: Ty, mem: Mem) {
+        self.emit(if ty.is_float() {
+            Inst::FLoad(prec(ty), dst, mem)
+        } else {
+            Inst::Load {
+                size: size(ty),
+                signed: true,
+                dst,
+                mem,
+            }
+        });
+    }
+
+    /// Store a register holding a value of a machine type to memory.
+    fn store(&mut self, src: Reg, ty: Ty, mem: Mem) {
+        self.emit(if ty.is_float() {
+            Inst::FStore(prec(ty), src, mem)
+        } else {
+            Inst::Store(size(ty), src, mem)
+        });
+    }
+
+    /// Copy bytes between memory locations.
+    fn copy(&mut self, dst: Mem, src: Mem, size: u64) {
+        let mut offset = 0;
+        for (chunk, ty) in
+            [(8, Ty::I64), (4, Ty::I32), (2, Ty::I16), (1, Ty::I8)]
+        {
+            while size - offset >= chunk {
+                let temp = self.temp(Class::Int);
+                self.load(temp, ty, src.offset(offset));
+                self.store(temp, ty, dst.offset(offset));
+                offset += chunk;
+            }
+        }
+    }
+
+    /// Load a part of an aggregate into a new register.
+    fn load_part(&mut self, mem: &Mem, part: &Part) -> Reg {
+        let mem = mem.offset(part.offset);
+        let reg = self.temp(class(part.ty));
+        if part.size == part.ty.size(MODEL) {
+            self.load(reg, part.ty, mem);
+            return reg;
+        }
+        // The end of an eightbyte is assembled from pieces of decreasing
+        // size, so as not to read past the aggregate.
+        let mut offset = 0;
+        for (piece, size) in [(4, Size::W), (2, Size::H), (1, Size::B)] {
+            if part.size - offset < piece {
+                continue;
+            }
+            let load = |dst| Inst::Load {
+                size,
+                signed: false,
+                dst,
+                mem: mem.offset(offset),
+            };
+            if offset == 0 {
+                self.emit(load(reg));
+            } else {
+                let temp = self.temp(Class::Int);
+                self.emit(load(temp));
+                let shift = offset as i64 * 8;
+                self.emit(Inst::AluImm(AluOp::Sll, Size::D, temp, temp, shift));
+                self.emit(Inst::Alu(AluOp::Or, Size::D, reg, reg, temp));
+            }
+            offset += piece;
+        }
+        reg
+    }
+
+    /// Store a part of an aggregate from a machine register.
+    ///
+    /// Eightbytes are stored whole, as stack slots are made of them.
+    fn store_part(&mut self, mem: &Mem, part: &Part, reg: u8) {
+        self.store(Reg::Phys(reg), part.ty, mem.offset(part.offset));
+    }
+
+    /// Copy a register into a machine register.
+    fn move_to(&mut self, reg: u8, value: Reg) {
+        let dst = Reg::Phys(reg);
+        self.emit(if reg >= F0 {
+            Inst::FMv(Prec::D, dst, value)
+        } else {
+            Inst::Mv(dst, value)
+        });
+    }
+
+    /// A scalar passed to or returned from another function, in a register:
+    /// unsigned integers narrower than 32 bits are zero-extended, as the
+    /// C type requires.
+    fn scalar_arg(&mut self, operand: &Operand, ty: &CType) -> Reg {
+        let mty = self.func.operand_ty(operand);
+        if matches!(mty, Ty::I8 | Ty::I16) && !signed(ty) {
+            self.unsigned(operand, mty)
+        } else {
+            self.reg(operand)
+        }
+    }
+
+    /// A scalar passed to another function in a part, as a register of the
+    /// class of the part.
+    fn scalar_part(&mut self, operand: &Operand, ty: &CType, loc: Loc) -> Reg {
+        let mty = self.func.operand_ty(operand);
+        if !mty.is_float() {
+            return self.scalar_arg(operand, ty);
+        }
+        let value = self.reg(operand);
+        match loc {
+            Loc::Reg(reg) if reg < F0 => {
+                let bits = self.temp(Class::Int);
+                self.emit(Inst::FMvToInt(prec(mty), bits, value));
+                bits
+            }
+            _ => value,
+        }
+    }
+}
+
+//----------- Instructions -----------------------------------------------------
+
+impl Isel<'_> {
+    /// Receive the parameters on entry to the function.
+    fn params(&mut self) {
+        let func = self.func;
+        if func.ty.variadic {
+            self.emit(Inst::SaveVarargs);
+        }
+        let mut args = Args::new(ARG_REGS);
+        if returned(self.types, &func.ty.ret).is_none() {
+            let sret = self.temp(Class::Int);
+            self.emit(Inst::Mv(sret, Reg::Phys(A0)));
+            self.sret = Some(sret);
+            args.ints = 1;
+        }
+
+        for (&param, ty) in func.params.iter().zip(&func.ty.params) {
+            let passed = args.place(self.types, ty, false);
+            if let Some(mty) = Ty::of(ty, MODEL) {
+                let dst = self.vreg(param);
+                match passed.parts[0].loc {
+                    Loc::Reg(reg) if reg >= F0 => {
+                        self.emit(Inst::FMv(prec(mty), dst, Reg::Phys(reg)));
+                    }
+                    Loc::Reg(reg) if mty.is_float() => {
+                        let src = Reg::Phys(reg);
+                        self.emit(Inst::FMvFromInt(prec(mty), dst, src));
+                    }
+                    Loc::Reg(reg) => self.normalize(dst, Reg::Phys(reg), mty),
+                    Loc::Stack(offset) => {
+                        self.load(dst, mty, stack_arg(FP, offset));
+                    }
+                }
+                continue;
+            }
+
+            let dst = Mem {
+                base: Base::Slot(self.slot(param)),
+                disp: 0,
+            };
+            if passed.by_ref {
+                let addr = self.temp(Class::Int);
+                match passed.parts[0].loc {
+                    Loc::Reg(reg) => self.emit(Inst::Mv(addr, Reg::Phys(reg))),
+                    Loc::Stack(offset) => {
+                        self.load(addr, Ty::Ptr, stack_arg(FP, offset));
+                    }
+                }
+                let src = Mem {
+                    base: Base::Reg(addr),
+                    disp: 0,
+                };
+                let size = self.types.size_of(ty);
+                self.copy(dst, src, size);
+                continue;
+            }
+            for part in &passed.parts {
+                match part.loc {
+                    Loc::Reg(reg) => self.store_part(&dst, part, reg),
+                    Loc::Stack(offset) => {
+                        let src = stack_arg(FP, offset);
+                        self.copy(dst.offset(part.offset), src, part.size);
+                    }
+                }
+            }
+        }
+        // The saved argument registers lie just below the stack arguments,
+        // so the variadic arguments follow each other in memory.
+        if func.ty.variadic {
+            self.varargs = Some(if args.ints < args.regs {
+                args.ints as i64 * 8 - SAVE_AREA as i64
+            } else {
+                args.stack as i64
+            });
+        }
+    }
+
+    /// Translate an instruction.
+    fn inst(&mut self, inst: &MirInst) {
+        match inst {
+            MirInst::Assign(dest, rvalue) => self.assign(*dest, rvalue),
+            MirInst::Store { addr, value } => {
+                let ty = self.func.operand_ty(value);
+                let value = self.reg(value);
+                let mem = self.mem(addr, 0);
+                self.store(value, ty, mem);
+            }
+            MirInst::Call {
+                dest,
+                callee,
+                args,
+                sig,
+                fixed,
+            } => self.call(*dest, callee, args, sig, *fixed),
+            MirInst::VaStart(addr) => {
+                let offset = self.varargs.expect("`va_start` is variadic");
+                let next = self.temp(Class::Int);
+                let base = Base::Reg(Reg::Phys(FP));
+                self.emit(Inst::Lea(next, Mem { base, disp: offset }));
+                let mem = self.mem(addr, 0);
+                self.store(next, Ty::Ptr, mem);
+            }
+        }
+    }
+
+    /// Translate an assignment to a register.
+    fn assign(&mut self, dest: Local, rvalue: &Rvalue) {
+        let ty = self.func.ty(dest);
+        let dst = self.vreg(dest);
+        match rvalue {
+            Rvalue::Use(Operand::Const(Const::Int(value, ty))) => {
+                self.emit(Inst::Li(dst, ty.wrap(*value)));
+            }
+            Rvalue::Use(value) => {
+                let value = self.reg(value);
+                if ty.is_float() {
+                    self.emit(Inst::FMv(prec(ty), dst, value));
+                } else {
+                    self.emit(Inst::Mv(dst, value));
+                }
+            }
+            Rvalue::Bin(op, a, b) => self.bin(*op, dst, ty, a, b),
+            Rvalue::Una(op, a) => match op {
+                UnaOp::Neg => {
+                    let zero = Operand::Const(Const::Int(0, ty));
+                    self.alu(AluOp::Sub, op_size(ty), dst, &zero, a);
+                    if matches!(ty, Ty::I8 | Ty::I16) {
+                        self.normalize(dst, dst, ty);
+                    }
+                }
+                UnaOp::Not => {
+                    let ones = Operand::Const(Const::Int(-1, ty));
+                    self.alu(AluOp::Xor, Size::D, dst, a, &ones);
+                }
+                UnaOp::FNeg => {
+                    let value = self.reg(a);
+                    self.emit(Inst::FNeg(prec(ty), dst, value));
+                }
+            },
+            Rvalue::Cast(op, a) => self.cast(*op, dst, ty, a),
+            Rvalue::Load(addr) => {
+                let mem = self.mem(addr, 0);
+                self.load(dst, ty, mem);
+            }
+            Rvalue::FieldAddr(base, id, field) => {
+                let offset = self.types.layout(*id).offsets[*field];
+                let mem = self.mem(base, offset as i64);
+                self.emit(Inst::Lea(dst, mem));
+            }
+            Rvalue::ElemAddr(base, index, elem) => {
+                let elem_size = self.types.size_of(elem) as i64;
+                if let Operand::Const(Const::Int(index, _)) = index {
+                    let mem = self.mem(base, index * elem_size);
+                    self.emit(Inst::Lea(dst, mem));
+                    return;
+                }
+                let index = self.reg(index);
+                let base = self.reg(base);
+                let offset = self.temp(Class::Int);
+                if elem_size > 0 && elem_size & (elem_size - 1) == 0 {
+                    let shift = elem_size.trailing_zeros() as i64;
+                    self.emit(Inst::AluImm(
+                        AluOp::Sll,
+                        Size::D,
+                        offset,
+                        index,
+                        shift,
+                    ));
+                } else {
+                    let elem_size =
+                        Operand::Const(Const::Int(elem_size, Ty::I64));
+                    let elem_size = self.reg(&elem_size);
+                    self.emit(Inst::Alu(
+                        AluOp::Mul,
+                        Size::D,
+                        offset,
+                        index,
+                        elem_size,
+                    ));
+                }
+                self.emit(Inst::Alu(AluOp::Add, Size::D, dst, base, offset));
+            }
+            Rvalue::Phi(_) => unreachable!("phis are removed before selection"),
+        }
+    }
+
+    /// Translate a binary operation.
+    fn bin(&mut self, op: BinOp, dst: Reg, ty: Ty, a: &Operand, b: &Operand) {
+        let size = op_size(ty);
+        let narrow = matches!(ty, Ty::I8 | Ty::I16);
+        let falu = match op {
+            BinOp::FAdd => Some(FAluOp::Add),
+            BinOp::FSub => Some(FAluOp::Sub),
+            BinOp::FMul => Some(FAluOp::Mul),
+            BinOp::FDiv => Some(FAluOp::Div),
+            _ => None,
+        };
+        if let Some(falu) = falu {
+            let (a, b) = (self.reg(a), self.reg(b));
+            self.emit(Inst::FAlu(falu, prec(ty), dst, a, b));
+            return;
+        }
+
+        let alu = match op {
+            BinOp::Add => AluOp::Add,
+            BinOp::Sub => AluOp::Sub,
+            BinOp::Mul => AluOp::Mul,
+            BinOp::SDiv => AluOp::Div,
+            BinOp::SRem => AluOp::Rem,
+            BinOp::Shl => AluOp::Sll,
+            BinOp::AShr => AluOp::Sra,
+            BinOp::And | BinOp::Or | BinOp::Xor => {
+                let alu = match op {
+                    BinOp::And => AluOp::And,
+                    BinOp::Or => AluOp::Or,
+                    _ => AluOp::Xor,
+                };
+                self.alu(alu, Size::D, dst, a, b);
+                return;
+            }
+            BinOp::UDiv | BinOp::URem | BinOp::LShr if narrow => {
+                // Narrow operands are zero-extended to 64 bits first.
+                let a = self.unsigned(a, ty);
+                let b = match op {
+                    BinOp::LShr => self.reg(b),
+                    _ => self.unsigned(b, ty),
+                };
+                let alu = match op {
+                    BinOp::UDiv => AluOp::Divu,
+                    BinOp::URem => AluOp::Remu,
+                    _ => AluOp::Srl,
+                };
+                self.emit(Inst::Alu(alu, Size::D, dst, a, b));
+                self.normalize(dst, dst, ty);
+                return;
+            }
+            BinOp::UDiv => AluOp::Divu,
+            BinOp::URem => AluOp::Remu,
+            BinOp::LShr => AluOp::Srl,
+            _ => return self.compare(op, dst, a, b),
+        };
+        self.alu(alu, size, dst, a, b);
+        if narrow && alu != AluOp::Sra {
+            self.normalize(dst, dst, ty);
+        }
+    }
+
+    /// Translate a comparison.
+    fn compare(&mut self, op: BinOp, dst: Reg, a: &Operand, b: &Operand) {
+        let ty = self.func.operand_ty(a);
+        if ty.is_float() {
+            let (cmp, a, b) = match op {
+                BinOp::FEq | BinOp::FNe => (FCmpOp::Eq, a, b),
+                BinOp::FLt => (FCmpOp::Lt, a, b),
+                BinOp::FLe => (FCmpOp::Le, a, b),
+                BinOp::FGt => (FCmpOp::Lt, b, a),
+                BinOp::FGe => (FCmpOp::Le, b, a),
+                _ => unreachable!("not a comparison"),
+            };
+            let (a, b) = (self.reg(a), self.reg(b));
+            if op == BinOp::FNe {
+                let eq = self.temp(Class::Int);
+                self.emit(Inst::FCmp(cmp, prec(ty), eq, a, b));
+                self.emit(Inst::AluImm(AluOp::Xor, Size::D, dst, eq, 1));
+            } else {
+                self.emit(Inst::FCmp(cmp, prec(ty), dst, a, b));
+            }
+            return;
+        }
+
+        if let BinOp::Eq | BinOp::Ne = op {
+            // The operands are equal if their difference is zero.
+            let diff = match self.imm(b) {
+                Some(0) => self.reg(a),
+                _ => {
+                    let diff = self.temp(Class::Int);
+                    self.alu(AluOp::Xor, Size::D, diff, a, b);
+                    diff
+                }
+            };
+            self.emit(if op == BinOp::Eq {
+                Inst::AluImm(AluOp::Sltu, Size::D, dst, diff, 1)
+            } else {
+                Inst::Alu(AluOp::Sltu, Size::D, dst, Reg::Phys(ZERO), diff)
+            });
+            return;
+        }
+
+        // Everything is a less-than, maybe with the operands swapped and the
+        // result inverted.
+        let (alu, swap, invert) = match op {
+            BinOp::SLt => (AluOp::Slt, false, false),
+            BinOp::SGt => (AluOp::Slt, true, false),
+            BinOp::SLe => (AluOp::Slt, true, true),
+            BinOp::SGe => (AluOp::Slt, false, true),
+            BinOp::ULt => (AluOp::Sltu, false, false),
+            BinOp::UGt => (AluOp::Sltu, true, false),
+            BinOp::ULe => (AluOp::Sltu, true, true),
+            BinOp::UGe => (AluOp::Sltu, false, true),
+            _ => unreachable!("not a comparison"),
+        };
+        let (a, b) = if swap { (b, a) } else { (a, b) };
+        if invert {
+            let less = self.temp(Class::Int);
+            self.alu(alu, Size::D, less, a, b);
+            self.emit(Inst::AluImm(AluOp::Xor, Size::D, dst, less, 1));
+        } else {
+            self.alu(alu, Size::D, dst, a, b);
+        }
+    }
+
+    /// Translate a conversion.
+    fn cast(&mut self, op: CastOp, dst: Reg, to: Ty, a: &Operand) {
+        let from = self.func.operand_ty(a);
+        match op {
+            CastOp::SExt | CastOp::IntToPtr => {
+                let value = self.reg(a);
+                self.emit(Inst::Mv(dst, value));
+            }
+            CastOp::ZExt => {
+                let value = self.reg(a);
+                self.zext(dst, value, from);
+            }
+            CastOp::Trunc | CastOp::PtrToInt => {
+                let value = self.reg(a);
+                self.normalize(dst, value, to);
+            }
+            CastOp::SToF | CastOp::UToF => {
+                let signed = op == CastOp::SToF;
+                // Narrow integers are converted from 64 bits, zero-extended
+                // if unsigned.
+                let (signed, size, src) = match from {
+                    Ty::I8 | Ty::I16 if !signed => {
+                        (true, Size::D, self.unsigned(a, from))
+                    }
+                    Ty::I32 => (signed, Size::W, self.reg(a)),
+                    _ => (signed, Size::D, self.reg(a)),
+                };
+                self.emit(Inst::CvtIntToFloat {
+                    signed,
+                    size,
+                    prec: prec(to),
+                    dst,
+                    src,
+                });
+            }
+            CastOp::FToS | CastOp::FToU => {
+                let src = self.reg(a);
+                self.emit(Inst::CvtFloatToInt {
+                    signed: op == CastOp::FToS,
+                    prec: prec(from),
+                    size: op_size(to),
+                    dst,
+                    src,
+                });
+                if matches!(to, Ty::I8 | Ty::I16) {
+                    self.normalize(dst, dst, to);
+                }
+            }
+            CastOp::FConv => {
+                let value = self.reg(a);
+                if from == to {
+                    self.emit(Inst::FMv(prec(to), dst, value));
+                } else {
+                    self.emit(Inst::CvtFloat(prec(from), dst, value));
+                }
+            }
+        }
+    }
+
+    /// Translate a call.
+    fn call(
+        &mut self,
+        dest: Option<Local>,
+        callee: &Operand,
+        args: &[Operand],
+        sig: &FnType,
+        fixed: usize,
+    ) {
+        let rets = returned(self.types, &sig.ret);
+        let mut places = Args::new(ARG_REGS);
+
+        // Stack arguments are stored, and register arguments computed, before
+        // any argument register is written.
+        let mut moves = Vec::new();
+        if rets.is_none() {
+            let dest = dest.expect("aggregate results have a destination");
+            let addr = self.reg(&Operand::Addr(dest));
+            moves.push((A0, addr));
+            places.ints = 1;
+        }
+        for (i, (arg, ty)) in args.iter().zip(&sig.params).enumerate() {
+            let passed = places.place(self.types, ty, i >= fixed);
+            let values: Vec<Reg> = if Ty::of(ty, MODEL).is_some() {
+                vec![self.scalar_part(arg, ty, passed.parts[0].loc)]
+            } else if passed.by_ref {
+                // The callee may change its copy of the aggregate.
+                let size = self.types.size_of(ty);
+                let align = self.types.align_of(ty).max(8);
+                let slot = self.out.new_slot(size.next_multiple_of(8), align);
+                let copy = Mem {
+                    base: Base::Slot(slot),
+                    disp: 0,
+                };
+                let src = self.mem(arg, 0);
+                self.copy(copy.clone(), src, size);
+                let addr = self.temp(Class::Int);
+                self.emit(Inst::Lea(addr, copy));
+                vec![addr]
+            } else {
+                let mem = self.mem(arg, 0);
+                let parts = passed.parts.iter();
+                parts.map(|part| self.load_part(&mem, part)).collect()
+            };
+            for (part, value) in passed.parts.iter().zip(values) {
+                match part.loc {
+                    Loc::Reg(reg) => moves.push((reg, value)),
+                    Loc::Stack(offset) => {
+                        // Integers are passed extended to 64 bits.
+                        let ty =
+                            if part.ty.is_float() { part.ty } else { Ty::I64 };
+                        self.store(value, ty, stack_arg(SP, offset));
+                    }
+                }
+            }
+        }
+        self.out.outgoing = self.out.outgoing.max(places.stack);
+        let callee = match callee {
+            Operand::Const(Const::Symbol(name)) => Callee::Symbol(name.clone()),
+            _ => Callee::Reg(self.reg(callee)),
+        };
+
+        let mut used = Vec::new();
+        for (reg, value) in moves {
+            self.move_to(reg, value);
+            used.push(reg);
+        }
+        self.emit(Inst::Call {
+            callee,
+            args: used,
+            rets: vec![A0, A0 + 1, FA0, FA0 + 1],
+        });
+
+        let (Some(dest), Some(rets)) = (dest, rets) else {
+            return;
+        };
+        if self.func.local(dest).memory {
+            let dst = Mem {
+                base: Base::Slot(self.slot(dest)),
+                disp: 0,
+            };
+            for part in &rets {
+                let Loc::Reg(reg) = part.loc else {
+                    unreachable!("results are returned in registers");
+                };
+                self.store_part(&dst, part, reg);
+            }
+        } else if let [part] = &rets[..] {
+            let Loc::Reg(reg) = part.loc else {
+                unreachable!("results are returned in registers");
+            };
+            let dst = self.vreg(dest);
+            if reg >= F0 {
+                self.emit(Inst::FMv(prec(part.ty), dst, Reg::Phys(reg)));
+            } else {
+                self.normalize(dst, Reg::Phys(reg), part.ty);
+            }
+        }
+    }
+
+    /// Translate a terminator.
+    fn term(&mut self, term: &Terminator) {
+        match term {
+            Terminator::Jump(target) => self.emit(Inst::J(target.0 as usize)),
+            Terminator::Branch(cond, then, r#else) => {
+                // A block jumping to a single block must end with nothing
+                // but the jump, before which moves may be inserted.
+                if then != r#else {
+                    let cond = self.reg(cond);
+                    let zero = Reg::Phys(ZERO);
+                    let then = then.0 as usize;
+                    self.emit(Inst::Branch(Cond::Ne, cond, zero, then));
+                }
+                self.emit(Inst::J(r#else.0 as usize));
+            }
+            Terminator::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                let ty = self.func.operand_ty(value);
+                let value = self.reg(value);
+                for &(case, target) in cases {
+                    if target == *default {
+                        continue;
+                    }
+                    let case = self.reg(&Operand::Const(Const::Int(case, ty)));
+                    let target = target.0 as usize;
+                    self.emit(Inst::Branch(Cond::Eq, value, case, target));
+                }
+                self.emit(Inst::J(default.0 as usize));
+            }
+            Terminator::Return(value) => {
+                let ret = &self.func.ty.ret;
+                let rets = match (value, returned(self.types, ret)) {
+                    (None, _) => Vec::new(),
+                    (Some(value), Some(parts))
+                        if Ty::of(ret, MODEL).is_some() =>
+                    {
+                        let Loc::Reg(reg) = parts[0].loc else {
+                            unreachable!("results are returned in registers");
+                        };
+                        let value = self.scalar_part(value, ret, parts[0].loc);
+                        self.move_to(reg, value);
+                        vec![reg]
+                    }
+                    (Some(addr), Some(parts)) => {
+                        let mem = self.mem(addr, 0);
+                        let mut moves = Vec::new();
+                        for part in &parts {
+                            let Loc::Reg(reg) = part.loc else {
+                                unreachable!(
+                                    "results are returned in registers"
+                                );
+                            };
+                            moves.push((reg, self.load_part(&mem, part)));
+                        }
+                        for &(reg, value) in &moves {
+                            self.move_to(reg, value);
+                        }
+                        moves.into_iter().map(|(reg, _)| reg).collect()
+                    }
+                    (Some(addr), None) => {
+                        // The result is copied to the caller's memory, whose
+                        // address is returned.
+                        let sret = self.sret.unwrap();
+                        let src = self.mem(addr, 0);
+                        let dst = Mem {
+                            base: Base::Reg(sret),
+                            disp: 0,
+                        };
+                        let size = self.types.size_of(ret);
+                        self.copy(dst, src, size);
+                        self.move_to(A0, sret);
+                        vec![A0]
+                    }
+                };
+                self.emit(Inst::Ret(rets));
+            }
+            Terminator::Unreachable => self.emit(Inst::Unimp),
+        }
+    }
+}
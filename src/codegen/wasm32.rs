@@ -0,0 +1,270 @@
+//! The WebAssembly backend.
+//!
+//! A module is written as a single WebAssembly text (WAT) module, for a host
+//! to instantiate.  Functions declared but not defined are imported from the
+//! `env` module, as are the addresses of undefined variables (as immutable
+//! `i32` globals), and the functions that are not `static` are exported
+//! under their own names, along with the linear memory.
+//!
+//! C types are laid out in the ILP32 data model, as by other compilers for
+//! the target, so that `long` and pointers take up 4 bytes.  As values,
+//! pointers are `i32`s, and integers narrower than `int` are kept
+//! sign-extended in `i32`s.  A function pointer is an index into the table
+//! of all functions, in which 0 is left empty.
+//!
+//! The linear memory holds the global variables from address
+//! [`DATA_BASE`], followed by the stack, which grows down from the address
+//! in the `__stack_pointer` global to the end of the data.  The heap may
+//! start at `__heap_base`, past the stack.  A function whose memory locals
+//! need it moves the stack pointer down by the size of its frame on entry,
+//! and restores it on return.
+//!
+//! Functions are called with the WebAssembly parameters of their scalar
+//! parameters.  An aggregate parameter is passed as the address of a copy
+//! made by the caller, and an aggregate result is written to an address
+//! passed before the other arguments.  A variadic function takes the
+//! address of a buffer holding its variadic arguments as an extra parameter;
+//! each of them is stored at its alignment, and aggregates are stored as
+//! addresses of copies.  This is the convention of clang, so that functions
+//! can be imported from and exported to C compiled by it.
+//!
+//! The translation of the blocks of a function into structured control flow
+//! is left to [`stackify`], and that of their instructions to [`emit`].
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::ctype::{CType, DataModel, FnType, TypeTable};
+use crate::mir::{GlobalInit, Module, Ty, ssa};
+
+mod emit;
+mod stackify;
+
+/// The data model of the target.
+const MODEL: DataModel = DataModel::Ilp32;
+
+/// The address of the first global variable.
+///
+/// Keeping the first kilobyte of memory unused leaves null pointers pointing
+/// nowhere useful.
+pub const DATA_BASE: u64 = 1024;
+
+/// The size of the stack in bytes.
+pub const STACK_SIZE: u64 = 1 << 20;
+
+/// The size of a page of linear memory.
+const PAGE_SIZE: u64 = 1 << 16;
+
+/// Compile a module to WebAssembly text.
+pub fn compile(module: &Module) -> String {
+    let mut ctx = Context {
+        types: &module.types,
+        symbols: HashMap::new(),
+    };
+    let mut out = String::from("(module\n");
+
+    // Imports come before any other definition.
+    let mut table = Vec::new();
+    for ext in &module.externs {
+        let name = &ext.name;
+        let import = match ext.ty.fn_type() {
+            Some(ty) => {
+                table.push(name.as_str());
+                let index = table.len() as u64;
+                ctx.symbols.insert(name.clone(), Symbol::Func(index));
+                format!("func ${name}{}", signature(ty))
+            }
+            None => {
+                ctx.symbols.insert(name.clone(), Symbol::Import);
+                format!("global ${name} i32")
+            }
+        };
+        writeln!(out, "  (import \"env\" \"{name}\" ({import}))").unwrap();
+    }
+    for func in &module.functions {
+        table.push(func.name.as_str());
+        let index = table.len() as u64;
+        ctx.symbols.insert(func.name.clone(), Symbol::Func(index));
+    }
+
+    let mut address = DATA_BASE;
+    for global in &module.globals {
+        let align = ctx.types.align_of(&global.ty).max(1);
+        address = address.next_multiple_of(align);
+        ctx.symbols
+            .insert(global.name.clone(), Symbol::Data(address));
+        address += ctx.types.size_of(&global.ty);
+    }
+    let stack = address.next_multiple_of(16) + STACK_SIZE;
+    writeln!(
+        out,
+        "  (memory (export \"memory\") {})",
+        stack.div_ceil(PAGE_SIZE)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  (global $__stack_pointer (mut i32) (i32.const {stack}))"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  (global (export \"__heap_base\") i32 (i32.const {stack}))"
+    )
+    .unwrap();
+    writeln!(out, "  (table {} funcref)", table.len() + 1).unwrap();
+    if !table.is_empty() {
+        let funcs: Vec<_> =
+            table.iter().map(|name| format!("${name}")).collect();
+        writeln!(out, "  (elem (i32.const 1) func {})", funcs.join(" "))
+            .unwrap();
+    }
+
+    // Addresses of imported variables are only known once the module is
+    // instantiated, and are stored by a start function.
+    let mut imported = Vec::new();
+    for global in &module.globals {
+        if let Some(init) = &global.init {
+            let Symbol::Data(address) = ctx.symbols[&global.name] else {
+                unreachable!("global variables are data");
+            };
+            ctx.data(&mut out, &mut imported, address, init);
+        }
+    }
+    if !imported.is_empty() {
+        out.push_str("  (func $__wasm_init\n");
+        for (address, symbol, addend) in imported {
+            writeln!(
+                out,
+                "    i32.const {address}\n    global.get ${symbol}\n    \
+                 i32.const {addend}\n    i32.add\n    i32.store"
+            )
+            .unwrap();
+        }
+        out.push_str("  )\n  (start $__wasm_init)\n");
+    }
+
+    for func in &module.functions {
+        let mut func = func.clone();
+        ssa::destruct(&mut func);
+        emit::function(&mut out, &ctx, &func);
+    }
+    out.push_str(")\n");
+    out
+}
+
+//----------- Context ----------------------------------------------------------
+
+/// What the translation of a module needs to know about it.
+pub struct Context<'a> {
+    /// The structure types of the module.
+    types: &'a TypeTable,
+
+    /// What every symbol of the module names.
+    symbols: HashMap<String, Symbol>,
+}
+
+/// What a symbol names.
+#[derive(Copy, Clone)]
+enum Symbol {
+    /// A function, with its index in the table.
+    Func(u64),
+
+    /// A global variable, with its address.
+    Data(u64),
+
+    /// An imported variable, whose address is in the global of the same
+    /// name.
+    Import,
+}
+
+impl Context<'_> {
+    /// Write out the initial contents of a global variable at an address,
+    /// collecting the addresses of imported variables to store in it as
+    /// triples of an address, a symbol and an addend.
+    fn data<'a>(
+        &self,
+        out: &mut String,
+        imported: &mut Vec<(u64, &'a str, i64)>,
+        address: u64,
+        init: &'a GlobalInit,
+    ) {
+        let mut bytes = init.bytes.clone();
+        for reloc in &init.relocs {
+            let offset = reloc.offset as usize;
+            let value = match self.symbols[&reloc.symbol] {
+                Symbol::Func(index) => index as i64 + reloc.addend,
+                Symbol::Data(target) => target as i64 + reloc.addend,
+                Symbol::Import => {
+                    let symbol = reloc.symbol.as_str();
+                    imported.push((
+                        address + reloc.offset,
+                        symbol,
+                        reloc.addend,
+                    ));
+                    continue;
+                }
+            };
+            bytes.resize(bytes.len().max(offset + 4), 0);
+            bytes[offset..offset + 4]
+                .copy_from_slice(&(value as u32).to_le_bytes());
+        }
+        if bytes.iter().all(|&byte| byte == 0) {
+            return;
+        }
+        writeln!(out, "  (data (i32.const {address}) \"{}\")", string(&bytes))
+            .unwrap();
+    }
+}
+
+//----------- Types ------------------------------------------------------------
+
+/// The WebAssembly type of a machine type.
+fn val(ty: Ty) -> &'static str {
+    match ty {
+        Ty::I8 | Ty::I16 | Ty::I32 | Ty::Ptr => "i32",
+        Ty::I64 => "i64",
+        Ty::F32 => "f32",
+        Ty::F64 => "f64",
+    }
+}
+
+/// The WebAssembly type of the value of a C parameter, which is an address
+/// for aggregates.
+fn param(ty: &CType) -> &'static str {
+    Ty::of(ty, MODEL).map_or("i32", val)
+}
+
+/// The parameters and result of a function type, as written after the name
+/// of a function or in `call_indirect`.
+fn signature(ty: &FnType) -> String {
+    let mut params = Vec::new();
+    if Ty::of(&ty.ret, MODEL).is_none() && ty.ret != CType::Void {
+        params.push("i32");
+    }
+    params.extend(ty.params.iter().map(param));
+    if ty.variadic {
+        params.push("i32");
+    }
+    let mut out = String::new();
+    if !params.is_empty() {
+        write!(out, " (param {})", params.join(" ")).unwrap();
+    }
+    if let Some(ret) = Ty::of(&ty.ret, MODEL) {
+        write!(out, " (result {})", val(ret)).unwrap();
+    }
+    out
+}
+
+/// Bytes as the contents of a WebAssembly string.
+fn string(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => write!(out, "\\{byte:02x}").unwrap(),
+            b' '..=b'~' => out.push(byte as char),
+            _ => write!(out, "\\{byte:02x}").unwrap(),
+        }
+    }
+    out
+}
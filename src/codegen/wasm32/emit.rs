@@ -0,0 +1,768 @@
+//! Translation of functions.
+//!
+//! Every register local is a WebAssembly local named after its number,
+//! `$lN`, as are aggregate parameters, which hold the address of the copy
+//! passed by the caller.  The other memory locals live in the frame, at
+//! offsets from `$fp`; `$sp` holds the stack pointer of the caller, which is
+//! restored on return.  Above the memory locals is an area shared by calls,
+//! for the copies of their aggregate arguments, the results they return in
+//! memory and their variadic arguments.
+
+use super::stackify::{Exit, Item, stackify};
+use super::*;
+use crate::mir::{
+    BinOp, BlockId, CastOp, Const, Function, Inst, Local, Operand, Rvalue,
+    Terminator, UnaOp,
+};
+
+/// The state of the translation of a function.
+struct Emitter<'a> {
+    /// The module-wide context.
+    ctx: &'a Context<'a>,
+
+    /// The function being translated.
+    func: &'a Function,
+
+    /// The offset in the frame of every memory local that has a slot.
+    slots: Vec<Option<u64>>,
+
+    /// Whether the function has a frame.
+    frame: bool,
+
+    /// The offset of the area shared by calls.
+    calls: u64,
+
+    /// The largest size of the area shared by calls.
+    calls_size: u64,
+
+    /// The translated body.
+    body: String,
+
+    /// The nesting depth of the structured code being translated.
+    depth: usize,
+}
+
+/// Write out the definition of a function.
+pub fn function(out: &mut String, ctx: &Context, func: &Function) {
+    let mut slots = vec![None; func.locals.len()];
+    let mut size = 0u64;
+    for (i, decl) in func.locals.iter().enumerate() {
+        if decl.memory && !func.params.contains(&Local(i as u32)) {
+            size = size.next_multiple_of(ctx.types.align_of(&decl.ty).max(1));
+            slots[i] = Some(size);
+            size += ctx.types.size_of(&decl.ty);
+        }
+    }
+    // Calls need room in the frame for anything passed in memory.
+    let calls = func
+        .blocks
+        .iter()
+        .flat_map(|block| &block.insts)
+        .any(|inst| {
+            matches!(inst, Inst::Call { sig, .. } if sig.variadic
+            || !sig.params.iter().chain([&sig.ret]).all(|ty| {
+                Ty::of(ty, MODEL).is_some() || *ty == CType::Void
+            }))
+        });
+
+    let items = stackify(func);
+    let mut emitter = Emitter {
+        ctx,
+        func,
+        frame: calls || slots.iter().any(Option::is_some),
+        slots,
+        calls: size.next_multiple_of(16),
+        calls_size: 0,
+        body: String::new(),
+        depth: 2,
+    };
+    emitter.items(&items);
+
+    let export = match func.local {
+        true => String::new(),
+        false => format!(" (export \"{}\")", func.name),
+    };
+    write!(out, "  (func ${}{export}", func.name).unwrap();
+    if Ty::of(&func.ty.ret, MODEL).is_none() && func.ty.ret != CType::Void {
+        out.push_str(" (param $sret i32)");
+    }
+    for &local in &func.params {
+        let ty = param(&func.local(local).ty);
+        write!(out, " (param $l{} {ty})", local.0).unwrap();
+    }
+    if func.ty.variadic {
+        out.push_str(" (param $va i32)");
+    }
+    let ret = Ty::of(&func.ty.ret, MODEL);
+    if let Some(ret) = ret {
+        write!(out, " (result {})", val(ret)).unwrap();
+    }
+    out.push('\n');
+
+    // Only the locals still mentioned are declared.
+    let mut used = vec![false; func.locals.len()];
+    for block in &func.blocks {
+        let operands = block.insts.iter().flat_map(Inst::operands);
+        for operand in operands.chain(block.term.operands()) {
+            if let Operand::Local(local) = operand {
+                used[local.0 as usize] = true;
+            }
+        }
+        for dest in block.insts.iter().filter_map(Inst::dest) {
+            used[dest.0 as usize] = true;
+        }
+    }
+    for (i, decl) in func.locals.iter().enumerate() {
+        if !used[i] || decl.memory || func.params.contains(&Local(i as u32)) {
+            continue;
+        }
+        if let Some(ty) = Ty::of(&decl.ty, MODEL) {
+            writeln!(out, "    (local $l{i} {})", val(ty)).unwrap();
+        }
+    }
+    if matches!(items[..], [Item::Dispatch(_)]) {
+        writeln!(out, "    (local $next i32)").unwrap();
+    }
+    if emitter.frame {
+        let size = (emitter.calls + emitter.calls_size).next_multiple_of(16);
+        writeln!(
+            out,
+            "    (local $sp i32)\n    (local $fp i32)\n    \
+             global.get $__stack_pointer\n    local.tee $sp\n    \
+             i32.const {size}\n    i32.sub\n    local.tee $fp\n    \
+             global.set $__stack_pointer"
+        )
+        .unwrap();
+    }
+    out.push_str(&emitter.body);
+    // Every path through the body ends with a branch or a return, but
+    // a function with a result must still appear to end with one.
+    if ret.is_some() {
+        out.push_str("    unreachable\n");
+    }
+    out.push_str("  )\n");
+}
+
+impl Emitter<'_> {
+    /// Add an instruction.
+    fn line(&mut self, inst: impl AsRef<str>) {
+        let indent = self.depth * 2;
+        writeln!(self.body, "{:indent$}{}", "", inst.as_ref()).unwrap();
+    }
+
+    /// Push the value of an operand.
+    fn get(&mut self, operand: &Operand) {
+        match operand {
+            Operand::Local(local) => {
+                self.line(format!("local.get $l{}", local.0))
+            }
+            Operand::Const(Const::Int(value, ty)) => self.int(*value, *ty),
+            Operand::Const(Const::Float(value, ty)) => {
+                let value = float(*value, *ty);
+                self.line(format!("{}.const {value}", val(*ty)))
+            }
+            Operand::Const(Const::Symbol(name)) => {
+                match self.ctx.symbols[name] {
+                    Symbol::Func(index) => {
+                        self.line(format!("i32.const {index}"))
+                    }
+                    Symbol::Data(address) => {
+                        self.line(format!("i32.const {address}"))
+                    }
+                    Symbol::Import => self.line(format!("global.get ${name}")),
+                }
+            }
+            Operand::Addr(local) => self.addr(*local),
+        }
+    }
+
+    /// Push the value of an operand used as a machine type, which only
+    /// differs from its own for integer constants.
+    fn get_as(&mut self, operand: &Operand, ty: Ty) {
+        match operand {
+            Operand::Const(Const::Int(value, _)) => self.int(*value, ty),
+            operand => self.get(operand),
+        }
+    }
+
+    /// Push an integer constant of a machine type.
+    fn int(&mut self, value: i64, ty: Ty) {
+        match ty {
+            Ty::F32 | Ty::F64 => {
+                let value = float(value as f64, ty);
+                self.line(format!("{}.const {value}", val(ty)))
+            }
+            Ty::I64 => self.line(format!("i64.const {value}")),
+            ty => self.line(format!("i32.const {}", ty.wrap(value) as i32)),
+        }
+    }
+
+    /// Push the address of a memory local.
+    fn addr(&mut self, local: Local) {
+        match self.slots[local.0 as usize] {
+            Some(offset) => self.frame_addr(offset),
+            None => self.line(format!("local.get $l{}", local.0)),
+        }
+    }
+
+    /// Push an address in the frame.
+    fn frame_addr(&mut self, offset: u64) {
+        assert!(self.frame, "frame used by a function without one");
+        self.line("local.get $fp");
+        if offset != 0 {
+            self.line(format!("i32.const {offset}"));
+            self.line("i32.add");
+        }
+    }
+
+    /// Reserve room in the area of a call, returning its offset in the
+    /// frame.
+    fn reserve(&mut self, used: &mut u64, size: u64, align: u64) -> u64 {
+        let offset = used.next_multiple_of(align.max(1));
+        *used = offset + size;
+        self.calls_size = self.calls_size.max(*used);
+        self.calls + offset
+    }
+
+    /// Copy an aggregate between the addresses on the stack.
+    fn memcpy(&mut self, ty: &CType) {
+        self.line(format!("i32.const {}", self.ctx.types.size_of(ty)));
+        self.line("memory.copy");
+    }
+
+    /// Load a value of a machine type from the address on the stack.
+    fn load(&mut self, ty: Ty) {
+        self.line(match ty {
+            Ty::I8 => "i32.load8_s",
+            Ty::I16 => "i32.load16_s",
+            Ty::I32 | Ty::Ptr => "i32.load",
+            Ty::I64 => "i64.load",
+            Ty::F32 => "f32.load",
+            Ty::F64 => "f64.load",
+        });
+    }
+
+    /// Store a value of a machine type to an address, both on the stack.
+    fn store(&mut self, ty: Ty) {
+        self.line(match ty {
+            Ty::I8 => "i32.store8",
+            Ty::I16 => "i32.store16",
+            Ty::I32 | Ty::Ptr => "i32.store",
+            Ty::I64 => "i64.store",
+            Ty::F32 => "f32.store",
+            Ty::F64 => "f64.store",
+        });
+    }
+
+    /// Sign-extend an `i32` on the stack from the width of an integer type.
+    fn normalize(&mut self, ty: Ty) {
+        match ty {
+            Ty::I8 => self.line("i32.extend8_s"),
+            Ty::I16 => self.line("i32.extend16_s"),
+            _ => {}
+        }
+    }
+
+    /// Zero-extend an `i32` on the stack from the width of an integer type.
+    fn mask(&mut self, ty: Ty) {
+        match ty {
+            Ty::I8 => self.line("i32.const 255"),
+            Ty::I16 => self.line("i32.const 65535"),
+            _ => return,
+        }
+        self.line("i32.and");
+    }
+
+    /// Convert an integer or pointer on the stack to another type.
+    fn resize(&mut self, from: Ty, to: Ty, signed: bool) {
+        let signed = signed && from != Ty::Ptr;
+        match (from == Ty::I64, to == Ty::I64) {
+            (false, true) => {
+                if !signed {
+                    self.mask(from);
+                }
+                self.line(match signed {
+                    true => "i64.extend_i32_s",
+                    false => "i64.extend_i32_u",
+                });
+            }
+            (true, false) => {
+                self.line("i32.wrap_i64");
+                self.normalize(to);
+            }
+            (true, true) => {}
+            (false, false) => {
+                if !signed && bits(from) < bits(to) {
+                    self.mask(from);
+                }
+                self.normalize(to);
+            }
+        }
+    }
+}
+
+/// The number of bits of an integer or pointer.
+fn bits(ty: Ty) -> u64 {
+    ty.size(MODEL) * 8
+}
+
+/// A floating-point constant of a machine type.
+fn float(value: f64, ty: Ty) -> String {
+    let value = match ty {
+        Ty::F32 => format!("{:?}", value as f32),
+        _ => format!("{value:?}"),
+    };
+    match value.as_str() {
+        "NaN" => "nan".to_string(),
+        _ => value,
+    }
+}
+
+//----------- Control flow -----------------------------------------------------
+
+impl Emitter<'_> {
+    /// Translate structured code.
+    fn items(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Block(id, items) => {
+                    self.line(format!("block ${id}"));
+                    self.nested(items);
+                }
+                Item::Loop(id, items) => {
+                    self.line(format!("loop $loop{}", id.0));
+                    self.nested(items);
+                }
+                Item::Dispatch(items) => {
+                    self.line("loop $dispatch");
+                    self.nested(items);
+                }
+                Item::Table(blocks) => {
+                    let labels: Vec<_> =
+                        blocks.iter().map(|id| format!("${id}")).collect();
+                    self.line("local.get $next");
+                    self.line(format!("br_table {}", labels.join(" ")));
+                }
+                Item::Basic(id, exits) => self.block(*id, exits),
+            }
+        }
+    }
+
+    /// Translate structured code nested in a `block` or `loop`, and end it.
+    fn nested(&mut self, items: &[Item]) {
+        self.depth += 1;
+        self.items(items);
+        self.depth -= 1;
+        self.line("end");
+    }
+
+    /// Translate an edge out of a block.
+    fn exit(&mut self, exit: &Exit) {
+        match exit {
+            Exit::Forward(target) => self.line(format!("br ${target}")),
+            Exit::Back(target) => self.line(format!("br $loop{}", target.0)),
+            Exit::Inline(items) => self.items(items),
+            Exit::Dispatch(target) => {
+                self.line(format!("i32.const {}", target.0));
+                self.line("local.set $next");
+                self.line("br $dispatch");
+            }
+        }
+    }
+
+    /// Translate a block, with where the edges of its terminator go.
+    fn block(&mut self, id: BlockId, exits: &[Exit]) {
+        self.line(format!(";; {id}"));
+        let block = self.func.block(id);
+        for inst in &block.insts {
+            self.inst(inst);
+        }
+        match &block.term {
+            Terminator::Jump(_) => self.exit(&exits[0]),
+            Terminator::Branch(_, then, r#else) if then == r#else => {
+                self.exit(&exits[0])
+            }
+            Terminator::Branch(cond, ..) => {
+                self.test(cond);
+                self.line("if");
+                self.depth += 1;
+                self.exit(&exits[0]);
+                self.depth -= 1;
+                self.line("else");
+                self.depth += 1;
+                self.exit(&exits[1]);
+                self.depth -= 1;
+                self.line("end");
+            }
+            Terminator::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                let ty = self.func.operand_ty(value);
+                let cases =
+                    cases.iter().filter(|(_, target)| target != default);
+                for ((case, _), exit) in cases.zip(exits) {
+                    self.get(value);
+                    self.int(*case, ty);
+                    self.line(format!("{}.eq", val(ty)));
+                    self.line("if");
+                    self.depth += 1;
+                    self.exit(exit);
+                    self.depth -= 1;
+                    self.line("end");
+                }
+                self.exit(exits.last().unwrap());
+            }
+            Terminator::Return(value) => self.ret(value.as_ref()),
+            Terminator::Unreachable => self.line("unreachable"),
+        }
+    }
+
+    /// Push whether a value is non-zero, as an `i32`.
+    fn test(&mut self, value: &Operand) {
+        let ty = self.func.operand_ty(value);
+        self.get(value);
+        match ty {
+            Ty::I64 => {
+                self.line("i64.eqz");
+                self.line("i32.eqz");
+            }
+            Ty::F32 | Ty::F64 => {
+                self.line(format!("{}.const 0", val(ty)));
+                self.line(format!("{}.ne", val(ty)));
+            }
+            _ => {}
+        }
+    }
+
+    /// Translate a return.
+    fn ret(&mut self, value: Option<&Operand>) {
+        let ty = &self.func.ty.ret;
+        match (Ty::of(ty, MODEL), value) {
+            (Some(mty), Some(value)) => self.get_as(value, mty),
+            // Falling off the end of a function returning a value.
+            (Some(mty), None) => self.int(0, mty),
+            (None, Some(addr)) => {
+                self.line("local.get $sret");
+                self.get(addr);
+                self.memcpy(ty);
+            }
+            (None, None) => {}
+        }
+        if self.frame {
+            self.line("local.get $sp");
+            self.line("global.set $__stack_pointer");
+        }
+        self.line("return");
+    }
+}
+
+//----------- Instructions -----------------------------------------------------
+
+impl Emitter<'_> {
+    /// Translate an instruction.
+    fn inst(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Assign(dest, rvalue) => {
+                self.rvalue(self.func.ty(*dest), rvalue);
+                self.line(format!("local.set $l{}", dest.0));
+            }
+            Inst::Store { addr, value } => {
+                self.get(addr);
+                self.get(value);
+                self.store(self.func.operand_ty(value));
+            }
+            Inst::Call {
+                dest,
+                callee,
+                args,
+                sig,
+                fixed,
+            } => self.call(*dest, callee, args, sig, *fixed),
+            Inst::VaStart(addr) => {
+                self.get(addr);
+                self.line("local.get $va");
+                self.store(Ty::Ptr);
+            }
+        }
+    }
+
+    /// Push the value of an rvalue of a machine type.
+    fn rvalue(&mut self, ty: Ty, rvalue: &Rvalue) {
+        match rvalue {
+            Rvalue::Use(operand) => self.get_as(operand, ty),
+            Rvalue::Bin(op, a, b) => self.bin(ty, *op, a, b),
+            Rvalue::Una(op, a) => match op {
+                UnaOp::Neg => {
+                    self.int(0, ty);
+                    self.get(a);
+                    self.line(format!("{}.sub", val(ty)));
+                    self.normalize(ty);
+                }
+                UnaOp::Not => {
+                    self.get(a);
+                    self.int(-1, ty);
+                    self.line(format!("{}.xor", val(ty)));
+                }
+                UnaOp::FNeg => {
+                    self.get(a);
+                    self.line(format!("{}.neg", val(ty)));
+                }
+            },
+            Rvalue::Cast(op, a) => {
+                let from = self.func.operand_ty(a);
+                self.get(a);
+                self.cast(*op, from, ty);
+            }
+            Rvalue::Load(addr) => {
+                self.get(addr);
+                self.load(ty);
+            }
+            Rvalue::FieldAddr(base, id, index) => {
+                self.get(base);
+                let offset = self.ctx.types.layout(*id).offsets[*index];
+                if offset != 0 {
+                    self.line(format!("i32.const {offset}"));
+                    self.line("i32.add");
+                }
+            }
+            Rvalue::ElemAddr(base, index, elem) => {
+                self.get(base);
+                let size = self.ctx.types.size_of(elem) as i64;
+                if let Operand::Const(Const::Int(index, _)) = index {
+                    if *index != 0 {
+                        self.int(index.wrapping_mul(size), Ty::I32);
+                        self.line("i32.add");
+                    }
+                    return;
+                }
+                self.get(index);
+                self.resize(self.func.operand_ty(index), Ty::I32, true);
+                if size != 1 {
+                    self.int(size, Ty::I32);
+                    self.line("i32.mul");
+                }
+                self.line("i32.add");
+            }
+            Rvalue::Phi(_) => unreachable!("phis are replaced by copies"),
+        }
+    }
+
+    /// Convert the value on the stack.
+    fn cast(&mut self, op: CastOp, from: Ty, to: Ty) {
+        let (src, dst) = (val(from), val(to));
+        match op {
+            CastOp::SExt => self.resize(from, to, true),
+            CastOp::ZExt
+            | CastOp::Trunc
+            | CastOp::PtrToInt
+            | CastOp::IntToPtr => self.resize(from, to, false),
+            CastOp::FToS | CastOp::FToU => {
+                let sign = if op == CastOp::FToS { "s" } else { "u" };
+                self.line(format!("{dst}.trunc_sat_{src}_{sign}"));
+                self.normalize(to);
+            }
+            CastOp::SToF => self.line(format!("{dst}.convert_{src}_s")),
+            CastOp::UToF => {
+                self.mask(from);
+                self.line(format!("{dst}.convert_{src}_u"));
+            }
+            CastOp::FConv => match (from, to) {
+                (Ty::F32, Ty::F64) => self.line("f64.promote_f32"),
+                (Ty::F64, Ty::F32) => self.line("f32.demote_f64"),
+                _ => {}
+            },
+        }
+    }
+
+    /// Push the value of a binary operation.
+    fn bin(&mut self, ty: Ty, op: BinOp, a: &Operand, b: &Operand) {
+        use BinOp::*;
+        let a_ty = self.func.operand_ty(a);
+        let b_ty = self.func.operand_ty(b);
+        if matches!(op, Add | Sub) && (a_ty == Ty::Ptr || b_ty == Ty::Ptr) {
+            self.ptr_arith(ty, op, a, b);
+            return;
+        }
+        let (name, compare, unsigned) = match op {
+            Add | FAdd => ("add", false, false),
+            Sub | FSub => ("sub", false, false),
+            Mul | FMul => ("mul", false, false),
+            SDiv => ("div_s", false, false),
+            UDiv => ("div_u", false, true),
+            FDiv => ("div", false, false),
+            SRem => ("rem_s", false, false),
+            URem => ("rem_u", false, true),
+            Shl => ("shl", false, false),
+            LShr => ("shr_u", false, true),
+            AShr => ("shr_s", false, false),
+            And => ("and", false, false),
+            Or => ("or", false, false),
+            Xor => ("xor", false, false),
+            Eq | FEq => ("eq", true, false),
+            Ne | FNe => ("ne", true, false),
+            SLt => ("lt_s", true, false),
+            SLe => ("le_s", true, false),
+            SGt => ("gt_s", true, false),
+            SGe => ("ge_s", true, false),
+            ULt => ("lt_u", true, true),
+            ULe => ("le_u", true, true),
+            UGt => ("gt_u", true, true),
+            UGe => ("ge_u", true, true),
+            FLt => ("lt", true, false),
+            FLe => ("le", true, false),
+            FGt => ("gt", true, false),
+            FGe => ("ge", true, false),
+        };
+
+        // Comparisons are of the type of their operands, of which constants
+        // may claim another; other operations are of the type of their
+        // result, and only shift amounts may be of another width.
+        let op_ty = match a {
+            _ if !compare => ty,
+            Operand::Const(Const::Int(..)) => b_ty,
+            _ => a_ty,
+        };
+        for (operand, operand_ty) in [(a, a_ty), (b, b_ty)] {
+            self.get_as(operand, op_ty);
+            if operand_ty != op_ty && !matches!(operand, Operand::Const(_)) {
+                self.resize(operand_ty, op_ty, false);
+            }
+            // Narrow operands are sign-extended.
+            if unsigned {
+                self.mask(op_ty);
+            }
+        }
+        self.line(format!("{}.{name}", val(op_ty)));
+        if !compare {
+            self.normalize(ty);
+        } else if ty == Ty::I64 {
+            self.line("i64.extend_i32_u");
+        }
+    }
+
+    /// Push the value of an addition to or subtraction from a pointer, which
+    /// is of bytes, or of the difference of two pointers.
+    fn ptr_arith(&mut self, ty: Ty, op: BinOp, a: &Operand, b: &Operand) {
+        let (a_ty, b_ty) = (self.func.operand_ty(a), self.func.operand_ty(b));
+        if ty != Ty::Ptr {
+            self.get(a);
+            self.resize(a_ty, Ty::I64, false);
+            self.get(b);
+            self.resize(b_ty, Ty::I64, false);
+            self.line("i64.sub");
+            self.resize(Ty::I64, ty, false);
+            return;
+        }
+        for (operand, operand_ty) in [(a, a_ty), (b, b_ty)] {
+            self.get_as(operand, Ty::I32);
+            if operand_ty != Ty::Ptr && !matches!(operand, Operand::Const(_)) {
+                self.resize(operand_ty, Ty::I32, true);
+            }
+        }
+        self.line(match op {
+            BinOp::Sub => "i32.sub",
+            _ => "i32.add",
+        });
+    }
+
+    /// Translate a call.
+    fn call(
+        &mut self,
+        dest: Option<Local>,
+        callee: &Operand,
+        args: &[Operand],
+        ty: &FnType,
+        fixed: usize,
+    ) {
+        // Where the arguments and result passed in memory go.
+        let mut used = 0;
+        let ret = Ty::of(&ty.ret, MODEL);
+        if ret.is_none() && ty.ret != CType::Void {
+            match dest {
+                Some(dest) => self.addr(dest),
+                None => {
+                    let size = self.ctx.types.size_of(&ty.ret);
+                    let align = self.ctx.types.align_of(&ty.ret);
+                    let offset = self.reserve(&mut used, size, align);
+                    self.frame_addr(offset);
+                }
+            }
+        }
+        for (arg, param) in args.iter().zip(&ty.params).take(fixed) {
+            match Ty::of(param, MODEL) {
+                Some(mty) => self.get_as(arg, mty),
+                None => {
+                    let copy = self.copy(&mut used, arg, param);
+                    self.frame_addr(copy);
+                }
+            }
+        }
+        if ty.variadic {
+            let mut offsets = Vec::new();
+            let mut size = 0u64;
+            for param in &ty.params[fixed..] {
+                let mty = Ty::of(param, MODEL).unwrap_or(Ty::Ptr);
+                size = size.next_multiple_of(mty.size(MODEL));
+                offsets.push(size);
+                size += mty.size(MODEL);
+            }
+            let buffer = self.reserve(&mut used, size, 8);
+            let args = args.iter().zip(&ty.params).skip(fixed);
+            for ((arg, param), offset) in args.zip(offsets) {
+                match Ty::of(param, MODEL) {
+                    Some(mty) => {
+                        self.frame_addr(buffer + offset);
+                        self.get_as(arg, mty);
+                        self.store(mty);
+                    }
+                    None => {
+                        let copy = self.copy(&mut used, arg, param);
+                        self.frame_addr(buffer + offset);
+                        self.frame_addr(copy);
+                        self.store(Ty::Ptr);
+                    }
+                }
+            }
+            self.frame_addr(buffer);
+        }
+
+        match callee {
+            Operand::Const(Const::Symbol(name))
+                if matches!(self.ctx.symbols[name], Symbol::Func(_)) =>
+            {
+                self.line(format!("call ${name}"))
+            }
+            callee => {
+                self.get(callee);
+                let callee_ty = FnType {
+                    ret: ty.ret.clone(),
+                    params: ty.params[..fixed].to_vec(),
+                    variadic: ty.variadic,
+                    prototype: ty.prototype,
+                };
+                self.line(format!("call_indirect{}", signature(&callee_ty)));
+            }
+        }
+        match (ret, dest) {
+            (Some(_), Some(dest)) => {
+                self.line(format!("local.set $l{}", dest.0))
+            }
+            (Some(_), None) => self.line("drop"),
+            _ => {}
+        }
+    }
+
+    /// Copy an aggregate argument to the area of a call, returning the
+    /// offset of the copy in the frame.
+    fn copy(&mut self, used: &mut u64, arg: &Operand, ty: &CType) -> u64 {
+        let size = self.ctx.types.size_of(ty);
+        let align = self.ctx.types.align_of(ty);
+        let offset = self.reserve(used, size, align);
+        self.frame_addr(offset);
+        self.get(arg);
+        self.memcpy(ty);
+        offset
+    }
+}
@@ -0,0 +1,190 @@
+//! Recovering structured control flow from the CFG.
+//!
+//! WebAssembly has no jumps, only `block`s, which a branch leaves, and
+//! `loop`s, which a branch restarts.  The blocks of a function are placed
+//! with the algorithm of Ramsey ("Beyond Relooper"), which follows the
+//! dominator tree:
+//!
+//! - A block is translated along with the blocks it immediately dominates.
+//!   Those with a single edge entering them are translated right where that
+//!   edge is taken.
+//! - The others (merge blocks) are placed after the block, each wrapped in a
+//!   `block` that the edges entering it leave; the later ones in reverse
+//!   postorder are the outer ones.
+//! - A loop header is wrapped in a `loop`, which the edges back to it
+//!   restart.
+//!
+//! This requires a reducible CFG, which only `goto` can break.  Functions
+//! with irreducible control flow are instead translated as a dispatch loop,
+//! with a `br_table` jumping to the block whose number is held in a local.
+
+use crate::mir::dom::Dominators;
+use crate::mir::{BlockId, Function, Terminator};
+
+/// A piece of structured code.
+pub enum Item {
+    /// A `block $bbN`, which is left to go to block `N`.
+    Block(BlockId, Vec<Item>),
+
+    /// A `loop $loopN`, which is restarted to go to block `N`.
+    Loop(BlockId, Vec<Item>),
+
+    /// The dispatch loop of a function with irreducible control flow.
+    Dispatch(Vec<Item>),
+
+    /// A jump to the block whose number is in the dispatch local, out of
+    /// the `block`s of every block of the function.
+    Table(Vec<BlockId>),
+
+    /// The code of a basic block, ending with its terminator, and where
+    /// each edge of the terminator goes (see [`edges()`]).
+    Basic(BlockId, Vec<Exit>),
+}
+
+/// How an edge leaves the code of its block.
+pub enum Exit {
+    /// By leaving the `block` of its target.
+    Forward(BlockId),
+
+    /// By restarting the `loop` of its target.
+    Back(BlockId),
+
+    /// By continuing with the code of its target.
+    Inline(Vec<Item>),
+
+    /// By setting the dispatch local and restarting the dispatch loop.
+    Dispatch(BlockId),
+}
+
+/// Structure the blocks of a function.
+pub fn stackify(func: &Function) -> Vec<Item> {
+    let doms = Dominators::new(func);
+    let rpo = func.reverse_postorder();
+    let mut order = vec![usize::MAX; func.blocks.len()];
+    for (i, block) in rpo.iter().enumerate() {
+        order[block.0 as usize] = i;
+    }
+
+    let mut forward = vec![0; func.blocks.len()];
+    let mut header = vec![false; func.blocks.len()];
+    let mut reducible = true;
+    for &block in &rpo {
+        for target in edges(&func.block(block).term) {
+            if order[target.0 as usize] > order[block.0 as usize] {
+                forward[target.0 as usize] += 1;
+            } else {
+                header[target.0 as usize] = true;
+                reducible &= doms.dominates(target, block);
+            }
+        }
+    }
+
+    let stackifier = Stackifier {
+        func,
+        doms: &doms,
+        order,
+        merge: forward.iter().map(|&edges| edges > 1).collect(),
+        header,
+    };
+    if reducible {
+        stackifier.tree(BlockId(0))
+    } else {
+        stackifier.dispatch()
+    }
+}
+
+/// The targets of a terminator, once for every edge of its translation.
+///
+/// The edges of a branch are the `then` and `else` ones, unless they are the
+/// same.  Those of a switch are its cases, in order, except those going to
+/// the default block, followed by the default.
+pub fn edges(term: &Terminator) -> Vec<BlockId> {
+    match term {
+        Terminator::Jump(target) => vec![*target],
+        Terminator::Branch(_, then, r#else) if then == r#else => vec![*then],
+        Terminator::Branch(_, then, r#else) => vec![*then, *r#else],
+        Terminator::Switch { cases, default, .. } => {
+            let cases = cases.iter().map(|(_, target)| *target);
+            let cases = cases.filter(|target| target != default);
+            cases.chain([*default]).collect()
+        }
+        Terminator::Return(_) | Terminator::Unreachable => Vec::new(),
+    }
+}
+
+/// The state of the structuring of a function.
+struct Stackifier<'a> {
+    /// The function.
+    func: &'a Function,
+
+    /// Its dominator tree.
+    doms: &'a Dominators,
+
+    /// The position of every block in reverse postorder.
+    order: Vec<usize>,
+
+    /// Whether several forward edges enter each block.
+    merge: Vec<bool>,
+
+    /// Whether each block is the target of a back edge.
+    header: Vec<bool>,
+}
+
+impl Stackifier<'_> {
+    /// Structure a block and those it dominates.
+    fn tree(&self, block: BlockId) -> Vec<Item> {
+        let mut merges: Vec<_> = (self.doms.children(block).iter().copied())
+            .filter(|child| self.merge[child.0 as usize])
+            .collect();
+        merges.sort_by_key(|child| {
+            std::cmp::Reverse(self.order[child.0 as usize])
+        });
+        let items = self.within(block, &merges);
+        match self.header[block.0 as usize] {
+            true => vec![Item::Loop(block, items)],
+            false => items,
+        }
+    }
+
+    /// Structure a block followed by some of the merge blocks it dominates,
+    /// latest first.
+    fn within(&self, block: BlockId, merges: &[BlockId]) -> Vec<Item> {
+        let Some((&merge, rest)) = merges.split_first() else {
+            let term = &self.func.block(block).term;
+            let exits = edges(term)
+                .into_iter()
+                .map(|target| self.exit(block, target))
+                .collect();
+            return vec![Item::Basic(block, exits)];
+        };
+        let mut items = vec![Item::Block(merge, self.within(block, rest))];
+        items.extend(self.tree(merge));
+        items
+    }
+
+    /// Structure an edge.
+    fn exit(&self, from: BlockId, to: BlockId) -> Exit {
+        if self.order[to.0 as usize] <= self.order[from.0 as usize] {
+            Exit::Back(to)
+        } else if self.merge[to.0 as usize] {
+            Exit::Forward(to)
+        } else {
+            Exit::Inline(self.tree(to))
+        }
+    }
+
+    /// Structure the function as a dispatch loop.
+    ///
+    /// Every block is placed after the end of a `block` of its own, and the
+    /// innermost one holds the `br_table`.
+    fn dispatch(&self) -> Vec<Item> {
+        let blocks: Vec<_> = self.func.block_ids().collect();
+        let mut items = vec![Item::Table(blocks.clone())];
+        for &block in &blocks {
+            let term = &self.func.block(block).term;
+            let exits = edges(term).into_iter().map(Exit::Dispatch).collect();
+            items = vec![Item::Block(block, items), Item::Basic(block, exits)];
+        }
+        vec![Item::Dispatch(items)]
+    }
+}
@@ -0,0 +1,664 @@
+//! The x86-64 backend.
+//!
+//! Code is generated for the System V ABI used on Linux, and written as GNU
+//! assembler text in AT&T syntax, ready to be assembled and linked by `cc`.
+//! Instruction selection ([`isel`]) translates each MIR instruction on its
+//! own into [`Inst`]s over virtual registers; after register allocation, the
+//! instructions are written out by [`emit`], or encoded into an ELF object
+//! file by [`encode`] (`sea -c`), without needing an assembler.
+//!
+//! Every function keeps a frame pointer in `%rbp`.  Below it are the saved
+//! callee-saved registers and the stack slots, and at the bottom of the frame
+//! the stack arguments of calls, addressed relative to `%rsp`.
+
+use std::collections::HashSet;
+
+use super::elf::{EM_X86_64, Object};
+use super::regalloc::{self, Allocator};
+use super::{Class, MachFunction, MachInst, Reg, Role, Slot, data};
+use crate::ctype::DataModel;
+use crate::mir::{Module, ssa};
+
+mod emit;
+mod encode;
+mod isel;
+
+/// The data model of the target.
+const MODEL: DataModel = DataModel::Lp64;
+
+//----------- Registers --------------------------------------------------------
+
+/// `%rax`; general-purpose registers are numbered as in instruction
+/// encodings.
+const RAX: u8 = 0;
+
+/// `%rcx`.
+const RCX: u8 = 1;
+
+/// `%rdx`.
+const RDX: u8 = 2;
+
+/// `%rbx`.
+const RBX: u8 = 3;
+
+/// `%rsp`.
+const RSP: u8 = 4;
+
+/// `%rbp`.
+const RBP: u8 = 5;
+
+/// `%rsi`.
+const RSI: u8 = 6;
+
+/// `%rdi`.
+const RDI: u8 = 7;
+
+/// `%xmm0`; `%xmmN` is numbered `XMM0 + N`.
+const XMM0: u8 = 16;
+
+/// The registers passing integer arguments, in order.
+const INT_ARGS: [u8; 6] = [RDI, RSI, RDX, RCX, 8, 9];
+
+/// The number of SSE registers passing floating-point arguments.
+const FLOAT_ARGS: u8 = 8;
+
+/// The registers a function must preserve, apart from `%rbp` and `%rsp`.
+const CALLEE_SAVED: [u8; 5] = [RBX, 12, 13, 14, 15];
+
+/// The registers a call may change.
+const CALLER_SAVED: [u8; 25] = [
+    RAX,
+    RCX,
+    RDX,
+    RSI,
+    RDI,
+    8,
+    9,
+    10,
+    11,
+    XMM0,
+    XMM0 + 1,
+    XMM0 + 2,
+    XMM0 + 3,
+    XMM0 + 4,
+    XMM0 + 5,
+    XMM0 + 6,
+    XMM0 + 7,
+    XMM0 + 8,
+    XMM0 + 9,
+    XMM0 + 10,
+    XMM0 + 11,
+    XMM0 + 12,
+    XMM0 + 13,
+    XMM0 + 14,
+    XMM0 + 15,
+];
+
+/// The size of the register save area of variadic functions: the six
+/// integer and eight SSE argument registers.
+const SAVE_AREA: u64 = 6 * 8 + 8 * 16;
+
+//----------- Instructions -----------------------------------------------------
+
+/// The size of an integer operation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Size {
+    /// A byte.
+    B,
+
+    /// A 16-bit word.
+    W,
+
+    /// A 32-bit long word.
+    L,
+
+    /// A 64-bit quad word.
+    Q,
+}
+
+/// The precision of a floating-point operation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Prec {
+    /// Single precision.
+    S,
+
+    /// Double precision.
+    D,
+}
+
+/// A condition on the flags.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Cond {
+    /// Equal.
+    E,
+
+    /// Not equal.
+    Ne,
+
+    /// Signed less than.
+    L,
+
+    /// Signed less than or equal.
+    Le,
+
+    /// Signed greater than.
+    G,
+
+    /// Signed greater than or equal.
+    Ge,
+
+    /// Unsigned less than ("below").
+    B,
+
+    /// Unsigned less than or equal.
+    Be,
+
+    /// Unsigned greater than ("above").
+    A,
+
+    /// Unsigned greater than or equal.
+    Ae,
+
+    /// Parity, set by unordered floating-point comparisons.
+    P,
+
+    /// No parity.
+    Np,
+}
+
+/// A two-operand integer operation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AluOp {
+    /// Addition.
+    Add,
+
+    /// Subtraction.
+    Sub,
+
+    /// Multiplication.
+    Imul,
+
+    /// Bitwise and.
+    And,
+
+    /// Bitwise or.
+    Or,
+
+    /// Bitwise exclusive or.
+    Xor,
+}
+
+/// A shift.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShiftOp {
+    /// Shift left.
+    Shl,
+
+    /// Logical shift right.
+    Shr,
+
+    /// Arithmetic shift right.
+    Sar,
+}
+
+/// A one-operand integer operation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// Negation.
+    Neg,
+
+    /// Bitwise inversion.
+    Not,
+}
+
+/// A two-operand floating-point operation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FAluOp {
+    /// Addition.
+    Add,
+
+    /// Subtraction.
+    Sub,
+
+    /// Multiplication.
+    Mul,
+
+    /// Division.
+    Div,
+
+    /// Bitwise exclusive or, of the whole register.
+    Xor,
+}
+
+/// An operand: a register, an immediate or memory.
+#[derive(Clone)]
+pub enum Opnd {
+    /// A register.
+    Reg(Reg),
+
+    /// A 32-bit immediate, sign-extended.
+    Imm(i64),
+
+    /// A memory location.
+    Mem(Mem),
+}
+
+/// A memory operand.
+#[derive(Clone)]
+pub struct Mem {
+    /// What the address is relative to.
+    pub base: Base,
+
+    /// The displacement from the base.
+    pub disp: i64,
+}
+
+/// The base of a memory operand.
+#[derive(Clone)]
+pub enum Base {
+    /// A register holding an address.
+    Reg(Reg),
+
+    /// A stack slot.
+    Slot(Slot),
+
+    /// A symbol defined in the module, addressed relative to `%rip`.
+    Symbol(String),
+
+    /// The global offset table entry holding the address of a symbol.
+    Got(String),
+}
+
+/// The target of a call.
+#[derive(Clone)]
+pub enum Callee {
+    /// A symbol, called through the PLT if it is not defined in the module.
+    Symbol(String, bool),
+
+    /// A register holding the address of the function.
+    Reg(Reg),
+}
+
+/// An x86-64 instruction.
+///
+/// Operands are given destination first.  Registers used implicitly are
+/// listed by [`Inst::implicit`].
+#[derive(Clone)]
+pub enum Inst {
+    /// Copy an integer; at most one operand may be in memory.
+    Mov(Size, Opnd, Opnd),
+
+    /// Load a 64-bit immediate.
+    MovAbs(Reg, i64),
+
+    /// Sign- or zero-extend an integer.
+    Ext {
+        signed: bool,
+        from: Size,
+        to: Size,
+        dst: Reg,
+        src: Opnd,
+    },
+
+    /// Compute an address.
+    Lea(Reg, Mem),
+
+    /// An integer operation, updating the destination.
+    Alu(AluOp, Size, Reg, Opnd),
+
+    /// Shift a register by a constant, or by `%cl`.
+    Shift(ShiftOp, Size, Reg, Option<u8>),
+
+    /// A unary integer operation.
+    Unary(UnaryOp, Size, Reg),
+
+    /// Compare two integers, setting the flags from the first minus the
+    /// second.
+    Cmp(Size, Reg, Opnd),
+
+    /// Set a byte register to whether a condition holds.
+    Set(Cond, Reg),
+
+    /// Sign-extend `%rax` into `%rdx` (`cltd` or `cqto`).
+    Cqo(Size),
+
+    /// Divide `%rdx:%rax`, leaving the quotient in `%rax` and the remainder
+    /// in `%rdx`.
+    Div { signed: bool, size: Size, src: Reg },
+
+    /// Copy a floating-point value; at most one operand may be in memory.
+    FMov(Prec, Opnd, Opnd),
+
+    /// A floating-point operation, updating the destination.
+    FAlu(FAluOp, Prec, Reg, Opnd),
+
+    /// Compare two floating-point values, setting the flags as for an
+    /// unsigned comparison of the first to the second.
+    Ucomi(Prec, Reg, Opnd),
+
+    /// Copy the bits of a general-purpose register to an SSE register.
+    MovBits(Size, Reg, Reg),
+
+    /// Convert a signed integer to floating point.
+    CvtIntToFloat(Size, Prec, Reg, Reg),
+
+    /// Convert floating point to a signed integer, truncating.
+    CvtFloatToInt(Prec, Size, Reg, Reg),
+
+    /// Convert between precisions, from the given one.
+    CvtFloat(Prec, Reg, Reg),
+
+    /// Convert an unsigned 64-bit integer to floating point; the source is
+    /// clobbered.
+    U64ToFloat {
+        prec: Prec,
+        dst: Reg,
+        src: Reg,
+        tmp: Reg,
+    },
+
+    /// Convert floating point to an unsigned 64-bit integer; the source is
+    /// clobbered.
+    FloatToU64 {
+        prec: Prec,
+        dst: Reg,
+        src: Reg,
+        tmp: Reg,
+        ftmp: Reg,
+    },
+
+    /// Call a function, with arguments and results in the given registers.
+    Call {
+        callee: Callee,
+        args: Vec<u8>,
+        rets: Vec<u8>,
+    },
+
+    /// Jump to a block.
+    Jmp(usize),
+
+    /// Jump to a block if a condition holds.
+    Jcc(Cond, usize),
+
+    /// Return, with the result in the given registers.
+    Ret(Vec<u8>),
+
+    /// Save the argument registers of a variadic function.
+    SaveVarargs(Slot),
+
+    /// Trap.
+    Ud2,
+}
+
+impl Opnd {
+    /// Collect the registers of the operand, accessed in a role.
+    fn regs_mut<'a>(
+        &'a mut self,
+        role: Role,
+        regs: &mut Vec<(&'a mut Reg, Role)>,
+    ) {
+        match self {
+            Self::Reg(reg) => regs.push((reg, role)),
+            Self::Mem(mem) => mem.regs_mut(regs),
+            Self::Imm(_) => {}
+        }
+    }
+}
+
+impl Mem {
+    /// Collect the registers used by the address.
+    fn regs_mut<'a>(&'a mut self, regs: &mut Vec<(&'a mut Reg, Role)>) {
+        if let Base::Reg(reg) = &mut self.base {
+            regs.push((reg, Role::Use));
+        }
+    }
+
+    /// The same address, moved by a number of bytes.
+    fn offset(&self, offset: u64) -> Self {
+        Self {
+            base: self.base.clone(),
+            disp: self.disp + offset as i64,
+        }
+    }
+}
+
+impl Inst {
+    /// The machine registers used or defined implicitly.
+    pub fn implicit(&self) -> Vec<(u8, Role)> {
+        let mut regs = Vec::new();
+        match self {
+            Self::Shift(.., None) => regs.push((RCX, Role::Use)),
+            Self::Cqo(_) => {
+                regs.push((RAX, Role::Use));
+                regs.push((RDX, Role::Def));
+            }
+            Self::Div { .. } => {
+                regs.push((RAX, Role::UseDef));
+                regs.push((RDX, Role::UseDef));
+            }
+            Self::Call { args, rets, .. } => {
+                regs.extend(args.iter().map(|&reg| (reg, Role::Use)));
+                regs.extend(rets.iter().map(|&reg| (reg, Role::Def)));
+            }
+            Self::Ret(rets) => {
+                regs.extend(rets.iter().map(|&reg| (reg, Role::Use)));
+            }
+            Self::SaveVarargs(_) => {
+                regs.extend(INT_ARGS.iter().map(|&reg| (reg, Role::Use)));
+                regs.push((RAX, Role::Use));
+                let floats = (0..FLOAT_ARGS).map(|i| (XMM0 + i, Role::Use));
+                regs.extend(floats);
+            }
+            _ => {}
+        }
+        regs
+    }
+}
+
+impl MachInst for Inst {
+    fn regs(&self) -> Vec<(Reg, Role)> {
+        let mut copy = self.clone();
+        let explicit =
+            copy.regs_mut().into_iter().map(|(reg, role)| (*reg, role));
+        let implicit = self
+            .implicit()
+            .into_iter()
+            .map(|(reg, role)| (Reg::Phys(reg), role));
+        explicit.chain(implicit).collect()
+    }
+
+    fn regs_mut(&mut self) -> Vec<(&mut Reg, Role)> {
+        let mut regs = Vec::new();
+        match self {
+            Self::Mov(_, dst, src) | Self::FMov(_, dst, src) => {
+                src.regs_mut(Role::Use, &mut regs);
+                dst.regs_mut(Role::Def, &mut regs);
+            }
+            Self::MovAbs(dst, _) | Self::Set(_, dst) => {
+                regs.push((dst, Role::Def));
+            }
+            Self::Ext { dst, src, .. } => {
+                src.regs_mut(Role::Use, &mut regs);
+                regs.push((dst, Role::Def));
+            }
+            Self::Lea(dst, mem) => {
+                mem.regs_mut(&mut regs);
+                regs.push((dst, Role::Def));
+            }
+            Self::Alu(_, _, dst, src) | Self::FAlu(_, _, dst, src) => {
+                src.regs_mut(Role::Use, &mut regs);
+                regs.push((dst, Role::UseDef));
+            }
+            Self::Shift(_, _, dst, _) | Self::Unary(_, _, dst) => {
+                regs.push((dst, Role::UseDef));
+            }
+            Self::Cmp(_, a, b) | Self::Ucomi(_, a, b) => {
+                regs.push((a, Role::Use));
+                b.regs_mut(Role::Use, &mut regs);
+            }
+            Self::Div { src, .. } => regs.push((src, Role::Use)),
+            Self::MovBits(_, dst, src)
+            | Self::CvtIntToFloat(_, _, dst, src)
+            | Self::CvtFloatToInt(_, _, dst, src)
+            | Self::CvtFloat(_, dst, src) => {
+                regs.push((src, Role::Use));
+                regs.push((dst, Role::Def));
+            }
+            Self::U64ToFloat { dst, src, tmp, .. } => {
+                regs.push((src, Role::UseDef));
+                regs.push((dst, Role::Def));
+                regs.push((tmp, Role::Def));
+            }
+            Self::FloatToU64 {
+                dst,
+                src,
+                tmp,
+                ftmp,
+                ..
+            } => {
+                regs.push((src, Role::UseDef));
+                regs.push((dst, Role::Def));
+                regs.push((tmp, Role::Def));
+                regs.push((ftmp, Role::Def));
+            }
+            Self::Call {
+                callee: Callee::Reg(reg),
+                ..
+            } => regs.push((reg, Role::Use)),
+            Self::Call { .. }
+            | Self::Cqo(_)
+            | Self::Jmp(_)
+            | Self::Jcc(..)
+            | Self::Ret(_)
+            | Self::SaveVarargs(_)
+            | Self::Ud2 => {}
+        }
+        regs
+    }
+
+    fn clobbers(&self) -> &'static [u8] {
+        match self {
+            Self::Call { .. } => &CALLER_SAVED,
+            _ => &[],
+        }
+    }
+
+    fn as_move(&self) -> Option<(Reg, Reg)> {
+        // Narrower moves are never between registers, and 32-bit ones only
+        // copy 32-bit values, whose upper halves are unspecified.
+        match self {
+            Self::Mov(Size::L | Size::Q, Opnd::Reg(dst), Opnd::Reg(src))
+            | Self::FMov(_, Opnd::Reg(dst), Opnd::Reg(src)) => {
+                Some((*dst, *src))
+            }
+            _ => None,
+        }
+    }
+
+    fn targets_mut(&mut self) -> Vec<&mut usize> {
+        match self {
+            Self::Jmp(target) | Self::Jcc(_, target) => vec![target],
+            _ => Vec::new(),
+        }
+    }
+
+    fn allocatable(class: Class) -> &'static [u8] {
+        match class {
+            Class::Int => &[RAX, RCX, RDX, RSI, RDI, 8, 9, RBX, 12, 13, 14, 15],
+            Class::Float => &CALLER_SAVED[9..23],
+        }
+    }
+
+    fn scratch(class: Class) -> [u8; 2] {
+        match class {
+            Class::Int => [10, 11],
+            Class::Float => [XMM0 + 14, XMM0 + 15],
+        }
+    }
+
+    fn reload(reg: u8, class: Class, slot: Slot) -> Self {
+        let mem = Opnd::Mem(Mem {
+            base: Base::Slot(slot),
+            disp: 0,
+        });
+        match class {
+            Class::Int => Self::Mov(Size::Q, Opnd::Reg(Reg::Phys(reg)), mem),
+            Class::Float => Self::FMov(Prec::D, Opnd::Reg(Reg::Phys(reg)), mem),
+        }
+    }
+
+    fn spill(reg: u8, class: Class, slot: Slot) -> Self {
+        let mem = Opnd::Mem(Mem {
+            base: Base::Slot(slot),
+            disp: 0,
+        });
+        match class {
+            Class::Int => Self::Mov(Size::Q, mem, Opnd::Reg(Reg::Phys(reg))),
+            Class::Float => Self::FMov(Prec::D, mem, Opnd::Reg(Reg::Phys(reg))),
+        }
+    }
+
+    fn copy(dst: u8, src: u8, class: Class) -> Self {
+        let (dst, src) = (Opnd::Reg(Reg::Phys(dst)), Opnd::Reg(Reg::Phys(src)));
+        match class {
+            Class::Int => Self::Mov(Size::Q, dst, src),
+            Class::Float => Self::FMov(Prec::D, dst, src),
+        }
+    }
+
+    fn jump(target: usize) -> Self {
+        Self::Jmp(target)
+    }
+}
+
+//----------- Compilation ------------------------------------------------------
+
+/// Select and allocate the instructions of every function of a module,
+/// with whether the function is local.
+fn functions(
+    module: &Module,
+    allocator: Allocator,
+) -> Vec<(MachFunction<Inst>, bool)> {
+    let defined: HashSet<String> = module
+        .functions
+        .iter()
+        .map(|func| func.name.clone())
+        .chain(module.globals.iter().map(|global| global.name.clone()))
+        .collect();
+    module
+        .functions
+        .iter()
+        .map(|func| {
+            let mut func = func.clone();
+            ssa::destruct(&mut func);
+            let mut mach = isel::select(&func, &module.types, &defined);
+            regalloc::allocate(&mut mach, allocator);
+            (mach, func.local)
+        })
+        .collect()
+}
+
+/// Compile a module to assembly text.
+pub fn compile(module: &Module, allocator: Allocator) -> String {
+    let mut out = String::new();
+    for global in &module.globals {
+        data::global(&mut out, global, &module.types);
+    }
+    for (mach, local) in functions(module, allocator) {
+        emit::function(&mut out, &mach, local);
+    }
+    out.push_str("\t.section .note.GNU-stack,\"\",@progbits\n");
+    out
+}
+
+/// Compile a module to a relocatable ELF object file.
+pub fn compile_object(module: &Module, allocator: Allocator) -> Vec<u8> {
+    let mut obj = Object::new(EM_X86_64);
+    for global in &module.globals {
+        data::place(&mut obj, global, &module.types, encode::R_X86_64_64);
+    }
+    for (mach, local) in functions(module, allocator) {
+        encode::function(&mut obj, &mach, local);
+    }
+    obj.write()
+}
@@ -0,0 +1,483 @@
+//! Writing x86-64 assembly text.
+
+use std::fmt::Write;
+
+use super::*;
+use crate::codegen::MachFunction;
+
+/// The layout of the frame of a function.
+pub(super) struct Frame {
+    /// The offset from `%rbp` of each stack slot.
+    pub(super) slots: Vec<i64>,
+
+    /// The callee-saved registers written by the function, with the offsets
+    /// from `%rbp` they are saved at.
+    pub(super) saved: Vec<(u8, i64)>,
+
+    /// The number of bytes allocated below the saved `%rbp`.
+    pub(super) size: u64,
+}
+
+impl Frame {
+    /// Lay out the frame of a function.
+    pub(super) fn new(func: &MachFunction<Inst>) -> Self {
+        let mut written = Vec::new();
+        for inst in func.blocks.iter().flat_map(|block| &block.insts) {
+            for (reg, role) in inst.regs() {
+                if let Reg::Phys(reg) = reg
+                    && role.is_def()
+                    && CALLEE_SAVED.contains(&reg)
+                    && !written.contains(&reg)
+                {
+                    written.push(reg);
+                }
+            }
+        }
+        written.sort();
+
+        let mut top = 0;
+        let saved = written
+            .into_iter()
+            .map(|reg| {
+                top += 8;
+                (reg, -(top as i64))
+            })
+            .collect();
+        let slots = func
+            .slots
+            .iter()
+            .map(|slot| {
+                top = (top + slot.size).next_multiple_of(slot.align);
+                -(top as i64)
+            })
+            .collect();
+        let size =
+            top.next_multiple_of(16) + func.outgoing.next_multiple_of(16);
+        Self { slots, saved, size }
+    }
+}
+
+/// Write out the code of a function, after register allocation.
+pub fn function(out: &mut String, func: &MachFunction<Inst>, local: bool) {
+    let name = &func.name;
+    let frame = Frame::new(func);
+    writeln!(out, "\t.text").unwrap();
+    if !local {
+        writeln!(out, "\t.globl {name}").unwrap();
+    }
+    writeln!(out, "\t.type {name}, @function").unwrap();
+    writeln!(out, "{name}:").unwrap();
+    writeln!(out, "\tpushq %rbp").unwrap();
+    writeln!(out, "\tmovq %rsp, %rbp").unwrap();
+    if frame.size > 0 {
+        writeln!(out, "\tsubq ${}, %rsp", frame.size).unwrap();
+    }
+    for &(reg, offset) in &frame.saved {
+        writeln!(out, "\tmovq {}, {offset}(%rbp)", gpr(reg, Size::Q)).unwrap();
+    }
+
+    let mut printer = Printer {
+        out,
+        name,
+        frame: &frame,
+    };
+    for (index, block) in func.blocks.iter().enumerate() {
+        writeln!(printer.out, ".L{name}.{index}:").unwrap();
+        for inst in &block.insts {
+            // Jumps to the next block fall through.
+            if let Inst::Jmp(target) = inst
+                && *target == index + 1
+            {
+                continue;
+            }
+            printer.inst(inst);
+        }
+    }
+    writeln!(out, "\t.size {name}, .-{name}").unwrap();
+}
+
+/// The name of a general-purpose register of a size.
+fn gpr(reg: u8, size: Size) -> String {
+    const NAMES: [[&str; 4]; 8] = [
+        ["al", "ax", "eax", "rax"],
+        ["cl", "cx", "ecx", "rcx"],
+        ["dl", "dx", "edx", "rdx"],
+        ["bl", "bx", "ebx", "rbx"],
+        ["spl", "sp", "esp", "rsp"],
+        ["bpl", "bp", "ebp", "rbp"],
+        ["sil", "si", "esi", "rsi"],
+        ["dil", "di", "edi", "rdi"],
+    ];
+    let index = size as usize;
+    match reg {
+        0..8 => format!("%{}", NAMES[reg as usize][index]),
+        _ => format!("%r{reg}{}", ["b", "w", "d", ""][index]),
+    }
+}
+
+/// The suffix of an integer instruction of a size.
+fn suffix(size: Size) -> char {
+    match size {
+        Size::B => 'b',
+        Size::W => 'w',
+        Size::L => 'l',
+        Size::Q => 'q',
+    }
+}
+
+/// The suffix of a floating-point instruction of a precision.
+fn prec_suffix(prec: Prec) -> &'static str {
+    match prec {
+        Prec::S => "ss",
+        Prec::D => "sd",
+    }
+}
+
+/// The mnemonic suffix of a condition.
+fn cond(cond: Cond) -> &'static str {
+    match cond {
+        Cond::E => "e",
+        Cond::Ne => "ne",
+        Cond::L => "l",
+        Cond::Le => "le",
+        Cond::G => "g",
+        Cond::Ge => "ge",
+        Cond::B => "b",
+        Cond::Be => "be",
+        Cond::A => "a",
+        Cond::Ae => "ae",
+        Cond::P => "p",
+        Cond::Np => "np",
+    }
+}
+
+/// Writes instructions of a function.
+struct Printer<'a> {
+    /// The text written so far.
+    out: &'a mut String,
+
+    /// The name of the function.
+    name: &'a str,
+
+    /// The layout of the frame.
+    frame: &'a Frame,
+}
+
+impl Printer<'_> {
+    /// A machine register.
+    fn phys(reg: Reg) -> u8 {
+        match reg {
+            Reg::Phys(reg) => reg,
+            Reg::Virt(_) => panic!("virtual register after allocation"),
+        }
+    }
+
+    /// A general-purpose register of a size.
+    fn gpr(&self, reg: Reg, size: Size) -> String {
+        gpr(Self::phys(reg), size)
+    }
+
+    /// An SSE register.
+    fn xmm(&self, reg: Reg) -> String {
+        format!("%xmm{}", Self::phys(reg) - XMM0)
+    }
+
+    /// A memory operand.
+    fn mem(&self, mem: &Mem) -> String {
+        let disp = mem.disp;
+        match &mem.base {
+            Base::Reg(reg) => format!("{disp}({})", self.gpr(*reg, Size::Q)),
+            Base::Slot(slot) => {
+                let offset = self.frame.slots[slot.0 as usize] + disp;
+                format!("{offset}(%rbp)")
+            }
+            Base::Symbol(name) if disp == 0 => format!("{name}(%rip)"),
+            Base::Symbol(name) => format!("{name}{disp:+}(%rip)"),
+            Base::Got(name) => format!("{name}@GOTPCREL(%rip)"),
+        }
+    }
+
+    /// An integer operand of a size.
+    fn opnd(&self, opnd: &Opnd, size: Size) -> String {
+        match opnd {
+            Opnd::Reg(reg) => self.gpr(*reg, size),
+            Opnd::Imm(value) => format!("${value}"),
+            Opnd::Mem(mem) => self.mem(mem),
+        }
+    }
+
+    /// A floating-point operand.
+    fn fopnd(&self, opnd: &Opnd) -> String {
+        match opnd {
+            Opnd::Reg(reg) => self.xmm(*reg),
+            Opnd::Imm(_) => unreachable!("no floating-point immediates"),
+            Opnd::Mem(mem) => self.mem(mem),
+        }
+    }
+
+    /// The label of a block.
+    fn label(&self, block: usize) -> String {
+        format!(".L{}.{block}", self.name)
+    }
+
+    /// Write a line of assembly.
+    fn line(&mut self, line: std::fmt::Arguments) {
+        writeln!(self.out, "\t{line}").unwrap();
+    }
+
+    /// Write an instruction.
+    fn inst(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Mov(size, dst, src) => {
+                let s = suffix(*size);
+                let (src, dst) = (self.opnd(src, *size), self.opnd(dst, *size));
+                self.line(format_args!("mov{s} {src}, {dst}"));
+            }
+            Inst::MovAbs(dst, value) => {
+                let dst = self.gpr(*dst, Size::Q);
+                self.line(format_args!("movabsq ${value}, {dst}"));
+            }
+            Inst::Ext {
+                signed,
+                from,
+                to,
+                dst,
+                src,
+            } => {
+                let src = self.opnd(src, *from);
+                if !signed && *from == Size::L {
+                    // Writing a 32-bit register clears the upper half.
+                    let dst = self.gpr(*dst, Size::L);
+                    self.line(format_args!("movl {src}, {dst}"));
+                    return;
+                }
+                let dst = self.gpr(*dst, *to);
+                let (from, to) = (suffix(*from), suffix(*to));
+                let op = match (signed, from) {
+                    (true, 'l') => "movslq".to_string(),
+                    (true, _) => format!("movs{from}{to}"),
+                    (false, _) => format!("movz{from}{to}"),
+                };
+                self.line(format_args!("{op} {src}, {dst}"));
+            }
+            Inst::Lea(dst, mem) => {
+                let (mem, dst) = (self.mem(mem), self.gpr(*dst, Size::Q));
+                self.line(format_args!("leaq {mem}, {dst}"));
+            }
+            Inst::Alu(op, size, dst, src) => {
+                let op = match op {
+                    AluOp::Add => "add",
+                    AluOp::Sub => "sub",
+                    AluOp::Imul => "imul",
+                    AluOp::And => "and",
+                    AluOp::Or => "or",
+                    AluOp::Xor => "xor",
+                };
+                let s = suffix(*size);
+                let (src, dst) = (self.opnd(src, *size), self.gpr(*dst, *size));
+                self.line(format_args!("{op}{s} {src}, {dst}"));
+            }
+            Inst::Shift(op, size, dst, count) => {
+                let op = match op {
+                    ShiftOp::Shl => "shl",
+                    ShiftOp::Shr => "shr",
+                    ShiftOp::Sar => "sar",
+                };
+                let s = suffix(*size);
+                let dst = self.gpr(*dst, *size);
+                match count {
+                    Some(count) => {
+                        self.line(format_args!("{op}{s} ${count}, {dst}"))
+                    }
+                    None => self.line(format_args!("{op}{s} %cl, {dst}")),
+                }
+            }
+            Inst::Unary(op, size, dst) => {
+                let op = match op {
+                    UnaryOp::Neg => "neg",
+                    UnaryOp::Not => "not",
+                };
+                let s = suffix(*size);
+                let dst = self.gpr(*dst, *size);
+                self.line(format_args!("{op}{s} {dst}"));
+            }
+            Inst::Cmp(size, a, b) => {
+                let s = suffix(*size);
+                let (a, b) = (self.gpr(*a, *size), self.opnd(b, *size));
+                self.line(format_args!("cmp{s} {b}, {a}"));
+            }
+            Inst::Set(c, dst) => {
+                let dst = self.gpr(*dst, Size::B);
+                self.line(format_args!("set{} {dst}", cond(*c)));
+            }
+            Inst::Cqo(size) => match size {
+                Size::Q => self.line(format_args!("cqto")),
+                _ => self.line(format_args!("cltd")),
+            },
+            Inst::Div { signed, size, src } => {
+                let op = if *signed { "idiv" } else { "div" };
+                let s = suffix(*size);
+                let src = self.gpr(*src, *size);
+                self.line(format_args!("{op}{s} {src}"));
+            }
+            Inst::FMov(prec, dst, src) => {
+                let op = match (dst, src) {
+                    (Opnd::Reg(_), Opnd::Reg(_)) => "movaps",
+                    _ => match prec {
+                        Prec::S => "movss",
+                        Prec::D => "movsd",
+                    },
+                };
+                let (src, dst) = (self.fopnd(src), self.fopnd(dst));
+                self.line(format_args!("{op} {src}, {dst}"));
+            }
+            Inst::FAlu(op, prec, dst, src) => {
+                let (src, dst) = (self.fopnd(src), self.xmm(*dst));
+                let op = match op {
+                    FAluOp::Add => "add",
+                    FAluOp::Sub => "sub",
+                    FAluOp::Mul => "mul",
+                    FAluOp::Div => "div",
+                    FAluOp::Xor => {
+                        self.line(format_args!("xorps {src}, {dst}"));
+                        return;
+                    }
+                };
+                let p = prec_suffix(*prec);
+                self.line(format_args!("{op}{p} {src}, {dst}"));
+            }
+            Inst::Ucomi(prec, a, b) => {
+                let (a, b) = (self.xmm(*a), self.fopnd(b));
+                let p = prec_suffix(*prec);
+                self.line(format_args!("ucomi{p} {b}, {a}"));
+            }
+            Inst::MovBits(size, dst, src) => {
+                let (src, dst) = (self.gpr(*src, *size), self.xmm(*dst));
+                let op = if *size == Size::Q { "movq" } else { "movd" };
+                self.line(format_args!("{op} {src}, {dst}"));
+            }
+            Inst::CvtIntToFloat(size, prec, dst, src) => {
+                let (src, dst) = (self.gpr(*src, *size), self.xmm(*dst));
+                let p = prec_suffix(*prec);
+                let s = suffix(*size);
+                self.line(format_args!("cvtsi2{p}{s} {src}, {dst}"));
+            }
+            Inst::CvtFloatToInt(prec, size, dst, src) => {
+                let (src, dst) = (self.xmm(*src), self.gpr(*dst, *size));
+                let p = prec_suffix(*prec);
+                self.line(format_args!("cvtt{p}2si {src}, {dst}"));
+            }
+            Inst::CvtFloat(from, dst, src) => {
+                let (src, dst) = (self.xmm(*src), self.xmm(*dst));
+                let op = match from {
+                    Prec::S => "cvtss2sd",
+                    Prec::D => "cvtsd2ss",
+                };
+                self.line(format_args!("{op} {src}, {dst}"));
+            }
+            Inst::U64ToFloat {
+                prec,
+                dst,
+                src,
+                tmp,
+            } => {
+                // Values with the top bit set are halved, keeping the low bit
+                // for rounding, then converted and doubled.
+                let p = prec_suffix(*prec);
+                let (dst, src) = (self.xmm(*dst), self.gpr(*src, Size::Q));
+                let tmp = self.gpr(*tmp, Size::Q);
+                self.line(format_args!("testq {src}, {src}"));
+                self.line(format_args!("js 1f"));
+                self.line(format_args!("cvtsi2{p}q {src}, {dst}"));
+                self.line(format_args!("jmp 2f"));
+                writeln!(self.out, "1:").unwrap();
+                self.line(format_args!("movq {src}, {tmp}"));
+                self.line(format_args!("shrq $1, {src}"));
+                self.line(format_args!("andq $1, {tmp}"));
+                self.line(format_args!("orq {tmp}, {src}"));
+                self.line(format_args!("cvtsi2{p}q {src}, {dst}"));
+                self.line(format_args!("add{p} {dst}, {dst}"));
+                writeln!(self.out, "2:").unwrap();
+            }
+            Inst::FloatToU64 {
+                prec,
+                dst,
+                src,
+                tmp,
+                ftmp,
+            } => {
+                // Values of at least 2^63 are converted after subtracting
+                // 2^63, which is added back by flipping the top bit.
+                let p = prec_suffix(*prec);
+                let (dst, src) = (self.gpr(*dst, Size::Q), self.xmm(*src));
+                let ftmp = self.xmm(*ftmp);
+                let (bits, size) = match prec {
+                    Prec::S => {
+                        (((1u64 << 63) as f32).to_bits() as i64, Size::L)
+                    }
+                    Prec::D => {
+                        (((1u64 << 63) as f64).to_bits() as i64, Size::Q)
+                    }
+                };
+                let (wide, tmp) =
+                    (self.gpr(*tmp, Size::Q), self.gpr(*tmp, size));
+                self.line(format_args!("movabsq ${bits}, {wide}"));
+                self.line(format_args!("mov{} {tmp}, {ftmp}", suffix(size)));
+                self.line(format_args!("ucomi{p} {ftmp}, {src}"));
+                self.line(format_args!("jae 1f"));
+                self.line(format_args!("cvtt{p}2si {src}, {dst}"));
+                self.line(format_args!("jmp 2f"));
+                writeln!(self.out, "1:").unwrap();
+                self.line(format_args!("sub{p} {ftmp}, {src}"));
+                self.line(format_args!("cvtt{p}2si {src}, {dst}"));
+                self.line(format_args!("btcq $63, {dst}"));
+                writeln!(self.out, "2:").unwrap();
+            }
+            Inst::Call { callee, .. } => match callee {
+                Callee::Symbol(name, true) => {
+                    self.line(format_args!("call {name}@PLT"))
+                }
+                Callee::Symbol(name, false) => {
+                    self.line(format_args!("call {name}"))
+                }
+                Callee::Reg(reg) => {
+                    let reg = self.gpr(*reg, Size::Q);
+                    self.line(format_args!("call *{reg}"));
+                }
+            },
+            Inst::Jmp(target) => {
+                let label = self.label(*target);
+                self.line(format_args!("jmp {label}"));
+            }
+            Inst::Jcc(c, target) => {
+                let label = self.label(*target);
+                self.line(format_args!("j{} {label}", cond(*c)));
+            }
+            Inst::Ret(_) => {
+                for &(reg, offset) in &self.frame.saved {
+                    let reg = gpr(reg, Size::Q);
+                    self.line(format_args!("movq {offset}(%rbp), {reg}"));
+                }
+                self.line(format_args!("leave"));
+                self.line(format_args!("ret"));
+            }
+            Inst::SaveVarargs(slot) => {
+                let base = self.frame.slots[slot.0 as usize];
+                for (i, &reg) in INT_ARGS.iter().enumerate() {
+                    let reg = gpr(reg, Size::Q);
+                    let offset = base + i as i64 * 8;
+                    self.line(format_args!("movq {reg}, {offset}(%rbp)"));
+                }
+                // `%al` holds the number of SSE registers used.
+                self.line(format_args!("testb %al, %al"));
+                self.line(format_args!("je 1f"));
+                for i in 0..FLOAT_ARGS {
+                    let offset = base + 48 + i as i64 * 16;
+                    self.line(format_args!("movaps %xmm{i}, {offset}(%rbp)"));
+                }
+                writeln!(self.out, "1:").unwrap();
+            }
+            Inst::Ud2 => self.line(format_args!("ud2")),
+        }
+    }
+}
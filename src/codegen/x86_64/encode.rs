@@ -0,0 +1,578 @@
+//! Encoding x86-64 machine code, for object files.
+//!
+//! The code is the same as that written out as text by [`emit`](super::emit),
+//! with the same frame layout, except that jumps always take 32-bit
+//! displacements.
+
+use super::emit::Frame;
+use super::*;
+use crate::codegen::MachFunction;
+use crate::codegen::elf::{Object, Section, SymbolKind};
+
+/// `R_X86_64_64`: a 64-bit address.
+pub const R_X86_64_64: u32 = 1;
+
+/// `R_X86_64_PC32`: a 32-bit offset to a symbol.
+const R_X86_64_PC32: u32 = 2;
+
+/// `R_X86_64_PLT32`: a 32-bit offset to the PLT entry of a function.
+const R_X86_64_PLT32: u32 = 4;
+
+/// `R_X86_64_GOTPCREL`: a 32-bit offset to the GOT entry of a symbol.
+const R_X86_64_GOTPCREL: u32 = 9;
+
+/// The register or memory operand of an instruction, encoded in its ModRM
+/// byte.
+enum Rm<'a> {
+    /// A register.
+    Reg(u8),
+
+    /// A base register and a displacement.
+    Mem(u8, i64),
+
+    /// A symbol relative to `%rip`, with the type of the relocation and the
+    /// displacement from the symbol.
+    Rip(&'a str, u32, i64),
+}
+
+/// Encode a function into an object file, after register allocation.
+pub fn function(obj: &mut Object, func: &MachFunction<Inst>, local: bool) {
+    let frame = Frame::new(func);
+    let mut enc = Encoder {
+        code: Vec::new(),
+        relocs: Vec::new(),
+        fixups: Vec::new(),
+        frame: &frame,
+    };
+    enc.code.push(0x55);
+    enc.int_op(Size::Q, 0x88, RSP, Rm::Reg(RBP), 0);
+    if frame.size > 0 {
+        enc.imm_op(Size::Q, 5, Rm::Reg(RSP), frame.size as i64);
+    }
+    for &(reg, offset) in &frame.saved {
+        enc.int_op(Size::Q, 0x88, reg, Rm::Mem(RBP, offset), 0);
+    }
+
+    let mut blocks = Vec::new();
+    for (index, block) in func.blocks.iter().enumerate() {
+        blocks.push(enc.code.len());
+        for inst in &block.insts {
+            // Jumps to the next block fall through.
+            if let Inst::Jmp(target) = inst
+                && *target == index + 1
+            {
+                continue;
+            }
+            enc.inst(inst);
+        }
+    }
+    for (at, block) in std::mem::take(&mut enc.fixups) {
+        enc.patch(at, blocks[block]);
+    }
+
+    let offset = obj.append(Section::Text, &enc.code, 16, 0x90);
+    let size = enc.code.len() as u64;
+    let kind = SymbolKind::Func;
+    obj.define(&func.name, Section::Text, offset, size, kind, local);
+    for (at, kind, symbol, addend) in enc.relocs {
+        obj.reloc(Section::Text, offset + at as u64, kind, &symbol, addend);
+    }
+}
+
+/// The encoding of a condition, added to the opcodes of `jcc` and `setcc`.
+fn cc(cond: Cond) -> u8 {
+    match cond {
+        Cond::B => 0x2,
+        Cond::Ae => 0x3,
+        Cond::E => 0x4,
+        Cond::Ne => 0x5,
+        Cond::Be => 0x6,
+        Cond::A => 0x7,
+        Cond::P => 0xa,
+        Cond::Np => 0xb,
+        Cond::L => 0xc,
+        Cond::Ge => 0xd,
+        Cond::Le => 0xe,
+        Cond::G => 0xf,
+    }
+}
+
+/// The mandatory prefix of scalar SSE instructions of a precision.
+fn fp(prec: Prec) -> Option<u8> {
+    match prec {
+        Prec::S => Some(0xf3),
+        Prec::D => Some(0xf2),
+    }
+}
+
+/// The number of bytes of an immediate of an integer operation.
+fn imm_len(size: Size) -> usize {
+    match size {
+        Size::B => 1,
+        Size::W => 2,
+        Size::L | Size::Q => 4,
+    }
+}
+
+/// The number of a machine register in instruction encodings.
+fn num(reg: Reg) -> u8 {
+    match reg {
+        // SSE registers are numbered from 0 like general-purpose ones.
+        Reg::Phys(reg) => reg & 15,
+        Reg::Virt(_) => panic!("virtual register after allocation"),
+    }
+}
+
+/// Encodes the instructions of a function.
+struct Encoder<'a> {
+    /// The code encoded so far.
+    code: Vec<u8>,
+
+    /// The relocations of the code: their offsets, types, symbols and
+    /// addends.
+    relocs: Vec<(usize, u32, String, i64)>,
+
+    /// The displacements of jumps to blocks, to be filled in once the
+    /// blocks are placed.
+    fixups: Vec<(usize, usize)>,
+
+    /// The layout of the frame.
+    frame: &'a Frame,
+}
+
+impl Encoder<'_> {
+    /// The operand for a memory location.
+    fn mem<'m>(&self, mem: &'m Mem) -> Rm<'m> {
+        match &mem.base {
+            Base::Reg(reg) => Rm::Mem(num(*reg), mem.disp),
+            Base::Slot(slot) => {
+                Rm::Mem(RBP, self.frame.slots[slot.0 as usize] + mem.disp)
+            }
+            Base::Symbol(name) => Rm::Rip(name, R_X86_64_PC32, mem.disp),
+            Base::Got(name) => Rm::Rip(name, R_X86_64_GOTPCREL, mem.disp),
+        }
+    }
+
+    /// The operand for a register or memory location.
+    fn rm<'m>(&self, opnd: &'m Opnd) -> Rm<'m> {
+        match opnd {
+            Opnd::Reg(reg) => Rm::Reg(num(*reg)),
+            Opnd::Mem(mem) => self.mem(mem),
+            Opnd::Imm(_) => unreachable!("immediates are encoded separately"),
+        }
+    }
+
+    /// Encode an instruction with a ModRM byte, followed by an immediate of
+    /// `imm` bytes.
+    ///
+    /// `reg` is a register or an opcode extension.  A size of `Q` selects
+    /// 64-bit operands, and `B` byte registers; other sizes change neither.
+    fn op(
+        &mut self,
+        prefix: Option<u8>,
+        size: Size,
+        opcode: &[u8],
+        reg: u8,
+        rm: Rm,
+        imm: usize,
+    ) {
+        self.code.extend(prefix);
+        let base = match rm {
+            Rm::Reg(reg) | Rm::Mem(reg, _) => reg,
+            Rm::Rip(..) => 0,
+        };
+        let w = (size == Size::Q) as u8;
+        let rex = w << 3 | (reg >> 3) << 2 | base >> 3;
+        // `%spl`, `%bpl`, `%sil` and `%dil` need a REX prefix.
+        let low = |reg: u8| (4..8).contains(&reg);
+        let forced =
+            size == Size::B && (low(reg) || matches!(rm, Rm::Reg(r) if low(r)));
+        if rex != 0 || forced {
+            self.code.push(0x40 | rex);
+        }
+        self.code.extend(opcode);
+
+        let reg = (reg & 7) << 3;
+        match rm {
+            Rm::Reg(rm) => self.code.push(0xc0 | reg | rm & 7),
+            Rm::Mem(base, disp) => {
+                // `%rbp` and `%r13` have no form without a displacement,
+                // and `%rsp` and `%r12` need a SIB byte.
+                let mode = if disp == 0 && base & 7 != RBP {
+                    0x00
+                } else if i8::try_from(disp).is_ok() {
+                    0x40
+                } else {
+                    0x80
+                };
+                self.code.push(mode | reg | base & 7);
+                if base & 7 == RSP {
+                    self.code.push(0x24);
+                }
+                match mode {
+                    0x40 => self.code.push(disp as u8),
+                    0x80 => self.code.extend((disp as i32).to_le_bytes()),
+                    _ => {}
+                }
+            }
+            Rm::Rip(symbol, kind, disp) => {
+                // The displacement is relative to the end of the
+                // instruction.
+                self.code.push(0x05 | reg);
+                let addend = disp - 4 - imm as i64;
+                let reloc = (self.code.len(), kind, symbol.to_string(), addend);
+                self.relocs.push(reloc);
+                self.code.extend([0; 4]);
+            }
+        }
+    }
+
+    /// Encode an integer operation of a size, given the opcode of its byte
+    /// form, which is one less than that of the other forms.
+    fn int_op(&mut self, size: Size, opcode: u8, reg: u8, rm: Rm, imm: usize) {
+        let prefix = (size == Size::W).then_some(0x66);
+        let opcode = opcode + (size != Size::B) as u8;
+        self.op(prefix, size, &[opcode], reg, rm, imm);
+    }
+
+    /// Encode an arithmetic operation with an immediate, given its opcode
+    /// extension.
+    fn imm_op(&mut self, size: Size, ext: u8, rm: Rm, value: i64) {
+        if size != Size::B && i8::try_from(value).is_ok() {
+            let prefix = (size == Size::W).then_some(0x66);
+            self.op(prefix, size, &[0x83], ext, rm, 1);
+            self.imm(value, 1);
+        } else {
+            self.int_op(size, 0x80, ext, rm, imm_len(size));
+            self.imm(value, imm_len(size));
+        }
+    }
+
+    /// Encode an immediate of a number of bytes.
+    fn imm(&mut self, value: i64, len: usize) {
+        self.code.extend(&value.to_le_bytes()[..len]);
+    }
+
+    /// Encode a jump with a 32-bit displacement, returning its offset.
+    fn jump(&mut self, opcode: &[u8]) -> usize {
+        self.code.extend(opcode);
+        let at = self.code.len();
+        self.code.extend([0; 4]);
+        at
+    }
+
+    /// Fill in the displacement of a jump at an offset, to a target.
+    fn patch(&mut self, at: usize, target: usize) {
+        let disp = target as i64 - (at + 4) as i64;
+        self.code[at..at + 4].copy_from_slice(&(disp as i32).to_le_bytes());
+    }
+
+    /// Fill in the displacement of a jump at an offset, to here.
+    fn land(&mut self, at: usize) {
+        self.patch(at, self.code.len());
+    }
+
+    /// Encode an instruction.
+    fn inst(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Mov(size, dst, src) => match (dst, src) {
+                (Opnd::Reg(dst), Opnd::Reg(_) | Opnd::Mem(_)) => {
+                    let src = self.rm(src);
+                    self.int_op(*size, 0x8a, num(*dst), src, 0);
+                }
+                (Opnd::Mem(dst), Opnd::Reg(src)) => {
+                    let dst = self.mem(dst);
+                    self.int_op(*size, 0x88, num(*src), dst, 0);
+                }
+                (dst, Opnd::Imm(value)) => {
+                    let dst = self.rm(dst);
+                    self.int_op(*size, 0xc6, 0, dst, imm_len(*size));
+                    self.imm(*value, imm_len(*size));
+                }
+                _ => unreachable!("moves between memory locations"),
+            },
+            Inst::MovAbs(dst, value) => {
+                let dst = num(*dst);
+                self.code.push(0x48 | dst >> 3);
+                self.code.push(0xb8 + (dst & 7));
+                self.code.extend(value.to_le_bytes());
+            }
+            Inst::Ext {
+                signed,
+                from,
+                to,
+                dst,
+                src,
+            } => {
+                let src = self.rm(src);
+                if !signed && *from == Size::L {
+                    // Writing a 32-bit register clears the upper half.
+                    self.op(None, Size::L, &[0x8b], num(*dst), src, 0);
+                    return;
+                }
+                let opcode = match (signed, from) {
+                    (true, Size::L) => &[0x63][..],
+                    (true, Size::B) => &[0x0f, 0xbe],
+                    (true, Size::W) => &[0x0f, 0xbf],
+                    (false, Size::B) => &[0x0f, 0xb6],
+                    (false, Size::W) => &[0x0f, 0xb7],
+                    _ => unreachable!("extension from 64 bits"),
+                };
+                let prefix = (*to == Size::W).then_some(0x66);
+                let size = match (from, to) {
+                    (_, Size::Q) => Size::Q,
+                    (Size::B, _) => Size::B,
+                    _ => Size::L,
+                };
+                self.op(prefix, size, opcode, num(*dst), src, 0);
+            }
+            Inst::Lea(dst, mem) => {
+                let mem = self.mem(mem);
+                self.op(None, Size::Q, &[0x8d], num(*dst), mem, 0);
+            }
+            Inst::Alu(AluOp::Imul, size, dst, src) => {
+                let prefix = (*size == Size::W).then_some(0x66);
+                let dst = num(*dst);
+                match src {
+                    Opnd::Imm(value) if i8::try_from(*value).is_ok() => {
+                        self.op(prefix, *size, &[0x6b], dst, Rm::Reg(dst), 1);
+                        self.imm(*value, 1);
+                    }
+                    Opnd::Imm(value) => {
+                        let len = imm_len(*size);
+                        let rm = Rm::Reg(dst);
+                        self.op(prefix, *size, &[0x69], dst, rm, len);
+                        self.imm(*value, len);
+                    }
+                    _ => {
+                        let src = self.rm(src);
+                        self.op(prefix, *size, &[0x0f, 0xaf], dst, src, 0);
+                    }
+                }
+            }
+            Inst::Alu(op, size, dst, src) => {
+                let (opcode, ext) = match op {
+                    AluOp::Add => (0x02, 0),
+                    AluOp::Or => (0x0a, 1),
+                    AluOp::And => (0x22, 4),
+                    AluOp::Sub => (0x2a, 5),
+                    AluOp::Xor => (0x32, 6),
+                    AluOp::Imul => unreachable!(),
+                };
+                match src {
+                    Opnd::Imm(value) => {
+                        self.imm_op(*size, ext, Rm::Reg(num(*dst)), *value)
+                    }
+                    _ => {
+                        let src = self.rm(src);
+                        self.int_op(*size, opcode, num(*dst), src, 0);
+                    }
+                }
+            }
+            Inst::Shift(op, size, dst, count) => {
+                let ext = match op {
+                    ShiftOp::Shl => 4,
+                    ShiftOp::Shr => 5,
+                    ShiftOp::Sar => 7,
+                };
+                let dst = Rm::Reg(num(*dst));
+                match count {
+                    Some(count) => {
+                        self.int_op(*size, 0xc0, ext, dst, 1);
+                        self.code.push(*count);
+                    }
+                    None => self.int_op(*size, 0xd2, ext, dst, 0),
+                }
+            }
+            Inst::Unary(op, size, dst) => {
+                let ext = match op {
+                    UnaryOp::Neg => 3,
+                    UnaryOp::Not => 2,
+                };
+                self.int_op(*size, 0xf6, ext, Rm::Reg(num(*dst)), 0);
+            }
+            Inst::Cmp(size, a, b) => match b {
+                Opnd::Imm(value) => {
+                    self.imm_op(*size, 7, Rm::Reg(num(*a)), *value)
+                }
+                _ => {
+                    let b = self.rm(b);
+                    self.int_op(*size, 0x3a, num(*a), b, 0);
+                }
+            },
+            Inst::Set(c, dst) => {
+                let opcode = [0x0f, 0x90 + cc(*c)];
+                self.op(None, Size::B, &opcode, 0, Rm::Reg(num(*dst)), 0);
+            }
+            Inst::Cqo(size) => match size {
+                Size::Q => self.code.extend([0x48, 0x99]),
+                _ => self.code.push(0x99),
+            },
+            Inst::Div { signed, size, src } => {
+                let ext = if *signed { 7 } else { 6 };
+                self.int_op(*size, 0xf6, ext, Rm::Reg(num(*src)), 0);
+            }
+            Inst::FMov(prec, dst, src) => match (dst, src) {
+                (Opnd::Reg(dst), Opnd::Reg(src)) => {
+                    let (dst, src) = (num(*dst), Rm::Reg(num(*src)));
+                    self.op(None, Size::L, &[0x0f, 0x28], dst, src, 0);
+                }
+                (Opnd::Reg(dst), Opnd::Mem(src)) => {
+                    let src = self.mem(src);
+                    let opcode = [0x0f, 0x10];
+                    self.op(fp(*prec), Size::L, &opcode, num(*dst), src, 0);
+                }
+                (Opnd::Mem(dst), Opnd::Reg(src)) => {
+                    let dst = self.mem(dst);
+                    let opcode = [0x0f, 0x11];
+                    self.op(fp(*prec), Size::L, &opcode, num(*src), dst, 0);
+                }
+                _ => unreachable!("moves between memory locations"),
+            },
+            Inst::FAlu(op, prec, dst, src) => {
+                let src = self.rm(src);
+                let (prefix, opcode) = match op {
+                    FAluOp::Add => (fp(*prec), 0x58),
+                    FAluOp::Mul => (fp(*prec), 0x59),
+                    FAluOp::Sub => (fp(*prec), 0x5c),
+                    FAluOp::Div => (fp(*prec), 0x5e),
+                    FAluOp::Xor => (None, 0x57),
+                };
+                let opcode = [0x0f, opcode];
+                self.op(prefix, Size::L, &opcode, num(*dst), src, 0);
+            }
+            Inst::Ucomi(prec, a, b) => {
+                let b = self.rm(b);
+                let prefix = (*prec == Prec::D).then_some(0x66);
+                self.op(prefix, Size::L, &[0x0f, 0x2e], num(*a), b, 0);
+            }
+            Inst::MovBits(size, dst, src) => {
+                let (dst, src) = (num(*dst), Rm::Reg(num(*src)));
+                self.op(Some(0x66), *size, &[0x0f, 0x6e], dst, src, 0);
+            }
+            Inst::CvtIntToFloat(size, prec, dst, src) => {
+                let (dst, src) = (num(*dst), Rm::Reg(num(*src)));
+                self.op(fp(*prec), *size, &[0x0f, 0x2a], dst, src, 0);
+            }
+            Inst::CvtFloatToInt(prec, size, dst, src) => {
+                let (dst, src) = (num(*dst), Rm::Reg(num(*src)));
+                self.op(fp(*prec), *size, &[0x0f, 0x2c], dst, src, 0);
+            }
+            Inst::CvtFloat(from, dst, src) => {
+                let (dst, src) = (num(*dst), Rm::Reg(num(*src)));
+                self.op(fp(*from), Size::L, &[0x0f, 0x5a], dst, src, 0);
+            }
+            Inst::U64ToFloat {
+                prec,
+                dst,
+                src,
+                tmp,
+            } => {
+                // As in the assembly text: values with the top bit set are
+                // halved, keeping the low bit, then converted and doubled.
+                let convert = Inst::CvtIntToFloat(Size::Q, *prec, *dst, *src);
+                let s = num(*src);
+                self.int_op(Size::Q, 0x84, s, Rm::Reg(s), 0);
+                // `js`.
+                let negative = self.jump(&[0x0f, 0x88]);
+                self.inst(&convert);
+                let done = self.jump(&[0xe9]);
+                self.land(negative);
+                let one = Opnd::Imm(1);
+                self.inst(&Inst::Mov(
+                    Size::Q,
+                    Opnd::Reg(*tmp),
+                    Opnd::Reg(*src),
+                ));
+                self.inst(&Inst::Shift(ShiftOp::Shr, Size::Q, *src, Some(1)));
+                self.inst(&Inst::Alu(AluOp::And, Size::Q, *tmp, one));
+                self.inst(&Inst::Alu(
+                    AluOp::Or,
+                    Size::Q,
+                    *src,
+                    Opnd::Reg(*tmp),
+                ));
+                self.inst(&convert);
+                let double =
+                    Inst::FAlu(FAluOp::Add, *prec, *dst, Opnd::Reg(*dst));
+                self.inst(&double);
+                self.land(done);
+            }
+            Inst::FloatToU64 {
+                prec,
+                dst,
+                src,
+                tmp,
+                ftmp,
+            } => {
+                // Values of at least 2^63 are converted after subtracting
+                // 2^63, which is added back by flipping the top bit.
+                let (bits, size) = match prec {
+                    Prec::S => {
+                        (((1u64 << 63) as f32).to_bits() as i64, Size::L)
+                    }
+                    Prec::D => {
+                        (((1u64 << 63) as f64).to_bits() as i64, Size::Q)
+                    }
+                };
+                let convert = Inst::CvtFloatToInt(*prec, Size::Q, *dst, *src);
+                self.inst(&Inst::MovAbs(*tmp, bits));
+                self.inst(&Inst::MovBits(size, *ftmp, *tmp));
+                self.inst(&Inst::Ucomi(*prec, *src, Opnd::Reg(*ftmp)));
+                let large = self.jump(&[0x0f, 0x80 + cc(Cond::Ae)]);
+                self.inst(&convert);
+                let done = self.jump(&[0xe9]);
+                self.land(large);
+                let ftmp = Opnd::Reg(*ftmp);
+                self.inst(&Inst::FAlu(FAluOp::Sub, *prec, *src, ftmp));
+                self.inst(&convert);
+                let dst = Rm::Reg(num(*dst));
+                self.op(None, Size::Q, &[0x0f, 0xba], 7, dst, 1);
+                self.code.push(63);
+                self.land(done);
+            }
+            Inst::Call { callee, .. } => match callee {
+                Callee::Symbol(name, plt) => {
+                    let kind =
+                        if *plt { R_X86_64_PLT32 } else { R_X86_64_PC32 };
+                    let at = self.jump(&[0xe8]);
+                    self.relocs.push((at, kind, name.clone(), -4));
+                }
+                Callee::Reg(reg) => {
+                    self.op(None, Size::L, &[0xff], 2, Rm::Reg(num(*reg)), 0)
+                }
+            },
+            Inst::Jmp(target) => {
+                let at = self.jump(&[0xe9]);
+                self.fixups.push((at, *target));
+            }
+            Inst::Jcc(c, target) => {
+                let at = self.jump(&[0x0f, 0x80 + cc(*c)]);
+                self.fixups.push((at, *target));
+            }
+            Inst::Ret(_) => {
+                for &(reg, offset) in &self.frame.saved {
+                    self.int_op(Size::Q, 0x8a, reg, Rm::Mem(RBP, offset), 0);
+                }
+                // `leave` and `ret`.
+                self.code.extend([0xc9, 0xc3]);
+            }
+            Inst::SaveVarargs(slot) => {
+                let base = self.frame.slots[slot.0 as usize];
+                for (i, &reg) in INT_ARGS.iter().enumerate() {
+                    let mem = Rm::Mem(RBP, base + i as i64 * 8);
+                    self.int_op(Size::Q, 0x88, reg, mem, 0);
+                }
+                // `%al` holds the number of SSE registers used.
+                self.int_op(Size::B, 0x84, RAX, Rm::Reg(RAX), 0);
+                let none = self.jump(&[0x0f, 0x80 + cc(Cond::E)]);
+                for i in 0..FLOAT_ARGS {
+                    let mem = Rm::Mem(RBP, base + 48 + i as i64 * 16);
+                    self.op(None, Size::L, &[0x0f, 0x29], i, mem, 0);
+                }
+                self.land(none);
+            }
+            Inst::Ud2 => self.code.extend([0x0f, 0x0b]),
+        }
+    }
+}
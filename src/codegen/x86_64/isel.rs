@@ -0,0 +1,1099 @@
+//! Instruction selection for x86-64.
+//!
+//! Each register local becomes a virtual register, and each memory local a
+//! stack slot.  Integers narrower than 32 bits are computed in 32-bit
+//! registers, leaving the upper bits unspecified; they are extended where
+//! that matters (division, right shifts and conversions), and compared and
+//! stored at their own size.
+//!
+//! Aggregates are passed and returned as the System V ABI classifies them:
+//! those of up to 16 bytes travel in registers, one for each eightbyte,
+//! unless there are not enough registers left; larger ones are copied onto
+//! the stack, or returned through a hidden pointer.
+
+use std::collections::HashSet;
+
+use super::*;
+use crate::codegen::{Class, MachFunction, Reg, Slot, scalars};
+use crate::ctype::{CType, FnType, TypeTable};
+use crate::mir::{
+    BinOp, CastOp, Const, Function, Inst as MirInst, Local, Operand, Rvalue,
+    Terminator, Ty, UnaOp,
+};
+
+/// The state of instruction selection for a function.
+struct Isel<'a> {
+    /// The structure types of the module.
+    types: &'a TypeTable,
+
+    /// The symbols defined by the module, which are addressed directly
+    /// rather than through the global offset table.
+    defined: &'a HashSet<String>,
+
+    /// The function being translated.
+    func: &'a Function,
+
+    /// The translated function.
+    out: MachFunction<Inst>,
+
+    /// The virtual register of each register local.
+    regs: Vec<Option<Reg>>,
+
+    /// The stack slot of each memory local.
+    slots: Vec<Option<Slot>>,
+
+    /// The block instructions are added to.
+    block: usize,
+
+    /// The register holding the address an aggregate result is returned
+    /// to, if it is returned in memory.
+    sret: Option<Reg>,
+
+    /// The register save area of a variadic function, and the arguments
+    /// its named parameters take up.
+    varargs: Option<(Slot, Args)>,
+}
+
+/// Translate a function out of SSA form into x86-64 instructions.
+pub fn select(
+    func: &Function,
+    types: &TypeTable,
+    defined: &HashSet<String>,
+) -> MachFunction<Inst> {
+    let mut isel = Isel {
+        types,
+        defined,
+        func,
+        out: MachFunction::new(func.name.clone(), func.blocks.len()),
+        regs: Vec::new(),
+        slots: Vec::new(),
+        block: 0,
+        sret: None,
+        varargs: None,
+    };
+    for decl in &func.locals {
+        if decl.memory {
+            let size = types.size_of(&decl.ty).next_multiple_of(8).max(8);
+            let align = types.align_of(&decl.ty).max(8);
+            isel.slots.push(Some(isel.out.new_slot(size, align)));
+            isel.regs.push(None);
+        } else {
+            let class = Ty::of(&decl.ty, MODEL).map_or(Class::Int, class);
+            isel.regs.push(Some(isel.out.new_vreg(class)));
+            isel.slots.push(None);
+        }
+    }
+
+    isel.params();
+    for id in func.block_ids() {
+        isel.block = id.0 as usize;
+        let block = func.block(id);
+        for inst in &block.insts {
+            isel.inst(inst);
+        }
+        isel.term(&block.term);
+        let succs = block.term.successors();
+        isel.out.blocks[isel.block].succs =
+            succs.iter().map(|succ| succ.0 as usize).collect();
+    }
+    isel.out
+}
+
+//----------- Types ------------------------------------------------------------
+
+/// The size of a value of a machine type.
+fn size(ty: Ty) -> Size {
+    match ty {
+        Ty::I8 => Size::B,
+        Ty::I16 => Size::W,
+        Ty::I32 | Ty::F32 => Size::L,
+        Ty::I64 | Ty::F64 | Ty::Ptr => Size::Q,
+    }
+}
+
+/// The size integer operations on a machine type are computed in.
+fn op_size(ty: Ty) -> Size {
+    match ty {
+        Ty::I64 | Ty::Ptr => Size::Q,
+        _ => Size::L,
+    }
+}
+
+/// The register class holding a machine type.
+fn class(ty: Ty) -> Class {
+    if ty.is_float() {
+        Class::Float
+    } else {
+        Class::Int
+    }
+}
+
+/// The precision of a floating-point type.
+fn prec(ty: Ty) -> Prec {
+    match ty {
+        Ty::F32 => Prec::S,
+        _ => Prec::D,
+    }
+}
+
+/// Whether a C type is a signed integer type.
+fn signed(ty: &CType) -> bool {
+    matches!(ty, CType::Int(int) if int.signed)
+}
+
+/// The classes of the eightbytes of an aggregate passed in registers, or
+/// `None` if it is passed in memory.
+fn classify(types: &TypeTable, ty: &CType) -> Option<Vec<Class>> {
+    let size = types.size_of(ty);
+    if size > 16 {
+        return None;
+    }
+    // An eightbyte is passed in an SSE register if it only holds floats.
+    let mut classes = vec![Class::Float; size.div_ceil(8) as usize];
+    scalars(types, ty, 0, &mut |offset, ty| {
+        if !ty.is_float() {
+            classes[(offset / 8) as usize] = Class::Int;
+        }
+    });
+    Some(classes)
+}
+
+/// Where a value is passed.
+enum Place {
+    /// In registers, one for each eightbyte.
+    Regs(Vec<u8>),
+
+    /// On the stack, at an offset from the first stack argument.
+    Stack(u64),
+}
+
+/// The assignment of arguments to registers and the stack.
+#[derive(Copy, Clone, Default)]
+struct Args {
+    /// The number of integer registers used.
+    ints: usize,
+
+    /// The number of SSE registers used.
+    floats: u8,
+
+    /// The size of the stack arguments.
+    stack: u64,
+}
+
+impl Args {
+    /// Assign the next argument.
+    fn place(&mut self, types: &TypeTable, ty: &CType) -> Place {
+        let classes = match Ty::of(ty, MODEL) {
+            Some(ty) => Some(vec![class(ty)]),
+            None => classify(types, ty),
+        };
+        if let Some(classes) = classes {
+            let ints = classes.iter().filter(|&&c| c == Class::Int).count();
+            let floats = classes.len() - ints;
+            if self.ints + ints <= INT_ARGS.len()
+                && self.floats as usize + floats <= FLOAT_ARGS as usize
+            {
+                let regs = classes.iter().map(|class| match class {
+                    Class::Int => {
+                        self.ints += 1;
+                        INT_ARGS[self.ints - 1]
+                    }
+                    Class::Float => {
+                        self.floats += 1;
+                        XMM0 + self.floats - 1
+                    }
+                });
+                return Place::Regs(regs.collect());
+            }
+        }
+        let offset = self.stack;
+        self.stack += types.size_of(ty).next_multiple_of(8).max(8);
+        Place::Stack(offset)
+    }
+}
+
+/// The registers a value of a type is returned in, or `None` if it is
+/// returned in memory.
+fn returned(types: &TypeTable, ty: &CType) -> Option<Vec<u8>> {
+    let classes = match Ty::of(ty, MODEL) {
+        Some(ty) => vec![class(ty)],
+        None if *ty == CType::Void => Vec::new(),
+        None => classify(types, ty)?,
+    };
+    let (mut ints, mut floats) = ([RAX, RDX].into_iter(), XMM0..);
+    let regs = classes.iter().map(|class| match class {
+        Class::Int => ints.next().unwrap(),
+        Class::Float => floats.next().unwrap(),
+    });
+    Some(regs.collect())
+}
+
+/// The class of a machine register.
+fn reg_class(reg: u8) -> Class {
+    if reg >= XMM0 {
+        Class::Float
+    } else {
+        Class::Int
+    }
+}
+
+/// A memory operand for a stack argument.
+fn stack_arg(base: u8, offset: u64) -> Mem {
+    Mem {
+        base: Base::Reg(Reg::Phys(base)),
+        disp: offset as i64,
+    }
+}
+
+//----------- Operands ---------------------------------------------------------
+
+impl Isel<'_> {
+    /// Add an instruction to the current block.
+    fn emit(&mut self, inst: Inst) {
+        self.out.blocks[self.block].insts.push(inst);
+    }
+
+    /// The virtual register of a register local.
+    fn vreg(&self, local: Local) -> Reg {
+        self.regs[local.0 as usize].expect("register locals have registers")
+    }
+
+    /// The stack slot of a memory local.
+    fn slot(&self, local: Local) -> Slot {
+        self.slots[local.0 as usize].expect("memory locals have slots")
+    }
+
+    /// A new virtual register.
+    fn temp(&mut self, class: Class) -> Reg {
+        self.out.new_vreg(class)
+    }
+
+    /// An operand as a register or an immediate.
+    fn value(&mut self, operand: &Operand) -> Opnd {
+        match operand {
+            Operand::Local(local) => Opnd::Reg(self.vreg(*local)),
+            Operand::Const(Const::Int(value, _))
+                if i32::try_from(*value).is_ok() =>
+            {
+                Opnd::Imm(*value)
+            }
+            _ => Opnd::Reg(self.reg(operand)),
+        }
+    }
+
+    /// An operand in a register.
+    fn reg(&mut self, operand: &Operand) -> Reg {
+        match operand {
+            Operand::Local(local) => self.vreg(*local),
+            Operand::Const(Const::Int(value, ty)) => {
+                let reg = self.temp(Class::Int);
+                if i32::try_from(*value).is_ok() {
+                    let value = Opnd::Imm(*value);
+                    self.emit(Inst::Mov(op_size(*ty), Opnd::Reg(reg), value));
+                } else {
+                    self.emit(Inst::MovAbs(reg, *value));
+                }
+                reg
+            }
+            Operand::Const(Const::Float(value, ty)) => {
+                let bits = self.temp(Class::Int);
+                let size = if *ty == Ty::F32 {
+                    let bits_value = (*value as f32).to_bits() as i32 as i64;
+                    self.emit(Inst::Mov(
+                        Size::L,
+                        Opnd::Reg(bits),
+                        Opnd::Imm(bits_value),
+                    ));
+                    Size::L
+                } else {
+                    self.emit(Inst::MovAbs(bits, value.to_bits() as i64));
+                    Size::Q
+                };
+                let reg = self.temp(Class::Float);
+                self.emit(Inst::MovBits(size, reg, bits));
+                reg
+            }
+            Operand::Const(Const::Symbol(name))
+                if !self.defined.contains(name) =>
+            {
+                let reg = self.temp(Class::Int);
+                let got = Mem {
+                    base: Base::Got(name.clone()),
+                    disp: 0,
+                };
+                self.emit(Inst::Mov(Size::Q, Opnd::Reg(reg), Opnd::Mem(got)));
+                reg
+            }
+            Operand::Const(Const::Symbol(_)) | Operand::Addr(_) => {
+                let reg = self.temp(Class::Int);
+                let mem = self.mem(operand, 0);
+                self.emit(Inst::Lea(reg, mem));
+                reg
+            }
+        }
+    }
+
+    /// A memory operand for an address, moved by a displacement.
+    fn mem(&mut self, addr: &Operand, disp: i64) -> Mem {
+        let base = match addr {
+            Operand::Addr(local) => Base::Slot(self.slot(*local)),
+            Operand::Const(Const::Symbol(name))
+                if self.defined.contains(name) =>
+            {
+                Base::Symbol(name.clone())
+            }
+            _ => Base::Reg(self.reg(addr)),
+        };
+        Mem { base, disp }
+    }
+
+    /// An integer operand extended to 32 bits, if it is narrower.
+    fn extend(&mut self, operand: &Operand, ty: Ty, signed: bool) -> Opnd {
+        if !matches!(ty, Ty::I8 | Ty::I16) {
+            return self.value(operand);
+        }
+        let src = Opnd::Reg(self.reg(operand));
+        let dst = self.temp(Class::Int);
+        self.emit(Inst::Ext {
+            signed,
+            from: size(ty),
+            to: Size::L,
+            dst,
+            src,
+        });
+        Opnd::Reg(dst)
+    }
+
+    /// Load a value of a machine type from memory into a register.
+    fn load(&mut self, dst: Reg, ty: Ty, mem: Mem) {
+        let src = Opnd::Mem(mem);
+        self.emit(match ty {
+            Ty::I8 | Ty::I16 => Inst::Ext {
+                signed: false,
+                from: size(ty),
+                to: Size::L,
+                dst,
+                src,
+            },
+            Ty::F32 | Ty::F64 => Inst::FMov(prec(ty), Opnd::Reg(dst), src),
+            _ => Inst::Mov(size(ty), Opnd::Reg(dst), src),
+        });
+    }
+
+    /// Copy bytes between memory locations.
+    fn copy(&mut self, dst: Mem, src: Mem, size: u64) {
+        let mut offset = 0;
+        for (chunk, chunk_size) in
+            [(8, Size::Q), (4, Size::L), (2, Size::W), (1, Size::B)]
+        {
+            while size - offset >= chunk {
+                let temp = self.temp(Class::Int);
+                let from = Opnd::Mem(src.offset(offset));
+                self.emit(Inst::Mov(chunk_size, Opnd::Reg(temp), from));
+                let to = Opnd::Mem(dst.offset(offset));
+                self.emit(Inst::Mov(chunk_size, to, Opnd::Reg(temp)));
+                offset += chunk;
+            }
+        }
+    }
+
+    /// Load an eightbyte of an aggregate, of which only the first `size`
+    /// bytes may be read.
+    fn load_eightbyte(&mut self, mem: Mem, size: u64, class: Class) -> Reg {
+        let reg = self.temp(class);
+        if class == Class::Float {
+            let prec = if size <= 4 { Prec::S } else { Prec::D };
+            self.emit(Inst::FMov(prec, Opnd::Reg(reg), Opnd::Mem(mem)));
+            return reg;
+        }
+        // The eightbyte is assembled from pieces of decreasing size.
+        let mut offset = 0;
+        for (piece, ty) in
+            [(8, Ty::I64), (4, Ty::I32), (2, Ty::I16), (1, Ty::I8)]
+        {
+            if size - offset < piece {
+                continue;
+            }
+            if offset == 0 {
+                self.load(reg, ty, mem.offset(0));
+            } else {
+                let temp = self.temp(Class::Int);
+                self.load(temp, ty, mem.offset(offset));
+                let shift = Some(offset as u8 * 8);
+                self.emit(Inst::Shift(ShiftOp::Shl, Size::Q, temp, shift));
+                self.emit(Inst::Alu(AluOp::Or, Size::Q, reg, Opnd::Reg(temp)));
+            }
+            offset += piece;
+        }
+        reg
+    }
+
+    /// Copy a value into a machine register.
+    fn move_to(&mut self, reg: u8, value: Opnd) {
+        let dst = Opnd::Reg(Reg::Phys(reg));
+        self.emit(match reg_class(reg) {
+            Class::Int => Inst::Mov(Size::Q, dst, value),
+            Class::Float => Inst::FMov(Prec::D, dst, value),
+        });
+    }
+
+    /// Copy a machine register into a value.
+    fn move_from(&mut self, dst: Opnd, reg: u8) {
+        let src = Opnd::Reg(Reg::Phys(reg));
+        self.emit(match reg_class(reg) {
+            Class::Int => Inst::Mov(Size::Q, dst, src),
+            Class::Float => Inst::FMov(Prec::D, dst, src),
+        });
+    }
+
+    /// A scalar passed to or returned from another function, with integers
+    /// narrower than 32 bits extended as the C type requires.
+    fn scalar_arg(&mut self, operand: &Operand, ty: &CType) -> Opnd {
+        let mty = self.func.operand_ty(operand);
+        match operand {
+            Operand::Local(_) => self.extend(operand, mty, signed(ty)),
+            _ => self.value(operand),
+        }
+    }
+
+    /// Load the eightbytes of an aggregate into new registers.
+    fn load_aggregate(
+        &mut self,
+        addr: &Operand,
+        ty: &CType,
+        regs: &[u8],
+    ) -> Vec<Reg> {
+        let size = self.types.size_of(ty);
+        let mem = self.mem(addr, 0);
+        (0..regs.len() as u64)
+            .map(|i| {
+                let part = (size - i * 8).min(8);
+                let class = reg_class(regs[i as usize]);
+                self.load_eightbyte(mem.offset(i * 8), part, class)
+            })
+            .collect()
+    }
+}
+
+//----------- Instructions -----------------------------------------------------
+
+impl Isel<'_> {
+    /// Receive the parameters on entry to the function.
+    fn params(&mut self) {
+        let func = self.func;
+        let save = func.ty.variadic.then(|| {
+            let slot = self.out.new_slot(SAVE_AREA, 16);
+            self.emit(Inst::SaveVarargs(slot));
+            slot
+        });
+        let mut args = Args::default();
+        if returned(self.types, &func.ty.ret).is_none() {
+            let sret = self.temp(Class::Int);
+            self.move_from(Opnd::Reg(sret), INT_ARGS[0]);
+            self.sret = Some(sret);
+            args.ints = 1;
+        }
+
+        for (&param, ty) in func.params.iter().zip(&func.ty.params) {
+            let place = args.place(self.types, ty);
+            match (place, Ty::of(ty, MODEL)) {
+                (Place::Regs(regs), Some(_)) => {
+                    let dst = Opnd::Reg(self.vreg(param));
+                    self.move_from(dst, regs[0]);
+                }
+                (Place::Stack(offset), Some(mty)) => {
+                    let dst = self.vreg(param);
+                    self.load(dst, mty, stack_arg(RBP, 16 + offset));
+                }
+                (Place::Regs(regs), None) => {
+                    let slot = self.slot(param);
+                    for (i, &reg) in regs.iter().enumerate() {
+                        let dst = Opnd::Mem(Mem {
+                            base: Base::Slot(slot),
+                            disp: i as i64 * 8,
+                        });
+                        self.move_from(dst, reg);
+                    }
+                }
+                (Place::Stack(offset), None) => {
+                    let dst = Mem {
+                        base: Base::Slot(self.slot(param)),
+                        disp: 0,
+                    };
+                    let size = self.types.size_of(ty);
+                    self.copy(dst, stack_arg(RBP, 16 + offset), size);
+                }
+            }
+        }
+        self.varargs = save.map(|slot| (slot, args));
+    }
+
+    /// Translate an instruction.
+    fn inst(&mut self, inst: &MirInst) {
+        match inst {
+            MirInst::Assign(dest, rvalue) => self.assign(*dest, rvalue),
+            MirInst::Store { addr, value } => {
+                let ty = self.func.operand_ty(value);
+                let mem = Opnd::Mem(self.mem(addr, 0));
+                if ty.is_float() {
+                    let value = Opnd::Reg(self.reg(value));
+                    self.emit(Inst::FMov(prec(ty), mem, value));
+                } else {
+                    let value = self.value(value);
+                    self.emit(Inst::Mov(size(ty), mem, value));
+                }
+            }
+            MirInst::Call {
+                dest,
+                callee,
+                args,
+                sig,
+                ..
+            } => self.call(*dest, callee, args, sig),
+            MirInst::VaStart(addr) => self.va_start(addr),
+        }
+    }
+
+    /// Set up a `va_list` to walk the arguments past the named parameters.
+    ///
+    /// The offsets into the save area skip the registers of the named
+    /// parameters, and the overflow area starts past their stack arguments.
+    fn va_start(&mut self, addr: &Operand) {
+        let (slot, args) = self.varargs.expect("`va_start` is variadic");
+        let gp_offset = Opnd::Imm(args.ints as i64 * 8);
+        let fp_offset = Opnd::Imm(6 * 8 + args.floats as i64 * 16);
+        let overflow = self.temp(Class::Int);
+        self.emit(Inst::Lea(overflow, stack_arg(RBP, 16 + args.stack)));
+        let save = self.temp(Class::Int);
+        let base = Base::Slot(slot);
+        self.emit(Inst::Lea(save, Mem { base, disp: 0 }));
+        let fields = [
+            (Size::L, 0, gp_offset),
+            (Size::L, 4, fp_offset),
+            (Size::Q, 8, Opnd::Reg(overflow)),
+            (Size::Q, 16, Opnd::Reg(save)),
+        ];
+        for (size, disp, value) in fields {
+            let mem = Opnd::Mem(self.mem(addr, disp));
+            self.emit(Inst::Mov(size, mem, value));
+        }
+    }
+
+    /// Translate an assignment to a register.
+    fn assign(&mut self, dest: Local, rvalue: &Rvalue) {
+        let ty = self.func.ty(dest);
+        let dst = self.vreg(dest);
+        match rvalue {
+            Rvalue::Use(value) => {
+                if ty.is_float() {
+                    let value = Opnd::Reg(self.reg(value));
+                    self.emit(Inst::FMov(prec(ty), Opnd::Reg(dst), value));
+                } else {
+                    let value = self.value(value);
+                    self.emit(Inst::Mov(op_size(ty), Opnd::Reg(dst), value));
+                }
+            }
+            Rvalue::Bin(op, a, b) => self.bin(*op, dst, ty, a, b),
+            Rvalue::Una(op, a) => match op {
+                UnaOp::Neg | UnaOp::Not => {
+                    let value = self.value(a);
+                    let size = op_size(ty);
+                    self.emit(Inst::Mov(size, Opnd::Reg(dst), value));
+                    let op = match op {
+                        UnaOp::Neg => UnaryOp::Neg,
+                        _ => UnaryOp::Not,
+                    };
+                    self.emit(Inst::Unary(op, size, dst));
+                }
+                UnaOp::FNeg => {
+                    // Flip the sign bit.
+                    let bits = self.temp(Class::Int);
+                    let size = if ty == Ty::F32 {
+                        let sign = Opnd::Imm(i32::MIN as i64);
+                        self.emit(Inst::Mov(Size::L, Opnd::Reg(bits), sign));
+                        Size::L
+                    } else {
+                        self.emit(Inst::MovAbs(bits, i64::MIN));
+                        Size::Q
+                    };
+                    let mask = self.temp(Class::Float);
+                    self.emit(Inst::MovBits(size, mask, bits));
+                    let value = Opnd::Reg(self.reg(a));
+                    self.emit(Inst::FMov(prec(ty), Opnd::Reg(dst), value));
+                    let mask = Opnd::Reg(mask);
+                    self.emit(Inst::FAlu(FAluOp::Xor, prec(ty), dst, mask));
+                }
+            },
+            Rvalue::Cast(op, a) => self.cast(*op, dst, ty, a),
+            Rvalue::Load(addr) => {
+                let mem = self.mem(addr, 0);
+                self.load(dst, ty, mem);
+            }
+            Rvalue::FieldAddr(base, id, field) => {
+                let offset = self.types.layout(*id).offsets[*field];
+                let mem = self.mem(base, offset as i64);
+                self.emit(Inst::Lea(dst, mem));
+            }
+            Rvalue::ElemAddr(base, index, elem) => {
+                let elem_size = self.types.size_of(elem) as i64;
+                if let Operand::Const(Const::Int(index, _)) = index {
+                    let mem = self.mem(base, index * elem_size);
+                    self.emit(Inst::Lea(dst, mem));
+                    return;
+                }
+                let index = self.value(index);
+                let base = self.value(base);
+                self.emit(Inst::Mov(Size::Q, Opnd::Reg(dst), index));
+                if elem_size > 0 && elem_size & (elem_size - 1) == 0 {
+                    let shift = elem_size.trailing_zeros() as u8;
+                    if shift > 0 {
+                        let shift = Some(shift);
+                        self.emit(Inst::Shift(
+                            ShiftOp::Shl,
+                            Size::Q,
+                            dst,
+                            shift,
+                        ));
+                    }
+                } else {
+                    let elem_size = Opnd::Imm(elem_size);
+                    self.emit(Inst::Alu(AluOp::Imul, Size::Q, dst, elem_size));
+                }
+                self.emit(Inst::Alu(AluOp::Add, Size::Q, dst, base));
+            }
+            Rvalue::Phi(_) => unreachable!("phis are removed before selection"),
+        }
+    }
+
+    /// Translate a binary operation.
+    fn bin(&mut self, op: BinOp, dst: Reg, ty: Ty, a: &Operand, b: &Operand) {
+        let size = op_size(ty);
+        let alu = match op {
+            BinOp::Add => Some(AluOp::Add),
+            BinOp::Sub => Some(AluOp::Sub),
+            BinOp::Mul => Some(AluOp::Imul),
+            BinOp::And => Some(AluOp::And),
+            BinOp::Or => Some(AluOp::Or),
+            BinOp::Xor => Some(AluOp::Xor),
+            _ => None,
+        };
+        if let Some(alu) = alu {
+            let (a, b) = (self.value(a), self.value(b));
+            self.emit(Inst::Mov(size, Opnd::Reg(dst), a));
+            self.emit(Inst::Alu(alu, size, dst, b));
+            return;
+        }
+        let falu = match op {
+            BinOp::FAdd => Some(FAluOp::Add),
+            BinOp::FSub => Some(FAluOp::Sub),
+            BinOp::FMul => Some(FAluOp::Mul),
+            BinOp::FDiv => Some(FAluOp::Div),
+            _ => None,
+        };
+        if let Some(falu) = falu {
+            let (a, b) = (self.reg(a), self.reg(b));
+            self.emit(Inst::FMov(prec(ty), Opnd::Reg(dst), Opnd::Reg(a)));
+            self.emit(Inst::FAlu(falu, prec(ty), dst, Opnd::Reg(b)));
+            return;
+        }
+
+        match op {
+            BinOp::SDiv | BinOp::UDiv | BinOp::SRem | BinOp::URem => {
+                let signed = matches!(op, BinOp::SDiv | BinOp::SRem);
+                let a = self.extend(a, ty, signed);
+                let b = match self.extend(b, ty, signed) {
+                    Opnd::Reg(reg) => reg,
+                    _ => self.reg(b),
+                };
+                self.move_to(RAX, a);
+                if signed {
+                    self.emit(Inst::Cqo(size));
+                } else {
+                    let zero = Opnd::Imm(0);
+                    self.emit(Inst::Mov(
+                        Size::L,
+                        Opnd::Reg(Reg::Phys(RDX)),
+                        zero,
+                    ));
+                }
+                self.emit(Inst::Div {
+                    signed,
+                    size,
+                    src: b,
+                });
+                let result = match op {
+                    BinOp::SDiv | BinOp::UDiv => RAX,
+                    _ => RDX,
+                };
+                self.move_from(Opnd::Reg(dst), result);
+            }
+            BinOp::Shl | BinOp::LShr | BinOp::AShr => {
+                let count = match b {
+                    Operand::Const(Const::Int(count, _)) => {
+                        Ok((*count & 63) as u8)
+                    }
+                    _ => Err(self.value(b)),
+                };
+                let a = match op {
+                    BinOp::Shl => self.value(a),
+                    _ => self.extend(a, ty, op == BinOp::AShr),
+                };
+                self.emit(Inst::Mov(size, Opnd::Reg(dst), a));
+                let shift = match op {
+                    BinOp::Shl => ShiftOp::Shl,
+                    BinOp::LShr => ShiftOp::Shr,
+                    _ => ShiftOp::Sar,
+                };
+                let count = match count {
+                    Ok(count) => Some(count),
+                    Err(count) => {
+                        let rcx = Opnd::Reg(Reg::Phys(RCX));
+                        self.emit(Inst::Mov(Size::L, rcx, count));
+                        None
+                    }
+                };
+                self.emit(Inst::Shift(shift, size, dst, count));
+            }
+            _ => self.compare(op, dst, a, b),
+        }
+    }
+
+    /// Translate a comparison.
+    fn compare(&mut self, op: BinOp, dst: Reg, a: &Operand, b: &Operand) {
+        let ty = self.func.operand_ty(a);
+        let cond = match op {
+            BinOp::Eq | BinOp::FEq => Cond::E,
+            BinOp::Ne | BinOp::FNe => Cond::Ne,
+            BinOp::SLt => Cond::L,
+            BinOp::SLe => Cond::Le,
+            BinOp::SGt => Cond::G,
+            BinOp::SGe => Cond::Ge,
+            BinOp::ULt => Cond::B,
+            BinOp::ULe => Cond::Be,
+            BinOp::UGt | BinOp::FGt | BinOp::FLt => Cond::A,
+            BinOp::UGe | BinOp::FGe | BinOp::FLe => Cond::Ae,
+            _ => unreachable!("not a comparison"),
+        };
+        if ty.is_float() {
+            // `a < b` is tested as `b > a`, since the unordered case sets the
+            // flags as for "below".
+            let (a, b) = match op {
+                BinOp::FLt | BinOp::FLe => (b, a),
+                _ => (a, b),
+            };
+            let (a, b) = (self.reg(a), self.reg(b));
+            self.emit(Inst::Ucomi(prec(ty), a, Opnd::Reg(b)));
+        } else {
+            let a = self.reg(a);
+            let b = self.value(b);
+            self.emit(Inst::Cmp(size(ty), a, b));
+        }
+        self.set(cond, dst);
+
+        // Comparisons with NaN are unordered: equal only if not unordered,
+        // and unequal if unordered.
+        let (parity, combine) = match op {
+            BinOp::FEq => (Cond::Np, AluOp::And),
+            BinOp::FNe => (Cond::P, AluOp::Or),
+            _ => return,
+        };
+        let temp = self.temp(Class::Int);
+        self.set(parity, temp);
+        self.emit(Inst::Alu(combine, Size::L, dst, Opnd::Reg(temp)));
+    }
+
+    /// Set a register to 0 or 1, depending on a condition.
+    fn set(&mut self, cond: Cond, dst: Reg) {
+        self.emit(Inst::Set(cond, dst));
+        self.emit(Inst::Ext {
+            signed: false,
+            from: Size::B,
+            to: Size::L,
+            dst,
+            src: Opnd::Reg(dst),
+        });
+    }
+
+    /// Translate a conversion.
+    fn cast(&mut self, op: CastOp, dst: Reg, to: Ty, a: &Operand) {
+        let from = self.func.operand_ty(a);
+        match op {
+            CastOp::SExt | CastOp::ZExt | CastOp::IntToPtr
+                if size(from) != size(to) =>
+            {
+                let signed = op != CastOp::ZExt;
+                let src = Opnd::Reg(self.reg(a));
+                let to = op_size(to);
+                let from = size(from);
+                self.emit(Inst::Ext {
+                    signed,
+                    from,
+                    to,
+                    dst,
+                    src,
+                });
+            }
+            CastOp::SExt
+            | CastOp::ZExt
+            | CastOp::Trunc
+            | CastOp::PtrToInt
+            | CastOp::IntToPtr => {
+                let value = self.value(a);
+                self.emit(Inst::Mov(op_size(to), Opnd::Reg(dst), value));
+            }
+            CastOp::SToF | CastOp::UToF => {
+                let signed = op == CastOp::SToF;
+                let prec = prec(to);
+                if !signed && from == Ty::I64 {
+                    let src = self.temp(Class::Int);
+                    let value = self.value(a);
+                    self.emit(Inst::Mov(Size::Q, Opnd::Reg(src), value));
+                    let tmp = self.temp(Class::Int);
+                    self.emit(Inst::U64ToFloat {
+                        prec,
+                        dst,
+                        src,
+                        tmp,
+                    });
+                    return;
+                }
+                let src = match self.extend(a, from, signed) {
+                    Opnd::Reg(reg) => reg,
+                    _ => self.reg(a),
+                };
+                // Unsigned 32-bit integers are converted as 64-bit ones,
+                // which holds them zero-extended.
+                let size = match from {
+                    Ty::I64 => Size::Q,
+                    Ty::I32 if !signed => {
+                        let wide = self.temp(Class::Int);
+                        self.emit(Inst::Ext {
+                            signed: false,
+                            from: Size::L,
+                            to: Size::Q,
+                            dst: wide,
+                            src: Opnd::Reg(src),
+                        });
+                        self.emit(Inst::CvtIntToFloat(
+                            Size::Q,
+                            prec,
+                            dst,
+                            wide,
+                        ));
+                        return;
+                    }
+                    _ => Size::L,
+                };
+                self.emit(Inst::CvtIntToFloat(size, prec, dst, src));
+            }
+            CastOp::FToS | CastOp::FToU => {
+                let prec = prec(from);
+                let value = self.reg(a);
+                if op == CastOp::FToU && to == Ty::I64 {
+                    let src = self.temp(Class::Float);
+                    let value = Opnd::Reg(value);
+                    self.emit(Inst::FMov(prec, Opnd::Reg(src), value));
+                    let tmp = self.temp(Class::Int);
+                    let ftmp = self.temp(Class::Float);
+                    self.emit(Inst::FloatToU64 {
+                        prec,
+                        dst,
+                        src,
+                        tmp,
+                        ftmp,
+                    });
+                    return;
+                }
+                // Unsigned 32-bit results are the low half of a 64-bit one.
+                let size = match (op, to) {
+                    (_, Ty::I64) | (CastOp::FToU, Ty::I32) => Size::Q,
+                    _ => Size::L,
+                };
+                self.emit(Inst::CvtFloatToInt(prec, size, dst, value));
+            }
+            CastOp::FConv => {
+                let value = self.reg(a);
+                if from == to {
+                    let value = Opnd::Reg(value);
+                    self.emit(Inst::FMov(prec(to), Opnd::Reg(dst), value));
+                } else {
+                    self.emit(Inst::CvtFloat(prec(from), dst, value));
+                }
+            }
+        }
+    }
+
+    /// Translate a call.
+    fn call(
+        &mut self,
+        dest: Option<Local>,
+        callee: &Operand,
+        args: &[Operand],
+        sig: &FnType,
+    ) {
+        let rets = returned(self.types, &sig.ret);
+        let mut places = Args::default();
+        if rets.is_none() {
+            places.ints = 1;
+        }
+
+        // Stack arguments are stored, and register arguments computed, before
+        // any argument register is written.
+        let mut moves = Vec::new();
+        if rets.is_none() {
+            let dest = dest.expect("aggregate results have a destination");
+            let addr = self.reg(&Operand::Addr(dest));
+            moves.push((INT_ARGS[0], Opnd::Reg(addr)));
+        }
+        for (arg, ty) in args.iter().zip(&sig.params) {
+            match (places.place(self.types, ty), Ty::of(ty, MODEL)) {
+                (Place::Regs(regs), Some(_)) => {
+                    let value = match reg_class(regs[0]) {
+                        Class::Int => self.scalar_arg(arg, ty),
+                        Class::Float => Opnd::Reg(self.reg(arg)),
+                    };
+                    moves.push((regs[0], value));
+                }
+                (Place::Regs(regs), None) => {
+                    let parts = self.load_aggregate(arg, ty, &regs);
+                    for (reg, part) in regs.into_iter().zip(parts) {
+                        moves.push((reg, Opnd::Reg(part)));
+                    }
+                }
+                (Place::Stack(offset), Some(mty)) => {
+                    let dst = Opnd::Mem(stack_arg(RSP, offset));
+                    if mty.is_float() {
+                        let value = Opnd::Reg(self.reg(arg));
+                        self.emit(Inst::FMov(prec(mty), dst, value));
+                    } else {
+                        let value = self.scalar_arg(arg, ty);
+                        self.emit(Inst::Mov(Size::Q, dst, value));
+                    }
+                }
+                (Place::Stack(offset), None) => {
+                    let src = self.mem(arg, 0);
+                    let size = self.types.size_of(ty);
+                    self.copy(stack_arg(RSP, offset), src, size);
+                }
+            }
+        }
+        self.out.outgoing = self.out.outgoing.max(places.stack);
+        let callee = match callee {
+            Operand::Const(Const::Symbol(name)) => {
+                Callee::Symbol(name.clone(), !self.defined.contains(name))
+            }
+            _ => Callee::Reg(self.reg(callee)),
+        };
+
+        let mut used = Vec::new();
+        for (reg, value) in moves {
+            self.move_to(reg, value);
+            used.push(reg);
+        }
+        if sig.variadic || !sig.prototype {
+            // The number of SSE registers used is passed in `%al` to callees
+            // that may be variadic.
+            let floats = Opnd::Imm(places.floats as i64);
+            self.emit(Inst::Mov(Size::L, Opnd::Reg(Reg::Phys(RAX)), floats));
+            used.push(RAX);
+        }
+        self.emit(Inst::Call {
+            callee,
+            args: used,
+            rets: vec![RAX, RDX, XMM0, XMM0 + 1],
+        });
+
+        let (Some(dest), Some(rets)) = (dest, rets) else {
+            return;
+        };
+        if self.func.local(dest).memory {
+            let slot = self.slot(dest);
+            for (i, reg) in rets.into_iter().enumerate() {
+                let dst = Opnd::Mem(Mem {
+                    base: Base::Slot(slot),
+                    disp: i as i64 * 8,
+                });
+                self.move_from(dst, reg);
+            }
+        } else {
+            let dst = Opnd::Reg(self.vreg(dest));
+            self.move_from(dst, rets[0]);
+        }
+    }
+
+    /// Translate a terminator.
+    fn term(&mut self, term: &Terminator) {
+        match term {
+            Terminator::Jump(target) => {
+                self.emit(Inst::Jmp(target.0 as usize));
+            }
+            Terminator::Branch(cond, then, r#else) => {
+                let ty = self.func.operand_ty(cond);
+                let cond = self.reg(cond);
+                self.emit(Inst::Cmp(size(ty), cond, Opnd::Imm(0)));
+                self.emit(Inst::Jcc(Cond::Ne, then.0 as usize));
+                self.emit(Inst::Jmp(r#else.0 as usize));
+            }
+            Terminator::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                let ty = self.func.operand_ty(value);
+                let value = self.reg(value);
+                for &(case, target) in cases {
+                    let case = ty.wrap(case);
+                    let case = if i32::try_from(case).is_ok() {
+                        Opnd::Imm(case)
+                    } else {
+                        Opnd::Reg(
+                            self.reg(&Operand::Const(Const::Int(case, ty))),
+                        )
+                    };
+                    self.emit(Inst::Cmp(size(ty), value, case));
+                    self.emit(Inst::Jcc(Cond::E, target.0 as usize));
+                }
+                self.emit(Inst::Jmp(default.0 as usize));
+            }
+            Terminator::Return(value) => {
+                let ret = &self.func.ty.ret;
+                let rets = match (value, returned(self.types, ret)) {
+                    (None, _) => Vec::new(),
+                    (Some(value), Some(rets))
+                        if Ty::of(ret, MODEL).is_some() =>
+                    {
+                        let value = match reg_class(rets[0]) {
+                            Class::Int => self.scalar_arg(value, ret),
+                            Class::Float => Opnd::Reg(self.reg(value)),
+                        };
+                        self.move_to(rets[0], value);
+                        rets
+                    }
+                    (Some(addr), Some(rets)) => {
+                        let parts = self.load_aggregate(addr, ret, &rets);
+                        for (&reg, part) in rets.iter().zip(parts) {
+                            self.move_to(reg, Opnd::Reg(part));
+                        }
+                        rets
+                    }
+                    (Some(addr), None) => {
+                        // The result is copied to the caller's memory, whose
+                        // address is returned.
+                        let sret = self.sret.unwrap();
+                        let src = self.mem(addr, 0);
+                        let dst = Mem {
+                            base: Base::Reg(sret),
+                            disp: 0,
+                        };
+                        let size = self.types.size_of(ret);
+                        self.copy(dst, src, size);
+                        self.move_to(RAX, Opnd::Reg(sret));
+                        vec![RAX]
+                    }
+                };
+                self.emit(Inst::Ret(rets));
+            }
+            Terminator::Unreachable => self.emit(Inst::Ud2),
+        }
+    }
+}
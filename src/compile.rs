@@ -0,0 +1,266 @@
+//! Compiling a single source.
+//!
+//! [`compile()`] takes a preprocessed source through every stage up to the
+//! output asked for.  The stages are also available on their own, for tools
+//! that need what is in between, or that compile several sources together
+//! (as the driver does, checking their linkage across them before
+//! generating code).
+
+use crate::codegen::regalloc::Allocator;
+use crate::codegen::{self, Target};
+use crate::diagnostics::{Diagnostic, Diagnostics, Severity};
+use crate::hir::serialize::Serialize;
+use crate::hir::{Arena, Program};
+use crate::lexer::LexerContext;
+use crate::mir::link::{self, Unit};
+use crate::mir::opt::{self, PassManager};
+use crate::mir::{self, Module};
+use crate::parser::Parseable;
+use crate::stack;
+use crate::typeck::Checker;
+
+/// What to compile a source to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Emit {
+    /// The parsed AST.
+    Ast,
+
+    /// The parsed AST, as JSON.
+    AstJson,
+
+    /// The parsed AST, as an S-expression.
+    AstSexp,
+
+    /// The parsed AST, printed back as C source.
+    C,
+
+    /// The resolved types of the structures and file-scope names, after
+    /// type checking.
+    Hir,
+
+    /// The syntax tree, as a Graphviz graph.
+    DotAst,
+
+    /// The control-flow graphs of the functions, as lowered to MIR, as a
+    /// Graphviz graph.
+    DotCfg,
+
+    /// The call graph, as a Graphviz graph.
+    DotCallgraph,
+
+    /// The lowered MIR.
+    Mir,
+
+    /// Assembly for the target machine (WebAssembly text for `wasm32`).
+    Asm,
+
+    /// LLVM IR for the target machine.
+    Llvm,
+
+    /// A relocatable object file for the target machine.
+    Object,
+}
+
+impl Emit {
+    /// Whether the output is made from the HIR, rather than from the MIR.
+    pub fn is_front_end(self) -> bool {
+        matches!(
+            self,
+            Self::Ast
+                | Self::AstJson
+                | Self::AstSexp
+                | Self::C
+                | Self::Hir
+                | Self::DotAst
+                | Self::DotCfg
+                | Self::DotCallgraph
+        )
+    }
+
+    /// The usual extension of a file of the output.
+    pub fn extension(self, target: Target) -> &'static str {
+        match self {
+            Self::Ast => "ast",
+            Self::AstJson => "json",
+            Self::AstSexp => "sexp",
+            Self::C => "c",
+            Self::Hir => "hir",
+            Self::DotAst | Self::DotCfg | Self::DotCallgraph => "dot",
+            Self::Mir => "mir",
+            Self::Asm if target == Target::Wasm32 => "wat",
+            Self::Asm => "s",
+            Self::Llvm => "ll",
+            Self::Object => "o",
+        }
+    }
+}
+
+/// The options of a compilation.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// What to compile to.
+    pub emit: Emit,
+
+    /// The target machine.
+    pub target: Target,
+
+    /// The register allocator.
+    pub allocator: Allocator,
+
+    /// The optimization options.
+    pub optimize: opt::Options,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            emit: Emit::Object,
+            target: Target::default(),
+            allocator: Allocator::default(),
+            optimize: opt::Options::default(),
+        }
+    }
+}
+
+impl Options {
+    /// Check that the output can be made for the target.
+    pub fn validate(&self) -> Result<(), Diagnostic> {
+        if self.emit == Emit::Llvm && self.target == Target::Wasm32 {
+            return Err(Diagnostic::error(
+                "LLVM IR cannot be emitted for wasm32",
+            ));
+        }
+        if self.emit == Emit::Object && self.target != Target::X86_64 {
+            return Err(Diagnostic::error(
+                "object files can only be written for x86_64",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The result of a compilation.
+#[derive(Debug)]
+pub struct Output {
+    /// The output, which is text for everything but object files.
+    pub contents: Vec<u8>,
+
+    /// The warnings about the source.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Compile a preprocessed source.
+pub fn compile(source: &str, options: &Options) -> Result<Output, Diagnostics> {
+    stack::with_stack(|| compile_on_stack(source, options))
+}
+
+/// Compile a preprocessed source, on a thread with room for deep recursion.
+fn compile_on_stack(
+    source: &str,
+    options: &Options,
+) -> Result<Output, Diagnostics> {
+    options.validate()?;
+    let arena = Arena::default();
+    let program = parse(&arena, source)?;
+    if options.emit.is_front_end() {
+        check(&program, options.target)?;
+        return Ok(Output {
+            contents: dump(&program, options.emit).into_bytes(),
+            warnings: Vec::new(),
+        });
+    }
+
+    let mut module = lower(&program, options.target)?;
+    let (errors, warnings): (Vec<_>, Vec<_>) = link::check(&[Unit {
+        name: "<source>",
+        source,
+        module: &module,
+    }])
+    .into_iter()
+    .map(Diagnostic::from)
+    .partition(|diagnostic| diagnostic.severity == Severity::Error);
+    if !errors.is_empty() {
+        return Err(Diagnostics(errors));
+    }
+    Ok(Output {
+        contents: generate(&mut module, options),
+        warnings,
+    })
+}
+
+//----------- Stages -----------------------------------------------------------
+
+/// Parse a preprocessed source.
+///
+/// The nodes of the program are allocated in `arena`.
+pub fn parse<'hir>(
+    arena: &'hir Arena<'hir>,
+    source: &str,
+) -> Result<Program<'hir>, Diagnostic> {
+    let mut ctx = LexerContext::new(source, arena);
+    let mut lexer = ctx.start();
+    Ok(Program::parse(lexer.delegate())?.into())
+}
+
+/// Type check a program for a target.
+pub fn check(program: &Program, target: Target) -> Result<(), Diagnostics> {
+    stack::with_stack(|| Checker::new(target).check(program))?;
+    Ok(())
+}
+
+/// Write out a program, in one of the front-end outputs.
+///
+/// # Panics
+///
+/// If `emit` is not [a front-end output](Emit::is_front_end), or if it is
+/// [`Emit::Hir`] or [`Emit::DotCfg`] and the program does not [type
+/// check](check).
+pub fn dump(program: &Program, emit: Emit) -> String {
+    stack::with_stack(|| dump_on_stack(program, emit))
+}
+
+/// Write out a program, on a thread with room for deep recursion.
+fn dump_on_stack(program: &Program, emit: Emit) -> String {
+    program.names.enter(|| match emit {
+        Emit::Ast => format!("{program:#?}"),
+        Emit::Hir => Checker::default()
+            .dump(program)
+            .unwrap_or_else(|_| panic!("the program does not type check")),
+        Emit::AstJson => program.serialize().to_json(),
+        Emit::AstSexp => program.serialize().to_sexp(),
+        Emit::C => program.to_string(),
+        Emit::DotAst => program.dot_ast(),
+        Emit::DotCfg => program.dot_cfg(),
+        Emit::DotCallgraph => program.dot_callgraph(),
+        _ => panic!("{emit:?} is not made from the HIR"),
+    })
+}
+
+/// Type check a program and lower it to MIR for a target.
+pub fn lower(program: &Program, target: Target) -> Result<Module, Diagnostics> {
+    Ok(stack::with_stack(|| mir::lower::lower(program, target))?)
+}
+
+/// Optimize a lowered program and compile it, in one of the back-end
+/// outputs.
+///
+/// # Panics
+///
+/// If `emit` is [a front-end output](Emit::is_front_end).
+pub fn generate(module: &mut Module, options: &Options) -> Vec<u8> {
+    PassManager::new(&options.optimize).run(module);
+    match options.emit {
+        Emit::Mir => module.to_string().into_bytes(),
+        Emit::Asm => {
+            codegen::compile(module, options.target, options.allocator)
+                .into_bytes()
+        }
+        Emit::Llvm => {
+            codegen::llvm::compile(module, options.target).into_bytes()
+        }
+        Emit::Object => {
+            codegen::x86_64::compile_object(module, options.allocator)
+        }
+        emit => panic!("{emit:?} is not made from the MIR"),
+    }
+}
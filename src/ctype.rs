@@ -0,0 +1,444 @@
+//! Resolved C types.
+//!
+//! Unlike [`hir::Type`](crate::hir::Type), which records a type as it was
+//! written, a [`CType`] is the complete type of a value: typedef names are
+//! expanded, bindings are applied, and structs are referred to by identity.
+
+use std::fmt::{self, Display, Formatter};
+
+//----------- CType ------------------------------------------------------------
+
+/// A resolved C type.
+#[derive(Clone, PartialEq)]
+pub enum CType {
+    /// The void type.
+    Void,
+
+    /// An integer type.
+    Int(IntType),
+
+    /// A floating-point type.
+    Float(FloatType),
+
+    /// A pointer type.
+    Pointer(Box<CType>),
+
+    /// An array type.
+    Array {
+        /// The element type.
+        elem: Box<CType>,
+
+        /// The number of elements, if known.
+        len: Option<u64>,
+    },
+
+    /// A function type.
+    Fn(Box<FnType>),
+
+    /// A structure type.
+    Struct(StructId),
+}
+
+/// An integer type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IntType {
+    /// The rank of the type.
+    pub rank: IntRank,
+
+    /// Whether the type is signed.
+    pub signed: bool,
+}
+
+/// The rank of an integer type, from lowest to highest.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IntRank {
+    /// `_Bool`.
+    Bool,
+
+    /// `char`.
+    Char,
+
+    /// `short`.
+    Short,
+
+    /// `int`.
+    Int,
+
+    /// `long`.
+    Long,
+
+    /// `long long`.
+    LongLong,
+}
+
+/// A floating-point type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FloatType {
+    /// `float`.
+    Float,
+
+    /// `double`.
+    Double,
+
+    /// `long double`.
+    LongDouble,
+}
+
+/// A function type.
+#[derive(Clone, PartialEq)]
+pub struct FnType {
+    /// The return type.
+    pub ret: CType,
+
+    /// The parameter types.
+    pub params: Vec<CType>,
+
+    /// Whether the function takes variable arguments after `params`.
+    pub variadic: bool,
+
+    /// Whether the parameters are declared.
+    ///
+    /// A function declared with `()` has no prototype, and may be called
+    /// with any (promoted) arguments.
+    pub prototype: bool,
+}
+
+//--- Constants
+
+impl CType {
+    /// `int`.
+    pub const INT: Self = Self::Int(IntType {
+        rank: IntRank::Int,
+        signed: true,
+    });
+
+    /// `char`, which is signed on all supported targets.
+    pub const CHAR: Self = Self::Int(IntType {
+        rank: IntRank::Char,
+        signed: true,
+    });
+
+    /// `long`.
+    pub const LONG: Self = Self::Int(IntType {
+        rank: IntRank::Long,
+        signed: true,
+    });
+
+    /// `size_t`, the type of `sizeof` expressions.
+    pub const SIZE_T: Self = Self::Int(IntType {
+        rank: IntRank::Long,
+        signed: false,
+    });
+
+    /// `ptrdiff_t`, the type of pointer differences.
+    pub const PTRDIFF_T: Self = Self::LONG;
+
+    /// `double`.
+    pub const DOUBLE: Self = Self::Float(FloatType::Double);
+}
+
+//--- Inspection
+
+impl CType {
+    /// Whether this is an integer type.
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Self::Int(_))
+    }
+
+    /// Whether this is an arithmetic type.
+    pub fn is_arithmetic(&self) -> bool {
+        matches!(self, Self::Int(_) | Self::Float(_))
+    }
+
+    /// Whether this is a pointer type.
+    pub fn is_pointer(&self) -> bool {
+        matches!(self, Self::Pointer(_))
+    }
+
+    /// Whether this is a scalar (arithmetic or pointer) type.
+    pub fn is_scalar(&self) -> bool {
+        self.is_arithmetic() || self.is_pointer()
+    }
+
+    /// The type pointed to, if this is a pointer type.
+    pub fn pointee(&self) -> Option<&CType> {
+        match self {
+            Self::Pointer(pointee) => Some(pointee),
+            _ => None,
+        }
+    }
+
+    /// The function type, if this is a function or function pointer type.
+    pub fn fn_type(&self) -> Option<&FnType> {
+        match self {
+            Self::Fn(fn_type) => Some(fn_type),
+            Self::Pointer(pointee) => match &**pointee {
+                Self::Fn(fn_type) => Some(fn_type),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+//--- Conversions
+
+impl CType {
+    /// Construct a pointer to this type.
+    pub fn pointer_to(self) -> Self {
+        Self::Pointer(Box::new(self))
+    }
+
+    /// Apply array-to-pointer and function-to-pointer conversion.
+    pub fn decay(self) -> Self {
+        match self {
+            Self::Array { elem, .. } => Self::Pointer(elem),
+            Self::Fn(_) => self.pointer_to(),
+            _ => self,
+        }
+    }
+
+    /// Apply the integer promotions.
+    pub fn promote(self) -> Self {
+        match self {
+            Self::Int(int) if int.rank < IntRank::Int => Self::INT,
+            _ => self,
+        }
+    }
+}
+
+//----------- Structures -------------------------------------------------------
+
+/// The identity of a structure type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StructId(pub usize);
+
+/// The definition of a structure type.
+#[derive(Clone)]
+pub struct StructDef {
+    /// The tag of the struct, if any.
+    pub name: Option<String>,
+
+    /// The fields of the struct, if it is complete.
+    pub fields: Option<Vec<Field>>,
+}
+
+/// A field of a structure.
+#[derive(Clone)]
+pub struct Field {
+    /// The name of the field.
+    pub name: String,
+
+    /// The type of the field.
+    pub ty: CType,
+}
+
+//----------- DataModel --------------------------------------------------------
+
+/// The sizes of the integer and pointer types on a target.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DataModel {
+    /// 32-bit `int`, and 64-bit `long` and pointers.
+    #[default]
+    Lp64,
+
+    /// 32-bit `int`, `long` and pointers.
+    Ilp32,
+}
+
+impl DataModel {
+    /// The size of `long` and of pointers in bytes.
+    pub fn pointer_size(self) -> u64 {
+        match self {
+            Self::Lp64 => 8,
+            Self::Ilp32 => 4,
+        }
+    }
+}
+
+//----------- TypeTable --------------------------------------------------------
+
+/// The structure types of a translation unit, and the data model they are
+/// laid out in.
+#[derive(Clone, Default)]
+pub struct TypeTable {
+    /// The structures, indexed by [`StructId`].
+    structs: Vec<StructDef>,
+
+    /// The sizes of the scalar types.
+    model: DataModel,
+}
+
+impl TypeTable {
+    /// Construct an empty table for a data model.
+    pub fn new(model: DataModel) -> Self {
+        Self {
+            structs: Vec::new(),
+            model,
+        }
+    }
+
+    /// The data model the types are laid out in.
+    pub fn model(&self) -> DataModel {
+        self.model
+    }
+
+    /// Add a new structure type.
+    pub fn add_struct(&mut self, def: StructDef) -> StructId {
+        self.structs.push(def);
+        StructId(self.structs.len() - 1)
+    }
+
+    /// Look up a structure type.
+    pub fn get(&self, id: StructId) -> &StructDef {
+        &self.structs[id.0]
+    }
+
+    /// Look up a structure type mutably.
+    pub fn get_mut(&mut self, id: StructId) -> &mut StructDef {
+        &mut self.structs[id.0]
+    }
+
+    /// The identities of all structure types.
+    pub fn struct_ids(&self) -> impl Iterator<Item = StructId> + use<> {
+        (0..self.structs.len()).map(StructId)
+    }
+
+    /// Find a field in a structure type.
+    ///
+    /// Returns the index of the field and the field itself.
+    pub fn field(&self, id: StructId, name: &str) -> Option<(usize, &Field)> {
+        self.get(id)
+            .fields
+            .as_ref()?
+            .iter()
+            .enumerate()
+            .find(|(_, field)| field.name == name)
+    }
+}
+
+//--- Layout
+
+impl TypeTable {
+    /// Show a type in a diagnostic, naming structs by their tags.
+    pub fn named<'a>(&'a self, ty: &'a CType) -> Named<'a> {
+        Named { types: self, ty }
+    }
+}
+
+/// A type shown with the tags of its structs; see [`TypeTable::named()`].
+pub struct Named<'a> {
+    /// The structure types the type refers to.
+    types: &'a TypeTable,
+
+    /// The type to show.
+    ty: &'a CType,
+}
+
+impl Display for Named<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        crate::dbg::write_ctype(f, self.ty, &|f, id| match &self
+            .types
+            .get(id)
+            .name
+        {
+            Some(name) => write!(f, "struct {name}"),
+            None => write!(f, "struct <anonymous>"),
+        })
+    }
+}
+
+/// The layout of a structure type.
+#[derive(Clone, Debug)]
+pub struct StructLayout {
+    /// The size of the structure, including trailing padding.
+    pub size: u64,
+
+    /// The alignment of the structure.
+    pub align: u64,
+
+    /// The offset of each field.
+    pub offsets: Vec<u64>,
+}
+
+impl TypeTable {
+    /// The size of a type in bytes.
+    ///
+    /// `long` and pointers follow the data model, `long long` is always 64
+    /// bits, and `long double` is treated as `double`.  Incomplete types have
+    /// size 0, except for `void`, which has size 1 (as in GNU C) so that
+    /// arithmetic on `void *` works.
+    pub fn size_of(&self, ty: &CType) -> u64 {
+        match ty {
+            CType::Void => 1,
+            CType::Int(int) => match int.rank {
+                IntRank::Bool | IntRank::Char => 1,
+                IntRank::Short => 2,
+                IntRank::Int => 4,
+                IntRank::Long => self.model.pointer_size(),
+                IntRank::LongLong => 8,
+            },
+            CType::Float(FloatType::Float) => 4,
+            CType::Float(_) => 8,
+            CType::Pointer(_) => self.model.pointer_size(),
+            CType::Array { elem, len } => self.size_of(elem) * len.unwrap_or(0),
+            CType::Fn(_) => 1,
+            CType::Struct(id) => self.layout(*id).size,
+        }
+    }
+
+    /// The alignment of a type in bytes.
+    pub fn align_of(&self, ty: &CType) -> u64 {
+        match ty {
+            CType::Array { elem, .. } => self.align_of(elem),
+            CType::Struct(id) => self.layout(*id).align,
+            _ => self.size_of(ty),
+        }
+    }
+
+    /// The layout of a structure type.
+    pub fn layout(&self, id: StructId) -> StructLayout {
+        let mut size = 0u64;
+        let mut align = 1;
+        let mut offsets = Vec::new();
+        for field in self.get(id).fields.iter().flatten() {
+            let field_align = self.align_of(&field.ty);
+            size = size.next_multiple_of(field_align);
+            offsets.push(size);
+            size += self.size_of(&field.ty);
+            align = align.max(field_align);
+        }
+        StructLayout {
+            size: size.next_multiple_of(align),
+            align,
+            offsets,
+        }
+    }
+
+    /// The common type of two arithmetic operands.
+    ///
+    /// This implements the "usual arithmetic conversions".
+    pub fn common(&self, lhs: &CType, rhs: &CType) -> CType {
+        match (lhs.clone().promote(), rhs.clone().promote()) {
+            (CType::Float(a), CType::Float(b)) => CType::Float(a.max(b)),
+            (CType::Float(a), _) | (_, CType::Float(a)) => CType::Float(a),
+            (CType::Int(a), CType::Int(b)) => {
+                let (hi, lo) = if a.rank >= b.rank { (a, b) } else { (b, a) };
+                let size = |int| self.size_of(&CType::Int(int));
+                if hi.signed == lo.signed || !hi.signed {
+                    CType::Int(hi)
+                } else if size(hi) > size(lo) {
+                    // The wider signed type can represent all the values of
+                    // the unsigned one.
+                    CType::Int(hi)
+                } else {
+                    CType::Int(IntType {
+                        rank: hi.rank,
+                        signed: false,
+                    })
+                }
+            }
+            (lhs, _) => lhs,
+        }
+    }
+}
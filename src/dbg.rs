@@ -1,10 +1,13 @@
+use crate::ctype::{CType, FloatType, IntRank, StructId};
 use crate::hir::{
-    Binding, Decl, Expr, FnDefn, MonoDecl, TopDefn, Type, TypeDefn, VarDefn,
+    Attrs, BinOp, Binding, Decl, Designator, Expr, FnDefn, InitItem,
+    Initializer, Lit, MonoDecl, Radix, TopDefn, Type, TypeDefn, TypeName,
+    UnaOp, VarDefn,
 };
-use std::fmt::{Debug, Pointer};
-use std::fmt::{Display, Formatter};
+use std::fmt::Debug;
+use std::fmt::Formatter;
 
-impl Debug for TopDefn {
+impl Debug for TopDefn<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             TopDefn::Fn(dfn) => dfn.fmt(f),
@@ -14,7 +17,7 @@ impl Debug for TopDefn {
     }
 }
 
-impl Debug for Type {
+impl Debug for Type<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Type::Void => write!(f, "void"),
@@ -26,27 +29,33 @@ impl Debug for Type {
             Type::Float => write!(f, "float"),
             Type::Double => write!(f, "double"),
             Type::LongDouble => write!(f, "long double"),
+            Type::VaList => write!(f, "__builtin_va_list"),
             Type::Struct { name, fields, .. } => {
-                write!(f, "struct ",)?;
-                match name {
-                    Some(name) => write!(f, "{} ", name),
-                    None => write!(f, ""),
-                }?;
-                fields.fmt(f)
+                write!(f, "struct",)?;
+                if let Some(name) = name {
+                    write!(f, " {}", name)?;
+                }
+                match fields {
+                    Some(fields) => {
+                        write!(f, " ")?;
+                        fields.fmt(f)
+                    }
+                    None => Ok(()),
+                }
             }
             Type::Ident(name) => write!(f, "{}", name),
         }
     }
 }
 
-impl Debug for Decl {
+impl Debug for Decl<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         self.base.fmt(f)?;
         write!(f, " ")?;
         self.bindings.fmt(f)
     }
 }
-impl Debug for MonoDecl {
+impl Debug for MonoDecl<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         self.base.fmt(f)?;
         write!(f, " ")?;
@@ -54,12 +63,29 @@ impl Debug for MonoDecl {
     }
 }
 
-impl Debug for Binding {
+impl Debug for TypeName<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.base.fmt(f)?;
+        match self.binding.node {
+            Binding::Anonymous => Ok(()),
+            ref binding => write!(f, " {:?}", binding),
+        }
+    }
+}
+
+impl Debug for Binding<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Binding::Fn { inner, params } => {
+            Binding::Fn {
+                inner,
+                params,
+                variadic,
+            } => {
                 write!(f, "{:?}(", inner)?;
                 params.fmt(f)?;
+                if *variadic {
+                    write!(f, ", ...")?;
+                }
                 write!(f, ")")
             }
             Binding::Array { inner, size } => {
@@ -71,15 +97,28 @@ impl Debug for Binding {
                 write!(f, "]")
             }
             Binding::Pointer(b) => write!(f, "*{:?}", b),
-            Binding::Paren(b) => b.as_ref().fmt(f),
+            Binding::Paren(b) => b.fmt(f),
             Binding::Ident(id) => write!(f, "{}", id),
             Binding::Anonymous => write!(f, "<anonymous>"),
         }
     }
 }
 
-impl Debug for VarDefn {
+impl Debug for Attrs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(storage) = self.storage {
+            write!(f, "{} ", storage.keyword())?;
+        }
+        if self.inline {
+            write!(f, "inline ")?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for VarDefn<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.attrs.fmt(f)?;
         self.base.fmt(f)?;
         write!(f, " ")?;
         for (binding, expr) in self.bindings.iter() {
@@ -96,20 +135,16 @@ impl Debug for VarDefn {
     }
 }
 
-impl Debug for TypeDefn {
+impl Debug for TypeDefn<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "typedef {:?} ", self.base)?;
         self.bindings.fmt(f)
     }
 }
 
-impl Debug for FnDefn {
+impl Debug for FnDefn<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "fn {}{:?} -> {:?} = ",
-            self.name, self.params, self.return_type
-        )?;
+        write!(f, "{:?} = ", self.signature())?;
         match &self.body {
             None => write!(f, "{{}}"),
             Some(body) => body.fmt(f),
@@ -117,13 +152,212 @@ impl Debug for FnDefn {
     }
 }
 
-impl Debug for Expr {
+impl FnDefn<'_> {
+    /// The signature of the function, as it starts its `Debug` output.
+    pub fn signature(&self) -> impl Debug + '_ {
+        Signature(self)
+    }
+}
+
+/// The signature of a function, without its body.
+struct Signature<'a>(&'a FnDefn<'a>);
+
+impl Debug for Signature<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let defn = self.0;
+        write!(f, "{:?}fn {}{:?}", defn.attrs, defn.name, defn.params)?;
+        if defn.variadic {
+            write!(f, "...")?;
+        }
+        write!(f, " -> {:?}", defn.return_type)
+    }
+}
+
+impl Debug for Expr<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Debug => write!(f, "expr"),
+            Expr::Ref(e) => write!(f, "&{:?}", e),
+            Expr::Bin(op, lhs, rhs) => {
+                write!(f, "({:?} {:?} {:?})", lhs, op, rhs)
+            }
+            Expr::Una(op, e) => write!(f, "({:?}{:?})", op, e),
+            Expr::Ident(id) => write!(f, "{}", id),
+            Expr::Lit(lit) => lit.fmt(f),
+            Expr::Cast(ty, e) => write!(f, "({:?}){:?}", ty, e),
+            Expr::Sizeof(e) => write!(f, "sizeof {:?}", e),
+            Expr::SizeofType(ty) => write!(f, "sizeof({:?})", ty),
+            Expr::Alignof(ty) => write!(f, "_Alignof({:?})", ty),
+            Expr::CompoundLit(ty, items) => {
+                write!(f, "({:?})", ty)?;
+                write_init_list(f, items)
+            }
+            Expr::Call(callee, args) => {
+                write!(f, "{:?}(", callee)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    arg.fmt(f)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Member(e, name) => write!(f, "{:?}.{}", e, name),
+            Expr::Arrow(e, name) => write!(f, "{:?}->{}", e, name),
+            Expr::Index(e, index) => write!(f, "{:?}[{:?}]", e, index),
+            Expr::PostInc(e) => write!(f, "{:?}++", e),
+            Expr::PostDec(e) => write!(f, "{:?}--", e),
+            Expr::Cond(cond, then, r#else) => {
+                write!(f, "({:?} ? {:?} : {:?})", cond, then, r#else)
+            }
+            Expr::VaStart(ap, last) => {
+                write!(f, "__builtin_va_start({:?}, {:?})", ap, last)
+            }
+            Expr::VaArg(ap, ty) => {
+                write!(f, "__builtin_va_arg({:?}, {:?})", ap, ty)
+            }
+            Expr::VaEnd(ap) => write!(f, "__builtin_va_end({:?})", ap),
+            Expr::VaCopy(dest, src) => {
+                write!(f, "__builtin_va_copy({:?}, {:?})", dest, src)
+            }
+        }
+    }
+}
+
+impl Debug for BinOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::Shl => "<<",
+            BinOp::Shr => ">>",
+            BinOp::BitAnd => "&",
+            BinOp::BitOr => "|",
+            BinOp::BitXor => "^",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Gt => ">",
+            BinOp::Le => "<=",
+            BinOp::Ge => ">=",
+            BinOp::Assign => "=",
+            BinOp::AddAssign => "+=",
+            BinOp::SubAssign => "-=",
+            BinOp::MulAssign => "*=",
+            BinOp::DivAssign => "/=",
+            BinOp::RemAssign => "%=",
+            BinOp::ShlAssign => "<<=",
+            BinOp::ShrAssign => ">>=",
+            BinOp::BitAndAssign => "&=",
+            BinOp::BitOrAssign => "|=",
+            BinOp::BitXorAssign => "^=",
+            BinOp::Comma => ",",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl Debug for UnaOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            UnaOp::Neg => "-",
+            UnaOp::Not => "!",
+            UnaOp::Inv => "~",
+            UnaOp::Plus => "+",
+            UnaOp::Deref => "*",
+            UnaOp::PreInc => "++",
+            UnaOp::PreDec => "--",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl Debug for Lit<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Lit::Int {
+                value,
+                radix,
+                unsigned,
+                long,
+            } => {
+                match radix {
+                    Radix::Decimal => write!(f, "{}", value)?,
+                    Radix::Octal => write!(f, "0{:o}", value)?,
+                    Radix::Hex => write!(f, "0x{:x}", value)?,
+                    Radix::Binary => write!(f, "0b{:b}", value)?,
+                }
+                if *unsigned {
+                    write!(f, "u")?;
+                }
+                (0..*long).try_for_each(|_| write!(f, "l"))
+            }
+            Lit::Float {
+                value,
+                single,
+                long,
+            } => {
+                let suffix = match (single, long) {
+                    (true, _) => "f",
+                    (_, true) => "l",
+                    _ => "",
+                };
+                write!(f, "{:?}{}", value, suffix)
+            }
+            Lit::Char(c) => write!(f, "'{}'", (*c as char).escape_default()),
+            Lit::Str(s) => write!(f, "\"{}\"", s.escape_ascii()),
+        }
+    }
+}
+
+impl Debug for Initializer<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Initializer::Expr(e) => e.fmt(f),
+            Initializer::List(items) => write_init_list(f, items),
+        }
+    }
+}
+
+impl Debug for InitItem<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for designator in self.designators {
+            designator.fmt(f)?;
+        }
+        if !self.designators.is_empty() {
+            write!(f, " = ")?;
+        }
+        self.init.fmt(f)
+    }
+}
+
+impl Debug for Designator<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "<expr>")
+        match self {
+            Designator::Member(name) => write!(f, ".{}", name),
+            Designator::Index(index) => write!(f, "[{:?}]", index),
+        }
+    }
+}
+
+/// Write a brace-enclosed initializer list.
+fn write_init_list(f: &mut Formatter, items: &[InitItem]) -> std::fmt::Result {
+    write!(f, "{{")?;
+    for (i, item) in items.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+        item.fmt(f)?;
     }
+    write!(f, "}}")
 }
 
-impl Type {
+impl Type<'_> {
     fn write_signed(
         f: &mut Formatter,
         type_name: &str,
@@ -145,3 +379,65 @@ impl Type {
         )
     }
 }
+
+impl Debug for CType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write_ctype(f, self, &|f, id| write!(f, "struct #{}", id.0))
+    }
+}
+
+/// Write out a type, with structs written by `tag`.
+pub(crate) fn write_ctype(
+    f: &mut Formatter<'_>,
+    ty: &CType,
+    tag: &dyn Fn(&mut Formatter<'_>, StructId) -> std::fmt::Result,
+) -> std::fmt::Result {
+    match ty {
+        CType::Void => write!(f, "void"),
+        CType::Int(int) => {
+            if !int.signed {
+                write!(f, "unsigned ")?;
+            }
+            let name = match int.rank {
+                IntRank::Bool => "_Bool",
+                IntRank::Char => "char",
+                IntRank::Short => "short",
+                IntRank::Int => "int",
+                IntRank::Long => "long",
+                IntRank::LongLong => "long long",
+            };
+            write!(f, "{}", name)
+        }
+        CType::Float(FloatType::Float) => write!(f, "float"),
+        CType::Float(FloatType::Double) => write!(f, "double"),
+        CType::Float(FloatType::LongDouble) => write!(f, "long double"),
+        CType::Pointer(pointee) => {
+            write_ctype(f, pointee, tag)?;
+            write!(f, " *")
+        }
+        CType::Array { elem, len } => {
+            write_ctype(f, elem, tag)?;
+            match len {
+                Some(len) => write!(f, " [{}]", len),
+                None => write!(f, " []"),
+            }
+        }
+        CType::Fn(fn_type) => {
+            write_ctype(f, &fn_type.ret, tag)?;
+            write!(f, " (")?;
+            for (i, param) in fn_type.params.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ", ")?;
+                }
+                write_ctype(f, param, tag)?;
+            }
+            match (fn_type.variadic, fn_type.params.is_empty()) {
+                (true, true) => write!(f, "...)"),
+                (true, false) => write!(f, ", ...)"),
+                (false, true) if fn_type.prototype => write!(f, "void)"),
+                (false, _) => write!(f, ")"),
+            }
+        }
+        CType::Struct(id) => tag(f, *id),
+    }
+}
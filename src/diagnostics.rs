@@ -0,0 +1,209 @@
+//! Problems found in sources.
+//!
+//! Every stage reports problems in its own terms ([`ParseError`],
+//! [`TypeError`], [`link::Diagnostic`], [`RuntimeError`]); they are gathered
+//! here as [`Diagnostic`]s, which can be shown against the source they are
+//! about.
+
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use crate::interp::RuntimeError;
+use crate::lexer::ParseError;
+use crate::mir::link;
+use crate::typeck::TypeError;
+
+/// How serious a problem is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The source cannot be compiled.
+    Error,
+
+    /// The source can be compiled, but is likely wrong.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        })
+    }
+}
+
+/// A problem found in a source.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    /// How serious the problem is.
+    pub severity: Severity,
+
+    /// The offset in the source that the problem is at, if known.
+    pub offset: Option<usize>,
+
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// An error, without a position.
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            offset: None,
+            message: message.into(),
+        }
+    }
+
+    /// Show the problem against a source, as `error: path:line:column:
+    /// message`, or as `error: path: message` if its position is unknown.
+    pub fn render(&self, path: &Path, source: &str) -> String {
+        let location = match self.offset {
+            Some(offset) => location(path, source, offset),
+            None => path.display().to_string(),
+        };
+        format!("{}: {location}: {}", self.severity, self.message)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for Diagnostic {}
+
+impl From<ParseError> for Diagnostic {
+    fn from(error: ParseError) -> Self {
+        Self {
+            severity: Severity::Error,
+            offset: Some(error.offset()),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<TypeError> for Diagnostic {
+    fn from(error: TypeError) -> Self {
+        Self {
+            severity: Severity::Error,
+            offset: error.span.map(|span| span.start),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<RuntimeError> for Diagnostic {
+    fn from(error: RuntimeError) -> Self {
+        Self {
+            severity: Severity::Error,
+            offset: error.span.map(|span| span.start),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<link::Diagnostic> for Diagnostic {
+    fn from(diagnostic: link::Diagnostic) -> Self {
+        Self {
+            severity: if diagnostic.error {
+                Severity::Error
+            } else {
+                Severity::Warning
+            },
+            offset: None,
+            message: diagnostic.message,
+        }
+    }
+}
+
+/// The problems that stopped a source from compiling.
+#[derive(Clone, Debug, Default)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    /// Show the problems against a source, one per line.
+    pub fn render(&self, path: &Path, source: &str) -> String {
+        self.0
+            .iter()
+            .map(|diagnostic| diagnostic.render(path, source) + "\n")
+            .collect()
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, diagnostic) in self.0.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: {diagnostic}", diagnostic.severity)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for Diagnostics {}
+
+impl<T: Into<Diagnostic>> From<Vec<T>> for Diagnostics {
+    fn from(errors: Vec<T>) -> Self {
+        Self(errors.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<Diagnostic> for Diagnostics {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Self(vec![diagnostic])
+    }
+}
+
+impl From<ParseError> for Diagnostics {
+    fn from(error: ParseError) -> Self {
+        Self(vec![error.into()])
+    }
+}
+
+impl From<RuntimeError> for Diagnostics {
+    fn from(error: RuntimeError) -> Self {
+        Self(vec![error.into()])
+    }
+}
+
+/// The location of an offset in a source, as `path:line:column`.
+///
+/// The line markers the preprocessor leaves, `# <line> "<path>"`, give the
+/// line and the path of the line after them.
+pub fn location(path: &Path, source: &str, offset: usize) -> String {
+    let before = &source[..offset.min(source.len())];
+    let start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before.len() - start;
+    let mut path = path.display().to_string();
+    let mut line = 1;
+    for text in source[..start].lines() {
+        match line_marker(text) {
+            Some((number, file)) => {
+                line = number;
+                if let Some(file) = file {
+                    path = file.to_string();
+                }
+            }
+            None => line += 1,
+        }
+    }
+    format!("{path}:{line}:{}", column + 1)
+}
+
+/// The line and the path a line marker or `#line` directive gives, if a
+/// line is one.
+fn line_marker(text: &str) -> Option<(usize, Option<&str>)> {
+    let rest = text.trim_start().strip_prefix('#')?.trim_start();
+    let rest = rest.strip_prefix("line").unwrap_or(rest).trim_start();
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let number = rest[..end].parse().ok()?;
+    let file = rest[end..].trim_start().strip_prefix('"');
+    Some((number, file.and_then(|file| file.split('"').next())))
+}
@@ -0,0 +1,717 @@
+//! The compiler driver.
+//!
+//! `sea` accepts the options of GCC that C builds rely on, so that it can be
+//! dropped in as `CC=sea`.  Each C source is a translation unit, and the
+//! output of the last stage asked for is written out for each:
+//!
+//! 1. The source is preprocessed by the system preprocessor (`cpp`) if it
+//!    needs it: with `-E`, `-D`, `-U` or `-I`, or if it has directives or
+//!    comments.  Other sources need no external tool at all.  `.i` files are
+//!    already preprocessed.  Problems are located by the line markers the
+//!    preprocessor leaves.
+//! 2. It is parsed (`--emit=ast`, or `--emit=ast-json` and `--emit=ast-sexp`
+//!    for tools, or `--emit=c` to print it back as C) and type checked
+//!    (`--emit=hir`, which writes the resolved types of its structures and
+//!    file-scope names).  `--emit=dot-ast`, `--emit=dot-cfg` and
+//!    `--emit=dot-callgraph` draw its syntax tree, the control flow of its
+//!    functions as lowered to MIR, or its call graph with Graphviz (see
+//!    [`sea::hir::dot`]).
+//! 3. It is lowered to MIR.  Once every source is, the linkage of the units
+//!    is checked across them (see [`sea::mir::link`]), and each is optimized
+//!    (`--emit=mir`).
+//! 4. The MIR is compiled to assembly (`-S`), LLVM IR (`--emit=llvm`), or,
+//!    on x86-64, an object file (`-c`).
+//!
+//! Without any of these options, the sources are compiled to objects and
+//! linked into an executable, together with the other input files (objects,
+//! archives or assembly), by the system C compiler, which runs the linker.
+//! Outputs are named after their sources, in the current directory, unless
+//! `-o` is given; `-` reads a source from standard input or writes an output
+//! to standard output.
+//!
+//! `-W` options are passed on to the preprocessor, and `-Werror` also turns
+//! the warnings of the driver into errors, in which case nothing is written
+//! out.  `-std=` only selects the standard the preprocessor follows: the
+//! parser and type checker accept the same language whatever it is.  `-g` is
+//! accepted, but no debug information is generated.  `-O2` and above
+//! optimize as `-O1` does.  The other code generation and dialect options
+//! of GCC (`-f*`, `-m*`, `-pedantic`, `-pipe` and `-ansi`) are ignored, and
+//! reported if any `-W` option asks for warnings.  The `-M` options, which
+//! write dependencies, are passed on to the preprocessor, and `-pthread` to
+//! the linker; `-x c` makes the inputs after it C sources, whatever their
+//! extension.
+//!
+//! Each stage is in the `sea` library (see [`sea::compile`]); the driver
+//! reads and writes the files, runs the external tools and reports problems.
+//!
+//! `sea run` interprets a program instead of compiling it (see
+//! [`sea::interp`]), rejecting the options that only apply to compiling,
+//! and `sea fmt` formats sources (see [`fmt`]).
+
+use std::cell::Cell;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use sea::codegen::Target;
+use sea::codegen::regalloc::Allocator;
+use sea::compile::{self, Emit, Options};
+use sea::diagnostics::Diagnostics;
+use sea::hir::{Arena, Program};
+use sea::interp;
+use sea::mir::Module;
+use sea::mir::link::{self, Unit};
+use sea::mir::opt::{OptLevel, PASSES};
+
+mod fmt;
+
+/// What the driver should do.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Action {
+    /// Output the preprocessed sources (`-E`).
+    Preprocess,
+
+    /// Compile each source to an output.
+    Compile,
+
+    /// Link an executable, with the system linker.
+    Link,
+
+    /// Run the program with the interpreter (`sea run`).
+    Run,
+}
+
+/// The options of an invocation.
+struct Driver {
+    /// What to do.
+    action: Action,
+
+    /// The input files, `-` being standard input.
+    inputs: Vec<PathBuf>,
+
+    /// The path to the output file; `-` for standard output.
+    out_path: Option<PathBuf>,
+
+    /// The options of compiling each source.
+    options: Options,
+
+    /// Whether the interpreter checks for undefined behavior.
+    check_ub: bool,
+
+    /// The options passed to the preprocessor.
+    cpp_args: Vec<String>,
+
+    /// Whether every source must be preprocessed, because of `-D`, `-U` or
+    /// `-I`.
+    cpp_always: bool,
+
+    /// The options passed to the linker.
+    link_args: Vec<String>,
+
+    /// The inputs that are C sources whatever their extension (`-x c`).
+    c_inputs: Vec<PathBuf>,
+
+    /// The options of GCC that are accepted but ignored.
+    ignored: Vec<String>,
+
+    /// Whether warnings were asked for (`-W<warning>`), in which case the
+    /// ignored options are reported.
+    warnings: bool,
+
+    /// Whether warnings are errors (`-Werror`).
+    werror: bool,
+
+    /// Whether warnings are suppressed (`-w`).
+    quiet: bool,
+
+    /// Whether a warning was reported as an error.
+    failed: Cell<bool>,
+}
+
+/// Print the usage and exit.
+pub fn usage() -> ! {
+    eprintln!(
+        "Usage: sea [-E|-S|-c|--emit=ast|ast-json|ast-sexp|c|hir|dot-ast|dot-cfg|\
+         dot-callgraph|mir|asm|llvm] [-o <output>] [-O0|-O1|-O2] [-std=<std>] [-I <dir>] \
+         [-D <macro>[=<value>]] [-U <macro>] [-W<warning>] [-Werror] [-w] [-g] [-l <lib>] \
+         [-L <dir>] [-pthread] [-M|-MM|-MD|-MMD] [-MF <path>] [-MT <target>] [-x c] \
+         [-finline-limit=<n>] [--target=x86_64|riscv64|wasm32] \
+         [--regalloc=naive|linear|graph] [--print-after=<pass>] <path>...\n       \
+         sea run [--check-ub] <path>\n       \
+         sea fmt [--check] [--config <path>] <path>..."
+    );
+    std::process::exit(1)
+}
+
+/// Run the driver on the command-line arguments.
+pub fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if(|arg| arg == "fmt").is_some() {
+        return fmt::main(args);
+    }
+    Driver::parse(args).run()
+}
+
+//----------- Options ----------------------------------------------------------
+
+impl Driver {
+    /// Compile each source to an output.
+    fn emit(&mut self, emit: Emit) {
+        self.action = Action::Compile;
+        self.options.emit = emit;
+    }
+
+    /// Parse the command-line arguments.
+    fn parse(args: impl Iterator<Item = String>) -> Self {
+        let mut driver = Self {
+            action: Action::Link,
+            inputs: Vec::new(),
+            out_path: None,
+            options: Options::default(),
+            check_ub: false,
+            cpp_args: Vec::new(),
+            cpp_always: false,
+            link_args: Vec::new(),
+            c_inputs: Vec::new(),
+            ignored: Vec::new(),
+            warnings: false,
+            werror: false,
+            quiet: false,
+            failed: Cell::new(false),
+        };
+
+        let mut args = args.peekable();
+        if args.next_if(|arg| arg == "run").is_some() {
+            driver.action = Action::Run;
+        }
+        // Whether the inputs are C sources whatever their extension.
+        let mut language_c = false;
+        while let Some(arg) = args.next() {
+            if driver.action == Action::Run && compile_only(&arg) {
+                fatal(format_args!("`{arg}` cannot be used with `sea run`"));
+            }
+            // The value of an option, attached or as the next argument.
+            let mut value = |option: &str| match &arg[option.len()..] {
+                "" => args.next().unwrap_or_else(|| usage()),
+                value => value.to_string(),
+            };
+            match arg.as_str() {
+                "-E" => driver.action = Action::Preprocess,
+                "--emit=ast" => driver.emit(Emit::Ast),
+                "--emit=ast-json" => driver.emit(Emit::AstJson),
+                "--emit=ast-sexp" => driver.emit(Emit::AstSexp),
+                "--emit=c" => driver.emit(Emit::C),
+                "--emit=hir" => driver.emit(Emit::Hir),
+                "--emit=dot-ast" => driver.emit(Emit::DotAst),
+                "--emit=dot-cfg" => driver.emit(Emit::DotCfg),
+                "--emit=dot-callgraph" => driver.emit(Emit::DotCallgraph),
+                "--emit=mir" => driver.emit(Emit::Mir),
+                "-S" | "--emit=asm" => driver.emit(Emit::Asm),
+                "--emit=llvm" => driver.emit(Emit::Llvm),
+                "-c" => driver.emit(Emit::Object),
+                "--target=x86_64" => driver.options.target = Target::X86_64,
+                "--target=riscv64" => driver.options.target = Target::Riscv64,
+                "--target=wasm32" => driver.options.target = Target::Wasm32,
+                "--regalloc=naive" => {
+                    driver.options.allocator = Allocator::Naive
+                }
+                "--regalloc=linear" => {
+                    driver.options.allocator = Allocator::Linear
+                }
+                "--regalloc=graph" => {
+                    driver.options.allocator = Allocator::Graph
+                }
+                "--check-ub" if driver.action == Action::Run => {
+                    driver.check_ub = true
+                }
+                "-O0" => driver.options.optimize.level = OptLevel::O0,
+                "-O" | "-O1" | "-O2" | "-O3" | "-Os" => {
+                    driver.options.optimize.level = OptLevel::O1
+                }
+                "-fno-inline" => driver.options.optimize.inline_limit = 0,
+                _ if arg.starts_with("-finline-limit=") => {
+                    let limit = &arg["-finline-limit=".len()..];
+                    driver.options.optimize.inline_limit =
+                        limit.parse().unwrap_or_else(|_| usage());
+                }
+                _ if arg.starts_with("--print-after=") => {
+                    let pass = &arg["--print-after=".len()..];
+                    if pass != "all" && !PASSES.contains(&pass) {
+                        fatal(format_args!("unknown pass `{pass}`"));
+                    }
+                    driver.options.optimize.print_after =
+                        Some(pass.to_string());
+                }
+                _ if arg.starts_with("-std=") => {
+                    let std = match &arg["-std=".len()..] {
+                        std @ ("c89" | "c99" | "c11" | "c17") => std,
+                        std @ ("gnu89" | "gnu99" | "gnu11" | "gnu17") => std,
+                        // Older preprocessors only know C23 by its draft
+                        // name.
+                        "c23" => "c2x",
+                        "gnu23" => "gnu2x",
+                        std => fatal(format_args!("unknown standard `{std}`")),
+                    };
+                    driver.cpp_args.push(format!("-std={std}"));
+                }
+                "-M" | "-MM" => {
+                    driver.action = Action::Preprocess;
+                    driver.cpp_args.push(arg);
+                }
+                "-MD" | "-MMD" => {
+                    driver.cpp_args.push(arg);
+                    driver.cpp_always = true;
+                }
+                "-MP" | "-MG" => driver.cpp_args.push(arg),
+                _ if ["-MF", "-MT", "-MQ"]
+                    .iter()
+                    .any(|option| arg.starts_with(option)) =>
+                {
+                    let option = &arg[..3];
+                    driver.cpp_args.push(option.to_string());
+                    driver.cpp_args.push(value(option));
+                }
+                _ if arg.starts_with("-x") => match value("-x").as_str() {
+                    "c" => language_c = true,
+                    "none" => language_c = false,
+                    language => {
+                        fatal(format_args!("unsupported language `{language}`"))
+                    }
+                },
+                _ if arg.starts_with("-o") => {
+                    driver.out_path = Some(value("-o").into())
+                }
+                _ if arg.starts_with("-I") => {
+                    driver.cpp_args.push(format!("-I{}", value("-I")));
+                    driver.cpp_always = true;
+                }
+                _ if arg.starts_with("-D") => {
+                    driver.cpp_args.push(format!("-D{}", value("-D")));
+                    driver.cpp_always = true;
+                }
+                _ if arg.starts_with("-U") => {
+                    driver.cpp_args.push(format!("-U{}", value("-U")));
+                    driver.cpp_always = true;
+                }
+                _ if arg.starts_with("-l") => {
+                    driver.link_args.push(format!("-l{}", value("-l")))
+                }
+                _ if arg.starts_with("-L") => {
+                    driver.link_args.push(format!("-L{}", value("-L")))
+                }
+                _ if arg.starts_with("-Wl,") => driver.link_args.push(arg),
+                "-pthread" => driver.link_args.push(arg),
+                "-Werror" => {
+                    driver.werror = true;
+                    driver.cpp_args.push(arg);
+                }
+                "-Wno-error" => {
+                    driver.werror = false;
+                    driver.cpp_args.push(arg);
+                }
+                "-w" => {
+                    driver.quiet = true;
+                    driver.cpp_args.push(arg);
+                }
+                _ if arg.starts_with("-W") => {
+                    driver.warnings = true;
+                    driver.cpp_args.push(arg);
+                }
+                _ if arg.starts_with("-g") => {}
+                // Code generation and dialect options, for `CFLAGS` written
+                // for GCC.
+                "-pedantic" | "-pedantic-errors" | "-pipe" | "-ansi" => {
+                    driver.ignored.push(arg)
+                }
+                _ if arg.starts_with("-f") || arg.starts_with("-m") => {
+                    driver.ignored.push(arg)
+                }
+                "-" => driver.inputs.push(arg.into()),
+                _ if arg.starts_with('-') => {
+                    fatal(format_args!("unrecognized option `{arg}`"))
+                }
+                _ => {
+                    if language_c {
+                        driver.c_inputs.push(arg.clone().into());
+                    }
+                    driver.inputs.push(arg.into())
+                }
+            }
+        }
+
+        if driver.warnings && !driver.quiet {
+            for arg in &driver.ignored {
+                eprintln!("warning: ignoring option `{arg}`");
+            }
+        }
+
+        if driver.inputs.is_empty() {
+            usage();
+        }
+        if driver.action == Action::Run && driver.inputs.len() > 1 {
+            usage();
+        }
+        driver
+    }
+}
+
+/// Report an error and exit.
+pub fn fatal(message: std::fmt::Arguments) -> ! {
+    eprintln!("error: {message}");
+    std::process::exit(1);
+}
+
+/// Report the problems with a source and exit.
+fn fail(diagnostics: &Diagnostics, path: &Path, source: &str) -> ! {
+    eprint!("{}", diagnostics.render(path, source));
+    std::process::exit(1);
+}
+
+/// Whether an option only applies to compiling, and not to `sea run`.
+fn compile_only(arg: &str) -> bool {
+    let prefixes = [
+        "--emit=",
+        "--target=",
+        "--regalloc=",
+        "--print-after=",
+        "-l",
+        "-L",
+        "-Wl,",
+    ];
+    matches!(arg, "-E" | "-S" | "-c" | "-M" | "-MM" | "-pthread")
+        || prefixes.iter().any(|prefix| arg.starts_with(prefix))
+}
+
+//----------- Stages -----------------------------------------------------------
+
+impl Driver {
+    /// Whether an input file is a C source, rather than an input of the
+    /// linker.
+    fn is_source(&self, path: &Path) -> bool {
+        path.as_os_str() == "-"
+            || self.c_inputs.iter().any(|input| input == path)
+            || path.extension().is_some_and(|ext| ext == "c" || ext == "i")
+    }
+
+    /// Produce the outputs.
+    fn run(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match self.action {
+            Action::Link => return self.link(),
+            Action::Run => return self.interpret(),
+            Action::Preprocess => {}
+            Action::Compile => {
+                if let Err(error) = self.options.validate() {
+                    fatal(format_args!("{error}"));
+                }
+            }
+        }
+
+        let sources: Vec<_> = self
+            .inputs
+            .iter()
+            .filter(|input| {
+                let source = self.is_source(input);
+                if !source {
+                    self.warn(format_args!(
+                        "{}: linker input file unused because linking not \
+                         done",
+                        input.display()
+                    ));
+                }
+                source
+            })
+            .collect();
+        self.stop_if_failed();
+        if self.out_path.is_some() && sources.len() > 1 {
+            fatal(format_args!(
+                "cannot specify `-o` with `-c`, `-S`, `-E` or `--emit` with \
+                 multiple files"
+            ));
+        }
+        let emit = self.options.emit;
+        let out_paths = sources.iter().map(|source| {
+            self.out_path.clone().unwrap_or_else(|| {
+                // Preprocessed and printed C go to standard output, so as
+                // not to overwrite the source.
+                if self.action == Action::Preprocess || emit == Emit::C {
+                    return "-".into();
+                }
+                Path::new(source.file_name().unwrap())
+                    .with_extension(emit.extension(self.options.target))
+            })
+        });
+        if self.action == Action::Preprocess {
+            for (source, out_path) in sources.iter().zip(out_paths) {
+                write(&out_path, self.preprocess(source, true)?.as_bytes())?;
+            }
+        } else if emit.is_front_end() {
+            for (source, out_path) in sources.iter().zip(out_paths) {
+                self.front_end(source, &out_path)?;
+            }
+        } else {
+            let modules = self.lower(&sources)?;
+            for (module, out_path) in modules.into_iter().zip(out_paths) {
+                self.back_end(module, &self.options, &out_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the front end on a source file, writing out the result.
+    fn front_end(
+        &self,
+        path: &Path,
+        out_path: &Path,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let source = self.preprocess(path, false)?;
+        let arena = Arena::default();
+        let program = parse(&arena, path, &source);
+        let emit = self.options.emit;
+
+        // The syntax is written out even if it does not type check, but the
+        // HIR and the control-flow graphs only once it does.
+        let checked = matches!(emit, Emit::Hir | Emit::DotCfg);
+        if checked {
+            check(&program, self.options.target, path, &source);
+        }
+        write(out_path, compile::dump(&program, emit).as_bytes())?;
+        if !checked {
+            check(&program, self.options.target, path, &source);
+        }
+        Ok(())
+    }
+
+    /// Optimize a lowered source and compile it, writing out the result.
+    fn back_end(
+        &self,
+        mut module: Module,
+        options: &Options,
+        out_path: &Path,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        write(out_path, &compile::generate(&mut module, options))
+    }
+
+    /// Read a source file, preprocessing it if needed or asked to.
+    fn preprocess(
+        &self,
+        path: &Path,
+        force: bool,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let stdin = path.as_os_str() == "-";
+        let mut source = String::new();
+        if stdin {
+            std::io::stdin().read_to_string(&mut source)?;
+        } else {
+            source = fs::read_to_string(path)?;
+        }
+        let preprocessed = path.extension().is_some_and(|ext| ext == "i");
+        if preprocessed
+            || !(force || self.cpp_always || needs_preprocessing(&source))
+        {
+            return Ok(source);
+        }
+
+        // The lexer skips the line markers, and diagnostics are located by
+        // them.
+        let mut cpp = Command::new("cpp")
+            .args(&self.cpp_args)
+            .arg(if stdin { Path::new("-") } else { path })
+            .stdin(if stdin { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|error| format!("cannot run `cpp`: {error}"))?;
+        if stdin {
+            cpp.stdin.take().unwrap().write_all(source.as_bytes())?;
+        }
+        let output = cpp.wait_with_output()?;
+        if !output.status.success() {
+            std::process::exit(1);
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Lower the source files to MIR, checking their linkage.
+    fn lower(
+        &self,
+        paths: &[&PathBuf],
+    ) -> Result<Vec<Module>, Box<dyn Error + Send + Sync>> {
+        let mut modules = Vec::new();
+        let mut sources = Vec::new();
+        for path in paths {
+            let source = self.preprocess(path, false)?;
+            let arena = Arena::default();
+            let program = parse(&arena, path, &source);
+            match compile::lower(&program, self.options.target) {
+                Ok(module) => modules.push(module),
+                Err(errors) => fail(&errors, path, &source),
+            }
+            sources.push(source);
+        }
+
+        let names: Vec<_> = paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        let units: Vec<_> = names
+            .iter()
+            .zip(&sources)
+            .zip(&modules)
+            .map(|((name, source), module)| Unit {
+                name,
+                source,
+                module,
+            })
+            .collect();
+        let diagnostics = link::check(&units);
+        for diagnostic in diagnostics.iter().filter(|d| !d.error) {
+            self.warn(format_args!("{diagnostic}"));
+        }
+        self.stop_if_failed();
+        let errors: Vec<_> = diagnostics.iter().filter(|d| d.error).collect();
+        if !errors.is_empty() {
+            for error in errors {
+                eprintln!("error: {error}");
+            }
+            std::process::exit(1);
+        }
+        Ok(modules)
+    }
+
+    /// Compile the sources to objects and link them with the other inputs.
+    fn link(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (emit, ext, linker) = match self.options.target {
+            Target::X86_64 => (Emit::Object, "o", "cc"),
+            Target::Riscv64 => (Emit::Asm, "s", "riscv64-linux-gnu-gcc"),
+            Target::Wasm32 => fatal(format_args!(
+                "executables cannot be linked for wasm32; use `-S`"
+            )),
+        };
+        let sources: Vec<_> = self
+            .inputs
+            .iter()
+            .filter(|input| self.is_source(input))
+            .collect();
+        let mut modules = self.lower(&sources)?.into_iter();
+        let options = Options {
+            emit,
+            ..self.options.clone()
+        };
+        let dir = std::env::temp_dir();
+        let mut temps = Vec::new();
+        let mut inputs = Vec::new();
+        for (i, input) in self.inputs.iter().enumerate() {
+            if !self.is_source(input) {
+                inputs.push(input.clone());
+                continue;
+            }
+            let temp =
+                dir.join(format!("sea-{}-{i}.{ext}", std::process::id()));
+            self.back_end(modules.next().unwrap(), &options, &temp)?;
+            temps.push(temp.clone());
+            inputs.push(temp);
+        }
+
+        let out_path = self.out_path.as_deref().unwrap_or(Path::new("a.out"));
+        let status = Command::new(linker)
+            .arg("-o")
+            .arg(out_path)
+            .args(&inputs)
+            .args(&self.link_args)
+            .status()
+            .map_err(|error| format!("cannot run `{linker}`: {error}"));
+        for temp in temps {
+            let _ = fs::remove_file(temp);
+        }
+        if !status?.success() {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+
+    /// Interpret the program.
+    fn interpret(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let path = &self.inputs[0];
+        let source = self.preprocess(path, false)?;
+        let arena = Arena::default();
+        let program = parse(&arena, path, &source);
+        // The interpreter lays out types as on x86-64.
+        check(&program, Target::X86_64, path, &source);
+        let out_path = self.out_path.as_deref().unwrap_or(Path::new("-"));
+        let mut writer = BufWriter::new(create(out_path)?);
+        let args = [path.display().to_string()];
+        match interp::run(&program, &args, &mut writer, self.check_ub) {
+            Ok(status) => std::process::exit(status),
+            Err(error) => fail(&error.into(), path, &source),
+        }
+    }
+
+    /// Report a warning, or an error with `-Werror`.
+    fn warn(&self, message: std::fmt::Arguments) {
+        if self.quiet {
+            return;
+        }
+        if self.werror {
+            eprintln!("error: {message}");
+            self.failed.set(true);
+        } else {
+            eprintln!("warning: {message}");
+        }
+    }
+
+    /// Exit if a warning was reported as an error, before anything is
+    /// written out.
+    fn stop_if_failed(&self) {
+        if self.failed.get() {
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Whether a source has to be preprocessed: whether it has lines that look
+/// like directives, or comments.
+fn needs_preprocessing(source: &str) -> bool {
+    source.lines().any(|line| {
+        line.trim_start().starts_with('#')
+            || line.contains("/*")
+            || line.contains("//")
+    })
+}
+
+/// Parse a source file, reporting errors with their position.
+fn parse<'hir>(
+    arena: &'hir Arena<'hir>,
+    path: &Path,
+    source: &str,
+) -> Program<'hir> {
+    compile::parse(arena, source)
+        .unwrap_or_else(|error| fail(&error.into(), path, source))
+}
+
+/// Type check a program for a target, reporting its errors.
+fn check(program: &Program, target: Target, path: &Path, source: &str) {
+    if let Err(errors) = compile::check(program, target) {
+        fail(&errors, path, source);
+    }
+}
+
+/// Open an output file; `-` is standard output.
+fn create(path: &Path) -> std::io::Result<Box<dyn Write + Send>> {
+    Ok(if path.as_os_str() == "-" {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(File::create(path)?)
+    })
+}
+
+/// Write out an output file.
+fn write(
+    path: &Path,
+    contents: &[u8],
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut writer = BufWriter::new(create(path)?);
+    writer.write_all(contents)?;
+    writer.flush()?;
+    Ok(())
+}
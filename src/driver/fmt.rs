@@ -0,0 +1,101 @@
+//! The `sea fmt` command.
+//!
+//! Sources are formatted (see [`sea::format`]) in the style of the
+//! configuration file found next to them, or given with `--config`, and
+//! rewritten in place, or with `--check`, only reported if they are not
+//! formatted.  `-` formats standard input to standard output.
+
+use std::error::Error;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use sea::format::{self, find_config, parse_config};
+use sea::hir::print::Style;
+
+use super::{fatal, usage};
+
+/// Run `sea fmt` on its command-line arguments.
+pub fn main(
+    args: impl Iterator<Item = String>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut check = false;
+    let mut config = None;
+    let mut paths = Vec::new();
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--config" => {
+                config =
+                    Some(PathBuf::from(args.next().unwrap_or_else(|| usage())))
+            }
+            _ if arg.starts_with("--config=") => {
+                config = Some(PathBuf::from(&arg["--config=".len()..]))
+            }
+            "-" => paths.push(PathBuf::from(arg)),
+            _ if arg.starts_with('-') => usage(),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        usage();
+    }
+    let config = config.map(|path| read_config(&path));
+
+    let mut failed = false;
+    for path in &paths {
+        let stdin = path.as_os_str() == "-";
+        let style = match &config {
+            Some(style) => style.clone(),
+            None => {
+                let dir = match path.parent() {
+                    Some(dir) if !stdin => dir,
+                    _ => Path::new("."),
+                };
+                find_config(dir)
+                    .map_or_else(Style::default, |config| read_config(&config))
+            }
+        };
+
+        let mut source = String::new();
+        if stdin {
+            std::io::stdin().read_to_string(&mut source)?;
+        } else {
+            source = fs::read_to_string(path)?;
+        }
+        let formatted = match format::format(&source, &style) {
+            Ok(formatted) => formatted,
+            Err(error) => {
+                eprintln!("{}", error.render(path, &source));
+                failed = true;
+                continue;
+            }
+        };
+
+        if check {
+            if formatted != source {
+                println!("{}", path.display());
+                failed = true;
+            }
+        } else if stdin {
+            print!("{formatted}");
+        } else if formatted != source {
+            fs::write(path, formatted)?;
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Read a style from a configuration file.
+fn read_config(path: &Path) -> Style {
+    let text = fs::read_to_string(path).unwrap_or_else(|error| {
+        fatal(format_args!("cannot read {}: {error}", path.display()))
+    });
+    parse_config(&text).unwrap_or_else(|error| {
+        fatal(format_args!("{}:{error}", path.display()))
+    })
+}
@@ -0,0 +1,325 @@
+//! Formatting C sources (`sea fmt`).
+//!
+//! Each source is parsed as it is, without preprocessing, and its top-level
+//! definitions are printed back in a [`Style`] (see [`crate::hir::print`]).
+//! Styles are configured in `seafmt.toml` or `.seafmt.toml` files, e.g.:
+//!
+//! ```toml
+//! indent_width = 4
+//! brace_style = "SameLine"  # or "NextLine"
+//! pointer_align = "Right"   # or "Left"
+//! max_width = 80
+//! ```
+//!
+//! The HIR does not keep comments and directives, so they are taken from the
+//! source: those between definitions are kept on lines of their own (or at
+//! the end of the line of the definition before them, if they were), those
+//! within a definition are printed along with its statements, and a
+//! definition with a directive inside it is left as it was written.  Blank
+//! lines between definitions are kept, up to one at a time.  Literals are
+//! written as they were spelled.
+//!
+//! Without preprocessing, the type names that headers declare are unknown:
+//! `FILE *f = 0;` parses as a multiplication, and `(size_t) -1` as a
+//! subtraction.  Printing these back would change what they mean, so a
+//! source where an undeclared name is used in such a way is not formatted.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::Diagnostic;
+use crate::hir::print::{BraceStyle, PointerAlign, Style};
+use crate::hir::visit::{self, Visit};
+use crate::hir::{
+    Arena, BinOp, Binding, Expr, FnDefn, Program, Spanned, Stmt, Symbol,
+};
+use crate::lexer::{self, LexerContext, ParseError};
+use crate::parser::Parseable;
+
+/// The names of the configuration file, in order of preference.
+const CONFIG_NAMES: [&str; 2] = ["seafmt.toml", ".seafmt.toml"];
+
+//----------- Formatting -------------------------------------------------------
+
+/// Format a C source in a style.
+///
+/// This fails if the source does not parse, or if it uses an undeclared name
+/// in a way that may make it a type (see the [module docs](self)).
+pub fn format(source: &str, style: &Style) -> Result<String, Diagnostic> {
+    let arena = Arena::default();
+    let mut ctx = LexerContext::new(source, &arena);
+    let program: Program = Program::parse(ctx.start())
+        .map_err(|error: ParseError| Diagnostic::from(error))?
+        .into();
+    program.names.enter(|| {
+        if let Some(name) = ambiguous(source, &program) {
+            return Err(Diagnostic {
+                offset: Some(name.span.start),
+                ..Diagnostic::error(format!(
+                    "`{}` is not declared and may be a type; \
+                     it cannot be formatted without its declaration",
+                    name.node
+                ))
+            });
+        }
+        Ok(write_out(source, &program, style))
+    })
+}
+
+/// Write out a parsed source in a style.
+fn write_out(source: &str, program: &Program, style: &Style) -> String {
+    let mut trivia = lexer::trivia(source).into_iter().peekable();
+
+    let mut out = String::new();
+    // The end of the last thing written out, if any.
+    let mut last = None;
+    // Whether the last definition was a function definition.
+    let mut last_fn = false;
+    let defns = program.decls.iter().zip(&program.spans).map(Some);
+    for defn in defns.chain([None]) {
+        let next = defn.map_or(source.len(), |(_, span)| span.start);
+        let next_fn = defn.is_some_and(|(decl, _)| decl.is_fn_defn());
+
+        // Function definitions are set apart by blank lines, along with the
+        // comments before them.
+        let mut first = true;
+        let mut separate = |out: &mut String, last: Option<usize>, start| {
+            let Some(last) = last else {
+                return;
+            };
+            let blank = source[last..start].matches('\n').count() > 1;
+            if blank || first && (last_fn || next_fn) {
+                out.push('\n');
+            }
+            first = false;
+        };
+
+        // Write out the comments and directives before the definition.
+        while let Some(comment) = trivia.next_if(|span| span.end <= next) {
+            let text = &source[comment.start..comment.end];
+            match last {
+                // A comment after a definition on its line stays there.
+                Some(last) if !source[last..comment.start].contains('\n') => {
+                    out.pop();
+                    out.push(' ');
+                }
+                _ => separate(&mut out, last, comment.start),
+            }
+            out.push_str(text);
+            out.push('\n');
+            last = Some(comment.end);
+        }
+
+        let Some((decl, span)) = defn else {
+            break;
+        };
+        separate(&mut out, last, span.start);
+        let mut inner = Vec::new();
+        while let Some(comment) =
+            trivia.next_if(|comment| comment.start < span.end)
+        {
+            inner.push(comment);
+        }
+        if inner
+            .iter()
+            .any(|comment| source[comment.start..].starts_with('#'))
+        {
+            // Directives must stay where they are among the tokens.
+            for line in source[span.start..span.end].lines() {
+                out.push_str(line.trim_end());
+                out.push('\n');
+            }
+        } else {
+            let styled = decl.styled(style).with_source(source, &inner);
+            out.push_str(&styled.to_string());
+            out.push('\n');
+        }
+        last = Some(span.end);
+        last_fn = next_fn;
+    }
+    out
+}
+
+//--- Ambiguity
+
+/// Find an undeclared name that may be a type, if any.
+fn ambiguous(source: &str, program: &Program) -> Option<Spanned<Symbol>> {
+    let mut declared = Declared::default();
+    declared.visit_program(program);
+    let mut ambiguity = Ambiguity {
+        source,
+        declared: declared.0,
+        found: None,
+    };
+    ambiguity.visit_program(program);
+    ambiguity.found
+}
+
+/// The names declared in a source.
+#[derive(Default)]
+struct Declared(HashSet<Symbol>);
+
+impl Visit<'_> for Declared {
+    fn visit_fn_defn(&mut self, defn: &FnDefn) {
+        self.0.insert(defn.name);
+        visit::walk_fn_defn(self, defn);
+    }
+
+    fn visit_binding(&mut self, binding: &Binding) {
+        if let Binding::Ident(name) = binding {
+            self.0.insert(*name);
+        }
+        visit::walk_binding(self, binding);
+    }
+}
+
+/// A search for undeclared names used where a type could be.
+struct Ambiguity<'a> {
+    /// The source.
+    source: &'a str,
+
+    /// The names declared in the source.
+    declared: HashSet<Symbol>,
+
+    /// The first undeclared name found where a type could be.
+    found: Option<Spanned<Symbol>>,
+}
+
+impl Ambiguity<'_> {
+    /// Note an expression if it is an undeclared name.
+    ///
+    /// If `paren` is set, the name only counts within parentheses.
+    fn check(&mut self, expr: &Spanned<Expr>, paren: bool) {
+        if let Expr::Ident(name) = expr.node
+            && !self.declared.contains(&name)
+            && (!paren || self.source[expr.span.start..].starts_with('('))
+            && self.found.is_none()
+        {
+            self.found = Some(Spanned::new(name, expr.span));
+        }
+    }
+}
+
+impl Visit<'_> for Ambiguity<'_> {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        // `T *x = 0, *y;` parses as `(T * x) = 0, *y;`.
+        if let Stmt::Expr(expr) = stmt {
+            let mut expr = expr;
+            while let Expr::Bin(BinOp::Assign | BinOp::Comma, lhs, _) =
+                expr.node
+            {
+                expr = lhs;
+            }
+            if let Expr::Bin(BinOp::Mul, lhs, _) = expr.node {
+                self.check(lhs, false);
+            }
+        }
+        visit::walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        // `(T) -x` and `(T)(x)` parse as a subtraction and a call.
+        match *expr {
+            Expr::Bin(
+                BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::BitAnd,
+                lhs,
+                _,
+            )
+            | Expr::Call(lhs, _) => self.check(lhs, true),
+            _ => {}
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+//----------- Configuration ----------------------------------------------------
+
+/// A mistake in a configuration file.
+#[derive(Clone, Debug)]
+pub struct ConfigError {
+    /// The line of the mistake.
+    pub line: usize,
+
+    /// A description of the mistake.
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Find the configuration file for the sources in a directory: the one in
+/// the directory or in the nearest directory above it.
+pub fn find_config(dir: &Path) -> Option<PathBuf> {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    dir.ancestors()
+        .flat_map(|dir| CONFIG_NAMES.map(|name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Parse the text of a configuration file.
+pub fn parse_config(text: &str) -> Result<Style, ConfigError> {
+    let mut style = Style::default();
+    for (i, line) in text.lines().enumerate() {
+        let fail = |message: String| ConfigError {
+            line: i + 1,
+            message,
+        };
+        // Comments cannot contain `#`, as strings here never do.
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(fail(format!(
+                "expected `key = value`, found `{line}`"
+            )));
+        };
+        let (key, value) = (key.trim(), value.trim());
+        let string = || {
+            value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .ok_or_else(|| fail(format!("expected a string for `{key}`")))
+        };
+        let number =
+            || {
+                value.parse::<usize>().ok().filter(|&n| n > 0).ok_or_else(
+                    || fail(format!("expected a positive integer for `{key}`")),
+                )
+            };
+        match key {
+            "indent_width" => style.indent_width = number()?,
+            "max_width" => style.max_width = number()?,
+            "brace_style" => {
+                style.brace_style = match string()? {
+                    "SameLine" => BraceStyle::SameLine,
+                    "NextLine" => BraceStyle::NextLine,
+                    other => {
+                        return Err(fail(format!(
+                            "unknown brace style `{other}`"
+                        )));
+                    }
+                }
+            }
+            "pointer_align" => {
+                style.pointer_align = match string()? {
+                    "Right" => PointerAlign::Right,
+                    "Left" => PointerAlign::Left,
+                    other => {
+                        return Err(fail(format!(
+                            "unknown pointer alignment `{other}`"
+                        )));
+                    }
+                }
+            }
+            _ => return Err(fail(format!("unknown option `{key}`"))),
+        }
+    }
+    Ok(style)
+}
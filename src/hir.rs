@@ -2,7 +2,24 @@
 
 use phf::phf_map;
 use std::fmt::Debug;
-pub const STD_TYPES: phf::Map<&'static str, Type> = phf_map! {
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+pub use arena::Arena;
+pub use symbol::{Names, Symbol};
+
+pub mod arena;
+pub mod dot;
+pub mod fold;
+pub mod print;
+pub mod serialize;
+pub mod symbol;
+pub mod visit;
+pub mod visit_mut;
+
+pub const STD_TYPES: phf::Map<&'static str, Type<'static>> = phf_map! {
     "void" => Type::Void,
     "char" => Type::Char { signed: None },
     "short" => Type::Short { signed: None },
@@ -10,107 +27,259 @@ pub const STD_TYPES: phf::Map<&'static str, Type> = phf_map! {
     "long" => Type::Long { signed: None },
     "float" => Type::Float,
     "double" => Type::Double,
+    "__builtin_va_list" => Type::VaList,
 };
 
 /// A C program (technically a "translation unit").
-#[derive(Debug)]
-pub struct Program {
+///
+/// The nodes of the program live in an [`Arena`].
+pub struct Program<'hir> {
     /// The top-level declarations and definitions in the program.
-    pub decls: Vec<TopDefn>,
+    pub decls: Vec<TopDefn<'hir>>,
+
+    /// The source text of each of `decls`.
+    pub spans: Vec<Span>,
+
+    /// The text of the symbols in the program.
+    pub names: Arc<Names>,
+}
+
+impl Program<'_> {
+    /// The symbol for a name, interning it if it is new.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        Arc::make_mut(&mut self.names).intern(name)
+    }
+}
+
+impl Debug for Program<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.names.enter(|| {
+            f.debug_struct("Program")
+                .field("decls", &self.decls)
+                .field("spans", &self.spans)
+                .finish()
+        })
+    }
+}
+
+/// A range of bytes in the source.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    /// The offset of the first byte.
+    pub start: usize,
+
+    /// The offset past the last byte.
+    pub end: usize,
+}
+
+/// A node, with the source text it was parsed from.
+///
+/// Statements, expressions and bindings are spanned wherever they occur.  A
+/// spanned node dereferences to the node itself, so code that has no use for
+/// positions can ignore the span.
+#[derive(Copy, Clone)]
+pub struct Spanned<T> {
+    /// The node.
+    pub node: T,
+
+    /// The source text of the node.
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    /// Construct a new [`Spanned`] node.
+    pub fn new(node: T, span: Span) -> Self {
+        Self { node, span }
+    }
+
+    /// Transform the node, keeping its span.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<U> {
+        Spanned::new(f(self.node), self.span)
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+impl<T> DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.node
+    }
+}
+
+/// Nodes are compared by their contents, but a node is equal to itself
+/// without comparing them, so that unchanged children compare cheaply.
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+            || self.span == other.span && self.node == other.node
+    }
+}
+
+impl<T: Debug> Debug for Spanned<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.node.fmt(f)
+    }
 }
 
 /// A top-level declaration or definition.
-pub enum TopDefn {
+#[derive(Copy, Clone, PartialEq)]
+pub enum TopDefn<'hir> {
     /// A function declaration or definition.
-    Fn(FnDefn),
+    Fn(FnDefn<'hir>),
 
     /// A variable declaration or definition.
-    Var(VarDefn),
+    Var(VarDefn<'hir>),
 
     /// A type definition.
-    Type(TypeDefn),
+    Type(TypeDefn<'hir>),
 }
 
 /// A function declaration or definition.
-pub struct FnDefn {
+#[derive(Copy, Clone, PartialEq)]
+pub struct FnDefn<'hir> {
     /// Attributes on the function.
-    pub attrs: (), // TODO
+    pub attrs: Attrs,
 
     /// The return type of the function.
-    pub return_type: Type,
+    pub return_type: &'hir Type<'hir>,
 
     /// The name of the function.
-    pub name: String,
+    pub name: Symbol,
 
     /// The parameters of the function.
-    pub params: Vec<MonoDecl>,
+    pub params: &'hir [MonoDecl<'hir>],
+
+    /// Whether the function takes variable arguments after `params`.
+    pub variadic: bool,
 
     /// The function body, if any.
-    pub body: Option<Block>,
+    pub body: Option<Block<'hir>>,
 }
 
 /// A variable declaration or definition.
-pub struct VarDefn {
-    /// Attributes on the function.
-    pub attrs: (), // TODO
+#[derive(Copy, Clone, PartialEq)]
+pub struct VarDefn<'hir> {
+    /// Attributes on the variable.
+    pub attrs: Attrs,
 
     /// The base type of the declaration.
-    pub base: Type,
+    pub base: &'hir Type<'hir>,
+
+    /// The bindings in the declaration, with optional initializers.
+    pub bindings: &'hir [(Spanned<Binding<'hir>>, Option<Initializer<'hir>>)],
+}
+
+/// The storage-class and function specifiers of a declaration.
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
+pub struct Attrs {
+    /// The storage class, if any.
+    pub storage: Option<Storage>,
+
+    /// Whether the function was declared `inline`.
+    pub inline: bool,
+}
+
+/// A storage class.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Storage {
+    /// `static`: internal linkage at file scope, static storage duration in
+    /// a block.
+    Static,
+
+    /// `extern`: a declaration of a symbol defined elsewhere.
+    Extern,
+
+    /// `auto`: automatic storage duration.
+    Auto,
 
-    /// The bindings in the declaration, with optional definitions.
-    pub bindings: Vec<(Binding, Option<Expr>)>,
+    /// `register`: automatic storage duration, without an address.
+    Register,
+}
+
+impl Storage {
+    /// The keyword naming the storage class.
+    pub fn keyword(self) -> &'static str {
+        match self {
+            Self::Static => "static",
+            Self::Extern => "extern",
+            Self::Auto => "auto",
+            Self::Register => "register",
+        }
+    }
 }
 
 /// A type definition.
-pub struct TypeDefn {
+#[derive(Copy, Clone, PartialEq)]
+pub struct TypeDefn<'hir> {
     /// Attributes on the function.
     pub attrs: (), // TODO
 
     /// The base type of the declaration.
-    pub base: Type,
+    pub base: &'hir Type<'hir>,
 
     /// The type bindings in the declaration.
-    pub bindings: Vec<Binding>,
+    pub bindings: &'hir [Spanned<Binding<'hir>>],
 }
 
 /// A declaration.
-#[derive(Clone)]
-pub struct Decl {
+#[derive(Copy, Clone, PartialEq)]
+pub struct Decl<'hir> {
     /// Attributes on the declaration.
     pub attrs: (), // TODO
 
     /// The base type of the declaration.
-    pub base: Type,
+    pub base: &'hir Type<'hir>,
 
     /// The bindings in the declaration.
-    pub bindings: Vec<Binding>,
+    pub bindings: &'hir [Spanned<Binding<'hir>>],
 }
 
 /// A declaration with a single binding.
-#[derive(Clone)]
-pub struct MonoDecl {
+#[derive(Copy, Clone, PartialEq)]
+pub struct MonoDecl<'hir> {
     /// Attributes on the declaration.
     pub attrs: (), // TODO
 
     /// The base type of the declaration.
-    pub base: Type,
+    pub base: &'hir Type<'hir>,
 
     /// The declaration's binding.
-    pub binding: Binding,
+    pub binding: Spanned<Binding<'hir>>,
+}
+
+/// A type name, as used in casts, `sizeof` and compound literals.
+#[derive(Copy, Clone, PartialEq)]
+pub struct TypeName<'hir> {
+    /// The base type.
+    pub base: &'hir Type<'hir>,
+
+    /// The abstract binding elaborating the base type.
+    ///
+    /// This is [`Binding::Anonymous`] if the type name is just the base type.
+    pub binding: Spanned<Binding<'hir>>,
 }
 
 /// A binding.
-#[derive(Clone)]
-pub enum Binding {
+#[derive(Copy, Clone, PartialEq)]
+pub enum Binding<'hir> {
     /// A function binding.
     Fn {
         /// The inner binding.
         ///
         /// This elaborates the return type.
-        inner: Box<Binding>,
+        inner: &'hir Spanned<Binding<'hir>>,
 
         /// Parameters to the function.
-        params: Vec<MonoDecl>,
+        params: &'hir [MonoDecl<'hir>],
+
+        /// Whether the function takes variable arguments after `params`.
+        variadic: bool,
     },
 
     /// An array binding.
@@ -118,20 +287,20 @@ pub enum Binding {
         /// The inner binding.
         ///
         /// This elaborates the element type.
-        inner: Box<Binding>,
+        inner: &'hir Spanned<Binding<'hir>>,
 
         /// The array size, if specified.
-        size: Option<Expr>,
+        size: Option<Spanned<Expr<'hir>>>,
     },
 
     /// A pointer binding.
-    Pointer(Box<Binding>),
+    Pointer(&'hir Spanned<Binding<'hir>>),
 
     /// A parenthesized binding.
-    Paren(Box<Binding>),
+    Paren(&'hir Spanned<Binding<'hir>>),
 
     /// A variable binding.
-    Ident(String),
+    Ident(Symbol),
 
     /// An anonymous binding.
     ///
@@ -139,9 +308,21 @@ pub enum Binding {
     Anonymous,
 }
 
+impl Binding<'_> {
+    /// The name bound by this binding, if any.
+    pub fn name(&self) -> Option<Symbol> {
+        match self {
+            Self::Fn { inner, .. } | Self::Array { inner, .. } => inner.name(),
+            Self::Pointer(inner) | Self::Paren(inner) => inner.name(),
+            Self::Ident(name) => Some(*name),
+            Self::Anonymous => None,
+        }
+    }
+}
+
 /// A type.
-#[derive(Clone)]
-pub enum Type {
+#[derive(Copy, Clone)]
+pub enum Type<'hir> {
     /// A void type.
     Void,
 
@@ -184,93 +365,143 @@ pub enum Type {
     /// A long double-precision floating-point number.
     LongDouble,
 
+    /// The type of the target holding the position in a list of variadic
+    /// arguments, spelled `__builtin_va_list` (`va_list` in `<stdarg.h>`).
+    VaList,
+
     /// A structure.
     Struct {
         /// Attributes on the struct.
         attrs: (), // TODO
 
         /// The name of the struct, if specified.
-        name: Option<String>,
+        name: Option<Symbol>,
 
-        /// The fields of the struct.
-        fields: Vec<MonoDecl>,
+        /// The fields of the struct, if it is being defined here.
+        fields: Option<&'hir [MonoDecl<'hir>]>,
     },
 
     /// An identifier.
-    Ident(String),
+    Ident(Symbol),
+}
+
+impl Type<'_> {
+    /// The parts of the type that it is compared and hashed by.
+    ///
+    /// Structure definitions are identified by the address of their fields.
+    fn key(&self) -> (mem::Discriminant<Self>, Option<bool>, Option<Symbol>) {
+        let (signed, name) = match *self {
+            Self::Char { signed }
+            | Self::Short { signed }
+            | Self::Int { signed }
+            | Self::Long { signed }
+            | Self::LongLong { signed } => (signed, None),
+            Self::Struct { name, .. } => (None, name),
+            Self::Ident(name) => (None, Some(name)),
+            _ => (None, None),
+        };
+        (mem::discriminant(self), signed, name)
+    }
+
+    /// The fields of a structure definition, as a pointer.
+    fn fields(&self) -> Option<*const [MonoDecl<'_>]> {
+        match *self {
+            Self::Struct { fields, .. } => fields.map(|f| f as *const _),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for Type<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+            && match (self.fields(), other.fields()) {
+                (Some(a), Some(b)) => std::ptr::eq(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            }
+    }
+}
+
+impl Eq for Type<'_> {}
+
+impl Hash for Type<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+        self.fields().map(|f| f.cast::<()>()).hash(state);
+    }
 }
 
 /// A block.
-#[derive(Debug)]
-pub struct Block {
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Block<'hir> {
     /// The statements making up the block.
-    pub stmts: Vec<Stmt>,
+    pub stmts: &'hir [Spanned<Stmt<'hir>>],
 }
 
 /// A statement.
-#[derive(Debug)]
-pub enum Stmt {
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Stmt<'hir> {
     /// A standalone semicolon.
     Empty,
 
     /// A block.
     Block {
         /// The statements making up the block.
-        stmts: Vec<Stmt>,
+        stmts: &'hir [Spanned<Stmt<'hir>>],
     },
 
     /// A variable declaration/definition.
-    VarDefn(VarDefn),
+    VarDefn(VarDefn<'hir>),
 
     /// A type definition.
-    Type(TypeDefn),
+    Type(TypeDefn<'hir>),
 
     /// An expression.
-    Expr(Expr),
+    Expr(Spanned<Expr<'hir>>),
 
     /// An if statement.
     If {
         /// The condition.
-        cond: Expr,
+        cond: Spanned<Expr<'hir>>,
 
         /// The success case.
-        then: Box<Stmt>,
+        then: &'hir Spanned<Stmt<'hir>>,
 
         /// The failure case.
-        r#else: Option<Box<Stmt>>,
+        r#else: Option<&'hir Spanned<Stmt<'hir>>>,
     },
 
     /// A for loop.
     For {
         /// The initialization expression.
-        init: Option<Expr>,
+        init: Option<Spanned<Expr<'hir>>>,
 
         /// The loop condition, if any.
-        cond: Option<Expr>,
+        cond: Option<Spanned<Expr<'hir>>>,
 
         /// The repetition statement.
-        step: Option<Expr>,
+        step: Option<Spanned<Expr<'hir>>>,
 
         /// The loop body.
-        body: Box<Stmt>,
+        body: &'hir Spanned<Stmt<'hir>>,
     },
 
     /// A while loop.
     While {
         /// The loop condition.
-        cond: Expr,
+        cond: Spanned<Expr<'hir>>,
 
         /// The loop body.
-        body: Box<Stmt>,
+        body: &'hir Spanned<Stmt<'hir>>,
     },
 
     /// A do-while loop.
     DoWhile {
         /// The loop body.
-        body: Box<Stmt>,
+        body: &'hir Spanned<Stmt<'hir>>,
 
         /// The loop condition.
-        cond: Expr,
+        cond: Spanned<Expr<'hir>>,
     },
 
     /// A break statement.
@@ -280,44 +511,318 @@ pub enum Stmt {
     Continue,
 
     /// A return statement
-    Return(Option<Expr>),
+    Return(Option<Spanned<Expr<'hir>>>),
 
     /// A goto statement
-    Goto(String),
+    Goto(Symbol),
+
+    /// A labelled statement.
+    Label(Symbol, &'hir Spanned<Stmt<'hir>>),
+
+    /// A switch statement.
+    Switch {
+        /// The controlling expression.
+        cond: Spanned<Expr<'hir>>,
+
+        /// The switch body.
+        body: &'hir Spanned<Stmt<'hir>>,
+    },
+
+    /// A `case` label in a switch body.
+    Case(Spanned<Expr<'hir>>, &'hir Spanned<Stmt<'hir>>),
+
+    /// A `default` label in a switch body.
+    Default(&'hir Spanned<Stmt<'hir>>),
 }
 
 /// An expression.
-#[derive(Clone)]
-pub enum Expr {
+#[derive(Copy, Clone, PartialEq)]
+pub enum Expr<'hir> {
     /// A debug expression
     /// Equivalent to reading `expr` in the source
     Debug,
 
     /// A reference expression.
-    Ref(Box<Expr>),
+    Ref(&'hir Spanned<Expr<'hir>>),
 
     /// A binary expression.
-    Bin(BinOp, Box<Expr>, Box<Expr>),
+    Bin(BinOp, &'hir Spanned<Expr<'hir>>, &'hir Spanned<Expr<'hir>>),
 
     /// A unary expression.
-    Una(UnaOp, Box<Expr>),
+    Una(UnaOp, &'hir Spanned<Expr<'hir>>),
 
     /// An identifier.
-    Ident(String),
+    Ident(Symbol),
+
+    /// A literal.
+    Lit(Lit<'hir>),
+
+    /// A cast expression.
+    Cast(&'hir TypeName<'hir>, &'hir Spanned<Expr<'hir>>),
+
+    /// The size of the type of an expression.
+    Sizeof(&'hir Spanned<Expr<'hir>>),
+
+    /// The size of a type.
+    SizeofType(&'hir TypeName<'hir>),
+
+    /// The alignment of a type.
+    Alignof(&'hir TypeName<'hir>),
+
+    /// A compound literal.
+    CompoundLit(&'hir TypeName<'hir>, &'hir [InitItem<'hir>]),
+
+    /// A function call.
+    Call(&'hir Spanned<Expr<'hir>>, &'hir [Spanned<Expr<'hir>>]),
+
+    /// A member access, as in `s.a`.
+    Member(&'hir Spanned<Expr<'hir>>, Symbol),
+
+    /// A member access through a pointer, as in `p->a`.
+    Arrow(&'hir Spanned<Expr<'hir>>, Symbol),
+
+    /// An array subscript.
+    Index(&'hir Spanned<Expr<'hir>>, &'hir Spanned<Expr<'hir>>),
+
+    /// A postfix increment.
+    PostInc(&'hir Spanned<Expr<'hir>>),
+
+    /// A postfix decrement.
+    PostDec(&'hir Spanned<Expr<'hir>>),
+
+    /// A conditional expression, as in `c ? a : b`.
+    Cond(
+        &'hir Spanned<Expr<'hir>>,
+        &'hir Spanned<Expr<'hir>>,
+        &'hir Spanned<Expr<'hir>>,
+    ),
+
+    /// The start of the variadic arguments of the function, after the named
+    /// parameter `last`, as in `__builtin_va_start(ap, last)`.
+    VaStart(&'hir Spanned<Expr<'hir>>, &'hir Spanned<Expr<'hir>>),
+
+    /// The next variadic argument, of a type, as in
+    /// `__builtin_va_arg(ap, int)`.
+    VaArg(&'hir Spanned<Expr<'hir>>, &'hir TypeName<'hir>),
+
+    /// The end of the use of a `va_list`, as in `__builtin_va_end(ap)`.
+    VaEnd(&'hir Spanned<Expr<'hir>>),
+
+    /// A copy of a `va_list`, as in `__builtin_va_copy(dest, src)`.
+    VaCopy(&'hir Spanned<Expr<'hir>>, &'hir Spanned<Expr<'hir>>),
+}
+
+/// A literal.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Lit<'hir> {
+    /// An integer constant.
+    Int {
+        /// The value of the constant.
+        value: u64,
+
+        /// The base the constant is written in.
+        radix: Radix,
+
+        /// Whether the constant has a `u` suffix.
+        unsigned: bool,
+
+        /// The number of `l` suffixes on the constant.
+        long: u8,
+    },
+
+    /// A floating-point constant.
+    Float {
+        /// The value of the constant.
+        value: f64,
+
+        /// Whether the constant has an `f` suffix.
+        single: bool,
+
+        /// Whether the constant has an `l` suffix.
+        long: bool,
+    },
+
+    /// A character constant.
+    Char(u8),
+
+    /// A string literal, without its null terminator.
+    Str(&'hir [u8]),
+}
+
+/// The base of an integer constant.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Radix {
+    /// A decimal constant, like `42`.
+    Decimal,
+
+    /// An octal constant, like `052`.
+    Octal,
+
+    /// A hexadecimal constant, like `0x2a`.
+    Hex,
+
+    /// A binary constant, like `0b101010`.
+    Binary,
+}
+
+/// An initializer.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Initializer<'hir> {
+    /// A single expression.
+    Expr(Spanned<Expr<'hir>>),
+
+    /// A brace-enclosed initializer list.
+    List(&'hir [InitItem<'hir>]),
+}
+
+/// An element of an initializer list.
+#[derive(Copy, Clone, PartialEq)]
+pub struct InitItem<'hir> {
+    /// The designators selecting the initialized subobject, if any.
+    pub designators: &'hir [Designator<'hir>],
+
+    /// The initializer for the subobject.
+    pub init: Initializer<'hir>,
+}
+
+/// A designator in an initializer list.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Designator<'hir> {
+    /// A struct member, as in `.name = ...`.
+    Member(Symbol),
+
+    /// An array element, as in `[index] = ...`.
+    Index(Spanned<Expr<'hir>>),
 }
 
 /// A binary operation.
-#[derive(Debug, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub enum BinOp {
     /// Addition.
     Add,
 
     /// Subtraction.
     Sub,
+
+    /// Multiplication.
+    Mul,
+
+    /// Division.
+    Div,
+
+    /// Remainder.
+    Rem,
+
+    /// Left shift.
+    Shl,
+
+    /// Right shift.
+    Shr,
+
+    /// Bitwise and.
+    BitAnd,
+
+    /// Bitwise or.
+    BitOr,
+
+    /// Bitwise exclusive or.
+    BitXor,
+
+    /// Logical and.
+    And,
+
+    /// Logical or.
+    Or,
+
+    /// Equality.
+    Eq,
+
+    /// Inequality.
+    Ne,
+
+    /// Less than.
+    Lt,
+
+    /// Greater than.
+    Gt,
+
+    /// Less than or equal.
+    Le,
+
+    /// Greater than or equal.
+    Ge,
+
+    /// Assignment.
+    Assign,
+
+    /// Addition assignment.
+    AddAssign,
+
+    /// Subtraction assignment.
+    SubAssign,
+
+    /// Multiplication assignment.
+    MulAssign,
+
+    /// Division assignment.
+    DivAssign,
+
+    /// Remainder assignment.
+    RemAssign,
+
+    /// Left shift assignment.
+    ShlAssign,
+
+    /// Right shift assignment.
+    ShrAssign,
+
+    /// Bitwise and assignment.
+    BitAndAssign,
+
+    /// Bitwise or assignment.
+    BitOrAssign,
+
+    /// Bitwise exclusive or assignment.
+    BitXorAssign,
+
+    /// The comma operator.
+    Comma,
+}
+
+impl BinOp {
+    /// The operation performed by a compound assignment operator.
+    pub fn compound(self) -> Option<Self> {
+        Some(match self {
+            Self::AddAssign => Self::Add,
+            Self::SubAssign => Self::Sub,
+            Self::MulAssign => Self::Mul,
+            Self::DivAssign => Self::Div,
+            Self::RemAssign => Self::Rem,
+            Self::ShlAssign => Self::Shl,
+            Self::ShrAssign => Self::Shr,
+            Self::BitAndAssign => Self::BitAnd,
+            Self::BitOrAssign => Self::BitOr,
+            Self::BitXorAssign => Self::BitXor,
+            _ => return None,
+        })
+    }
+
+    /// Whether this is a plain or compound assignment operator.
+    pub fn is_assign(self) -> bool {
+        self == Self::Assign || self.compound().is_some()
+    }
+
+    /// Whether this is a comparison operator.
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            Self::Eq | Self::Ne | Self::Lt | Self::Gt | Self::Le | Self::Ge
+        )
+    }
 }
 
 /// A unary operation.
-#[derive(Debug, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub enum UnaOp {
     /// Negation.
     Neg,
@@ -327,4 +832,16 @@ pub enum UnaOp {
 
     /// Bitwise inversion.
     Inv,
+
+    /// Unary plus.
+    Plus,
+
+    /// Pointer dereference.
+    Deref,
+
+    /// Prefix increment.
+    PreInc,
+
+    /// Prefix decrement.
+    PreDec,
 }
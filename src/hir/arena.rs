@@ -0,0 +1,215 @@
+//! The storage of the HIR.
+//!
+//! The nodes of a program live in an [`Arena`], which is made for a
+//! compilation and dropped after it, and nodes refer to their children by
+//! `&'hir` references into it.  Nodes are allocated a chunk at a time rather
+//! than one by one, are [`Copy`], and are identified by their addresses,
+//! which makes them cheap keys for side tables (see
+//! [`ptr::eq`](std::ptr::eq)).
+//!
+//! Types are hash-consed: [`Arena::ty()`] gives back the same reference for
+//! equal types.  The exception is structure definitions, which are only
+//! equal to themselves, as every definition declares a new type.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use super::{
+    Binding, Designator, Expr, InitItem, Initializer, MonoDecl, Spanned, Stmt,
+    Type, TypeName,
+};
+
+/// The storage of the HIR of a compilation.
+#[derive(Default)]
+pub struct Arena<'hir> {
+    /// The expressions.
+    exprs: Store<Spanned<Expr<'hir>>>,
+
+    /// The statements.
+    stmts: Store<Spanned<Stmt<'hir>>>,
+
+    /// The bindings.
+    bindings: Store<Spanned<Binding<'hir>>>,
+
+    /// The bindings of variable definitions, with their initializers.
+    init_bindings: Store<(Spanned<Binding<'hir>>, Option<Initializer<'hir>>)>,
+
+    /// The declarations of parameters and fields.
+    mono_decls: Store<MonoDecl<'hir>>,
+
+    /// The type names.
+    type_names: Store<TypeName<'hir>>,
+
+    /// The elements of initializer lists.
+    init_items: Store<InitItem<'hir>>,
+
+    /// The designators.
+    designators: Store<Designator<'hir>>,
+
+    /// The contents of string literals.
+    bytes: Store<u8>,
+
+    /// The types.
+    types: Store<Type<'hir>>,
+
+    /// The types allocated so far, other than structure definitions.
+    interned: RefCell<HashSet<&'hir Type<'hir>>>,
+}
+
+impl<'hir> Arena<'hir> {
+    /// Allocate an expression.
+    pub fn expr(
+        &'hir self,
+        expr: Spanned<Expr<'hir>>,
+    ) -> &'hir Spanned<Expr<'hir>> {
+        &self.exprs.alloc([expr])[0]
+    }
+
+    /// Allocate a list of expressions.
+    pub fn exprs(
+        &'hir self,
+        exprs: Vec<Spanned<Expr<'hir>>>,
+    ) -> &'hir [Spanned<Expr<'hir>>] {
+        self.exprs.alloc(exprs)
+    }
+
+    /// Allocate a statement.
+    pub fn stmt(
+        &'hir self,
+        stmt: Spanned<Stmt<'hir>>,
+    ) -> &'hir Spanned<Stmt<'hir>> {
+        &self.stmts.alloc([stmt])[0]
+    }
+
+    /// Allocate a list of statements.
+    pub fn stmts(
+        &'hir self,
+        stmts: Vec<Spanned<Stmt<'hir>>>,
+    ) -> &'hir [Spanned<Stmt<'hir>>] {
+        self.stmts.alloc(stmts)
+    }
+
+    /// Allocate a binding.
+    pub fn binding(
+        &'hir self,
+        binding: Spanned<Binding<'hir>>,
+    ) -> &'hir Spanned<Binding<'hir>> {
+        &self.bindings.alloc([binding])[0]
+    }
+
+    /// Allocate a list of bindings.
+    pub fn bindings(
+        &'hir self,
+        bindings: Vec<Spanned<Binding<'hir>>>,
+    ) -> &'hir [Spanned<Binding<'hir>>] {
+        self.bindings.alloc(bindings)
+    }
+
+    /// Allocate the bindings of a variable definition.
+    pub fn init_bindings(
+        &'hir self,
+        bindings: Vec<(Spanned<Binding<'hir>>, Option<Initializer<'hir>>)>,
+    ) -> &'hir [(Spanned<Binding<'hir>>, Option<Initializer<'hir>>)] {
+        self.init_bindings.alloc(bindings)
+    }
+
+    /// Allocate a list of parameter or field declarations.
+    pub fn mono_decls(
+        &'hir self,
+        decls: Vec<MonoDecl<'hir>>,
+    ) -> &'hir [MonoDecl<'hir>] {
+        self.mono_decls.alloc(decls)
+    }
+
+    /// Allocate a type name.
+    pub fn type_name(&'hir self, name: TypeName<'hir>) -> &'hir TypeName<'hir> {
+        &self.type_names.alloc([name])[0]
+    }
+
+    /// Allocate an initializer list.
+    pub fn init_items(
+        &'hir self,
+        items: Vec<InitItem<'hir>>,
+    ) -> &'hir [InitItem<'hir>] {
+        self.init_items.alloc(items)
+    }
+
+    /// Allocate a list of designators.
+    pub fn designators(
+        &'hir self,
+        designators: Vec<Designator<'hir>>,
+    ) -> &'hir [Designator<'hir>] {
+        self.designators.alloc(designators)
+    }
+
+    /// Allocate the contents of a string literal.
+    pub fn bytes(&'hir self, bytes: Vec<u8>) -> &'hir [u8] {
+        self.bytes.alloc(bytes)
+    }
+
+    /// The type equal to a type, allocating it if it is new.
+    pub fn ty(&'hir self, ty: Type<'hir>) -> &'hir Type<'hir> {
+        if let Type::Struct {
+            fields: Some(_), ..
+        } = ty
+        {
+            return &self.types.alloc([ty])[0];
+        }
+        if let Some(&ty) = self.interned.borrow().get(&ty) {
+            return ty;
+        }
+        let ty = &self.types.alloc([ty])[0];
+        self.interned.borrow_mut().insert(ty);
+        ty
+    }
+}
+
+//----------- Store ------------------------------------------------------------
+
+/// Values of one type, which stay where they are until the store is dropped.
+struct Store<T> {
+    /// The chunks of values, which are never filled past their capacity.
+    chunks: RefCell<Vec<Vec<T>>>,
+}
+
+impl<T> Default for Store<T> {
+    fn default() -> Self {
+        Self {
+            chunks: RefCell::default(),
+        }
+    }
+}
+
+impl<T> Store<T> {
+    /// The least number of values in a chunk.
+    const CHUNK_LEN: usize = 64;
+
+    /// Move some values into the store, next to each other.
+    fn alloc(
+        &self,
+        values: impl IntoIterator<Item = T, IntoIter: ExactSizeIterator>,
+    ) -> &[T] {
+        let values = values.into_iter();
+        let len = values.len();
+        if len == 0 {
+            return &[];
+        }
+
+        let mut chunks = self.chunks.borrow_mut();
+        let last = chunks.last().map_or(0, Vec::capacity);
+        if chunks.last().is_none_or(|c| c.capacity() - c.len() < len) {
+            let capacity = len.max(2 * last).max(Self::CHUNK_LEN);
+            chunks.push(Vec::with_capacity(capacity));
+        }
+        let chunk = chunks.last_mut().unwrap();
+        let start = chunk.len();
+        chunk.extend(values.take(len));
+        let values: *const [T] = &chunk[start..];
+
+        // SAFETY: The values stay where they are for as long as the store is
+        // borrowed: chunks are never filled past their capacity, so they are
+        // never reallocated, and are only dropped with the store.  Nothing
+        // changes them either, as only shared references to them are made.
+        unsafe { &*values }
+    }
+}
@@ -0,0 +1,520 @@
+//! Graphviz output of the HIR.
+//!
+//! Programs can be drawn as their syntax tree (`--emit=dot-ast`), as the
+//! control-flow graphs of their functions (`--emit=dot-cfg`, as lowered to
+//! the MIR) or as their call graph (`--emit=dot-callgraph`).  Every node is
+//! labelled with the `Debug` text of what it stands for, as in `--emit=ast`;
+//! render the output with e.g. `dot -Tsvg`.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Write};
+
+use super::visit::{self, Visit};
+use super::{
+    Block, Expr, FnDefn, InitItem, Initializer, Program, Spanned, Stmt, Symbol,
+    TopDefn, VarDefn,
+};
+use crate::codegen::Target;
+use crate::mir::lower::{self, Origin};
+use crate::mir::{BlockId, Terminator};
+
+impl Program<'_> {
+    /// The syntax tree of the program, as a Graphviz graph.
+    pub fn dot_ast(&self) -> String {
+        self.names.enter(|| {
+            let mut graph = Graph::new("ast");
+            let root = graph.node_text("Program", "");
+            for defn in &self.decls {
+                let node = match defn {
+                    TopDefn::Fn(defn) => {
+                        let node = graph.node(defn.signature(), "");
+                        if let Some(body) = &defn.body {
+                            let body = graph.block(body);
+                            graph.edge(node, body, "body");
+                        }
+                        node
+                    }
+                    TopDefn::Var(var) => {
+                        let node = graph.node(var, "");
+                        for (_, init) in var.bindings {
+                            if let Some(init) = init {
+                                let init = graph.init(init);
+                                graph.edge(node, init, "init");
+                            }
+                        }
+                        node
+                    }
+                    TopDefn::Type(ty) => graph.node(ty, ""),
+                };
+                graph.edge(root, node, "");
+            }
+            graph.finish()
+        })
+    }
+
+    /// The control-flow graphs of the functions defined in the program, as
+    /// a Graphviz graph with a cluster for each.
+    ///
+    /// The graphs are those of the MIR the functions are lowered to.  Blocks
+    /// are labelled with the statements and controlling expressions their
+    /// code comes from, and each function has an `exit` node, which its
+    /// returns go to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the program does not type check.
+    pub fn dot_cfg(&self) -> String {
+        self.names.enter(|| {
+            let (module, traces) = lower::lower_traced(self, Target::default())
+                .unwrap_or_else(|_| panic!("the program does not type check"));
+            let mut graph = Graph::new("cfg");
+            for (func, trace) in module.functions.iter().zip(&traces) {
+                graph.begin_cluster(&func.name, trace.defn.signature());
+                let first = graph.nodes;
+                for (id, block) in func.blocks.iter().enumerate() {
+                    let mut label = format!("bb{id}\n");
+                    // Consecutive instructions from the same origin share a
+                    // line.
+                    let mut origins: Vec<Origin> = Vec::new();
+                    for origin in trace.insts[id].iter().flatten() {
+                        if !origins.last().is_some_and(|last| last.is(origin)) {
+                            origins.push(*origin);
+                        }
+                    }
+                    // The code of a labelled terminator shares its line.
+                    let term = match (&block.term, trace.terms[id]) {
+                        (Terminator::Branch(..), Some(cond)) => {
+                            Some((cond, format!("if {cond:?}")))
+                        }
+                        (Terminator::Switch { .. }, Some(value)) => {
+                            Some((value, format!("switch {value:?}")))
+                        }
+                        (Terminator::Return(_), Some(origin)) => {
+                            Some((origin, format!("{origin:?}")))
+                        }
+                        _ => None,
+                    };
+                    if let Some((origin, _)) = &term
+                        && origins.last().is_some_and(|last| last.is(origin))
+                    {
+                        origins.pop();
+                    }
+                    for origin in origins {
+                        writeln!(label, "{origin:?}").unwrap();
+                    }
+                    match (&block.term, term) {
+                        (_, Some((_, line))) => {
+                            writeln!(label, "{line}").unwrap()
+                        }
+                        (Terminator::Return(_), None) => {
+                            label.push_str("return\n")
+                        }
+                        _ => {}
+                    }
+                    graph.node_text(&label, "");
+                }
+                let exit = graph.node_text("exit", ", shape=ellipse");
+                graph.end_cluster();
+
+                for (id, block) in func.blocks.iter().enumerate() {
+                    let from = first + id;
+                    let to = |block: &BlockId| first + block.0 as usize;
+                    match &block.term {
+                        Terminator::Jump(target) => {
+                            graph.edge(from, to(target), "")
+                        }
+                        Terminator::Branch(_, then, r#else) => {
+                            graph.edge(from, to(then), "true");
+                            graph.edge(from, to(r#else), "false");
+                        }
+                        Terminator::Switch { cases, default, .. } => {
+                            for (value, target) in cases {
+                                graph.edge(
+                                    from,
+                                    to(target),
+                                    &value.to_string(),
+                                );
+                            }
+                            graph.edge(from, to(default), "default");
+                        }
+                        Terminator::Return(_) => graph.edge(from, exit, ""),
+                        Terminator::Unreachable => {}
+                    }
+                }
+            }
+            graph.finish()
+        })
+    }
+
+    /// The call graph of the program, as a Graphviz graph.
+    ///
+    /// There is a node for every function defined in the program, and for
+    /// every function declared in it that they call, drawn dashed.  Only
+    /// direct calls are known: calls through pointers are not drawn.
+    pub fn dot_callgraph(&self) -> String {
+        self.names.enter(|| {
+            // The declarations of each function, the definition last if any.
+            let mut fns: HashMap<Symbol, Vec<&FnDefn>> = HashMap::new();
+            let mut names = Vec::new();
+            for defn in &self.decls {
+                if let TopDefn::Fn(defn) = defn {
+                    let decls = fns.entry(defn.name).or_default();
+                    if decls.is_empty() {
+                        names.push(defn.name);
+                    }
+                    decls.push(defn);
+                    decls.sort_by_key(|defn| defn.body.is_some());
+                }
+            }
+            let defined =
+                |name: Symbol| fns[&name].last().unwrap().body.is_some();
+
+            let mut calls = Vec::new();
+            let mut called = HashSet::new();
+            for &name in names.iter().filter(|&&name| defined(name)) {
+                let defn = fns[&name].last().unwrap();
+                let mut visitor = Calls::default();
+                for param in defn.params {
+                    visitor.locals.extend(param.binding.name());
+                }
+                visitor.visit_block(defn.body.as_ref().unwrap());
+                let Calls {
+                    mut callees,
+                    locals,
+                } = visitor;
+                // A call to a name declared anywhere in the function as a
+                // variable may be through it, so is not counted.
+                callees.retain(|callee| {
+                    fns.contains_key(callee) && !locals.contains(callee)
+                });
+                let mut seen = HashSet::new();
+                callees.retain(|callee| seen.insert(*callee));
+                called.extend(callees.iter().copied());
+                calls.push((name, callees));
+            }
+
+            let mut graph = Graph::new("callgraph");
+            let mut nodes = HashMap::new();
+            for &name in &names {
+                if defined(name) || called.contains(&name) {
+                    let defn = fns[&name].last().unwrap();
+                    let style =
+                        if defined(name) { "" } else { ", style=dashed" };
+                    nodes.insert(name, graph.node(defn.signature(), style));
+                }
+            }
+            for (caller, callees) in calls {
+                for callee in callees {
+                    graph.edge(nodes[&caller], nodes[&callee], "");
+                }
+            }
+            graph.finish()
+        })
+    }
+}
+
+//----------- Calls ------------------------------------------------------------
+
+/// The names of the functions called directly in a function, and of the
+/// variables declared in it.
+#[derive(Default)]
+struct Calls {
+    /// The names called, in order, with repeats.
+    callees: Vec<Symbol>,
+
+    /// The names of the parameters and variables.
+    locals: HashSet<Symbol>,
+}
+
+impl<'a> Visit<'a> for Calls {
+    fn visit_var_defn(&mut self, defn: &'a VarDefn) {
+        for (binding, _) in defn.bindings {
+            self.locals.extend(binding.name());
+        }
+        visit::walk_var_defn(self, defn)
+    }
+
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        match expr {
+            Expr::Call(callee, args)
+                if let Expr::Ident(name) = &callee.node =>
+            {
+                self.callees.push(*name);
+                for arg in *args {
+                    self.visit_expr(arg);
+                }
+            }
+            _ => visit::walk_expr(self, expr),
+        }
+    }
+}
+
+//----------- Graph ------------------------------------------------------------
+
+/// A Graphviz graph being written out.
+struct Graph {
+    /// The text of the graph so far.
+    out: String,
+
+    /// The number of nodes so far, which are named `n0`, `n1`, ....
+    nodes: usize,
+
+    /// The indentation of nodes, which is deeper within clusters.
+    indent: &'static str,
+}
+
+impl Graph {
+    /// Start a directed graph.
+    fn new(name: &str) -> Self {
+        let mut out = format!("digraph {name} {{\n");
+        out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+        Self {
+            out,
+            nodes: 0,
+            indent: "  ",
+        }
+    }
+
+    /// Add a node labelled with the `Debug` text of something, with more
+    /// attributes (starting with a comma) if any.
+    fn node(&mut self, label: impl Debug, attrs: &str) -> usize {
+        let label = format!("{label:?}");
+        self.node_text(&label, attrs)
+    }
+
+    /// Add a node with a label, with more attributes (starting with a comma)
+    /// if any.
+    fn node_text(&mut self, label: &str, attrs: &str) -> usize {
+        writeln!(
+            self.out,
+            "{}n{} [label={}{attrs}];",
+            self.indent,
+            self.nodes,
+            quote_text(label)
+        )
+        .unwrap();
+        self.nodes += 1;
+        self.nodes - 1
+    }
+
+    /// Add an edge, with a label if it is not empty.
+    fn edge(&mut self, from: usize, to: usize, label: &str) {
+        write!(self.out, "  n{from} -> n{to}").unwrap();
+        if !label.is_empty() {
+            write!(self.out, " [label={}]", quote_text(label)).unwrap();
+        }
+        self.out.push_str(";\n");
+    }
+
+    /// Start a cluster of nodes, labelled with the `Debug` text of
+    /// something.
+    fn begin_cluster(&mut self, name: &str, label: impl Debug) {
+        writeln!(self.out, "  subgraph cluster_{name} {{").unwrap();
+        writeln!(self.out, "    label={};", quote(label)).unwrap();
+        self.indent = "    ";
+    }
+
+    /// End the current cluster.
+    fn end_cluster(&mut self) {
+        self.out.push_str("  }\n");
+        self.indent = "  ";
+    }
+
+    /// Finish the graph.
+    fn finish(mut self) -> String {
+        self.out.push_str("}\n");
+        self.out
+    }
+
+    //--- Syntax trees
+
+    /// Add the tree of a block.
+    fn block(&mut self, block: &Block) -> usize {
+        let node = self.node_text("Block", "");
+        for stmt in block.stmts {
+            let child = self.stmt(stmt);
+            self.edge(node, child, "");
+        }
+        node
+    }
+
+    /// Add the tree of a statement.
+    ///
+    /// Statements that contain others are labelled with their kind alone,
+    /// and expressions and declarations stand for their statements.
+    fn stmt(&mut self, stmt: &Stmt) -> usize {
+        let (kind, children): (&str, Vec<(&str, Child)>) = match stmt {
+            Stmt::Expr(expr) => return self.expr(expr),
+            Stmt::VarDefn(var) => {
+                let node = self.node(var, "");
+                for (_, init) in var.bindings {
+                    if let Some(init) = init {
+                        let init = self.init(init);
+                        self.edge(node, init, "init");
+                    }
+                }
+                return node;
+            }
+            Stmt::Type(ty) => return self.node(ty, ""),
+            Stmt::Empty
+            | Stmt::Break
+            | Stmt::Continue
+            | Stmt::Goto(_)
+            | Stmt::Return(None) => return self.node(stmt, ""),
+            Stmt::Block { stmts } => (
+                "Block",
+                stmts.iter().map(|stmt| ("", Child::Stmt(stmt))).collect(),
+            ),
+            Stmt::If { cond, then, r#else } => (
+                "If",
+                [("cond", Child::Expr(cond)), ("then", Child::Stmt(then))]
+                    .into_iter()
+                    .chain(r#else.iter().map(|e| ("else", Child::Stmt(e))))
+                    .collect(),
+            ),
+            Stmt::For {
+                init,
+                cond,
+                step,
+                body,
+            } => (
+                "For",
+                [("init", init), ("cond", cond), ("step", step)]
+                    .into_iter()
+                    .filter_map(|(field, e)| {
+                        e.as_ref().map(|e| (field, Child::Expr(e)))
+                    })
+                    .chain([("body", Child::Stmt(body))])
+                    .collect(),
+            ),
+            Stmt::While { cond, body } => (
+                "While",
+                vec![("cond", Child::Expr(cond)), ("body", Child::Stmt(body))],
+            ),
+            Stmt::DoWhile { body, cond } => (
+                "DoWhile",
+                vec![("body", Child::Stmt(body)), ("cond", Child::Expr(cond))],
+            ),
+            Stmt::Return(Some(value)) => {
+                ("Return", vec![("", Child::Expr(value))])
+            }
+            Stmt::Label(name, body) => {
+                let node = self.node_text(&format!("Label({name:?})"), "");
+                let body = self.stmt(body);
+                self.edge(node, body, "");
+                return node;
+            }
+            Stmt::Switch { cond, body } => (
+                "Switch",
+                vec![("cond", Child::Expr(cond)), ("body", Child::Stmt(body))],
+            ),
+            Stmt::Case(value, body) => (
+                "Case",
+                vec![("value", Child::Expr(value)), ("", Child::Stmt(body))],
+            ),
+            Stmt::Default(body) => ("Default", vec![("", Child::Stmt(body))]),
+        };
+        let node = self.node_text(kind, "");
+        for (field, child) in children {
+            let child = match child {
+                Child::Stmt(stmt) => self.stmt(stmt),
+                Child::Expr(expr) => self.expr(expr),
+            };
+            self.edge(node, child, field);
+        }
+        node
+    }
+
+    /// Add the tree of an expression.
+    fn expr(&mut self, expr: &Expr) -> usize {
+        let node = self.node(expr, "");
+        let children: Vec<(&str, &Spanned<Expr>)> = match expr {
+            Expr::Debug
+            | Expr::Ident(_)
+            | Expr::Lit(_)
+            | Expr::SizeofType(_)
+            | Expr::Alignof(_) => Vec::new(),
+            Expr::Ref(e)
+            | Expr::Una(_, e)
+            | Expr::Cast(_, e)
+            | Expr::Sizeof(e)
+            | Expr::Member(e, _)
+            | Expr::Arrow(e, _)
+            | Expr::PostInc(e)
+            | Expr::PostDec(e)
+            | Expr::VaArg(e, _)
+            | Expr::VaEnd(e) => vec![("", e)],
+            Expr::VaStart(ap, last) => vec![("", ap), ("last", last)],
+            Expr::VaCopy(dest, src) => vec![("dest", dest), ("src", src)],
+            Expr::Bin(_, lhs, rhs) => vec![("lhs", lhs), ("rhs", rhs)],
+            Expr::Index(e, index) => vec![("", e), ("index", index)],
+            Expr::Cond(cond, then, r#else) => {
+                vec![("cond", cond), ("then", then), ("else", r#else)]
+            }
+            Expr::Call(callee, args) => [("callee", &**callee)]
+                .into_iter()
+                .chain(args.iter().map(|arg| ("arg", arg)))
+                .collect(),
+            Expr::CompoundLit(_, items) => {
+                self.items(node, items);
+                Vec::new()
+            }
+        };
+        for (field, child) in children {
+            let child = self.expr(child);
+            self.edge(node, child, field);
+        }
+        node
+    }
+
+    /// Add the tree of an initializer.
+    fn init(&mut self, init: &Initializer) -> usize {
+        match init {
+            Initializer::Expr(expr) => self.expr(expr),
+            Initializer::List(items) => {
+                let node = self.node(init, "");
+                self.items(node, items);
+                node
+            }
+        }
+    }
+
+    /// Add the trees of the items of an initializer list, under its node.
+    fn items(&mut self, node: usize, items: &[InitItem]) {
+        for item in items {
+            let child = self.init(&item.init);
+            self.edge(node, child, "");
+        }
+    }
+}
+
+/// A child of a statement in its tree.
+enum Child<'a> {
+    /// A statement.
+    Stmt(&'a Stmt<'a>),
+
+    /// An expression.
+    Expr(&'a Expr<'a>),
+}
+
+/// Quote the `Debug` text of something as a label.
+fn quote(label: impl Debug) -> String {
+    quote_text(&format!("{label:?}"))
+}
+
+/// Quote a label, with its lines left-justified.
+fn quote_text(label: &str) -> String {
+    let mut out = String::from("\"");
+    for c in label.chars() {
+        match c {
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\l"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,568 @@
+//! Rebuilding the HIR.
+//!
+//! A [`Fold`] takes every node by value and returns the node to put in its
+//! place, which suits rewrites that replace nodes with others of a different
+//! kind, e.g. folding constant additions:
+//!
+//! ```
+//! use sea::hir::fold::{self, Fold};
+//! use sea::hir::{Arena, BinOp, Expr, Lit};
+//!
+//! /// Fold additions of integer constants.
+//! struct Constants<'hir>(&'hir Arena<'hir>);
+//!
+//! impl<'hir> Fold<'hir> for Constants<'hir> {
+//!     fn arena(&self) -> &'hir Arena<'hir> {
+//!         self.0
+//!     }
+//!
+//!     fn fold_expr(&mut self, expr: Expr<'hir>) -> Expr<'hir> {
+//!         match fold::walk_expr(self, expr) {
+//!             Expr::Bin(BinOp::Add, lhs, rhs) => {
+//!                 match (&lhs.node, &rhs.node) {
+//!                     (
+//!                         &Expr::Lit(Lit::Int {
+//!                             value: a,
+//!                             radix,
+//!                             unsigned,
+//!                             long,
+//!                         }),
+//!                         &Expr::Lit(Lit::Int { value: b, .. }),
+//!                     ) => Expr::Lit(Lit::Int {
+//!                         value: a + b,
+//!                         radix,
+//!                         unsigned,
+//!                         long,
+//!                     }),
+//!                     _ => Expr::Bin(BinOp::Add, lhs, rhs),
+//!                 }
+//!             }
+//!             expr => expr,
+//!         }
+//!     }
+//! }
+//!
+//! let arena = Arena::default();
+//! let source = "int f(void) { return 1 + 2 + 3; }";
+//! let program = sea::compile::parse(&arena, source).unwrap();
+//! let program = Constants(&arena).fold_program(program);
+//! assert!(program.to_string().contains("return 6;"));
+//! ```
+//!
+//! Every method has a default, which rebuilds the node from its children,
+//! folded with the `walk_*` function of the same name.  Folded statements,
+//! expressions and bindings keep their spans, even if they no longer match.
+//! Rebuilt children are allocated in the [`arena()`](Fold::arena), and
+//! folded types are found in it again.
+
+use super::{
+    Arena, Binding, Block, Decl, Designator, Expr, FnDefn, InitItem,
+    Initializer, Lit, MonoDecl, Program, Spanned, Stmt, TopDefn, Type,
+    TypeDefn, TypeName, VarDefn,
+};
+
+/// A rewrite of the HIR.
+pub trait Fold<'hir> {
+    /// The arena to allocate rebuilt nodes in.
+    fn arena(&self) -> &'hir Arena<'hir>;
+
+    /// Fold a program.
+    fn fold_program(&mut self, program: Program<'hir>) -> Program<'hir> {
+        walk_program(self, program)
+    }
+
+    /// Fold a top-level declaration or definition.
+    fn fold_top_defn(&mut self, defn: TopDefn<'hir>) -> TopDefn<'hir> {
+        walk_top_defn(self, defn)
+    }
+
+    /// Fold a function declaration or definition.
+    fn fold_fn_defn(&mut self, defn: FnDefn<'hir>) -> FnDefn<'hir> {
+        walk_fn_defn(self, defn)
+    }
+
+    /// Fold a variable declaration or definition.
+    fn fold_var_defn(&mut self, defn: VarDefn<'hir>) -> VarDefn<'hir> {
+        walk_var_defn(self, defn)
+    }
+
+    /// Fold a type definition.
+    fn fold_type_defn(&mut self, defn: TypeDefn<'hir>) -> TypeDefn<'hir> {
+        walk_type_defn(self, defn)
+    }
+
+    /// Fold a declaration.
+    fn fold_decl(&mut self, decl: Decl<'hir>) -> Decl<'hir> {
+        walk_decl(self, decl)
+    }
+
+    /// Fold a declaration with a single binding.
+    fn fold_mono_decl(&mut self, decl: MonoDecl<'hir>) -> MonoDecl<'hir> {
+        walk_mono_decl(self, decl)
+    }
+
+    /// Fold a type name.
+    fn fold_type_name(&mut self, name: TypeName<'hir>) -> TypeName<'hir> {
+        walk_type_name(self, name)
+    }
+
+    /// Fold a binding.
+    fn fold_binding(&mut self, binding: Binding<'hir>) -> Binding<'hir> {
+        walk_binding(self, binding)
+    }
+
+    /// Fold a type.
+    fn fold_type(&mut self, ty: Type<'hir>) -> Type<'hir> {
+        walk_type(self, ty)
+    }
+
+    /// Fold a block.
+    fn fold_block(&mut self, block: Block<'hir>) -> Block<'hir> {
+        walk_block(self, block)
+    }
+
+    /// Fold a statement.
+    fn fold_stmt(&mut self, stmt: Stmt<'hir>) -> Stmt<'hir> {
+        walk_stmt(self, stmt)
+    }
+
+    /// Fold an expression.
+    fn fold_expr(&mut self, expr: Expr<'hir>) -> Expr<'hir> {
+        walk_expr(self, expr)
+    }
+
+    /// Fold a literal.
+    fn fold_lit(&mut self, lit: Lit<'hir>) -> Lit<'hir> {
+        lit
+    }
+
+    /// Fold an initializer.
+    fn fold_initializer(
+        &mut self,
+        init: Initializer<'hir>,
+    ) -> Initializer<'hir> {
+        walk_initializer(self, init)
+    }
+
+    /// Fold an element of an initializer list.
+    fn fold_init_item(&mut self, item: InitItem<'hir>) -> InitItem<'hir> {
+        walk_init_item(self, item)
+    }
+
+    /// Fold a designator.
+    fn fold_designator(
+        &mut self,
+        designator: Designator<'hir>,
+    ) -> Designator<'hir> {
+        walk_designator(self, designator)
+    }
+}
+
+/// Fold the definitions of a program.
+///
+/// The spans of the definitions are kept, even if they no longer match.
+pub fn walk_program<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    program: Program<'hir>,
+) -> Program<'hir> {
+    Program {
+        decls: program
+            .decls
+            .into_iter()
+            .map(|defn| f.fold_top_defn(defn))
+            .collect(),
+        spans: program.spans,
+        names: program.names,
+    }
+}
+
+/// Fold the definition in a top-level definition.
+pub fn walk_top_defn<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    defn: TopDefn<'hir>,
+) -> TopDefn<'hir> {
+    match defn {
+        TopDefn::Fn(defn) => TopDefn::Fn(f.fold_fn_defn(defn)),
+        TopDefn::Var(defn) => TopDefn::Var(f.fold_var_defn(defn)),
+        TopDefn::Type(defn) => TopDefn::Type(f.fold_type_defn(defn)),
+    }
+}
+
+/// Fold the return type, parameters and body of a function.
+pub fn walk_fn_defn<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    defn: FnDefn<'hir>,
+) -> FnDefn<'hir> {
+    FnDefn {
+        return_type: fold_type_ref(f, defn.return_type),
+        params: fold_mono_decls(f, defn.params),
+        body: defn.body.map(|body| f.fold_block(body)),
+        ..defn
+    }
+}
+
+/// Fold the type, bindings and initializers of a variable declaration.
+pub fn walk_var_defn<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    defn: VarDefn<'hir>,
+) -> VarDefn<'hir> {
+    let base = fold_type_ref(f, defn.base);
+    let bindings = defn
+        .bindings
+        .iter()
+        .map(|&(binding, init)| {
+            let binding = binding.map(|b| f.fold_binding(b));
+            (binding, init.map(|init| f.fold_initializer(init)))
+        })
+        .collect();
+    VarDefn {
+        attrs: defn.attrs,
+        base,
+        bindings: f.arena().init_bindings(bindings),
+    }
+}
+
+/// Fold the type and bindings of a type definition.
+pub fn walk_type_defn<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    defn: TypeDefn<'hir>,
+) -> TypeDefn<'hir> {
+    TypeDefn {
+        attrs: defn.attrs,
+        base: fold_type_ref(f, defn.base),
+        bindings: fold_bindings(f, defn.bindings),
+    }
+}
+
+/// Fold the type and bindings of a declaration.
+pub fn walk_decl<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    decl: Decl<'hir>,
+) -> Decl<'hir> {
+    Decl {
+        attrs: decl.attrs,
+        base: fold_type_ref(f, decl.base),
+        bindings: fold_bindings(f, decl.bindings),
+    }
+}
+
+/// Fold the type and binding of a declaration.
+pub fn walk_mono_decl<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    decl: MonoDecl<'hir>,
+) -> MonoDecl<'hir> {
+    MonoDecl {
+        attrs: decl.attrs,
+        base: fold_type_ref(f, decl.base),
+        binding: decl.binding.map(|b| f.fold_binding(b)),
+    }
+}
+
+/// Fold the type and binding of a type name.
+pub fn walk_type_name<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    name: TypeName<'hir>,
+) -> TypeName<'hir> {
+    TypeName {
+        base: fold_type_ref(f, name.base),
+        binding: name.binding.map(|b| f.fold_binding(b)),
+    }
+}
+
+/// Fold the inner bindings, parameters and array sizes of a binding.
+pub fn walk_binding<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    binding: Binding<'hir>,
+) -> Binding<'hir> {
+    match binding {
+        Binding::Fn {
+            inner,
+            params,
+            variadic,
+        } => Binding::Fn {
+            inner: fold_binding_ref(f, inner),
+            params: fold_mono_decls(f, params),
+            variadic,
+        },
+        Binding::Array { inner, size } => Binding::Array {
+            inner: fold_binding_ref(f, inner),
+            size: size.map(|size| size.map(|e| f.fold_expr(e))),
+        },
+        Binding::Pointer(inner) => Binding::Pointer(fold_binding_ref(f, inner)),
+        Binding::Paren(inner) => Binding::Paren(fold_binding_ref(f, inner)),
+        Binding::Ident(_) | Binding::Anonymous => binding,
+    }
+}
+
+/// Fold the fields of a structure type.
+pub fn walk_type<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    ty: Type<'hir>,
+) -> Type<'hir> {
+    match ty {
+        Type::Struct {
+            attrs,
+            name,
+            fields,
+        } => Type::Struct {
+            attrs,
+            name,
+            fields: fields.map(|fields| fold_mono_decls(f, fields)),
+        },
+        ty => ty,
+    }
+}
+
+/// Fold the statements of a block.
+pub fn walk_block<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    block: Block<'hir>,
+) -> Block<'hir> {
+    Block {
+        stmts: fold_stmts(f, block.stmts),
+    }
+}
+
+/// Fold the statements, declarations and expressions in a statement.
+pub fn walk_stmt<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    stmt: Stmt<'hir>,
+) -> Stmt<'hir> {
+    match stmt {
+        Stmt::Empty | Stmt::Break | Stmt::Continue | Stmt::Goto(_) => stmt,
+        Stmt::Block { stmts } => Stmt::Block {
+            stmts: fold_stmts(f, stmts),
+        },
+        Stmt::VarDefn(defn) => Stmt::VarDefn(f.fold_var_defn(defn)),
+        Stmt::Type(defn) => Stmt::Type(f.fold_type_defn(defn)),
+        Stmt::Expr(expr) => Stmt::Expr(expr.map(|e| f.fold_expr(e))),
+        Stmt::Return(value) => {
+            Stmt::Return(value.map(|e| e.map(|e| f.fold_expr(e))))
+        }
+        Stmt::If { cond, then, r#else } => Stmt::If {
+            cond: cond.map(|e| f.fold_expr(e)),
+            then: fold_stmt_ref(f, then),
+            r#else: r#else.map(|e| fold_stmt_ref(f, e)),
+        },
+        Stmt::For {
+            init,
+            cond,
+            step,
+            body,
+        } => Stmt::For {
+            init: init.map(|e| e.map(|e| f.fold_expr(e))),
+            cond: cond.map(|e| e.map(|e| f.fold_expr(e))),
+            step: step.map(|e| e.map(|e| f.fold_expr(e))),
+            body: fold_stmt_ref(f, body),
+        },
+        Stmt::While { cond, body } => Stmt::While {
+            cond: cond.map(|e| f.fold_expr(e)),
+            body: fold_stmt_ref(f, body),
+        },
+        Stmt::DoWhile { body, cond } => Stmt::DoWhile {
+            body: fold_stmt_ref(f, body),
+            cond: cond.map(|e| f.fold_expr(e)),
+        },
+        Stmt::Label(name, stmt) => Stmt::Label(name, fold_stmt_ref(f, stmt)),
+        Stmt::Switch { cond, body } => Stmt::Switch {
+            cond: cond.map(|e| f.fold_expr(e)),
+            body: fold_stmt_ref(f, body),
+        },
+        Stmt::Case(value, stmt) => {
+            Stmt::Case(value.map(|e| f.fold_expr(e)), fold_stmt_ref(f, stmt))
+        }
+        Stmt::Default(stmt) => Stmt::Default(fold_stmt_ref(f, stmt)),
+    }
+}
+
+/// Fold the operands, literals and type names in an expression.
+pub fn walk_expr<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    expr: Expr<'hir>,
+) -> Expr<'hir> {
+    let mut fold = |e| fold_expr_ref(f, e);
+    match expr {
+        Expr::Debug | Expr::Ident(_) => expr,
+        Expr::Lit(lit) => Expr::Lit(f.fold_lit(lit)),
+        Expr::Ref(e) => Expr::Ref(fold(e)),
+        Expr::Bin(op, lhs, rhs) => {
+            let lhs = fold(lhs);
+            Expr::Bin(op, lhs, fold(rhs))
+        }
+        Expr::Una(op, e) => Expr::Una(op, fold(e)),
+        Expr::Cast(ty, e) => {
+            let ty = fold_type_name_ref(f, ty);
+            Expr::Cast(ty, fold_expr_ref(f, e))
+        }
+        Expr::Sizeof(e) => Expr::Sizeof(fold(e)),
+        Expr::SizeofType(ty) => Expr::SizeofType(fold_type_name_ref(f, ty)),
+        Expr::Alignof(ty) => Expr::Alignof(fold_type_name_ref(f, ty)),
+        Expr::CompoundLit(ty, items) => {
+            let ty = fold_type_name_ref(f, ty);
+            Expr::CompoundLit(ty, fold_init_items(f, items))
+        }
+        Expr::Call(callee, args) => {
+            let callee = fold_expr_ref(f, callee);
+            let args =
+                args.iter().map(|arg| arg.map(|e| f.fold_expr(e))).collect();
+            Expr::Call(callee, f.arena().exprs(args))
+        }
+        Expr::Member(e, name) => Expr::Member(fold(e), name),
+        Expr::Arrow(e, name) => Expr::Arrow(fold(e), name),
+        Expr::Index(e, index) => {
+            let e = fold(e);
+            Expr::Index(e, fold(index))
+        }
+        Expr::PostInc(e) => Expr::PostInc(fold(e)),
+        Expr::PostDec(e) => Expr::PostDec(fold(e)),
+        Expr::Cond(cond, then, r#else) => {
+            let cond = fold(cond);
+            let then = fold(then);
+            Expr::Cond(cond, then, fold(r#else))
+        }
+        Expr::VaStart(ap, last) => {
+            let ap = fold(ap);
+            Expr::VaStart(ap, fold(last))
+        }
+        Expr::VaArg(ap, ty) => {
+            let ap = fold(ap);
+            Expr::VaArg(ap, fold_type_name_ref(f, ty))
+        }
+        Expr::VaEnd(ap) => Expr::VaEnd(fold(ap)),
+        Expr::VaCopy(dest, src) => {
+            let dest = fold(dest);
+            Expr::VaCopy(dest, fold(src))
+        }
+    }
+}
+
+/// Fold the expression or elements of an initializer.
+pub fn walk_initializer<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    init: Initializer<'hir>,
+) -> Initializer<'hir> {
+    match init {
+        Initializer::Expr(expr) => {
+            Initializer::Expr(expr.map(|e| f.fold_expr(e)))
+        }
+        Initializer::List(items) => {
+            Initializer::List(fold_init_items(f, items))
+        }
+    }
+}
+
+/// Fold the designators and initializer of an element.
+pub fn walk_init_item<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    item: InitItem<'hir>,
+) -> InitItem<'hir> {
+    let designators = item
+        .designators
+        .iter()
+        .map(|&designator| f.fold_designator(designator))
+        .collect();
+    InitItem {
+        designators: f.arena().designators(designators),
+        init: f.fold_initializer(item.init),
+    }
+}
+
+/// Fold the index of a designator.
+pub fn walk_designator<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    designator: Designator<'hir>,
+) -> Designator<'hir> {
+    match designator {
+        Designator::Member(_) => designator,
+        Designator::Index(index) => {
+            Designator::Index(index.map(|e| f.fold_expr(e)))
+        }
+    }
+}
+
+/// Fold a type, and find it in the arena.
+fn fold_type_ref<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    ty: &'hir Type<'hir>,
+) -> &'hir Type<'hir> {
+    let ty = f.fold_type(*ty);
+    f.arena().ty(ty)
+}
+
+/// Fold a type name into the arena.
+fn fold_type_name_ref<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    name: &'hir TypeName<'hir>,
+) -> &'hir TypeName<'hir> {
+    let name = f.fold_type_name(*name);
+    f.arena().type_name(name)
+}
+
+/// Fold a statement into the arena.
+fn fold_stmt_ref<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    stmt: &'hir Spanned<Stmt<'hir>>,
+) -> &'hir Spanned<Stmt<'hir>> {
+    let stmt = stmt.map(|stmt| f.fold_stmt(stmt));
+    f.arena().stmt(stmt)
+}
+
+/// Fold an expression into the arena.
+fn fold_expr_ref<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    expr: &'hir Spanned<Expr<'hir>>,
+) -> &'hir Spanned<Expr<'hir>> {
+    let expr = expr.map(|expr| f.fold_expr(expr));
+    f.arena().expr(expr)
+}
+
+/// Fold a binding into the arena.
+fn fold_binding_ref<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    binding: &'hir Spanned<Binding<'hir>>,
+) -> &'hir Spanned<Binding<'hir>> {
+    let binding = binding.map(|binding| f.fold_binding(binding));
+    f.arena().binding(binding)
+}
+
+/// Fold a list of statements.
+fn fold_stmts<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    stmts: &'hir [Spanned<Stmt<'hir>>],
+) -> &'hir [Spanned<Stmt<'hir>>] {
+    let stmts = stmts
+        .iter()
+        .map(|stmt| stmt.map(|stmt| f.fold_stmt(stmt)))
+        .collect();
+    f.arena().stmts(stmts)
+}
+
+/// Fold a list of declarations.
+fn fold_mono_decls<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    decls: &'hir [MonoDecl<'hir>],
+) -> &'hir [MonoDecl<'hir>] {
+    let decls = decls.iter().map(|&decl| f.fold_mono_decl(decl)).collect();
+    f.arena().mono_decls(decls)
+}
+
+/// Fold a list of bindings.
+fn fold_bindings<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    bindings: &'hir [Spanned<Binding<'hir>>],
+) -> &'hir [Spanned<Binding<'hir>>] {
+    let bindings = bindings
+        .iter()
+        .map(|binding| binding.map(|binding| f.fold_binding(binding)))
+        .collect();
+    f.arena().bindings(bindings)
+}
+
+/// Fold the elements of an initializer list.
+fn fold_init_items<'hir, F: Fold<'hir> + ?Sized>(
+    f: &mut F,
+    items: &'hir [InitItem<'hir>],
+) -> &'hir [InitItem<'hir>] {
+    let items = items.iter().map(|&item| f.fold_init_item(item)).collect();
+    f.arena().init_items(items)
+}
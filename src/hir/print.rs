@@ -0,0 +1,1206 @@
+//! Printing the HIR back as C source.
+//!
+//! The [`Display`] impls here write out nodes as compilable C, such that
+//! parsing the output gives back the same HIR.  Declarators are written as
+//! they were parsed, parenthesized bindings included.  Expressions are
+//! written with the fewest parentheses the grammar allows, following the
+//! precedence levels of the parser.  Statements are indented a level each,
+//! with labels outdented by one.
+//!
+//! The layout follows a [`Style`]; [`Display`] uses the default one, and
+//! [`Program::styled()`] others.  Lines that would be too wide are broken
+//! greedily, before binary operators and after commas, with the rest of the
+//! statement indented one more level.
+//!
+//! Given the source a node was parsed from ([`Styled::with_source()`]),
+//! literals are written as they were spelled there, and comments are written
+//! out before the statement after them: on a line of their own, or at the
+//! end of the line before if they followed code on theirs.
+
+use std::fmt::{self, Display, Formatter, Write};
+
+use super::*;
+use crate::lexer::{self, LexerContext};
+use crate::parser::Parseable;
+
+//----------- Style ------------------------------------------------------------
+
+/// The layout of printed C.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Style {
+    /// The number of spaces per indentation level.
+    pub indent_width: usize,
+
+    /// Where opening braces go.
+    pub brace_style: BraceStyle,
+
+    /// Which side of the space the `*`s of a declarator go.
+    pub pointer_align: PointerAlign,
+
+    /// The width that lines are broken to fit in, where possible.
+    pub max_width: usize,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            brace_style: BraceStyle::SameLine,
+            pointer_align: PointerAlign::Right,
+            max_width: 80,
+        }
+    }
+}
+
+/// Where opening braces go.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BraceStyle {
+    /// At the end of the line that opens the block, as in `if (x) {`.
+    SameLine,
+
+    /// On a line of their own, as are `else` and `while` after a block.
+    NextLine,
+}
+
+/// Which side of the space the `*`s of a declarator go.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PointerAlign {
+    /// With the name, as in `char *p`.
+    Right,
+
+    /// With the type, as in `char* p`.
+    Left,
+}
+
+/// A node printed in a [`Style`].
+pub struct Styled<'a, T>(&'a T, &'a Style, Option<Source<'a>>);
+
+impl<'a, T> Styled<'a, T> {
+    /// Print the node against the source it was parsed from, along with
+    /// some comments within it.
+    pub fn with_source(self, text: &'a str, comments: &'a [Span]) -> Self {
+        Self(self.0, self.1, Some(Source { text, comments }))
+    }
+}
+
+/// The source that a node is printed against.
+#[derive(Copy, Clone)]
+struct Source<'a> {
+    /// The text of the source.
+    text: &'a str,
+
+    /// The comments left to write out, in order.
+    comments: &'a [Span],
+}
+
+impl Program<'_> {
+    /// Print the program in a style.
+    pub fn styled<'a>(&'a self, style: &'a Style) -> Styled<'a, Self> {
+        Styled(self, style, None)
+    }
+}
+
+impl TopDefn<'_> {
+    /// Print the definition in a style.
+    pub fn styled<'a>(&'a self, style: &'a Style) -> Styled<'a, Self> {
+        Styled(self, style, None)
+    }
+
+    /// Whether this is a function definition, with a body.
+    pub fn is_fn_defn(&self) -> bool {
+        matches!(self, Self::Fn(defn) if defn.body.is_some())
+    }
+}
+
+impl Display for Styled<'_, Program<'_>> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Function definitions are set apart by blank lines.
+        let decls = &self.0.decls;
+        self.0.names.enter(|| {
+            for (i, decl) in decls.iter().enumerate() {
+                if i != 0 && (decl.is_fn_defn() || decls[i - 1].is_fn_defn()) {
+                    writeln!(f)?;
+                }
+                writeln!(f, "{}", decl.styled(self.1))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Display for Styled<'_, TopDefn<'_>> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut p = Printer::new(self.1);
+        p.source = self.2;
+        match self.0 {
+            TopDefn::Fn(defn) => fn_defn(&mut p, defn)?,
+            TopDefn::Var(defn) => var_defn(&mut p, defn, 0)?,
+            TopDefn::Type(defn) => type_defn(&mut p, defn, 0)?,
+        }
+        // Comments outside of any block go after the definition.
+        if p.source.is_some_and(|source| !source.comments.is_empty()) {
+            writeln!(p)?;
+            comments(&mut p, usize::MAX, 0)?;
+            p.out.pop();
+        }
+        f.write_str(&p.out)
+    }
+}
+
+//----------- Printer ----------------------------------------------------------
+
+/// C source being printed.
+struct Printer<'a> {
+    /// The text printed so far.
+    out: String,
+
+    /// The style to print in.
+    style: &'a Style,
+
+    /// The indentation level of the current statement.
+    level: usize,
+
+    /// The source being printed against, if any.
+    source: Option<Source<'a>>,
+}
+
+impl<'a> Printer<'a> {
+    /// Construct a new [`Printer`].
+    fn new(style: &'a Style) -> Self {
+        Self {
+            out: String::new(),
+            style,
+            level: 0,
+            source: None,
+        }
+    }
+
+    /// The column that printing is at.
+    fn column(&self) -> usize {
+        let line = self.out.rfind('\n').map_or(0, |i| i + 1);
+        self.out[line..].chars().count()
+    }
+
+    /// Whether some text fits on the current line.
+    fn fits(&self, text: &str) -> bool {
+        self.column() + text.chars().count() <= self.style.max_width
+    }
+
+    /// Whether breaking the line would move printing to the left.
+    fn can_break(&self) -> bool {
+        self.column() > (self.level + 1) * self.style.indent_width
+    }
+
+    /// Break the line, continuing the current statement one level in.
+    fn break_line(&mut self) -> fmt::Result {
+        writeln!(self)?;
+        let width = (self.level + 1) * self.style.indent_width;
+        write!(self, "{:width$}", "")
+    }
+
+    /// Print something on a single line, to measure it.
+    fn flat(
+        &self,
+        print: impl FnOnce(&mut Printer<'_>) -> fmt::Result,
+    ) -> String {
+        let style = Style {
+            max_width: usize::MAX,
+            ..self.style.clone()
+        };
+        let mut p = Printer::new(&style);
+        p.source = self.source.map(|source| Source {
+            comments: &[],
+            ..source
+        });
+        // Writing to a string cannot fail.
+        let _ = print(&mut p);
+        p.out
+    }
+}
+
+impl Write for Printer<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.out.push_str(s);
+        Ok(())
+    }
+}
+
+/// Print something in a style.
+fn print(
+    f: &mut Formatter<'_>,
+    style: &Style,
+    print: impl FnOnce(&mut Printer<'_>) -> fmt::Result,
+) -> fmt::Result {
+    let mut p = Printer::new(style);
+    print(&mut p)?;
+    f.write_str(&p.out)
+}
+
+/// A node to print on its own, without a source to look up its span in.
+fn detached<T: Copy>(node: &T) -> Spanned<T> {
+    Spanned::new(*node, Span { start: 0, end: 0 })
+}
+
+/// Write out the space before some text, or break the line if the text
+/// would not fit otherwise.
+fn space(p: &mut Printer<'_>, text: &str) -> fmt::Result {
+    if p.fits(&format!(" {text}")) || !p.can_break() {
+        write!(p, " ")
+    } else {
+        p.break_line()
+    }
+}
+
+/// Write out the separator before an item of a comma-separated list.
+///
+/// The line is broken after the comma if the item would not fit otherwise.
+fn separator(p: &mut Printer<'_>, i: usize, item: &str) -> fmt::Result {
+    if i != 0 {
+        write!(p, ",")?;
+        space(p, item)
+    } else if p.fits(item) || !p.can_break() {
+        Ok(())
+    } else {
+        p.break_line()
+    }
+}
+
+/// Write out a binary operator, with its right operand.
+///
+/// The line is broken before the operator if the operand would not fit
+/// otherwise.
+fn operator(
+    p: &mut Printer<'_>,
+    op: &str,
+    rhs: &Spanned<Expr>,
+    prec: Prec,
+) -> fmt::Result {
+    let text = p.flat(|p| expr(p, rhs, prec));
+    space(p, &format!("{op} {text}"))?;
+    write!(p, "{op} ")?;
+    expr(p, rhs, prec)
+}
+
+//----------- Declarations -----------------------------------------------------
+
+impl Display for Program<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.styled(&Style::default()).fmt(f)
+    }
+}
+
+impl Display for TopDefn<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.styled(&Style::default()).fmt(f)
+    }
+}
+
+impl Display for FnDefn<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        print(f, &Style::default(), |p| fn_defn(p, self))
+    }
+}
+
+impl Display for VarDefn<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        print(f, &Style::default(), |p| var_defn(p, self, 0))
+    }
+}
+
+impl Display for TypeDefn<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        print(f, &Style::default(), |p| type_defn(p, self, 0))
+    }
+}
+
+impl Display for MonoDecl<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        print(f, &Style::default(), |p| mono_decl(p, self, 0))
+    }
+}
+
+impl Display for TypeName<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        print(f, &Style::default(), |p| type_name(p, self))
+    }
+}
+
+impl Display for Type<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        print(f, &Style::default(), |p| ty(p, self, 0))
+    }
+}
+
+impl Display for Binding<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        print(f, &Style::default(), |p| binding(p, self, 0))
+    }
+}
+
+/// Write out a function definition or declaration.
+fn fn_defn(p: &mut Printer<'_>, defn: &FnDefn) -> fmt::Result {
+    attrs(p, &defn.attrs)?;
+    ty(p, defn.return_type, 0)?;
+    write!(p, " {}", defn.name)?;
+    params(p, defn.params, defn.variadic, 0)?;
+    match &defn.body {
+        Some(body) => {
+            brace(p, 0)?;
+            block(p, body.stmts, usize::MAX, 0)
+        }
+        None => write!(p, ";"),
+    }
+}
+
+/// Write out the specifiers of a declaration, each followed by a space.
+fn attrs(p: &mut Printer<'_>, attrs: &Attrs) -> fmt::Result {
+    if let Some(storage) = attrs.storage {
+        write!(p, "{} ", storage.keyword())?;
+    }
+    if attrs.inline {
+        write!(p, "inline ")?;
+    }
+    Ok(())
+}
+
+/// Write out a variable definition, ending in a semicolon.
+fn var_defn(p: &mut Printer<'_>, defn: &VarDefn, level: usize) -> fmt::Result {
+    attrs(p, &defn.attrs)?;
+    ty(p, defn.base, level)?;
+    for (i, (b, init)) in defn.bindings.iter().enumerate() {
+        if i == 0 {
+            declarator(p, b, level)?;
+        } else {
+            let text = p.flat(|p| binding(p, b, level));
+            separator(p, i, &text)?;
+            binding(p, b, level)?;
+        }
+        if let Some(init) = init {
+            let text = p.flat(|p| initializer(p, init));
+            write!(p, " =")?;
+            space(p, &text)?;
+            initializer(p, init)?;
+        }
+    }
+    write!(p, ";")
+}
+
+/// Write out a type definition, ending in a semicolon.
+fn type_defn(
+    p: &mut Printer<'_>,
+    defn: &TypeDefn,
+    level: usize,
+) -> fmt::Result {
+    write!(p, "typedef ")?;
+    ty(p, defn.base, level)?;
+    for (i, b) in defn.bindings.iter().enumerate() {
+        if i == 0 {
+            declarator(p, b, level)?;
+        } else {
+            write!(p, ", ")?;
+            binding(p, b, level)?;
+        }
+    }
+    write!(p, ";")
+}
+
+/// Write out a declaration with a single binding.
+fn mono_decl(
+    p: &mut Printer<'_>,
+    decl: &MonoDecl,
+    level: usize,
+) -> fmt::Result {
+    ty(p, decl.base, level)?;
+    declarator(p, &decl.binding, level)
+}
+
+/// Write out a type name, as in a cast.
+fn type_name(p: &mut Printer<'_>, name: &TypeName) -> fmt::Result {
+    ty(p, name.base, p.level)?;
+    declarator(p, &name.binding, p.level)
+}
+
+/// Write out a parenthesized parameter list.
+fn params(
+    p: &mut Printer<'_>,
+    params: &[MonoDecl],
+    variadic: bool,
+    level: usize,
+) -> fmt::Result {
+    write!(p, "(")?;
+    for (i, param) in params.iter().enumerate() {
+        let text = p.flat(|p| mono_decl(p, param, level));
+        separator(p, i, &text)?;
+        mono_decl(p, param, level)?;
+    }
+    if variadic {
+        separator(p, params.len(), "...")?;
+        write!(p, "...")?;
+    }
+    write!(p, ")")
+}
+
+/// Write out a type, with struct fields indented past a level.
+fn ty(p: &mut Printer<'_>, ty: &Type, level: usize) -> fmt::Result {
+    let signed = |p: &mut Printer<'_>, name, signed: &Option<bool>| match signed
+    {
+        Some(true) => write!(p, "signed {name}"),
+        Some(false) => write!(p, "unsigned {name}"),
+        None => write!(p, "{name}"),
+    };
+    match ty {
+        Type::Void => write!(p, "void"),
+        Type::Char { signed: s } => signed(p, "char", s),
+        Type::Short { signed: s } => signed(p, "short", s),
+        Type::Int { signed: s } => signed(p, "int", s),
+        Type::Long { signed: s } => signed(p, "long", s),
+        Type::LongLong { signed: s } => signed(p, "long long", s),
+        Type::Float => write!(p, "float"),
+        Type::Double => write!(p, "double"),
+        Type::LongDouble => write!(p, "long double"),
+        Type::VaList => write!(p, "__builtin_va_list"),
+        Type::Struct { name, fields, .. } => {
+            write!(p, "struct")?;
+            if let Some(name) = name {
+                write!(p, " {name}")?;
+            }
+            let Some(fields) = fields else {
+                return Ok(());
+            };
+            // The fields are statements of their own, within the one the
+            // type is written in.
+            let outer = p.level;
+            brace(p, level)?;
+            writeln!(p, "{{")?;
+            for field in *fields {
+                indent(p, level + 1)?;
+                mono_decl(p, field, level + 1)?;
+                writeln!(p, ";")?;
+            }
+            indent(p, level)?;
+            write!(p, "}}")?;
+            p.level = outer;
+            Ok(())
+        }
+        Type::Ident(name) => write!(p, "{name}"),
+    }
+}
+
+/// Write out the binding of a declaration after its type.
+///
+/// The leading `*`s go with the type or with the rest of the binding, as the
+/// style asks.
+fn declarator(p: &mut Printer<'_>, b: &Binding, level: usize) -> fmt::Result {
+    let mut inner = b;
+    if p.style.pointer_align == PointerAlign::Left {
+        while let Binding::Pointer(b) = inner {
+            write!(p, "*")?;
+            inner = b;
+        }
+    }
+    if !matches!(inner, Binding::Anonymous) {
+        write!(p, " ")?;
+        binding(p, inner, level)?;
+    }
+    Ok(())
+}
+
+/// Write out a binding.
+///
+/// A pointer binding inside an array or function binding must be
+/// parenthesized; the parser keeps the parentheses as [`Binding::Paren`],
+/// but they are added if missing.
+fn binding(p: &mut Printer<'_>, b: &Binding, level: usize) -> fmt::Result {
+    // Write out the binding elaborated by a suffix.
+    let inner = |p: &mut Printer<'_>, inner: &Binding| {
+        if let Binding::Pointer(_) = inner {
+            write!(p, "(")?;
+            binding(p, inner, level)?;
+            write!(p, ")")
+        } else {
+            binding(p, inner, level)
+        }
+    };
+    match b {
+        Binding::Fn {
+            inner: b,
+            params: ps,
+            variadic,
+        } => {
+            inner(p, b)?;
+            params(p, ps, *variadic, level)
+        }
+        Binding::Array { inner: b, size } => {
+            inner(p, b)?;
+            write!(p, "[")?;
+            if let Some(size) = size {
+                expr(p, size, Prec::Cond)?;
+            }
+            write!(p, "]")
+        }
+        Binding::Pointer(b) => {
+            write!(p, "*")?;
+            binding(p, b, level)
+        }
+        Binding::Paren(b) => {
+            write!(p, "(")?;
+            binding(p, b, level)?;
+            write!(p, ")")
+        }
+        Binding::Ident(name) => write!(p, "{name}"),
+        Binding::Anonymous => Ok(()),
+    }
+}
+
+//----------- Statements -------------------------------------------------------
+
+impl Display for Block<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        print(f, &Style::default(), |p| block(p, self.stmts, 0, 0))
+    }
+}
+
+impl Display for Stmt<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        print(f, &Style::default(), |p| stmt(p, &detached(self), 0))
+    }
+}
+
+/// Write the indentation of a level, starting a statement at it.
+fn indent(p: &mut Printer<'_>, level: usize) -> fmt::Result {
+    p.level = level;
+    let width = level * p.style.indent_width;
+    write!(p, "{:width$}", "")
+}
+
+/// Write out the comments before an offset in the source, at the start of a
+/// line.
+///
+/// A comment that followed code on its line goes at the end of the last line
+/// written, and others on lines of their own, indented to a level.
+fn comments(p: &mut Printer<'_>, before: usize, level: usize) -> fmt::Result {
+    while let Some(source) = p.source
+        && let Some((comment, rest)) = source.comments.split_first()
+        && comment.start < before
+    {
+        p.source = Some(Source {
+            comments: rest,
+            ..source
+        });
+        let text = &source.text[comment.start..comment.end];
+        let line = source.text[..comment.start].rsplit('\n').next();
+        if line.is_some_and(|line| line.trim().is_empty()) {
+            indent(p, level)?;
+        } else {
+            p.out.pop();
+            write!(p, " ")?;
+        }
+        writeln!(p, "{text}")?;
+    }
+    Ok(())
+}
+
+/// Write out what goes between a line and the opening brace of a block at a
+/// level.
+fn brace(p: &mut Printer<'_>, level: usize) -> fmt::Result {
+    match p.style.brace_style {
+        BraceStyle::SameLine => write!(p, " "),
+        BraceStyle::NextLine => {
+            writeln!(p)?;
+            indent(p, level)
+        }
+    }
+}
+
+/// Write out a braced block, which ends at an offset in the source, and
+/// whose closing brace is at a level.
+fn block(
+    p: &mut Printer<'_>,
+    stmts: &[Spanned<Stmt>],
+    end: usize,
+    level: usize,
+) -> fmt::Result {
+    writeln!(p, "{{")?;
+    for s in stmts {
+        line(p, s, level + 1)?;
+        writeln!(p)?;
+    }
+    comments(p, end, level + 1)?;
+    indent(p, level)?;
+    write!(p, "}}")
+}
+
+/// Write out the body of a compound statement at a level.
+///
+/// A block body follows on the same line; other statements go on the next
+/// line, indented.
+fn body(
+    p: &mut Printer<'_>,
+    body: &Spanned<Stmt>,
+    level: usize,
+) -> fmt::Result {
+    if let Stmt::Block { stmts } = body.node {
+        brace(p, level)?;
+        block(p, stmts, body.span.end, level)
+    } else {
+        writeln!(p)?;
+        line(p, body, level + 1)
+    }
+}
+
+/// Write out what goes between the body of a compound statement at a level
+/// and a following `else` or `while`.
+fn after_body(p: &mut Printer<'_>, body: &Stmt, level: usize) -> fmt::Result {
+    if let Stmt::Block { .. } = body {
+        // The closing brace is placed like the opening one.
+        brace(p, level)
+    } else {
+        writeln!(p)?;
+        indent(p, level)
+    }
+}
+
+/// Write out a statement on lines of its own, indented to a level.
+///
+/// Labels are written one level out, and the statement they label on the
+/// next line.
+fn line(p: &mut Printer<'_>, s: &Spanned<Stmt>, level: usize) -> fmt::Result {
+    comments(p, s.span.start, level)?;
+    let label = |p: &mut Printer<'_>| indent(p, level.saturating_sub(1));
+    match &s.node {
+        Stmt::Label(name, s) => {
+            label(p)?;
+            writeln!(p, "{name}:")?;
+            line(p, s, level)
+        }
+        Stmt::Case(value, s) => {
+            label(p)?;
+            write!(p, "case ")?;
+            expr(p, value, Prec::Cond)?;
+            writeln!(p, ":")?;
+            line(p, s, level)
+        }
+        Stmt::Default(s) => {
+            label(p)?;
+            writeln!(p, "default:")?;
+            line(p, s, level)
+        }
+        _ => {
+            indent(p, level)?;
+            stmt(p, s, level)
+        }
+    }
+}
+
+/// Write out the parenthesized head of a statement, as in `while (x)`.
+fn head(
+    p: &mut Printer<'_>,
+    keyword: &str,
+    cond: &Spanned<Expr>,
+) -> fmt::Result {
+    write!(p, "{keyword} (")?;
+    expr(p, cond, Prec::Comma)?;
+    write!(p, ")")
+}
+
+/// Write out a statement, whose first line is already indented to a level.
+fn stmt(p: &mut Printer<'_>, s: &Spanned<Stmt>, level: usize) -> fmt::Result {
+    match &s.node {
+        Stmt::Empty => write!(p, ";"),
+        Stmt::Block { stmts } => block(p, stmts, s.span.end, level),
+        Stmt::VarDefn(defn) => var_defn(p, defn, level),
+        Stmt::Type(defn) => type_defn(p, defn, level),
+        Stmt::Expr(e) => {
+            expr(p, e, Prec::Comma)?;
+            write!(p, ";")
+        }
+        Stmt::If { cond, then, r#else } => {
+            head(p, "if", cond)?;
+            let Some(r#else) = r#else else {
+                return body(p, then, level);
+            };
+            if dangles(then) {
+                // Without braces, the `else` would be taken by an inner
+                // `if`.
+                brace(p, level)?;
+                writeln!(p, "{{")?;
+                line(p, then, level + 1)?;
+                writeln!(p)?;
+                indent(p, level)?;
+                write!(p, "}}")?;
+                brace(p, level)?;
+            } else {
+                body(p, then, level)?;
+                after_body(p, then, level)?;
+            }
+            write!(p, "else")?;
+            if let Stmt::If { .. } = r#else.node {
+                write!(p, " ")?;
+                stmt(p, r#else, level)
+            } else {
+                body(p, r#else, level)
+            }
+        }
+        Stmt::For {
+            init,
+            cond,
+            step,
+            body: b,
+        } => {
+            // Write out a clause after the first.
+            let clause = |p: &mut Printer<'_>, e: &Option<Spanned<Expr>>| {
+                let Some(e) = e else {
+                    return Ok(());
+                };
+                let text = p.flat(|p| expr(p, e, Prec::Comma));
+                space(p, &text)?;
+                expr(p, e, Prec::Comma)
+            };
+            write!(p, "for (")?;
+            if let Some(init) = init {
+                expr(p, init, Prec::Comma)?;
+            }
+            write!(p, ";")?;
+            clause(p, cond)?;
+            write!(p, ";")?;
+            clause(p, step)?;
+            write!(p, ")")?;
+            body(p, b, level)
+        }
+        Stmt::While { cond, body: b } => {
+            head(p, "while", cond)?;
+            body(p, b, level)
+        }
+        Stmt::DoWhile { body: b, cond } => {
+            write!(p, "do")?;
+            body(p, b, level)?;
+            after_body(p, b, level)?;
+            head(p, "while", cond)?;
+            write!(p, ";")
+        }
+        Stmt::Break => write!(p, "break;"),
+        Stmt::Continue => write!(p, "continue;"),
+        Stmt::Return(None) => write!(p, "return;"),
+        Stmt::Return(Some(value)) => {
+            write!(p, "return ")?;
+            expr(p, value, Prec::Comma)?;
+            write!(p, ";")
+        }
+        Stmt::Goto(label) => write!(p, "goto {label};"),
+        Stmt::Switch { cond, body: b } => {
+            head(p, "switch", cond)?;
+            body(p, b, level)
+        }
+        // Labels normally go through `line`, which outdents them.
+        Stmt::Label(name, s) => {
+            writeln!(p, "{name}:")?;
+            line(p, s, level)
+        }
+        Stmt::Case(value, s) => {
+            write!(p, "case ")?;
+            expr(p, value, Prec::Cond)?;
+            writeln!(p, ":")?;
+            line(p, s, level)
+        }
+        Stmt::Default(s) => {
+            writeln!(p, "default:")?;
+            line(p, s, level)
+        }
+    }
+}
+
+/// Whether a statement ends in an `if` without an `else`, which would take
+/// an `else` written after the statement.
+fn dangles(s: &Stmt) -> bool {
+    match s {
+        Stmt::If { r#else: None, .. } => true,
+        Stmt::If {
+            r#else: Some(s), ..
+        }
+        | Stmt::For { body: s, .. }
+        | Stmt::While { body: s, .. }
+        | Stmt::Switch { body: s, .. }
+        | Stmt::Label(_, s)
+        | Stmt::Case(_, s)
+        | Stmt::Default(s) => dangles(s),
+        _ => false,
+    }
+}
+
+//----------- Expressions ------------------------------------------------------
+
+/// A precedence level of the expression grammar, from loosest to tightest.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Prec {
+    Comma,
+    Assign,
+    Cond,
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Rel,
+    Shift,
+    Add,
+    Mul,
+    Cast,
+    Unary,
+    Postfix,
+    Primary,
+}
+
+impl Prec {
+    /// The level of a binary operator.
+    fn of_bin(op: BinOp) -> Self {
+        match op {
+            BinOp::Comma => Self::Comma,
+            BinOp::Or => Self::Or,
+            BinOp::And => Self::And,
+            BinOp::BitOr => Self::BitOr,
+            BinOp::BitXor => Self::BitXor,
+            BinOp::BitAnd => Self::BitAnd,
+            BinOp::Eq | BinOp::Ne => Self::Eq,
+            BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge => Self::Rel,
+            BinOp::Shl | BinOp::Shr => Self::Shift,
+            BinOp::Add | BinOp::Sub => Self::Add,
+            BinOp::Mul | BinOp::Div | BinOp::Rem => Self::Mul,
+            _ => Self::Assign,
+        }
+    }
+
+    /// The level of an expression.
+    fn of(e: &Expr) -> Self {
+        match e {
+            Expr::Bin(op, ..) => Self::of_bin(*op),
+            Expr::Cond(..) => Self::Cond,
+            Expr::Cast(..) => Self::Cast,
+            Expr::Ref(_)
+            | Expr::Una(..)
+            | Expr::Sizeof(_)
+            | Expr::SizeofType(_)
+            | Expr::Alignof(_) => Self::Unary,
+            Expr::Call(..)
+            | Expr::Member(..)
+            | Expr::Arrow(..)
+            | Expr::Index(..)
+            | Expr::PostInc(_)
+            | Expr::PostDec(_) => Self::Postfix,
+            Expr::VaStart(..)
+            | Expr::VaArg(..)
+            | Expr::VaEnd(_)
+            | Expr::VaCopy(..) => Self::Postfix,
+            Expr::Debug
+            | Expr::Ident(_)
+            | Expr::Lit(_)
+            | Expr::CompoundLit(..) => Self::Primary,
+        }
+    }
+
+    /// The next tighter level.
+    fn next(self) -> Self {
+        match self {
+            Self::Comma => Self::Assign,
+            Self::Assign => Self::Cond,
+            Self::Cond => Self::Or,
+            Self::Or => Self::And,
+            Self::And => Self::BitOr,
+            Self::BitOr => Self::BitXor,
+            Self::BitXor => Self::BitAnd,
+            Self::BitAnd => Self::Eq,
+            Self::Eq => Self::Rel,
+            Self::Rel => Self::Shift,
+            Self::Shift => Self::Add,
+            Self::Add => Self::Mul,
+            Self::Mul => Self::Cast,
+            Self::Cast => Self::Unary,
+            Self::Unary => Self::Postfix,
+            Self::Postfix | Self::Primary => Self::Primary,
+        }
+    }
+}
+
+impl Display for Expr<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        print(f, &Style::default(), |p| {
+            expr(p, &detached(self), Prec::Comma)
+        })
+    }
+}
+
+impl Display for Lit<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int {
+                value,
+                radix,
+                unsigned,
+                long,
+            } => {
+                match radix {
+                    Radix::Decimal => write!(f, "{value}")?,
+                    Radix::Octal => write!(f, "0{value:o}")?,
+                    Radix::Hex => write!(f, "0x{value:x}")?,
+                    Radix::Binary => write!(f, "0b{value:b}")?,
+                }
+                if *unsigned {
+                    write!(f, "u")?;
+                }
+                (0..*long).try_for_each(|_| write!(f, "l"))
+            }
+            Self::Float {
+                value,
+                single,
+                long,
+            } => {
+                // Literals are never negative, but may overflow to infinity.
+                if value.is_infinite() {
+                    write!(f, "1e999")?;
+                } else {
+                    write!(f, "{value:?}")?;
+                }
+                if *single {
+                    write!(f, "f")?;
+                }
+                if *long {
+                    write!(f, "l")?;
+                }
+                Ok(())
+            }
+            Self::Char(c) => {
+                write!(f, "'")?;
+                escape(f, *c, b'\'')?;
+                write!(f, "'")
+            }
+            Self::Str(s) => {
+                write!(f, "\"")?;
+                s.iter().try_for_each(|&c| escape(f, c, b'"'))?;
+                write!(f, "\"")
+            }
+        }
+    }
+}
+
+impl Display for Initializer<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        print(f, &Style::default(), |p| initializer(p, self))
+    }
+}
+
+/// The spelling of a literal in the source printed against, if any.
+///
+/// Folded literals keep the spans of the nodes they replace, so the text is
+/// only taken if it parses back to the same literal.
+fn spelling<'a>(p: &Printer<'a>, lit: &Lit, span: Span) -> Option<&'a str> {
+    let mut text = p.source?.text.get(span.start..span.end)?.trim();
+    // The span of a parenthesized literal includes the parentheses.
+    while let Some(inner) = text.strip_prefix('(') {
+        text = inner.strip_suffix(')')?.trim();
+    }
+    // Strings spread over several lines are written out on one.
+    if text.contains('\n') || !lexer::trivia(text).is_empty() {
+        return None;
+    }
+    let arena = Arena::default();
+    let mut ctx = LexerContext::new(text, &arena);
+    let parsed: Spanned<Expr> =
+        Spanned::<Expr>::parse(ctx.start()).ok()?.into();
+    (parsed.node == Expr::Lit(*lit) && parsed.span.end == text.len())
+        .then_some(text)
+}
+
+/// Write out a character of a literal delimited by a quote.
+fn escape(f: &mut Formatter<'_>, c: u8, quote: u8) -> fmt::Result {
+    match c {
+        b'\n' => write!(f, "\\n"),
+        b'\t' => write!(f, "\\t"),
+        b'\\' => write!(f, "\\\\"),
+        _ if c == quote => write!(f, "\\{}", c as char),
+        b' '..=b'~' => write!(f, "{}", c as char),
+        // Octal escapes take at most three digits, so a following digit is
+        // never taken as part of the escape.
+        _ => write!(f, "\\{c:03o}"),
+    }
+}
+
+/// Write out an expression, parenthesized if looser than a level.
+fn expr(p: &mut Printer<'_>, e: &Spanned<Expr>, min: Prec) -> fmt::Result {
+    if Prec::of(e) < min {
+        write!(p, "(")?;
+        expr(p, e, Prec::Comma)?;
+        return write!(p, ")");
+    }
+
+    match &e.node {
+        Expr::Debug => write!(p, "expr"),
+        Expr::Ident(name) => write!(p, "{name}"),
+        Expr::Lit(lit) => match spelling(p, lit, e.span) {
+            Some(text) => write!(p, "{text}"),
+            None => write!(p, "{lit}"),
+        },
+        Expr::Bin(BinOp::Comma, lhs, rhs) => {
+            expr(p, lhs, Prec::Comma)?;
+            let text = p.flat(|p| expr(p, rhs, Prec::Assign));
+            separator(p, 1, &text)?;
+            expr(p, rhs, Prec::Assign)
+        }
+        Expr::Bin(op, lhs, rhs) if op.is_assign() => {
+            expr(p, lhs, Prec::Unary)?;
+            operator(p, &format!("{op:?}"), rhs, Prec::Assign)
+        }
+        Expr::Bin(op, lhs, rhs) => {
+            let prec = Prec::of_bin(*op);
+            expr(p, lhs, prec)?;
+            operator(p, &format!("{op:?}"), rhs, prec.next())
+        }
+        Expr::Cond(cond, then, r#else) => {
+            expr(p, cond, Prec::Or)?;
+            operator(p, "?", then, Prec::Comma)?;
+            operator(p, ":", r#else, Prec::Cond)
+        }
+        Expr::Cast(name, operand) => {
+            write!(p, "(")?;
+            type_name(p, name)?;
+            write!(p, ")")?;
+            expr(p, operand, Prec::Cast)
+        }
+        Expr::Ref(operand) => prefix(p, "&", operand, Prec::Cast),
+        Expr::Una(op @ (UnaOp::PreInc | UnaOp::PreDec), operand) => {
+            prefix(p, &format!("{op:?}"), operand, Prec::Unary)
+        }
+        Expr::Una(op, operand) => {
+            prefix(p, &format!("{op:?}"), operand, Prec::Cast)
+        }
+        Expr::Sizeof(operand) => prefix(p, "sizeof ", operand, Prec::Unary),
+        Expr::SizeofType(name) => {
+            write!(p, "sizeof(")?;
+            type_name(p, name)?;
+            write!(p, ")")
+        }
+        Expr::Alignof(name) => {
+            write!(p, "_Alignof(")?;
+            type_name(p, name)?;
+            write!(p, ")")
+        }
+        Expr::CompoundLit(name, items) => {
+            write!(p, "(")?;
+            type_name(p, name)?;
+            write!(p, ")")?;
+            init_list(p, items)
+        }
+        Expr::Call(callee, args) => {
+            expr(p, callee, Prec::Postfix)?;
+            write!(p, "(")?;
+            for (i, arg) in args.iter().enumerate() {
+                let text = p.flat(|p| expr(p, arg, Prec::Assign));
+                separator(p, i, &text)?;
+                expr(p, arg, Prec::Assign)?;
+            }
+            write!(p, ")")
+        }
+        Expr::Member(operand, name) => {
+            expr(p, operand, Prec::Postfix)?;
+            write!(p, ".{name}")
+        }
+        Expr::Arrow(operand, name) => {
+            expr(p, operand, Prec::Postfix)?;
+            write!(p, "->{name}")
+        }
+        Expr::Index(operand, index) => {
+            expr(p, operand, Prec::Postfix)?;
+            write!(p, "[")?;
+            expr(p, index, Prec::Comma)?;
+            write!(p, "]")
+        }
+        Expr::PostInc(operand) => {
+            expr(p, operand, Prec::Postfix)?;
+            write!(p, "++")
+        }
+        Expr::PostDec(operand) => {
+            expr(p, operand, Prec::Postfix)?;
+            write!(p, "--")
+        }
+        Expr::VaStart(ap, last) => builtin(p, "va_start", &[ap, last]),
+        Expr::VaArg(ap, name) => {
+            write!(p, "__builtin_va_arg(")?;
+            expr(p, ap, Prec::Assign)?;
+            write!(p, ", ")?;
+            type_name(p, name)?;
+            write!(p, ")")
+        }
+        Expr::VaEnd(ap) => builtin(p, "va_end", &[ap]),
+        Expr::VaCopy(dest, src) => builtin(p, "va_copy", &[dest, src]),
+    }
+}
+
+/// Write out a call to a `__builtin_` function on expressions.
+fn builtin(
+    p: &mut Printer<'_>,
+    name: &str,
+    args: &[&Spanned<Expr>],
+) -> fmt::Result {
+    write!(p, "__builtin_{name}(")?;
+    for (i, arg) in args.iter().enumerate() {
+        let text = p.flat(|p| expr(p, arg, Prec::Assign));
+        separator(p, i, &text)?;
+        expr(p, arg, Prec::Assign)?;
+    }
+    write!(p, ")")
+}
+
+/// Write out a prefix operator and its operand.
+///
+/// A space is put between them if they would otherwise lex as a different
+/// punctuator, as in `- -x` or `& &x`.
+fn prefix(
+    p: &mut Printer<'_>,
+    op: &str,
+    operand: &Spanned<Expr>,
+    min: Prec,
+) -> fmt::Result {
+    let first = p.flat(|p| expr(p, operand, min)).chars().next();
+    let last = op.chars().last();
+    write!(p, "{op}")?;
+    if last == first && matches!(last, Some('+' | '-' | '&')) {
+        write!(p, " ")?;
+    }
+    expr(p, operand, min)
+}
+
+/// Write out an initializer.
+fn initializer(p: &mut Printer<'_>, init: &Initializer) -> fmt::Result {
+    match init {
+        Initializer::Expr(e) => expr(p, e, Prec::Assign),
+        Initializer::List(items) => init_list(p, items),
+    }
+}
+
+/// Write out a brace-enclosed initializer list.
+fn init_list(p: &mut Printer<'_>, items: &[InitItem]) -> fmt::Result {
+    write!(p, "{{")?;
+    for (i, item) in items.iter().enumerate() {
+        let text = p.flat(|p| init_item(p, item));
+        separator(p, i, &text)?;
+        init_item(p, item)?;
+    }
+    write!(p, "}}")
+}
+
+/// Write out an element of an initializer list.
+fn init_item(p: &mut Printer<'_>, item: &InitItem) -> fmt::Result {
+    for designator in item.designators {
+        match designator {
+            Designator::Member(name) => write!(p, ".{name}")?,
+            Designator::Index(index) => {
+                write!(p, "[")?;
+                expr(p, index, Prec::Cond)?;
+                write!(p, "]")?;
+            }
+        }
+    }
+    if !item.designators.is_empty() {
+        write!(p, " = ")?;
+    }
+    initializer(p, &item.init)
+}
@@ -0,0 +1,732 @@
+//! Machine-readable dumps of the HIR.
+//!
+//! Every node is converted to a generic [`Node`] tree, which is written out
+//! as JSON (`sea --emit=ast-json`) or as an S-expression (`sea
+//! --emit=ast-sexp`).  An HIR node becomes an object recording its kind (the
+//! name of its type), its variant for enums, and its fields by name; the
+//! fields of tuple variants are given names too.  Top-level definitions,
+//! statements, expressions and bindings carry their [`Span`] as a last
+//! field.
+//!
+//! In JSON, an object is written as `{"kind": "Expr", "variant": "Bin",
+//! "op": "Add", ...}`.  As an S-expression, it is written as `(Expr.Bin :op
+//! Add ...)`; lists are parenthesized, `nil` is a missing value, and `#t` and
+//! `#f` are booleans.  String literals hold bytes, which are written as the
+//! characters with the same code points.
+
+use std::fmt::Write;
+
+use super::*;
+
+//----------- Node -------------------------------------------------------------
+
+/// A serialized node.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    /// A missing value.
+    Null,
+
+    /// A boolean.
+    Bool(bool),
+
+    /// An integer.
+    Int(u64),
+
+    /// A floating-point number.
+    Float(f64),
+
+    /// A string.
+    Str(String),
+
+    /// The name of a fieldless variant, such as an operator.
+    Symbol(&'static str),
+
+    /// A list of nodes.
+    List(Vec<Node>),
+
+    /// An HIR node.
+    Object {
+        /// The name of the type of the node.
+        kind: &'static str,
+
+        /// The name of the variant of the node, for enums.
+        variant: Option<&'static str>,
+
+        /// The fields of the node, in order.
+        fields: Vec<(&'static str, Node)>,
+    },
+}
+
+impl Node {
+    /// An HIR node without fields yet.
+    fn object(kind: &'static str, variant: Option<&'static str>) -> Self {
+        Self::Object {
+            kind,
+            variant,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Add a field to an object.
+    fn field(
+        mut self,
+        name: &'static str,
+        value: &(impl Serialize + ?Sized),
+    ) -> Self {
+        if let Self::Object { fields, .. } = &mut self {
+            fields.push((name, value.serialize()));
+        }
+        self
+    }
+
+    /// Write out the node as JSON, indented by two spaces per level.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out, 0);
+        out.push('\n');
+        out
+    }
+
+    /// Write out the node as an S-expression, indented by two spaces per
+    /// level.
+    pub fn to_sexp(&self) -> String {
+        let mut out = String::new();
+        self.write_sexp(&mut out, 0);
+        out.push('\n');
+        out
+    }
+
+    /// Write out the node as JSON at an indentation level.
+    fn write_json(&self, out: &mut String, level: usize) {
+        let indent = |out: &mut String, level: usize| {
+            out.push('\n');
+            out.extend(std::iter::repeat_n("  ", level));
+        };
+        match self {
+            Self::Null => out.push_str("null"),
+            Self::Bool(value) => write!(out, "{value}").unwrap(),
+            Self::Int(value) => write!(out, "{value}").unwrap(),
+            // JSON has no infinities; a constant too large for a double is
+            // written as a string.
+            Self::Float(value) if !value.is_finite() => {
+                write!(out, "\"{value}\"").unwrap()
+            }
+            Self::Float(value) => write!(out, "{value:?}").unwrap(),
+            Self::Str(value) => json_string(out, value),
+            Self::Symbol(name) => json_string(out, name),
+            Self::List(items) if items.is_empty() => out.push_str("[]"),
+            Self::List(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    indent(out, level + 1);
+                    item.write_json(out, level + 1);
+                }
+                indent(out, level);
+                out.push(']');
+            }
+            Self::Object {
+                kind,
+                variant,
+                fields,
+            } => {
+                out.push('{');
+                indent(out, level + 1);
+                write!(out, "\"kind\": \"{kind}\"").unwrap();
+                if let Some(variant) = variant {
+                    out.push(',');
+                    indent(out, level + 1);
+                    write!(out, "\"variant\": \"{variant}\"").unwrap();
+                }
+                for (name, value) in fields {
+                    out.push(',');
+                    indent(out, level + 1);
+                    write!(out, "\"{name}\": ").unwrap();
+                    value.write_json(out, level + 1);
+                }
+                indent(out, level);
+                out.push('}');
+            }
+        }
+    }
+
+    /// Write out the node as an S-expression at an indentation level.
+    ///
+    /// A node nesting others is written with each field (or list item) on its
+    /// own line.
+    fn write_sexp(&self, out: &mut String, level: usize) {
+        let separate = |out: &mut String, multiline: bool| {
+            if multiline {
+                out.push('\n');
+                out.extend(std::iter::repeat_n("  ", level + 1));
+            } else {
+                out.push(' ');
+            }
+        };
+        match self {
+            Self::Null => out.push_str("nil"),
+            Self::Bool(true) => out.push_str("#t"),
+            Self::Bool(false) => out.push_str("#f"),
+            Self::Int(value) => write!(out, "{value}").unwrap(),
+            Self::Float(value) => write!(out, "{value:?}").unwrap(),
+            Self::Str(value) => sexp_string(out, value),
+            Self::Symbol(name) => out.push_str(name),
+            Self::List(items) => {
+                let multiline = items.iter().any(|item| !item.is_atom());
+                out.push('(');
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        separate(out, multiline);
+                    }
+                    item.write_sexp(out, level + 1);
+                }
+                out.push(')');
+            }
+            Self::Object {
+                kind,
+                variant,
+                fields,
+            } => {
+                let multiline =
+                    fields.iter().any(|(_, value)| !value.is_atom());
+                write!(out, "({kind}").unwrap();
+                if let Some(variant) = variant {
+                    write!(out, ".{variant}").unwrap();
+                }
+                for (name, value) in fields {
+                    separate(out, multiline);
+                    write!(out, ":{name} ").unwrap();
+                    value.write_sexp(out, level + 1);
+                }
+                out.push(')');
+            }
+        }
+    }
+
+    /// Whether the node holds no other nodes.
+    fn is_atom(&self) -> bool {
+        match self {
+            Self::List(items) => items.is_empty(),
+            Self::Object { fields, .. } => fields.is_empty(),
+            _ => true,
+        }
+    }
+}
+
+/// Write out a JSON string, escaping everything but printable ASCII.
+fn json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            ' '..='~' => out.push(c),
+            _ => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    write!(out, "\\u{unit:04x}").unwrap();
+                }
+            }
+        }
+    }
+    out.push('"');
+}
+
+/// Write out an S-expression string, escaping everything but printable
+/// ASCII.
+fn sexp_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            ' '..='~' => out.push(c),
+            _ => write!(out, "\\x{:x};", c as u32).unwrap(),
+        }
+    }
+    out.push('"');
+}
+
+//----------- Serialize --------------------------------------------------------
+
+/// A value that can be converted to a [`Node`].
+pub trait Serialize {
+    /// Convert the value to a node.
+    fn serialize(&self) -> Node;
+}
+
+impl Serialize for bool {
+    fn serialize(&self) -> Node {
+        Node::Bool(*self)
+    }
+}
+
+impl Serialize for u8 {
+    fn serialize(&self) -> Node {
+        Node::Int((*self).into())
+    }
+}
+
+impl Serialize for u64 {
+    fn serialize(&self) -> Node {
+        Node::Int(*self)
+    }
+}
+
+impl Serialize for usize {
+    fn serialize(&self) -> Node {
+        Node::Int(*self as u64)
+    }
+}
+
+impl Serialize for f64 {
+    fn serialize(&self) -> Node {
+        Node::Float(*self)
+    }
+}
+
+impl Serialize for String {
+    fn serialize(&self) -> Node {
+        Node::Str(self.clone())
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize(&self) -> Node {
+        Node::Str(self.to_string())
+    }
+}
+
+impl<T: Serialize + ?Sized> Serialize for &T {
+    fn serialize(&self) -> Node {
+        T::serialize(self)
+    }
+}
+
+impl<T: Serialize> Serialize for Spanned<T> {
+    fn serialize(&self) -> Node {
+        self.node.serialize().field("span", &self.span)
+    }
+}
+
+impl<T: Serialize> Serialize for Option<T> {
+    fn serialize(&self) -> Node {
+        self.as_ref().map_or(Node::Null, T::serialize)
+    }
+}
+
+impl<T: Serialize> Serialize for [T] {
+    fn serialize(&self) -> Node {
+        Node::List(self.iter().map(T::serialize).collect())
+    }
+}
+
+impl Serialize for Node {
+    fn serialize(&self) -> Node {
+        self.clone()
+    }
+}
+
+//--- Declarations
+
+impl Serialize for Program<'_> {
+    fn serialize(&self) -> Node {
+        let decls = self
+            .decls
+            .iter()
+            .zip(&self.spans)
+            .map(|(decl, span)| decl.serialize().field("span", span));
+        let decls = self.names.enter(|| decls.collect());
+        Node::object("Program", None).field("decls", &Node::List(decls))
+    }
+}
+
+impl Serialize for Span {
+    fn serialize(&self) -> Node {
+        Node::object("Span", None)
+            .field("start", &self.start)
+            .field("end", &self.end)
+    }
+}
+
+impl Serialize for TopDefn<'_> {
+    fn serialize(&self) -> Node {
+        match self {
+            Self::Fn(defn) => {
+                Node::object("TopDefn", Some("Fn")).field("defn", defn)
+            }
+            Self::Var(defn) => {
+                Node::object("TopDefn", Some("Var")).field("defn", defn)
+            }
+            Self::Type(defn) => {
+                Node::object("TopDefn", Some("Type")).field("defn", defn)
+            }
+        }
+    }
+}
+
+impl Serialize for FnDefn<'_> {
+    fn serialize(&self) -> Node {
+        Node::object("FnDefn", None)
+            .field("attrs", &self.attrs)
+            .field("return_type", &self.return_type)
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .field("variadic", &self.variadic)
+            .field("body", &self.body)
+    }
+}
+
+impl Serialize for VarDefn<'_> {
+    fn serialize(&self) -> Node {
+        // A binding with its initializer is an "init-declarator" in the C
+        // grammar.
+        let bindings = self.bindings.iter().map(|(binding, init)| {
+            Node::object("InitDeclarator", None)
+                .field("binding", binding)
+                .field("init", init)
+        });
+        Node::object("VarDefn", None)
+            .field("attrs", &self.attrs)
+            .field("base", &self.base)
+            .field("bindings", &Node::List(bindings.collect()))
+    }
+}
+
+impl Serialize for Attrs {
+    fn serialize(&self) -> Node {
+        Node::object("Attrs", None)
+            .field("storage", &self.storage)
+            .field("inline", &self.inline)
+    }
+}
+
+impl Serialize for Storage {
+    fn serialize(&self) -> Node {
+        Node::Symbol(match self {
+            Self::Static => "Static",
+            Self::Extern => "Extern",
+            Self::Auto => "Auto",
+            Self::Register => "Register",
+        })
+    }
+}
+
+impl Serialize for TypeDefn<'_> {
+    fn serialize(&self) -> Node {
+        Node::object("TypeDefn", None)
+            .field("base", &self.base)
+            .field("bindings", &self.bindings)
+    }
+}
+
+impl Serialize for Decl<'_> {
+    fn serialize(&self) -> Node {
+        Node::object("Decl", None)
+            .field("base", &self.base)
+            .field("bindings", &self.bindings)
+    }
+}
+
+impl Serialize for MonoDecl<'_> {
+    fn serialize(&self) -> Node {
+        Node::object("MonoDecl", None)
+            .field("base", &self.base)
+            .field("binding", &self.binding)
+    }
+}
+
+impl Serialize for TypeName<'_> {
+    fn serialize(&self) -> Node {
+        Node::object("TypeName", None)
+            .field("base", &self.base)
+            .field("binding", &self.binding)
+    }
+}
+
+impl Serialize for Binding<'_> {
+    fn serialize(&self) -> Node {
+        let node = |variant| Node::object("Binding", Some(variant));
+        match self {
+            Self::Fn {
+                inner,
+                params,
+                variadic,
+            } => node("Fn")
+                .field("inner", inner)
+                .field("params", params)
+                .field("variadic", variadic),
+            Self::Array { inner, size } => {
+                node("Array").field("inner", inner).field("size", size)
+            }
+            Self::Pointer(inner) => node("Pointer").field("inner", inner),
+            Self::Paren(inner) => node("Paren").field("inner", inner),
+            Self::Ident(name) => node("Ident").field("name", name),
+            Self::Anonymous => node("Anonymous"),
+        }
+    }
+}
+
+impl Serialize for Type<'_> {
+    fn serialize(&self) -> Node {
+        let node = |variant| Node::object("Type", Some(variant));
+        match self {
+            Self::Void => node("Void"),
+            Self::Char { signed } => node("Char").field("signed", signed),
+            Self::Short { signed } => node("Short").field("signed", signed),
+            Self::Int { signed } => node("Int").field("signed", signed),
+            Self::Long { signed } => node("Long").field("signed", signed),
+            Self::LongLong { signed } => {
+                node("LongLong").field("signed", signed)
+            }
+            Self::Float => node("Float"),
+            Self::Double => node("Double"),
+            Self::LongDouble => node("LongDouble"),
+            Self::VaList => node("VaList"),
+            Self::Struct { name, fields, .. } => {
+                node("Struct").field("name", name).field("fields", fields)
+            }
+            Self::Ident(name) => node("Ident").field("name", name),
+        }
+    }
+}
+
+//--- Statements
+
+impl Serialize for Block<'_> {
+    fn serialize(&self) -> Node {
+        Node::object("Block", None).field("stmts", &self.stmts)
+    }
+}
+
+impl Serialize for Stmt<'_> {
+    fn serialize(&self) -> Node {
+        let node = |variant| Node::object("Stmt", Some(variant));
+        match self {
+            Self::Empty => node("Empty"),
+            Self::Block { stmts } => node("Block").field("stmts", stmts),
+            Self::VarDefn(defn) => node("VarDefn").field("defn", defn),
+            Self::Type(defn) => node("Type").field("defn", defn),
+            Self::Expr(expr) => node("Expr").field("expr", expr),
+            Self::If { cond, then, r#else } => node("If")
+                .field("cond", cond)
+                .field("then", then)
+                .field("else", r#else),
+            Self::For {
+                init,
+                cond,
+                step,
+                body,
+            } => node("For")
+                .field("init", init)
+                .field("cond", cond)
+                .field("step", step)
+                .field("body", body),
+            Self::While { cond, body } => {
+                node("While").field("cond", cond).field("body", body)
+            }
+            Self::DoWhile { body, cond } => {
+                node("DoWhile").field("body", body).field("cond", cond)
+            }
+            Self::Break => node("Break"),
+            Self::Continue => node("Continue"),
+            Self::Return(value) => node("Return").field("value", value),
+            Self::Goto(label) => node("Goto").field("label", label),
+            Self::Label(label, stmt) => {
+                node("Label").field("label", label).field("stmt", stmt)
+            }
+            Self::Switch { cond, body } => {
+                node("Switch").field("cond", cond).field("body", body)
+            }
+            Self::Case(value, stmt) => {
+                node("Case").field("value", value).field("stmt", stmt)
+            }
+            Self::Default(stmt) => node("Default").field("stmt", stmt),
+        }
+    }
+}
+
+//--- Expressions
+
+impl Serialize for Expr<'_> {
+    fn serialize(&self) -> Node {
+        let node = |variant| Node::object("Expr", Some(variant));
+        match self {
+            Self::Debug => node("Debug"),
+            Self::Ref(expr) => node("Ref").field("expr", expr),
+            Self::Bin(op, lhs, rhs) => node("Bin")
+                .field("op", op)
+                .field("lhs", lhs)
+                .field("rhs", rhs),
+            Self::Una(op, expr) => {
+                node("Una").field("op", op).field("expr", expr)
+            }
+            Self::Ident(name) => node("Ident").field("name", name),
+            Self::Lit(lit) => node("Lit").field("lit", lit),
+            Self::Cast(ty, expr) => {
+                node("Cast").field("ty", ty).field("expr", expr)
+            }
+            Self::Sizeof(expr) => node("Sizeof").field("expr", expr),
+            Self::SizeofType(ty) => node("SizeofType").field("ty", ty),
+            Self::Alignof(ty) => node("Alignof").field("ty", ty),
+            Self::CompoundLit(ty, items) => {
+                node("CompoundLit").field("ty", ty).field("items", items)
+            }
+            Self::Call(callee, args) => {
+                node("Call").field("callee", callee).field("args", args)
+            }
+            Self::Member(expr, name) => {
+                node("Member").field("expr", expr).field("name", name)
+            }
+            Self::Arrow(expr, name) => {
+                node("Arrow").field("expr", expr).field("name", name)
+            }
+            Self::Index(expr, index) => {
+                node("Index").field("expr", expr).field("index", index)
+            }
+            Self::PostInc(expr) => node("PostInc").field("expr", expr),
+            Self::PostDec(expr) => node("PostDec").field("expr", expr),
+            Self::Cond(cond, then, r#else) => node("Cond")
+                .field("cond", cond)
+                .field("then", then)
+                .field("else", r#else),
+            Self::VaStart(ap, last) => {
+                node("VaStart").field("ap", ap).field("last", last)
+            }
+            Self::VaArg(ap, ty) => {
+                node("VaArg").field("ap", ap).field("ty", ty)
+            }
+            Self::VaEnd(ap) => node("VaEnd").field("ap", ap),
+            Self::VaCopy(dest, src) => {
+                node("VaCopy").field("dest", dest).field("src", src)
+            }
+        }
+    }
+}
+
+impl Serialize for Lit<'_> {
+    fn serialize(&self) -> Node {
+        let node = |variant| Node::object("Lit", Some(variant));
+        match self {
+            Self::Int {
+                value,
+                radix,
+                unsigned,
+                long,
+            } => node("Int")
+                .field("value", value)
+                .field("radix", radix)
+                .field("unsigned", unsigned)
+                .field("long", long),
+            Self::Float {
+                value,
+                single,
+                long,
+            } => node("Float")
+                .field("value", value)
+                .field("single", single)
+                .field("long", long),
+            Self::Char(value) => node("Char").field("value", value),
+            Self::Str(bytes) => {
+                let value: String = bytes.iter().map(|&b| b as char).collect();
+                node("Str").field("value", &value)
+            }
+        }
+    }
+}
+
+impl Serialize for Radix {
+    fn serialize(&self) -> Node {
+        Node::Symbol(match self {
+            Self::Decimal => "Decimal",
+            Self::Octal => "Octal",
+            Self::Hex => "Hex",
+            Self::Binary => "Binary",
+        })
+    }
+}
+
+impl Serialize for Initializer<'_> {
+    fn serialize(&self) -> Node {
+        let node = |variant| Node::object("Initializer", Some(variant));
+        match self {
+            Self::Expr(expr) => node("Expr").field("expr", expr),
+            Self::List(items) => node("List").field("items", items),
+        }
+    }
+}
+
+impl Serialize for InitItem<'_> {
+    fn serialize(&self) -> Node {
+        Node::object("InitItem", None)
+            .field("designators", &self.designators)
+            .field("init", &self.init)
+    }
+}
+
+impl Serialize for Designator<'_> {
+    fn serialize(&self) -> Node {
+        let node = |variant| Node::object("Designator", Some(variant));
+        match self {
+            Self::Member(name) => node("Member").field("name", name),
+            Self::Index(index) => node("Index").field("index", index),
+        }
+    }
+}
+
+impl Serialize for BinOp {
+    fn serialize(&self) -> Node {
+        Node::Symbol(match self {
+            Self::Add => "Add",
+            Self::Sub => "Sub",
+            Self::Mul => "Mul",
+            Self::Div => "Div",
+            Self::Rem => "Rem",
+            Self::Shl => "Shl",
+            Self::Shr => "Shr",
+            Self::BitAnd => "BitAnd",
+            Self::BitOr => "BitOr",
+            Self::BitXor => "BitXor",
+            Self::And => "And",
+            Self::Or => "Or",
+            Self::Eq => "Eq",
+            Self::Ne => "Ne",
+            Self::Lt => "Lt",
+            Self::Gt => "Gt",
+            Self::Le => "Le",
+            Self::Ge => "Ge",
+            Self::Assign => "Assign",
+            Self::AddAssign => "AddAssign",
+            Self::SubAssign => "SubAssign",
+            Self::MulAssign => "MulAssign",
+            Self::DivAssign => "DivAssign",
+            Self::RemAssign => "RemAssign",
+            Self::ShlAssign => "ShlAssign",
+            Self::ShrAssign => "ShrAssign",
+            Self::BitAndAssign => "BitAndAssign",
+            Self::BitOrAssign => "BitOrAssign",
+            Self::BitXorAssign => "BitXorAssign",
+            Self::Comma => "Comma",
+        })
+    }
+}
+
+impl Serialize for UnaOp {
+    fn serialize(&self) -> Node {
+        Node::Symbol(match self {
+            Self::Neg => "Neg",
+            Self::Not => "Not",
+            Self::Inv => "Inv",
+            Self::Plus => "Plus",
+            Self::Deref => "Deref",
+            Self::PreInc => "PreInc",
+            Self::PreDec => "PreDec",
+        })
+    }
+}
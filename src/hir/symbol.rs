@@ -0,0 +1,128 @@
+//! Interned identifiers.
+//!
+//! Every name in the HIR is a [`Symbol`]: a number standing for its text in
+//! the [`Names`] of its program, which keeps the text of each distinct name
+//! once.  Symbols are copied, compared and hashed as numbers, which makes
+//! them cheap keys for the tables of later stages.
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use sea::hir::symbol::Names;
+//!
+//! let mut names = Names::default();
+//! let main = names.intern("main");
+//! assert_eq!(main, names.intern("main"));
+//! assert_ne!(main, names.intern("exit"));
+//! assert_eq!(&names[main], "main");
+//!
+//! let names = Arc::new(names);
+//! names.enter(|| assert_eq!(main.to_string(), "main"));
+//! ```
+//!
+//! A symbol does not know its program, so it is printed with the names that
+//! the current thread has [entered](Names::enter): the stages that take a
+//! [`Program`](super::Program) enter its names, as does printing it.  Outside
+//! of them, a symbol is printed by its number, as `Symbol(0)`, so that parts
+//! of a program can still be printed.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Index;
+use std::sync::Arc;
+
+/// An interned identifier.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// Look up the text of the symbol in the names entered on this thread.
+    fn with_text<T>(self, f: impl FnOnce(Option<&str>) -> T) -> T {
+        SCOPES.with_borrow(|scopes| {
+            let names = scopes.last();
+            f(names
+                .and_then(|names| names.text.get(self.0 as usize))
+                .map(|text| &**text))
+        })
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.with_text(|text| match text {
+            Some(text) => f.write_str(text),
+            None => write!(f, "Symbol({})", self.0),
+        })
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.with_text(|text| match text {
+            Some(text) => fmt::Debug::fmt(text, f),
+            None => write!(f, "Symbol({})", self.0),
+        })
+    }
+}
+
+//----------- Names ------------------------------------------------------------
+
+/// The text of the symbols of a program.
+#[derive(Clone, Default)]
+pub struct Names {
+    /// The text of each symbol, by number.
+    text: Vec<Box<str>>,
+
+    /// The symbol for each text.
+    symbols: HashMap<Box<str>, Symbol>,
+}
+
+impl Names {
+    /// The symbol for a name, interning it if it is new.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(self.text.len() as u32);
+        self.text.push(name.into());
+        self.symbols.insert(name.into(), symbol);
+        symbol
+    }
+
+    /// The symbol for a name, if it has been interned.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Run a function with these names entered on the current thread, so
+    /// that it can print their symbols.
+    pub fn enter<T>(self: &Arc<Self>, f: impl FnOnce() -> T) -> T {
+        /// Leaves the names again, even if the function panics.
+        struct Leave;
+
+        impl Drop for Leave {
+            fn drop(&mut self) {
+                SCOPES.with_borrow_mut(|scopes| scopes.pop());
+            }
+        }
+
+        SCOPES.with_borrow_mut(|scopes| scopes.push(self.clone()));
+        let _leave = Leave;
+        f()
+    }
+}
+
+impl Index<Symbol> for Names {
+    type Output = str;
+
+    fn index(&self, symbol: Symbol) -> &str {
+        &self.text[symbol.0 as usize]
+    }
+}
+
+thread_local! {
+    /// The names entered on this thread, innermost last.
+    static SCOPES: RefCell<Vec<Arc<Names>>> =
+        const { RefCell::new(Vec::new()) };
+}
@@ -0,0 +1,383 @@
+//! Visiting the HIR by reference.
+//!
+//! A [`Visit`] has a method for every kind of node, which by default walks
+//! into the children of the node with the `walk_*` function of the same
+//! name.  Passes override the methods of the nodes they care about, calling
+//! the `walk_*` function themselves if they want to go deeper:
+//!
+//! ```
+//! use sea::hir::visit::{self, Visit};
+//! use sea::hir::{Arena, Expr};
+//!
+//! /// Count the calls in a program.
+//! struct Calls(usize);
+//!
+//! impl<'a> Visit<'a> for Calls {
+//!     fn visit_expr(&mut self, expr: &'a Expr) {
+//!         if let Expr::Call(..) = expr {
+//!             self.0 += 1;
+//!         }
+//!         visit::walk_expr(self, expr);
+//!     }
+//! }
+//!
+//! let arena = Arena::default();
+//! let source = "int f(void) { return f() + f(); }";
+//! let program = sea::compile::parse(&arena, source).unwrap();
+//! let mut calls = Calls(0);
+//! calls.visit_program(&program);
+//! assert_eq!(calls.0, 2);
+//! ```
+//!
+//! See [`super::visit_mut`] to change nodes in place, and [`super::fold`]
+//! to rebuild them.
+
+use super::{
+    Binding, Block, Decl, Designator, Expr, FnDefn, InitItem, Initializer, Lit,
+    MonoDecl, Program, Stmt, TopDefn, Type, TypeDefn, TypeName, VarDefn,
+};
+
+/// A pass over the HIR, by reference.
+pub trait Visit<'a> {
+    /// Visit a program.
+    fn visit_program(&mut self, program: &'a Program) {
+        walk_program(self, program)
+    }
+
+    /// Visit a top-level declaration or definition.
+    fn visit_top_defn(&mut self, defn: &'a TopDefn) {
+        walk_top_defn(self, defn)
+    }
+
+    /// Visit a function declaration or definition.
+    fn visit_fn_defn(&mut self, defn: &'a FnDefn) {
+        walk_fn_defn(self, defn)
+    }
+
+    /// Visit a variable declaration or definition.
+    fn visit_var_defn(&mut self, defn: &'a VarDefn) {
+        walk_var_defn(self, defn)
+    }
+
+    /// Visit a type definition.
+    fn visit_type_defn(&mut self, defn: &'a TypeDefn) {
+        walk_type_defn(self, defn)
+    }
+
+    /// Visit a declaration.
+    fn visit_decl(&mut self, decl: &'a Decl) {
+        walk_decl(self, decl)
+    }
+
+    /// Visit a declaration with a single binding.
+    fn visit_mono_decl(&mut self, decl: &'a MonoDecl) {
+        walk_mono_decl(self, decl)
+    }
+
+    /// Visit a type name.
+    fn visit_type_name(&mut self, name: &'a TypeName) {
+        walk_type_name(self, name)
+    }
+
+    /// Visit a binding.
+    fn visit_binding(&mut self, binding: &'a Binding) {
+        walk_binding(self, binding)
+    }
+
+    /// Visit a type.
+    fn visit_type(&mut self, ty: &'a Type) {
+        walk_type(self, ty)
+    }
+
+    /// Visit a block.
+    fn visit_block(&mut self, block: &'a Block) {
+        walk_block(self, block)
+    }
+
+    /// Visit a statement.
+    fn visit_stmt(&mut self, stmt: &'a Stmt) {
+        walk_stmt(self, stmt)
+    }
+
+    /// Visit an expression.
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        walk_expr(self, expr)
+    }
+
+    /// Visit a literal.
+    fn visit_lit(&mut self, _lit: &'a Lit) {}
+
+    /// Visit an initializer.
+    fn visit_initializer(&mut self, init: &'a Initializer) {
+        walk_initializer(self, init)
+    }
+
+    /// Visit an element of an initializer list.
+    fn visit_init_item(&mut self, item: &'a InitItem) {
+        walk_init_item(self, item)
+    }
+
+    /// Visit a designator.
+    fn visit_designator(&mut self, designator: &'a Designator) {
+        walk_designator(self, designator)
+    }
+}
+
+/// Visit the definitions of a program.
+pub fn walk_program<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    program: &'a Program,
+) {
+    for defn in &program.decls {
+        v.visit_top_defn(defn);
+    }
+}
+
+/// Visit the definition in a top-level definition.
+pub fn walk_top_defn<'a, V: Visit<'a> + ?Sized>(v: &mut V, defn: &'a TopDefn) {
+    match defn {
+        TopDefn::Fn(defn) => v.visit_fn_defn(defn),
+        TopDefn::Var(defn) => v.visit_var_defn(defn),
+        TopDefn::Type(defn) => v.visit_type_defn(defn),
+    }
+}
+
+/// Visit the return type, parameters and body of a function.
+pub fn walk_fn_defn<'a, V: Visit<'a> + ?Sized>(v: &mut V, defn: &'a FnDefn) {
+    v.visit_type(defn.return_type);
+    for param in defn.params {
+        v.visit_mono_decl(param);
+    }
+    if let Some(body) = &defn.body {
+        v.visit_block(body);
+    }
+}
+
+/// Visit the type, bindings and initializers of a variable declaration.
+pub fn walk_var_defn<'a, V: Visit<'a> + ?Sized>(v: &mut V, defn: &'a VarDefn) {
+    v.visit_type(defn.base);
+    for (binding, init) in defn.bindings {
+        v.visit_binding(binding);
+        if let Some(init) = init {
+            v.visit_initializer(init);
+        }
+    }
+}
+
+/// Visit the type and bindings of a type definition.
+pub fn walk_type_defn<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    defn: &'a TypeDefn,
+) {
+    v.visit_type(defn.base);
+    for binding in defn.bindings {
+        v.visit_binding(binding);
+    }
+}
+
+/// Visit the type and bindings of a declaration.
+pub fn walk_decl<'a, V: Visit<'a> + ?Sized>(v: &mut V, decl: &'a Decl) {
+    v.visit_type(decl.base);
+    for binding in decl.bindings {
+        v.visit_binding(binding);
+    }
+}
+
+/// Visit the type and binding of a declaration.
+pub fn walk_mono_decl<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    decl: &'a MonoDecl,
+) {
+    v.visit_type(decl.base);
+    v.visit_binding(&decl.binding);
+}
+
+/// Visit the type and binding of a type name.
+pub fn walk_type_name<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    name: &'a TypeName,
+) {
+    v.visit_type(name.base);
+    v.visit_binding(&name.binding);
+}
+
+/// Visit the inner bindings, parameters and array sizes of a binding.
+pub fn walk_binding<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    binding: &'a Binding,
+) {
+    match binding {
+        Binding::Fn { inner, params, .. } => {
+            v.visit_binding(inner);
+            for param in *params {
+                v.visit_mono_decl(param);
+            }
+        }
+        Binding::Array { inner, size } => {
+            v.visit_binding(inner);
+            if let Some(size) = size {
+                v.visit_expr(size);
+            }
+        }
+        Binding::Pointer(inner) | Binding::Paren(inner) => {
+            v.visit_binding(inner)
+        }
+        Binding::Ident(_) | Binding::Anonymous => {}
+    }
+}
+
+/// Visit the fields of a structure type.
+pub fn walk_type<'a, V: Visit<'a> + ?Sized>(v: &mut V, ty: &'a Type) {
+    if let Type::Struct {
+        fields: Some(fields),
+        ..
+    } = ty
+    {
+        for field in *fields {
+            v.visit_mono_decl(field);
+        }
+    }
+}
+
+/// Visit the statements of a block.
+pub fn walk_block<'a, V: Visit<'a> + ?Sized>(v: &mut V, block: &'a Block) {
+    for stmt in block.stmts {
+        v.visit_stmt(stmt);
+    }
+}
+
+/// Visit the statements, declarations and expressions in a statement.
+pub fn walk_stmt<'a, V: Visit<'a> + ?Sized>(v: &mut V, stmt: &'a Stmt) {
+    match stmt {
+        Stmt::Empty
+        | Stmt::Break
+        | Stmt::Continue
+        | Stmt::Goto(_)
+        | Stmt::Return(None) => {}
+        Stmt::Block { stmts } => {
+            for stmt in *stmts {
+                v.visit_stmt(stmt);
+            }
+        }
+        Stmt::VarDefn(defn) => v.visit_var_defn(defn),
+        Stmt::Type(defn) => v.visit_type_defn(defn),
+        Stmt::Expr(expr) | Stmt::Return(Some(expr)) => v.visit_expr(expr),
+        Stmt::If { cond, then, r#else } => {
+            v.visit_expr(cond);
+            v.visit_stmt(then);
+            if let Some(r#else) = r#else {
+                v.visit_stmt(r#else);
+            }
+        }
+        Stmt::For {
+            init,
+            cond,
+            step,
+            body,
+        } => {
+            for expr in [init, cond, step].into_iter().flatten() {
+                v.visit_expr(expr);
+            }
+            v.visit_stmt(body);
+        }
+        Stmt::While { cond, body } | Stmt::Switch { cond, body } => {
+            v.visit_expr(cond);
+            v.visit_stmt(body);
+        }
+        Stmt::DoWhile { body, cond } => {
+            v.visit_stmt(body);
+            v.visit_expr(cond);
+        }
+        Stmt::Case(value, stmt) => {
+            v.visit_expr(value);
+            v.visit_stmt(stmt);
+        }
+        Stmt::Label(_, stmt) | Stmt::Default(stmt) => v.visit_stmt(stmt),
+    }
+}
+
+/// Visit the operands, literals and type names in an expression.
+pub fn walk_expr<'a, V: Visit<'a> + ?Sized>(v: &mut V, expr: &'a Expr) {
+    match expr {
+        Expr::Debug | Expr::Ident(_) => {}
+        Expr::Lit(lit) => v.visit_lit(lit),
+        Expr::Ref(e)
+        | Expr::Una(_, e)
+        | Expr::Sizeof(e)
+        | Expr::Member(e, _)
+        | Expr::Arrow(e, _)
+        | Expr::PostInc(e)
+        | Expr::PostDec(e)
+        | Expr::VaEnd(e) => v.visit_expr(e),
+        Expr::Bin(_, lhs, rhs)
+        | Expr::Index(lhs, rhs)
+        | Expr::VaStart(lhs, rhs)
+        | Expr::VaCopy(lhs, rhs) => {
+            v.visit_expr(lhs);
+            v.visit_expr(rhs);
+        }
+        Expr::Cast(ty, e) => {
+            v.visit_type_name(ty);
+            v.visit_expr(e);
+        }
+        Expr::VaArg(e, ty) => {
+            v.visit_expr(e);
+            v.visit_type_name(ty);
+        }
+        Expr::SizeofType(ty) | Expr::Alignof(ty) => v.visit_type_name(ty),
+        Expr::CompoundLit(ty, items) => {
+            v.visit_type_name(ty);
+            for item in *items {
+                v.visit_init_item(item);
+            }
+        }
+        Expr::Call(callee, args) => {
+            v.visit_expr(callee);
+            for arg in *args {
+                v.visit_expr(arg);
+            }
+        }
+        Expr::Cond(cond, then, r#else) => {
+            v.visit_expr(cond);
+            v.visit_expr(then);
+            v.visit_expr(r#else);
+        }
+    }
+}
+
+/// Visit the expression or elements of an initializer.
+pub fn walk_initializer<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    init: &'a Initializer,
+) {
+    match init {
+        Initializer::Expr(expr) => v.visit_expr(expr),
+        Initializer::List(items) => {
+            for item in *items {
+                v.visit_init_item(item);
+            }
+        }
+    }
+}
+
+/// Visit the designators and initializer of an element.
+pub fn walk_init_item<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    item: &'a InitItem,
+) {
+    for designator in item.designators {
+        v.visit_designator(designator);
+    }
+    v.visit_initializer(&item.init);
+}
+
+/// Visit the index of a designator.
+pub fn walk_designator<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    designator: &'a Designator,
+) {
+    match designator {
+        Designator::Member(_) => {}
+        Designator::Index(index) => v.visit_expr(index),
+    }
+}
@@ -0,0 +1,491 @@
+//! Visiting the HIR by mutable reference.
+//!
+//! A [`VisitMut`] is a [`Visit`](super::visit::Visit) that can change the
+//! nodes it visits in place, e.g. to rename every use of a variable:
+//!
+//! ```
+//! use sea::hir::visit_mut::{self, VisitMut};
+//! use sea::hir::{Arena, Expr, Symbol};
+//!
+//! /// Rename a variable.
+//! struct Rename<'hir>(&'hir Arena<'hir>, Symbol, Symbol);
+//!
+//! impl<'hir> VisitMut<'hir> for Rename<'hir> {
+//!     fn arena(&self) -> &'hir Arena<'hir> {
+//!         self.0
+//!     }
+//!
+//!     fn visit_expr_mut(&mut self, expr: &mut Expr<'hir>) {
+//!         match expr {
+//!             Expr::Ident(name) if *name == self.1 => *name = self.2,
+//!             _ => visit_mut::walk_expr(self, expr),
+//!         }
+//!     }
+//! }
+//!
+//! let arena = Arena::default();
+//! let source = "int f(int x) { return x; }";
+//! let mut program = sea::compile::parse(&arena, source).unwrap();
+//! let (x, y) = (program.names.get("x").unwrap(), program.intern("y"));
+//! Rename(&arena, x, y).visit_program_mut(&mut program);
+//! assert!(program.to_string().contains("return y;"));
+//! ```
+//!
+//! Every method has a default, which walks into the children of the node
+//! with the `walk_*` function of the same name.
+//!
+//! Nodes in the [`Arena`] are shared, so a child behind a reference is not
+//! changed where it is: it is copied, and the copy is visited.  Only if the
+//! copy changed is the parent pointed at a new node allocated for it in the
+//! [`arena()`](VisitMut::arena), so that unchanged nodes keep their
+//! addresses.
+
+use super::{
+    Arena, Binding, Block, Decl, Designator, Expr, FnDefn, InitItem,
+    Initializer, Lit, MonoDecl, Program, Spanned, Stmt, TopDefn, Type,
+    TypeDefn, TypeName, VarDefn,
+};
+
+/// A pass over the HIR, changing it in place.
+pub trait VisitMut<'hir> {
+    /// The arena to allocate changed nodes in.
+    fn arena(&self) -> &'hir Arena<'hir>;
+
+    /// Visit a program.
+    fn visit_program_mut(&mut self, program: &mut Program<'hir>) {
+        walk_program(self, program)
+    }
+
+    /// Visit a top-level declaration or definition.
+    fn visit_top_defn_mut(&mut self, defn: &mut TopDefn<'hir>) {
+        walk_top_defn(self, defn)
+    }
+
+    /// Visit a function declaration or definition.
+    fn visit_fn_defn_mut(&mut self, defn: &mut FnDefn<'hir>) {
+        walk_fn_defn(self, defn)
+    }
+
+    /// Visit a variable declaration or definition.
+    fn visit_var_defn_mut(&mut self, defn: &mut VarDefn<'hir>) {
+        walk_var_defn(self, defn)
+    }
+
+    /// Visit a type definition.
+    fn visit_type_defn_mut(&mut self, defn: &mut TypeDefn<'hir>) {
+        walk_type_defn(self, defn)
+    }
+
+    /// Visit a declaration.
+    fn visit_decl_mut(&mut self, decl: &mut Decl<'hir>) {
+        walk_decl(self, decl)
+    }
+
+    /// Visit a declaration with a single binding.
+    fn visit_mono_decl_mut(&mut self, decl: &mut MonoDecl<'hir>) {
+        walk_mono_decl(self, decl)
+    }
+
+    /// Visit a type name.
+    fn visit_type_name_mut(&mut self, name: &mut TypeName<'hir>) {
+        walk_type_name(self, name)
+    }
+
+    /// Visit a binding.
+    fn visit_binding_mut(&mut self, binding: &mut Binding<'hir>) {
+        walk_binding(self, binding)
+    }
+
+    /// Visit a type.
+    fn visit_type_mut(&mut self, ty: &mut Type<'hir>) {
+        walk_type(self, ty)
+    }
+
+    /// Visit a block.
+    fn visit_block_mut(&mut self, block: &mut Block<'hir>) {
+        walk_block(self, block)
+    }
+
+    /// Visit a statement.
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt<'hir>) {
+        walk_stmt(self, stmt)
+    }
+
+    /// Visit an expression.
+    fn visit_expr_mut(&mut self, expr: &mut Expr<'hir>) {
+        walk_expr(self, expr)
+    }
+
+    /// Visit a literal.
+    fn visit_lit_mut(&mut self, _lit: &mut Lit<'hir>) {}
+
+    /// Visit an initializer.
+    fn visit_initializer_mut(&mut self, init: &mut Initializer<'hir>) {
+        walk_initializer(self, init)
+    }
+
+    /// Visit an element of an initializer list.
+    fn visit_init_item_mut(&mut self, item: &mut InitItem<'hir>) {
+        walk_init_item(self, item)
+    }
+
+    /// Visit a designator.
+    fn visit_designator_mut(&mut self, designator: &mut Designator<'hir>) {
+        walk_designator(self, designator)
+    }
+}
+
+/// Visit the definitions of a program.
+pub fn walk_program<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    program: &mut Program<'hir>,
+) {
+    for defn in &mut program.decls {
+        v.visit_top_defn_mut(defn);
+    }
+}
+
+/// Visit the definition in a top-level definition.
+pub fn walk_top_defn<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    defn: &mut TopDefn<'hir>,
+) {
+    match defn {
+        TopDefn::Fn(defn) => v.visit_fn_defn_mut(defn),
+        TopDefn::Var(defn) => v.visit_var_defn_mut(defn),
+        TopDefn::Type(defn) => v.visit_type_defn_mut(defn),
+    }
+}
+
+/// Visit the return type, parameters and body of a function.
+pub fn walk_fn_defn<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    defn: &mut FnDefn<'hir>,
+) {
+    visit_type(v, &mut defn.return_type);
+    visit_all(
+        v,
+        &mut defn.params,
+        V::visit_mono_decl_mut,
+        Arena::mono_decls,
+    );
+    if let Some(body) = &mut defn.body {
+        v.visit_block_mut(body);
+    }
+}
+
+/// Visit the type, bindings and initializers of a variable declaration.
+pub fn walk_var_defn<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    defn: &mut VarDefn<'hir>,
+) {
+    visit_type(v, &mut defn.base);
+    let visit = |v: &mut V, (binding, init): &mut (Spanned<_>, Option<_>)| {
+        v.visit_binding_mut(binding);
+        if let Some(init) = init {
+            v.visit_initializer_mut(init);
+        }
+    };
+    visit_all(v, &mut defn.bindings, visit, Arena::init_bindings);
+}
+
+/// Visit the type and bindings of a type definition.
+pub fn walk_type_defn<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    defn: &mut TypeDefn<'hir>,
+) {
+    visit_type(v, &mut defn.base);
+    visit_bindings(v, &mut defn.bindings);
+}
+
+/// Visit the type and bindings of a declaration.
+pub fn walk_decl<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    decl: &mut Decl<'hir>,
+) {
+    visit_type(v, &mut decl.base);
+    visit_bindings(v, &mut decl.bindings);
+}
+
+/// Visit the type and binding of a declaration.
+pub fn walk_mono_decl<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    decl: &mut MonoDecl<'hir>,
+) {
+    visit_type(v, &mut decl.base);
+    v.visit_binding_mut(&mut decl.binding);
+}
+
+/// Visit the type and binding of a type name.
+pub fn walk_type_name<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    name: &mut TypeName<'hir>,
+) {
+    visit_type(v, &mut name.base);
+    v.visit_binding_mut(&mut name.binding);
+}
+
+/// Visit the inner bindings, parameters and array sizes of a binding.
+pub fn walk_binding<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    binding: &mut Binding<'hir>,
+) {
+    match binding {
+        Binding::Fn { inner, params, .. } => {
+            visit_one(v, inner, |v, b| v.visit_binding_mut(b), Arena::binding);
+            visit_all(v, params, V::visit_mono_decl_mut, Arena::mono_decls);
+        }
+        Binding::Array { inner, size } => {
+            visit_one(v, inner, |v, b| v.visit_binding_mut(b), Arena::binding);
+            if let Some(size) = size {
+                v.visit_expr_mut(size);
+            }
+        }
+        Binding::Pointer(inner) | Binding::Paren(inner) => {
+            visit_one(v, inner, |v, b| v.visit_binding_mut(b), Arena::binding)
+        }
+        Binding::Ident(_) | Binding::Anonymous => {}
+    }
+}
+
+/// Visit the fields of a structure type.
+pub fn walk_type<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    ty: &mut Type<'hir>,
+) {
+    if let Type::Struct {
+        fields: Some(fields),
+        ..
+    } = ty
+    {
+        visit_all(v, fields, V::visit_mono_decl_mut, Arena::mono_decls);
+    }
+}
+
+/// Visit the statements of a block.
+pub fn walk_block<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    block: &mut Block<'hir>,
+) {
+    visit_stmts(v, &mut block.stmts);
+}
+
+/// Visit the statements, declarations and expressions in a statement.
+pub fn walk_stmt<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    stmt: &mut Stmt<'hir>,
+) {
+    match stmt {
+        Stmt::Empty
+        | Stmt::Break
+        | Stmt::Continue
+        | Stmt::Goto(_)
+        | Stmt::Return(None) => {}
+        Stmt::Block { stmts } => visit_stmts(v, stmts),
+        Stmt::VarDefn(defn) => v.visit_var_defn_mut(defn),
+        Stmt::Type(defn) => v.visit_type_defn_mut(defn),
+        Stmt::Expr(expr) | Stmt::Return(Some(expr)) => v.visit_expr_mut(expr),
+        Stmt::If { cond, then, r#else } => {
+            v.visit_expr_mut(cond);
+            visit_stmt(v, then);
+            if let Some(r#else) = r#else {
+                visit_stmt(v, r#else);
+            }
+        }
+        Stmt::For {
+            init,
+            cond,
+            step,
+            body,
+        } => {
+            for expr in [init, cond, step].into_iter().flatten() {
+                v.visit_expr_mut(expr);
+            }
+            visit_stmt(v, body);
+        }
+        Stmt::While { cond, body } | Stmt::Switch { cond, body } => {
+            v.visit_expr_mut(cond);
+            visit_stmt(v, body);
+        }
+        Stmt::DoWhile { body, cond } => {
+            visit_stmt(v, body);
+            v.visit_expr_mut(cond);
+        }
+        Stmt::Case(value, stmt) => {
+            v.visit_expr_mut(value);
+            visit_stmt(v, stmt);
+        }
+        Stmt::Label(_, stmt) | Stmt::Default(stmt) => visit_stmt(v, stmt),
+    }
+}
+
+/// Visit the operands, literals and type names in an expression.
+pub fn walk_expr<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    expr: &mut Expr<'hir>,
+) {
+    match expr {
+        Expr::Debug | Expr::Ident(_) => {}
+        Expr::Lit(lit) => v.visit_lit_mut(lit),
+        Expr::Ref(e)
+        | Expr::Una(_, e)
+        | Expr::Sizeof(e)
+        | Expr::Member(e, _)
+        | Expr::Arrow(e, _)
+        | Expr::PostInc(e)
+        | Expr::PostDec(e)
+        | Expr::VaEnd(e) => visit_expr(v, e),
+        Expr::Bin(_, lhs, rhs)
+        | Expr::Index(lhs, rhs)
+        | Expr::VaStart(lhs, rhs)
+        | Expr::VaCopy(lhs, rhs) => {
+            visit_expr(v, lhs);
+            visit_expr(v, rhs);
+        }
+        Expr::Cast(ty, e) => {
+            visit_type_name(v, ty);
+            visit_expr(v, e);
+        }
+        Expr::VaArg(e, ty) => {
+            visit_expr(v, e);
+            visit_type_name(v, ty);
+        }
+        Expr::SizeofType(ty) | Expr::Alignof(ty) => visit_type_name(v, ty),
+        Expr::CompoundLit(ty, items) => {
+            visit_type_name(v, ty);
+            visit_init_items(v, items);
+        }
+        Expr::Call(callee, args) => {
+            visit_expr(v, callee);
+            let visit = |v: &mut V, e: &mut Spanned<_>| v.visit_expr_mut(e);
+            visit_all(v, args, visit, Arena::exprs);
+        }
+        Expr::Cond(cond, then, r#else) => {
+            visit_expr(v, cond);
+            visit_expr(v, then);
+            visit_expr(v, r#else);
+        }
+    }
+}
+
+/// Visit the expression or elements of an initializer.
+pub fn walk_initializer<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    init: &mut Initializer<'hir>,
+) {
+    match init {
+        Initializer::Expr(expr) => v.visit_expr_mut(expr),
+        Initializer::List(items) => visit_init_items(v, items),
+    }
+}
+
+/// Visit the designators and initializer of an element.
+pub fn walk_init_item<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    item: &mut InitItem<'hir>,
+) {
+    let visit = V::visit_designator_mut;
+    visit_all(v, &mut item.designators, visit, Arena::designators);
+    v.visit_initializer_mut(&mut item.init);
+}
+
+/// Visit the index of a designator.
+pub fn walk_designator<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    designator: &mut Designator<'hir>,
+) {
+    match designator {
+        Designator::Member(_) => {}
+        Designator::Index(index) => v.visit_expr_mut(index),
+    }
+}
+
+//--- Copying
+
+/// Visit a copy of a node in the arena, and put a new node in its place if
+/// the copy changed.
+fn visit_one<'hir, V: VisitMut<'hir> + ?Sized, T: Copy + PartialEq>(
+    v: &mut V,
+    node: &mut &'hir T,
+    visit: impl FnOnce(&mut V, &mut T),
+    alloc: fn(&'hir Arena<'hir>, T) -> &'hir T,
+) {
+    let mut copy = **node;
+    visit(v, &mut copy);
+    if copy != **node {
+        *node = alloc(v.arena(), copy);
+    }
+}
+
+/// Visit copies of a list of nodes in the arena, and put a new list in its
+/// place if any of them changed.
+fn visit_all<'hir, V: VisitMut<'hir> + ?Sized, T: Copy + PartialEq>(
+    v: &mut V,
+    nodes: &mut &'hir [T],
+    mut visit: impl FnMut(&mut V, &mut T),
+    alloc: fn(&'hir Arena<'hir>, Vec<T>) -> &'hir [T],
+) {
+    let mut copy = nodes.to_vec();
+    for node in &mut copy {
+        visit(v, node);
+    }
+    if copy[..] != **nodes {
+        *nodes = alloc(v.arena(), copy);
+    }
+}
+
+/// Visit a type in the arena.
+fn visit_type<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    ty: &mut &'hir Type<'hir>,
+) {
+    visit_one(v, ty, V::visit_type_mut, Arena::ty);
+}
+
+/// Visit a type name in the arena.
+fn visit_type_name<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    name: &mut &'hir TypeName<'hir>,
+) {
+    visit_one(v, name, V::visit_type_name_mut, Arena::type_name);
+}
+
+/// Visit a statement in the arena.
+fn visit_stmt<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    stmt: &mut &'hir Spanned<Stmt<'hir>>,
+) {
+    visit_one(v, stmt, |v, s| v.visit_stmt_mut(s), Arena::stmt);
+}
+
+/// Visit an expression in the arena.
+fn visit_expr<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    expr: &mut &'hir Spanned<Expr<'hir>>,
+) {
+    visit_one(v, expr, |v, e| v.visit_expr_mut(e), Arena::expr);
+}
+
+/// Visit a list of statements in the arena.
+fn visit_stmts<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    stmts: &mut &'hir [Spanned<Stmt<'hir>>],
+) {
+    visit_all(v, stmts, |v, s| v.visit_stmt_mut(s), Arena::stmts);
+}
+
+/// Visit a list of bindings in the arena.
+fn visit_bindings<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    bindings: &mut &'hir [Spanned<Binding<'hir>>],
+) {
+    visit_all(v, bindings, |v, b| v.visit_binding_mut(b), Arena::bindings);
+}
+
+/// Visit the elements of an initializer list in the arena.
+fn visit_init_items<'hir, V: VisitMut<'hir> + ?Sized>(
+    v: &mut V,
+    items: &mut &'hir [InitItem<'hir>],
+) {
+    visit_all(v, items, V::visit_init_item_mut, Arena::init_items);
+}
@@ -0,0 +1,1784 @@
+//! A tree-walking interpreter for the HIR.
+//!
+//! `sea run` executes a program straight from its HIR, which makes it handy
+//! for trying out the front end without going through a backend.  Like
+//! [`lower`](crate::mir::lower), the interpreter walks the program with a
+//! [`Checker`] to resolve names and types as it goes.  Unlike it, the body of
+//! a function is walked whenever the function is called, so the block scopes
+//! of the checker are set aside for the duration of every call.
+//!
+//! Objects live in a [`Memory`], laid out as on the 64-bit compiled targets
+//! (see [`TypeTable::layout()`](crate::ctype::TypeTable::layout)), and pointers
+//! remember which object they point into.  Accessing memory outside of the
+//! live object a pointer was derived from stops the program with a
+//! [`RuntimeError`].
+//!
+//! Jumps are followed by leaving statements up to the block holding the
+//! target, then walking that block again, skipping statements until the
+//! target is found.  `switch` statements jump to their `case` labels the
+//! same way.
+//!
+//! With `--check-ub`, the interpreter also stops at the undefined behavior
+//! it can otherwise let slide, such as signed overflow; see [`ub`].  Errors
+//! are located at the innermost expression being evaluated.
+//!
+//! The few C library functions a program may call are provided by
+//! [`libc`].
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io::Write;
+use std::ptr;
+
+use crate::codegen::Target;
+use crate::ctype::{CType, FloatType, IntRank, IntType};
+use crate::hir::{
+    self, Binding, Designator, Expr, FnDefn, Initializer, Lit, Names, Program,
+    Span, Spanned, Stmt, Storage, Symbol, TopDefn, UnaOp, VarDefn,
+};
+use crate::stack;
+use crate::typeck::{BlockScopes, Checker, TypeError};
+
+mod libc;
+mod memory;
+mod ub;
+
+use memory::{Kind, Memory, ObjectId, Pointer};
+use ub::Access;
+
+/// Run a program, returning the exit status of `main()`.
+///
+/// The program must have been type checked.  `args` are passed to `main()`
+/// as `argv`, if it takes them, and the output of the program is written to
+/// `out`.  If `check_ub` is set, undefined behavior is reported as an error.
+pub fn run(
+    program: &Program,
+    args: &[String],
+    out: &mut (dyn Write + Send),
+    check_ub: bool,
+) -> Result<i32, RuntimeError> {
+    stack::with_stack(|| run_on_stack(program, args, out, check_ub))
+}
+
+/// Run a program, on a thread with room for deep recursion.
+fn run_on_stack(
+    program: &Program,
+    args: &[String],
+    out: &mut (dyn Write + Send),
+    check_ub: bool,
+) -> Result<i32, RuntimeError> {
+    let mut interp = Interpreter::new(&program.names, out, check_ub);
+    let result = program.names.enter(|| {
+        program
+            .decls
+            .iter()
+            .try_for_each(|decl| interp.top_defn(decl))
+            .and_then(|()| interp.start(args))
+    });
+    interp.out.flush().map_err(|err| RuntimeError {
+        function: None,
+        span: None,
+        message: err.to_string(),
+    })?;
+    match result {
+        Ok(status) | Err(Stop::Exit(status)) => Ok(status),
+        Err(Stop::Error(err)) => Err(err),
+    }
+}
+
+/// Unwrap the result of a check that already succeeded.
+fn checked<T>(result: Result<T, TypeError>) -> T {
+    result.expect("the program has been type checked")
+}
+
+//----------- Interpreter ------------------------------------------------------
+
+/// The state of a running program.
+struct Interpreter<'a> {
+    /// The checker tracking names and types in scope.
+    checker: Checker,
+
+    /// The objects of the program.
+    memory: Memory,
+
+    /// Where the output of the program goes.
+    out: &'a mut dyn Write,
+
+    /// The names of the program.
+    names: &'a Names,
+
+    /// The functions defined by the program, by name.
+    functions: HashMap<Symbol, &'a FnDefn<'a>>,
+
+    /// The objects of the file-scope symbols, by name.
+    globals: HashMap<Symbol, Pointer>,
+
+    /// The objects of the block-scope `static` variables, by the address of
+    /// their binding in the HIR.
+    statics: HashMap<*const Binding<'a>, Pointer>,
+
+    /// The objects holding string literals, by contents.
+    strings: HashMap<Vec<u8>, Pointer>,
+
+    /// The calls being executed, innermost last.
+    frames: Vec<Frame>,
+
+    /// The variadic arguments of the calls being executed, by the object
+    /// holding them.
+    varargs: HashMap<ObjectId, Vec<Scalar>>,
+
+    /// The target of the jump being made, while looking for it.
+    seek: Option<Seek<'a>>,
+
+    /// Whether undefined behavior is checked for.
+    check_ub: bool,
+}
+
+/// A function call being executed.
+struct Frame {
+    /// The name of the function.
+    name: Symbol,
+
+    /// The return type of the function.
+    ret: CType,
+
+    /// The block scopes of the function, innermost last.
+    scopes: Vec<Scope>,
+
+    /// The block scopes of the checker in the caller.
+    caller: BlockScopes,
+
+    /// The accesses made by the current full expression, with `--check-ub`.
+    accesses: Vec<Access>,
+
+    /// The object holding the variadic arguments, in a variadic function.
+    varargs: Option<Pointer>,
+}
+
+/// A block scope being executed.
+#[derive(Default)]
+struct Scope {
+    /// The objects the names declared in the scope refer to.
+    names: HashMap<Symbol, Pointer>,
+
+    /// The objects that live until the end of the scope.
+    objects: Vec<ObjectId>,
+}
+
+/// How the execution of a statement ended.
+enum Flow {
+    /// By completing normally.
+    Normal,
+
+    /// By a `break` statement.
+    Break,
+
+    /// By a `continue` statement.
+    Continue,
+
+    /// By a `return` statement, with the returned value.
+    Return(Option<Scalar>),
+
+    /// By a `goto` statement, with the target label.
+    Goto(Symbol),
+}
+
+/// The target of a jump.
+#[derive(Copy, Clone)]
+enum Seek<'a> {
+    /// A label, for `goto`.
+    Label(Symbol),
+
+    /// A `case` or `default` statement, for `switch`.
+    Case(&'a Stmt<'a>),
+}
+
+/// Why a program stopped before `main()` returned.
+enum Stop {
+    /// It called `exit()` with the given status.
+    Exit(i32),
+
+    /// It went wrong.
+    Error(RuntimeError),
+}
+
+impl Stop {
+    /// Locate an error at an expression, unless it already is.
+    fn at(self, expr: &Spanned<Expr>) -> Self {
+        match self {
+            Self::Error(mut err) if err.span.is_none() => {
+                err.span = Some(expr.span);
+                Self::Error(err)
+            }
+            stop => stop,
+        }
+    }
+}
+
+/// The result of executing part of a program.
+type Exec<T> = Result<T, Stop>;
+
+/// An evaluated expression.
+enum Value {
+    /// A scalar value.
+    Scalar(Scalar, CType),
+
+    /// An object in memory.
+    ///
+    /// Lvalues and aggregates are represented this way.
+    Place(Pointer, CType),
+
+    /// The result of a void expression.
+    Void,
+}
+
+/// A scalar value.
+///
+/// Integers are kept sign- or zero-extended from their type, following its
+/// signedness.  Aggregates are passed around as pointers to them.
+#[derive(Copy, Clone, Debug)]
+enum Scalar {
+    /// An integer.
+    Int(i64),
+
+    /// A floating-point number, rounded to its type.
+    Float(f64),
+
+    /// A pointer.
+    Ptr(Pointer),
+}
+
+impl Scalar {
+    /// The pointer held by a value of pointer type.
+    fn ptr(self) -> Pointer {
+        match self {
+            Self::Ptr(ptr) => ptr,
+            _ => unreachable!("{self:?} is not a pointer"),
+        }
+    }
+
+    /// Whether the value is non-zero.
+    fn truth(self) -> bool {
+        match self {
+            Self::Int(value) => value != 0,
+            Self::Float(value) => value != 0.0,
+            Self::Ptr(ptr) => ptr.addr != 0,
+        }
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    /// Construct a new [`Interpreter`] writing to `out`.
+    fn new(names: &'a Names, out: &'a mut dyn Write, check_ub: bool) -> Self {
+        Self {
+            checker: Checker::new(Target::X86_64),
+            memory: Memory::default(),
+            out,
+            names,
+            functions: HashMap::new(),
+            globals: HashMap::new(),
+            statics: HashMap::new(),
+            strings: HashMap::new(),
+            frames: Vec::new(),
+            varargs: HashMap::new(),
+            seek: None,
+            check_ub,
+        }
+    }
+
+    /// The error for something going wrong at the current point.
+    fn error(&self, message: impl Into<String>) -> Stop {
+        Stop::Error(RuntimeError {
+            function: self.frames.last().map(|frame| frame.name.to_string()),
+            span: None,
+            message: message.into(),
+        })
+    }
+
+    /// Report a failed memory access.
+    fn fault<T>(&self, result: Result<T, String>) -> Exec<T> {
+        result.map_err(|message| self.error(message))
+    }
+
+    /// Call `main()`, returning its result.
+    fn start(&mut self, args: &[String]) -> Exec<i32> {
+        let main = self.names.get("main");
+        let Some(main) = main.and_then(|main| self.functions.get(&main)) else {
+            return Err(self.error("undefined reference to `main`"));
+        };
+        let mut operands = Vec::new();
+        if main.params.len() >= 2 {
+            let size = (args.len() + 1) * 8;
+            let argv = self.memory.alloc(vec![0; size], Kind::Static, None);
+            for (i, arg) in args.iter().enumerate() {
+                let mut bytes = arg.as_bytes().to_vec();
+                bytes.push(0);
+                let string = self.memory.alloc(bytes, Kind::Static, None);
+                let elem = argv.offset(i as i64 * 8);
+                let result = self.memory.write_ptr(elem, string);
+                self.fault(result)?;
+            }
+            operands.push(Scalar::Int(args.len() as i64));
+            operands.push(Scalar::Ptr(argv));
+        }
+        match self.call_function(main, operands)? {
+            Value::Scalar(Scalar::Int(status), _) => Ok(status as i32),
+            _ => Ok(0),
+        }
+    }
+}
+
+//--- Top-level definitions
+
+impl<'a> Interpreter<'a> {
+    /// Execute a top-level definition.
+    fn top_defn(&mut self, decl: &'a TopDefn<'a>) -> Exec<()> {
+        match decl {
+            TopDefn::Fn(defn) => self.fn_defn(defn),
+            TopDefn::Var(defn) => self.global_var_defn(defn)?,
+            TopDefn::Type(defn) => checked(self.checker.type_defn(defn)),
+        }
+        Ok(())
+    }
+
+    /// Declare a function, and define it if it has a body.
+    fn fn_defn(&mut self, defn: &'a FnDefn<'a>) {
+        let fn_type = checked(self.checker.fn_signature(defn));
+        let ty = CType::Fn(Box::new(fn_type));
+        checked(self.checker.declare(defn.name, ty.clone()));
+        self.symbol(defn.name, &ty);
+        if defn.body.is_some() {
+            self.functions.insert(defn.name, defn);
+        }
+    }
+
+    /// Define the file-scope variables of a declaration.
+    fn global_var_defn(&mut self, defn: &'a VarDefn<'a>) -> Exec<()> {
+        let base = checked(self.checker.resolve_base(defn.base));
+        for (binding, init) in defn.bindings {
+            let ty = checked(self.checker.var_binding(
+                &base,
+                binding,
+                init.as_ref(),
+            ));
+            let Some(name) = binding.name() else {
+                continue;
+            };
+            let ptr = self.symbol(name, &ty);
+            if let Some(init) = init {
+                self.init(ptr, &ty, init, true)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The object of a file-scope symbol, created on its first declaration.
+    ///
+    /// A variable is created again if a later declaration gives it another
+    /// size, such as the length of an array.
+    fn symbol(&mut self, name: Symbol, ty: &CType) -> Pointer {
+        let size = self.checker.types.size_of(ty) as usize;
+        if let Some(&ptr) = self.globals.get(&name) {
+            let object = self.memory.get(ptr.object.unwrap());
+            if matches!(object.kind, Kind::Function(_))
+                || object.bytes.len() == size
+            {
+                return ptr;
+            }
+        }
+        let ptr = match ty {
+            CType::Fn(_) => {
+                let kind = Kind::Function(name.to_string());
+                self.memory.alloc(Vec::new(), kind, None)
+            }
+            _ => self.object(ty, Kind::Static),
+        };
+        self.globals.insert(name, ptr);
+        ptr
+    }
+
+    /// Get the object holding a string literal.
+    fn string(&mut self, bytes: &[u8]) -> Pointer {
+        if let Some(&ptr) = self.strings.get(bytes) {
+            return ptr;
+        }
+        let mut contents = bytes.to_vec();
+        contents.push(0);
+        let ty = CType::Array {
+            elem: Box::new(CType::CHAR),
+            len: Some(contents.len() as u64),
+        };
+        let ptr = self.memory.alloc(contents, Kind::Str, Some(ty));
+        self.strings.insert(bytes.to_vec(), ptr);
+        ptr
+    }
+}
+
+//--- Calls
+
+impl<'a> Interpreter<'a> {
+    /// Call a function defined by the program with converted arguments.
+    fn call_function(
+        &mut self,
+        defn: &'a FnDefn<'a>,
+        mut args: Vec<Scalar>,
+    ) -> Exec<Value> {
+        let ty = self.checker.lookup(defn.name).and_then(CType::fn_type);
+        let fn_type = ty.unwrap().clone();
+        // Only calls without a prototype can pass the wrong arguments.
+        let arity = fn_type.params.len();
+        if args.len() < arity || (args.len() > arity && !fn_type.variadic) {
+            return Err(self.error(format!(
+                "`{}` takes {arity} arguments but {} were passed",
+                defn.name,
+                args.len()
+            )));
+        }
+        let varargs = if fn_type.variadic {
+            Some(self.store_varargs(args.split_off(arity))?)
+        } else {
+            None
+        };
+        let caller = self.checker.take_scopes();
+        self.frames.push(Frame {
+            name: defn.name,
+            ret: fn_type.ret.clone(),
+            scopes: Vec::new(),
+            caller,
+            accesses: Vec::new(),
+            varargs,
+        });
+        self.push_scope();
+        for ((param, ty), arg) in
+            defn.params.iter().zip(&fn_type.params).zip(args)
+        {
+            let ptr = self.temp(ty);
+            self.assign_to(ptr, ty, arg)?;
+            if let Some(name) = param.binding.name() {
+                self.bind(name, ty, ptr);
+            }
+        }
+        let body = defn.body.as_ref().unwrap();
+        let value = match self.stmts(body.stmts)? {
+            Flow::Return(Some(value)) => value,
+            // Falling off the end of `main` returns 0; for other functions,
+            // the result is unspecified.
+            _ => self.zero(&fn_type.ret),
+        };
+
+        // An aggregate result outlives the callee.
+        let ret = fn_type.ret;
+        let result = if Self::is_aggregate(&ret) {
+            let size = self.checker.types.size_of(&ret);
+            let ptr = self.object(&ret, Kind::Auto);
+            let result = self.memory.copy(ptr, value.ptr(), size);
+            self.fault(result)?;
+            Some(ptr)
+        } else {
+            None
+        };
+        self.pop_scope();
+        let frame = self.frames.pop().unwrap();
+        self.checker.restore_scopes(frame.caller);
+        if let Some(id) = frame.varargs.and_then(|ptr| ptr.object) {
+            self.memory.kill(id);
+            self.varargs.remove(&id);
+        }
+
+        Ok(match result {
+            Some(ptr) => {
+                if let Some(frame) = self.frames.last_mut() {
+                    let scope = frame.scopes.last_mut().unwrap();
+                    scope.objects.push(ptr.object.unwrap());
+                }
+                Value::Place(ptr, ret)
+            }
+            None if ret == CType::Void => Value::Void,
+            None => Value::Scalar(value, ret),
+        })
+    }
+
+    /// Evaluate a call.
+    fn call(
+        &mut self,
+        callee: &Spanned<Expr>,
+        args: &[Spanned<Expr>],
+    ) -> Exec<Value> {
+        let mut marks = vec![self.mark()];
+        let (callee, ty) = self.rvalue(callee)?;
+        marks.push(self.mark());
+        let fn_type = ty.fn_type().unwrap().clone();
+        let mut operands = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            let (value, from) = self.rvalue(arg)?;
+            marks.push(self.mark());
+            let to = match fn_type.params.get(i) {
+                Some(param) => param.clone(),
+                // The default argument promotions.
+                None => match from {
+                    CType::Float(FloatType::Float) => CType::DOUBLE,
+                    ref from => from.clone().promote(),
+                },
+            };
+            operands.push(self.convert(value, &from, &to));
+        }
+        self.unsequenced(&marks)?;
+        self.complete(marks[0]);
+
+        let result = self.memory.function(callee.ptr()).map(str::to_string);
+        let name = self.fault(result)?;
+        let symbol = self.names.get(&name);
+        if let Some(&defn) = symbol.and_then(|name| self.functions.get(&name)) {
+            return self.call_function(defn, operands);
+        }
+        let (value, from) = self.libc(&name, &operands)?;
+        let ret = fn_type.ret;
+        Ok(match ret {
+            CType::Void => Value::Void,
+            _ => Value::Scalar(self.convert(value, &from, &ret), ret),
+        })
+    }
+}
+
+//--- Variadic arguments
+
+impl Interpreter<'_> {
+    /// Store the variadic arguments of a call in a new object, in slots of 8
+    /// bytes, as a compiled call passes them on the stack.
+    fn store_varargs(&mut self, args: Vec<Scalar>) -> Exec<Pointer> {
+        let ptr = self.memory.alloc(vec![0; args.len() * 8], Kind::Auto, None);
+        for (i, &arg) in args.iter().enumerate() {
+            let slot = ptr.offset(i as i64 * 8);
+            let result = match arg {
+                Scalar::Ptr(value) => self.memory.write_ptr(slot, value),
+                Scalar::Int(value) => {
+                    self.memory.write(slot, &value.to_le_bytes())
+                }
+                Scalar::Float(value) => {
+                    self.memory.write(slot, &value.to_bits().to_le_bytes())
+                }
+            };
+            self.fault(result)?;
+        }
+        self.varargs.insert(ptr.object.unwrap(), args);
+        Ok(ptr)
+    }
+
+    /// The pointer to a field of the `__va_list_tag` of a `va_list`, and
+    /// the type of the field.
+    fn va_field(&mut self, state: Pointer, name: &str) -> (Pointer, CType) {
+        let CType::Array { elem, .. } = self.checker.va_list() else {
+            unreachable!("`va_list` is an array on x86-64");
+        };
+        let CType::Struct(id) = *elem else {
+            unreachable!("`va_list` is an array of a struct on x86-64");
+        };
+        let (index, field) = self.checker.types.field(id, name).unwrap();
+        let ty = field.ty.clone();
+        let offset = self.checker.types.layout(id).offsets[index];
+        (state.offset(offset as i64), ty)
+    }
+
+    /// Evaluate an operand of a `va_*` builtin, returning a pointer to its
+    /// `__va_list_tag`.
+    fn va_list(&mut self, ap: &Spanned<Expr>) -> Exec<Pointer> {
+        Ok(self.rvalue(ap)?.0.ptr())
+    }
+
+    /// Evaluate `va_start`.
+    ///
+    /// Every argument register counts as used, so that the arguments are
+    /// all taken from the overflow area, which holds the variadic arguments
+    /// of the call.
+    fn va_start(&mut self, ap: &Spanned<Expr>) -> Exec<()> {
+        let state = self.va_list(ap)?;
+        let frame = self.frames.last();
+        let varargs = frame.and_then(|frame| frame.varargs);
+        let varargs = varargs.expect("`va_start` is in a variadic function");
+        let fields = [
+            ("gp_offset", Scalar::Int(6 * 8)),
+            ("fp_offset", Scalar::Int(6 * 8 + 8 * 16)),
+            ("overflow_arg_area", Scalar::Ptr(varargs)),
+            ("reg_save_area", Scalar::Ptr(Pointer::NULL)),
+        ];
+        for (name, value) in fields {
+            let (ptr, ty) = self.va_field(state, name);
+            self.store(ptr, &ty, value)?;
+        }
+        Ok(())
+    }
+
+    /// The variadic arguments a `va_list` has yet to walk.
+    fn va_rest(&mut self, state: Pointer) -> Exec<Vec<Scalar>> {
+        let (ptr, ty) = self.va_field(state, "overflow_arg_area");
+        let next = self.load(ptr, &ty)?.ptr();
+        let args = next.object.and_then(|id| self.varargs.get(&id));
+        let Some(args) = args else {
+            return Err(self.error("use of a `va_list` that is not started"));
+        };
+        let base = self.memory.get(next.object.unwrap()).base;
+        let index = next.addr.wrapping_sub(base) / 8;
+        Ok(args.get(index as usize..).unwrap_or_default().to_vec())
+    }
+
+    /// Evaluate `va_arg`, which must ask for the type of the argument.
+    fn va_arg(&mut self, ap: &Spanned<Expr>, ty: &CType) -> Exec<Scalar> {
+        let state = self.va_list(ap)?;
+        let Some(&arg) = self.va_rest(state)?.first() else {
+            return Err(self.error("`va_arg` past the last argument"));
+        };
+        let (from, same) = match arg {
+            Scalar::Int(_) => (CType::LONG, ty.is_integer()),
+            Scalar::Float(_) => (CType::DOUBLE, matches!(ty, CType::Float(_))),
+            Scalar::Ptr(_) => (CType::Void.pointer_to(), ty.is_pointer()),
+        };
+        if !same {
+            let ty = self.checker.types.named(ty);
+            let message = format!("`va_arg` of `{ty}` for another type");
+            return Err(self.error(message));
+        }
+        let (ptr, field_ty) = self.va_field(state, "overflow_arg_area");
+        let next = self.load(ptr, &field_ty)?.ptr().offset(8);
+        self.store(ptr, &field_ty, Scalar::Ptr(next))?;
+        Ok(self.convert(arg, &from, ty))
+    }
+}
+
+//--- Scopes
+
+impl<'a> Interpreter<'a> {
+    /// The block scopes of the current function.
+    fn scopes(&mut self) -> &mut Vec<Scope> {
+        match self.frames.last_mut() {
+            Some(frame) => &mut frame.scopes,
+            None => unreachable!("no function is being executed"),
+        }
+    }
+
+    /// Enter a new block scope.
+    fn push_scope(&mut self) {
+        self.checker.push_scope();
+        self.scopes().push(Scope::default());
+    }
+
+    /// Leave the innermost block scope, ending the lifetime of its objects.
+    fn pop_scope(&mut self) {
+        self.checker.pop_scope();
+        let scope = self.scopes().pop().unwrap();
+        for id in scope.objects {
+            self.memory.kill(id);
+        }
+    }
+
+    /// Bind a name to an object in the innermost scope.
+    ///
+    /// Declarations executed again, after jumping back before them, keep
+    /// their object.
+    fn bind(&mut self, name: Symbol, ty: &CType, ptr: Pointer) {
+        let scope = self.scopes().last_mut().unwrap();
+        if scope.names.insert(name, ptr).is_none() {
+            checked(self.checker.declare(name, ty.clone()));
+        }
+    }
+
+    /// The object a name refers to.
+    fn lookup(&self, name: Symbol) -> Pointer {
+        let scopes = self.frames.last().map(|frame| &frame.scopes[..]);
+        scopes
+            .unwrap_or_default()
+            .iter()
+            .rev()
+            .find_map(|scope| scope.names.get(&name).copied())
+            .or_else(|| self.globals.get(&name).copied())
+            .expect("the program has been type checked")
+    }
+
+    /// Create an object that lives until the end of the innermost scope, or
+    /// forever at file scope.
+    fn temp(&mut self, ty: &CType) -> Pointer {
+        if self.frames.is_empty() {
+            return self.object(ty, Kind::Static);
+        }
+        let ptr = self.object(ty, Kind::Auto);
+        let scope = self.scopes().last_mut().unwrap();
+        scope.objects.push(ptr.object.unwrap());
+        ptr
+    }
+
+    /// Create an object declared with some type.
+    fn object(&mut self, ty: &CType, kind: Kind) -> Pointer {
+        let size = self.checker.types.size_of(ty) as usize;
+        self.memory.alloc(vec![0; size], kind, Some(ty.clone()))
+    }
+}
+
+//--- Memory
+
+impl Interpreter<'_> {
+    /// Whether a type is held in memory rather than as a scalar.
+    fn is_aggregate(ty: &CType) -> bool {
+        matches!(ty, CType::Struct(_) | CType::Array { .. })
+    }
+
+    /// Load a scalar from memory.
+    fn load(&mut self, ptr: Pointer, ty: &CType) -> Exec<Scalar> {
+        let size = self.checker.types.size_of(ty);
+        if self.check_ub {
+            self.fault(self.memory.access(ptr, size))?;
+            self.check_access(ptr, ty, false)?;
+        }
+        if ty.is_pointer() {
+            return self.fault(self.memory.read_ptr(ptr)).map(Scalar::Ptr);
+        }
+        let bytes = self.fault(self.memory.read(ptr, size))?;
+        let mut buf = [0; 8];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        let bits = u64::from_le_bytes(buf);
+        Ok(match ty {
+            CType::Float(FloatType::Float) => {
+                Scalar::Float(f32::from_bits(bits as u32) as f64)
+            }
+            CType::Float(_) => Scalar::Float(f64::from_bits(bits)),
+            _ => Scalar::Int(wrap(bits as i64, ty)),
+        })
+    }
+
+    /// Store a scalar of some type to memory.
+    fn store(&mut self, ptr: Pointer, ty: &CType, value: Scalar) -> Exec<()> {
+        let size = self.checker.types.size_of(ty) as usize;
+        let result = match value {
+            Scalar::Ptr(value) => self.memory.write_ptr(ptr, value),
+            Scalar::Int(value) => {
+                self.memory.write(ptr, &value.to_le_bytes()[..size])
+            }
+            Scalar::Float(value) if size == 4 => {
+                let bits = (value as f32).to_bits();
+                self.memory.write(ptr, &bits.to_le_bytes())
+            }
+            Scalar::Float(value) => {
+                self.memory.write(ptr, &value.to_bits().to_le_bytes())
+            }
+        };
+        self.fault(result)?;
+        if self.check_ub {
+            self.check_access(ptr, ty, true)?;
+        }
+        Ok(())
+    }
+
+    /// Store a converted value into an object, copying aggregates.
+    fn assign_to(
+        &mut self,
+        ptr: Pointer,
+        ty: &CType,
+        value: Scalar,
+    ) -> Exec<()> {
+        if Self::is_aggregate(ty) {
+            let size = self.checker.types.size_of(ty);
+            let result = self.memory.copy(ptr, value.ptr(), size);
+            self.fault(result)?;
+            self.log(value.ptr(), size, false);
+            self.log(ptr, size, true);
+            return Ok(());
+        }
+        self.store(ptr, ty, value)
+    }
+
+    /// Fill an object with zeros.
+    fn zero_fill(&mut self, ptr: Pointer, ty: &CType) -> Exec<()> {
+        let size = self.checker.types.size_of(ty) as usize;
+        let result = self.memory.write(ptr, &vec![0; size]);
+        self.fault(result)
+    }
+
+    /// The zero value of a type.
+    ///
+    /// For aggregates, this is a pointer to a new object filled with zeros.
+    fn zero(&mut self, ty: &CType) -> Scalar {
+        match ty {
+            CType::Float(_) => Scalar::Float(0.0),
+            CType::Pointer(_) => Scalar::Ptr(Pointer::NULL),
+            ty if Self::is_aggregate(ty) => Scalar::Ptr(self.temp(ty)),
+            _ => Scalar::Int(0),
+        }
+    }
+}
+
+//--- Statements
+
+impl<'a> Interpreter<'a> {
+    /// Execute a statement.
+    ///
+    /// While looking for the target of a jump, statements are only entered
+    /// to look for it, and declarations only create their objects.
+    fn stmt(&mut self, stmt: &'a Stmt<'a>) -> Exec<Flow> {
+        if self.seek.is_some() {
+            return self.seek(stmt);
+        }
+        self.sequence_point();
+        Ok(match stmt {
+            Stmt::Empty => Flow::Normal,
+            Stmt::Goto(label) => Flow::Goto(*label),
+            Stmt::Label(_, stmt)
+            | Stmt::Case(_, stmt)
+            | Stmt::Default(stmt) => {
+                return self.stmt(stmt);
+            }
+            Stmt::Switch { cond, body } => return self.switch(cond, body),
+            Stmt::Block { stmts } => return self.block(stmts),
+            Stmt::VarDefn(defn) => {
+                self.local_var_defn(defn)?;
+                Flow::Normal
+            }
+            Stmt::Type(defn) => {
+                checked(self.checker.type_defn(defn));
+                Flow::Normal
+            }
+            Stmt::Expr(expr) => {
+                self.expr(expr)?;
+                Flow::Normal
+            }
+            Stmt::If { cond, then, r#else } => {
+                if self.truth(cond)? {
+                    return self.stmt(then);
+                }
+                match r#else {
+                    Some(r#else) => return self.stmt(r#else),
+                    None => Flow::Normal,
+                }
+            }
+            Stmt::For {
+                init,
+                cond,
+                step,
+                body,
+            } => {
+                if let Some(init) = init {
+                    self.expr(init)?;
+                }
+                return self.run_loop(
+                    cond.as_ref(),
+                    body,
+                    step.as_ref(),
+                    false,
+                );
+            }
+            Stmt::While { cond, body } => {
+                return self.run_loop(Some(cond), body, None, false);
+            }
+            Stmt::DoWhile { body, cond } => {
+                return self.run_loop(Some(cond), body, None, true);
+            }
+            Stmt::Break => Flow::Break,
+            Stmt::Continue => Flow::Continue,
+            Stmt::Return(expr) => {
+                let ret = self.frames.last().unwrap().ret.clone();
+                let Some(expr) = expr else {
+                    return Ok(Flow::Return(None));
+                };
+                let (value, ty) = self.rvalue(expr)?;
+                if ret == CType::Void {
+                    return Ok(Flow::Return(None));
+                }
+                Flow::Return(Some(self.convert(value, &ty, &ret)))
+            }
+        })
+    }
+
+    /// Look for the target of a jump in a statement, executing the rest of
+    /// the statement from there if it is found.
+    fn seek(&mut self, stmt: &'a Stmt<'a>) -> Exec<Flow> {
+        match stmt {
+            Stmt::Label(label, inner) => {
+                if matches!(self.seek, Some(Seek::Label(target)) if target == *label)
+                {
+                    self.seek = None;
+                }
+                self.stmt(inner)
+            }
+            Stmt::Case(_, inner) | Stmt::Default(inner) => {
+                if matches!(self.seek, Some(Seek::Case(target)) if ptr::eq(target, stmt))
+                {
+                    self.seek = None;
+                }
+                self.stmt(inner)
+            }
+            Stmt::Block { stmts } => self.block(stmts),
+            Stmt::VarDefn(defn) => {
+                self.local_var_defn(defn)?;
+                Ok(Flow::Normal)
+            }
+            Stmt::Type(defn) => {
+                checked(self.checker.type_defn(defn));
+                Ok(Flow::Normal)
+            }
+            Stmt::If { then, r#else, .. } => {
+                let flow = self.stmt(then)?;
+                match r#else {
+                    Some(r#else) if self.seek.is_some() => self.stmt(r#else),
+                    _ => Ok(flow),
+                }
+            }
+            Stmt::For {
+                cond, step, body, ..
+            } => self.run_loop(cond.as_ref(), body, step.as_ref(), true),
+            Stmt::While { cond, body } | Stmt::DoWhile { body, cond } => {
+                self.run_loop(Some(cond), body, None, true)
+            }
+            Stmt::Switch { body, .. } => self.switch_body(body),
+            _ => Ok(Flow::Normal),
+        }
+    }
+
+    /// Execute a block in a new scope.
+    fn block(&mut self, stmts: &'a [Spanned<Stmt<'a>>]) -> Exec<Flow> {
+        self.push_scope();
+        let flow = self.stmts(stmts);
+        self.pop_scope();
+        flow
+    }
+
+    /// Execute the statements of a block, and the jumps to the labels among
+    /// them.
+    fn stmts(&mut self, stmts: &'a [Spanned<Stmt<'a>>]) -> Exec<Flow> {
+        let mut flow = self.sequence(stmts)?;
+        while let Flow::Goto(label) = flow {
+            self.seek = Some(Seek::Label(label));
+            flow = self.sequence(stmts)?;
+            if self.seek.is_some() {
+                // The label is outside of the block.
+                self.seek = None;
+                return Ok(Flow::Goto(label));
+            }
+        }
+        Ok(flow)
+    }
+
+    /// Execute statements in order, until one does not complete normally.
+    fn sequence(&mut self, stmts: &'a [Spanned<Stmt<'a>>]) -> Exec<Flow> {
+        for stmt in stmts {
+            match self.stmt(stmt)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Execute a loop.
+    ///
+    /// The condition is checked before every iteration, except the first one
+    /// if `enter` is set, as for `do`-`while` loops and jumps into the body.
+    fn run_loop(
+        &mut self,
+        cond: Option<&'a Spanned<Expr<'a>>>,
+        body: &'a Stmt<'a>,
+        step: Option<&'a Spanned<Expr<'a>>>,
+        mut enter: bool,
+    ) -> Exec<Flow> {
+        loop {
+            self.sequence_point();
+            if !enter
+                && let Some(cond) = cond
+                && !self.truth(cond)?
+            {
+                return Ok(Flow::Normal);
+            }
+            enter = false;
+            match self.stmt(body)? {
+                Flow::Break => return Ok(Flow::Normal),
+                Flow::Normal | Flow::Continue => {}
+                flow => return Ok(flow),
+            }
+            if self.seek.is_some() {
+                // The target of the jump is not in the loop.
+                return Ok(Flow::Normal);
+            }
+            if let Some(step) = step {
+                self.sequence_point();
+                self.expr(step)?;
+            }
+        }
+    }
+
+    /// Execute a switch statement.
+    fn switch(
+        &mut self,
+        cond: &'a Spanned<Expr<'a>>,
+        body: &'a Stmt<'a>,
+    ) -> Exec<Flow> {
+        let (value, ty) = self.rvalue(cond)?;
+        let promoted = ty.clone().promote();
+        let Scalar::Int(value) = self.convert(value, &ty, &promoted) else {
+            unreachable!("switch on a non-integer");
+        };
+        let mut labels = Vec::new();
+        Self::case_labels(body, &mut labels);
+        let case = labels.iter().copied().find(|label| match label {
+            Stmt::Case(case, _) => {
+                let case = self.checker.const_int(case).unwrap();
+                wrap(case, &promoted) == value
+            }
+            _ => false,
+        });
+        let default = || {
+            let mut labels = labels.iter().copied();
+            labels.find(|label| matches!(label, Stmt::Default(_)))
+        };
+        match case.or_else(default) {
+            Some(target) => {
+                self.seek = Some(Seek::Case(target));
+                self.switch_body(body)
+            }
+            None => Ok(Flow::Normal),
+        }
+    }
+
+    /// Execute the body of a switch statement, from the label being looked
+    /// for.
+    fn switch_body(&mut self, body: &'a Stmt<'a>) -> Exec<Flow> {
+        match self.stmt(body)? {
+            Flow::Break => Ok(Flow::Normal),
+            flow => Ok(flow),
+        }
+    }
+
+    /// Collect the `case` and `default` statements of a switch body, outside
+    /// of nested switch statements.
+    fn case_labels(stmt: &'a Stmt<'a>, labels: &mut Vec<&'a Stmt<'a>>) {
+        match stmt {
+            Stmt::Case(_, inner) | Stmt::Default(inner) => {
+                labels.push(stmt);
+                Self::case_labels(inner, labels);
+            }
+            Stmt::Block { stmts } => {
+                for stmt in *stmts {
+                    Self::case_labels(stmt, labels);
+                }
+            }
+            Stmt::If { then, r#else, .. } => {
+                Self::case_labels(then, labels);
+                if let Some(r#else) = r#else {
+                    Self::case_labels(r#else, labels);
+                }
+            }
+            Stmt::Label(_, body)
+            | Stmt::For { body, .. }
+            | Stmt::While { body, .. }
+            | Stmt::DoWhile { body, .. } => Self::case_labels(body, labels),
+            _ => {}
+        }
+    }
+
+    /// Execute a block-scope variable definition.
+    fn local_var_defn(&mut self, defn: &'a VarDefn<'a>) -> Exec<()> {
+        let base = checked(self.checker.resolve_base(defn.base));
+        for (binding, init) in defn.bindings {
+            let Some(name) = binding.name() else {
+                checked(self.checker.apply(base.clone(), binding));
+                continue;
+            };
+            // Resolve the type in a scratch scope, so that the variable is
+            // only declared once by `bind()`.
+            self.checker.push_scope();
+            let ty = checked(self.checker.var_binding(
+                &base,
+                binding,
+                init.as_ref(),
+            ));
+            self.checker.pop_scope();
+
+            let storage = defn.attrs.storage;
+            if matches!(ty, CType::Fn(_)) || storage == Some(Storage::Extern) {
+                let ptr = self.symbol(name, &ty);
+                self.bind(name, &ty, ptr);
+                continue;
+            }
+            if storage == Some(Storage::Static) {
+                let key = ptr::from_ref(&binding.node);
+                let ptr = match self.statics.get(&key) {
+                    Some(&ptr) => ptr,
+                    None => {
+                        let ptr = self.object(&ty, Kind::Static);
+                        self.statics.insert(key, ptr);
+                        if let Some(init) = init {
+                            self.init(ptr, &ty, init, true)?;
+                        }
+                        ptr
+                    }
+                };
+                self.bind(name, &ty, ptr);
+                continue;
+            }
+
+            let scope = self.scopes().last().unwrap();
+            let ptr = match scope.names.get(&name) {
+                Some(&ptr) => ptr,
+                None => {
+                    let ptr = self.temp(&ty);
+                    self.bind(name, &ty, ptr);
+                    ptr
+                }
+            };
+            if self.seek.is_none()
+                && let Some(init) = init
+            {
+                self.init(ptr, &ty, init, false)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Initialize an object.
+    ///
+    /// If `zeroed` is set, the object is already filled with zeros.
+    fn init(
+        &mut self,
+        ptr: Pointer,
+        ty: &CType,
+        init: &Initializer,
+        zeroed: bool,
+    ) -> Exec<()> {
+        match (ty, init) {
+            (
+                CType::Array { elem, len },
+                Initializer::Expr(Spanned {
+                    node: Expr::Lit(Lit::Str(s)),
+                    ..
+                }),
+            ) if is_char(elem) => {
+                let mut bytes = s.to_vec();
+                bytes.resize(len.unwrap() as usize, 0);
+                let result = self.memory.write(ptr, &bytes);
+                self.fault(result)
+            }
+            (CType::Array { elem, .. }, Initializer::List(items)) => {
+                if !zeroed {
+                    self.zero_fill(ptr, ty)?;
+                }
+                let size = self.checker.types.size_of(elem) as i64;
+                let mut next = 0;
+                for item in *items {
+                    if let Some(Designator::Index(index)) =
+                        item.designators.first()
+                    {
+                        next = self.checker.const_int(index).unwrap();
+                    }
+                    let rest = item.designators.get(1..).unwrap_or_default();
+                    let elem_ptr = ptr.offset(next * size);
+                    self.designated(elem_ptr, elem, rest, &item.init)?;
+                    next += 1;
+                }
+                Ok(())
+            }
+            (CType::Struct(id), Initializer::List(items)) => {
+                if !zeroed {
+                    self.zero_fill(ptr, ty)?;
+                }
+                let layout = self.checker.types.layout(*id);
+                let mut next = 0;
+                for item in *items {
+                    if let Some(Designator::Member(name)) =
+                        item.designators.first()
+                    {
+                        let name = &name.to_string();
+                        next = self.checker.types.field(*id, name).unwrap().0;
+                    }
+                    let fields = self.checker.types.get(*id).fields.as_ref();
+                    let field_ty = fields.unwrap()[next].ty.clone();
+                    let rest = item.designators.get(1..).unwrap_or_default();
+                    let field_ptr = ptr.offset(layout.offsets[next] as i64);
+                    self.designated(field_ptr, &field_ty, rest, &item.init)?;
+                    next += 1;
+                }
+                Ok(())
+            }
+            (_, Initializer::List(items)) => {
+                self.init(ptr, ty, &items[0].init, zeroed)
+            }
+            (_, Initializer::Expr(expr)) => {
+                let (value, from) = self.rvalue(expr)?;
+                let value = self.convert(value, &from, ty);
+                self.assign_to(ptr, ty, value)
+            }
+        }
+    }
+
+    /// Initialize the subobject of an already zeroed object selected by some
+    /// designators.
+    fn designated(
+        &mut self,
+        ptr: Pointer,
+        ty: &CType,
+        designators: &[Designator],
+        init: &Initializer,
+    ) -> Exec<()> {
+        let Some((first, rest)) = designators.split_first() else {
+            return self.init(ptr, ty, init, true);
+        };
+        match (ty, first) {
+            (CType::Array { elem, .. }, Designator::Index(index)) => {
+                let index = self.checker.const_int(index).unwrap();
+                let size = self.checker.types.size_of(elem) as i64;
+                self.designated(ptr.offset(index * size), elem, rest, init)
+            }
+            (CType::Struct(id), Designator::Member(name)) => {
+                let name = &name.to_string();
+                let (index, field) =
+                    self.checker.types.field(*id, name).unwrap();
+                let field_ty = field.ty.clone();
+                let offset = self.checker.types.layout(*id).offsets[index];
+                let ptr = ptr.offset(offset as i64);
+                self.designated(ptr, &field_ty, rest, init)
+            }
+            _ => unreachable!("mismatched designator"),
+        }
+    }
+}
+
+//--- Expressions
+
+impl Interpreter<'_> {
+    /// Evaluate an expression.
+    fn expr(&mut self, expr: &Spanned<Expr>) -> Exec<Value> {
+        self.eval(expr).map_err(|stop| stop.at(expr))
+    }
+
+    /// Evaluate an expression, without locating errors at it.
+    fn eval(&mut self, expr: &Spanned<Expr>) -> Exec<Value> {
+        Ok(match &expr.node {
+            Expr::Debug => Value::Scalar(Scalar::Int(0), CType::INT),
+            Expr::Ref(e) => match self.expr(e)? {
+                Value::Place(ptr, ty) => {
+                    Value::Scalar(Scalar::Ptr(ptr), ty.pointer_to())
+                }
+                _ => unreachable!("address of a non-lvalue"),
+            },
+            Expr::Bin(hir::BinOp::Assign, lhs, rhs) => {
+                let start = self.mark();
+                let (ptr, ty) = self.place(lhs)?;
+                let mid = self.mark();
+                let (value, from) = self.rvalue(rhs)?;
+                self.unsequenced(&[start, mid, self.mark()])?;
+                let value = self.convert(value, &from, &ty);
+                self.assign_to(ptr, &ty, value)?;
+                self.modified(start)?;
+                match Self::is_aggregate(&ty) {
+                    true => Value::Place(ptr, ty),
+                    false => Value::Scalar(value, ty),
+                }
+            }
+            Expr::Bin(hir::BinOp::Comma, lhs, rhs) => {
+                let start = self.mark();
+                self.expr(lhs)?;
+                self.complete(start);
+                return self.expr(rhs);
+            }
+            Expr::Bin(hir::BinOp::And | hir::BinOp::Or, _, _)
+            | Expr::Una(UnaOp::Not, _) => {
+                let value = self.truth(expr)? as i64;
+                Value::Scalar(Scalar::Int(value), CType::INT)
+            }
+            Expr::Bin(op, lhs, rhs) => match op.compound() {
+                Some(op) => {
+                    let start = self.mark();
+                    let (ptr, ty) = self.place(lhs)?;
+                    let old = self.load(ptr, &ty)?;
+                    let mid = self.mark();
+                    let rhs = self.rvalue(rhs)?;
+                    self.unsequenced(&[start, mid, self.mark()])?;
+                    let (value, from) = self.bin(op, (old, ty.clone()), rhs)?;
+                    let value = self.convert(value, &from, &ty);
+                    self.store(ptr, &ty, value)?;
+                    self.modified(start)?;
+                    Value::Scalar(value, ty)
+                }
+                None => {
+                    let start = self.mark();
+                    let lhs = self.rvalue(lhs)?;
+                    let mid = self.mark();
+                    let rhs = self.rvalue(rhs)?;
+                    self.unsequenced(&[start, mid, self.mark()])?;
+                    let (value, ty) = self.bin(*op, lhs, rhs)?;
+                    Value::Scalar(value, ty)
+                }
+            },
+            Expr::Una(UnaOp::PreInc, e) => {
+                return self.inc_dec(e, hir::BinOp::Add, true);
+            }
+            Expr::Una(UnaOp::PreDec, e) => {
+                return self.inc_dec(e, hir::BinOp::Sub, true);
+            }
+            Expr::Una(UnaOp::Deref, e) => {
+                let (ptr, ty) = self.rvalue(e)?;
+                Value::Place(ptr.ptr(), ty.pointee().unwrap().clone())
+            }
+            Expr::Una(op, e) => {
+                let (value, ty) = self.rvalue(e)?;
+                let promoted = ty.clone().promote();
+                let value = match (op, self.convert(value, &ty, &promoted)) {
+                    (UnaOp::Neg, Scalar::Int(value)) => {
+                        if self.check_ub {
+                            let sub = hir::BinOp::Sub;
+                            self.check_arith(sub, 0, value, &promoted)?;
+                        }
+                        Scalar::Int(wrap(value.wrapping_neg(), &promoted))
+                    }
+                    (UnaOp::Neg, Scalar::Float(value)) => Scalar::Float(-value),
+                    (UnaOp::Inv, Scalar::Int(value)) => {
+                        Scalar::Int(wrap(!value, &promoted))
+                    }
+                    (_, value) => value,
+                };
+                Value::Scalar(value, promoted)
+            }
+            Expr::Ident(name) => {
+                let ty = self.checker.lookup(*name).unwrap().clone();
+                Value::Place(self.lookup(*name), ty)
+            }
+            Expr::Lit(lit @ Lit::Str(s)) => {
+                Value::Place(self.string(s), checked(self.checker.lit(lit)))
+            }
+            Expr::Lit(lit) => {
+                let ty = checked(self.checker.lit(lit));
+                let value = match *lit {
+                    Lit::Int { value, .. } => {
+                        Scalar::Int(wrap(value as i64, &ty))
+                    }
+                    Lit::Char(c) => Scalar::Int(c as i8 as i64),
+                    Lit::Float { value, single, .. } => {
+                        Scalar::Float(if single {
+                            value as f32 as f64
+                        } else {
+                            value
+                        })
+                    }
+                    Lit::Str(_) => unreachable!(),
+                };
+                Value::Scalar(value, ty)
+            }
+            Expr::Cast(ty, e) => {
+                let to = checked(self.checker.type_name(ty));
+                let (value, from) = self.rvalue(e)?;
+                if to == CType::Void {
+                    return Ok(Value::Void);
+                }
+                Value::Scalar(self.convert(value, &from, &to), to)
+            }
+            Expr::Sizeof(_) | Expr::SizeofType(_) | Expr::Alignof(_) => {
+                let value = self.checker.const_int(expr).unwrap();
+                Value::Scalar(Scalar::Int(value), CType::SIZE_T)
+            }
+            Expr::CompoundLit(ty, items) => {
+                let ty = checked(self.checker.type_name(ty));
+                let init = Initializer::List(items);
+                let ty = checked(self.checker.initializer(&ty, &init));
+                let ptr = self.temp(&ty);
+                self.init(ptr, &ty, &init, false)?;
+                Value::Place(ptr, ty)
+            }
+            Expr::Call(callee, args) => return self.call(callee, args),
+            Expr::Member(e, name) => {
+                let (ptr, ty) = self.place(e)?;
+                self.member(ptr, &ty, &name.to_string())
+            }
+            Expr::Arrow(e, name) => {
+                let (ptr, ty) = self.rvalue(e)?;
+                self.member(ptr.ptr(), ty.pointee().unwrap(), &name.to_string())
+            }
+            Expr::Index(e, index) => {
+                let start = self.mark();
+                let lhs = self.rvalue(e)?;
+                let mid = self.mark();
+                let rhs = self.rvalue(index)?;
+                self.unsequenced(&[start, mid, self.mark()])?;
+                let ((base, ptr_ty), (index, index_ty)) = if lhs.1.is_pointer()
+                {
+                    (lhs, rhs)
+                } else {
+                    (rhs, lhs)
+                };
+                let Scalar::Int(index) =
+                    self.convert(index, &index_ty, &CType::LONG)
+                else {
+                    unreachable!("non-integer index");
+                };
+                let elem = ptr_ty.pointee().unwrap().clone();
+                let size = self.checker.types.size_of(&elem) as i64;
+                let ptr = base.ptr().offset(index.wrapping_mul(size));
+                if self.check_ub {
+                    self.check_offset(base.ptr(), ptr)?;
+                }
+                Value::Place(ptr, elem)
+            }
+            Expr::PostInc(e) => return self.inc_dec(e, hir::BinOp::Add, false),
+            Expr::PostDec(e) => return self.inc_dec(e, hir::BinOp::Sub, false),
+            Expr::VaStart(ap, _) => {
+                self.va_start(ap)?;
+                Value::Void
+            }
+            Expr::VaArg(ap, ty) => {
+                let ty = checked(self.checker.type_name(ty));
+                Value::Scalar(self.va_arg(ap, &ty)?, ty)
+            }
+            Expr::VaEnd(ap) => {
+                self.va_list(ap)?;
+                Value::Void
+            }
+            Expr::VaCopy(dest, src) => {
+                let dest = self.va_list(dest)?;
+                let src = self.va_list(src)?;
+                let CType::Array { elem, .. } = self.checker.va_list() else {
+                    unreachable!("`va_list` is an array on x86-64");
+                };
+                self.assign_to(dest, &elem, Scalar::Ptr(src))?;
+                Value::Void
+            }
+            Expr::Cond(cond, then, r#else) => {
+                let ty = checked(self.checker.rvalue(expr));
+                let start = self.mark();
+                let truth = self.truth(cond)?;
+                self.complete(start);
+                let e = if truth { then } else { r#else };
+                let (value, from) = self.rvalue(e)?;
+                match ty {
+                    CType::Void => Value::Void,
+                    ty if Self::is_aggregate(&ty) => {
+                        Value::Place(value.ptr(), ty)
+                    }
+                    ty => Value::Scalar(self.convert(value, &from, &ty), ty),
+                }
+            }
+        })
+    }
+
+    /// Evaluate an expression used as a value.
+    ///
+    /// Scalars are loaded from memory, arrays and functions decay to
+    /// pointers, and aggregates are left in memory (returning a pointer to
+    /// them).
+    fn rvalue(&mut self, expr: &Spanned<Expr>) -> Exec<(Scalar, CType)> {
+        Ok(match self.expr(expr)? {
+            Value::Scalar(value, ty) => (value, ty),
+            Value::Place(ptr, ty @ (CType::Array { .. } | CType::Fn(_))) => {
+                (Scalar::Ptr(ptr), ty.decay())
+            }
+            Value::Place(ptr, ty) if Self::is_aggregate(&ty) => {
+                (Scalar::Ptr(ptr), ty)
+            }
+            Value::Place(ptr, ty) => {
+                let value = self.load(ptr, &ty).map_err(|stop| stop.at(expr));
+                (value?, ty)
+            }
+            Value::Void => (Scalar::Int(0), CType::Void),
+        })
+    }
+
+    /// Evaluate an expression designating an object.
+    fn place(&mut self, expr: &Spanned<Expr>) -> Exec<(Pointer, CType)> {
+        match self.expr(expr)? {
+            Value::Place(ptr, ty) => Ok((ptr, ty)),
+            _ => unreachable!("`{expr:?}` is not an lvalue"),
+        }
+    }
+
+    /// Evaluate a controlling expression.
+    fn truth(&mut self, cond: &Spanned<Expr>) -> Exec<bool> {
+        Ok(match &cond.node {
+            Expr::Bin(hir::BinOp::And, lhs, rhs) => {
+                let start = self.mark();
+                let lhs = self.truth(lhs)?;
+                self.complete(start);
+                lhs && self.truth(rhs)?
+            }
+            Expr::Bin(hir::BinOp::Or, lhs, rhs) => {
+                let start = self.mark();
+                let lhs = self.truth(lhs)?;
+                self.complete(start);
+                lhs || self.truth(rhs)?
+            }
+            Expr::Una(UnaOp::Not, e) => !self.truth(e)?,
+            _ => self.rvalue(cond)?.0.truth(),
+        })
+    }
+
+    /// Access a struct member, given a pointer to the struct.
+    fn member(&self, ptr: Pointer, ty: &CType, name: &str) -> Value {
+        let CType::Struct(id) = *ty else {
+            unreachable!("member access on a non-struct");
+        };
+        let (index, field) = self.checker.types.field(id, name).unwrap();
+        let offset = self.checker.types.layout(id).offsets[index];
+        Value::Place(ptr.offset(offset as i64), field.ty.clone())
+    }
+
+    /// Evaluate an increment or decrement.
+    fn inc_dec(
+        &mut self,
+        expr: &Spanned<Expr>,
+        op: hir::BinOp,
+        prefix: bool,
+    ) -> Exec<Value> {
+        let (ptr, ty) = self.place(expr)?;
+        let old = self.load(ptr, &ty)?;
+        let one = (Scalar::Int(1), CType::INT);
+        let (new, from) = self.bin(op, (old, ty.clone()), one)?;
+        let new = self.convert(new, &from, &ty);
+        self.store(ptr, &ty, new)?;
+        Ok(Value::Scalar(if prefix { new } else { old }, ty))
+    }
+
+    /// Evaluate a binary operation on values.
+    fn bin(
+        &mut self,
+        op: hir::BinOp,
+        (lhs, lhs_ty): (Scalar, CType),
+        (rhs, rhs_ty): (Scalar, CType),
+    ) -> Exec<(Scalar, CType)> {
+        let ty = checked(self.checker.bin(op, lhs_ty.clone(), rhs_ty.clone()));
+        let (ptr, elem, index, index_ty) = match (op, &lhs_ty, &rhs_ty) {
+            (hir::BinOp::Add | hir::BinOp::Sub, CType::Pointer(elem), int)
+                if int.is_integer() =>
+            {
+                (lhs, elem, rhs, &rhs_ty)
+            }
+            (hir::BinOp::Add, int, CType::Pointer(elem))
+                if int.is_integer() =>
+            {
+                (rhs, elem, lhs, &lhs_ty)
+            }
+            (hir::BinOp::Sub, CType::Pointer(elem), CType::Pointer(_)) => {
+                if self.check_ub {
+                    self.check_pointers(op, lhs.ptr(), rhs.ptr())?;
+                }
+                let diff = lhs.ptr().addr.wrapping_sub(rhs.ptr().addr) as i64;
+                let size = self.checker.types.size_of(elem) as i64;
+                return Ok((Scalar::Int(diff / size), ty));
+            }
+            _ if op.is_comparison() => {
+                let common = if lhs_ty.is_arithmetic() && rhs_ty.is_arithmetic()
+                {
+                    self.checker.types.common(&lhs_ty, &rhs_ty)
+                } else if lhs_ty.is_pointer() {
+                    lhs_ty.clone()
+                } else {
+                    rhs_ty.clone()
+                };
+                let lhs = self.convert(lhs, &lhs_ty, &common);
+                let rhs = self.convert(rhs, &rhs_ty, &common);
+                if self.check_ub
+                    && let (Scalar::Ptr(lhs), Scalar::Ptr(rhs)) = (lhs, rhs)
+                {
+                    self.check_pointers(op, lhs, rhs)?;
+                }
+                let result = compare(op, lhs, rhs, signed(&common));
+                return Ok((Scalar::Int(result as i64), ty));
+            }
+            _ => {
+                let lhs = self.convert(lhs, &lhs_ty, &ty);
+                let rhs = self.convert(rhs, &rhs_ty, &ty);
+                let value = self.arith(op, lhs, rhs, &ty)?;
+                return Ok((value, ty));
+            }
+        };
+        let Scalar::Int(index) = self.convert(index, index_ty, &CType::LONG)
+        else {
+            unreachable!("non-integer offset");
+        };
+        let size = self.checker.types.size_of(elem) as i64;
+        let delta = match op {
+            hir::BinOp::Sub => index.wrapping_neg(),
+            _ => index,
+        };
+        let result = ptr.ptr().offset(delta.wrapping_mul(size));
+        if self.check_ub {
+            self.check_offset(ptr.ptr(), result)?;
+        }
+        Ok((Scalar::Ptr(result), ty))
+    }
+
+    /// Perform an arithmetic operation on operands of its result type.
+    fn arith(
+        &self,
+        op: hir::BinOp,
+        lhs: Scalar,
+        rhs: Scalar,
+        ty: &CType,
+    ) -> Exec<Scalar> {
+        use hir::BinOp::*;
+        let (a, b) = match (lhs, rhs) {
+            (Scalar::Float(a), Scalar::Float(b)) => {
+                let value = match op {
+                    Add => a + b,
+                    Sub => a - b,
+                    Mul => a * b,
+                    Div => a / b,
+                    _ => unreachable!("{op:?} on floating-point numbers"),
+                };
+                let value = match ty {
+                    CType::Float(FloatType::Float) => value as f32 as f64,
+                    _ => value,
+                };
+                return Ok(Scalar::Float(value));
+            }
+            (Scalar::Int(a), Scalar::Int(b)) => (a, b),
+            _ => unreachable!("{op:?} on {lhs:?} and {rhs:?}"),
+        };
+        if matches!(op, Div | Rem) && b == 0 {
+            return Err(self.error("division by zero"));
+        }
+        if self.check_ub {
+            self.check_arith(op, a, b, ty)?;
+        }
+        let (ua, ub) = (a as u64, b as u64);
+        let value = match op {
+            Add => a.wrapping_add(b),
+            Sub => a.wrapping_sub(b),
+            Mul => a.wrapping_mul(b),
+            Div if signed(ty) => a.wrapping_div(b),
+            Div => (ua / ub) as i64,
+            Rem if signed(ty) => a.wrapping_rem(b),
+            Rem => (ua % ub) as i64,
+            Shl => a.wrapping_shl(b as u32),
+            // Values of unsigned types are zero-extended, so shifting them
+            // arithmetically shifts in zeros.
+            Shr if signed(ty) => a.wrapping_shr(b as u32),
+            Shr => ua.wrapping_shr(b as u32) as i64,
+            BitAnd => a & b,
+            BitOr => a | b,
+            BitXor => a ^ b,
+            _ => unreachable!("{op:?} is not an arithmetic operator"),
+        };
+        Ok(Scalar::Int(wrap(value, ty)))
+    }
+
+    /// Convert a scalar between types.
+    fn convert(&self, value: Scalar, from: &CType, to: &CType) -> Scalar {
+        match (to, value) {
+            (CType::Int(int), value) if int.rank == IntRank::Bool => {
+                Scalar::Int(value.truth() as i64)
+            }
+            (CType::Int(_), Scalar::Int(value)) => Scalar::Int(wrap(value, to)),
+            (CType::Int(int), Scalar::Float(value)) => {
+                let value = if int.signed {
+                    value as i64
+                } else {
+                    value as u64 as i64
+                };
+                Scalar::Int(wrap(value, to))
+            }
+            (CType::Int(_), Scalar::Ptr(ptr)) => {
+                Scalar::Int(wrap(ptr.addr as i64, to))
+            }
+            (CType::Float(float), Scalar::Int(value)) => {
+                let single = *float == FloatType::Float;
+                Scalar::Float(match (single, signed(from)) {
+                    (true, true) => value as f32 as f64,
+                    (true, false) => value as u64 as f32 as f64,
+                    (false, true) => value as f64,
+                    (false, false) => value as u64 as f64,
+                })
+            }
+            (CType::Float(FloatType::Float), Scalar::Float(value)) => {
+                Scalar::Float(value as f32 as f64)
+            }
+            (CType::Pointer(_), Scalar::Int(value)) => {
+                Scalar::Ptr(self.memory.pointer(value as u64))
+            }
+            // Aggregates are passed around by address.
+            _ => value,
+        }
+    }
+}
+
+//----------- RuntimeError -----------------------------------------------------
+
+/// A program going wrong while it is interpreted.
+#[derive(Clone, Debug)]
+pub struct RuntimeError {
+    /// The function being executed, if any.
+    pub function: Option<String>,
+
+    /// The source text of the innermost expression being evaluated, if any.
+    pub span: Option<Span>,
+
+    /// A description of the error.
+    pub message: String,
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.function {
+            Some(function) => write!(f, "in `{}`: {}", function, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Error for RuntimeError {}
+
+//----------- Helpers ----------------------------------------------------------
+
+/// Whether a type is signed, for the purpose of selecting operations.
+///
+/// Pointers are treated as unsigned.
+fn signed(ty: &CType) -> bool {
+    match ty {
+        CType::Int(int) => int.signed,
+        CType::Pointer(_) => false,
+        _ => true,
+    }
+}
+
+/// Whether a type is a character type.
+fn is_char(ty: &CType) -> bool {
+    matches!(
+        ty,
+        CType::Int(IntType {
+            rank: IntRank::Char,
+            ..
+        })
+    )
+}
+
+/// Truncate an integer to a type, extending it back to 64 bits following
+/// the signedness of the type.
+fn wrap(value: i64, ty: &CType) -> i64 {
+    let CType::Int(int) = ty else {
+        return value;
+    };
+    let bits = match int.rank {
+        IntRank::Bool => return (value != 0) as i64,
+        IntRank::Char => 8,
+        IntRank::Short => 16,
+        IntRank::Int => 32,
+        IntRank::Long | IntRank::LongLong => return value,
+    };
+    let shift = 64 - bits;
+    match int.signed {
+        true => value << shift >> shift,
+        false => ((value as u64) << shift >> shift) as i64,
+    }
+}
+
+/// Compare two values of the same type.
+fn compare(op: hir::BinOp, lhs: Scalar, rhs: Scalar, signed: bool) -> bool {
+    use std::cmp::Ordering::*;
+    let ordering = match (lhs, rhs) {
+        (Scalar::Int(a), Scalar::Int(b)) if signed => a.partial_cmp(&b),
+        (Scalar::Int(a), Scalar::Int(b)) => (a as u64).partial_cmp(&(b as u64)),
+        (Scalar::Float(a), Scalar::Float(b)) => a.partial_cmp(&b),
+        (Scalar::Ptr(a), Scalar::Ptr(b)) => a.addr.partial_cmp(&b.addr),
+        _ => unreachable!("comparison of {lhs:?} and {rhs:?}"),
+    };
+    match op {
+        hir::BinOp::Eq => ordering == Some(Equal),
+        hir::BinOp::Ne => ordering != Some(Equal),
+        hir::BinOp::Lt => ordering == Some(Less),
+        hir::BinOp::Gt => ordering == Some(Greater),
+        hir::BinOp::Le => matches!(ordering, Some(Less | Equal)),
+        hir::BinOp::Ge => matches!(ordering, Some(Greater | Equal)),
+        _ => unreachable!("{op:?} is not a comparison"),
+    }
+}
@@ -0,0 +1,367 @@
+//! The C library functions available to interpreted programs.
+//!
+//! Only a handful of functions are provided: `printf()`, `vprintf()`,
+//! `puts()` and `putchar()` for output, `malloc()` and `free()` for the heap, `memcpy()`
+//! and `strlen()`, and `exit()`.  Calling any other function that the program
+//! declares but does not define is an error.
+
+use crate::ctype::CType;
+
+use super::memory::{Kind, Pointer};
+use super::{Exec, Interpreter, Scalar, Stop};
+
+impl Interpreter<'_> {
+    /// Call a C library function with converted arguments, returning its
+    /// result and the type the library declares it with.
+    pub(super) fn libc(
+        &mut self,
+        name: &str,
+        args: &[Scalar],
+    ) -> Exec<(Scalar, CType)> {
+        let arity = match name {
+            "printf" | "puts" | "putchar" | "malloc" | "free" | "strlen"
+            | "exit" => 1,
+            "vprintf" => 2,
+            "memcpy" => 3,
+            _ => {
+                let message = format!("call to undefined function `{name}`");
+                return Err(self.error(message));
+            }
+        };
+        if args.len() < arity {
+            let message = format!("too few arguments to `{name}`");
+            return Err(self.error(message));
+        }
+        let void_ptr = CType::Void.pointer_to();
+
+        match name {
+            "printf" => {
+                let result = self.memory.cstring(args[0].ptr());
+                let format = self.fault(result)?;
+                let text = self.format(&format, &args[1..])?;
+                self.write(&text)?;
+                Ok((Scalar::Int(text.len() as i64), CType::INT))
+            }
+            "vprintf" => {
+                let result = self.memory.cstring(args[0].ptr());
+                let format = self.fault(result)?;
+                let rest = self.va_rest(args[1].ptr())?;
+                let text = self.format(&format, &rest)?;
+                self.write(&text)?;
+                Ok((Scalar::Int(text.len() as i64), CType::INT))
+            }
+            "puts" => {
+                let result = self.memory.cstring(args[0].ptr());
+                let mut text = self.fault(result)?;
+                text.push(b'\n');
+                self.write(&text)?;
+                Ok((Scalar::Int(text.len() as i64), CType::INT))
+            }
+            "putchar" => {
+                let c = int(args[0]) as u8;
+                self.write(&[c])?;
+                Ok((Scalar::Int(c as i64), CType::INT))
+            }
+            "malloc" => {
+                let size = int(args[0]) as u64;
+                // Allocations the host could not make fail.
+                if size > u32::MAX as u64 {
+                    return Ok((Scalar::Ptr(Pointer::NULL), void_ptr));
+                }
+                let bytes = vec![0; size as usize];
+                let ptr = self.memory.alloc(bytes, Kind::Heap, None);
+                Ok((Scalar::Ptr(ptr), void_ptr))
+            }
+            "free" => {
+                let result = self.memory.free(args[0].ptr());
+                self.fault(result)?;
+                Ok((Scalar::Int(0), CType::Void))
+            }
+            "memcpy" => {
+                let (dest, src) = (args[0].ptr(), args[1].ptr());
+                let result = self.memory.copy(dest, src, int(args[2]) as u64);
+                self.fault(result)?;
+                Ok((Scalar::Ptr(dest), void_ptr))
+            }
+            "strlen" => {
+                let result = self.memory.cstring(args[0].ptr());
+                let len = self.fault(result)?.len();
+                Ok((Scalar::Int(len as i64), CType::SIZE_T))
+            }
+            "exit" => Err(Stop::Exit(int(args[0]) as i32)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Write to the output of the program.
+    fn write(&mut self, bytes: &[u8]) -> Exec<()> {
+        let result = self.out.write_all(bytes);
+        result.map_err(|err| self.error(err.to_string()))
+    }
+
+    /// Format the arguments of `printf()`.
+    fn format(&self, format: &[u8], args: &[Scalar]) -> Exec<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut args = args.iter().copied();
+        let mut next = || {
+            let arg = args.next();
+            arg.ok_or_else(|| self.error("too few arguments for the format"))
+        };
+
+        let mut rest = format;
+        while let Some((&byte, tail)) = rest.split_first() {
+            rest = tail;
+            if byte != b'%' {
+                out.push(byte);
+                continue;
+            }
+
+            let mut spec = Spec::default();
+            while let Some((&flag, tail)) = rest.split_first()
+                && b"-+ #0".contains(&flag)
+            {
+                spec.flags.push(flag);
+                rest = tail;
+            }
+            if let Some((b'*', tail)) = rest.split_first() {
+                rest = tail;
+                let width = int(next()?) as i32;
+                if width < 0 {
+                    spec.flags.push(b'-');
+                }
+                spec.width = width.unsigned_abs() as usize;
+            } else {
+                spec.width = digits(&mut rest).unwrap_or(0);
+            }
+            if let Some((b'.', tail)) = rest.split_first() {
+                rest = tail;
+                if let Some((b'*', tail)) = rest.split_first() {
+                    rest = tail;
+                    let precision = int(next()?) as i32;
+                    spec.precision = usize::try_from(precision).ok();
+                } else {
+                    spec.precision = Some(digits(&mut rest).unwrap_or(0));
+                }
+            }
+            let len =
+                rest.iter().take_while(|c| b"hlLqjzt".contains(c)).count();
+            let length = &rest[..len];
+            rest = &rest[len..];
+            let Some((&conv, tail)) = rest.split_first() else {
+                out.push(b'%');
+                break;
+            };
+            rest = tail;
+
+            // The text, and where zeros go to pad it for the `0` flag.
+            let (text, zeros) = match conv {
+                b'%' => (b"%".to_vec(), None),
+                b'c' => (vec![int(next()?) as u8], None),
+                b's' => {
+                    let result = self.memory.cstring(next()?.ptr());
+                    let mut text = self.fault(result)?;
+                    if let Some(precision) = spec.precision {
+                        text.truncate(precision);
+                    }
+                    (text, None)
+                }
+                b'p' => match next()? {
+                    Scalar::Ptr(Pointer { addr: 0, .. }) => {
+                        (b"(nil)".to_vec(), None)
+                    }
+                    arg => (format!("{:#x}", int(arg)).into_bytes(), None),
+                },
+                b'd' | b'i' => {
+                    let value = match length {
+                        b"hh" => int(next()?) as i8 as i64,
+                        b"h" => int(next()?) as i16 as i64,
+                        b"" => int(next()?) as i32 as i64,
+                        _ => int(next()?),
+                    };
+                    let sign = if value < 0 { "-" } else { spec.sign() };
+                    let digits = value.unsigned_abs().to_string();
+                    spec.integer(sign, "", digits)
+                }
+                b'u' | b'o' | b'x' | b'X' => {
+                    let value = match length {
+                        b"hh" => int(next()?) as u8 as u64,
+                        b"h" => int(next()?) as u16 as u64,
+                        b"" => int(next()?) as u32 as u64,
+                        _ => int(next()?) as u64,
+                    };
+                    let alt = spec.flags.contains(&b'#') && value != 0;
+                    let (prefix, digits) = match conv {
+                        b'u' => ("", value.to_string()),
+                        b'o' if alt => ("", format!("0{value:o}")),
+                        b'o' => ("", format!("{value:o}")),
+                        b'x' if alt => ("0x", format!("{value:x}")),
+                        b'x' => ("", format!("{value:x}")),
+                        _ if alt => ("0X", format!("{value:X}")),
+                        _ => ("", format!("{value:X}")),
+                    };
+                    spec.integer("", prefix, digits)
+                }
+                b'f' | b'F' | b'e' | b'E' | b'g' | b'G' => {
+                    let value = match next()? {
+                        Scalar::Float(value) => value,
+                        arg => f64::from_bits(int(arg) as u64),
+                    };
+                    spec.float(conv, value)
+                }
+                _ => {
+                    let message = format!(
+                        "unsupported conversion `%{}` in format",
+                        conv as char
+                    );
+                    return Err(self.error(message));
+                }
+            };
+            out.extend(spec.pad(text, zeros));
+        }
+        Ok(out)
+    }
+}
+
+//----------- Formatting -------------------------------------------------------
+
+/// A conversion specification of `printf()`, after the `%`.
+#[derive(Default)]
+struct Spec {
+    /// The flag characters.
+    flags: Vec<u8>,
+
+    /// The minimum field width.
+    width: usize,
+
+    /// The precision, if any.
+    precision: Option<usize>,
+}
+
+impl Spec {
+    /// The sign of a non-negative number.
+    fn sign(&self) -> &'static str {
+        if self.flags.contains(&b'+') {
+            "+"
+        } else if self.flags.contains(&b' ') {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    /// Format an integer from its sign, prefix and digits.
+    ///
+    /// Zeros for the `0` flag go after the prefix, unless there is a
+    /// precision.
+    fn integer(
+        &self,
+        sign: &str,
+        prefix: &str,
+        digits: String,
+    ) -> (Vec<u8>, Option<usize>) {
+        let (digits, zeros) = match self.precision {
+            Some(0) if digits == "0" => (String::new(), None),
+            Some(precision) => (format!("{digits:0>precision$}"), None),
+            None => (digits, Some(sign.len() + prefix.len())),
+        };
+        (format!("{sign}{prefix}{digits}").into_bytes(), zeros)
+    }
+
+    /// Format a floating-point number with a conversion (`f`, `e` or `g`, in
+    /// either case).
+    ///
+    /// Zeros for the `0` flag go after the sign, unless the number is
+    /// infinite or NaN.
+    fn float(&self, conv: u8, value: f64) -> (Vec<u8>, Option<usize>) {
+        let sign = if value.is_sign_negative() {
+            "-"
+        } else {
+            self.sign()
+        };
+        let magnitude = value.abs();
+        let precision = self.precision.unwrap_or(6);
+        let text = if !value.is_finite() {
+            match value.is_nan() {
+                true => "nan".to_string(),
+                false => "inf".to_string(),
+            }
+        } else {
+            match conv.to_ascii_lowercase() {
+                b'f' => format!("{magnitude:.precision$}"),
+                b'e' => exponent(magnitude, precision),
+                _ => {
+                    // Use the style of `e` only for exponents out of range
+                    // for the precision.
+                    let precision = precision.max(1);
+                    let e = exponent(magnitude, precision - 1);
+                    let x: i32 = e[e.find('e').unwrap() + 1..].parse().unwrap();
+                    let mut text = if x < -4 || x >= precision as i32 {
+                        e
+                    } else {
+                        let decimals = (precision as i32 - 1 - x) as usize;
+                        format!("{magnitude:.decimals$}")
+                    };
+                    if !self.flags.contains(&b'#') && text.contains('.') {
+                        let split = text.find('e').unwrap_or(text.len());
+                        let (mantissa, exp) = text.split_at(split);
+                        let mantissa = mantissa
+                            .trim_end_matches('0')
+                            .trim_end_matches('.');
+                        text = format!("{mantissa}{exp}");
+                    }
+                    text
+                }
+            }
+        };
+        let text = match conv.is_ascii_uppercase() {
+            true => text.to_ascii_uppercase(),
+            false => text,
+        };
+        let zeros = value.is_finite().then_some(sign.len());
+        (format!("{sign}{text}").into_bytes(), zeros)
+    }
+
+    /// Pad a converted argument to the field width, with spaces, or with
+    /// zeros at some position for the `0` flag.
+    fn pad(&self, mut text: Vec<u8>, zeros: Option<usize>) -> Vec<u8> {
+        let Some(fill) = self.width.checked_sub(text.len()) else {
+            return text;
+        };
+        if self.flags.contains(&b'-') {
+            text.resize(self.width, b' ');
+        } else if let Some(at) = zeros
+            && self.flags.contains(&b'0')
+        {
+            text.splice(at..at, std::iter::repeat_n(b'0', fill));
+        } else {
+            text.splice(0..0, std::iter::repeat_n(b' ', fill));
+        }
+        text
+    }
+}
+
+/// Format a number in the style of `%e`, with an exponent of at least two
+/// digits.
+fn exponent(value: f64, precision: usize) -> String {
+    let text = format!("{value:.precision$e}");
+    let (mantissa, exp) = text.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    format!("{mantissa}e{exp:+03}")
+}
+
+/// Parse a decimal number at the start of a format, if any.
+fn digits(format: &mut &[u8]) -> Option<usize> {
+    let len = format.iter().take_while(|c| c.is_ascii_digit()).count();
+    let (digits, rest) = format.split_at(len);
+    *format = rest;
+    std::str::from_utf8(digits).unwrap().parse().ok()
+}
+
+/// The bits of an argument, as an integer.
+fn int(arg: Scalar) -> i64 {
+    match arg {
+        Scalar::Int(value) => value,
+        Scalar::Float(value) => value.to_bits() as i64,
+        Scalar::Ptr(ptr) => ptr.addr as i64,
+    }
+}
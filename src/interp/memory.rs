@@ -0,0 +1,444 @@
+//! The memory of an interpreted program.
+//!
+//! Memory is a collection of separate objects, each a vector of bytes: a
+//! variable, a string literal, a block from `malloc()`, or a function.
+//! Objects get addresses in a single address space, so that pointers can be
+//! compared, subtracted and converted to integers, but never sit next to
+//! each other: a pointer past the end of an object does not point into
+//! another one.  Addresses are not reused, even once an object is freed.
+//!
+//! Besides its address, a pointer carries the object it was derived from
+//! (its provenance), and can only be used to access that object, within its
+//! bounds and while it is alive.  Pointers stored in memory keep their
+//! provenance; pointers made up from integers take that of the object their
+//! address falls in, if any.
+//!
+//! Memory also keeps what `--check-ub` needs to know about the bytes of
+//! objects: whether they have been initialized, and the types of the values
+//! stored in objects that have no declared type.
+
+use std::collections::BTreeMap;
+
+use crate::ctype::CType;
+
+/// The address of the first object.
+///
+/// Leaving the lowest addresses unused keeps small integers cast to
+/// pointers from pointing anywhere.
+const FIRST_ADDRESS: u64 = 0x1000;
+
+/// The alignment of objects, which suits every C type.
+const ALIGN: u64 = 16;
+
+//----------- Pointer ----------------------------------------------------------
+
+/// The identity of an object.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ObjectId(pub usize);
+
+/// A pointer.
+#[derive(Copy, Clone, Debug)]
+pub struct Pointer {
+    /// The address pointed to.
+    pub addr: u64,
+
+    /// The object the pointer was derived from, if any.
+    pub object: Option<ObjectId>,
+}
+
+impl Pointer {
+    /// The null pointer.
+    pub const NULL: Self = Self {
+        addr: 0,
+        object: None,
+    };
+
+    /// Offset the pointer by a number of bytes, keeping its provenance.
+    pub fn offset(self, bytes: i64) -> Self {
+        Self {
+            addr: self.addr.wrapping_add_signed(bytes),
+            object: self.object,
+        }
+    }
+}
+
+//----------- Object -----------------------------------------------------------
+
+/// What an object is for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// A variable with static storage duration.
+    Static,
+
+    /// A variable with automatic storage duration, or a temporary.
+    Auto,
+
+    /// A string literal, which may not be modified.
+    Str,
+
+    /// A block allocated by `malloc()`.
+    Heap,
+
+    /// A function, with its name, which has no bytes to access.
+    Function(String),
+}
+
+/// An object in memory.
+pub struct Object {
+    /// The address of the first byte.
+    pub base: u64,
+
+    /// The contents.
+    pub bytes: Vec<u8>,
+
+    /// Whether each byte has been initialized.
+    init: Vec<bool>,
+
+    /// The objects pointed into by the pointers stored in the object, by
+    /// offset.
+    provenance: BTreeMap<u64, ObjectId>,
+
+    /// The declared type of the object, if it has one.
+    pub declared: Option<CType>,
+
+    /// The sizes and types of the values stored in an object without a
+    /// declared type, by offset.
+    types: BTreeMap<u64, (u64, CType)>,
+
+    /// What the object is for.
+    pub kind: Kind,
+
+    /// Whether the lifetime of the object has not ended yet.
+    pub live: bool,
+}
+
+//----------- Memory -----------------------------------------------------------
+
+/// The objects of a program.
+pub struct Memory {
+    /// The objects, indexed by [`ObjectId`].
+    objects: Vec<Object>,
+
+    /// The objects by base address.
+    bases: BTreeMap<u64, ObjectId>,
+
+    /// The lowest address past every object.
+    next: u64,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self {
+            objects: Vec::new(),
+            bases: BTreeMap::new(),
+            next: FIRST_ADDRESS,
+        }
+    }
+}
+
+//--- Objects
+
+impl Memory {
+    /// Create an object with some contents, returning a pointer to it.
+    ///
+    /// The contents of automatic variables and heap blocks start out
+    /// uninitialized.
+    pub fn alloc(
+        &mut self,
+        bytes: Vec<u8>,
+        kind: Kind,
+        declared: Option<CType>,
+    ) -> Pointer {
+        let id = ObjectId(self.objects.len());
+        let base = self.next;
+        // Leave a gap after every object, even an empty one.
+        self.next = (base + bytes.len() as u64 + 1).next_multiple_of(ALIGN);
+        let init = !matches!(kind, Kind::Auto | Kind::Heap);
+        self.objects.push(Object {
+            base,
+            init: vec![init; bytes.len()],
+            bytes,
+            provenance: BTreeMap::new(),
+            declared,
+            types: BTreeMap::new(),
+            kind,
+            live: true,
+        });
+        self.bases.insert(base, id);
+        Pointer {
+            addr: base,
+            object: Some(id),
+        }
+    }
+
+    /// Look up an object.
+    pub fn get(&self, id: ObjectId) -> &Object {
+        &self.objects[id.0]
+    }
+
+    /// End the lifetime of an object.
+    pub fn kill(&mut self, id: ObjectId) {
+        let object = &mut self.objects[id.0];
+        object.live = false;
+        object.bytes = Vec::new();
+        object.init = Vec::new();
+        object.provenance.clear();
+        object.types.clear();
+    }
+
+    /// Free a block allocated by `malloc()`; freeing null does nothing.
+    pub fn free(&mut self, ptr: Pointer) -> Result<(), String> {
+        if ptr.addr == 0 {
+            return Ok(());
+        }
+        let object = ptr.object.map(|id| self.get(id));
+        match object {
+            Some(object)
+                if object.kind == Kind::Heap && object.base == ptr.addr =>
+            {
+                if !object.live {
+                    return Err("double free".into());
+                }
+                self.kill(ptr.object.unwrap());
+                Ok(())
+            }
+            _ => Err(format!(
+                "free of {:#x}, which was not returned by `malloc()`",
+                ptr.addr
+            )),
+        }
+    }
+
+    /// The pointer to an address, with the provenance of the object it falls
+    /// in (or just past).
+    pub fn pointer(&self, addr: u64) -> Pointer {
+        let object = self
+            .bases
+            .range(..=addr)
+            .next_back()
+            .map(|(_, &id)| id)
+            .filter(|&id| {
+                let object = self.get(id);
+                addr <= object.base + object.bytes.len() as u64
+            });
+        Pointer { addr, object }
+    }
+
+    /// The name of the function a pointer points to.
+    pub fn function(&self, ptr: Pointer) -> Result<&str, String> {
+        let object = ptr.object.map(|id| self.get(id));
+        match object {
+            Some(Object {
+                base,
+                kind: Kind::Function(name),
+                ..
+            }) if *base == ptr.addr => Ok(name),
+            _ if ptr.addr < FIRST_ADDRESS => {
+                Err("call through a null pointer".into())
+            }
+            _ => Err(format!(
+                "call through {:#x}, which is not a function",
+                ptr.addr
+            )),
+        }
+    }
+}
+
+//--- Accesses
+
+impl Memory {
+    /// Check an access of some bytes through a pointer, returning the
+    /// object and the offset accessed.
+    pub fn access(
+        &self,
+        ptr: Pointer,
+        size: u64,
+    ) -> Result<(ObjectId, u64), String> {
+        let Some(id) = ptr.object else {
+            // Members of a struct at a null address are below any object.
+            return Err(match ptr.addr {
+                ..FIRST_ADDRESS => "null pointer dereference".into(),
+                addr => format!("access to {addr:#x}, which is in no object"),
+            });
+        };
+        let object = self.get(id);
+        if let Kind::Function(name) = &object.kind {
+            return Err(format!("access to the bytes of function `{name}`"));
+        }
+        if !object.live {
+            return Err(match object.kind {
+                Kind::Heap => "use of freed memory".into(),
+                _ => "use of a variable after its lifetime ended".into(),
+            });
+        }
+        let offset = ptr.addr.wrapping_sub(object.base);
+        let len = object.bytes.len() as u64;
+        if ptr.addr < object.base || offset + size > len {
+            return Err(format!(
+                "out-of-bounds access of {size} bytes at offset {} of an \
+                 object of {len} bytes",
+                ptr.addr as i64 - object.base as i64
+            ));
+        }
+        Ok((id, offset))
+    }
+
+    /// Read some bytes.
+    pub fn read(&self, ptr: Pointer, size: u64) -> Result<&[u8], String> {
+        let (id, offset) = self.access(ptr, size)?;
+        let start = offset as usize;
+        Ok(&self.get(id).bytes[start..start + size as usize])
+    }
+
+    /// Whether some bytes have all been initialized.
+    pub fn initialized(&self, ptr: Pointer, size: u64) -> Result<bool, String> {
+        let (id, offset) = self.access(ptr, size)?;
+        let start = offset as usize;
+        let init = &self.get(id).init[start..start + size as usize];
+        Ok(init.iter().all(|&init| init))
+    }
+
+    /// Write some bytes.
+    pub fn write(&mut self, ptr: Pointer, bytes: &[u8]) -> Result<(), String> {
+        let (id, offset) = self.access(ptr, bytes.len() as u64)?;
+        let object = &mut self.objects[id.0];
+        if object.kind == Kind::Str {
+            return Err("write to a string literal".into());
+        }
+        let start = offset as usize;
+        object.bytes[start..start + bytes.len()].copy_from_slice(bytes);
+        object.init[start..start + bytes.len()].fill(true);
+        // Pointers and typed values partly overwritten are gone.
+        let end = offset + bytes.len() as u64;
+        let stale: Vec<_> = object
+            .provenance
+            .range(offset.saturating_sub(7)..end)
+            .map(|(&offset, _)| offset)
+            .collect();
+        for offset in stale {
+            object.provenance.remove(&offset);
+        }
+        let stale: Vec<_> = object
+            .types
+            .range(..end)
+            .rev()
+            .take_while(|&(&start, &(size, _))| start + size > offset)
+            .map(|(&offset, _)| offset)
+            .collect();
+        for offset in stale {
+            object.types.remove(&offset);
+        }
+        Ok(())
+    }
+
+    /// The types of the values stored in some bytes of an object without a
+    /// declared type, with their offsets from the pointer and their sizes.
+    pub fn stored_types(
+        &self,
+        ptr: Pointer,
+        size: u64,
+    ) -> Result<Vec<(i64, u64, &CType)>, String> {
+        let (id, offset) = self.access(ptr, size)?;
+        let mut types: Vec<_> = self
+            .get(id)
+            .types
+            .range(..offset + size)
+            .rev()
+            .take_while(|&(&start, &(len, _))| start + len > offset)
+            .map(|(&start, (len, ty))| (start as i64 - offset as i64, *len, ty))
+            .collect();
+        types.reverse();
+        Ok(types)
+    }
+
+    /// Record the type of a value just stored in an object without a
+    /// declared type.
+    pub fn set_type(
+        &mut self,
+        ptr: Pointer,
+        size: u64,
+        ty: CType,
+    ) -> Result<(), String> {
+        let (id, offset) = self.access(ptr, size)?;
+        self.objects[id.0].types.insert(offset, (size, ty));
+        Ok(())
+    }
+
+    /// Read a pointer.
+    pub fn read_ptr(&self, ptr: Pointer) -> Result<Pointer, String> {
+        let bytes = self.read(ptr, 8)?;
+        let addr = u64::from_le_bytes(bytes.try_into().unwrap());
+        let (id, offset) = self.access(ptr, 8)?;
+        match self.get(id).provenance.get(&offset) {
+            Some(&object) => Ok(Pointer {
+                addr,
+                object: Some(object),
+            }),
+            None => Ok(self.pointer(addr)),
+        }
+    }
+
+    /// Write a pointer.
+    pub fn write_ptr(
+        &mut self,
+        ptr: Pointer,
+        value: Pointer,
+    ) -> Result<(), String> {
+        self.write(ptr, &value.addr.to_le_bytes())?;
+        if let Some(object) = value.object {
+            let (id, offset) = self.access(ptr, 8)?;
+            self.objects[id.0].provenance.insert(offset, object);
+        }
+        Ok(())
+    }
+
+    /// Copy some bytes, along with whether they are initialized, the
+    /// provenance of the pointers among them and the types of the values
+    /// among them.
+    pub fn copy(
+        &mut self,
+        dest: Pointer,
+        src: Pointer,
+        size: u64,
+    ) -> Result<(), String> {
+        let bytes = self.read(src, size)?.to_vec();
+        let (src_id, src_offset) = self.access(src, size)?;
+        let object = self.get(src_id);
+        let (start, end) = (src_offset, src_offset + size);
+        let init = object.init[start as usize..end as usize].to_vec();
+        let pointers: Vec<_> = object
+            .provenance
+            .range(start..end)
+            .filter(|&(&offset, _)| offset + 8 <= end)
+            .map(|(&offset, &object)| (offset - start, object))
+            .collect();
+        let types: Vec<_> = object
+            .types
+            .range(start..end)
+            .filter(|&(&offset, &(len, _))| offset + len <= end)
+            .map(|(&offset, ty)| (offset - start, ty.clone()))
+            .collect();
+        self.write(dest, &bytes)?;
+        let (dest_id, dest_offset) = self.access(dest, size)?;
+        let object = &mut self.objects[dest_id.0];
+        let start = dest_offset as usize;
+        object.init[start..start + init.len()].copy_from_slice(&init);
+        for (offset, pointee) in pointers {
+            object.provenance.insert(dest_offset + offset, pointee);
+        }
+        for (offset, ty) in types {
+            object.types.insert(dest_offset + offset, ty);
+        }
+        Ok(())
+    }
+
+    /// Read a null-terminated string, without its terminator.
+    pub fn cstring(&self, ptr: Pointer) -> Result<Vec<u8>, String> {
+        let (id, offset) = self.access(ptr, 0)?;
+        let rest = &self.get(id).bytes[offset as usize..];
+        match rest.iter().position(|&byte| byte == 0) {
+            Some(len) => Ok(rest[..len].to_vec()),
+            None => Err("unterminated string".into()),
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! Checks for undefined behavior, enabled by `--check-ub`.
+//!
+//! Accesses outside of live objects are always errors, since the
+//! interpreter has nothing to read or write there.  With `--check-ub`, the
+//! interpreter also stops at:
+//!
+//! - signed overflow, and shifts by negative or too large amounts;
+//! - pointer arithmetic leaving the bounds of an object (one past the end is
+//!   fine), and subtracting or ordering pointers into different objects;
+//! - reads of uninitialized memory, except through character types;
+//! - accesses at addresses not aligned for their type;
+//! - accesses violating the strict aliasing rule: a scalar object may only
+//!   be accessed through its own type, the same type of the other
+//!   signedness, or a character type.  Objects without a declared type, like
+//!   those from `malloc()`, take the type of the value last stored in them.
+//!   All pointer types are treated as one;
+//! - an object modified twice, or modified and read, by operations that are
+//!   not sequenced with respect to each other, such as the operands of `+`
+//!   or the arguments of a call.
+//!
+//! Accesses made by the called functions are sequenced with respect to the
+//! caller, so each call keeps its own log of accesses; the log is cleared at
+//! the start of every full expression.  The sequence points within a full
+//! expression, after the first operand of `,`, `&&`, `||` and `?:` and
+//! after the arguments of a call, complete the accesses logged before them:
+//! these are sequenced before the rest of the operation, like the store of
+//! an enclosing assignment, but not before the operands of the enclosing
+//! operators.
+
+use crate::ctype::CType;
+use crate::hir::BinOp;
+
+use super::memory::{Kind, Pointer};
+use super::{Exec, Interpreter, is_char, signed};
+
+/// An access made while evaluating a full expression.
+#[derive(Copy, Clone, Debug)]
+pub struct Access {
+    /// The address of the first byte accessed.
+    addr: u64,
+
+    /// The number of bytes accessed.
+    size: u64,
+
+    /// Whether the access is a write.
+    write: bool,
+
+    /// Whether a sequence point has passed since the access.
+    complete: bool,
+}
+
+impl Access {
+    /// Whether two accesses conflict, when they are unsequenced.
+    fn conflicts(self, other: Self) -> bool {
+        (self.write || other.write)
+            && self.addr < other.addr + other.size
+            && other.addr < self.addr + self.size
+    }
+}
+
+//--- Arithmetic
+
+impl Interpreter<'_> {
+    /// Check an integer operation on operands of its result type.
+    pub(super) fn check_arith(
+        &self,
+        op: BinOp,
+        a: i64,
+        b: i64,
+        ty: &CType,
+    ) -> Exec<()> {
+        let bits = self.checker.types.size_of(ty) as i64 * 8;
+        if matches!(op, BinOp::Shl | BinOp::Shr) && !(0..bits).contains(&b) {
+            let ty = self.checker.types.named(ty);
+            let message = format!("shift of `{ty}` by {b} bits");
+            return Err(self.error(message));
+        }
+        if !signed(ty) {
+            return Ok(());
+        }
+        let (wide_a, wide_b) = (a as i128, b as i128);
+        let exact = match op {
+            BinOp::Add => wide_a + wide_b,
+            BinOp::Sub => wide_a - wide_b,
+            BinOp::Mul => wide_a * wide_b,
+            // The remainder is undefined whenever the quotient is.
+            BinOp::Div | BinOp::Rem => wide_a / wide_b,
+            BinOp::Shl if a < 0 => {
+                let message = format!("left shift of negative value {a}");
+                return Err(self.error(message));
+            }
+            BinOp::Shl => wide_a << b,
+            _ => return Ok(()),
+        };
+        let max = (1 << (bits - 1)) - 1;
+        if !(-max - 1..=max).contains(&exact) {
+            let message = format!(
+                "signed overflow: {a} {op:?} {b} does not fit in `{ty:?}`"
+            );
+            return Err(self.error(message));
+        }
+        Ok(())
+    }
+
+    /// Check pointer arithmetic, from a pointer to a result.
+    pub(super) fn check_offset(
+        &self,
+        ptr: Pointer,
+        result: Pointer,
+    ) -> Exec<()> {
+        let Some(id) = ptr.object else {
+            if ptr.addr == 0 && result.addr != 0 {
+                return Err(self.error("arithmetic on a null pointer"));
+            }
+            return Ok(());
+        };
+        let object = self.memory.get(id);
+        if !object.live {
+            return Err(self.error(match object.kind {
+                Kind::Heap => "arithmetic on a pointer to freed memory",
+                _ => "arithmetic on a pointer to a dead variable",
+            }));
+        }
+        let len = object.bytes.len() as u64;
+        if result.addr < object.base || result.addr > object.base + len {
+            let message = format!(
+                "pointer arithmetic to offset {} of an object of {len} bytes",
+                result.addr as i64 - object.base as i64
+            );
+            return Err(self.error(message));
+        }
+        Ok(())
+    }
+
+    /// Check the subtraction or ordering of two pointers.
+    pub(super) fn check_pointers(
+        &self,
+        op: BinOp,
+        lhs: Pointer,
+        rhs: Pointer,
+    ) -> Exec<()> {
+        if lhs.object == rhs.object {
+            return Ok(());
+        }
+        match op {
+            BinOp::Sub => {
+                Err(self.error("subtraction of pointers to different objects"))
+            }
+            BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge => {
+                Err(self.error("comparison of pointers to different objects"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+//--- Accesses
+
+impl Interpreter<'_> {
+    /// Check a scalar access to memory that is in bounds.
+    ///
+    /// Writes to objects without a declared type set their type.
+    pub(super) fn check_access(
+        &mut self,
+        ptr: Pointer,
+        ty: &CType,
+        write: bool,
+    ) -> Exec<()> {
+        let size = self.checker.types.size_of(ty);
+        if !ptr.addr.is_multiple_of(self.checker.types.align_of(ty)) {
+            let ty = self.checker.types.named(ty);
+            let message =
+                format!("misaligned access of `{ty}` at {:#x}", ptr.addr);
+            return Err(self.error(message));
+        }
+        if !write
+            && !is_char(ty)
+            && !self.fault(self.memory.initialized(ptr, size))?
+        {
+            let ty = self.checker.types.named(ty);
+            let message = format!("read of an uninitialized `{ty}`");
+            return Err(self.error(message));
+        }
+
+        let (id, offset) = self.fault(self.memory.access(ptr, size))?;
+        let stored = match self.memory.get(id).declared.clone() {
+            _ if is_char(ty) => None,
+            Some(declared) => match self.scalar_at(&declared, offset) {
+                (0, scalar) if compatible(&scalar, ty) => None,
+                (_, scalar) => Some(scalar),
+            },
+            None if write => {
+                let result = self.memory.set_type(ptr, size, ty.clone());
+                self.fault(result)?;
+                None
+            }
+            None => {
+                let result = self.memory.stored_types(ptr, size);
+                match self.fault(result)?[..] {
+                    [] => None,
+                    [(0, len, scalar)]
+                        if len == size && compatible(scalar, ty) =>
+                    {
+                        None
+                    }
+                    [(_, _, scalar), ..] => Some(scalar.clone()),
+                }
+            }
+        };
+        if let Some(stored) = stored {
+            let message = format!(
+                "access to an object of type `{}` through an lvalue of type \
+                 `{}`",
+                self.checker.types.named(&stored),
+                self.checker.types.named(ty),
+            );
+            return Err(self.error(message));
+        }
+
+        self.log(ptr, size, write);
+        Ok(())
+    }
+
+    /// The innermost scalar of a type at an offset, or the aggregate
+    /// holding the padding at the offset, with the offset into it.
+    fn scalar_at(&self, ty: &CType, offset: u64) -> (u64, CType) {
+        match ty {
+            CType::Array { elem, .. } => {
+                let size = self.checker.types.size_of(elem).max(1);
+                self.scalar_at(elem, offset % size)
+            }
+            CType::Struct(id) => {
+                let fields = self.checker.types.get(*id).fields.as_ref();
+                let layout = self.checker.types.layout(*id);
+                let field = fields.unwrap().iter().zip(&layout.offsets).find(
+                    |&(field, &start)| {
+                        let size = self.checker.types.size_of(&field.ty);
+                        (start..start + size).contains(&offset)
+                    },
+                );
+                match field {
+                    Some((field, start)) => {
+                        self.scalar_at(&field.ty, offset - start)
+                    }
+                    None => (offset, ty.clone()),
+                }
+            }
+            _ => (offset, ty.clone()),
+        }
+    }
+}
+
+/// Whether an object of some type may be accessed through an lvalue of
+/// another type.
+fn compatible(object: &CType, lvalue: &CType) -> bool {
+    match (object, lvalue) {
+        (CType::Int(object), CType::Int(lvalue)) => object.rank == lvalue.rank,
+        (CType::Pointer(_), CType::Pointer(_)) => true,
+        _ => object == lvalue,
+    }
+}
+
+//--- Sequencing
+
+impl Interpreter<'_> {
+    /// Start a full expression, after which every side effect so far is
+    /// complete.
+    pub(super) fn sequence_point(&mut self) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.accesses.clear();
+        }
+    }
+
+    /// The position in the log of accesses of the current call.
+    pub(super) fn mark(&self) -> usize {
+        self.frames.last().map_or(0, |frame| frame.accesses.len())
+    }
+
+    /// Pass a sequence point within a full expression, completing the
+    /// accesses logged since a mark.
+    pub(super) fn complete(&mut self, start: usize) {
+        if let Some(frame) = self.frames.last_mut() {
+            for access in &mut frame.accesses[start..] {
+                access.complete = true;
+            }
+        }
+    }
+
+    /// Log an access.
+    pub(super) fn log(&mut self, ptr: Pointer, size: u64, write: bool) {
+        if self.check_ub
+            && let Some(frame) = self.frames.last_mut()
+        {
+            let addr = ptr.addr;
+            let complete = false;
+            frame.accesses.push(Access {
+                addr,
+                size,
+                write,
+                complete,
+            });
+        }
+    }
+
+    /// Check that the accesses made by the evaluations of unsequenced
+    /// operands, between consecutive marks, do not conflict.
+    pub(super) fn unsequenced(&self, marks: &[usize]) -> Exec<()> {
+        let Some(frame) = self.frames.last().filter(|_| self.check_ub) else {
+            return Ok(());
+        };
+        let operands: Vec<_> = marks
+            .windows(2)
+            .map(|marks| &frame.accesses[marks[0]..marks[1]])
+            .collect();
+        for (i, a) in operands.iter().enumerate() {
+            for b in &operands[i + 1..] {
+                if a.iter().any(|&a| b.iter().any(|&b| a.conflicts(b))) {
+                    return Err(self.error(
+                        "unsequenced accesses to the same object, one of \
+                         them a modification",
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the store just made by an assignment does not conflict
+    /// with the side effects of its operands, evaluated since a mark, that
+    /// are not complete.
+    pub(super) fn modified(&self, start: usize) -> Exec<()> {
+        let Some(frame) = self.frames.last() else {
+            return Ok(());
+        };
+        let Some((&store, operands)) = frame.accesses[start..].split_last()
+        else {
+            return Ok(());
+        };
+        let pending = |a: &&Access| a.write && !a.complete;
+        if operands.iter().filter(pending).any(|&a| a.conflicts(store)) {
+            return Err(self.error("unsequenced modifications of an object"));
+        }
+        Ok(())
+    }
+}
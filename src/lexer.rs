@@ -1,53 +1,85 @@
 //! Tokenizing source code.
+//!
+//! Comments and preprocessing directives are trivia: they are skipped like
+//! whitespace, so that source which has not been preprocessed can still be
+//! parsed (e.g. by `sea fmt`), and [`trivia()`] finds them again.
 
 //----------- LexerContext -----------------------------------------------------
 
 use std::{
     borrow::{Borrow, BorrowMut},
+    collections::HashSet,
     ops::{Deref, DerefMut},
 };
 
+use crate::hir::{Arena, Names, Span, Symbol};
+
 /// Context for a [`Lexer`].
-pub struct LexerContext<'src> {
+pub struct LexerContext<'src, 'hir> {
     /// The complete input string.
     input: &'src str,
 
+    /// The arena to allocate nodes in.
+    arena: &'hir Arena<'hir>,
+
+    /// The names of the symbols interned so far.
+    names: Names,
+
     /// The current offset into the input.
     offset: usize,
+
+    /// The offset past the last token lexed.
+    end: usize,
+
+    /// The names declared by `typedef` so far.
+    ///
+    /// C cannot be parsed without knowing which identifiers name types (e.g.
+    /// `(x) - y` is a cast only if `x` is a type), so the set is tracked here.
+    typedefs: HashSet<Symbol>,
+
+    /// The furthest offset that lexing failed at, and the tokens that were
+    /// expected there.
+    ///
+    /// The parser backtracks over failures, so that the one it ends with is
+    /// usually at the start of a declaration; the furthest one is where the
+    /// input stops making sense.
+    furthest: ParseError,
 }
 
 //--- Construction
 
-impl<'src> LexerContext<'src> {
+impl<'src, 'hir> LexerContext<'src, 'hir> {
     /// Construct a new [`LexerContext`].
-    pub const fn new(input: &'src str) -> Self {
-        Self { input, offset: 0 }
+    pub fn new(input: &'src str, arena: &'hir Arena<'hir>) -> Self {
+        Self {
+            input,
+            arena,
+            names: Names::default(),
+            offset: 0,
+            end: 0,
+            typedefs: HashSet::new(),
+            furthest: ParseError {
+                offset: 0,
+                expected: Vec::new(),
+            },
+        }
     }
 }
 
 //--- Interaction
 
-impl<'src> LexerContext<'src> {
+impl<'src, 'hir> LexerContext<'src, 'hir> {
     /// Create a [`Lexer`] from the start of the input.
-    pub fn start(&mut self) -> Lexer<'_, 'src> {
-        // Skip any initial whitespace.
-        let start = self
-            .input
-            .find(|c: char| !c.is_ascii_whitespace())
-            .unwrap_or(self.input.len());
+    pub fn start(&mut self) -> Lexer<'_, 'src, 'hir> {
+        // Skip any initial trivia.
+        let start = trivia_len(self.input, true);
+        self.offset = start;
+        self.end = start;
 
         Lexer {
             context: self,
             start,
-        }
-    }
-
-    /// Create a [`Lexer`] from the current input offset.
-    const fn resume(&mut self) -> Lexer<'_, 'src> {
-        let start = self.offset;
-        Lexer {
-            context: self,
-            start,
+            end: start,
         }
     }
 }
@@ -55,32 +87,104 @@ impl<'src> LexerContext<'src> {
 //----------- Lexer ------------------------------------------------------------
 
 /// An on-demand lexer.
-pub struct Lexer<'ctx, 'src> {
+pub struct Lexer<'ctx, 'src, 'hir> {
     /// Context for the lexer.
-    context: &'ctx mut LexerContext<'src>,
+    context: &'ctx mut LexerContext<'src, 'hir>,
 
     /// The start point of this lexer instance.
     start: usize,
+
+    /// The end of the last token lexed before this lexer instance.
+    end: usize,
 }
 
 //--- Interaction
 
-impl<'src, 'ctx> Lexer<'ctx, 'src> {
+impl<'src, 'ctx, 'hir> Lexer<'ctx, 'src, 'hir> {
     /// The input waiting to be lexed.
     pub fn input(&self) -> &'src str {
         &self.context.input[self.context.offset..]
     }
-    
+
     pub fn remaining_chars(&self) -> usize {
         self.input().len()
     }
 
+    /// The current offset into the input.
+    pub fn offset(&self) -> usize {
+        self.context.offset
+    }
+
+    /// The span from an offset to the end of the last token lexed.
+    ///
+    /// The span is empty if no token was lexed since the offset.
+    pub fn span(&self, start: usize) -> Span {
+        Span {
+            start,
+            end: self.context.end.max(start),
+        }
+    }
+
+    /// The arena to allocate nodes in.
+    pub fn arena(&self) -> &'hir Arena<'hir> {
+        self.context.arena
+    }
+
+    /// The symbol for a name, interning it if it is new.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        self.context.names.intern(name)
+    }
+
+    /// Take the names of the symbols interned so far.
+    pub fn take_names(&mut self) -> Names {
+        std::mem::take(&mut self.context.names)
+    }
+
+    /// Whether an identifier has been declared as a type name.
+    pub fn is_typedef(&self, name: Symbol) -> bool {
+        self.context.typedefs.contains(&name)
+    }
+
+    /// Declare an identifier as a type name.
+    pub fn declare_typedef(&mut self, name: Symbol) {
+        self.context.typedefs.insert(name);
+    }
+
+    /// Construct an error at the current offset.
+    pub fn error(&mut self, expected: &str) -> ParseError {
+        self.fail(self.context.offset, expected)
+    }
+
+    /// Construct an error at an offset, recording it if it is the furthest.
+    fn fail(&mut self, offset: usize, expected: &str) -> ParseError {
+        let furthest = &mut self.context.furthest;
+        if offset > furthest.offset {
+            furthest.offset = offset;
+            furthest.expected.clear();
+        }
+        if offset == furthest.offset
+            && !furthest.expected.iter().any(|e| **e == *expected)
+        {
+            furthest.expected.push(expected.into());
+        }
+        ParseError {
+            offset,
+            expected: vec![expected.into()],
+        }
+    }
+
+    /// The furthest failure so far, with everything expected there.
+    pub fn furthest(&self) -> ParseError {
+        self.context.furthest.clone()
+    }
+
     /// Create a new [`Lexer`] from the current offset.
-    pub fn delegate(&mut self) -> Lexer<'_, 'src> {
-        let start = self.context.offset;
+    pub fn delegate(&mut self) -> Lexer<'_, 'src, 'hir> {
+        let (start, end) = (self.context.offset, self.context.end);
         Lexer {
             context: &mut *self.context,
             start,
+            end,
         }
     }
 
@@ -88,32 +192,34 @@ impl<'src, 'ctx> Lexer<'ctx, 'src> {
     pub fn finish<T>(mut self, value: T) -> ParseOutput<T> {
         // Mark the lexer as complete.
         self.start = self.context.offset;
+        self.end = self.context.end;
         ParseOutput(value)
     }
 }
 
 //--- Drop
 
-impl<'src, 'ctx> Drop for Lexer<'src, 'ctx> {
+impl Drop for Lexer<'_, '_, '_> {
     /// Drop the lexer, rewinding if it was dropped too early.
     fn drop(&mut self) {
         self.context.offset = self.start;
+        self.context.end = self.end;
     }
 }
 
 //--- Actual lexing
 
-impl<'src, 'ctx> Lexer<'src, 'ctx> {
-    /// Trim whitespace.
+impl<'src> Lexer<'_, 'src, '_> {
+    /// Trim whitespace and other trivia, after a token.
     ///
-    /// Returns whether any whitespace was parsed.
+    /// Returns whether any trivia was parsed.
     pub fn trim_ws(&mut self) -> bool {
-        let input = self.input();
-        let num_ws = input
-            .find(|c: char| !c.is_ascii_whitespace())
-            .unwrap_or(input.len());
-        self.context.offset += num_ws;
-        num_ws != 0
+        self.context.end = self.context.offset;
+        let before = &self.context.input[..self.context.offset];
+        let line = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+        let len = trivia_len(self.input(), line.trim().is_empty());
+        self.context.offset += len;
+        len != 0
     }
 
     /// Parse a specific symbol.
@@ -125,10 +231,27 @@ impl<'src, 'ctx> Lexer<'src, 'ctx> {
             self.trim_ws();
             Ok(())
         } else {
-            Err(ParseError {
-                offset: self.context.offset,
-                expected: vec![format!("'{symbol}'").into_boxed_str()],
-            })
+            Err(self.fail(self.context.offset, &format!("'{symbol}'")))
+        }
+    }
+
+    /// Parse a specific punctuator.
+    ///
+    /// Unlike [`Self::symbol()`], this fails if the input starts with a longer
+    /// punctuator that has `punct` as a prefix (e.g. `-` will not match `->`).
+    pub fn punct(&mut self, punct: &str) -> Result<(), ParseError> {
+        let input = self.input();
+        let longest = PUNCTUATORS
+            .iter()
+            .filter(|p| input.starts_with(*p))
+            .map(|p| p.len())
+            .max();
+        if longest == Some(punct.len()) && input.starts_with(punct) {
+            self.context.offset += punct.len();
+            self.trim_ws();
+            Ok(())
+        } else {
+            Err(self.fail(self.context.offset, &format!("'{punct}'")))
         }
     }
 
@@ -141,15 +264,15 @@ impl<'src, 'ctx> Lexer<'src, 'ctx> {
             .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
             .unwrap_or(input.len());
 
-        if len != 0 && !input.starts_with(|c: char| c.is_ascii_digit()) {
+        if len != 0
+            && !input.starts_with(|c: char| c.is_ascii_digit())
+            && !KEYWORDS.contains(&&input[..len])
+        {
             self.context.offset += len;
             self.trim_ws();
             Ok(&input[..len])
         } else {
-            Err(ParseError {
-                offset: self.context.offset,
-                expected: vec!["identifier".into()],
-            })
+            Err(self.error("identifier"))
         }
     }
 
@@ -164,14 +287,299 @@ impl<'src, 'ctx> Lexer<'src, 'ctx> {
             self.trim_ws();
             Ok(())
         } else {
-            Err(ParseError {
-                offset: self.context.offset,
-                expected: vec![format!("'{keyword}'").into_boxed_str()],
-            })
+            Err(self.fail(self.context.offset, &format!("'{keyword}'")))
         }
     }
+
+    /// Parse a numeric constant.
+    ///
+    /// This returns the raw text of the constant (a C "preprocessing number"),
+    /// leaving its interpretation to the caller.
+    pub fn number(&mut self) -> Result<&'src str, ParseError> {
+        let input = self.input();
+        let bytes = input.as_bytes();
+        let starts_number =
+            matches!(bytes, [b'0'..=b'9', ..] | [b'.', b'0'..=b'9', ..]);
+        if !starts_number {
+            return Err(self.error("number"));
+        }
+
+        let mut len = 1;
+        while let Some(&c) = bytes.get(len) {
+            let exponent = matches!(bytes[len - 1], b'e' | b'E' | b'p' | b'P');
+            if c.is_ascii_alphanumeric()
+                || c == b'_'
+                || c == b'.'
+                || (exponent && (c == b'+' || c == b'-'))
+            {
+                len += 1;
+            } else {
+                break;
+            }
+        }
+
+        self.context.offset += len;
+        self.trim_ws();
+        Ok(&input[..len])
+    }
+
+    /// Parse a character constant.
+    ///
+    /// If the input did not start with a character constant, fail.
+    pub fn char_lit(&mut self) -> Result<u8, ParseError> {
+        let lit = self.input().strip_prefix('\'').and_then(|rest| {
+            let (value, len) = unescape(rest.as_bytes())?;
+            (rest.as_bytes().get(len) == Some(&b'\'')).then_some((value, len))
+        });
+        let Some((value, len)) = lit else {
+            return Err(self.error("character constant"));
+        };
+
+        self.context.offset += len + 2;
+        self.trim_ws();
+        Ok(value)
+    }
+
+    /// Parse a string literal.
+    ///
+    /// Adjacent string literals are concatenated, as in C.  If the input did
+    /// not start with a string literal, fail.
+    pub fn string_lit(&mut self) -> Result<Vec<u8>, ParseError> {
+        let mut value = Vec::new();
+        let mut found = false;
+        while let Some(rest) = self.input().strip_prefix('"') {
+            let rest = rest.as_bytes();
+            let mut len = 0;
+            loop {
+                match rest.get(len) {
+                    Some(b'"') => break,
+                    Some(b'\n') | None => return Err(self.error("'\"'")),
+                    Some(_) => {
+                        let Some((c, n)) = unescape(&rest[len..]) else {
+                            let offset = self.context.offset + 1 + len;
+                            return Err(self.fail(offset, "escape sequence"));
+                        };
+                        value.push(c);
+                        len += n;
+                    }
+                }
+            }
+            self.context.offset += len + 2;
+            self.trim_ws();
+            found = true;
+        }
+
+        if found {
+            Ok(value)
+        } else {
+            Err(self.error("string literal"))
+        }
+    }
+}
+
+/// Decode a single (possibly escaped) character.
+///
+/// Returns the character value and the number of input bytes consumed.
+fn unescape(input: &[u8]) -> Option<(u8, usize)> {
+    match *input {
+        [b'\\', b'x', ref rest @ ..] => {
+            let len = rest.iter().take_while(|c| c.is_ascii_hexdigit()).count();
+            let digits = std::str::from_utf8(&rest[..len]).ok()?;
+            let value = u32::from_str_radix(digits, 16).ok()?;
+            Some((value as u8, len + 2))
+        }
+        [b'\\', b'0'..=b'7', ..] => {
+            let len = input[1..]
+                .iter()
+                .take(3)
+                .take_while(|c| (b'0'..=b'7').contains(*c))
+                .count();
+            let digits = std::str::from_utf8(&input[1..1 + len]).ok()?;
+            let value = u32::from_str_radix(digits, 8).ok()?;
+            Some((value as u8, len + 1))
+        }
+        [b'\\', c, ..] => {
+            let value = match c {
+                b'n' => b'\n',
+                b't' => b'\t',
+                b'r' => b'\r',
+                b'a' => 0x07,
+                b'b' => 0x08,
+                b'f' => 0x0C,
+                b'v' => 0x0B,
+                b'\\' | b'\'' | b'"' | b'?' => c,
+                _ => return None,
+            };
+            Some((value, 2))
+        }
+        [b'\n' | b'\'', ..] | [] => None,
+        [c, ..] => Some((c, 1)),
+    }
+}
+
+//----------- Trivia -----------------------------------------------------------
+
+/// The spans of the comments and directives in some source.
+pub fn trivia(input: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    scan(input, |span| spans.push(span));
+    spans
+}
+
+/// Scan through some source, reporting the span of each comment and
+/// directive.
+///
+/// Returns the end of the last token.  Tokens are not lexed properly; only
+/// literals are, so that their contents are not taken as trivia.
+fn scan(input: &str, mut trivia: impl FnMut(Span)) -> usize {
+    let bytes = input.as_bytes();
+    let (mut offset, mut end) = (0, 0);
+    let mut line_start = true;
+    while let Some(&c) = bytes.get(offset) {
+        if c.is_ascii_whitespace() {
+            line_start |= c == b'\n';
+        } else if let Some(len) = (c == b'/' || c == b'#')
+            .then(|| comment_len(&input[offset..], line_start))
+            .flatten()
+        {
+            let text = input[offset..offset + len].trim_end();
+            trivia(Span {
+                start: offset,
+                end: offset + text.len(),
+            });
+            offset += len;
+            continue;
+        } else if c == b'"' || c == b'\'' {
+            // Skip to the closing quote, or the end of the line.
+            let mut len = 1;
+            while let Some(&d) = bytes.get(offset + len) {
+                len += 1;
+                match d {
+                    b'\\' => len += 1,
+                    b'\n' => break,
+                    _ if d == c => break,
+                    _ => {}
+                }
+            }
+            offset = (offset + len).min(bytes.len());
+            end = offset;
+            line_start = false;
+            continue;
+        } else {
+            offset += input[offset..].chars().next().unwrap().len_utf8();
+            end = offset;
+            line_start = false;
+            continue;
+        }
+        offset += 1;
+    }
+    end
+}
+
+/// The length of the whitespace, comments and directives at the start of
+/// some input.
+///
+/// A `#` only starts a directive at the start of a line, which the input
+/// starts at if `line_start`.
+fn trivia_len(input: &str, mut line_start: bool) -> usize {
+    let bytes = input.as_bytes();
+    let mut len = 0;
+    while let Some(&c) = bytes.get(len) {
+        if c == b'\n' {
+            line_start = true;
+            len += 1;
+        } else if c.is_ascii_whitespace() {
+            len += 1;
+        } else if let Some(n) = comment_len(&input[len..], line_start) {
+            len += n;
+        } else {
+            break;
+        }
+    }
+    len
 }
 
+/// The length of the comment or directive at the start of some input, if
+/// there is one.
+///
+/// Line comments and directives end before the newline; the lines of a
+/// directive can be continued by a backslash before the newline.
+fn comment_len(input: &str, line_start: bool) -> Option<usize> {
+    if let Some(rest) = input.strip_prefix("/*") {
+        // An unterminated comment is left for the parser to reject.
+        return rest.find("*/").map(|end| end + 4);
+    }
+    if !(input.starts_with("//") || line_start && input.starts_with('#')) {
+        return None;
+    }
+    let mut len = 0;
+    loop {
+        let Some(end) = input[len..].find('\n') else {
+            return Some(input.len());
+        };
+        len += end;
+        if !input[..len].ends_with('\\') {
+            return Some(len);
+        }
+        len += 1;
+    }
+}
+
+/// The keywords of C, which cannot be used as identifiers.
+const KEYWORDS: &[&str] = &[
+    "_Alignas",
+    "_Alignof",
+    "_Bool",
+    "_Static_assert",
+    "__builtin_va_arg",
+    "__builtin_va_copy",
+    "__builtin_va_end",
+    "__builtin_va_list",
+    "__builtin_va_start",
+    "auto",
+    "break",
+    "case",
+    "char",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "typedef",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+];
+
+/// The punctuators of C.
+const PUNCTUATORS: &[&str] = &[
+    "[", "]", "(", ")", "{", "}", ".", "->", "++", "--", "&", "*", "+", "-",
+    "~", "!", "/", "%", "<<", ">>", "<", ">", "<=", ">=", "==", "!=", "^", "|",
+    "&&", "||", "?", ":", ";", "...", "=", "*=", "/=", "%=", "+=", "-=", "<<=",
+    ">>=", "&=", "^=", "|=", ",",
+];
+
 //----------- ParseResult ------------------------------------------------------
 
 /// The result of lexing/parsing.
@@ -240,3 +648,26 @@ pub struct ParseError {
     /// A list of tokens that were allowed to occur here.
     expected: Vec<Box<str>>,
 }
+
+//--- Inspection
+
+impl ParseError {
+    /// The byte offset of the error in the input.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+//--- Formatting
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.expected[..] {
+            [] => write!(f, "unexpected input"),
+            [expected] => write!(f, "expected {expected}"),
+            [expected @ .., last] => {
+                write!(f, "expected {} or {last}", expected.join(", "))
+            }
+        }
+    }
+}
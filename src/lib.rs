@@ -0,0 +1,45 @@
+//! A C compiler.
+//!
+//! C sources are parsed into the [HIR](hir), type checked ([`typeck`]),
+//! lowered to the [MIR](mir) and optimized there, and compiled to assembly,
+//! LLVM IR or object files for a [target](codegen::Target); programs can
+//! also be run by an [interpreter](interp) instead.
+//!
+//! [`compile()`] takes a source through all of it, e.g.:
+//!
+//! ```
+//! let options = sea::Options {
+//!     emit: sea::Emit::Asm,
+//!     ..Default::default()
+//! };
+//! let output = sea::compile("int main(void) { return 0; }", &options)
+//!     .unwrap();
+//! assert!(String::from_utf8(output.contents).unwrap().contains("main"));
+//! ```
+//!
+//! Sources must already be preprocessed: the `sea` driver runs the system
+//! preprocessor when they need it.  Every problem found in them is reported
+//! as a [`Diagnostic`].
+
+// The documentation of modules also describes their private parts.
+#![allow(rustdoc::private_intra_doc_links)]
+
+pub mod codegen;
+pub mod compile;
+pub mod ctype;
+mod dbg;
+pub mod diagnostics;
+pub mod format;
+pub mod hir;
+pub mod interp;
+pub mod lexer;
+pub mod mir;
+pub mod parser;
+pub mod stack;
+pub mod typeck;
+
+pub use compile::{Emit, Options, Output, compile};
+pub use diagnostics::{Diagnostic, Diagnostics, Severity};
+pub use hir::Program;
+pub use lexer::{LexerContext, ParseError};
+pub use parser::Parseable;
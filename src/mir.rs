@@ -0,0 +1,920 @@
+//! The Mid-Level Intermediate Representation.
+//!
+//! Each function is a control-flow graph of basic blocks.  Blocks hold
+//! three-address instructions and end in an explicit [`Terminator`].  Values
+//! live in [`Local`]s, which are either registers (assigned by instructions)
+//! or memory (stack slots accessed through loads and stores).  Arithmetic is
+//! performed on machine types ([`Ty`]), with signedness carried by the
+//! operations themselves.
+//!
+//! Lowering from the HIR places every variable in memory; registers are only
+//! used for temporaries, which may still be assigned in several blocks (e.g.
+//! for the result of `&&`).  SSA construction later promotes memory locals to
+//! registers where possible (see [`ssa`]).
+
+use std::fmt;
+
+use crate::ctype::{
+    CType, DataModel, FloatType, FnType, IntRank, StructId, TypeTable,
+};
+use crate::hir::Span;
+
+pub mod dom;
+pub mod dump;
+pub mod link;
+pub mod loops;
+pub mod lower;
+pub mod opt;
+pub mod ssa;
+
+//----------- Module -----------------------------------------------------------
+
+/// A lowered translation unit.
+pub struct Module {
+    /// The structure types used by the module.
+    pub types: TypeTable,
+
+    /// The global variables defined by the module.
+    pub globals: Vec<Global>,
+
+    /// The functions defined by the module.
+    pub functions: Vec<Function>,
+
+    /// The external symbols declared, but not defined, by the module.
+    pub externs: Vec<Extern>,
+}
+
+/// A global variable.
+pub struct Global {
+    /// The symbol name.
+    pub name: String,
+
+    /// The type of the variable.
+    pub ty: CType,
+
+    /// Whether the symbol is local to the translation unit.
+    pub local: bool,
+
+    /// Whether the variable is never written (e.g. a string literal).
+    pub readonly: bool,
+
+    /// The initial contents of the variable.
+    ///
+    /// Variables without an initializer are zero-initialized.
+    pub init: Option<GlobalInit>,
+
+    /// The source text of the first definition, if the variable is a
+    /// file-scope one.
+    pub span: Option<Span>,
+}
+
+/// The initial contents of a global variable.
+#[derive(Clone, Default)]
+pub struct GlobalInit {
+    /// The bytes of the variable, with relocated fields left zero.
+    pub bytes: Vec<u8>,
+
+    /// The addresses of symbols stored in the variable.
+    pub relocs: Vec<Reloc>,
+}
+
+/// The address of a symbol stored in a global variable.
+#[derive(Clone)]
+pub struct Reloc {
+    /// The byte offset of the address within the variable.
+    pub offset: u64,
+
+    /// The symbol whose address is stored.
+    pub symbol: String,
+
+    /// A constant added to the address.
+    pub addend: i64,
+}
+
+/// An external symbol.
+pub struct Extern {
+    /// The symbol name.
+    pub name: String,
+
+    /// The type of the symbol.
+    pub ty: CType,
+
+    /// The source text of the first declaration.
+    pub span: Span,
+}
+
+//----------- Function ---------------------------------------------------------
+
+/// A function definition.
+#[derive(Clone)]
+pub struct Function {
+    /// The symbol name.
+    pub name: String,
+
+    /// The type of the function.
+    pub ty: FnType,
+
+    /// Whether the symbol is local to the translation unit.
+    pub local: bool,
+
+    /// Whether the function was declared `inline`.
+    pub inline: bool,
+
+    /// The parameters of the function.
+    ///
+    /// Scalar parameters are registers; aggregate parameters are memory.
+    pub params: Vec<Local>,
+
+    /// The locals of the function, indexed by [`Local`].
+    pub locals: Vec<LocalDecl>,
+
+    /// The basic blocks of the function, indexed by [`BlockId`].
+    ///
+    /// The first block is the entry block.
+    pub blocks: Vec<Block>,
+
+    /// The data model the C types of the function are laid out in.
+    pub model: DataModel,
+
+    /// The source text of the definition.
+    pub span: Span,
+}
+
+/// A local variable or temporary.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Local(pub u32);
+
+/// The declaration of a [`Local`].
+#[derive(Clone)]
+pub struct LocalDecl {
+    /// The C type of the local.
+    pub ty: CType,
+
+    /// The name of the variable, if it is not a temporary.
+    pub name: Option<String>,
+
+    /// Whether the local lives in memory rather than in a register.
+    pub memory: bool,
+}
+
+/// The identity of a basic block.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockId(pub u32);
+
+/// A basic block.
+#[derive(Clone)]
+pub struct Block {
+    /// The instructions in the block.
+    pub insts: Vec<Inst>,
+
+    /// How control leaves the block.
+    pub term: Terminator,
+}
+
+impl Function {
+    /// The declaration of a local.
+    pub fn local(&self, local: Local) -> &LocalDecl {
+        &self.locals[local.0 as usize]
+    }
+
+    /// The machine type of a register local.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the local has an aggregate type.
+    pub fn ty(&self, local: Local) -> Ty {
+        Ty::of(&self.local(local).ty, self.model)
+            .expect("register locals are scalars")
+    }
+
+    /// The machine type of a scalar operand.
+    pub fn operand_ty(&self, operand: &Operand) -> Ty {
+        match operand {
+            Operand::Local(local) => self.ty(*local),
+            Operand::Const(c) => c.ty(),
+            Operand::Addr(_) => Ty::Ptr,
+        }
+    }
+
+    /// Look up a basic block.
+    pub fn block(&self, id: BlockId) -> &Block {
+        &self.blocks[id.0 as usize]
+    }
+
+    /// Look up a basic block mutably.
+    pub fn block_mut(&mut self, id: BlockId) -> &mut Block {
+        &mut self.blocks[id.0 as usize]
+    }
+
+    /// The identities of all blocks.
+    pub fn block_ids(&self) -> impl Iterator<Item = BlockId> + use<> {
+        (0..self.blocks.len() as u32).map(BlockId)
+    }
+
+    /// Add a new local.
+    pub fn add_local(&mut self, decl: LocalDecl) -> Local {
+        self.locals.push(decl);
+        Local(self.locals.len() as u32 - 1)
+    }
+
+    /// Add a new register temporary of the given type.
+    pub fn add_temp(&mut self, ty: CType) -> Local {
+        self.add_local(LocalDecl {
+            ty,
+            name: None,
+            memory: false,
+        })
+    }
+
+    /// The symbols referenced by the function.
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.blocks.iter().flat_map(|block| {
+            let insts = block.insts.iter().flat_map(Inst::operands);
+            insts.chain(block.term.operands()).filter_map(|operand| {
+                match operand {
+                    Operand::Const(Const::Symbol(name)) => Some(name.as_str()),
+                    _ => None,
+                }
+            })
+        })
+    }
+
+    /// The predecessors of every block.
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![Vec::new(); self.blocks.len()];
+        for id in self.block_ids() {
+            for succ in self.block(id).term.successors() {
+                preds[succ.0 as usize].push(id);
+            }
+        }
+        preds
+    }
+
+    /// The blocks reachable from the entry block, in reverse postorder.
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut postorder = Vec::new();
+        // An explicit stack of (block, next successor index).  Successors
+        // are visited last to first, so that the first successor comes first
+        // in the final order.
+        let mut stack = vec![(BlockId(0), 0)];
+        visited[0] = true;
+        while let Some((block, index)) = stack.pop() {
+            let succs = self.block(block).term.successors();
+            if let Some(&succ) = succs.iter().rev().nth(index) {
+                stack.push((block, index + 1));
+                if !visited[succ.0 as usize] {
+                    visited[succ.0 as usize] = true;
+                    stack.push((succ, 0));
+                }
+            } else {
+                postorder.push(block);
+            }
+        }
+        postorder.reverse();
+        postorder
+    }
+
+    /// Remove the blocks that are not reachable from the entry block.
+    ///
+    /// The remaining blocks are renumbered in reverse postorder, and their
+    /// old identities are returned in their new order.
+    pub fn remove_unreachable(&mut self) -> Vec<BlockId> {
+        let order = self.reverse_postorder();
+        let mut map = vec![None; self.blocks.len()];
+        for (new, old) in order.iter().enumerate() {
+            map[old.0 as usize] = Some(BlockId(new as u32));
+        }
+
+        let mut blocks: Vec<_> = std::mem::take(&mut self.blocks)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.blocks = order
+            .iter()
+            .map(|old| blocks[old.0 as usize].take().unwrap())
+            .collect();
+        for block in &mut self.blocks {
+            for target in block.term.targets_mut() {
+                *target = map[target.0 as usize].unwrap();
+            }
+            // Drop the phi arguments flowing in from removed blocks.
+            for inst in &mut block.insts {
+                if let Inst::Assign(_, Rvalue::Phi(args)) = inst {
+                    args.retain(|(pred, _)| map[pred.0 as usize].is_some());
+                    for (pred, _) in args {
+                        *pred = map[pred.0 as usize].unwrap();
+                    }
+                }
+            }
+        }
+        order
+    }
+
+    /// Remove the phi arguments for one edge from `pred` to `block`.
+    ///
+    /// This must be called whenever an edge is removed from the terminator
+    /// of `pred`, so that phis keep one argument per incoming edge.
+    pub fn remove_edge(&mut self, pred: BlockId, block: BlockId) {
+        for inst in &mut self.block_mut(block).insts {
+            if let Inst::Assign(_, Rvalue::Phi(args)) = inst
+                && let Some(i) = args.iter().position(|(p, _)| *p == pred)
+            {
+                args.remove(i);
+            }
+        }
+    }
+}
+
+//----------- Instructions -----------------------------------------------------
+
+/// An instruction.
+#[derive(Clone)]
+pub enum Inst {
+    /// Compute a value into a register.
+    Assign(Local, Rvalue),
+
+    /// Store a scalar to memory.
+    Store {
+        /// The address to store to.
+        addr: Operand,
+
+        /// The value to store.
+        value: Operand,
+    },
+
+    /// Call a function.
+    ///
+    /// Aggregate arguments are passed as the address of the aggregate, and
+    /// an aggregate result is written to a memory local.
+    Call {
+        /// The local receiving the result, if any.
+        dest: Option<Local>,
+
+        /// The function to call.
+        callee: Operand,
+
+        /// The arguments to the function.
+        args: Vec<Operand>,
+
+        /// The types of the result and of every argument.
+        ///
+        /// For a variadic callee, this lists the (promoted) types of the
+        /// variadic arguments as parameters too.
+        sig: Box<FnType>,
+
+        /// The number of arguments matching named parameters.
+        fixed: usize,
+    },
+
+    /// Set up the `va_list` at an address to walk the variadic arguments of
+    /// the function, as `va_start` does.
+    VaStart(Operand),
+}
+
+/// A value computed by an instruction.
+#[derive(Clone)]
+pub enum Rvalue {
+    /// An operand.
+    Use(Operand),
+
+    /// A binary operation.
+    Bin(BinOp, Operand, Operand),
+
+    /// A unary operation.
+    Una(UnaOp, Operand),
+
+    /// A conversion to the type of the destination.
+    Cast(CastOp, Operand),
+
+    /// A load of the destination type from memory.
+    Load(Operand),
+
+    /// The address of a field, given the address of a struct.
+    FieldAddr(Operand, StructId, usize),
+
+    /// The address of an element, given a pointer to the first element and
+    /// a 64-bit index.
+    ElemAddr(Operand, Operand, CType),
+
+    /// The value flowing in from the predecessor block (in SSA form).
+    ///
+    /// Phis may only appear at the start of a block, and have exactly one
+    /// argument per predecessor.
+    Phi(Vec<(BlockId, Operand)>),
+}
+
+impl Inst {
+    /// The register defined by the instruction, if any.
+    pub fn dest(&self) -> Option<Local> {
+        match self {
+            Self::Assign(dest, _) => Some(*dest),
+            Self::Call { dest, .. } => *dest,
+            Self::Store { .. } | Self::VaStart(_) => None,
+        }
+    }
+
+    /// Whether the instruction is a phi.
+    pub fn is_phi(&self) -> bool {
+        matches!(self, Self::Assign(_, Rvalue::Phi(_)))
+    }
+
+    /// The operands used by the instruction.
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Self::Assign(_, rvalue) => rvalue.operands(),
+            Self::Store { addr, value } => vec![addr, value],
+            Self::Call { callee, args, .. } => {
+                std::iter::once(callee).chain(args).collect()
+            }
+            Self::VaStart(addr) => vec![addr],
+        }
+    }
+
+    /// The operands used by the instruction, mutably.
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Self::Assign(_, rvalue) => rvalue.operands_mut(),
+            Self::Store { addr, value } => vec![addr, value],
+            Self::Call { callee, args, .. } => {
+                std::iter::once(callee).chain(args).collect()
+            }
+            Self::VaStart(addr) => vec![addr],
+        }
+    }
+}
+
+impl Rvalue {
+    /// The operands used by the value.
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Self::Use(a)
+            | Self::Una(_, a)
+            | Self::Cast(_, a)
+            | Self::Load(a)
+            | Self::FieldAddr(a, ..) => vec![a],
+            Self::Bin(_, a, b) | Self::ElemAddr(a, b, _) => vec![a, b],
+            Self::Phi(args) => args.iter().map(|(_, arg)| arg).collect(),
+        }
+    }
+
+    /// The operands used by the value, mutably.
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Self::Use(a)
+            | Self::Una(_, a)
+            | Self::Cast(_, a)
+            | Self::Load(a)
+            | Self::FieldAddr(a, ..) => vec![a],
+            Self::Bin(_, a, b) | Self::ElemAddr(a, b, _) => vec![a, b],
+            Self::Phi(args) => args.iter_mut().map(|(_, arg)| arg).collect(),
+        }
+    }
+}
+
+/// A binary operation.
+///
+/// Comparisons produce 0 or 1 in the destination type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    /// Integer or pointer addition.
+    Add,
+
+    /// Integer or pointer subtraction.
+    Sub,
+
+    /// Integer multiplication.
+    Mul,
+
+    /// Signed division.
+    SDiv,
+
+    /// Unsigned division.
+    UDiv,
+
+    /// Signed remainder.
+    SRem,
+
+    /// Unsigned remainder.
+    URem,
+
+    /// Left shift.
+    Shl,
+
+    /// Logical (unsigned) right shift.
+    LShr,
+
+    /// Arithmetic (signed) right shift.
+    AShr,
+
+    /// Bitwise and.
+    And,
+
+    /// Bitwise or.
+    Or,
+
+    /// Bitwise exclusive or.
+    Xor,
+
+    /// Integer equality.
+    Eq,
+
+    /// Integer inequality.
+    Ne,
+
+    /// Signed less than.
+    SLt,
+
+    /// Signed less than or equal.
+    SLe,
+
+    /// Signed greater than.
+    SGt,
+
+    /// Signed greater than or equal.
+    SGe,
+
+    /// Unsigned less than.
+    ULt,
+
+    /// Unsigned less than or equal.
+    ULe,
+
+    /// Unsigned greater than.
+    UGt,
+
+    /// Unsigned greater than or equal.
+    UGe,
+
+    /// Floating-point addition.
+    FAdd,
+
+    /// Floating-point subtraction.
+    FSub,
+
+    /// Floating-point multiplication.
+    FMul,
+
+    /// Floating-point division.
+    FDiv,
+
+    /// Floating-point equality.
+    FEq,
+
+    /// Floating-point inequality.
+    FNe,
+
+    /// Floating-point less than.
+    FLt,
+
+    /// Floating-point less than or equal.
+    FLe,
+
+    /// Floating-point greater than.
+    FGt,
+
+    /// Floating-point greater than or equal.
+    FGe,
+}
+
+impl BinOp {
+    /// The name of the operation in textual dumps.
+    pub fn name(self) -> &'static str {
+        use BinOp::*;
+        match self {
+            Add => "add",
+            Sub => "sub",
+            Mul => "mul",
+            SDiv => "sdiv",
+            UDiv => "udiv",
+            SRem => "srem",
+            URem => "urem",
+            Shl => "shl",
+            LShr => "lshr",
+            AShr => "ashr",
+            And => "and",
+            Or => "or",
+            Xor => "xor",
+            Eq => "eq",
+            Ne => "ne",
+            SLt => "slt",
+            SLe => "sle",
+            SGt => "sgt",
+            SGe => "sge",
+            ULt => "ult",
+            ULe => "ule",
+            UGt => "ugt",
+            UGe => "uge",
+            FAdd => "fadd",
+            FSub => "fsub",
+            FMul => "fmul",
+            FDiv => "fdiv",
+            FEq => "feq",
+            FNe => "fne",
+            FLt => "flt",
+            FLe => "fle",
+            FGt => "fgt",
+            FGe => "fge",
+        }
+    }
+
+    /// Whether the operands of the operation may be swapped.
+    pub fn is_commutative(self) -> bool {
+        use BinOp::*;
+        matches!(
+            self,
+            Add | Mul | And | Or | Xor | Eq | Ne | FAdd | FMul | FEq | FNe
+        )
+    }
+}
+
+/// A unary operation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum UnaOp {
+    /// Integer negation.
+    Neg,
+
+    /// Bitwise inversion.
+    Not,
+
+    /// Floating-point negation.
+    FNeg,
+}
+
+/// A conversion between machine types.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CastOp {
+    /// Sign-extend an integer.
+    SExt,
+
+    /// Zero-extend an integer.
+    ZExt,
+
+    /// Truncate an integer.
+    Trunc,
+
+    /// Convert a float to a signed integer.
+    FToS,
+
+    /// Convert a float to an unsigned integer.
+    FToU,
+
+    /// Convert a signed integer to a float.
+    SToF,
+
+    /// Convert an unsigned integer to a float.
+    UToF,
+
+    /// Convert between float types.
+    FConv,
+
+    /// Convert a pointer to a 64-bit integer.
+    PtrToInt,
+
+    /// Convert a 64-bit integer to a pointer.
+    IntToPtr,
+}
+
+impl CastOp {
+    /// The name of the conversion in textual dumps.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SExt => "sext",
+            Self::ZExt => "zext",
+            Self::Trunc => "trunc",
+            Self::FToS => "ftos",
+            Self::FToU => "ftou",
+            Self::SToF => "stof",
+            Self::UToF => "utof",
+            Self::FConv => "fconv",
+            Self::PtrToInt => "ptrtoint",
+            Self::IntToPtr => "inttoptr",
+        }
+    }
+}
+
+/// An operand of an instruction.
+#[derive(Clone, PartialEq)]
+pub enum Operand {
+    /// The value of a register local.
+    Local(Local),
+
+    /// A constant.
+    Const(Const),
+
+    /// The address of a memory local.
+    Addr(Local),
+}
+
+/// A constant.
+#[derive(Clone, PartialEq)]
+pub enum Const {
+    /// An integer, sign-extended to 64 bits.
+    Int(i64, Ty),
+
+    /// A floating-point number.
+    Float(f64, Ty),
+
+    /// The address of a symbol.
+    Symbol(String),
+}
+
+impl Const {
+    /// The machine type of the constant.
+    pub fn ty(&self) -> Ty {
+        match self {
+            Self::Int(_, ty) | Self::Float(_, ty) => *ty,
+            Self::Symbol(_) => Ty::Ptr,
+        }
+    }
+}
+
+//----------- Terminator -------------------------------------------------------
+
+/// How control leaves a basic block.
+#[derive(Clone)]
+pub enum Terminator {
+    /// Jump to another block.
+    Jump(BlockId),
+
+    /// Jump to one of two blocks, depending on whether a value is non-zero.
+    Branch(Operand, BlockId, BlockId),
+
+    /// Jump to the block for the matching value, or the default block.
+    Switch {
+        /// The value to switch on.
+        value: Operand,
+
+        /// The cases, as pairs of a value and its target.
+        cases: Vec<(i64, BlockId)>,
+
+        /// The target if no case matches.
+        default: BlockId,
+    },
+
+    /// Return from the function.
+    ///
+    /// Aggregate values are returned by address.
+    Return(Option<Operand>),
+
+    /// Control never reaches the end of the block.
+    Unreachable,
+}
+
+impl Terminator {
+    /// The blocks control may flow to.
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Self::Jump(target) => vec![*target],
+            Self::Branch(_, then, r#else) => vec![*then, *r#else],
+            Self::Switch { cases, default, .. } => {
+                let mut succs = vec![*default];
+                for (_, target) in cases {
+                    if !succs.contains(target) {
+                        succs.push(*target);
+                    }
+                }
+                succs
+            }
+            Self::Return(_) | Self::Unreachable => Vec::new(),
+        }
+    }
+
+    /// The targets of the terminator, mutably.
+    pub fn targets_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            Self::Jump(target) => vec![target],
+            Self::Branch(_, then, r#else) => vec![then, r#else],
+            Self::Switch { cases, default, .. } => {
+                let mut targets = vec![default];
+                targets.extend(cases.iter_mut().map(|(_, target)| target));
+                targets
+            }
+            Self::Return(_) | Self::Unreachable => Vec::new(),
+        }
+    }
+
+    /// The operands used by the terminator.
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Self::Branch(value, ..)
+            | Self::Switch { value, .. }
+            | Self::Return(Some(value)) => vec![value],
+            Self::Jump(_) | Self::Return(None) | Self::Unreachable => {
+                Vec::new()
+            }
+        }
+    }
+
+    /// The operands used by the terminator, mutably.
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Self::Branch(value, ..)
+            | Self::Switch { value, .. }
+            | Self::Return(Some(value)) => vec![value],
+            Self::Jump(_) | Self::Return(None) | Self::Unreachable => {
+                Vec::new()
+            }
+        }
+    }
+}
+
+//----------- Ty ---------------------------------------------------------------
+
+/// A machine type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Ty {
+    /// An 8-bit integer.
+    I8,
+
+    /// A 16-bit integer.
+    I16,
+
+    /// A 32-bit integer.
+    I32,
+
+    /// A 64-bit integer.
+    I64,
+
+    /// A single-precision float.
+    F32,
+
+    /// A double-precision float.
+    F64,
+
+    /// A pointer, as wide as the data model makes it.
+    Ptr,
+}
+
+impl Ty {
+    /// The machine type of a scalar C type.
+    ///
+    /// Returns `None` for aggregates, `void` and functions.
+    pub fn of(ty: &CType, model: DataModel) -> Option<Self> {
+        Some(match ty {
+            CType::Int(int) => match int.rank {
+                IntRank::Bool | IntRank::Char => Self::I8,
+                IntRank::Short => Self::I16,
+                IntRank::Int => Self::I32,
+                IntRank::Long if model == DataModel::Ilp32 => Self::I32,
+                IntRank::Long | IntRank::LongLong => Self::I64,
+            },
+            CType::Float(FloatType::Float) => Self::F32,
+            CType::Float(_) => Self::F64,
+            CType::Pointer(_) => Self::Ptr,
+            _ => return None,
+        })
+    }
+
+    /// The size of the type in bytes.
+    pub fn size(self, model: DataModel) -> u64 {
+        match self {
+            Self::I8 => 1,
+            Self::I16 => 2,
+            Self::I32 | Self::F32 => 4,
+            Self::I64 | Self::F64 => 8,
+            Self::Ptr => model.pointer_size(),
+        }
+    }
+
+    /// Whether this is a floating-point type.
+    pub fn is_float(self) -> bool {
+        matches!(self, Self::F32 | Self::F64)
+    }
+
+    /// Truncate a 64-bit value to this (integer) type, sign-extending the
+    /// result back to 64 bits.
+    pub fn wrap(self, value: i64) -> i64 {
+        match self {
+            Self::I8 => value as i8 as i64,
+            Self::I16 => value as i16 as i64,
+            Self::I32 => value as i32 as i64,
+            _ => value,
+        }
+    }
+
+    /// Mask a 64-bit value to the bits of this (integer) type, as unsigned.
+    pub fn unsigned(self, value: i64) -> u64 {
+        match self {
+            Self::I8 => value as u8 as u64,
+            Self::I16 => value as u16 as u64,
+            Self::I32 => value as u32 as u64,
+            _ => value as u64,
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::I8 => "i8",
+            Self::I16 => "i16",
+            Self::I32 => "i32",
+            Self::I64 => "i64",
+            Self::F32 => "f32",
+            Self::F64 => "f64",
+            Self::Ptr => "ptr",
+        };
+        f.write_str(name)
+    }
+}
@@ -0,0 +1,147 @@
+//! Dominator trees and dominance frontiers.
+//!
+//! Dominators are computed with the iterative algorithm of Cooper, Harvey and
+//! Kennedy ("A Simple, Fast Dominance Algorithm").
+
+use super::{BlockId, Function};
+
+/// The dominator tree of a function.
+pub struct Dominators {
+    /// The immediate dominator of every block.
+    ///
+    /// The entry block and unreachable blocks have none.
+    idom: Vec<Option<BlockId>>,
+
+    /// The position of every reachable block in reverse postorder.
+    order: Vec<Option<usize>>,
+
+    /// The children of every block in the dominator tree.
+    children: Vec<Vec<BlockId>>,
+}
+
+impl Dominators {
+    /// Compute the dominator tree of a function.
+    pub fn new(func: &Function) -> Self {
+        let rpo = func.reverse_postorder();
+        let preds = func.predecessors();
+        let mut order = vec![None; func.blocks.len()];
+        for (i, block) in rpo.iter().enumerate() {
+            order[block.0 as usize] = Some(i);
+        }
+
+        let mut idom: Vec<Option<BlockId>> = vec![None; func.blocks.len()];
+        idom[0] = Some(BlockId(0));
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in &rpo[1..] {
+                let mut new = None;
+                for &pred in &preds[block.0 as usize] {
+                    if idom[pred.0 as usize].is_none() {
+                        continue;
+                    }
+                    new = Some(match new {
+                        None => pred,
+                        Some(new) => Self::intersect(&idom, &order, pred, new),
+                    });
+                }
+                if idom[block.0 as usize] != new {
+                    idom[block.0 as usize] = new;
+                    changed = true;
+                }
+            }
+        }
+        idom[0] = None;
+
+        let mut children = vec![Vec::new(); func.blocks.len()];
+        for &block in &rpo[1..] {
+            let parent = idom[block.0 as usize].unwrap();
+            children[parent.0 as usize].push(block);
+        }
+        Self {
+            idom,
+            order,
+            children,
+        }
+    }
+
+    /// Find the nearest common dominator of two blocks during construction.
+    fn intersect(
+        idom: &[Option<BlockId>],
+        order: &[Option<usize>],
+        mut a: BlockId,
+        mut b: BlockId,
+    ) -> BlockId {
+        let index = |block: BlockId| order[block.0 as usize].unwrap();
+        while a != b {
+            while index(a) > index(b) {
+                a = idom[a.0 as usize].unwrap();
+            }
+            while index(b) > index(a) {
+                b = idom[b.0 as usize].unwrap();
+            }
+        }
+        a
+    }
+
+    /// The immediate dominator of a block.
+    pub fn idom(&self, block: BlockId) -> Option<BlockId> {
+        self.idom[block.0 as usize]
+    }
+
+    /// The blocks immediately dominated by a block.
+    pub fn children(&self, block: BlockId) -> &[BlockId] {
+        &self.children[block.0 as usize]
+    }
+
+    /// Whether a block is reachable from the entry block.
+    pub fn is_reachable(&self, block: BlockId) -> bool {
+        self.order[block.0 as usize].is_some()
+    }
+
+    /// Whether `a` dominates `b`.
+    ///
+    /// Every block dominates itself.
+    pub fn dominates(&self, a: BlockId, mut b: BlockId) -> bool {
+        if !self.is_reachable(a) || !self.is_reachable(b) {
+            return false;
+        }
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.idom(b) {
+                Some(parent) => b = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// The dominance frontier of every block.
+    pub fn frontiers(&self, func: &Function) -> Vec<Vec<BlockId>> {
+        let mut frontiers = vec![Vec::new(); func.blocks.len()];
+        for (block, preds) in func.predecessors().into_iter().enumerate() {
+            let block = BlockId(block as u32);
+            if preds.len() < 2 || !self.is_reachable(block) {
+                continue;
+            }
+            let idom = self.idom(block);
+            for pred in preds {
+                if !self.is_reachable(pred) {
+                    continue;
+                }
+                let mut runner = Some(pred);
+                while let Some(current) = runner
+                    && runner != idom
+                {
+                    let frontier = &mut frontiers[current.0 as usize];
+                    if !frontier.contains(&block) {
+                        frontier.push(block);
+                    }
+                    runner = self.idom(current);
+                }
+            }
+        }
+        frontiers
+    }
+}
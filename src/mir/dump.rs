@@ -0,0 +1,265 @@
+//! Textual dumps of the MIR.
+//!
+//! The format is stable enough to be used for golden tests; see `sea
+//! --emit=mir`.
+
+use std::fmt::{self, Display, Formatter};
+
+use super::*;
+
+impl Display for Module {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for id in self.types.struct_ids() {
+            let def = self.types.get(id);
+            let Some(fields) = &def.fields else {
+                continue;
+            };
+            let layout = self.types.layout(id);
+            write!(f, "type #{} = struct ", id.0)?;
+            if let Some(name) = &def.name {
+                write!(f, "{} ", name)?;
+            }
+            write!(f, "{{")?;
+            for (i, (field, offset)) in
+                fields.iter().zip(&layout.offsets).enumerate()
+            {
+                let sep = if i == 0 { " " } else { ", " };
+                write!(f, "{}{}: {:?} @{}", sep, field.name, field.ty, offset)?;
+            }
+            writeln!(f, " }} size {} align {}", layout.size, layout.align)?;
+        }
+        for global in &self.globals {
+            writeln!(f, "{}", global)?;
+        }
+        for ext in &self.externs {
+            writeln!(f, "extern @{}: {:?}", ext.name, ext.ty)?;
+        }
+        for function in &self.functions {
+            writeln!(f)?;
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Global {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "global ")?;
+        if self.local {
+            write!(f, "local ")?;
+        }
+        if self.readonly {
+            write!(f, "readonly ")?;
+        }
+        write!(f, "@{}: {:?} = ", self.name, self.ty)?;
+        match &self.init {
+            None => write!(f, "zeroinit"),
+            Some(init) => {
+                write!(f, "[")?;
+                for (i, byte) in init.bytes.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{:02x}", byte)?;
+                }
+                write!(f, "]")?;
+                for reloc in &init.relocs {
+                    write!(f, " +{}: @{}", reloc.offset, reloc.symbol)?;
+                    if reloc.addend != 0 {
+                        write!(f, "{:+}", reloc.addend)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "fn ")?;
+        if self.local {
+            write!(f, "local ")?;
+        }
+        if self.inline {
+            write!(f, "inline ")?;
+        }
+        write!(f, "@{}(", self.name)?;
+        for (i, param) in self.params.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            let decl = self.local(*param);
+            write!(f, "{}: {:?}", Operand::Local(*param), decl.ty)?;
+        }
+        if self.ty.variadic {
+            write!(f, ", ...")?;
+        }
+        writeln!(f, ") -> {:?} {{", self.ty.ret)?;
+
+        // Registers are typed by the instructions assigning them, so only
+        // memory locals are listed.
+        for (i, decl) in self.locals.iter().enumerate() {
+            if !decl.memory {
+                continue;
+            }
+            write!(f, "    %{}: {:?}", i, decl.ty)?;
+            if let Some(name) = &decl.name {
+                write!(f, " ({})", name)?;
+            }
+            writeln!(f)?;
+        }
+
+        for id in self.block_ids() {
+            let block = self.block(id);
+            writeln!(f, "  {}:", id)?;
+            for inst in &block.insts {
+                write!(f, "    ")?;
+                self.fmt_inst(f, inst)?;
+                writeln!(f)?;
+            }
+            writeln!(f, "    {}", block.term)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl Function {
+    /// Write an instruction, annotated with machine types.
+    fn fmt_inst(&self, f: &mut Formatter<'_>, inst: &Inst) -> fmt::Result {
+        match inst {
+            Inst::Assign(dest, rvalue) => {
+                write!(f, "{} = ", Operand::Local(*dest))?;
+                let ty = self.ty(*dest);
+                match rvalue {
+                    Rvalue::Use(op) => write!(f, "copy {} {}", ty, op),
+                    Rvalue::Bin(op, lhs, rhs) => {
+                        let ty = self.operand_ty(lhs);
+                        write!(f, "{} {} {}, {}", op.name(), ty, lhs, rhs)
+                    }
+                    Rvalue::Una(op, operand) => {
+                        let name = match op {
+                            UnaOp::Neg => "neg",
+                            UnaOp::Not => "not",
+                            UnaOp::FNeg => "fneg",
+                        };
+                        write!(f, "{} {} {}", name, ty, operand)
+                    }
+                    Rvalue::Cast(op, operand) => {
+                        let from = self.operand_ty(operand);
+                        write!(
+                            f,
+                            "{} {} {} to {}",
+                            op.name(),
+                            from,
+                            operand,
+                            ty
+                        )
+                    }
+                    Rvalue::Load(addr) => write!(f, "load {} {}", ty, addr),
+                    Rvalue::FieldAddr(base, id, index) => {
+                        write!(f, "fieldaddr {}, #{}.{}", base, id.0, index)
+                    }
+                    Rvalue::ElemAddr(base, index, elem) => {
+                        write!(f, "elemaddr {:?}, {}, {}", elem, base, index)
+                    }
+                    Rvalue::Phi(args) => {
+                        write!(f, "phi {}", ty)?;
+                        for (i, (block, value)) in args.iter().enumerate() {
+                            let sep = if i == 0 { " " } else { ", " };
+                            write!(f, "{}[{}: {}]", sep, block, value)?;
+                        }
+                        Ok(())
+                    }
+                }
+            }
+            Inst::Store { addr, value } => {
+                let ty = self.operand_ty(value);
+                write!(f, "store {} {}, {}", ty, value, addr)
+            }
+            Inst::Call {
+                dest,
+                callee,
+                args,
+                sig,
+                fixed,
+            } => {
+                if let Some(dest) = dest {
+                    write!(f, "{} = ", Operand::Local(*dest))?;
+                }
+                write!(f, "call {}(", callee)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    if sig.variadic && i == *fixed {
+                        write!(f, "... ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                if sig.variadic && args.len() == *fixed {
+                    if *fixed != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "...")?;
+                }
+                write!(f, ")")
+            }
+            Inst::VaStart(addr) => write!(f, "vastart {}", addr),
+        }
+    }
+}
+
+impl Display for Terminator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Jump(target) => write!(f, "jump {}", target),
+            Self::Branch(cond, then, r#else) => {
+                write!(f, "branch {}, {}, {}", cond, then, r#else)
+            }
+            Self::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                write!(f, "switch {} [", value)?;
+                for (i, (case, target)) in cases.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", case, target)?;
+                }
+                write!(f, "], default {}", default)
+            }
+            Self::Return(Some(value)) => write!(f, "ret {}", value),
+            Self::Return(None) => write!(f, "ret"),
+            Self::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local(local) => write!(f, "%{}", local.0),
+            Self::Const(c) => write!(f, "{}", c),
+            Self::Addr(local) => write!(f, "&%{}", local.0),
+        }
+    }
+}
+
+impl Display for Const {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(value, _) => write!(f, "{}", value),
+            Self::Float(value, _) => write!(f, "{:?}", value),
+            Self::Symbol(name) => write!(f, "@{}", name),
+        }
+    }
+}
+
+impl Display for BlockId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
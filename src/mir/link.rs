@@ -0,0 +1,267 @@
+//! Checks of linkage across translation units.
+//!
+//! Each translation unit is lowered on its own, so mistakes that only show
+//! when units are put together go unnoticed by the type checker: an external
+//! symbol declared with different types in different units, or defined in
+//! several of them.  These are errors, since the program would misbehave or
+//! fail to link.  Static functions that are never used are reported as
+//! warnings.  Problems are located at the declarations involved, found by
+//! the source text that the lowered symbols keep.
+//!
+//! Types from different units are compared structurally, as their structs
+//! live in different [`TypeTable`]s: structs are the same if they have the
+//! same tag and their fields have the same names and compatible types.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use super::*;
+use crate::diagnostics;
+
+/// A translation unit to check.
+pub struct Unit<'a> {
+    /// The name of the unit in diagnostics, usually the path of its source.
+    pub name: &'a str,
+
+    /// The preprocessed source of the unit.
+    pub source: &'a str,
+
+    /// The lowered unit.
+    pub module: &'a Module,
+}
+
+impl Unit<'_> {
+    /// The location of some source text of the unit, as
+    /// `path:line:column`.
+    fn location(&self, span: Span) -> String {
+        diagnostics::location(Path::new(self.name), self.source, span.start)
+    }
+}
+
+/// A problem found by the checker.
+pub struct Diagnostic {
+    /// Whether the problem is an error, rather than a warning.
+    pub error: bool,
+
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// A declaration of an external symbol in one unit.
+struct Decl {
+    /// The index of the unit.
+    unit: usize,
+
+    /// The declared type.
+    ty: CType,
+
+    /// Whether the unit defines the symbol.
+    defined: bool,
+
+    /// The source text of the declaration.
+    span: Span,
+}
+
+/// Check a program made of several translation units.
+///
+/// Errors come first, by symbol in order of first declaration, followed by
+/// warnings, by unit.
+pub fn check(units: &[Unit]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    // The declarations of every external symbol, in order of appearance.
+    let mut symbols: Vec<(&str, Vec<Decl>)> = Vec::new();
+    let mut index = HashMap::new();
+    for (unit, Unit { module, .. }) in units.iter().enumerate() {
+        let globals = module.globals.iter().filter(|global| !global.local).map(
+            |global| {
+                let span = global.span.expect("a file-scope variable");
+                (&global.name, global.ty.clone(), true, span)
+            },
+        );
+        let functions =
+            module
+                .functions
+                .iter()
+                .filter(|func| !func.local)
+                .map(|func| {
+                    let ty = CType::Fn(Box::new(func.ty.clone()));
+                    (&func.name, ty, true, func.span)
+                });
+        let externs = module
+            .externs
+            .iter()
+            .map(|ext| (&ext.name, ext.ty.clone(), false, ext.span));
+        let decls = globals.chain(functions).chain(externs);
+        for (name, ty, defined, span) in decls {
+            let i = *index.entry(name.as_str()).or_insert_with(|| {
+                symbols.push((name.as_str(), Vec::new()));
+                symbols.len() - 1
+            });
+            symbols[i].1.push(Decl {
+                unit,
+                ty,
+                defined,
+                span,
+            });
+        }
+    }
+
+    for (name, decls) in &symbols {
+        let first = &decls[0];
+        let types = |decl: &Decl| &units[decl.unit].module.types;
+        let conflict = decls.iter().find(|decl| {
+            let mut seen = Vec::new();
+            !compatible(
+                &first.ty,
+                types(first),
+                &decl.ty,
+                types(decl),
+                &mut seen,
+            )
+        });
+        if let Some(decl) = conflict {
+            let a = units[first.unit].location(first.span);
+            let b = units[decl.unit].location(decl.span);
+            let first_ty = types(first).named(&first.ty).to_string();
+            let ty = types(decl).named(&decl.ty).to_string();
+            // The types may only differ in the fields of a struct.
+            let message = if first_ty == ty {
+                format!(
+                    "conflicting types for `{name}`: `{ty}` has different \
+                     fields in {a} and {b}"
+                )
+            } else {
+                format!(
+                    "conflicting types for `{name}`: `{first_ty}` in {a}, but \
+                     `{ty}` in {b}"
+                )
+            };
+            diagnostics.push(Diagnostic {
+                error: true,
+                message,
+            });
+        }
+
+        let mut defined = decls.iter().filter(|decl| decl.defined);
+        if let (Some(first), Some(second)) = (defined.next(), defined.next()) {
+            diagnostics.push(Diagnostic {
+                error: true,
+                message: format!(
+                    "multiple definitions of `{name}`, in {} and {}",
+                    units[first.unit].location(first.span),
+                    units[second.unit].location(second.span),
+                ),
+            });
+        }
+    }
+
+    for unit in units {
+        for func in unused_statics(unit.module) {
+            diagnostics.push(Diagnostic {
+                error: false,
+                message: format!(
+                    "{}: static function `{}` defined but not used",
+                    unit.location(func.span),
+                    func.name
+                ),
+            });
+        }
+    }
+    diagnostics
+}
+
+/// The static functions of a module that nothing else refers to.
+///
+/// Inline functions are left out, since headers commonly define them for
+/// units that may not use them.
+fn unused_statics(module: &Module) -> impl Iterator<Item = &Function> {
+    let mut used = HashSet::new();
+    for func in &module.functions {
+        let symbols = func.symbols().filter(|symbol| *symbol != func.name);
+        used.extend(symbols);
+    }
+    let relocs = module
+        .globals
+        .iter()
+        .filter_map(|global| global.init.as_ref())
+        .flat_map(|init| &init.relocs);
+    used.extend(relocs.map(|reloc| reloc.symbol.as_str()));
+    module.functions.iter().filter(move |func| {
+        func.local && !func.inline && !used.contains(func.name.as_str())
+    })
+}
+
+/// Whether two types from different units are compatible.
+///
+/// `seen` holds the pairs of structs being compared, which are assumed to be
+/// compatible while their fields are, so that recursive structs terminate.
+fn compatible(
+    a: &CType,
+    a_types: &TypeTable,
+    b: &CType,
+    b_types: &TypeTable,
+    seen: &mut Vec<(StructId, StructId)>,
+) -> bool {
+    match (a, b) {
+        (CType::Pointer(a), CType::Pointer(b)) => {
+            compatible(a, a_types, b, b_types, seen)
+        }
+        (
+            CType::Array {
+                elem: a,
+                len: a_len,
+            },
+            CType::Array {
+                elem: b,
+                len: b_len,
+            },
+        ) => {
+            (a_len.is_none() || b_len.is_none() || a_len == b_len)
+                && compatible(a, a_types, b, b_types, seen)
+        }
+        // A function without a prototype is compatible with any parameters.
+        (CType::Fn(a), CType::Fn(b)) if !a.prototype || !b.prototype => {
+            compatible(&a.ret, a_types, &b.ret, b_types, seen)
+        }
+        (CType::Fn(a), CType::Fn(b)) => {
+            a.variadic == b.variadic
+                && a.params.len() == b.params.len()
+                && compatible(&a.ret, a_types, &b.ret, b_types, seen)
+                && a.params
+                    .iter()
+                    .zip(&b.params)
+                    .all(|(a, b)| compatible(a, a_types, b, b_types, seen))
+        }
+        (CType::Struct(a), CType::Struct(b)) => {
+            if seen.contains(&(*a, *b)) {
+                return true;
+            }
+            let (a_def, b_def) = (a_types.get(*a), b_types.get(*b));
+            if a_def.name != b_def.name {
+                return false;
+            }
+            seen.push((*a, *b));
+            // An incomplete struct is compatible with any struct with its
+            // tag.
+            let (Some(a_fields), Some(b_fields)) =
+                (&a_def.fields, &b_def.fields)
+            else {
+                return true;
+            };
+            a_fields.len() == b_fields.len()
+                && a_fields.iter().zip(b_fields).all(|(a, b)| {
+                    a.name == b.name
+                        && compatible(&a.ty, a_types, &b.ty, b_types, seen)
+                })
+        }
+        _ => a == b,
+    }
+}
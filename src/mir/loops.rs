@@ -0,0 +1,265 @@
+//! Natural loops and induction variables.
+//!
+//! A natural loop is identified by a back edge: an edge whose target (the
+//! loop's header) dominates its source (a latch).  The loop consists of the
+//! header and every block that can reach a latch without passing through the
+//! header.  Loops sharing a header are merged.
+
+use std::collections::HashMap;
+
+use super::dom::Dominators;
+use super::*;
+
+/// A natural loop.
+pub struct Loop {
+    /// The header, which dominates every block in the loop.
+    pub header: BlockId,
+
+    /// The blocks in the loop, including the header, in reverse postorder.
+    pub blocks: Vec<BlockId>,
+
+    /// The blocks with an edge back to the header.
+    pub latches: Vec<BlockId>,
+
+    /// Whether each block of the function is in the loop.
+    members: Vec<bool>,
+}
+
+impl Loop {
+    /// Whether a block is part of the loop.
+    ///
+    /// Blocks added to the function after finding the loop are not.
+    pub fn contains(&self, block: BlockId) -> bool {
+        self.members.get(block.0 as usize).copied().unwrap_or(false)
+    }
+
+    /// The predecessors of the header from outside the loop.
+    pub fn entries(&self, preds: &[Vec<BlockId>]) -> Vec<BlockId> {
+        let preds = &preds[self.header.0 as usize];
+        preds
+            .iter()
+            .copied()
+            .filter(|&p| !self.contains(p))
+            .collect()
+    }
+
+    /// The preheader of the loop, if it has one.
+    ///
+    /// This is the only block entering the loop, and it only jumps to the
+    /// header.
+    pub fn preheader(&self, func: &Function) -> Option<BlockId> {
+        match self.entries(&func.predecessors())[..] {
+            [pred] if matches!(func.block(pred).term, Terminator::Jump(_)) => {
+                Some(pred)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Find the natural loops of a function, innermost first.
+pub fn find(func: &Function, doms: &Dominators) -> Vec<Loop> {
+    let preds = func.predecessors();
+    let rpo = func.reverse_postorder();
+    let mut loops: Vec<Loop> = Vec::new();
+    for &block in &rpo {
+        for succ in func.block(block).term.successors() {
+            if !doms.dominates(succ, block) {
+                continue;
+            }
+            let index = match loops.iter().position(|l| l.header == succ) {
+                Some(index) => index,
+                None => {
+                    let mut members = vec![false; func.blocks.len()];
+                    members[succ.0 as usize] = true;
+                    loops.push(Loop {
+                        header: succ,
+                        blocks: Vec::new(),
+                        latches: Vec::new(),
+                        members,
+                    });
+                    loops.len() - 1
+                }
+            };
+            let lp = &mut loops[index];
+            lp.latches.push(block);
+
+            // Walk backwards from the latch up to the header.
+            let mut worklist = vec![block];
+            while let Some(block) = worklist.pop() {
+                if std::mem::replace(&mut lp.members[block.0 as usize], true) {
+                    continue;
+                }
+                worklist.extend(&preds[block.0 as usize]);
+            }
+        }
+    }
+
+    for lp in &mut loops {
+        lp.blocks = rpo.iter().copied().filter(|&b| lp.contains(b)).collect();
+    }
+    loops.sort_by_key(|lp| lp.blocks.len());
+    loops
+}
+
+/// Give a loop a preheader, returning it.
+///
+/// If there is no single block entering the loop that only jumps to the
+/// header, a new one is inserted, taking over the edges entering the loop.
+pub fn insert_preheader(func: &mut Function, lp: &Loop) -> BlockId {
+    if let Some(preheader) = lp.preheader(func) {
+        return preheader;
+    }
+    let entries = lp.entries(&func.predecessors());
+    let preheader = BlockId(func.blocks.len() as u32);
+    let mut phis = Vec::new();
+    for inst in &mut func.block_mut(lp.header).insts {
+        let Inst::Assign(dest, Rvalue::Phi(args)) = inst else {
+            break;
+        };
+        // The values from outside the loop are merged in the preheader.
+        let (outside, inside) = std::mem::take(args)
+            .into_iter()
+            .partition(|(pred, _)| entries.contains(pred));
+        *args = inside;
+        phis.push((*dest, outside));
+    }
+    let mut insts = Vec::new();
+    for (dest, mut outside) in phis {
+        let value = if outside.len() == 1 {
+            outside.pop().unwrap().1
+        } else {
+            let merged = func.add_temp(func.local(dest).ty.clone());
+            insts.push(Inst::Assign(merged, Rvalue::Phi(outside)));
+            Operand::Local(merged)
+        };
+        let Inst::Assign(_, Rvalue::Phi(args)) = func
+            .block_mut(lp.header)
+            .insts
+            .iter_mut()
+            .find(|inst| inst.dest() == Some(dest))
+            .unwrap()
+        else {
+            unreachable!("the phi was found above");
+        };
+        args.push((preheader, value));
+    }
+    func.blocks.push(Block {
+        insts,
+        term: Terminator::Jump(lp.header),
+    });
+
+    let mut redirected = entries;
+    redirected.dedup();
+    for pred in redirected {
+        for target in func.block_mut(pred).term.targets_mut() {
+            if *target == lp.header {
+                *target = preheader;
+            }
+        }
+    }
+    preheader
+}
+
+/// Find the loops of a function, innermost first, after giving each of them
+/// a preheader.
+///
+/// The preheaders are inserted before the loops are found again, so that
+/// each is part of the loops enclosing its own.
+pub fn with_preheaders(func: &mut Function) -> Vec<(Loop, BlockId)> {
+    let doms = Dominators::new(func);
+    let found = find(func, &doms);
+    if found.is_empty() {
+        return Vec::new();
+    }
+    for lp in &found {
+        insert_preheader(func, lp);
+    }
+    let doms = Dominators::new(func);
+    find(func, &doms)
+        .into_iter()
+        .filter_map(|lp| {
+            let preheader = lp.preheader(func)?;
+            Some((lp, preheader))
+        })
+        .collect()
+}
+
+/// A basic induction variable: a phi in the loop header that is increased
+/// by a constant on every iteration.
+pub struct BasicIv {
+    /// The phi holding the value in the current iteration.
+    pub phi: Local,
+
+    /// The value on entry to the loop.
+    pub init: Operand,
+
+    /// The amount added on every iteration.
+    pub step: i64,
+}
+
+/// Find the basic induction variables of a loop with a single latch and a
+/// preheader.
+pub fn basic_ivs(
+    func: &Function,
+    lp: &Loop,
+    preheader: BlockId,
+) -> Vec<BasicIv> {
+    let [latch] = lp.latches[..] else {
+        return Vec::new();
+    };
+    let defs: HashMap<Local, &Rvalue> = lp
+        .blocks
+        .iter()
+        .flat_map(|&block| &func.block(block).insts)
+        .filter_map(|inst| match inst {
+            Inst::Assign(dest, rvalue) => Some((*dest, rvalue)),
+            _ => None,
+        })
+        .collect();
+
+    let mut ivs = Vec::new();
+    for inst in &func.block(lp.header).insts {
+        let Inst::Assign(phi, Rvalue::Phi(args)) = inst else {
+            break;
+        };
+        let [(a, ref a_value), (b, ref b_value)] = args[..] else {
+            continue;
+        };
+        let (init, next) = if a == preheader && b == latch {
+            (a_value, b_value)
+        } else if a == latch && b == preheader {
+            (b_value, a_value)
+        } else {
+            continue;
+        };
+        let Operand::Local(next) = next else {
+            continue;
+        };
+        let this = Operand::Local(*phi);
+        let step = match defs.get(next) {
+            Some(Rvalue::Bin(
+                BinOp::Add,
+                x,
+                Operand::Const(Const::Int(c, _)),
+            ))
+            | Some(Rvalue::Bin(
+                BinOp::Add,
+                Operand::Const(Const::Int(c, _)),
+                x,
+            )) if *x == this => *c,
+            Some(Rvalue::Bin(
+                BinOp::Sub,
+                x,
+                Operand::Const(Const::Int(c, _)),
+            )) if *x == this => c.wrapping_neg(),
+            _ => continue,
+        };
+        ivs.push(BasicIv {
+            phi: *phi,
+            init: init.clone(),
+            step,
+        });
+    }
+    ivs
+}
@@ -0,0 +1,2085 @@
+//! Lowering from the HIR to the MIR.
+//!
+//! Lowering assumes a well-typed program: the program is type checked first,
+//! and then walked again with a fresh [`Checker`] to resolve names and types.
+//!
+//! Lowering also notes the HIR statement or expression that every instruction
+//! and terminator comes from, in a [`Trace`] of each function.  Traces are
+//! not kept up to date by the optimizations, so they only describe a module
+//! as it was lowered.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::codegen::Target;
+use crate::ctype::{
+    CType, DataModel, FloatType, FnType, IntRank, IntType, StructId,
+};
+use crate::hir::{
+    self, Attrs, Designator, Expr, FnDefn, InitItem, Initializer, Lit, Program,
+    Span, Spanned, Stmt, Storage, Symbol, TopDefn, UnaOp as HUnaOp, VarDefn,
+};
+use crate::typeck::{Checker, TypeError};
+
+use super::*;
+
+/// Lower a program to the MIR, laying out its types for a target.
+pub fn lower(
+    program: &Program,
+    target: Target,
+) -> Result<Module, Vec<TypeError>> {
+    lower_traced(program, target).map(|(module, _)| module)
+}
+
+/// Lower a program to the MIR, along with the traces of its functions.
+pub fn lower_traced<'a>(
+    program: &'a Program<'a>,
+    target: Target,
+) -> Result<(Module, Vec<Trace<'a>>), Vec<TypeError>> {
+    Checker::new(target).check(program)?;
+    program.names.enter(|| {
+        let mut lowerer = Lowerer::new(target);
+        for (decl, span) in program.decls.iter().zip(&program.spans) {
+            lowerer.span = *span;
+            lowerer.top_defn(decl);
+        }
+        lowerer.finish()
+    })
+}
+
+/// Unwrap the result of a check that already succeeded.
+fn checked<T>(result: Result<T, TypeError>) -> T {
+    result.expect("the program has been type checked")
+}
+
+//----------- Trace ------------------------------------------------------------
+
+/// Where the code of a lowered function comes from in the HIR.
+pub struct Trace<'a> {
+    /// The definition of the function.
+    pub defn: &'a FnDefn<'a>,
+
+    /// The origins of the instructions of each block, indexed by [`BlockId`].
+    pub insts: Vec<Vec<Option<Origin<'a>>>>,
+
+    /// The origins of the terminators of each block, indexed by [`BlockId`].
+    pub terms: Vec<Option<Origin<'a>>>,
+}
+
+/// The HIR node some code was lowered from.
+///
+/// Code is traced to the statement it implements, or to the controlling
+/// expression of a conditional, loop or `switch`.  Code without an origin,
+/// such as spilling the parameters, was added by lowering itself.
+#[derive(Copy, Clone)]
+pub enum Origin<'a> {
+    /// A statement.
+    Stmt(&'a Stmt<'a>),
+
+    /// An expression.
+    Expr(&'a Expr<'a>),
+}
+
+impl Origin<'_> {
+    /// Whether two origins are the same node.
+    pub fn is(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Stmt(a), Self::Stmt(b)) => std::ptr::eq(*a, *b),
+            (Self::Expr(a), Self::Expr(b)) => std::ptr::eq(*a, *b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Origin<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stmt(Stmt::Expr(expr)) => write!(f, "{expr:?}"),
+            Self::Expr(expr) => write!(f, "{expr:?}"),
+            Self::Stmt(Stmt::VarDefn(defn)) => write!(f, "{defn:?}"),
+            Self::Stmt(Stmt::Return(None)) => write!(f, "return"),
+            Self::Stmt(Stmt::Return(Some(expr))) => {
+                write!(f, "return {expr:?}")
+            }
+            Self::Stmt(stmt) => write!(f, "{stmt:?}"),
+        }
+    }
+}
+
+//----------- Lowerer ----------------------------------------------------------
+
+/// The state of lowering a translation unit.
+struct Lowerer<'a> {
+    /// The checker tracking names and types in scope.
+    checker: Checker,
+
+    /// The global variables defined so far.
+    globals: Vec<Global>,
+
+    /// The functions defined so far.
+    functions: Vec<Function>,
+
+    /// The file-scope symbols, in order of first declaration, with their
+    /// latest declared types and their first declarations.
+    symbols: Vec<(String, CType, Span)>,
+
+    /// The source text of the top-level definition being lowered.
+    span: Span,
+
+    /// The file-scope symbols that have been defined.
+    defined: HashSet<String>,
+
+    /// The file-scope symbols with internal linkage.
+    statics: HashSet<String>,
+
+    /// The globals holding string literals, by contents.
+    strings: HashMap<Vec<u8>, String>,
+
+    /// The errors found during lowering.
+    errors: Vec<TypeError>,
+
+    /// The function being lowered.
+    func: Function,
+
+    /// The block instructions are being appended to.
+    current: BlockId,
+
+    /// The addresses of the variables in scope, innermost last.
+    ///
+    /// Names not found here refer to file-scope symbols.
+    scopes: Vec<HashMap<Symbol, Operand>>,
+
+    /// The targets of `break` statements, innermost last.
+    breaks: Vec<BlockId>,
+
+    /// The targets of `continue` statements, innermost last.
+    continues: Vec<BlockId>,
+
+    /// The switch statements being lowered, innermost last.
+    switches: Vec<SwitchTargets>,
+
+    /// The blocks of the labels in the function.
+    labels: HashMap<Symbol, BlockId>,
+
+    /// The traces of the functions lowered so far, the current one last.
+    traces: Vec<Trace<'a>>,
+
+    /// The origin of the code being emitted, if any.
+    origin: Option<Origin<'a>>,
+}
+
+/// The targets collected for a switch statement.
+struct SwitchTargets {
+    /// The machine type of the controlling expression.
+    ty: Ty,
+
+    /// The `case` labels, as pairs of a value and its target.
+    cases: Vec<(i64, BlockId)>,
+
+    /// The `default` label, if any.
+    default: Option<BlockId>,
+}
+
+/// A lowered expression.
+enum Value {
+    /// A scalar value.
+    Scalar(Operand, CType),
+
+    /// An object in memory, by address.
+    ///
+    /// Lvalues and aggregates are represented this way.
+    Place(Operand, CType),
+
+    /// The result of a void expression.
+    Void,
+}
+
+/// The value of a constant expression in a static initializer.
+enum ConstValue {
+    /// An integer, and whether it is signed.
+    Int(i64, bool),
+
+    /// A floating-point number.
+    Float(f64),
+
+    /// The address of a symbol plus an offset.
+    Addr(String, i64),
+}
+
+impl<'a> Lowerer<'a> {
+    /// Construct a new [`Lowerer`] for a target.
+    fn new(target: Target) -> Self {
+        Self {
+            checker: Checker::new(target),
+            globals: Vec::new(),
+            functions: Vec::new(),
+            symbols: Vec::new(),
+            span: Span { start: 0, end: 0 },
+            defined: HashSet::new(),
+            statics: HashSet::new(),
+            strings: HashMap::new(),
+            errors: Vec::new(),
+            func: Self::placeholder(),
+            current: BlockId(0),
+            scopes: Vec::new(),
+            breaks: Vec::new(),
+            continues: Vec::new(),
+            switches: Vec::new(),
+            labels: HashMap::new(),
+            traces: Vec::new(),
+            origin: None,
+        }
+    }
+
+    /// Construct a function without any blocks.
+    fn empty_function(
+        name: String,
+        ty: FnType,
+        model: DataModel,
+        span: Span,
+    ) -> Function {
+        Function {
+            name,
+            ty,
+            local: false,
+            inline: false,
+            params: Vec::new(),
+            locals: Vec::new(),
+            blocks: Vec::new(),
+            model,
+            span,
+        }
+    }
+
+    /// The function held while not lowering any.
+    fn placeholder() -> Function {
+        let ty = FnType {
+            ret: CType::Void,
+            params: Vec::new(),
+            variadic: false,
+            prototype: true,
+        };
+        let span = Span { start: 0, end: 0 };
+        Self::empty_function(String::new(), ty, DataModel::default(), span)
+    }
+
+    /// The data model the types are laid out in.
+    fn model(&self) -> DataModel {
+        self.checker.types.model()
+    }
+
+    /// Finish lowering, returning the module and the traces of its
+    /// functions.
+    fn finish(self) -> Result<(Module, Vec<Trace<'a>>), Vec<TypeError>> {
+        if !self.errors.is_empty() {
+            return Err(self.errors);
+        }
+        let mut functions = self.functions;
+        for func in &mut functions {
+            func.local |= self.statics.contains(&func.name);
+        }
+        let mut globals = self.globals;
+        for global in &mut globals {
+            global.local |= self.statics.contains(&global.name);
+        }
+        let externs = self
+            .symbols
+            .into_iter()
+            .filter(|(name, ..)| !self.defined.contains(name))
+            .map(|(name, ty, span)| Extern { name, ty, span })
+            .collect();
+        let module = Module {
+            types: self.checker.types,
+            globals,
+            functions,
+            externs,
+        };
+        Ok((module, self.traces))
+    }
+
+    /// Record a declaration of a file-scope symbol, at some source text.
+    fn declare_symbol(&mut self, name: &str, ty: &CType, span: Span) {
+        match self.symbols.iter_mut().find(|(symbol, ..)| symbol == name) {
+            Some((_, old, _)) => *old = ty.clone(),
+            None => self.symbols.push((name.into(), ty.clone(), span)),
+        }
+    }
+
+    /// Record the storage class of a file-scope declaration.
+    ///
+    /// A symbol declared `static` anywhere has internal linkage.
+    fn declare_linkage(&mut self, name: &str, attrs: &Attrs) {
+        if attrs.storage == Some(Storage::Static) {
+            self.statics.insert(name.into());
+        }
+    }
+}
+
+//--- Top-level definitions
+
+impl<'a> Lowerer<'a> {
+    /// Lower a top-level definition.
+    fn top_defn(&mut self, decl: &'a TopDefn) {
+        match decl {
+            TopDefn::Fn(defn) => self.fn_defn(defn),
+            TopDefn::Var(defn) => self.global_var_defn(defn),
+            TopDefn::Type(defn) => checked(self.checker.type_defn(defn)),
+        }
+    }
+
+    /// Lower a function definition.
+    fn fn_defn(&mut self, defn: &'a FnDefn) {
+        let fn_type = checked(self.checker.fn_signature(defn));
+        let ty = CType::Fn(Box::new(fn_type.clone()));
+        checked(self.checker.declare(defn.name, ty.clone()));
+        let name = defn.name.to_string();
+        self.declare_symbol(&name, &ty, self.span);
+        self.declare_linkage(&name, &defn.attrs);
+        let Some(body) = &defn.body else {
+            return;
+        };
+        self.defined.insert(defn.name.to_string());
+
+        self.func = Self::empty_function(
+            defn.name.to_string(),
+            fn_type.clone(),
+            self.model(),
+            self.span,
+        );
+        self.func.inline = defn.attrs.inline;
+        self.traces.push(Trace {
+            defn,
+            insts: Vec::new(),
+            terms: Vec::new(),
+        });
+        self.current = self.new_block();
+        self.push_scope();
+        for (param, ty) in defn.params.iter().zip(&fn_type.params) {
+            let name = param.binding.name();
+            let memory = LocalDecl {
+                ty: ty.clone(),
+                name: name.map(|name| name.to_string()),
+                memory: true,
+            };
+            if Ty::of(ty, self.model()).is_some() {
+                // Scalar parameters arrive in registers, and are spilled to
+                // memory like every other variable.
+                let reg = self.func.add_temp(ty.clone());
+                self.func.params.push(reg);
+                if let Some(name) = name {
+                    let local = self.func.add_local(memory);
+                    self.store(Operand::Addr(local), Operand::Local(reg));
+                    self.bind(name, ty, Operand::Addr(local));
+                }
+            } else {
+                let local = self.func.add_local(memory);
+                self.func.params.push(local);
+                if let Some(name) = name {
+                    self.bind(name, ty, Operand::Addr(local));
+                }
+            }
+        }
+        for stmt in body.stmts {
+            self.stmt(stmt);
+        }
+
+        // Falling off the end of `main` returns 0; for other functions, the
+        // result is unspecified.
+        let ret = fn_type.ret;
+        let value = match Ty::of(&ret, self.model()) {
+            Some(ty) => Some(Self::zero(ty)),
+            None if ret == CType::Void => None,
+            None => Some(Operand::Addr(self.memory_temp(ret))),
+        };
+        self.origin = None;
+        self.set_term(Terminator::Return(value));
+
+        self.pop_scope();
+        self.labels.clear();
+        let mut func = std::mem::replace(&mut self.func, Self::placeholder());
+        let order = func.remove_unreachable();
+        let trace = self.traces.last_mut().unwrap();
+        let mut insts: Vec<_> = std::mem::take(&mut trace.insts)
+            .into_iter()
+            .map(Some)
+            .collect();
+        trace.insts = order
+            .iter()
+            .map(|old| insts[old.0 as usize].take().unwrap())
+            .collect();
+        trace.terms = order
+            .iter()
+            .map(|old| trace.terms[old.0 as usize])
+            .collect();
+        self.functions.push(func);
+    }
+
+    /// Lower a file-scope variable definition.
+    fn global_var_defn(&mut self, defn: &VarDefn) {
+        let base = checked(self.checker.resolve_base(defn.base));
+        for (binding, init) in defn.bindings {
+            let ty = checked(self.checker.var_binding(
+                &base,
+                binding,
+                init.as_ref(),
+            ));
+            let Some(name) = binding.name() else {
+                continue;
+            };
+            let name = name.to_string();
+            self.declare_symbol(&name, &ty, binding.span);
+            self.declare_linkage(&name, &defn.attrs);
+            let is_extern = defn.attrs.storage == Some(Storage::Extern);
+            if let CType::Fn(_) = ty {
+                continue;
+            }
+            if is_extern && init.is_none() {
+                // Only a declaration; the variable is defined elsewhere (or
+                // later in this translation unit).
+                if let Some(global) =
+                    self.globals.iter_mut().find(|global| global.name == name)
+                {
+                    global.ty = ty;
+                }
+                continue;
+            }
+            self.defined.insert(name.clone());
+
+            let init = self.static_init(&ty, init.as_ref());
+            // Tentative definitions may be repeated.
+            match self.globals.iter_mut().find(|global| global.name == name) {
+                Some(global) => {
+                    global.ty = ty;
+                    if init.is_some() {
+                        global.init = init;
+                    }
+                }
+                None => self.globals.push(Global {
+                    name,
+                    ty,
+                    local: false,
+                    readonly: false,
+                    init,
+                    span: Some(binding.span),
+                }),
+            }
+        }
+    }
+
+    /// Define the global holding a block-scope `static` variable, returning
+    /// its symbol.
+    fn static_local(
+        &mut self,
+        name: &str,
+        ty: &CType,
+        init: Option<&Initializer>,
+    ) -> String {
+        let mut symbol = format!("{}.{}", self.func.name, name);
+        let mut count = 0;
+        while self.globals.iter().any(|global| global.name == symbol) {
+            count += 1;
+            symbol = format!("{}.{}.{}", self.func.name, name, count);
+        }
+        let init = self.static_init(ty, init);
+        self.globals.push(Global {
+            name: symbol.clone(),
+            ty: ty.clone(),
+            local: true,
+            readonly: false,
+            init,
+            span: None,
+        });
+        symbol
+    }
+
+    /// Evaluate the initializer of an object with static storage, if any.
+    fn static_init(
+        &mut self,
+        ty: &CType,
+        init: Option<&Initializer>,
+    ) -> Option<GlobalInit> {
+        match self.global_init(ty, init?) {
+            Ok(init) => Some(init),
+            Err(err) => {
+                self.errors.push(err);
+                None
+            }
+        }
+    }
+
+    /// Get the global holding a string literal.
+    fn string(&mut self, bytes: &[u8]) -> String {
+        if let Some(name) = self.strings.get(bytes) {
+            return name.clone();
+        }
+        let name = format!(".str.{}", self.strings.len());
+        let mut contents = bytes.to_vec();
+        contents.push(0);
+        self.globals.push(Global {
+            name: name.clone(),
+            ty: CType::Array {
+                elem: Box::new(CType::CHAR),
+                len: Some(contents.len() as u64),
+            },
+            local: true,
+            readonly: true,
+            init: Some(GlobalInit {
+                bytes: contents,
+                relocs: Vec::new(),
+            }),
+            span: None,
+        });
+        self.strings.insert(bytes.to_vec(), name.clone());
+        name
+    }
+}
+
+//--- Static initializers
+
+impl<'a> Lowerer<'a> {
+    /// Evaluate the initializer of a global variable.
+    fn global_init(
+        &mut self,
+        ty: &CType,
+        init: &Initializer,
+    ) -> Result<GlobalInit, TypeError> {
+        let mut out = GlobalInit {
+            bytes: vec![0; self.checker.types.size_of(ty) as usize],
+            relocs: Vec::new(),
+        };
+        self.const_init(&mut out, 0, ty, init)?;
+        Ok(out)
+    }
+
+    /// Evaluate the initializer of an object at an offset in a global.
+    fn const_init(
+        &mut self,
+        out: &mut GlobalInit,
+        offset: u64,
+        ty: &CType,
+        init: &Initializer,
+    ) -> Result<(), TypeError> {
+        match (ty, init) {
+            (
+                CType::Array { elem, len },
+                Initializer::Expr(Spanned {
+                    node: Expr::Lit(Lit::Str(s)),
+                    ..
+                }),
+            ) if is_char(elem) => {
+                let len = len.unwrap() as usize;
+                let start = offset as usize;
+                let n = s.len().min(len);
+                out.bytes[start..start + n].copy_from_slice(&s[..n]);
+                Ok(())
+            }
+            (CType::Array { elem, .. }, Initializer::List(items)) => {
+                let size = self.checker.types.size_of(elem);
+                let mut next = 0;
+                for item in *items {
+                    if let Some(Designator::Index(index)) =
+                        item.designators.first()
+                    {
+                        next = self.checker.const_int(index).unwrap() as u64;
+                    }
+                    let rest = Self::rest(item);
+                    let rest = rest.as_ref().map(std::slice::from_ref);
+                    let rest = rest.map(Initializer::List);
+                    self.const_init(
+                        out,
+                        offset + next * size,
+                        elem,
+                        rest.as_ref().unwrap_or(&item.init),
+                    )?;
+                    next += 1;
+                }
+                Ok(())
+            }
+            (CType::Struct(id), Initializer::List(items)) => {
+                let layout = self.checker.types.layout(*id);
+                let mut next = 0;
+                for item in *items {
+                    if let Some(Designator::Member(name)) =
+                        item.designators.first()
+                    {
+                        let name = &name.to_string();
+                        next = self.checker.types.field(*id, name).unwrap().0;
+                    }
+                    let field_ty = self.field_ty(*id, next);
+                    let rest = Self::rest(item);
+                    let rest = rest.as_ref().map(std::slice::from_ref);
+                    let rest = rest.map(Initializer::List);
+                    self.const_init(
+                        out,
+                        offset + layout.offsets[next],
+                        &field_ty,
+                        rest.as_ref().unwrap_or(&item.init),
+                    )?;
+                    next += 1;
+                }
+                Ok(())
+            }
+            (_, Initializer::List(items)) => {
+                self.const_init(out, offset, ty, &items[0].init)
+            }
+            (_, Initializer::Expr(expr)) => self
+                .const_value(expr)
+                .and_then(|value| self.write_const(out, offset, ty, value))
+                .map_err(|error| TypeError {
+                    span: Some(expr.span),
+                    ..error
+                }),
+        }
+    }
+
+    /// Write a constant scalar into a global.
+    fn write_const(
+        &mut self,
+        out: &mut GlobalInit,
+        offset: u64,
+        ty: &CType,
+        value: ConstValue,
+    ) -> Result<(), TypeError> {
+        let Some(mty) = Ty::of(ty, self.model()) else {
+            return Err(Self::not_constant());
+        };
+        let model = self.model();
+        let bytes = match (value, mty) {
+            (ConstValue::Addr(symbol, addend), _)
+                if !mty.is_float()
+                    && mty.size(model) == model.pointer_size() =>
+            {
+                out.relocs.push(Reloc {
+                    offset,
+                    symbol,
+                    addend,
+                });
+                return Ok(());
+            }
+            (ConstValue::Addr(..), _) => return Err(Self::not_constant()),
+            (ConstValue::Int(value, _), _) if is_bool(ty) => {
+                ((value != 0) as u64).to_le_bytes()
+            }
+            (ConstValue::Float(value), _) if is_bool(ty) => {
+                ((value != 0.0) as u64).to_le_bytes()
+            }
+            (ConstValue::Int(value, signed), Ty::F32) => {
+                let value = if signed {
+                    value as f32
+                } else {
+                    value as u64 as f32
+                };
+                (value.to_bits() as u64).to_le_bytes()
+            }
+            (ConstValue::Int(value, signed), Ty::F64) => {
+                let value = if signed {
+                    value as f64
+                } else {
+                    value as u64 as f64
+                };
+                value.to_bits().to_le_bytes()
+            }
+            (ConstValue::Int(value, _), _) => value.to_le_bytes(),
+            (ConstValue::Float(value), Ty::F32) => {
+                ((value as f32).to_bits() as u64).to_le_bytes()
+            }
+            (ConstValue::Float(value), Ty::F64) => {
+                value.to_bits().to_le_bytes()
+            }
+            (ConstValue::Float(value), _) if signed(ty) => {
+                (value as i64).to_le_bytes()
+            }
+            (ConstValue::Float(value), _) => (value as u64).to_le_bytes(),
+        };
+        let start = offset as usize;
+        let size = mty.size(model) as usize;
+        out.bytes[start..start + size].copy_from_slice(&bytes[..size]);
+        Ok(())
+    }
+
+    /// Evaluate a constant expression in a static initializer.
+    fn const_value(
+        &mut self,
+        expr: &Spanned<Expr>,
+    ) -> Result<ConstValue, TypeError> {
+        let ty = checked(self.checker.rvalue(expr));
+        if ty.is_integer()
+            && let Some(value) = self.checker.const_int(expr)
+        {
+            return Ok(ConstValue::Int(value, signed(&ty)));
+        }
+        match &expr.node {
+            Expr::Lit(Lit::Float { value, .. }) => {
+                Ok(ConstValue::Float(*value))
+            }
+            Expr::Lit(Lit::Str(_)) | Expr::Ident(_) => {
+                let (symbol, offset) = self.const_addr(expr)?;
+                Ok(ConstValue::Addr(symbol, offset))
+            }
+            Expr::Ref(e) => {
+                let (symbol, offset) = self.const_addr(e)?;
+                Ok(ConstValue::Addr(symbol, offset))
+            }
+            Expr::Una(HUnaOp::Neg, e) => match self.const_value(e)? {
+                ConstValue::Float(value) => Ok(ConstValue::Float(-value)),
+                _ => Err(Self::not_constant()),
+            },
+            Expr::Una(HUnaOp::Plus, e) => self.const_value(e),
+            Expr::Cast(_, e) => match (self.const_value(e)?, &ty) {
+                (ConstValue::Int(value, signed), CType::Float(_)) => {
+                    Ok(ConstValue::Float(if signed {
+                        value as f64
+                    } else {
+                        value as u64 as f64
+                    }))
+                }
+                (ConstValue::Float(value), CType::Int(int)) => {
+                    Ok(ConstValue::Int(value as i64, int.signed))
+                }
+                (ConstValue::Addr(..), CType::Float(_)) => {
+                    Err(Self::not_constant())
+                }
+                (value, _) => Ok(value),
+            },
+            Expr::Bin(op @ (hir::BinOp::Add | hir::BinOp::Sub), lhs, rhs) => {
+                let lhs_ty = checked(self.checker.rvalue(lhs));
+                let (ptr, int, ptr_ty) = if lhs_ty.is_pointer() {
+                    (lhs, rhs, lhs_ty)
+                } else {
+                    (rhs, lhs, checked(self.checker.rvalue(rhs)))
+                };
+                let (
+                    Some(pointee),
+                    ConstValue::Addr(symbol, offset),
+                    ConstValue::Int(index, _),
+                ) = (
+                    ptr_ty.pointee(),
+                    self.const_value(ptr)?,
+                    self.const_value(int)?,
+                )
+                else {
+                    return Err(Self::not_constant());
+                };
+                let size = self.checker.types.size_of(pointee) as i64;
+                let delta = if *op == hir::BinOp::Sub {
+                    -index
+                } else {
+                    index
+                };
+                Ok(ConstValue::Addr(symbol, offset + delta * size))
+            }
+            _ => Err(Self::not_constant()),
+        }
+    }
+
+    /// Evaluate an address constant.
+    fn const_addr(
+        &mut self,
+        expr: &Spanned<Expr>,
+    ) -> Result<(String, i64), TypeError> {
+        match &expr.node {
+            // Only objects with static storage have constant addresses.
+            Expr::Ident(name) => match self.lookup_addr(*name) {
+                Operand::Const(Const::Symbol(symbol)) => Ok((symbol, 0)),
+                _ => Err(Self::not_constant()),
+            },
+            Expr::Lit(Lit::Str(s)) => Ok((self.string(s), 0)),
+            Expr::Member(e, name) => {
+                let CType::Struct(id) = checked(self.checker.expr(e)) else {
+                    unreachable!("member access on a non-struct");
+                };
+                let (symbol, offset) = self.const_addr(e)?;
+                let name = &name.to_string();
+                let (index, _) = self.checker.types.field(id, name).unwrap();
+                let field_offset = self.checker.types.layout(id).offsets[index];
+                Ok((symbol, offset + field_offset as i64))
+            }
+            Expr::Index(e, index) => {
+                let ty = checked(self.checker.expr(e));
+                let CType::Array { elem, .. } = ty else {
+                    return Err(Self::not_constant());
+                };
+                let (symbol, offset) = self.const_addr(e)?;
+                let Some(index) = self.checker.const_int(index) else {
+                    return Err(Self::not_constant());
+                };
+                let size = self.checker.types.size_of(&elem) as i64;
+                Ok((symbol, offset + index * size))
+            }
+            _ => Err(Self::not_constant()),
+        }
+    }
+
+    /// The error for a non-constant static initializer.
+    fn not_constant() -> TypeError {
+        TypeError {
+            function: None,
+            span: None,
+            message: "initializer element is not a constant".into(),
+        }
+    }
+
+    /// The item for the designators after the first, if any.
+    ///
+    /// Without further designators, the item's own initializer applies.
+    fn rest<'b>(item: &InitItem<'b>) -> Option<InitItem<'b>> {
+        match item.designators.get(1..).unwrap_or_default() {
+            [] => None,
+            rest => Some(InitItem {
+                designators: rest,
+                init: item.init,
+            }),
+        }
+    }
+}
+
+//--- Building blocks
+
+impl<'a> Lowerer<'a> {
+    /// Add a new, empty block.
+    fn new_block(&mut self) -> BlockId {
+        self.func.blocks.push(Block {
+            insts: Vec::new(),
+            term: Terminator::Unreachable,
+        });
+        let trace = self.traces.last_mut().unwrap();
+        trace.insts.push(Vec::new());
+        trace.terms.push(None);
+        BlockId(self.func.blocks.len() as u32 - 1)
+    }
+
+    /// Set the terminator of the current block.
+    ///
+    /// The caller must switch to another block before emitting more code.
+    fn set_term(&mut self, term: Terminator) {
+        self.func.block_mut(self.current).term = term;
+        let trace = self.traces.last_mut().unwrap();
+        trace.terms[self.current.0 as usize] = self.origin;
+    }
+
+    /// End the current block, continuing in a fresh (unreachable) block.
+    fn terminate(&mut self, term: Terminator) {
+        self.set_term(term);
+        self.current = self.new_block();
+    }
+
+    /// Fall through from the current block into another.
+    fn fall_into(&mut self, block: BlockId) {
+        self.set_term(Terminator::Jump(block));
+        self.current = block;
+    }
+
+    /// Append an instruction to the current block.
+    fn emit(&mut self, inst: Inst) {
+        self.func.block_mut(self.current).insts.push(inst);
+        let trace = self.traces.last_mut().unwrap();
+        trace.insts[self.current.0 as usize].push(self.origin);
+    }
+
+    /// Compute a value into a new register temporary.
+    fn assign(&mut self, ty: CType, rvalue: Rvalue) -> Operand {
+        let temp = self.func.add_temp(ty);
+        self.emit(Inst::Assign(temp, rvalue));
+        Operand::Local(temp)
+    }
+
+    /// Add a new memory temporary.
+    fn memory_temp(&mut self, ty: CType) -> Local {
+        self.func.add_local(LocalDecl {
+            ty,
+            name: None,
+            memory: true,
+        })
+    }
+
+    /// Load a scalar from memory.
+    fn load(&mut self, addr: Operand, ty: &CType) -> Operand {
+        self.assign(ty.clone(), Rvalue::Load(addr))
+    }
+
+    /// Store a scalar to memory.
+    fn store(&mut self, addr: Operand, value: Operand) {
+        self.emit(Inst::Store { addr, value });
+    }
+
+    /// Compute the address of an element at a constant index.
+    fn elem_addr(
+        &mut self,
+        base: Operand,
+        index: u64,
+        elem: &CType,
+    ) -> Operand {
+        if index == 0 {
+            return base;
+        }
+        let index = Operand::Const(Const::Int(index as i64, Ty::I64));
+        let ty = elem.clone().pointer_to();
+        self.assign(ty, Rvalue::ElemAddr(base, index, elem.clone()))
+    }
+
+    /// Compute the address of a struct field.
+    fn field_addr(
+        &mut self,
+        base: Operand,
+        id: StructId,
+        index: usize,
+    ) -> Operand {
+        let ty = self.field_ty(id, index).pointer_to();
+        self.assign(ty, Rvalue::FieldAddr(base, id, index))
+    }
+
+    /// The type of a struct field.
+    fn field_ty(&self, id: StructId, index: usize) -> CType {
+        let fields = self.checker.types.get(id).fields.as_ref().unwrap();
+        fields[index].ty.clone()
+    }
+
+    /// The zero constant of a machine type.
+    fn zero(ty: Ty) -> Operand {
+        Operand::Const(match ty {
+            Ty::F32 | Ty::F64 => Const::Float(0.0, ty),
+            _ => Const::Int(0, ty),
+        })
+    }
+
+    /// The integer type used to move a chunk of memory of the given size.
+    fn chunk_type(size: u64) -> CType {
+        let rank = match size {
+            1 => IntRank::Char,
+            2 => IntRank::Short,
+            4 => IntRank::Int,
+            _ => IntRank::LongLong,
+        };
+        CType::Int(IntType { rank, signed: true })
+    }
+
+    /// Copy an object between memory locations.
+    fn copy(&mut self, dest: Operand, src: Operand, ty: &CType) {
+        let size = self.checker.types.size_of(ty);
+        let chunk = self.checker.types.align_of(ty).min(8);
+        let chunk_ty = Self::chunk_type(chunk);
+        for i in 0..size / chunk {
+            let from = self.elem_addr(src.clone(), i, &chunk_ty);
+            let value = self.load(from, &chunk_ty);
+            let to = self.elem_addr(dest.clone(), i, &chunk_ty);
+            self.store(to, value);
+        }
+    }
+
+    /// Fill an object in memory with zeros.
+    fn zero_fill(&mut self, dest: Operand, ty: &CType) {
+        let size = self.checker.types.size_of(ty);
+        let chunk = self.checker.types.align_of(ty).min(8);
+        let chunk_ty = Self::chunk_type(chunk);
+        let zero = Self::zero(Ty::of(&chunk_ty, self.model()).unwrap());
+        for i in 0..size / chunk {
+            let to = self.elem_addr(dest.clone(), i, &chunk_ty);
+            self.store(to, zero.clone());
+        }
+    }
+}
+
+//--- Scopes
+
+impl<'a> Lowerer<'a> {
+    /// Enter a new block scope.
+    fn push_scope(&mut self) {
+        self.checker.push_scope();
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Leave the innermost block scope.
+    fn pop_scope(&mut self) {
+        self.checker.pop_scope();
+        self.scopes.pop();
+    }
+
+    /// Bind a name to the address of an object in the innermost scope.
+    fn bind(&mut self, name: Symbol, ty: &CType, addr: Operand) {
+        checked(self.checker.declare(name, ty.clone()));
+        self.scopes.last_mut().unwrap().insert(name, addr);
+    }
+
+    /// The address of the object a name refers to.
+    fn lookup_addr(&self, name: Symbol) -> Operand {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name).cloned())
+            .unwrap_or_else(|| Operand::Const(Const::Symbol(name.to_string())))
+    }
+
+    /// The block for a label in the current function.
+    fn label(&mut self, name: Symbol) -> BlockId {
+        if let Some(&block) = self.labels.get(&name) {
+            return block;
+        }
+        let block = self.new_block();
+        self.labels.insert(name, block);
+        block
+    }
+}
+
+//--- Statements
+
+impl<'a> Lowerer<'a> {
+    /// Lower a statement.
+    fn stmt(&mut self, stmt: &'a Stmt) {
+        self.origin = Some(Origin::Stmt(stmt));
+        match stmt {
+            Stmt::Empty => {}
+            Stmt::Goto(label) => {
+                let block = self.label(*label);
+                self.terminate(Terminator::Jump(block));
+            }
+            Stmt::Label(label, stmt) => {
+                let block = self.label(*label);
+                self.fall_into(block);
+                self.stmt(stmt);
+            }
+            Stmt::Switch { cond, body } => self.switch(cond, body),
+            Stmt::Case(value, stmt) => {
+                let block = self.new_block();
+                self.fall_into(block);
+                let value = self.checker.const_int(value).unwrap();
+                let switch = self.switches.last_mut().unwrap();
+                switch.cases.push((switch.ty.wrap(value), block));
+                self.stmt(stmt);
+            }
+            Stmt::Default(stmt) => {
+                let block = self.new_block();
+                self.fall_into(block);
+                self.switches.last_mut().unwrap().default = Some(block);
+                self.stmt(stmt);
+            }
+            Stmt::Block { stmts } => {
+                self.push_scope();
+                for stmt in *stmts {
+                    self.stmt(stmt);
+                }
+                self.pop_scope();
+            }
+            Stmt::VarDefn(defn) => self.local_var_defn(defn),
+            Stmt::Type(defn) => checked(self.checker.type_defn(defn)),
+            Stmt::Expr(expr) => {
+                self.expr(expr);
+            }
+            Stmt::If { cond, then, r#else } => {
+                let then_block = self.new_block();
+                let else_block = self.new_block();
+                self.origin = Some(Origin::Expr(cond));
+                self.branch(cond, then_block, else_block);
+                self.current = then_block;
+                self.stmt(then);
+                match r#else {
+                    Some(r#else) => {
+                        let join = self.new_block();
+                        self.set_term(Terminator::Jump(join));
+                        self.current = else_block;
+                        self.stmt(r#else);
+                        self.fall_into(join);
+                    }
+                    None => self.fall_into(else_block),
+                }
+            }
+            Stmt::For {
+                init,
+                cond,
+                step,
+                body,
+            } => {
+                if let Some(init) = init {
+                    self.origin = Some(Origin::Expr(init));
+                    self.expr(init);
+                }
+                let head = self.new_block();
+                let body_block = self.new_block();
+                let step_block = self.new_block();
+                let exit = self.new_block();
+                self.fall_into(head);
+                match cond {
+                    Some(cond) => {
+                        self.origin = Some(Origin::Expr(cond));
+                        self.branch(cond, body_block, exit);
+                    }
+                    None => self.set_term(Terminator::Jump(body_block)),
+                }
+                self.current = body_block;
+                self.loop_body(body, exit, step_block);
+                self.fall_into(step_block);
+                if let Some(step) = step {
+                    self.origin = Some(Origin::Expr(step));
+                    self.expr(step);
+                }
+                self.set_term(Terminator::Jump(head));
+                self.current = exit;
+            }
+            Stmt::While { cond, body } => {
+                let head = self.new_block();
+                let body_block = self.new_block();
+                let exit = self.new_block();
+                self.fall_into(head);
+                self.origin = Some(Origin::Expr(cond));
+                self.branch(cond, body_block, exit);
+                self.current = body_block;
+                self.loop_body(body, exit, head);
+                self.set_term(Terminator::Jump(head));
+                self.current = exit;
+            }
+            Stmt::DoWhile { body, cond } => {
+                let body_block = self.new_block();
+                let cond_block = self.new_block();
+                let exit = self.new_block();
+                self.fall_into(body_block);
+                self.loop_body(body, exit, cond_block);
+                self.fall_into(cond_block);
+                self.origin = Some(Origin::Expr(cond));
+                self.branch(cond, body_block, exit);
+                self.current = exit;
+            }
+            Stmt::Break => {
+                let target = *self.breaks.last().unwrap();
+                self.terminate(Terminator::Jump(target));
+            }
+            Stmt::Continue => {
+                let target = *self.continues.last().unwrap();
+                self.terminate(Terminator::Jump(target));
+            }
+            Stmt::Return(expr) => {
+                let value = expr.as_ref().and_then(|expr| {
+                    let ret = self.func.ty.ret.clone();
+                    let (value, ty) = self.rvalue(expr);
+                    (ret != CType::Void).then(|| self.convert(value, &ty, &ret))
+                });
+                self.terminate(Terminator::Return(value));
+            }
+        }
+    }
+
+    /// Lower the body of a loop.
+    fn loop_body(
+        &mut self,
+        body: &'a Stmt,
+        r#break: BlockId,
+        r#continue: BlockId,
+    ) {
+        self.breaks.push(r#break);
+        self.continues.push(r#continue);
+        self.stmt(body);
+        self.breaks.pop();
+        self.continues.pop();
+    }
+
+    /// Lower a switch statement.
+    ///
+    /// The body is lowered first, collecting the `case` labels; the
+    /// dispatching terminator is filled in afterwards.
+    fn switch(&mut self, cond: &'a Spanned<Expr>, body: &'a Stmt) {
+        self.origin = Some(Origin::Expr(cond));
+        let (value, ty) = self.rvalue(cond);
+        let promoted = ty.clone().promote();
+        let value = self.convert(value, &ty, &promoted);
+        let dispatch = self.current;
+        let exit = self.new_block();
+
+        // Code before the first label is unreachable.
+        self.current = self.new_block();
+        self.switches.push(SwitchTargets {
+            ty: Ty::of(&promoted, self.model()).unwrap(),
+            cases: Vec::new(),
+            default: None,
+        });
+        self.breaks.push(exit);
+        self.stmt(body);
+        self.breaks.pop();
+        let targets = self.switches.pop().unwrap();
+        self.fall_into(exit);
+
+        self.func.block_mut(dispatch).term = Terminator::Switch {
+            value,
+            cases: targets.cases,
+            default: targets.default.unwrap_or(exit),
+        };
+        let trace = self.traces.last_mut().unwrap();
+        trace.terms[dispatch.0 as usize] = Some(Origin::Expr(cond));
+    }
+
+    /// Lower a block-scope variable definition.
+    fn local_var_defn(&mut self, defn: &VarDefn) {
+        let base = checked(self.checker.resolve_base(defn.base));
+        for (binding, init) in defn.bindings {
+            let Some(name) = binding.name() else {
+                checked(self.checker.apply(base.clone(), binding));
+                continue;
+            };
+            // Resolve the type in a scratch scope, so that the variable is
+            // only declared once by `bind()`.
+            self.checker.push_scope();
+            let ty = checked(self.checker.var_binding(
+                &base,
+                binding,
+                init.as_ref(),
+            ));
+            self.checker.pop_scope();
+
+            let storage = defn.attrs.storage;
+            let text = name.to_string();
+            if matches!(ty, CType::Fn(_)) || storage == Some(Storage::Extern) {
+                self.declare_symbol(&text, &ty, binding.span);
+                let symbol = Operand::Const(Const::Symbol(text));
+                self.bind(name, &ty, symbol);
+                continue;
+            }
+            if storage == Some(Storage::Static) {
+                let symbol = self.static_local(&text, &ty, init.as_ref());
+                self.bind(name, &ty, Operand::Const(Const::Symbol(symbol)));
+                continue;
+            }
+            let local = self.func.add_local(LocalDecl {
+                ty: ty.clone(),
+                name: Some(text),
+                memory: true,
+            });
+            self.bind(name, &ty, Operand::Addr(local));
+            if let Some(init) = init {
+                self.init(Operand::Addr(local), &ty, init, false);
+            }
+        }
+    }
+
+    /// Lower the initialization of an object in memory.
+    ///
+    /// If `zeroed` is set, the object has already been filled with zeros.
+    fn init(
+        &mut self,
+        addr: Operand,
+        ty: &CType,
+        init: &Initializer,
+        zeroed: bool,
+    ) {
+        match (ty, init) {
+            (
+                CType::Array { elem, len },
+                Initializer::Expr(Spanned {
+                    node: Expr::Lit(Lit::Str(s)),
+                    ..
+                }),
+            ) if is_char(elem) => {
+                for i in 0..len.unwrap() {
+                    let byte = s.get(i as usize).copied().unwrap_or(0);
+                    if byte != 0 || !zeroed {
+                        let to = self.elem_addr(addr.clone(), i, elem);
+                        let value = Const::Int(byte as i8 as i64, Ty::I8);
+                        self.store(to, Operand::Const(value));
+                    }
+                }
+            }
+            (CType::Array { elem, .. }, Initializer::List(items)) => {
+                if !zeroed {
+                    self.zero_fill(addr.clone(), ty);
+                }
+                let mut next = 0;
+                for item in *items {
+                    if let Some(Designator::Index(index)) =
+                        item.designators.first()
+                    {
+                        next = self.checker.const_int(index).unwrap() as u64;
+                    }
+                    let to = self.elem_addr(addr.clone(), next, elem);
+                    let rest = Self::rest(item);
+                    let rest = rest.as_ref().map(std::slice::from_ref);
+                    let rest = rest.map(Initializer::List);
+                    self.init(
+                        to,
+                        elem,
+                        rest.as_ref().unwrap_or(&item.init),
+                        true,
+                    );
+                    next += 1;
+                }
+            }
+            (CType::Struct(id), Initializer::List(items)) => {
+                if !zeroed {
+                    self.zero_fill(addr.clone(), ty);
+                }
+                let mut next = 0;
+                for item in *items {
+                    if let Some(Designator::Member(name)) =
+                        item.designators.first()
+                    {
+                        let name = &name.to_string();
+                        next = self.checker.types.field(*id, name).unwrap().0;
+                    }
+                    let field_ty = self.field_ty(*id, next);
+                    let to = self.field_addr(addr.clone(), *id, next);
+                    let rest = Self::rest(item);
+                    let rest = rest.as_ref().map(std::slice::from_ref);
+                    let rest = rest.map(Initializer::List);
+                    self.init(
+                        to,
+                        &field_ty,
+                        rest.as_ref().unwrap_or(&item.init),
+                        true,
+                    );
+                    next += 1;
+                }
+            }
+            (_, Initializer::List(items)) => {
+                self.init(addr, ty, &items[0].init, zeroed);
+            }
+            (_, Initializer::Expr(expr)) => {
+                let (value, from) = self.rvalue(expr);
+                if Ty::of(ty, self.model()).is_some() {
+                    let value = self.convert(value, &from, ty);
+                    self.store(addr, value);
+                } else {
+                    self.copy(addr, value, ty);
+                }
+            }
+        }
+    }
+}
+
+//--- Expressions
+
+impl<'a> Lowerer<'a> {
+    /// Lower an expression.
+    fn expr(&mut self, expr: &Spanned<Expr>) -> Value {
+        match &expr.node {
+            Expr::Debug => Value::Scalar(Self::zero(Ty::I32), CType::INT),
+            Expr::Ref(e) => match self.expr(e) {
+                Value::Place(addr, ty) => Value::Scalar(addr, ty.pointer_to()),
+                _ => unreachable!("address of a non-lvalue"),
+            },
+            Expr::Bin(hir::BinOp::Assign, lhs, rhs) => {
+                let (addr, ty) = self.place(lhs);
+                let (value, from) = self.rvalue(rhs);
+                if Ty::of(&ty, self.model()).is_none() {
+                    self.copy(addr.clone(), value, &ty);
+                    return Value::Place(addr, ty);
+                }
+                let value = self.convert(value, &from, &ty);
+                self.store(addr, value.clone());
+                Value::Scalar(value, ty)
+            }
+            Expr::Bin(hir::BinOp::Comma, lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs)
+            }
+            Expr::Bin(hir::BinOp::And | hir::BinOp::Or, _, _) => {
+                self.logical(expr)
+            }
+            Expr::Bin(op, lhs, rhs) => match op.compound() {
+                Some(op) => {
+                    let (addr, ty) = self.place(lhs);
+                    let old = self.load(addr.clone(), &ty);
+                    let rhs = self.rvalue(rhs);
+                    let (value, from) = self.bin(op, (old, ty.clone()), rhs);
+                    let value = self.convert(value, &from, &ty);
+                    self.store(addr, value.clone());
+                    Value::Scalar(value, ty)
+                }
+                None => {
+                    let lhs = self.rvalue(lhs);
+                    let rhs = self.rvalue(rhs);
+                    let (value, ty) = self.bin(*op, lhs, rhs);
+                    Value::Scalar(value, ty)
+                }
+            },
+            Expr::Una(HUnaOp::PreInc, e) => {
+                self.inc_dec(e, hir::BinOp::Add, true)
+            }
+            Expr::Una(HUnaOp::PreDec, e) => {
+                self.inc_dec(e, hir::BinOp::Sub, true)
+            }
+            Expr::Una(HUnaOp::Deref, e) => {
+                let (ptr, ty) = self.rvalue(e);
+                Value::Place(ptr, ty.pointee().unwrap().clone())
+            }
+            Expr::Una(HUnaOp::Not, e) => {
+                let (value, ty) = self.rvalue(e);
+                let mty = Ty::of(&ty, self.model()).unwrap();
+                let op = if mty.is_float() {
+                    BinOp::FEq
+                } else {
+                    BinOp::Eq
+                };
+                let rvalue = Rvalue::Bin(op, value, Self::zero(mty));
+                Value::Scalar(self.assign(CType::INT, rvalue), CType::INT)
+            }
+            Expr::Una(op, e) => {
+                let (value, ty) = self.rvalue(e);
+                let promoted = ty.clone().promote();
+                let value = self.convert(value, &ty, &promoted);
+                let op = match op {
+                    HUnaOp::Plus => return Value::Scalar(value, promoted),
+                    HUnaOp::Neg if matches!(promoted, CType::Float(_)) => {
+                        UnaOp::FNeg
+                    }
+                    HUnaOp::Neg => UnaOp::Neg,
+                    _ => UnaOp::Not,
+                };
+                let value = match (op, value) {
+                    // Fold negative constants.
+                    (UnaOp::Neg, Operand::Const(Const::Int(value, ty))) => {
+                        Operand::Const(Const::Int(
+                            ty.wrap(value.wrapping_neg()),
+                            ty,
+                        ))
+                    }
+                    (UnaOp::FNeg, Operand::Const(Const::Float(value, ty))) => {
+                        Operand::Const(Const::Float(-value, ty))
+                    }
+                    (op, value) => {
+                        self.assign(promoted.clone(), Rvalue::Una(op, value))
+                    }
+                };
+                Value::Scalar(value, promoted)
+            }
+            Expr::Ident(name) => {
+                let ty = self.checker.lookup(*name).unwrap().clone();
+                Value::Place(self.lookup_addr(*name), ty)
+            }
+            Expr::Lit(Lit::Str(s)) => {
+                let name = self.string(s);
+                let ty = checked(self.checker.lit(&Lit::Str(s)));
+                Value::Place(Operand::Const(Const::Symbol(name)), ty)
+            }
+            Expr::Lit(lit) => {
+                let ty = checked(self.checker.lit(lit));
+                let mty = Ty::of(&ty, self.model()).unwrap();
+                let value = match *lit {
+                    Lit::Int { value, .. } => {
+                        Const::Int(mty.wrap(value as i64), mty)
+                    }
+                    Lit::Char(c) => Const::Int(c as i8 as i64, mty),
+                    Lit::Float { value, .. } => Const::Float(value, mty),
+                    Lit::Str(_) => unreachable!(),
+                };
+                Value::Scalar(Operand::Const(value), ty)
+            }
+            Expr::Cast(ty, e) => {
+                let to = checked(self.checker.type_name(ty));
+                let (value, from) = self.rvalue(e);
+                if to == CType::Void {
+                    return Value::Void;
+                }
+                Value::Scalar(self.convert(value, &from, &to), to)
+            }
+            Expr::Sizeof(_) | Expr::SizeofType(_) | Expr::Alignof(_) => {
+                let value = self.checker.const_int(expr).unwrap();
+                let mty = Ty::of(&CType::SIZE_T, self.model()).unwrap();
+                let value = Operand::Const(Const::Int(value, mty));
+                Value::Scalar(value, CType::SIZE_T)
+            }
+            Expr::CompoundLit(ty, items) => {
+                let ty = checked(self.checker.type_name(ty));
+                let init = Initializer::List(items);
+                let ty = checked(self.checker.initializer(&ty, &init));
+                let local = self.memory_temp(ty.clone());
+                self.init(Operand::Addr(local), &ty, &init, false);
+                Value::Place(Operand::Addr(local), ty)
+            }
+            Expr::Call(callee, args) => self.call(callee, args),
+            Expr::Member(e, name) => {
+                let (addr, ty) = self.place(e);
+                self.member(addr, &ty, &name.to_string())
+            }
+            Expr::Arrow(e, name) => {
+                let (ptr, ty) = self.rvalue(e);
+                self.member(ptr, ty.pointee().unwrap(), &name.to_string())
+            }
+            Expr::Index(e, index) => {
+                let lhs = self.rvalue(e);
+                let rhs = self.rvalue(index);
+                let ((base, ptr_ty), (index, index_ty)) = if lhs.1.is_pointer()
+                {
+                    (lhs, rhs)
+                } else {
+                    (rhs, lhs)
+                };
+                let index = self.convert(index, &index_ty, &CType::LONG);
+                let elem = ptr_ty.pointee().unwrap().clone();
+                let rvalue = Rvalue::ElemAddr(base, index, elem.clone());
+                Value::Place(self.assign(ptr_ty, rvalue), elem)
+            }
+            Expr::PostInc(e) => self.inc_dec(e, hir::BinOp::Add, false),
+            Expr::PostDec(e) => self.inc_dec(e, hir::BinOp::Sub, false),
+            Expr::Cond(cond, then, r#else) => {
+                self.conditional(expr, cond, then, r#else)
+            }
+            Expr::VaStart(ap, _) => {
+                let state = self.va_list(ap);
+                self.emit(Inst::VaStart(state));
+                Value::Void
+            }
+            Expr::VaArg(ap, ty) => {
+                let ty = checked(self.checker.type_name(ty));
+                let state = self.va_list(ap);
+                let addr = self.va_arg(state, &ty);
+                Value::Scalar(self.load(addr, &ty), ty)
+            }
+            Expr::VaEnd(ap) => {
+                self.va_list(ap);
+                Value::Void
+            }
+            Expr::VaCopy(dest, src) => {
+                let dest = self.va_list(dest);
+                let src = self.va_list(src);
+                let state = self.va_state();
+                self.copy(dest, src, &state);
+                Value::Void
+            }
+        }
+    }
+
+    /// Lower an expression used as a value.
+    ///
+    /// Scalars are loaded from memory, arrays and functions decay to
+    /// pointers, and aggregates are left in memory (returning their
+    /// address).
+    fn rvalue(&mut self, expr: &Spanned<Expr>) -> (Operand, CType) {
+        match self.expr(expr) {
+            Value::Scalar(value, ty) => (value, ty),
+            Value::Place(addr, ty @ (CType::Array { .. } | CType::Fn(_))) => {
+                (addr, ty.decay())
+            }
+            Value::Place(addr, ty) if Ty::of(&ty, self.model()).is_some() => {
+                (self.load(addr, &ty), ty)
+            }
+            Value::Place(addr, ty) => (addr, ty),
+            Value::Void => (Self::zero(Ty::I32), CType::Void),
+        }
+    }
+
+    /// Lower an expression designating an object, returning its address.
+    fn place(&mut self, expr: &Spanned<Expr>) -> (Operand, CType) {
+        match self.expr(expr) {
+            Value::Place(addr, ty) => (addr, ty),
+            _ => unreachable!("`{expr:?}` is not an lvalue"),
+        }
+    }
+
+    /// Lower a struct member access, given the address of the struct.
+    fn member(&mut self, addr: Operand, ty: &CType, name: &str) -> Value {
+        let CType::Struct(id) = *ty else {
+            unreachable!("member access on a non-struct");
+        };
+        let (index, field) = self.checker.types.field(id, name).unwrap();
+        let field_ty = field.ty.clone();
+        Value::Place(self.field_addr(addr, id, index), field_ty)
+    }
+
+    /// Lower an increment or decrement.
+    fn inc_dec(
+        &mut self,
+        expr: &Spanned<Expr>,
+        op: hir::BinOp,
+        prefix: bool,
+    ) -> Value {
+        let (addr, ty) = self.place(expr);
+        let old = self.load(addr.clone(), &ty);
+        let one = (Operand::Const(Const::Int(1, Ty::I32)), CType::INT);
+        let (new, from) = self.bin(op, (old.clone(), ty.clone()), one);
+        let new = self.convert(new, &from, &ty);
+        self.store(addr, new.clone());
+        Value::Scalar(if prefix { new } else { old }, ty)
+    }
+
+    /// Lower a binary operation on rvalues.
+    fn bin(
+        &mut self,
+        op: hir::BinOp,
+        (lhs, lhs_ty): (Operand, CType),
+        (rhs, rhs_ty): (Operand, CType),
+    ) -> (Operand, CType) {
+        let ty = checked(self.checker.bin(op, lhs_ty.clone(), rhs_ty.clone()));
+        match (op, &lhs_ty, &rhs_ty) {
+            (hir::BinOp::Add | hir::BinOp::Sub, CType::Pointer(elem), int)
+                if int.is_integer() =>
+            {
+                let mut index = self.convert(rhs, &rhs_ty, &CType::LONG);
+                if op == hir::BinOp::Sub {
+                    index = self
+                        .assign(CType::LONG, Rvalue::Una(UnaOp::Neg, index));
+                }
+                let rvalue = Rvalue::ElemAddr(lhs, index, (**elem).clone());
+                (self.assign(ty.clone(), rvalue), ty)
+            }
+            (hir::BinOp::Add, int, CType::Pointer(elem))
+                if int.is_integer() =>
+            {
+                let index = self.convert(lhs, &lhs_ty, &CType::LONG);
+                let rvalue = Rvalue::ElemAddr(rhs, index, (**elem).clone());
+                (self.assign(ty.clone(), rvalue), ty)
+            }
+            (hir::BinOp::Sub, CType::Pointer(elem), CType::Pointer(_)) => {
+                let lhs = self.convert(lhs, &lhs_ty, &CType::LONG);
+                let rhs = self.convert(rhs, &rhs_ty, &CType::LONG);
+                let diff =
+                    self.assign(ty.clone(), Rvalue::Bin(BinOp::Sub, lhs, rhs));
+                let size = self.checker.types.size_of(elem) as i64;
+                if size == 1 {
+                    return (diff, ty);
+                }
+                let mty = Ty::of(&ty, self.model()).unwrap();
+                let size = Operand::Const(Const::Int(size, mty));
+                let rvalue = Rvalue::Bin(BinOp::SDiv, diff, size);
+                (self.assign(ty.clone(), rvalue), ty)
+            }
+            _ if op.is_comparison() => {
+                let common = if lhs_ty.is_arithmetic() && rhs_ty.is_arithmetic()
+                {
+                    self.checker.types.common(&lhs_ty, &rhs_ty)
+                } else if lhs_ty.is_pointer() {
+                    lhs_ty.clone()
+                } else {
+                    rhs_ty.clone()
+                };
+                let lhs = self.convert(lhs, &lhs_ty, &common);
+                let rhs = self.convert(rhs, &rhs_ty, &common);
+                let rvalue = Rvalue::Bin(Self::bin_op(op, &common), lhs, rhs);
+                (self.assign(ty.clone(), rvalue), ty)
+            }
+            _ => {
+                let lhs = self.convert(lhs, &lhs_ty, &ty);
+                let rhs = self.convert(rhs, &rhs_ty, &ty);
+                let rvalue = Rvalue::Bin(Self::bin_op(op, &ty), lhs, rhs);
+                (self.assign(ty.clone(), rvalue), ty)
+            }
+        }
+    }
+
+    /// Select the MIR operation for an arithmetic or comparison operator on
+    /// operands of the given type.
+    fn bin_op(op: hir::BinOp, ty: &CType) -> BinOp {
+        let float = matches!(ty, CType::Float(_));
+        let signed = signed(ty);
+        let pick = |f, s, u| {
+            if float {
+                f
+            } else if signed {
+                s
+            } else {
+                u
+            }
+        };
+        match op {
+            hir::BinOp::Add => pick(BinOp::FAdd, BinOp::Add, BinOp::Add),
+            hir::BinOp::Sub => pick(BinOp::FSub, BinOp::Sub, BinOp::Sub),
+            hir::BinOp::Mul => pick(BinOp::FMul, BinOp::Mul, BinOp::Mul),
+            hir::BinOp::Div => pick(BinOp::FDiv, BinOp::SDiv, BinOp::UDiv),
+            hir::BinOp::Rem => pick(BinOp::SRem, BinOp::SRem, BinOp::URem),
+            hir::BinOp::Shl => BinOp::Shl,
+            hir::BinOp::Shr => pick(BinOp::AShr, BinOp::AShr, BinOp::LShr),
+            hir::BinOp::BitAnd => BinOp::And,
+            hir::BinOp::BitOr => BinOp::Or,
+            hir::BinOp::BitXor => BinOp::Xor,
+            hir::BinOp::Eq => pick(BinOp::FEq, BinOp::Eq, BinOp::Eq),
+            hir::BinOp::Ne => pick(BinOp::FNe, BinOp::Ne, BinOp::Ne),
+            hir::BinOp::Lt => pick(BinOp::FLt, BinOp::SLt, BinOp::ULt),
+            hir::BinOp::Gt => pick(BinOp::FGt, BinOp::SGt, BinOp::UGt),
+            hir::BinOp::Le => pick(BinOp::FLe, BinOp::SLe, BinOp::ULe),
+            hir::BinOp::Ge => pick(BinOp::FGe, BinOp::SGe, BinOp::UGe),
+            _ => unreachable!("{op:?} is not an arithmetic operator"),
+        }
+    }
+
+    /// Convert a scalar between types.
+    fn convert(&mut self, value: Operand, from: &CType, to: &CType) -> Operand {
+        let (Some(src), Some(dst)) =
+            (Ty::of(from, self.model()), Ty::of(to, self.model()))
+        else {
+            // Aggregates are passed around by address.
+            return value;
+        };
+
+        if is_bool(to) && !is_bool(from) {
+            let op = if src.is_float() {
+                BinOp::FNe
+            } else {
+                BinOp::Ne
+            };
+            let rvalue = Rvalue::Bin(op, value, Self::zero(src));
+            return self.assign(to.clone(), rvalue);
+        }
+
+        // Fold conversions of constants.
+        match value {
+            Operand::Const(Const::Int(value, _)) if !dst.is_float() => {
+                let value = if signed(from) {
+                    value
+                } else {
+                    src.unsigned(value) as i64
+                };
+                return Operand::Const(Const::Int(dst.wrap(value), dst));
+            }
+            Operand::Const(Const::Int(value, _)) => {
+                let value = if signed(from) {
+                    value as f64
+                } else {
+                    src.unsigned(value) as f64
+                };
+                let value = if dst == Ty::F32 {
+                    value as f32 as f64
+                } else {
+                    value
+                };
+                return Operand::Const(Const::Float(value, dst));
+            }
+            Operand::Const(Const::Float(value, _)) if dst.is_float() => {
+                let value = if dst == Ty::F32 {
+                    value as f32 as f64
+                } else {
+                    value
+                };
+                return Operand::Const(Const::Float(value, dst));
+            }
+            _ => {}
+        }
+
+        if src == dst {
+            return value;
+        }
+        let op = match (src, dst) {
+            (Ty::Ptr, _) => {
+                let int = self
+                    .assign(CType::LONG, Rvalue::Cast(CastOp::PtrToInt, value));
+                return self.convert(int, &CType::LONG, to);
+            }
+            (_, Ty::Ptr) => {
+                let int = self.convert(value, from, &CType::LONG);
+                return self
+                    .assign(to.clone(), Rvalue::Cast(CastOp::IntToPtr, int));
+            }
+            (src, dst) if src.is_float() && dst.is_float() => CastOp::FConv,
+            (src, _) if src.is_float() => {
+                if signed(to) {
+                    CastOp::FToS
+                } else {
+                    CastOp::FToU
+                }
+            }
+            (_, dst) if dst.is_float() => {
+                if signed(from) {
+                    CastOp::SToF
+                } else {
+                    CastOp::UToF
+                }
+            }
+            (src, dst) if src.size(self.model()) > dst.size(self.model()) => {
+                CastOp::Trunc
+            }
+            _ if signed(from) => CastOp::SExt,
+            _ => CastOp::ZExt,
+        };
+        self.assign(to.clone(), Rvalue::Cast(op, value))
+    }
+
+    /// Lower a call.
+    fn call(
+        &mut self,
+        callee: &Spanned<Expr>,
+        args: &[Spanned<Expr>],
+    ) -> Value {
+        let (callee, ty) = self.rvalue(callee);
+        let fn_type = ty.fn_type().unwrap().clone();
+        let mut operands = Vec::new();
+        let mut params = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            let (value, from) = self.rvalue(arg);
+            let to = match fn_type.params.get(i) {
+                Some(param) => param.clone(),
+                // The default argument promotions.
+                None => match from {
+                    CType::Float(FloatType::Float) => CType::DOUBLE,
+                    ref from => from.clone().promote(),
+                },
+            };
+            operands.push(self.convert(value, &from, &to));
+            params.push(to);
+        }
+
+        let ret = fn_type.ret.clone();
+        let dest = match Ty::of(&ret, self.model()) {
+            Some(_) => Some(self.func.add_temp(ret.clone())),
+            None if ret == CType::Void => None,
+            None => Some(self.memory_temp(ret.clone())),
+        };
+        self.emit(Inst::Call {
+            dest,
+            callee,
+            args: operands,
+            sig: Box::new(FnType {
+                ret: ret.clone(),
+                params,
+                variadic: fn_type.variadic,
+                prototype: fn_type.prototype,
+            }),
+            // Without a prototype, every argument is taken to be named.
+            fixed: if fn_type.prototype {
+                fn_type.params.len()
+            } else {
+                args.len()
+            },
+        });
+        match dest {
+            None => Value::Void,
+            Some(dest) if Ty::of(&ret, self.model()).is_some() => {
+                Value::Scalar(Operand::Local(dest), ret)
+            }
+            Some(dest) => Value::Place(Operand::Addr(dest), ret),
+        }
+    }
+
+    /// The type of the state a `va_list` walks the arguments with.
+    ///
+    /// This is the element type of `va_list` if it is an array, and
+    /// `va_list` itself otherwise.
+    fn va_state(&mut self) -> CType {
+        match self.checker.va_list() {
+            CType::Array { elem, .. } => *elem,
+            ty => ty,
+        }
+    }
+
+    /// Lower an operand of a `va_*` builtin, returning the address of its
+    /// state.
+    fn va_list(&mut self, ap: &Spanned<Expr>) -> Operand {
+        match self.checker.va_list() {
+            CType::Array { .. } => self.rvalue(ap).0,
+            _ => self.place(ap).0,
+        }
+    }
+
+    /// Lower `va_arg`, stepping the state at an address past the next
+    /// argument and returning the address of the argument.
+    fn va_arg(&mut self, state: Operand, ty: &CType) -> Operand {
+        let ptr = CType::Void.pointer_to();
+        match self.checker.target {
+            Target::X86_64 => self.va_arg_x86_64(state, ty),
+            // Every argument takes up 8 bytes, whether it is saved from a
+            // register or was passed on the stack.
+            Target::Riscv64 => {
+                let addr = self.load(state.clone(), &ptr);
+                let next = self.elem_addr(addr.clone(), 8, &CType::CHAR);
+                self.store(state, next);
+                addr
+            }
+            // Every argument is stored at its alignment.  They are promoted
+            // to at least `int`, so only wider ones need to be aligned.
+            Target::Wasm32 => {
+                let mut addr = self.load(state.clone(), &ptr);
+                let align = self.checker.types.align_of(ty) as i64;
+                if align > 4 {
+                    let int = CType::LONG;
+                    let mty = Ty::of(&int, self.model()).unwrap();
+                    let value = self.convert(addr, &ptr, &int);
+                    let add = Operand::Const(Const::Int(align - 1, mty));
+                    let value = self.assign(
+                        int.clone(),
+                        Rvalue::Bin(BinOp::Add, value, add),
+                    );
+                    let mask = Operand::Const(Const::Int(-align, mty));
+                    let value = self.assign(
+                        int.clone(),
+                        Rvalue::Bin(BinOp::And, value, mask),
+                    );
+                    addr = self.convert(value, &int, &ptr);
+                }
+                let size = self.checker.types.size_of(ty);
+                let next = self.elem_addr(addr.clone(), size, &CType::CHAR);
+                self.store(state, next);
+                addr
+            }
+        }
+    }
+
+    /// Lower `va_arg` for x86-64, returning the address of the argument.
+    ///
+    /// Arguments are taken from the register save area until the registers
+    /// of their class run out, and from the stack after that.
+    fn va_arg_x86_64(&mut self, state: Operand, ty: &CType) -> Operand {
+        let CType::Struct(id) = self.va_state() else {
+            unreachable!("`va_list` is an array of a struct on x86-64");
+        };
+        // The offset into the save area for the next argument, which is past
+        // the 6 general-purpose registers for the 8 vector registers.
+        let (field, limit, step) = match ty {
+            CType::Float(_) => (1, 6 * 8 + 8 * 16, 16),
+            _ => (0, 6 * 8, 8),
+        };
+        let ptr = CType::Void.pointer_to();
+        let uint = self.field_ty(id, field);
+        let mty = Ty::of(&uint, self.model()).unwrap();
+        let offset_addr = self.field_addr(state.clone(), id, field);
+        let offset = self.load(offset_addr.clone(), &uint);
+        let limit = Operand::Const(Const::Int(limit, mty));
+        let rvalue = Rvalue::Bin(BinOp::ULt, offset.clone(), limit);
+        let in_regs = self.assign(CType::INT, rvalue);
+        let regs = self.new_block();
+        let stack = self.new_block();
+        let join = self.new_block();
+        self.set_term(Terminator::Branch(in_regs, regs, stack));
+        let addr = self.func.add_temp(ptr.clone());
+
+        self.current = regs;
+        let save_addr = self.field_addr(state.clone(), id, 3);
+        let save = self.load(save_addr, &ptr);
+        let index = self.convert(offset.clone(), &uint, &CType::LONG);
+        let rvalue = Rvalue::ElemAddr(save, index, CType::CHAR);
+        self.emit(Inst::Assign(addr, rvalue));
+        let step = Operand::Const(Const::Int(step, mty));
+        let next = self.assign(uint, Rvalue::Bin(BinOp::Add, offset, step));
+        self.store(offset_addr, next);
+        self.set_term(Terminator::Jump(join));
+
+        self.current = stack;
+        let area_addr = self.field_addr(state, id, 2);
+        let area = self.load(area_addr.clone(), &ptr);
+        self.emit(Inst::Assign(addr, Rvalue::Use(area.clone())));
+        let next = self.elem_addr(area, 8, &CType::CHAR);
+        self.store(area_addr, next);
+        self.set_term(Terminator::Jump(join));
+
+        self.current = join;
+        Operand::Local(addr)
+    }
+
+    /// Lower `&&` or `||` to a value.
+    fn logical(&mut self, expr: &Spanned<Expr>) -> Value {
+        let result = self.func.add_temp(CType::INT);
+        let then = self.new_block();
+        let r#else = self.new_block();
+        let join = self.new_block();
+        self.branch(expr, then, r#else);
+        for (block, value) in [(then, 1), (r#else, 0)] {
+            self.current = block;
+            let value = Operand::Const(Const::Int(value, Ty::I32));
+            self.emit(Inst::Assign(result, Rvalue::Use(value)));
+            self.set_term(Terminator::Jump(join));
+        }
+        self.current = join;
+        Value::Scalar(Operand::Local(result), CType::INT)
+    }
+
+    /// Lower a conditional expression.
+    fn conditional(
+        &mut self,
+        expr: &Spanned<Expr>,
+        cond: &Spanned<Expr>,
+        then: &Spanned<Expr>,
+        r#else: &Spanned<Expr>,
+    ) -> Value {
+        let ty = checked(self.checker.rvalue(expr));
+        let then_block = self.new_block();
+        let else_block = self.new_block();
+        let join = self.new_block();
+        self.branch(cond, then_block, else_block);
+
+        let dest = match Ty::of(&ty, self.model()) {
+            Some(_) => Some(self.func.add_temp(ty.clone())),
+            None if ty == CType::Void => None,
+            None => Some(self.memory_temp(ty.clone())),
+        };
+        for (block, e) in [(then_block, then), (else_block, r#else)] {
+            self.current = block;
+            let (value, from) = self.rvalue(e);
+            match dest {
+                Some(dest) if Ty::of(&ty, self.model()).is_some() => {
+                    let value = self.convert(value, &from, &ty);
+                    self.emit(Inst::Assign(dest, Rvalue::Use(value)));
+                }
+                Some(dest) => self.copy(Operand::Addr(dest), value, &ty),
+                None => {}
+            }
+            self.set_term(Terminator::Jump(join));
+        }
+        self.current = join;
+
+        match dest {
+            None => Value::Void,
+            Some(dest) if Ty::of(&ty, self.model()).is_some() => {
+                Value::Scalar(Operand::Local(dest), ty)
+            }
+            Some(dest) => Value::Place(Operand::Addr(dest), ty),
+        }
+    }
+
+    /// Lower a controlling expression, jumping to `then` if it is non-zero
+    /// and to `else` otherwise.
+    ///
+    /// `&&`, `||` and `!` are lowered to short-circuiting control flow.
+    fn branch(&mut self, cond: &Spanned<Expr>, then: BlockId, r#else: BlockId) {
+        match &cond.node {
+            Expr::Bin(hir::BinOp::And, lhs, rhs) => {
+                let rhs_block = self.new_block();
+                self.branch(lhs, rhs_block, r#else);
+                self.current = rhs_block;
+                self.branch(rhs, then, r#else);
+            }
+            Expr::Bin(hir::BinOp::Or, lhs, rhs) => {
+                let rhs_block = self.new_block();
+                self.branch(lhs, then, rhs_block);
+                self.current = rhs_block;
+                self.branch(rhs, then, r#else);
+            }
+            Expr::Una(HUnaOp::Not, e) => self.branch(e, r#else, then),
+            _ => {
+                let (value, ty) = self.rvalue(cond);
+                let term = match value {
+                    Operand::Const(Const::Int(value, _)) => {
+                        Terminator::Jump(if value != 0 { then } else { r#else })
+                    }
+                    value => {
+                        let mty = Ty::of(&ty, self.model()).unwrap();
+                        let value = if mty.is_float() {
+                            let rvalue =
+                                Rvalue::Bin(BinOp::FNe, value, Self::zero(mty));
+                            self.assign(CType::INT, rvalue)
+                        } else {
+                            value
+                        };
+                        Terminator::Branch(value, then, r#else)
+                    }
+                };
+                self.set_term(term);
+            }
+        }
+    }
+}
+
+//----------- Helpers ----------------------------------------------------------
+
+/// Whether a type is signed, for the purpose of selecting operations.
+///
+/// Pointers are treated as unsigned.
+fn signed(ty: &CType) -> bool {
+    match ty {
+        CType::Int(int) => int.signed,
+        CType::Pointer(_) => false,
+        _ => true,
+    }
+}
+
+/// Whether a type is `_Bool`.
+fn is_bool(ty: &CType) -> bool {
+    matches!(
+        ty,
+        CType::Int(IntType {
+            rank: IntRank::Bool,
+            ..
+        })
+    )
+}
+
+/// Whether a type is a character type.
+fn is_char(ty: &CType) -> bool {
+    matches!(
+        ty,
+        CType::Int(IntType {
+            rank: IntRank::Char,
+            ..
+        })
+    )
+}
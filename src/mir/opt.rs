@@ -0,0 +1,185 @@
+//! The optimization pipeline.
+//!
+//! Optimizations are [`Pass`]es over a whole [`Module`], run in order by a
+//! [`PassManager`].  Most passes transform one function at a time and are
+//! wrapped in a [`FunctionPass`].  Every pipeline starts by converting the
+//! functions to SSA form, which the other passes rely on; in debug builds,
+//! the SSA form is verified after every pass.
+
+use crate::mir::{Function, Module, ssa};
+
+pub mod dce;
+pub mod fold;
+pub mod gvn;
+pub mod inline;
+pub mod licm;
+pub mod rotate;
+pub mod sccp;
+pub mod simplify;
+pub mod strength;
+
+/// An optimization level.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    /// Only convert to SSA form.
+    O0,
+
+    /// Run the scalar optimizations.
+    O1,
+}
+
+/// A transformation of a module.
+pub trait Pass {
+    /// The name of the pass, as used by `--print-after`.
+    fn name(&self) -> &'static str;
+
+    /// Transform the module.
+    fn run(&mut self, module: &mut Module);
+}
+
+/// A pass applied to each function independently.
+pub struct FunctionPass {
+    /// The name of the pass.
+    name: &'static str,
+
+    /// The transformation of a single function.
+    run: fn(&mut Function),
+}
+
+impl Pass for FunctionPass {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn run(&mut self, module: &mut Module) {
+        for func in &mut module.functions {
+            (self.run)(func);
+        }
+    }
+}
+
+/// The names of all passes, as accepted by `--print-after`.
+pub const PASSES: &[&str] = &[
+    "mem2reg",
+    "sccp",
+    "simplify-cfg",
+    "gvn",
+    "dce",
+    "inline",
+    "loop-rotate",
+    "licm",
+    "strength-reduce",
+];
+
+/// Options controlling the optimization pipeline.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The optimization level.
+    pub level: OptLevel,
+
+    /// The largest cost of a function to inline (see [`inline`]).
+    pub inline_limit: usize,
+
+    /// The pass after which to print the module, or `all`.
+    pub print_after: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            level: OptLevel::O0,
+            inline_limit: inline::DEFAULT_LIMIT,
+            print_after: None,
+        }
+    }
+}
+
+/// Construct a pass by name.
+fn pass(name: &'static str, options: &Options) -> Box<dyn Pass> {
+    let run = match name {
+        "mem2reg" => ssa::mem2reg,
+        "sccp" => sccp::run,
+        "simplify-cfg" => simplify::run,
+        "gvn" => gvn::run,
+        "dce" => dce::run,
+        "loop-rotate" => rotate::run,
+        "licm" => licm::run,
+        "strength-reduce" => strength::run,
+        "inline" => {
+            let limit = options.inline_limit;
+            return Box::new(inline::Inline { limit });
+        }
+        _ => unreachable!("unknown pass `{name}`"),
+    };
+    Box::new(FunctionPass { name, run })
+}
+
+/// Runs a sequence of passes over a module.
+pub struct PassManager {
+    /// The passes, in order.
+    passes: Vec<Box<dyn Pass>>,
+
+    /// The pass after which to print the module, or `all`.
+    print_after: Option<String>,
+
+    /// Whether to verify SSA form after every pass.
+    verify: bool,
+}
+
+impl PassManager {
+    /// Build the pipeline for a set of options.
+    pub fn new(options: &Options) -> Self {
+        let names: &[&'static str] = match options.level {
+            OptLevel::O0 => &["mem2reg"],
+            // Callees are cleaned up before their cost is estimated, and
+            // callers once more after inlining.  Loops are rotated before
+            // hoisting code out of them, so that it only runs if they do,
+            // and the copies left by rotation are removed before induction
+            // variables are looked for.
+            OptLevel::O1 => &[
+                "mem2reg",
+                "sccp",
+                "simplify-cfg",
+                "dce",
+                "inline",
+                "sccp",
+                "simplify-cfg",
+                "gvn",
+                "dce",
+                "loop-rotate",
+                "gvn",
+                "licm",
+                "strength-reduce",
+                "sccp",
+                "gvn",
+                "dce",
+                "simplify-cfg",
+            ],
+        };
+        Self {
+            passes: names.iter().map(|&name| pass(name, options)).collect(),
+            print_after: options.print_after.clone(),
+            verify: cfg!(debug_assertions),
+        }
+    }
+
+    /// Run the passes over a module.
+    pub fn run(&mut self, module: &mut Module) {
+        for pass in &mut self.passes {
+            pass.run(module);
+            if self.verify {
+                for func in &module.functions {
+                    if let Err(err) = ssa::verify(func) {
+                        panic!("invalid SSA form after {}: {err}", pass.name());
+                    }
+                }
+            }
+            if let Some(name) = &self.print_after
+                && (name == "all" || name == pass.name())
+            {
+                eprintln!("; *** MIR after {} ***", pass.name());
+                eprint!("{module}");
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! Dead code elimination.
+//!
+//! Stores, calls and terminators are assumed to be needed; every register
+//! they (transitively) use is live, and the remaining assignments are
+//! removed.  Marking liveness from these roots, rather than deleting unused
+//! registers one at a time, also removes cycles of dead phis.
+
+use crate::mir::*;
+
+/// Remove the assignments whose values are never needed.
+pub fn run(func: &mut Function) {
+    // The operands of the instruction defining each register.
+    let mut defs: Vec<Vec<Local>> = vec![Vec::new(); func.locals.len()];
+    let mut live = vec![false; func.locals.len()];
+    let mut worklist = Vec::new();
+    let locals = |operands: Vec<&Operand>| {
+        operands
+            .into_iter()
+            .filter_map(|operand| match operand {
+                Operand::Local(local) => Some(*local),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for block in &func.blocks {
+        for inst in &block.insts {
+            match inst {
+                Inst::Assign(dest, rvalue) => {
+                    defs[dest.0 as usize] = locals(rvalue.operands());
+                }
+                inst => worklist.extend(locals(inst.operands())),
+            }
+        }
+        worklist.extend(locals(block.term.operands()));
+    }
+
+    while let Some(local) = worklist.pop() {
+        if std::mem::replace(&mut live[local.0 as usize], true) {
+            continue;
+        }
+        worklist.extend(&defs[local.0 as usize]);
+    }
+
+    for block in &mut func.blocks {
+        block.insts.retain(|inst| match inst {
+            Inst::Assign(dest, _) => live[dest.0 as usize],
+            _ => true,
+        });
+    }
+}
@@ -0,0 +1,130 @@
+//! Constant folding.
+//!
+//! Each function evaluates an operation on constant operands, producing a
+//! constant of the destination type.  Operations whose result is undefined
+//! (division by zero, oversized shifts, conversions of floating-point values
+//! out of range) or that involve symbol addresses are not folded.
+
+use crate::ctype::DataModel;
+use crate::mir::{BinOp, CastOp, Const, Ty, UnaOp};
+
+/// Round a floating-point result to the precision of a type.
+fn round(ty: Ty, value: f64) -> f64 {
+    match ty {
+        Ty::F32 => value as f32 as f64,
+        _ => value,
+    }
+}
+
+/// Fold a binary operation with a result of type `ty`.
+pub fn bin(op: BinOp, ty: Ty, a: &Const, b: &Const) -> Option<Const> {
+    use BinOp::*;
+    match (a, b) {
+        (&Const::Int(x, t), &Const::Int(y, _)) => {
+            let (x, y) = (t.wrap(x), t.wrap(y));
+            let (ux, uy) = (t.unsigned(x), t.unsigned(y));
+            // Pointers are never shifted, so their width does not matter.
+            let bits = t.size(DataModel::Lp64) * 8;
+            let value = match op {
+                Add => x.wrapping_add(y),
+                Sub => x.wrapping_sub(y),
+                Mul => x.wrapping_mul(y),
+                SDiv | SRem if y == 0 => return None,
+                UDiv | URem if uy == 0 => return None,
+                SDiv => x.wrapping_div(y),
+                SRem => x.wrapping_rem(y),
+                UDiv => (ux / uy) as i64,
+                URem => (ux % uy) as i64,
+                Shl | LShr | AShr if uy >= bits => return None,
+                Shl => x << uy,
+                LShr => (ux >> uy) as i64,
+                AShr => x >> uy,
+                And => x & y,
+                Or => x | y,
+                Xor => x ^ y,
+                Eq => (x == y) as i64,
+                Ne => (x != y) as i64,
+                SLt => (x < y) as i64,
+                SLe => (x <= y) as i64,
+                SGt => (x > y) as i64,
+                SGe => (x >= y) as i64,
+                ULt => (ux < uy) as i64,
+                ULe => (ux <= uy) as i64,
+                UGt => (ux > uy) as i64,
+                UGe => (ux >= uy) as i64,
+                _ => return None,
+            };
+            Some(Const::Int(ty.wrap(value), ty))
+        }
+        (&Const::Float(x, _), &Const::Float(y, _)) => {
+            let value = match op {
+                FAdd => x + y,
+                FSub => x - y,
+                FMul => x * y,
+                FDiv => x / y,
+                FEq => return Some(Const::Int((x == y) as i64, ty)),
+                FNe => return Some(Const::Int((x != y) as i64, ty)),
+                FLt => return Some(Const::Int((x < y) as i64, ty)),
+                FLe => return Some(Const::Int((x <= y) as i64, ty)),
+                FGt => return Some(Const::Int((x > y) as i64, ty)),
+                FGe => return Some(Const::Int((x >= y) as i64, ty)),
+                _ => return None,
+            };
+            Some(Const::Float(round(ty, value), ty))
+        }
+        _ => None,
+    }
+}
+
+/// Fold a unary operation with a result of type `ty`.
+pub fn una(op: UnaOp, ty: Ty, a: &Const) -> Option<Const> {
+    match (op, a) {
+        (UnaOp::Neg, &Const::Int(x, _)) => {
+            Some(Const::Int(ty.wrap(x.wrapping_neg()), ty))
+        }
+        (UnaOp::Not, &Const::Int(x, _)) => Some(Const::Int(ty.wrap(!x), ty)),
+        (UnaOp::FNeg, &Const::Float(x, _)) => Some(Const::Float(-x, ty)),
+        _ => None,
+    }
+}
+
+/// Fold a conversion to type `ty`.
+pub fn cast(op: CastOp, ty: Ty, a: &Const) -> Option<Const> {
+    use CastOp::*;
+    match (op, a) {
+        (SExt | Trunc | PtrToInt | IntToPtr, &Const::Int(x, t)) => {
+            Some(Const::Int(ty.wrap(t.wrap(x)), ty))
+        }
+        (ZExt, &Const::Int(x, t)) => {
+            Some(Const::Int(ty.wrap(t.unsigned(x) as i64), ty))
+        }
+        (FToS, &Const::Float(x, _))
+            if ty.wrap(x as i64) as f64 == x.trunc() =>
+        {
+            Some(Const::Int(ty.wrap(x as i64), ty))
+        }
+        (FToU, &Const::Float(x, _))
+            if ty.unsigned(x as u64 as i64) as f64 == x.trunc() =>
+        {
+            Some(Const::Int(ty.wrap(x as u64 as i64), ty))
+        }
+        (SToF, &Const::Int(x, t)) => {
+            Some(Const::Float(round(ty, t.wrap(x) as f64), ty))
+        }
+        (UToF, &Const::Int(x, t)) => {
+            Some(Const::Float(round(ty, t.unsigned(x) as f64), ty))
+        }
+        (FConv, &Const::Float(x, _)) => Some(Const::Float(round(ty, x), ty)),
+        _ => None,
+    }
+}
+
+/// Whether a constant is non-zero.
+pub fn truth(c: &Const) -> bool {
+    match *c {
+        Const::Int(x, _) => x != 0,
+        Const::Float(x, _) => x != 0.0,
+        // Symbols never have a null address.
+        Const::Symbol(_) => true,
+    }
+}
@@ -0,0 +1,200 @@
+//! Global value numbering.
+//!
+//! The dominator tree is walked with a scoped table of the pure values
+//! computed so far.  An instruction recomputing a value already available in
+//! a dominating block is removed, and its register replaced by the earlier
+//! one.  Copies and phis whose arguments are all the same value are removed
+//! in the same way.  Loads are never merged, since memory may change between
+//! them.
+
+use std::collections::HashMap;
+
+use crate::mir::dom::Dominators;
+use crate::mir::*;
+
+/// A hashable description of an operand.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Local(Local),
+    Int(i64, Ty),
+    Float(u64, Ty),
+    Symbol(String),
+    Addr(Local),
+}
+
+impl From<&Operand> for Key {
+    fn from(operand: &Operand) -> Self {
+        match operand {
+            Operand::Local(local) => Self::Local(*local),
+            Operand::Const(Const::Int(value, ty)) => Self::Int(*value, *ty),
+            Operand::Const(Const::Float(value, ty)) => {
+                Self::Float(value.to_bits(), *ty)
+            }
+            Operand::Const(Const::Symbol(name)) => Self::Symbol(name.clone()),
+            Operand::Addr(local) => Self::Addr(*local),
+        }
+    }
+}
+
+/// A hashable description of a pure value.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Expr {
+    Bin(BinOp, Key, Key, Ty),
+    Una(UnaOp, Key, Ty),
+    Cast(CastOp, Key, Ty),
+    FieldAddr(Key, StructId, usize),
+    ElemAddr(Key, Key, String),
+}
+
+impl Expr {
+    /// Describe a value computed into a register of type `ty`.
+    fn new(rvalue: &Rvalue, ty: Ty) -> Option<Self> {
+        Some(match rvalue {
+            Rvalue::Bin(op, a, b) => {
+                let (mut a, mut b) = (Key::from(a), Key::from(b));
+                // Put commutative operands in a canonical order.
+                if op.is_commutative() && key_order(&b) < key_order(&a) {
+                    std::mem::swap(&mut a, &mut b);
+                }
+                Self::Bin(*op, a, b, ty)
+            }
+            Rvalue::Una(op, a) => Self::Una(*op, a.into(), ty),
+            Rvalue::Cast(op, a) => Self::Cast(*op, a.into(), ty),
+            Rvalue::FieldAddr(a, id, field) => {
+                Self::FieldAddr(a.into(), *id, *field)
+            }
+            Rvalue::ElemAddr(a, b, elem) => {
+                Self::ElemAddr(a.into(), b.into(), format!("{elem:?}"))
+            }
+            Rvalue::Use(_) | Rvalue::Load(_) | Rvalue::Phi(_) => return None,
+        })
+    }
+}
+
+/// A total order on keys, used to canonicalize commutative operations.
+fn key_order(key: &Key) -> (u8, i64) {
+    match key {
+        Key::Local(local) => (0, local.0 as i64),
+        Key::Addr(local) => (1, local.0 as i64),
+        Key::Symbol(_) => (2, 0),
+        Key::Int(value, _) => (3, *value),
+        Key::Float(bits, _) => (4, *bits as i64),
+    }
+}
+
+/// The state of value numbering.
+struct Gvn<'a> {
+    /// The dominator tree of the function.
+    doms: &'a Dominators,
+
+    /// The values available in the current block, with the registers
+    /// holding them.
+    available: HashMap<Expr, Local>,
+
+    /// The registers replaced by an equivalent operand.
+    replace: HashMap<Local, Operand>,
+}
+
+/// Remove redundant computations from a function.
+pub fn run(func: &mut Function) {
+    let doms = Dominators::new(func);
+    let mut gvn = Gvn {
+        doms: &doms,
+        available: HashMap::new(),
+        replace: HashMap::new(),
+    };
+    gvn.visit(func, BlockId(0));
+
+    // Phi arguments flowing along back edges were visited before the
+    // registers they use were replaced.
+    let replace = gvn.replace;
+    for block in &mut func.blocks {
+        let operands =
+            block.insts.iter_mut().flat_map(|inst| inst.operands_mut());
+        for operand in operands.chain(block.term.operands_mut()) {
+            substitute(&replace, operand);
+        }
+    }
+}
+
+/// Rewrite an operand replaced by an equivalent one.
+fn substitute(replace: &HashMap<Local, Operand>, operand: &mut Operand) {
+    while let Operand::Local(local) = operand
+        && let Some(value) = replace.get(local)
+    {
+        *operand = value.clone();
+    }
+}
+
+impl Gvn<'_> {
+    /// Look up an available value, or make it available in `dest`.
+    fn number(
+        &mut self,
+        expr: Expr,
+        dest: Local,
+        added: &mut Vec<Expr>,
+    ) -> Option<Operand> {
+        if let Some(&local) = self.available.get(&expr) {
+            return Some(Operand::Local(local));
+        }
+        self.available.insert(expr.clone(), dest);
+        added.push(expr);
+        None
+    }
+
+    /// Number the values in a block and the blocks it dominates.
+    fn visit(&mut self, func: &mut Function, block: BlockId) {
+        // The values made available by this block, to be removed at the end.
+        let mut added = Vec::new();
+
+        let old = std::mem::take(&mut func.block_mut(block).insts);
+        let mut insts = Vec::with_capacity(old.len());
+        for mut inst in old {
+            for operand in inst.operands_mut() {
+                substitute(&self.replace, operand);
+            }
+            let Inst::Assign(dest, rvalue) = &inst else {
+                insts.push(inst);
+                continue;
+            };
+            let dest = *dest;
+            let same = match rvalue {
+                Rvalue::Use(value) => Some(value.clone()),
+                Rvalue::Phi(args) => trivial_phi(dest, args),
+                rvalue => Expr::new(rvalue, func.ty(dest))
+                    .and_then(|expr| self.number(expr, dest, &mut added)),
+            };
+            match same {
+                Some(value) => {
+                    self.replace.insert(dest, value);
+                }
+                None => insts.push(inst),
+            }
+        }
+        let block_ref = func.block_mut(block);
+        block_ref.insts = insts;
+        for operand in block_ref.term.operands_mut() {
+            substitute(&self.replace, operand);
+        }
+
+        let doms = self.doms;
+        for &child in doms.children(block) {
+            self.visit(func, child);
+        }
+
+        for expr in added {
+            self.available.remove(&expr);
+        }
+    }
+}
+
+/// The single value of a phi, if all of its arguments are the same (apart
+/// from the phi itself).
+fn trivial_phi(dest: Local, args: &[(BlockId, Operand)]) -> Option<Operand> {
+    let mut values = args
+        .iter()
+        .map(|(_, arg)| arg)
+        .filter(|arg| **arg != Operand::Local(dest));
+    let first = values.next()?;
+    values.all(|arg| arg == first).then(|| first.clone())
+}
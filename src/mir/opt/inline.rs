@@ -0,0 +1,348 @@
+//! Function inlining.
+//!
+//! Calls to small functions defined in the module are replaced by a copy of
+//! the callee's body.  Only `static` and `inline` functions are considered,
+//! since other functions must be kept for callers outside the translation
+//! unit anyway.  A callee is inlined if its cost (roughly its number of
+//! instructions) is at most the inline limit, or a few times that if it is
+//! a `static` function with a single caller, whose body then disappears.
+//!
+//! Functions are processed bottom-up in the call graph, so that callees have
+//! already been inlined into when they are considered.  Calls exposed by
+//! inlining are inlined in turn, for a bounded number of rounds, which also
+//! bounds the unrolling of recursive calls; a function is never inlined into
+//! itself.  Afterwards, `static` functions and variables that are no longer
+//! referenced are removed.
+
+use std::collections::{HashMap, HashSet};
+
+use super::Pass;
+use crate::ctype::CType;
+use crate::mir::*;
+
+/// The default inline limit.
+pub const DEFAULT_LIMIT: usize = 40;
+
+/// The number of times calls exposed by inlining are inlined in turn.
+const MAX_ROUNDS: usize = 3;
+
+/// How much larger than the limit a `static` function with a single caller
+/// may be.
+const ONCE_FACTOR: usize = 4;
+
+/// The inlining pass.
+pub struct Inline {
+    /// The largest cost of a function to inline; 0 disables inlining.
+    pub limit: usize,
+}
+
+impl Pass for Inline {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn run(&mut self, module: &mut Module) {
+        if self.limit == 0 {
+            return;
+        }
+        let index: HashMap<String, usize> = module
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(i, func)| (func.name.clone(), i))
+            .collect();
+        let refs = reference_counts(module);
+
+        for caller in bottom_up(module, &index) {
+            for _ in 0..MAX_ROUNDS {
+                let sites = self.call_sites(module, &index, &refs, caller);
+                if sites.is_empty() {
+                    break;
+                }
+                // Later call sites come first, so that splitting a block
+                // leaves the positions of earlier ones intact.
+                for (block, inst, callee) in sites.into_iter().rev() {
+                    let callee = module.functions[callee].clone();
+                    inline_call(
+                        &mut module.functions[caller],
+                        block,
+                        inst,
+                        &callee,
+                    );
+                }
+            }
+            module.functions[caller].remove_unreachable();
+        }
+        remove_unused(module);
+    }
+}
+
+impl Inline {
+    /// The calls in a function worth inlining, in order, as the block and
+    /// index of the call and the index of the callee.
+    fn call_sites(
+        &self,
+        module: &Module,
+        index: &HashMap<String, usize>,
+        refs: &HashMap<String, usize>,
+        caller: usize,
+    ) -> Vec<(BlockId, usize, usize)> {
+        let func = &module.functions[caller];
+        let mut sites = Vec::new();
+        for id in func.block_ids() {
+            for (i, inst) in func.block(id).insts.iter().enumerate() {
+                let Inst::Call {
+                    callee: Operand::Const(Const::Symbol(name)),
+                    args,
+                    ..
+                } = inst
+                else {
+                    continue;
+                };
+                let Some(&j) = index.get(name) else {
+                    continue;
+                };
+                let callee = &module.functions[j];
+                let limit = match refs.get(name) {
+                    Some(1) if callee.local => self.limit * ONCE_FACTOR,
+                    _ => self.limit,
+                };
+                if j != caller
+                    && (callee.local || callee.inline)
+                    && can_inline(func, args, callee)
+                    && cost(callee) <= limit
+                {
+                    sites.push((id, i, j));
+                }
+            }
+        }
+        sites
+    }
+}
+
+/// The cost of inlining a function: its number of instructions, counting
+/// terminators but not phis.
+fn cost(func: &Function) -> usize {
+    func.blocks
+        .iter()
+        .map(|block| {
+            block.insts.iter().filter(|inst| !inst.is_phi()).count() + 1
+        })
+        .sum()
+}
+
+/// Whether a call can be replaced by the body of the callee.
+///
+/// Aggregates are passed and returned through memory owned by the caller,
+/// so functions taking or returning them are not inlined; neither are
+/// variadic functions, or calls whose arguments do not match the callee's
+/// parameters (as allowed for functions declared without a prototype).
+fn can_inline(caller: &Function, args: &[Operand], callee: &Function) -> bool {
+    let ret = &callee.ty.ret;
+    !callee.ty.variadic
+        && (*ret == CType::Void || Ty::of(ret, callee.model).is_some())
+        && args.len() == callee.params.len()
+        && args.iter().zip(&callee.params).all(|(arg, &param)| {
+            !callee.local(param).memory
+                && Ty::of(&callee.local(param).ty, callee.model)
+                    == Some(caller.operand_ty(arg))
+        })
+        // Parameters are only defined on entry to the callee.
+        && !callee
+            .blocks
+            .iter()
+            .any(|block| block.term.successors().contains(&BlockId(0)))
+}
+
+/// Replace the call at a position in a function by the body of the callee.
+fn inline_call(
+    caller: &mut Function,
+    block: BlockId,
+    index: usize,
+    callee: &Function,
+) {
+    // Split the block after the call; the rest continues after the return.
+    let split = caller.block_mut(block);
+    let rest = split.insts.split_off(index + 1);
+    let Some(Inst::Call { dest, args, .. }) = split.insts.pop() else {
+        unreachable!("inlining a call site");
+    };
+    let term = std::mem::replace(&mut split.term, Terminator::Unreachable);
+    let cont = BlockId(caller.blocks.len() as u32);
+    for succ in term.successors() {
+        rename_pred(caller, succ, block, cont);
+    }
+    caller.blocks.push(Block { insts: rest, term });
+
+    // Copy the callee's locals and blocks, with parameters replaced by the
+    // arguments and returns jumping to the continuation.
+    let offset = caller.blocks.len() as u32;
+    let locals: Vec<Local> = callee
+        .locals
+        .iter()
+        .map(|decl| caller.add_local(decl.clone()))
+        .collect();
+    let local = |local: &Local| locals[local.0 as usize];
+    let target = |block: BlockId| BlockId(block.0 + offset);
+    let operand = |operand: &Operand| match operand {
+        Operand::Local(l) => match callee.params.iter().position(|p| p == l) {
+            Some(i) => args[i].clone(),
+            None => Operand::Local(local(l)),
+        },
+        Operand::Addr(l) => Operand::Addr(local(l)),
+        Operand::Const(_) => operand.clone(),
+    };
+
+    let mut returns = Vec::new();
+    for (id, block) in callee.block_ids().zip(&callee.blocks) {
+        let mut block = block.clone();
+        for inst in &mut block.insts {
+            for op in inst.operands_mut() {
+                *op = operand(op);
+            }
+            match inst {
+                Inst::Assign(dest, rvalue) => {
+                    *dest = local(dest);
+                    if let Rvalue::Phi(args) = rvalue {
+                        for (pred, _) in args {
+                            *pred = target(*pred);
+                        }
+                    }
+                }
+                Inst::Call {
+                    dest: Some(dest), ..
+                } => *dest = local(dest),
+                _ => {}
+            }
+        }
+        for op in block.term.operands_mut() {
+            *op = operand(op);
+        }
+        for succ in block.term.targets_mut() {
+            *succ = target(*succ);
+        }
+        if let Terminator::Return(value) = &block.term {
+            if let Some(value) = value {
+                returns.push((target(id), value.clone()));
+            }
+            block.term = Terminator::Jump(cont);
+        }
+        caller.blocks.push(block);
+    }
+
+    caller.block_mut(block).term = Terminator::Jump(target(BlockId(0)));
+    if let Some(dest) = dest {
+        let phi = Inst::Assign(dest, Rvalue::Phi(returns));
+        caller.block_mut(cont).insts.insert(0, phi);
+    }
+}
+
+/// Make the phis in a block refer to a new predecessor.
+fn rename_pred(
+    func: &mut Function,
+    block: BlockId,
+    old: BlockId,
+    new: BlockId,
+) {
+    for inst in &mut func.block_mut(block).insts {
+        if let Inst::Assign(_, Rvalue::Phi(args)) = inst {
+            for (pred, _) in args {
+                if *pred == old {
+                    *pred = new;
+                }
+            }
+        }
+    }
+}
+
+/// The number of references to every symbol in the module.
+fn reference_counts(module: &Module) -> HashMap<String, usize> {
+    let mut refs = HashMap::new();
+    let globals = module
+        .globals
+        .iter()
+        .filter_map(|global| global.init.as_ref());
+    let relocs = globals
+        .flat_map(|init| &init.relocs)
+        .map(|reloc| reloc.symbol.as_str());
+    for symbol in module
+        .functions
+        .iter()
+        .flat_map(Function::symbols)
+        .chain(relocs)
+    {
+        *refs.entry(symbol.to_string()).or_default() += 1;
+    }
+    refs
+}
+
+/// The functions of a module in bottom-up order: callees before callers,
+/// except around cycles of recursion.
+fn bottom_up(module: &Module, index: &HashMap<String, usize>) -> Vec<usize> {
+    fn visit(
+        module: &Module,
+        index: &HashMap<String, usize>,
+        func: usize,
+        visited: &mut [bool],
+        order: &mut Vec<usize>,
+    ) {
+        visited[func] = true;
+        for symbol in module.functions[func].symbols() {
+            if let Some(&callee) = index.get(symbol)
+                && !visited[callee]
+            {
+                visit(module, index, callee, visited, order);
+            }
+        }
+        order.push(func);
+    }
+
+    let mut visited = vec![false; module.functions.len()];
+    let mut order = Vec::new();
+    for func in 0..module.functions.len() {
+        if !visited[func] {
+            visit(module, index, func, &mut visited, &mut order);
+        }
+    }
+    order
+}
+
+/// Remove the `static` functions and variables that are no longer
+/// referenced.
+fn remove_unused(module: &mut Module) {
+    // Mark everything reachable from the symbols visible outside the module.
+    let mut live: HashSet<String> = HashSet::new();
+    let mut worklist: Vec<String> = module
+        .functions
+        .iter()
+        .filter(|func| !func.local)
+        .map(|func| func.name.clone())
+        .chain(
+            module
+                .globals
+                .iter()
+                .filter(|global| !global.local)
+                .map(|global| global.name.clone()),
+        )
+        .collect();
+    while let Some(name) = worklist.pop() {
+        if !live.insert(name.clone()) {
+            continue;
+        }
+        if let Some(func) = module.functions.iter().find(|f| f.name == name) {
+            worklist.extend(func.symbols().map(String::from));
+        }
+        if let Some(global) = module.globals.iter().find(|g| g.name == name)
+            && let Some(init) = &global.init
+        {
+            worklist.extend(init.relocs.iter().map(|r| r.symbol.clone()));
+        }
+    }
+
+    module
+        .functions
+        .retain(|func| !func.local || live.contains(&func.name));
+    module
+        .globals
+        .retain(|global| !global.local || live.contains(&global.name));
+}
@@ -0,0 +1,84 @@
+//! Loop-invariant code motion.
+//!
+//! A computation in a loop whose operands are all defined outside the loop,
+//! or are themselves invariant, gives the same value on every iteration and
+//! is moved to the loop's preheader.  Loops are processed innermost first,
+//! so that code hoisted out of an inner loop may be hoisted again out of the
+//! enclosing one.
+//!
+//! Only computations that cannot trap are hoisted, since the loop might not
+//! have reached them.  Loads are hoisted only from variables and globals,
+//! and only out of loops that neither store nor call.
+
+use std::collections::HashSet;
+
+use crate::mir::loops::{self, Loop};
+use crate::mir::*;
+
+/// Hoist the loop-invariant computations of a function.
+pub fn run(func: &mut Function) {
+    for (lp, preheader) in loops::with_preheaders(func) {
+        hoist(func, &lp, preheader);
+    }
+}
+
+/// Move the invariant computations of a loop to its preheader.
+fn hoist(func: &mut Function, lp: &Loop, preheader: BlockId) {
+    let insts = || lp.blocks.iter().flat_map(|&block| &func.block(block).insts);
+    let mut variant: HashSet<Local> = insts().filter_map(Inst::dest).collect();
+    let memory = insts().any(|inst| !matches!(inst, Inst::Assign(..)));
+
+    // Blocks are visited in reverse postorder, so the invariant operands of
+    // an instruction are hoisted before it.
+    let mut hoisted = Vec::new();
+    for &block in &lp.blocks {
+        let insts = std::mem::take(&mut func.block_mut(block).insts);
+        let mut kept = Vec::with_capacity(insts.len());
+        for inst in insts {
+            match &inst {
+                Inst::Assign(dest, rvalue)
+                    if can_hoist(rvalue, memory)
+                        && rvalue.operands().iter().all(|operand| {
+                            !matches!(operand, Operand::Local(local)
+                                if variant.contains(local))
+                        }) =>
+                {
+                    variant.remove(dest);
+                    hoisted.push(inst);
+                }
+                _ => kept.push(inst),
+            }
+        }
+        func.block_mut(block).insts = kept;
+    }
+    func.block_mut(preheader).insts.extend(hoisted);
+}
+
+/// Whether a computation may be moved out of a loop, given whether the loop
+/// writes to memory.
+fn can_hoist(rvalue: &Rvalue, memory: bool) -> bool {
+    match rvalue {
+        Rvalue::Bin(
+            BinOp::SDiv | BinOp::UDiv | BinOp::SRem | BinOp::URem,
+            _,
+            divisor,
+        ) => {
+            // Division traps on a zero divisor, and on overflow.
+            matches!(divisor, Operand::Const(Const::Int(c, _)) if *c != 0 && *c != -1)
+        }
+        Rvalue::Use(_)
+        | Rvalue::Bin(..)
+        | Rvalue::Una(..)
+        | Rvalue::Cast(..)
+        | Rvalue::FieldAddr(..)
+        | Rvalue::ElemAddr(..) => true,
+        Rvalue::Load(addr) => {
+            !memory
+                && matches!(
+                    addr,
+                    Operand::Addr(_) | Operand::Const(Const::Symbol(_))
+                )
+        }
+        Rvalue::Phi(_) => false,
+    }
+}
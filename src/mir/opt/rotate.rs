@@ -0,0 +1,123 @@
+//! Loop rotation.
+//!
+//! A loop whose header tests the exit condition, as `while` and `for` loops
+//! are lowered, is turned into a guarded do-while loop.  The header is copied
+//! into the preheader, where it guards the loop, and into the latch, which
+//! then branches straight back into the body.  Each iteration then takes a
+//! single branch, and code hoisted out of the loop only runs if the loop
+//! does.
+//!
+//! The copies define the same registers as the header did; SSA form is
+//! restored afterwards by [`mem2reg()`](ssa::mem2reg).
+
+use crate::mir::dom::Dominators;
+use crate::mir::loops::{self, Loop};
+use crate::mir::*;
+
+/// The largest header to copy, in instructions.
+const MAX_HEADER: usize = 16;
+
+/// Rotate the loops of a function.
+pub fn run(func: &mut Function) {
+    let doms = Dominators::new(func);
+    let count = loops::find(func, &doms).len();
+    let mut rotated = vec![false; count];
+    let mut changed = false;
+    // Rotating a loop changes the control-flow graph, so the loops are found
+    // again after each one; rotated headers are left unreachable but keep
+    // their numbers until SSA form is restored.
+    'search: for _ in 0..count {
+        let doms = Dominators::new(func);
+        for (i, lp) in loops::find(func, &doms).iter().enumerate() {
+            if rotated.get(i).copied().unwrap_or(true) || !rotate(func, lp) {
+                continue;
+            }
+            rotated[i] = true;
+            changed = true;
+            continue 'search;
+        }
+        break;
+    }
+    if changed {
+        ssa::mem2reg(func);
+    }
+}
+
+/// Rotate a loop, if its header exits the loop.
+fn rotate(func: &mut Function, lp: &Loop) -> bool {
+    let header = lp.header;
+    let Terminator::Branch(cond, then, r#else) = &func.block(header).term
+    else {
+        return false;
+    };
+    let (cond, then, r#else) = (cond.clone(), *then, *r#else);
+    let body = match (lp.contains(then), lp.contains(r#else)) {
+        (true, false) => then,
+        (false, true) => r#else,
+        _ => return false,
+    };
+    let [latch] = lp.latches[..] else {
+        return false;
+    };
+    let insts = &func.block(header).insts;
+    if latch == header
+        || body == header
+        || !matches!(func.block(latch).term, Terminator::Jump(_))
+        || insts.iter().filter(|inst| !inst.is_phi()).count() > MAX_HEADER
+    {
+        return false;
+    }
+    let preheader = loops::insert_preheader(func, lp);
+
+    let old = std::mem::replace(
+        func.block_mut(header),
+        Block {
+            insts: Vec::new(),
+            term: Terminator::Unreachable,
+        },
+    );
+    let phis = old.insts.iter().take_while(|inst| inst.is_phi());
+    let rest = &old.insts[phis.clone().count()..];
+    for pred in [preheader, latch] {
+        // The phis are assigned their values for this edge, through
+        // temporaries since they may refer to each other.
+        let mut insts = Vec::new();
+        let mut temps = Vec::new();
+        for phi in phis.clone() {
+            let Inst::Assign(dest, Rvalue::Phi(args)) = phi else {
+                unreachable!("only phis are taken");
+            };
+            let (_, value) = args.iter().find(|(p, _)| *p == pred).unwrap();
+            let temp = func.add_temp(func.local(*dest).ty.clone());
+            insts.push(Inst::Assign(temp, Rvalue::Use(value.clone())));
+            temps.push((*dest, temp));
+        }
+        for (dest, temp) in temps {
+            insts.push(Inst::Assign(dest, Rvalue::Use(Operand::Local(temp))));
+        }
+        insts.extend_from_slice(rest);
+
+        let block = func.block_mut(pred);
+        block.insts.extend(insts);
+        block.term = Terminator::Branch(cond.clone(), then, r#else);
+    }
+
+    // The successors of the header are now entered from the copies.
+    for succ in [then, r#else] {
+        for inst in &mut func.block_mut(succ).insts {
+            let Inst::Assign(_, Rvalue::Phi(args)) = inst else {
+                break;
+            };
+            let mut renamed = Vec::new();
+            for (pred, value) in args.iter() {
+                if *pred == header {
+                    renamed.push((preheader, value.clone()));
+                    renamed.push((latch, value.clone()));
+                }
+            }
+            args.retain(|(pred, _)| *pred != header);
+            args.extend(renamed);
+        }
+    }
+    true
+}
@@ -0,0 +1,318 @@
+//! Sparse conditional constant propagation.
+//!
+//! This is the algorithm of Wegman and Zadeck: registers are evaluated over
+//! the lattice unknown > constant > varying, and only blocks reachable along
+//! edges that may be taken are considered.  Afterwards, registers with a
+//! constant value are replaced by that constant, and branches whose condition
+//! is known jump straight to their target.
+
+use std::collections::HashSet;
+
+use super::fold;
+use crate::mir::*;
+
+/// The abstract value of a register.
+#[derive(Clone, PartialEq)]
+enum Value {
+    /// No definition has been evaluated yet.
+    Unknown,
+
+    /// Every evaluated definition produces this constant.
+    Const(Const),
+
+    /// The value is not a known constant.
+    Varying,
+}
+
+impl Value {
+    /// Combine two values for the same register.
+    fn meet(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Unknown, value) | (value, Self::Unknown) => value,
+            (Self::Const(a), Self::Const(b)) if a == b => Self::Const(a),
+            _ => Self::Varying,
+        }
+    }
+}
+
+/// Where a register is used: an instruction index, or the terminator.
+type Use = (BlockId, Option<usize>);
+
+/// The state of the propagation.
+struct Sccp<'a> {
+    /// The function being analyzed.
+    func: &'a Function,
+
+    /// The abstract value of every local.
+    values: Vec<Value>,
+
+    /// Whether each block may be executed.
+    executable: Vec<bool>,
+
+    /// The control-flow edges that may be taken.
+    edges: HashSet<(BlockId, BlockId)>,
+
+    /// The uses of every local.
+    uses: Vec<Vec<Use>>,
+
+    /// Edges that have become executable but have not been processed.
+    edge_worklist: Vec<(BlockId, BlockId)>,
+
+    /// Registers whose value has changed but whose uses have not been
+    /// re-evaluated.
+    value_worklist: Vec<Local>,
+}
+
+/// Propagate constants through a function.
+pub fn run(func: &mut Function) {
+    let mut uses = vec![Vec::new(); func.locals.len()];
+    for id in func.block_ids() {
+        let block = func.block(id);
+        let insts = block.insts.iter().enumerate();
+        let operands = insts
+            .flat_map(|(i, inst)| {
+                inst.operands().into_iter().map(move |op| (op, Some(i)))
+            })
+            .chain(block.term.operands().into_iter().map(|op| (op, None)));
+        for (operand, index) in operands {
+            if let Operand::Local(local) = operand {
+                uses[local.0 as usize].push((id, index));
+            }
+        }
+    }
+
+    let mut sccp = Sccp {
+        func,
+        values: vec![Value::Unknown; func.locals.len()],
+        executable: vec![false; func.blocks.len()],
+        edges: HashSet::new(),
+        uses,
+        edge_worklist: Vec::new(),
+        value_worklist: Vec::new(),
+    };
+    for &param in &func.params {
+        sccp.values[param.0 as usize] = Value::Varying;
+    }
+    sccp.executable[0] = true;
+    sccp.visit_block(BlockId(0));
+    sccp.solve();
+
+    let Sccp {
+        values,
+        executable,
+        edges,
+        ..
+    } = sccp;
+    rewrite(func, &values, &executable, &edges);
+}
+
+impl Sccp<'_> {
+    /// Process the worklists until nothing changes.
+    fn solve(&mut self) {
+        loop {
+            if let Some((_, block)) = self.edge_worklist.pop() {
+                if self.executable[block.0 as usize] {
+                    // Only the phis can observe the new edge.
+                    let insts = &self.func.block(block).insts;
+                    for i in 0..insts.iter().take_while(|i| i.is_phi()).count()
+                    {
+                        self.visit_inst(block, i);
+                    }
+                } else {
+                    self.executable[block.0 as usize] = true;
+                    self.visit_block(block);
+                }
+            } else if let Some(local) = self.value_worklist.pop() {
+                for i in 0..self.uses[local.0 as usize].len() {
+                    let (block, index) = self.uses[local.0 as usize][i];
+                    if !self.executable[block.0 as usize] {
+                        continue;
+                    }
+                    match index {
+                        Some(index) => self.visit_inst(block, index),
+                        None => self.visit_term(block),
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Evaluate every instruction in a newly executable block.
+    fn visit_block(&mut self, block: BlockId) {
+        for i in 0..self.func.block(block).insts.len() {
+            self.visit_inst(block, i);
+        }
+        self.visit_term(block);
+    }
+
+    /// The abstract value of an operand.
+    fn operand(&self, operand: &Operand) -> Value {
+        match operand {
+            Operand::Local(local) => self.values[local.0 as usize].clone(),
+            Operand::Const(c) => Value::Const(c.clone()),
+            Operand::Addr(_) => Value::Varying,
+        }
+    }
+
+    /// Evaluate an instruction and update the value of its destination.
+    fn visit_inst(&mut self, block: BlockId, index: usize) {
+        let func = self.func;
+        let inst = &func.block(block).insts[index];
+        let Some(dest) = inst.dest() else {
+            return;
+        };
+        let value = match inst {
+            Inst::Assign(dest, rvalue) => self.rvalue(block, *dest, rvalue),
+            _ => Value::Varying,
+        };
+        let old = &self.values[dest.0 as usize];
+        let new = old.clone().meet(value);
+        if *old != new {
+            self.values[dest.0 as usize] = new;
+            self.value_worklist.push(dest);
+        }
+    }
+
+    /// Evaluate the value computed by an instruction.
+    fn rvalue(&self, block: BlockId, dest: Local, rvalue: &Rvalue) -> Value {
+        // Fold an operation once all of its operands are constants.
+        let eval =
+            |operands: &[&Operand],
+             fold: &dyn Fn(&[Const]) -> Option<Const>| {
+                let mut consts = Vec::new();
+                let mut unknown = false;
+                for operand in operands {
+                    match self.operand(operand) {
+                        Value::Unknown => unknown = true,
+                        Value::Const(c) => consts.push(c),
+                        Value::Varying => return Value::Varying,
+                    }
+                }
+                if unknown {
+                    return Value::Unknown;
+                }
+                fold(&consts).map_or(Value::Varying, Value::Const)
+            };
+        let ty = || self.func.ty(dest);
+
+        match rvalue {
+            Rvalue::Use(a) => self.operand(a),
+            Rvalue::Bin(op, a, b) => {
+                eval(&[a, b], &|c| fold::bin(*op, ty(), &c[0], &c[1]))
+            }
+            Rvalue::Una(op, a) => eval(&[a], &|c| fold::una(*op, ty(), &c[0])),
+            Rvalue::Cast(op, a) => {
+                eval(&[a], &|c| fold::cast(*op, ty(), &c[0]))
+            }
+            Rvalue::Phi(args) => args
+                .iter()
+                .filter(|(pred, _)| self.edges.contains(&(*pred, block)))
+                .fold(Value::Unknown, |value, (_, arg)| {
+                    value.meet(self.operand(arg))
+                }),
+            Rvalue::Load(_) | Rvalue::FieldAddr(..) | Rvalue::ElemAddr(..) => {
+                Value::Varying
+            }
+        }
+    }
+
+    /// Evaluate a terminator and mark the edges it may take.
+    fn visit_term(&mut self, block: BlockId) {
+        let term = &self.func.block(block).term;
+        let known = |sccp: &Self, operand: &Operand| match sccp.operand(operand)
+        {
+            Value::Const(c) => Some(c),
+            // A branch on a value that is never defined may go either way.
+            Value::Unknown | Value::Varying => None,
+        };
+        let targets = match term {
+            Terminator::Branch(cond, then, r#else) => match known(self, cond) {
+                Some(c) if fold::truth(&c) => vec![*then],
+                Some(_) => vec![*r#else],
+                None => term.successors(),
+            },
+            Terminator::Switch {
+                value,
+                cases,
+                default,
+            } => match known(self, value) {
+                Some(Const::Int(value, _)) => {
+                    vec![switch_target(cases, *default, value)]
+                }
+                _ => term.successors(),
+            },
+            term => term.successors(),
+        };
+        for target in targets {
+            if self.edges.insert((block, target)) {
+                self.edge_worklist.push((block, target));
+            }
+        }
+    }
+}
+
+/// The target of a switch on a known value.
+fn switch_target(
+    cases: &[(i64, BlockId)],
+    default: BlockId,
+    value: i64,
+) -> BlockId {
+    cases
+        .iter()
+        .find(|(case, _)| *case == value)
+        .map_or(default, |(_, target)| *target)
+}
+
+/// Replace constant registers and fold branches that always go one way.
+fn rewrite(
+    func: &mut Function,
+    values: &[Value],
+    executable: &[bool],
+    edges: &HashSet<(BlockId, BlockId)>,
+) {
+    let constant = |local: Local| match &values[local.0 as usize] {
+        Value::Const(c) => Some(c.clone()),
+        _ => None,
+    };
+
+    for id in func.block_ids() {
+        if !executable[id.0 as usize] {
+            continue;
+        }
+        let block = func.block_mut(id);
+        block.insts.retain(|inst| match inst {
+            Inst::Assign(dest, _) => constant(*dest).is_none(),
+            _ => true,
+        });
+        let operands =
+            block.insts.iter_mut().flat_map(|inst| inst.operands_mut());
+        for operand in operands.chain(block.term.operands_mut()) {
+            if let Operand::Local(local) = *operand
+                && let Some(c) = constant(local)
+            {
+                *operand = Operand::Const(c);
+            }
+        }
+
+        // Keep only the edges that may be taken.
+        let succs = block.term.successors();
+        let taken: Vec<_> = succs
+            .iter()
+            .copied()
+            .filter(|&succ| edges.contains(&(id, succ)))
+            .collect();
+        if let [target] = taken[..]
+            && succs.len() > 1
+        {
+            block.term = Terminator::Jump(target);
+            for succ in succs.into_iter().filter(|&succ| succ != target) {
+                func.remove_edge(id, succ);
+            }
+        }
+    }
+
+    // Blocks that are never executed are now unreachable.
+    func.remove_unreachable();
+}
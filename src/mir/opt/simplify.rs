@@ -0,0 +1,182 @@
+//! Control-flow graph simplification.
+//!
+//! This folds branches whose outcome is known, forwards jumps through empty
+//! blocks, merges blocks into their only predecessor and removes blocks that
+//! have become unreachable, until none of these apply.
+
+use std::collections::HashMap;
+
+use super::fold;
+use crate::mir::*;
+
+/// Simplify the control-flow graph of a function.
+pub fn run(func: &mut Function) {
+    loop {
+        let mut changed = false;
+        for id in func.block_ids() {
+            changed |= fold_branch(func, id);
+        }
+        func.remove_unreachable();
+        for id in func.block_ids() {
+            changed |= forward_jump(func, id);
+        }
+        func.remove_unreachable();
+        changed |= merge_blocks(func);
+        func.remove_unreachable();
+        if !changed {
+            break;
+        }
+    }
+}
+
+/// Replace a branch or switch that always goes to the same block by a jump.
+fn fold_branch(func: &mut Function, id: BlockId) -> bool {
+    let target = match &func.block(id).term {
+        Terminator::Branch(_, then, r#else) if then == r#else => *then,
+        Terminator::Branch(Operand::Const(c), then, r#else) => {
+            if fold::truth(c) { *then } else { *r#else }
+        }
+        Terminator::Switch {
+            value: Operand::Const(Const::Int(value, _)),
+            cases,
+            default,
+        } => cases
+            .iter()
+            .find(|(case, _)| case == value)
+            .map_or(*default, |(_, target)| *target),
+        Terminator::Switch { cases, default, .. }
+            if cases.iter().all(|(_, target)| target == default) =>
+        {
+            *default
+        }
+        _ => return false,
+    };
+
+    // Drop the phi arguments for every edge but the one kept.
+    let mut succs = func.block(id).term.successors();
+    if let Terminator::Branch(..) = func.block(id).term {
+        let kept = succs.iter().position(|&succ| succ == target).unwrap();
+        succs.remove(kept);
+    } else {
+        succs.retain(|&succ| succ != target);
+    }
+    for succ in succs {
+        func.remove_edge(id, succ);
+    }
+    func.block_mut(id).term = Terminator::Jump(target);
+    true
+}
+
+/// Redirect the predecessors of an empty block ending in a jump to the
+/// jump's target.
+fn forward_jump(func: &mut Function, id: BlockId) -> bool {
+    let block = func.block(id);
+    let Terminator::Jump(target) = block.term else {
+        return false;
+    };
+    if id == BlockId(0) || target == id || !block.insts.is_empty() {
+        return false;
+    }
+
+    // If the target has phis, each predecessor needs its own argument, so
+    // it must not already be a predecessor of the target.
+    let preds = func.predecessors();
+    let has_phis = func.block(target).insts.first().is_some_and(Inst::is_phi);
+    let own = &preds[id.0 as usize];
+    if own.is_empty()
+        || has_phis
+            && own
+                .iter()
+                .any(|pred| preds[target.0 as usize].contains(pred))
+    {
+        return false;
+    }
+
+    for &pred in own {
+        for t in func.block_mut(pred).term.targets_mut() {
+            if *t == id {
+                *t = target;
+            }
+        }
+    }
+    if has_phis {
+        for inst in &mut func.block_mut(target).insts {
+            if let Inst::Assign(_, Rvalue::Phi(args)) = inst {
+                let i = args.iter().position(|(p, _)| *p == id).unwrap();
+                let (_, value) = args.remove(i);
+                args.extend(own.iter().map(|&pred| (pred, value.clone())));
+            }
+        }
+    }
+    true
+}
+
+/// Merge blocks into their predecessor where it is their only one and they
+/// are its only successor.
+fn merge_blocks(func: &mut Function) -> bool {
+    let mut changed = false;
+    let mut replace = HashMap::new();
+    let mut preds = func.predecessors();
+    for id in func.block_ids() {
+        // The block may have been merged into its predecessor already.
+        if preds[id.0 as usize].is_empty() && id != BlockId(0) {
+            continue;
+        }
+        while let Terminator::Jump(succ) = func.block(id).term
+            && succ != id
+            && succ != BlockId(0)
+            && preds[succ.0 as usize] == [id]
+        {
+            let next = std::mem::replace(
+                func.block_mut(succ),
+                Block {
+                    insts: Vec::new(),
+                    term: Terminator::Unreachable,
+                },
+            );
+            let mut insts = next.insts.into_iter().peekable();
+            // The phis of the merged block have a single argument.
+            while let Some(Inst::Assign(dest, Rvalue::Phi(args))) =
+                insts.next_if(Inst::is_phi)
+            {
+                replace.insert(dest, args.into_iter().next().unwrap().1);
+            }
+            func.block_mut(id).insts.extend(insts);
+
+            // The successors of the merged block now follow this one.
+            for next_succ in next.term.successors() {
+                let block = func.block_mut(next_succ);
+                for inst in &mut block.insts {
+                    if let Inst::Assign(_, Rvalue::Phi(args)) = inst {
+                        for (pred, _) in args {
+                            if *pred == succ {
+                                *pred = id;
+                            }
+                        }
+                    }
+                }
+                for pred in &mut preds[next_succ.0 as usize] {
+                    if *pred == succ {
+                        *pred = id;
+                    }
+                }
+            }
+            preds[succ.0 as usize].clear();
+            func.block_mut(id).term = next.term;
+            changed = true;
+        }
+    }
+
+    for block in &mut func.blocks {
+        let operands =
+            block.insts.iter_mut().flat_map(|inst| inst.operands_mut());
+        for operand in operands.chain(block.term.operands_mut()) {
+            while let Operand::Local(local) = operand
+                && let Some(value) = replace.get(local)
+            {
+                *operand = value.clone();
+            }
+        }
+    }
+    changed
+}
@@ -0,0 +1,169 @@
+//! Strength reduction of induction variables.
+//!
+//! A basic induction variable is a header phi advanced by a constant on
+//! every iteration (see [`loops::basic_ivs`]).  Multiplying one by a
+//! constant, shifting it, adding an invariant value, sign-extending it or
+//! using it to index an invariant array also gives a value advancing by a
+//! constant.  Such a value
+//! becomes an induction variable of its own: it is computed from the initial
+//! value in the preheader and advanced by an addition in the latch, which
+//! removes the multiplication from the loop.
+//!
+//! Sign extension relies on signed induction variables never overflowing,
+//! as the C standard guarantees.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ctype::{CType, DataModel};
+use crate::mir::loops::{self, Loop};
+use crate::mir::*;
+
+/// An induction variable.
+struct Iv {
+    /// The value on entry to the loop.
+    init: Operand,
+
+    /// The amount added on every iteration.
+    step: i64,
+}
+
+/// How a value derived from an induction variable advances.
+enum Step {
+    /// By adding a constant.
+    Add(i64),
+
+    /// By a constant number of elements of a type.
+    Elem(CType, i64),
+}
+
+/// Reduce the strength of the induction variables of a function.
+pub fn run(func: &mut Function) {
+    for (lp, preheader) in loops::with_preheaders(func) {
+        reduce(func, &lp, preheader);
+    }
+}
+
+/// Turn the values derived from the induction variables of a loop into
+/// induction variables.
+fn reduce(func: &mut Function, lp: &Loop, preheader: BlockId) {
+    let [latch] = lp.latches[..] else {
+        return;
+    };
+    let mut ivs: HashMap<Local, Iv> = loops::basic_ivs(func, lp, preheader)
+        .into_iter()
+        .map(|iv| {
+            let (init, step) = (iv.init, iv.step);
+            (iv.phi, Iv { init, step })
+        })
+        .collect();
+    if ivs.is_empty() {
+        return;
+    }
+    let variant: HashSet<Local> = lp
+        .blocks
+        .iter()
+        .flat_map(|&block| &func.block(block).insts)
+        .filter_map(Inst::dest)
+        .collect();
+
+    let mut phis = Vec::new();
+    for &block in &lp.blocks {
+        let insts = std::mem::take(&mut func.block_mut(block).insts);
+        let mut kept = Vec::with_capacity(insts.len());
+        for inst in insts {
+            let Inst::Assign(dest, rvalue) = &inst else {
+                kept.push(inst);
+                continue;
+            };
+            let Some((iv, step)) = derive(rvalue, &ivs, &variant) else {
+                kept.push(inst);
+                continue;
+            };
+            let (dest, mut rvalue) = (*dest, rvalue.clone());
+            let ty = func.local(dest).ty.clone();
+
+            // The initial value is computed from that of the variable.
+            for operand in rvalue.operands_mut() {
+                if *operand == Operand::Local(iv) {
+                    *operand = ivs[&iv].init.clone();
+                }
+            }
+            let init = func.add_temp(ty.clone());
+            let insts = &mut func.block_mut(preheader).insts;
+            insts.push(Inst::Assign(init, rvalue));
+
+            let next = func.add_temp(ty);
+            let this = Operand::Local(dest);
+            let rvalue = match &step {
+                Step::Add(step) => {
+                    let step = Const::Int(*step, func.ty(dest));
+                    Rvalue::Bin(BinOp::Add, this, Operand::Const(step))
+                }
+                Step::Elem(elem, step) => {
+                    let step = Operand::Const(Const::Int(*step, Ty::I64));
+                    Rvalue::ElemAddr(this, step, elem.clone())
+                }
+            };
+            func.block_mut(latch).insts.push(Inst::Assign(next, rvalue));
+
+            let args = vec![
+                (preheader, Operand::Local(init)),
+                (latch, Operand::Local(next)),
+            ];
+            phis.push(Inst::Assign(dest, Rvalue::Phi(args)));
+            if let Step::Add(step) = step {
+                let init = Operand::Local(init);
+                ivs.insert(dest, Iv { init, step });
+            }
+        }
+        // The latch may have gained instructions already.
+        let block = func.block_mut(block);
+        kept.append(&mut block.insts);
+        block.insts = kept;
+    }
+    let header = &mut func.block_mut(lp.header).insts;
+    header.splice(0..0, phis);
+}
+
+/// The induction variable a value is derived from, and how it advances.
+fn derive(
+    rvalue: &Rvalue,
+    ivs: &HashMap<Local, Iv>,
+    variant: &HashSet<Local>,
+) -> Option<(Local, Step)> {
+    let iv = |operand: &Operand| match operand {
+        Operand::Local(local) => Some((*local, ivs.get(local)?.step)),
+        _ => None,
+    };
+    match rvalue {
+        Rvalue::Bin(BinOp::Mul, a, Operand::Const(Const::Int(c, ty)))
+        | Rvalue::Bin(BinOp::Mul, Operand::Const(Const::Int(c, ty)), a) => {
+            let (local, step) = iv(a)?;
+            Some((local, Step::Add(ty.wrap(step.wrapping_mul(*c)))))
+        }
+        Rvalue::Bin(BinOp::Shl, a, Operand::Const(Const::Int(c, ty)))
+            if (0..ty.size(DataModel::Lp64) as i64 * 8).contains(c) =>
+        {
+            let (local, step) = iv(a)?;
+            Some((local, Step::Add(ty.wrap(step << c))))
+        }
+        // Constant offsets are left alone, as the variable's own update is
+        // one of them.
+        Rvalue::Bin(BinOp::Add, a, Operand::Local(b))
+        | Rvalue::Bin(BinOp::Add, Operand::Local(b), a)
+            if !variant.contains(b) =>
+        {
+            iv(a).map(|(local, step)| (local, Step::Add(step)))
+        }
+        Rvalue::Cast(CastOp::SExt, a) => {
+            let (local, step) = iv(a)?;
+            Some((local, Step::Add(step)))
+        }
+        Rvalue::ElemAddr(base, index, elem) if !matches!(base, Operand::Local(l) if variant.contains(l)) =>
+        {
+            let (local, step) = iv(index)?;
+            Some((local, Step::Elem(elem.clone(), step)))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,589 @@
+//! SSA construction and verification.
+//!
+//! [`mem2reg()`] promotes memory locals whose address is only used by loads
+//! and stores into registers, and renames registers that are assigned more
+//! than once, so that afterwards every register has a single definition.
+//! This is the classic algorithm of Cytron et al.: phis are inserted on the
+//! iterated dominance frontiers of the definitions, and values are renamed
+//! in a walk over the dominator tree.
+//!
+//! The function may already be partly in SSA form: transformations that
+//! duplicate code (such as loop rotation) may simply copy instructions,
+//! defining the same registers several times, and run [`mem2reg()`] again to
+//! restore SSA form.
+//!
+//! Backends that have no use for phis convert the function back out of SSA
+//! form with [`destruct()`].
+
+use std::collections::HashMap;
+
+use super::dom::Dominators;
+use super::*;
+
+/// Convert a function to SSA form.
+pub fn mem2reg(func: &mut Function) {
+    func.remove_unreachable();
+    let vars = variables(func);
+    if !vars.iter().any(|&var| var) {
+        return;
+    }
+    let doms = Dominators::new(func);
+
+    // Find the blocks defining each variable.
+    let mut defs = vec![Vec::new(); func.locals.len()];
+    for &param in &func.params {
+        if vars[param.0 as usize] {
+            defs[param.0 as usize].push(BlockId(0));
+        }
+    }
+    for id in func.block_ids() {
+        for inst in &func.block(id).insts {
+            let var = match inst {
+                Inst::Store {
+                    addr: Operand::Addr(local),
+                    ..
+                } => Some(*local),
+                inst => inst.dest(),
+            };
+            if let Some(var) = var
+                && vars[var.0 as usize]
+                && !defs[var.0 as usize].contains(&id)
+            {
+                defs[var.0 as usize].push(id);
+            }
+        }
+    }
+
+    // Insert phis on the iterated dominance frontiers.
+    let frontiers = doms.frontiers(func);
+    let mut phis: Vec<Vec<(Local, Local)>> =
+        vec![Vec::new(); func.blocks.len()];
+    for (var, blocks) in defs.into_iter().enumerate() {
+        let var = Local(var as u32);
+        let mut worklist = blocks;
+        let mut has_phi = vec![false; func.blocks.len()];
+        while let Some(block) = worklist.pop() {
+            for &frontier in &frontiers[block.0 as usize] {
+                if has_phi[frontier.0 as usize] {
+                    continue;
+                }
+                has_phi[frontier.0 as usize] = true;
+                let dest = func.add_temp(func.local(var).ty.clone());
+                phis[frontier.0 as usize].push((var, dest));
+                worklist.push(frontier);
+            }
+        }
+    }
+    for (block, phis) in phis.iter().enumerate() {
+        let insts = &mut func.blocks[block].insts;
+        insts.splice(
+            0..0,
+            phis.iter()
+                .map(|&(_, dest)| Inst::Assign(dest, Rvalue::Phi(Vec::new()))),
+        );
+    }
+
+    let mut renamer = Renamer {
+        vars: &vars,
+        phis: &phis,
+        doms: &doms,
+        stacks: vec![Vec::new(); func.locals.len()],
+        replace: HashMap::new(),
+    };
+    for &param in &func.params.clone() {
+        if vars[param.0 as usize] {
+            renamer.define(func, param);
+        }
+    }
+    renamer.rename(func, BlockId(0));
+
+    // Promoted locals are no longer referenced.
+    for (decl, &var) in func.locals.iter_mut().zip(&vars) {
+        if var {
+            decl.memory = false;
+        }
+    }
+    remove_dead_phis(func);
+}
+
+/// Determine which locals need renaming.
+///
+/// These are the memory locals of scalar type that are only accessed by
+/// loads and stores of their own type, and registers with more than one
+/// definition.
+fn variables(func: &Function) -> Vec<bool> {
+    let mut promotable: Vec<bool> = func
+        .locals
+        .iter()
+        .map(|decl| decl.memory && Ty::of(&decl.ty, func.model).is_some())
+        .collect();
+    let mut def_counts = vec![0; func.locals.len()];
+    for &param in &func.params {
+        def_counts[param.0 as usize] += 1;
+    }
+    let scalar = |local: Local| Ty::of(&func.local(local).ty, func.model);
+    let mut escape = |operand: &Operand| {
+        if let Operand::Addr(local) = operand {
+            promotable[local.0 as usize] = false;
+        }
+    };
+
+    for block in &func.blocks {
+        for inst in &block.insts {
+            if let Some(dest) = inst.dest() {
+                def_counts[dest.0 as usize] += 1;
+            }
+            match inst {
+                Inst::Assign(dest, Rvalue::Load(Operand::Addr(local)))
+                    if scalar(*local) == Some(func.ty(*dest)) => {}
+                Inst::Store {
+                    addr: Operand::Addr(local),
+                    value,
+                } if scalar(*local) == Some(func.operand_ty(value)) => {
+                    escape(value)
+                }
+                inst => inst.operands().into_iter().for_each(&mut escape),
+            }
+        }
+        block.term.operands().into_iter().for_each(&mut escape);
+    }
+
+    promotable
+        .into_iter()
+        .zip(def_counts)
+        .zip(&func.locals)
+        .map(|((promotable, defs), decl)| {
+            promotable || (defs > 1 && !decl.memory)
+        })
+        .collect()
+}
+
+/// The state of renaming variables.
+struct Renamer<'a> {
+    /// Which locals are variables to rename.
+    vars: &'a [bool],
+
+    /// The phis inserted at the start of each block, as pairs of a variable
+    /// and the phi's register.
+    phis: &'a [Vec<(Local, Local)>],
+
+    /// The dominator tree of the function.
+    doms: &'a Dominators,
+
+    /// The current values of each variable, innermost definition last.
+    stacks: Vec<Vec<Operand>>,
+
+    /// The values of the registers that loaded promoted variables.
+    replace: HashMap<Local, Operand>,
+}
+
+impl Renamer<'_> {
+    /// Whether a local is a variable to rename.
+    fn is_var(&self, local: Local) -> bool {
+        // Registers created during renaming are never variables.
+        self.vars.get(local.0 as usize).copied().unwrap_or(false)
+    }
+
+    /// The current value of a variable.
+    ///
+    /// Variables read before being written are indeterminate; they are
+    /// given the value zero.
+    fn current(&self, func: &Function, var: Local) -> Operand {
+        match self.stacks[var.0 as usize].last() {
+            Some(value) => value.clone(),
+            None => {
+                let ty = func.ty(var);
+                Operand::Const(match ty {
+                    Ty::F32 | Ty::F64 => Const::Float(0.0, ty),
+                    _ => Const::Int(0, ty),
+                })
+            }
+        }
+    }
+
+    /// Create a fresh register for a definition of a variable.
+    fn define(&mut self, func: &mut Function, var: Local) -> Local {
+        let fresh = func.add_temp(func.local(var).ty.clone());
+        self.stacks[var.0 as usize].push(Operand::Local(fresh));
+        fresh
+    }
+
+    /// Record a definition of a variable in the block being renamed.
+    fn push(&mut self, var: Local, value: Operand, pushed: &mut Vec<Local>) {
+        self.stacks[var.0 as usize].push(value);
+        pushed.push(var);
+    }
+
+    /// Rewrite an operand to refer to current values.
+    fn substitute(&self, func: &Function, operand: &mut Operand) {
+        if let Operand::Local(local) = *operand {
+            if let Some(value) = self.replace.get(&local) {
+                *operand = value.clone();
+            } else if self.is_var(local) {
+                *operand = self.current(func, local);
+            }
+        }
+    }
+
+    /// Rename the variables in a block and the blocks it dominates.
+    fn rename(&mut self, func: &mut Function, block: BlockId) {
+        // The variables defined in this block, to be popped at the end.
+        let mut pushed = Vec::new();
+
+        let all_phis = self.phis;
+        let phis = &all_phis[block.0 as usize];
+        for &(var, dest) in phis {
+            self.push(var, Operand::Local(dest), &mut pushed);
+        }
+
+        let old = std::mem::take(&mut func.block_mut(block).insts);
+        let mut insts = Vec::with_capacity(old.len());
+        for (i, mut inst) in old.into_iter().enumerate() {
+            if i < phis.len() {
+                insts.push(inst);
+                continue;
+            }
+            // The arguments of existing phis are renamed at the end of the
+            // predecessors, below.
+            if let Inst::Assign(dest, Rvalue::Phi(args)) = inst {
+                let dest = if self.is_var(dest) {
+                    pushed.push(dest);
+                    self.define(func, dest)
+                } else {
+                    dest
+                };
+                insts.push(Inst::Assign(dest, Rvalue::Phi(args)));
+                continue;
+            }
+            for operand in inst.operands_mut() {
+                self.substitute(func, operand);
+            }
+            match inst {
+                Inst::Store {
+                    addr: Operand::Addr(var),
+                    value,
+                } if self.is_var(var) => {
+                    self.push(var, value, &mut pushed);
+                }
+                Inst::Assign(dest, Rvalue::Load(Operand::Addr(var)))
+                    if self.is_var(var) =>
+                {
+                    let value = self.current(func, var);
+                    self.replace.insert(dest, value);
+                }
+                Inst::Assign(dest, rvalue) if self.is_var(dest) => {
+                    let fresh = self.define(func, dest);
+                    pushed.push(dest);
+                    insts.push(Inst::Assign(fresh, rvalue));
+                }
+                Inst::Call {
+                    dest: Some(dest),
+                    callee,
+                    args,
+                    sig,
+                    fixed,
+                } if self.is_var(dest) => {
+                    let fresh = self.define(func, dest);
+                    pushed.push(dest);
+                    insts.push(Inst::Call {
+                        dest: Some(fresh),
+                        callee,
+                        args,
+                        sig,
+                        fixed,
+                    });
+                }
+                inst => insts.push(inst),
+            }
+        }
+        func.block_mut(block).insts = insts;
+
+        let mut term = std::mem::replace(
+            &mut func.block_mut(block).term,
+            Terminator::Unreachable,
+        );
+        for operand in term.operands_mut() {
+            self.substitute(func, operand);
+        }
+        let succs = term.successors();
+        func.block_mut(block).term = term;
+
+        // Fill in the phi arguments flowing out of this block.
+        for succ in succs {
+            let inserted = &all_phis[succ.0 as usize];
+            for (i, &(var, _)) in inserted.iter().enumerate() {
+                let value = self.current(func, var);
+                let Inst::Assign(_, Rvalue::Phi(args)) =
+                    &mut func.block_mut(succ).insts[i]
+                else {
+                    unreachable!("phis are at the start of the block");
+                };
+                args.push((block, value));
+            }
+            for i in inserted.len()..func.block(succ).insts.len() {
+                let mut inst = func.block(succ).insts[i].clone();
+                let Inst::Assign(_, Rvalue::Phi(args)) = &mut inst else {
+                    break;
+                };
+                for (pred, arg) in args {
+                    if *pred == block {
+                        self.substitute(func, arg);
+                    }
+                }
+                func.block_mut(succ).insts[i] = inst;
+            }
+        }
+
+        let doms = self.doms;
+        for &child in doms.children(block) {
+            self.rename(func, child);
+        }
+
+        for var in pushed {
+            self.stacks[var.0 as usize].pop();
+        }
+    }
+}
+
+/// Remove phis whose values are never used.
+fn remove_dead_phis(func: &mut Function) {
+    // Mark the registers used by non-phi instructions, then propagate
+    // liveness through the phis.
+    let mut live = vec![false; func.locals.len()];
+    let mut phi_args: HashMap<Local, Vec<Local>> = HashMap::new();
+    let mut worklist = Vec::new();
+    for block in &func.blocks {
+        let uses = block.insts.iter().flat_map(|inst| match inst {
+            Inst::Assign(dest, Rvalue::Phi(args)) => {
+                let locals = args.iter().filter_map(|(_, arg)| match arg {
+                    Operand::Local(local) => Some(*local),
+                    _ => None,
+                });
+                phi_args.insert(*dest, locals.collect());
+                Vec::new()
+            }
+            inst => inst.operands(),
+        });
+        for operand in uses.chain(block.term.operands()) {
+            if let Operand::Local(local) = operand {
+                worklist.push(*local);
+            }
+        }
+    }
+    while let Some(local) = worklist.pop() {
+        if std::mem::replace(&mut live[local.0 as usize], true) {
+            continue;
+        }
+        if let Some(args) = phi_args.get(&local) {
+            worklist.extend(args);
+        }
+    }
+
+    for block in &mut func.blocks {
+        block.insts.retain(|inst| match inst {
+            Inst::Assign(dest, Rvalue::Phi(_)) => live[dest.0 as usize],
+            _ => true,
+        });
+    }
+}
+
+//----------- Destruction ------------------------------------------------------
+
+/// Convert a function out of SSA form, replacing phis by copies.
+///
+/// The copies for a phi are placed at the end of each predecessor.  Critical
+/// edges, from a block with several successors to a block with phis, are
+/// split first, so that the copies only run on their own edge.  The phis of
+/// a block are assigned simultaneously: values that are themselves phis of
+/// the block are copied to temporaries before any phi is assigned.
+pub fn destruct(func: &mut Function) {
+    let preds = func.predecessors();
+    for id in func.block_ids() {
+        if !func.block(id).insts.first().is_some_and(Inst::is_phi) {
+            continue;
+        }
+        let mut critical = preds[id.0 as usize].clone();
+        critical.dedup();
+        critical.retain(|&pred| func.block(pred).term.successors().len() > 1);
+        for pred in critical {
+            let split = BlockId(func.blocks.len() as u32);
+            func.blocks.push(Block {
+                insts: Vec::new(),
+                term: Terminator::Jump(id),
+            });
+            for target in func.block_mut(pred).term.targets_mut() {
+                if *target == id {
+                    *target = split;
+                }
+            }
+            for inst in &mut func.block_mut(id).insts {
+                let Inst::Assign(_, Rvalue::Phi(args)) = inst else {
+                    break;
+                };
+                for (arg_pred, _) in args {
+                    if *arg_pred == pred {
+                        *arg_pred = split;
+                    }
+                }
+            }
+        }
+    }
+
+    for id in func.block_ids() {
+        let insts = &mut func.block_mut(id).insts;
+        let count = insts.iter().take_while(|inst| inst.is_phi()).count();
+        let phis: Vec<_> = insts
+            .drain(..count)
+            .map(|phi| match phi {
+                Inst::Assign(dest, Rvalue::Phi(args)) => (dest, args),
+                _ => unreachable!("only phis are taken"),
+            })
+            .collect();
+        let dests: Vec<Local> = phis.iter().map(|(dest, _)| *dest).collect();
+
+        let mut copies: HashMap<BlockId, (Vec<Inst>, Vec<Inst>)> =
+            HashMap::new();
+        for (dest, args) in phis {
+            for (pred, mut value) in args {
+                let (saves, assigns) = copies.entry(pred).or_default();
+                if let Operand::Local(local) = value
+                    && dests.contains(&local)
+                {
+                    let temp = func.add_temp(func.local(local).ty.clone());
+                    saves.push(Inst::Assign(temp, Rvalue::Use(value)));
+                    value = Operand::Local(temp);
+                }
+                assigns.push(Inst::Assign(dest, Rvalue::Use(value)));
+            }
+        }
+        for (pred, (saves, assigns)) in copies {
+            let insts = &mut func.block_mut(pred).insts;
+            insts.extend(saves);
+            insts.extend(assigns);
+        }
+    }
+}
+
+//----------- Verification -----------------------------------------------------
+
+/// Check that a function is in valid SSA form.
+///
+/// Every register must be defined exactly once (or be a parameter), phis
+/// must come first in their block with one argument per predecessor, and
+/// every use must be dominated by its definition.
+pub fn verify(func: &Function) -> Result<(), String> {
+    let error = |message: String| format!("in `{}`: {}", func.name, message);
+    let doms = Dominators::new(func);
+    let preds = func.predecessors();
+
+    // The location of each register's definition; parameters are defined
+    // before the first instruction.
+    let mut defs: Vec<Option<(BlockId, Option<usize>)>> =
+        vec![None; func.locals.len()];
+    for &param in &func.params {
+        if !func.local(param).memory {
+            defs[param.0 as usize] = Some((BlockId(0), None));
+        }
+    }
+    for id in func.block_ids() {
+        let mut phis_done = false;
+        for (i, inst) in func.block(id).insts.iter().enumerate() {
+            if inst.is_phi() && phis_done {
+                return Err(error(format!("phi after non-phi in {id}")));
+            }
+            phis_done |= !inst.is_phi();
+            let Some(dest) = inst.dest() else {
+                continue;
+            };
+            // Aggregate results of calls are written to memory.
+            if let Inst::Call { .. } = inst
+                && func.local(dest).memory
+                && Ty::of(&func.local(dest).ty, func.model).is_none()
+            {
+                continue;
+            }
+            if func.local(dest).memory {
+                return Err(error(format!(
+                    "assignment to memory local %{}",
+                    dest.0
+                )));
+            }
+            if defs[dest.0 as usize].replace((id, Some(i))).is_some() {
+                return Err(error(format!(
+                    "%{} is defined more than once",
+                    dest.0
+                )));
+            }
+        }
+    }
+
+    // Check that a use at a location is dominated by its definition.
+    let check = |operand: &Operand, block: BlockId, index: Option<usize>| {
+        match *operand {
+            Operand::Local(local) => {
+                if func.local(local).memory {
+                    return Err(error(format!(
+                        "use of memory local %{}",
+                        local.0
+                    )));
+                }
+                let Some((def_block, def_index)) = defs[local.0 as usize]
+                else {
+                    return Err(error(format!(
+                        "%{} is never defined",
+                        local.0
+                    )));
+                };
+                let dominated = if def_block == block {
+                    match (def_index, index) {
+                        (None, _) | (Some(_), None) => true,
+                        (Some(def), Some(at)) => def < at,
+                    }
+                } else {
+                    doms.dominates(def_block, block)
+                };
+                if !dominated {
+                    return Err(error(format!(
+                        "use of %{} in {block} is not dominated by its definition",
+                        local.0
+                    )));
+                }
+                Ok(())
+            }
+            Operand::Addr(local) if !func.local(local).memory => {
+                Err(error(format!("address of register %{}", local.0)))
+            }
+            _ => Ok(()),
+        }
+    };
+
+    for id in func.block_ids() {
+        let block = func.block(id);
+        for (i, inst) in block.insts.iter().enumerate() {
+            if let Inst::Assign(_, Rvalue::Phi(args)) = inst {
+                let mut incoming: Vec<_> =
+                    args.iter().map(|(pred, _)| *pred).collect();
+                let mut expected = preds[id.0 as usize].clone();
+                incoming.sort();
+                expected.sort();
+                if incoming != expected {
+                    return Err(error(format!(
+                        "phi in {id} does not match the predecessors"
+                    )));
+                }
+                // A phi argument is used at the end of its predecessor.
+                for (pred, arg) in args {
+                    check(arg, *pred, None)?;
+                }
+                continue;
+            }
+            for operand in inst.operands() {
+                check(operand, id, Some(i))?;
+            }
+        }
+        for operand in block.term.operands() {
+            check(operand, id, None)?;
+        }
+    }
+    Ok(())
+}
@@ -1,30 +1,52 @@
+use std::sync::Arc;
+
 use crate::hir::VarDefn;
-use crate::lexer::{Lexer, ParseResult};
+use crate::lexer::{Lexer, ParseOutput, ParseResult};
 
 use super::hir::*;
-pub trait Parseable {
-    fn parse(lexer: Lexer) -> ParseResult<Self>
+pub trait Parseable<'hir> {
+    fn parse(lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self>
     where
         Self: std::marker::Sized;
 }
-impl Parseable for Type {
-    fn parse(mut lexer: Lexer) -> ParseResult<Self> {
+
+impl<'hir, T: Parseable<'hir>> Parseable<'hir> for Spanned<T> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
+        let start = lexer.offset();
+        let node = T::parse(lexer.delegate())?.into();
+        Ok(spanned(lexer, start, node))
+    }
+}
+
+/// Finish parsing a node that began at an offset, recording its span.
+fn spanned<T>(lexer: Lexer, start: usize, node: T) -> ParseOutput<Spanned<T>> {
+    let span = lexer.span(start);
+    lexer.finish(Spanned::new(node, span))
+}
+impl<'hir> Parseable<'hir> for Type<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
         if lexer.keyword("struct").is_ok() {
             let name = if let Ok(id_res) = lexer.ident() {
-                Some(id_res.to_string())
+                Some(lexer.intern(id_res))
             } else {
                 None
             };
-            lexer.symbol("{")?;
-            let mut fields = Vec::new();
-            loop {
-                if lexer.symbol("}").is_ok() {
-                    break;
+            let fields = if lexer.symbol("{").is_ok() {
+                let mut fields = Vec::new();
+                loop {
+                    if lexer.symbol("}").is_ok() {
+                        break;
+                    }
+                    let member = MonoDecl::parse(lexer.delegate())?.into();
+                    fields.push(member);
+                    lexer.symbol(";")?;
                 }
-                let member = MonoDecl::parse(lexer.delegate())?.into();
-                fields.push(member);
-                lexer.symbol(";")?;
-            }
+                Some(lexer.arena().mono_decls(fields))
+            } else if name.is_some() {
+                None
+            } else {
+                return Err(lexer.error("'{'"));
+            };
 
             return Ok(lexer.finish(Self::Struct {
                 attrs: (),
@@ -35,61 +57,165 @@ impl Parseable for Type {
 
         for (std_type, concrete) in STD_TYPES.entries() {
             if lexer.keyword(std_type).is_ok() {
-                return Ok(lexer.finish(concrete.clone()));
+                return Ok(lexer.finish(*concrete));
             }
         }
 
         // attempt to parse user-defined type or fail
-        let name = lexer.ident()?.to_string();
+        let name = lexer.ident()?;
+        let name = lexer.intern(name);
+        if !lexer.is_typedef(name) {
+            return Err(lexer.error("type"));
+        }
         Ok(lexer.finish(Self::Ident(name)))
     }
 }
-impl Parseable for Binding {
-    fn parse(mut lexer: Lexer) -> ParseResult<Self> {
-        if lexer.symbol("(").is_ok() {
-            let inner = Box::new(Binding::parse(lexer.delegate())?.into());
-            lexer.symbol(")")?;
-            return Ok(lexer.finish(Self::Paren(inner)));
+
+/// Parse a [`Type`], and find it in the arena.
+fn base_type<'hir>(
+    mut lexer: Lexer<'_, '_, 'hir>,
+) -> ParseResult<&'hir Type<'hir>> {
+    let ty = Type::parse(lexer.delegate())?.into();
+    let ty = lexer.arena().ty(ty);
+    Ok(lexer.finish(ty))
+}
+
+impl<'hir> Parseable<'hir> for Binding<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
+        if lexer.punct("*").is_ok() {
+            let inner = Spanned::<Binding>::parse(lexer.delegate())?.into();
+            let inner = lexer.arena().binding(inner);
+            return Ok(lexer.finish(Self::Pointer(inner)));
         }
 
-        let name = lexer.ident()?.to_string();
-        if lexer.symbol("(").is_ok() {
-            if lexer.symbol(")").is_ok() {
-                return Ok(lexer.finish(Self::Fn {
-                    inner: Box::new(Binding::Ident(name)),
-                    params: Default::default(),
-                }));
+        let start = lexer.offset();
+        let mut binding = if let Ok(name) = lexer.ident() {
+            Self::Ident(lexer.intern(name))
+        } else if let Ok(inner) = paren_binding(lexer.delegate()) {
+            Self::Paren(lexer.arena().binding(inner.into()))
+        } else {
+            Self::Anonymous
+        };
+
+        loop {
+            let span = lexer.span(start);
+            if lexer.symbol("[").is_ok() {
+                let size = if lexer.symbol("]").is_ok() {
+                    None
+                } else {
+                    let size = conditional(lexer.delegate())?.into();
+                    lexer.symbol("]")?;
+                    Some(size)
+                };
+                binding = Self::Array {
+                    inner: lexer.arena().binding(Spanned::new(binding, span)),
+                    size,
+                };
+            } else if let Ok(params) = params(lexer.delegate()) {
+                let (params, variadic) = params.into();
+                binding = Self::Fn {
+                    inner: lexer.arena().binding(Spanned::new(binding, span)),
+                    params,
+                    variadic,
+                };
+            } else {
+                break;
             }
-            let mut params = Vec::new();
-            loop {
-                let inner = MonoDecl::parse(lexer.delegate())?.into();
-                params.push(inner);
-                if lexer.symbol(")").is_ok() {
-                    break;
-                }
-                lexer.symbol(",")?;
+        }
+        Ok(lexer.finish(binding))
+    }
+}
+
+/// Parse a parenthesized, non-abstract [`Binding`].
+///
+/// In abstract bindings, an opening parenthesis may also begin a parameter
+/// list; this only succeeds if the parenthesized binding is not empty.
+fn paren_binding<'hir>(
+    mut lexer: Lexer<'_, '_, 'hir>,
+) -> ParseResult<Spanned<Binding<'hir>>> {
+    lexer.symbol("(")?;
+    let inner = Spanned::<Binding>::parse(lexer.delegate())?.into();
+    if let Binding::Anonymous = inner.node {
+        return Err(lexer.error("binding"));
+    }
+    lexer.symbol(")")?;
+    Ok(lexer.finish(inner))
+}
+
+/// Parse a parenthesized parameter list.
+///
+/// Returns the parameters and whether they end in `...`.
+fn params<'hir>(
+    mut lexer: Lexer<'_, '_, 'hir>,
+) -> ParseResult<(&'hir [MonoDecl<'hir>], bool)> {
+    lexer.symbol("(")?;
+    let mut params = Vec::new();
+    let mut variadic = false;
+    if lexer.symbol(")").is_err() {
+        loop {
+            if !params.is_empty() && lexer.punct("...").is_ok() {
+                variadic = true;
+                lexer.symbol(")")?;
+                break;
             }
-            return Ok(lexer.finish(Self::Fn {
-                inner: Box::new(Binding::Ident(name)),
-                params,
-            }));
+            let inner = MonoDecl::parse(lexer.delegate())?.into();
+            params.push(inner);
+            if lexer.symbol(")").is_ok() {
+                break;
+            }
+            lexer.symbol(",")?;
         }
-        Ok(lexer.finish(Self::Ident(name)))
+    }
+    let params = lexer.arena().mono_decls(params);
+    Ok(lexer.finish((params, variadic)))
+}
+
+impl<'hir> Parseable<'hir> for TypeName<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
+        let base = base_type(lexer.delegate())?.into();
+        let binding = Spanned::<Binding>::parse(lexer.delegate())?.into();
+        if binding.name().is_some() {
+            return Err(lexer.error("abstract binding"));
+        }
+        Ok(lexer.finish(Self { base, binding }))
     }
 }
 
-impl Parseable for Program {
-    fn parse(mut lexer: Lexer) -> ParseResult<Self> {
+/// Parse a parenthesized [`TypeName`].
+fn paren_type_name<'hir>(
+    mut lexer: Lexer<'_, '_, 'hir>,
+) -> ParseResult<TypeName<'hir>> {
+    lexer.symbol("(")?;
+    let type_name = TypeName::parse(lexer.delegate())?.into();
+    lexer.symbol(")")?;
+    Ok(lexer.finish(type_name))
+}
+
+impl<'hir> Parseable<'hir> for Program<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
         let mut decls = Vec::new();
+        let mut spans = Vec::new();
         while lexer.remaining_chars() > 0 {
-            let decl = TopDefn::parse(lexer.delegate())?.into();
+            let start = lexer.offset();
+            // The failure of the last alternative tried is not the most
+            // helpful one.
+            let decl = match TopDefn::parse(lexer.delegate()) {
+                Ok(decl) => decl.into(),
+                Err(_) => return Err(lexer.furthest()),
+            };
             decls.push(decl);
+            spans.push(lexer.span(start));
         }
-        Ok(lexer.finish(Self { decls }))
+        let names = Arc::new(lexer.take_names());
+        Ok(lexer.finish(Self {
+            decls,
+            spans,
+            names,
+        }))
     }
 }
-impl Parseable for TopDefn {
-    fn parse(mut lexer: Lexer) -> ParseResult<Self> {
+impl<'hir> Parseable<'hir> for TopDefn<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
         if let Ok(fn_res) = FnDefn::parse(lexer.delegate()) {
             return Ok(lexer.finish(Self::Fn(fn_res.into())));
         }
@@ -100,68 +226,96 @@ impl Parseable for TopDefn {
         Ok(lexer.finish(Self::Type(typedef)))
     }
 }
-impl Parseable for FnDefn {
-    fn parse(mut lexer: Lexer) -> ParseResult<Self> {
-        let return_type = Type::parse(lexer.delegate())?.into();
-        let name = lexer.ident()?.to_string();
-        lexer.symbol("(")?;
-        let mut params = Vec::new();
-        if lexer.symbol(")").is_err() {
-            loop {
-                let inner = MonoDecl::parse(lexer.delegate())?.into();
-                params.push(inner);
-                if lexer.symbol(")").is_ok() {
-                    break;
-                }
-                lexer.symbol(",")?;
-            }
+/// Parse the storage-class and function specifiers before a declaration.
+fn specifiers(mut lexer: Lexer) -> ParseResult<Attrs> {
+    const STORAGE: [Storage; 4] = [
+        Storage::Static,
+        Storage::Extern,
+        Storage::Auto,
+        Storage::Register,
+    ];
+    let mut attrs = Attrs::default();
+    loop {
+        if lexer.keyword("inline").is_ok() {
+            attrs.inline = true;
+            continue;
+        }
+        let Some(storage) = STORAGE
+            .into_iter()
+            .find(|storage| lexer.keyword(storage.keyword()).is_ok())
+        else {
+            break;
+        };
+        if attrs.storage.replace(storage).is_some() {
+            return Err(lexer.error("at most one storage class"));
         }
+    }
+    Ok(lexer.finish(attrs))
+}
+
+impl<'hir> Parseable<'hir> for FnDefn<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
+        let attrs = specifiers(lexer.delegate())?.into();
+        let return_type = base_type(lexer.delegate())?.into();
+        let name = lexer.ident()?;
+        let name = lexer.intern(name);
+        let (params, variadic) = params(lexer.delegate())?.into();
         let body = if lexer.symbol(";").is_ok() {
             None
         } else {
             Some(Block::parse(lexer.delegate())?.into())
         };
         Ok(lexer.finish(Self {
-            attrs: (),
+            attrs,
             return_type,
             name,
             params,
+            variadic,
             body,
         }))
     }
 }
 
-impl Parseable for VarDefn {
-    fn parse(mut lexer: Lexer) -> ParseResult<Self> {
-        let base = Type::parse(lexer.delegate())?.into();
+impl<'hir> Parseable<'hir> for VarDefn<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
+        let attrs = specifiers(lexer.delegate())?.into();
+        let base = base_type(lexer.delegate())?.into();
         let mut bindings = Vec::new();
 
-        // TODO initialization stmts
         loop {
-            let binding = Binding::parse(lexer.delegate())?.into();
-            bindings.push((binding, None::<Expr>));
+            let binding = Spanned::<Binding>::parse(lexer.delegate())?.into();
+            let init = if lexer.punct("=").is_ok() {
+                Some(Initializer::parse(lexer.delegate())?.into())
+            } else {
+                None
+            };
+            bindings.push((binding, init));
             if lexer.symbol(";").is_ok() {
                 break;
             }
             lexer.symbol(",")?;
         }
 
+        let bindings = lexer.arena().init_bindings(bindings);
         Ok(lexer.finish(Self {
-            attrs: (),
+            attrs,
             base,
             bindings,
         }))
     }
 }
 
-impl Parseable for TypeDefn {
-    fn parse(mut lexer: Lexer) -> ParseResult<Self> {
+impl<'hir> Parseable<'hir> for TypeDefn<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
         lexer.keyword("typedef")?;
-        let base = Type::parse(lexer.delegate())?.into();
+        let base = base_type(lexer.delegate())?.into();
         let mut bindings = Vec::new();
 
         loop {
-            let binding = Binding::parse(lexer.delegate())?.into();
+            let binding = Spanned::<Binding>::parse(lexer.delegate())?.into();
+            if let Some(name) = binding.name() {
+                lexer.declare_typedef(name);
+            }
             bindings.push(binding);
             if lexer.symbol(";").is_ok() {
                 break;
@@ -169,6 +323,7 @@ impl Parseable for TypeDefn {
             lexer.symbol(",")?;
         }
 
+        let bindings = lexer.arena().bindings(bindings);
         Ok(lexer.finish(Self {
             attrs: (),
             base,
@@ -176,13 +331,13 @@ impl Parseable for TypeDefn {
         }))
     }
 }
-impl Parseable for Decl {
-    fn parse(mut lexer: Lexer) -> ParseResult<Self> {
-        let base = Type::parse(lexer.delegate())?.into();
+impl<'hir> Parseable<'hir> for Decl<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
+        let base = base_type(lexer.delegate())?.into();
         let mut bindings = Vec::new();
 
         loop {
-            let binding = Binding::parse(lexer.delegate())?.into();
+            let binding = Spanned::<Binding>::parse(lexer.delegate())?.into();
             bindings.push(binding);
             if lexer.symbol(";").is_ok() {
                 break;
@@ -190,6 +345,7 @@ impl Parseable for Decl {
             lexer.symbol(",")?;
         }
 
+        let bindings = lexer.arena().bindings(bindings);
         Ok(lexer.finish(Self {
             attrs: (),
             base,
@@ -198,10 +354,10 @@ impl Parseable for Decl {
     }
 }
 
-impl Parseable for MonoDecl {
-    fn parse(mut lexer: Lexer) -> ParseResult<Self> {
-        let base = Type::parse(lexer.delegate())?.into();
-        let binding = Binding::parse(lexer.delegate())?.into();
+impl<'hir> Parseable<'hir> for MonoDecl<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
+        let base = base_type(lexer.delegate())?.into();
+        let binding = Spanned::<Binding>::parse(lexer.delegate())?.into();
         Ok(lexer.finish(Self {
             attrs: (),
             base,
@@ -210,21 +366,22 @@ impl Parseable for MonoDecl {
     }
 }
 
-impl Parseable for Block {
-    fn parse(mut lexer: Lexer) -> ParseResult<Self> {
+impl<'hir> Parseable<'hir> for Block<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
         lexer.symbol("{")?;
         let mut stmts = Vec::new();
         while lexer.symbol("}").is_err() {
-            let stmt = Stmt::parse(lexer.delegate())?.into();
+            let stmt = Spanned::<Stmt>::parse(lexer.delegate())?.into();
             stmts.push(stmt);
         }
+        let stmts = lexer.arena().stmts(stmts);
         Ok(lexer.finish(Self { stmts }))
     }
 }
 
-impl Parseable for Stmt {
+impl<'hir> Parseable<'hir> for Stmt<'hir> {
     /// Parse a [`Stmt`].
-    fn parse(mut lexer: Lexer) -> ParseResult<Self> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
         // empty
         if lexer.symbol(";").is_ok() {
             return Ok(lexer.finish(Self::Empty));
@@ -250,14 +407,61 @@ impl Parseable for Stmt {
 
         // goto
         if lexer.keyword("goto").is_ok() {
-            let label = lexer.ident()?.to_string();
+            let label = lexer.ident()?;
+            let label = lexer.intern(label);
             lexer.symbol(";")?;
             return Ok(lexer.finish(Self::Goto(label)));
         }
 
+        // switch
+        if lexer.keyword("switch").is_ok() {
+            lexer.symbol("(")?;
+            let cond = Spanned::<Expr>::parse(lexer.delegate())?.into();
+            lexer.symbol(")")?;
+            let body = lexer
+                .arena()
+                .stmt(Spanned::<Stmt>::parse(lexer.delegate())?.into());
+            return Ok(lexer.finish(Self::Switch { cond, body }));
+        }
+
+        // case
+        if lexer.keyword("case").is_ok() {
+            let value = conditional(lexer.delegate())?.into();
+            lexer.punct(":")?;
+            let stmt = lexer
+                .arena()
+                .stmt(Spanned::<Stmt>::parse(lexer.delegate())?.into());
+            return Ok(lexer.finish(Self::Case(value, stmt)));
+        }
+
+        // default
+        if lexer.keyword("default").is_ok() {
+            lexer.punct(":")?;
+            let stmt = lexer
+                .arena()
+                .stmt(Spanned::<Stmt>::parse(lexer.delegate())?.into());
+            return Ok(lexer.finish(Self::Default(stmt)));
+        }
+
+        // label
+        let mut probe = lexer.delegate();
+        if let Ok(label) = probe.ident()
+            && probe.punct(":").is_ok()
+        {
+            let label = probe.intern(label);
+            let stmt = probe
+                .arena()
+                .stmt(Spanned::<Stmt>::parse(probe.delegate())?.into());
+            probe.finish(());
+            return Ok(lexer.finish(Self::Label(label, stmt)));
+        }
+        drop(probe);
+
         // return
         if lexer.keyword("return").is_ok() {
-            let expr = Expr::parse(lexer.delegate()).ok().map(|e| e.into());
+            let expr = Spanned::<Expr>::parse(lexer.delegate())
+                .ok()
+                .map(|e| e.into());
             lexer.symbol(";")?;
             return Ok(lexer.finish(Self::Return(expr)));
         }
@@ -265,12 +469,18 @@ impl Parseable for Stmt {
         // if
         if lexer.keyword("if").is_ok() {
             lexer.symbol("(")?;
-            let cond = Expr::parse(lexer.delegate())?.into();
+            let cond = Spanned::<Expr>::parse(lexer.delegate())?.into();
             lexer.symbol(")")?;
-            let then = Box::new(Stmt::parse(lexer.delegate())?.into());
+            let then = lexer
+                .arena()
+                .stmt(Spanned::<Stmt>::parse(lexer.delegate())?.into());
 
             let r#else = if lexer.keyword("else").is_ok() {
-                Some(Box::new(Stmt::parse(lexer.delegate())?.into()))
+                Some(
+                    lexer
+                        .arena()
+                        .stmt(Spanned::<Stmt>::parse(lexer.delegate())?.into()),
+                )
             } else {
                 None
             };
@@ -281,18 +491,22 @@ impl Parseable for Stmt {
         // while
         if lexer.keyword("while").is_ok() {
             lexer.symbol("(")?;
-            let cond = Expr::parse(lexer.delegate())?.into();
+            let cond = Spanned::<Expr>::parse(lexer.delegate())?.into();
             lexer.symbol(")")?;
-            let body = Box::new(Stmt::parse(lexer.delegate())?.into());
+            let body = lexer
+                .arena()
+                .stmt(Spanned::<Stmt>::parse(lexer.delegate())?.into());
             return Ok(lexer.finish(Self::While { cond, body }));
         }
 
         // do-while
         if lexer.keyword("do").is_ok() {
-            let body = Box::new(Stmt::parse(lexer.delegate())?.into());
+            let body = lexer
+                .arena()
+                .stmt(Spanned::<Stmt>::parse(lexer.delegate())?.into());
             lexer.keyword("while")?;
             lexer.symbol("(")?;
-            let cond = Expr::parse(lexer.delegate())?.into();
+            let cond = Spanned::<Expr>::parse(lexer.delegate())?.into();
             lexer.symbol(")")?;
             lexer.symbol(";")?;
             return Ok(lexer.finish(Self::DoWhile { body, cond }));
@@ -301,13 +515,21 @@ impl Parseable for Stmt {
         // for
         if lexer.keyword("for").is_ok() {
             lexer.symbol("(")?;
-            let init = Expr::parse(lexer.delegate()).ok().map(|e| e.into());
+            let init = Spanned::<Expr>::parse(lexer.delegate())
+                .ok()
+                .map(|e| e.into());
             lexer.symbol(";")?;
-            let cond = Expr::parse(lexer.delegate()).ok().map(|e| e.into());
+            let cond = Spanned::<Expr>::parse(lexer.delegate())
+                .ok()
+                .map(|e| e.into());
             lexer.symbol(";")?;
-            let step = Expr::parse(lexer.delegate()).ok().map(|e| e.into());
+            let step = Spanned::<Expr>::parse(lexer.delegate())
+                .ok()
+                .map(|e| e.into());
             lexer.symbol(")")?;
-            let body = Box::new(Stmt::parse(lexer.delegate())?.into());
+            let body = lexer
+                .arena()
+                .stmt(Spanned::<Stmt>::parse(lexer.delegate())?.into());
             return Ok(lexer.finish(Self::For {
                 init,
                 cond,
@@ -321,13 +543,454 @@ impl Parseable for Stmt {
             return Ok(lexer.finish(Self::VarDefn(decl)));
         }
 
-        todo!()
+        // expression
+        let expr = Spanned::<Expr>::parse(lexer.delegate())?.into();
+        lexer.symbol(";")?;
+        Ok(lexer.finish(Self::Expr(expr)))
+    }
+}
+
+impl<'hir> Parseable<'hir> for Initializer<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
+        if let Ok(items) = init_list(lexer.delegate()) {
+            return Ok(lexer.finish(Self::List(items.into())));
+        }
+        let expr = assignment(lexer.delegate())?.into();
+        Ok(lexer.finish(Self::Expr(expr)))
+    }
+}
+
+/// Parse a brace-enclosed initializer list.
+fn init_list<'hir>(
+    mut lexer: Lexer<'_, '_, 'hir>,
+) -> ParseResult<&'hir [InitItem<'hir>]> {
+    lexer.symbol("{")?;
+    let mut items = Vec::new();
+    while lexer.symbol("}").is_err() {
+        items.push(InitItem::parse(lexer.delegate())?.into());
+        if lexer.symbol("}").is_ok() {
+            break;
+        }
+        lexer.symbol(",")?;
+    }
+    let items = lexer.arena().init_items(items);
+    Ok(lexer.finish(items))
+}
+
+impl<'hir> Parseable<'hir> for InitItem<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
+        let mut designators = Vec::new();
+        while let Ok(designator) = Designator::parse(lexer.delegate()) {
+            designators.push(designator.into());
+        }
+        if !designators.is_empty() {
+            lexer.punct("=")?;
+        }
+        let init = Initializer::parse(lexer.delegate())?.into();
+        let designators = lexer.arena().designators(designators);
+        Ok(lexer.finish(Self { designators, init }))
     }
 }
 
-impl Parseable for Expr {
-    fn parse(mut lexer: Lexer) -> ParseResult<Self> {
-        lexer.keyword("expr")?;
-        Ok(lexer.finish(Self::Debug))
+impl<'hir> Parseable<'hir> for Designator<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
+        if lexer.punct(".").is_ok() {
+            let name = lexer.ident()?;
+            let name = lexer.intern(name);
+            return Ok(lexer.finish(Self::Member(name)));
+        }
+        lexer.symbol("[")?;
+        let index = conditional(lexer.delegate())?.into();
+        lexer.symbol("]")?;
+        Ok(lexer.finish(Self::Index(index)))
+    }
+}
+
+impl<'hir> Parseable<'hir> for Expr<'hir> {
+    fn parse(mut lexer: Lexer<'_, '_, 'hir>) -> ParseResult<Self> {
+        let arena = lexer.arena();
+        let start = lexer.offset();
+        let mut lhs = assignment(lexer.delegate())?.into();
+        while lexer.punct(",").is_ok() {
+            let rhs = assignment(lexer.delegate())?.into();
+            let expr =
+                Expr::Bin(BinOp::Comma, arena.expr(lhs), arena.expr(rhs));
+            lhs = Spanned::new(expr, lexer.span(start));
+        }
+        Ok(lexer.finish(lhs.node))
+    }
+}
+
+/// The assignment operators.
+const ASSIGN_OPS: &[(&str, BinOp)] = &[
+    ("=", BinOp::Assign),
+    ("+=", BinOp::AddAssign),
+    ("-=", BinOp::SubAssign),
+    ("*=", BinOp::MulAssign),
+    ("/=", BinOp::DivAssign),
+    ("%=", BinOp::RemAssign),
+    ("<<=", BinOp::ShlAssign),
+    (">>=", BinOp::ShrAssign),
+    ("&=", BinOp::BitAndAssign),
+    ("|=", BinOp::BitOrAssign),
+    ("^=", BinOp::BitXorAssign),
+];
+
+/// The left-associative binary operators, from lowest to highest precedence.
+const BIN_OPS: &[&[(&str, BinOp)]] = &[
+    &[("||", BinOp::Or)],
+    &[("&&", BinOp::And)],
+    &[("|", BinOp::BitOr)],
+    &[("^", BinOp::BitXor)],
+    &[("&", BinOp::BitAnd)],
+    &[("==", BinOp::Eq), ("!=", BinOp::Ne)],
+    &[
+        ("<", BinOp::Lt),
+        (">", BinOp::Gt),
+        ("<=", BinOp::Le),
+        (">=", BinOp::Ge),
+    ],
+    &[("<<", BinOp::Shl), (">>", BinOp::Shr)],
+    &[("+", BinOp::Add), ("-", BinOp::Sub)],
+    &[("*", BinOp::Mul), ("/", BinOp::Div), ("%", BinOp::Rem)],
+];
+
+/// Parse an assignment expression.
+fn assignment<'hir>(
+    mut lexer: Lexer<'_, '_, 'hir>,
+) -> ParseResult<Spanned<Expr<'hir>>> {
+    let arena = lexer.arena();
+    let start = lexer.offset();
+    let lhs = conditional(lexer.delegate())?.into();
+    for &(punct, op) in ASSIGN_OPS {
+        if lexer.punct(punct).is_ok() {
+            let rhs = assignment(lexer.delegate())?.into();
+            let expr = Expr::Bin(op, arena.expr(lhs), arena.expr(rhs));
+            return Ok(spanned(lexer, start, expr));
+        }
+    }
+    Ok(lexer.finish(lhs))
+}
+
+/// Parse a conditional expression.
+fn conditional<'hir>(
+    mut lexer: Lexer<'_, '_, 'hir>,
+) -> ParseResult<Spanned<Expr<'hir>>> {
+    let arena = lexer.arena();
+    let start = lexer.offset();
+    let cond = binary(lexer.delegate(), 0)?.into();
+    if lexer.punct("?").is_err() {
+        return Ok(lexer.finish(cond));
+    }
+    let then = Spanned::<Expr>::parse(lexer.delegate())?.into();
+    lexer.punct(":")?;
+    let r#else = conditional(lexer.delegate())?.into();
+    let expr =
+        Expr::Cond(arena.expr(cond), arena.expr(then), arena.expr(r#else));
+    Ok(spanned(lexer, start, expr))
+}
+
+/// Parse a binary expression at the given precedence level of [`BIN_OPS`].
+fn binary<'hir>(
+    mut lexer: Lexer<'_, '_, 'hir>,
+    level: usize,
+) -> ParseResult<Spanned<Expr<'hir>>> {
+    let arena = lexer.arena();
+    let Some(ops) = BIN_OPS.get(level) else {
+        return cast(lexer);
+    };
+    let start = lexer.offset();
+    let mut lhs = binary(lexer.delegate(), level + 1)?.into();
+    'outer: loop {
+        for &(punct, op) in *ops {
+            if lexer.punct(punct).is_ok() {
+                let rhs = binary(lexer.delegate(), level + 1)?.into();
+                let expr = Expr::Bin(op, arena.expr(lhs), arena.expr(rhs));
+                lhs = Spanned::new(expr, lexer.span(start));
+                continue 'outer;
+            }
+        }
+        break;
+    }
+    Ok(lexer.finish(lhs))
+}
+
+/// Parse a cast expression.
+fn cast<'hir>(
+    mut lexer: Lexer<'_, '_, 'hir>,
+) -> ParseResult<Spanned<Expr<'hir>>> {
+    let arena = lexer.arena();
+    // A parenthesized type followed by a brace is a compound literal, which
+    // is handled as a primary expression.
+    let start = lexer.offset();
+    let mut probe = lexer.delegate();
+    if let Ok(type_name) = paren_type_name(probe.delegate())
+        && !probe.input().starts_with('{')
+    {
+        let operand = cast(probe.delegate())?.into();
+        probe.finish(());
+        let type_name = lexer.arena().type_name(type_name.into());
+        let expr = Expr::Cast(type_name, arena.expr(operand));
+        return Ok(spanned(lexer, start, expr));
+    }
+    drop(probe);
+
+    unary(lexer)
+}
+
+/// Parse a unary expression.
+fn unary<'hir>(
+    mut lexer: Lexer<'_, '_, 'hir>,
+) -> ParseResult<Spanned<Expr<'hir>>> {
+    let arena = lexer.arena();
+    let start = lexer.offset();
+    if lexer.punct("&").is_ok() {
+        let operand = cast(lexer.delegate())?.into();
+        return Ok(spanned(lexer, start, Expr::Ref(arena.expr(operand))));
+    }
+
+    for (punct, op) in [
+        ("-", UnaOp::Neg),
+        ("!", UnaOp::Not),
+        ("~", UnaOp::Inv),
+        ("+", UnaOp::Plus),
+        ("*", UnaOp::Deref),
+    ] {
+        if lexer.punct(punct).is_ok() {
+            let operand = cast(lexer.delegate())?.into();
+            return Ok(spanned(
+                lexer,
+                start,
+                Expr::Una(op, arena.expr(operand)),
+            ));
+        }
+    }
+
+    for (punct, op) in [("++", UnaOp::PreInc), ("--", UnaOp::PreDec)] {
+        if lexer.punct(punct).is_ok() {
+            let operand = unary(lexer.delegate())?.into();
+            return Ok(spanned(
+                lexer,
+                start,
+                Expr::Una(op, arena.expr(operand)),
+            ));
+        }
+    }
+
+    if lexer.keyword("sizeof").is_ok() {
+        let mut probe = lexer.delegate();
+        if let Ok(type_name) = paren_type_name(probe.delegate())
+            && !probe.input().starts_with('{')
+        {
+            probe.finish(());
+            let type_name = lexer.arena().type_name(type_name.into());
+            return Ok(spanned(lexer, start, Expr::SizeofType(type_name)));
+        }
+        drop(probe);
+
+        let operand = unary(lexer.delegate())?.into();
+        return Ok(spanned(lexer, start, Expr::Sizeof(arena.expr(operand))));
+    }
+
+    if lexer.keyword("_Alignof").is_ok() {
+        let type_name = paren_type_name(lexer.delegate())?.into();
+        let type_name = lexer.arena().type_name(type_name);
+        return Ok(spanned(lexer, start, Expr::Alignof(type_name)));
+    }
+
+    postfix(lexer)
+}
+
+/// Parse a postfix expression.
+fn postfix<'hir>(
+    mut lexer: Lexer<'_, '_, 'hir>,
+) -> ParseResult<Spanned<Expr<'hir>>> {
+    let arena = lexer.arena();
+    let start = lexer.offset();
+    let mut expr = primary(lexer.delegate())?.into();
+    loop {
+        let operand = || arena.expr(expr);
+        let node = if lexer.symbol("[").is_ok() {
+            let index = Spanned::<Expr>::parse(lexer.delegate())?.into();
+            lexer.symbol("]")?;
+            Expr::Index(operand(), arena.expr(index))
+        } else if lexer.symbol("(").is_ok() {
+            let mut args = Vec::new();
+            if lexer.symbol(")").is_err() {
+                loop {
+                    args.push(assignment(lexer.delegate())?.into());
+                    if lexer.symbol(")").is_ok() {
+                        break;
+                    }
+                    lexer.symbol(",")?;
+                }
+            }
+            Expr::Call(operand(), arena.exprs(args))
+        } else if lexer.punct(".").is_ok() {
+            let name = lexer.ident()?;
+            Expr::Member(operand(), lexer.intern(name))
+        } else if lexer.punct("->").is_ok() {
+            let name = lexer.ident()?;
+            Expr::Arrow(operand(), lexer.intern(name))
+        } else if lexer.punct("++").is_ok() {
+            Expr::PostInc(operand())
+        } else if lexer.punct("--").is_ok() {
+            Expr::PostDec(operand())
+        } else {
+            break;
+        };
+        expr = Spanned::new(node, lexer.span(start));
+    }
+    Ok(lexer.finish(expr))
+}
+
+/// Parse a primary expression.
+///
+/// The span of a parenthesized expression includes the parentheses.
+fn primary<'hir>(
+    mut lexer: Lexer<'_, '_, 'hir>,
+) -> ParseResult<Spanned<Expr<'hir>>> {
+    let start = lexer.offset();
+    if lexer.keyword("expr").is_ok() {
+        return Ok(spanned(lexer, start, Expr::Debug));
+    }
+
+    if let Ok(name) = lexer.ident() {
+        let name = lexer.intern(name);
+        return Ok(spanned(lexer, start, Expr::Ident(name)));
+    }
+
+    if let Some(expr) = va_builtin(lexer.delegate())?.into() {
+        return Ok(spanned(lexer, start, expr));
+    }
+
+    if let Ok(text) = lexer.number() {
+        let lit = number(text).ok_or_else(|| lexer.error("number"))?;
+        return Ok(spanned(lexer, start, Expr::Lit(lit)));
+    }
+
+    if let Ok(value) = lexer.char_lit() {
+        return Ok(spanned(lexer, start, Expr::Lit(Lit::Char(value))));
+    }
+
+    if let Ok(value) = lexer.string_lit() {
+        let value = lexer.arena().bytes(value);
+        return Ok(spanned(lexer, start, Expr::Lit(Lit::Str(value))));
+    }
+
+    if let Ok(type_name) = paren_type_name(lexer.delegate()) {
+        let type_name = lexer.arena().type_name(type_name.into());
+        let items = init_list(lexer.delegate())?.into();
+        return Ok(spanned(lexer, start, Expr::CompoundLit(type_name, items)));
+    }
+
+    lexer.symbol("(")?;
+    let expr = Expr::parse(lexer.delegate())?.into();
+    lexer.symbol(")")?;
+    Ok(spanned(lexer, start, expr))
+}
+
+/// Parse a use of one of the builtins behind `<stdarg.h>`, if the input
+/// starts with one.
+fn va_builtin<'hir>(
+    mut lexer: Lexer<'_, '_, 'hir>,
+) -> ParseResult<Option<Expr<'hir>>> {
+    let arena = lexer.arena();
+    let name = [
+        "__builtin_va_start",
+        "__builtin_va_arg",
+        "__builtin_va_end",
+        "__builtin_va_copy",
+    ]
+    .into_iter()
+    .find(|name| lexer.keyword(name).is_ok());
+    let Some(name) = name else {
+        return Ok(lexer.finish(None));
+    };
+    lexer.symbol("(")?;
+    let ap = arena.expr(assignment(lexer.delegate())?.into());
+    let expr = match name {
+        "__builtin_va_end" => Expr::VaEnd(ap),
+        "__builtin_va_arg" => {
+            lexer.symbol(",")?;
+            let type_name = TypeName::parse(lexer.delegate())?.into();
+            Expr::VaArg(ap, arena.type_name(type_name))
+        }
+        _ => {
+            lexer.symbol(",")?;
+            let other = arena.expr(assignment(lexer.delegate())?.into());
+            match name {
+                "__builtin_va_start" => Expr::VaStart(ap, other),
+                _ => Expr::VaCopy(ap, other),
+            }
+        }
+    };
+    lexer.symbol(")")?;
+    Ok(lexer.finish(Some(expr)))
+}
+
+/// Interpret the text of a numeric constant.
+fn number(text: &str) -> Option<Lit<'static>> {
+    let lower = text.to_ascii_lowercase();
+    let hex = lower.starts_with("0x");
+    let is_float = lower.contains('.')
+        || (!hex && lower.contains('e'))
+        || (hex && lower.contains('p'));
+
+    if is_float {
+        let digits = lower.trim_end_matches(['f', 'l']);
+        let suffix = &lower[digits.len()..];
+        if suffix.len() > 1 {
+            return None;
+        }
+        let value = match digits.strip_prefix("0x") {
+            Some(digits) => hex_float(digits)?,
+            None => digits.parse().ok()?,
+        };
+        return Some(Lit::Float {
+            value,
+            single: suffix == "f",
+            long: suffix == "l",
+        });
+    }
+
+    let digits = lower.trim_end_matches(['u', 'l']);
+    let suffix = &lower[digits.len()..];
+    let unsigned = suffix.contains('u');
+    let long = suffix.matches('l').count() as u8;
+    if suffix.matches('u').count() > 1 || long > 2 {
+        return None;
+    }
+
+    let (radix, value) = if let Some(digits) = digits.strip_prefix("0x") {
+        (Radix::Hex, u64::from_str_radix(digits, 16).ok()?)
+    } else if let Some(digits) = digits.strip_prefix("0b") {
+        (Radix::Binary, u64::from_str_radix(digits, 2).ok()?)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (Radix::Octal, u64::from_str_radix(&digits[1..], 8).ok()?)
+    } else {
+        (Radix::Decimal, digits.parse().ok()?)
+    };
+    Some(Lit::Int {
+        value,
+        radix,
+        unsigned,
+        long,
+    })
+}
+
+/// Parse the digits of a hexadecimal floating constant after its `0x`.
+fn hex_float(digits: &str) -> Option<f64> {
+    let (mantissa, exp) = digits.split_once('p')?;
+    let exp: i32 = exp.parse().ok()?;
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if int.is_empty() && frac.is_empty() {
+        return None;
+    }
+    let mut value = 0.0;
+    for c in int.chars().chain(frac.chars()) {
+        value = value * 16.0 + c.to_digit(16)? as f64;
     }
+    let exp = exp.checked_sub(4 * frac.len() as i32)?;
+    Some(value * 2f64.powi(exp))
 }
@@ -0,0 +1,45 @@
+//! Room for deep recursion.
+//!
+//! The front end walks the HIR recursively, so an expression nests the stack
+//! as deeply as it is long: `x + x + ... + x` is a left-nested chain of
+//! [`Expr::Bin`](crate::hir::Expr::Bin)s.  Generated C easily has chains of
+//! thousands of terms, more than the stack of the main thread takes, so the
+//! stages that walk the HIR run on a thread with a large stack instead.
+
+use std::cell::Cell;
+use std::panic;
+use std::thread;
+
+/// The stack size of the threads that stages run on.
+///
+/// The memory is only reserved, and is committed as it is used.
+pub const STACK_SIZE: usize = 1 << 30;
+
+thread_local! {
+    /// Whether the current thread was started by [`with_stack()`].
+    static DEEP: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run a function on a thread with a [`STACK_SIZE`] stack, and return its
+/// result.
+///
+/// The function runs on the current thread if it already is one.  Panics are
+/// passed on to the caller.
+pub fn with_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    if DEEP.get() {
+        return f();
+    }
+    thread::scope(|scope| {
+        let thread = thread::Builder::new()
+            .name("sea".into())
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || {
+                DEEP.set(true);
+                f()
+            })
+            .expect("cannot start a thread");
+        thread
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload))
+    })
+}
@@ -0,0 +1,1646 @@
+//! Type checking.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter, Write as _};
+
+use crate::codegen::Target;
+use crate::ctype::{
+    CType, Field, FloatType, FnType, IntRank, IntType, StructDef, StructId,
+    TypeTable,
+};
+use crate::hir::*;
+
+//----------- Checker ----------------------------------------------------------
+
+/// A type checker for a translation unit.
+///
+/// Besides checking whole programs, the checker keeps track of the names in
+/// scope, so later passes can use it to resolve types as they walk the HIR.
+pub struct Checker {
+    /// The structure types defined so far.
+    pub types: TypeTable,
+
+    /// The target the types are laid out for.
+    pub target: Target,
+
+    /// The type of `va_list`, once it is first used.
+    va_list: Option<CType>,
+
+    /// The scopes enclosing the current point, innermost last.
+    scopes: Vec<Scope>,
+
+    /// The function being checked, if any.
+    function: Option<Symbol>,
+
+    /// The source text of the innermost construct being checked, if any.
+    span: Option<Span>,
+
+    /// The return type of the function being checked.
+    ret: CType,
+
+    /// Whether the function being checked is variadic.
+    variadic: bool,
+
+    /// The number of loops enclosing the current statement.
+    loops: usize,
+
+    /// The switch statements enclosing the current statement, innermost last.
+    switches: Vec<SwitchCases>,
+
+    /// The labels defined in the function being checked.
+    labels: HashSet<Symbol>,
+
+    /// The labels targeted by `goto` in the function being checked.
+    gotos: Vec<Symbol>,
+
+    /// The errors found so far.
+    errors: Vec<TypeError>,
+}
+
+/// A lexical scope.
+#[derive(Default)]
+struct Scope {
+    /// The ordinary identifiers declared in the scope.
+    names: HashMap<Symbol, Name>,
+
+    /// The struct tags declared in the scope.
+    tags: HashMap<Symbol, StructId>,
+}
+
+/// Block scopes set aside by [`Checker::take_scopes()`].
+pub struct BlockScopes(Vec<Scope>);
+
+/// The cases seen so far in a switch statement.
+#[derive(Default)]
+struct SwitchCases {
+    /// The values of the `case` labels.
+    values: HashSet<i64>,
+
+    /// Whether there is a `default` label.
+    default: bool,
+}
+
+/// The meaning of an ordinary identifier.
+#[derive(Clone)]
+enum Name {
+    /// A variable or function of the given type.
+    Var(CType),
+
+    /// A typedef name for the given type.
+    Typedef(CType),
+}
+
+//--- Construction
+
+impl Checker {
+    /// Construct a new [`Checker`] at file scope, for a target.
+    pub fn new(target: Target) -> Self {
+        Self {
+            types: TypeTable::new(target.data_model()),
+            target,
+            va_list: None,
+            scopes: vec![Scope::default()],
+            function: None,
+            span: None,
+            ret: CType::Void,
+            variadic: false,
+            loops: 0,
+            switches: Vec::new(),
+            labels: HashSet::new(),
+            gotos: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        Self::new(Target::default())
+    }
+}
+
+//--- Checking programs
+
+impl Checker {
+    /// Check a complete program.
+    ///
+    /// On success, the structure types of the program are returned.
+    pub fn check(
+        mut self,
+        program: &Program,
+    ) -> Result<TypeTable, Vec<TypeError>> {
+        program.names.enter(|| {
+            for (decl, span) in program.decls.iter().zip(&program.spans) {
+                self.span = Some(*span);
+                self.top_defn(decl);
+            }
+            self.span = None;
+            if self.errors.is_empty() {
+                Ok(self.types)
+            } else {
+                Err(self.errors)
+            }
+        })
+    }
+
+    /// Check a complete program, and write out the resolved types of its
+    /// structures and of the names it declares at file scope.
+    pub fn dump(mut self, program: &Program) -> Result<String, Vec<TypeError>> {
+        program.names.enter(|| {
+            for (decl, span) in program.decls.iter().zip(&program.spans) {
+                self.span = Some(*span);
+                self.top_defn(decl);
+            }
+            self.span = None;
+            if !self.errors.is_empty() {
+                return Err(self.errors);
+            }
+
+            let mut out = String::new();
+            for id in self.types.struct_ids() {
+                let def = self.types.get(id);
+                write!(out, "struct #{}", id.0).unwrap();
+                if let Some(name) = &def.name {
+                    write!(out, " {name}").unwrap();
+                }
+                if let Some(fields) = &def.fields {
+                    out.push_str(" {");
+                    for field in fields {
+                        write!(out, " {}: {:?};", field.name, field.ty)
+                            .unwrap();
+                    }
+                    out.push_str(" }");
+                }
+                out.push('\n');
+            }
+
+            // Each name is written once, even if it is declared again.
+            let mut seen = HashSet::new();
+            let names = program.decls.iter().flat_map(|decl| match decl {
+                TopDefn::Fn(defn) => vec![defn.name],
+                TopDefn::Var(defn) => defn
+                    .bindings
+                    .iter()
+                    .filter_map(|(binding, _)| binding.name())
+                    .collect(),
+                TopDefn::Type(defn) => {
+                    defn.bindings.iter().filter_map(|b| b.name()).collect()
+                }
+            });
+            for name in names.filter(|&name| seen.insert(name)) {
+                match &self.scopes[0].names[&name] {
+                    Name::Var(ty) => writeln!(out, "{name}: {ty:?}"),
+                    Name::Typedef(ty) => {
+                        writeln!(out, "typedef {name}: {ty:?}")
+                    }
+                }
+                .unwrap();
+            }
+            Ok(out)
+        })
+    }
+
+    /// Check a top-level definition.
+    fn top_defn(&mut self, decl: &TopDefn) {
+        let result = match decl {
+            TopDefn::Fn(defn) => self.fn_defn(defn),
+            TopDefn::Var(defn) => self.var_defn(defn),
+            TopDefn::Type(defn) => self.type_defn(defn),
+        };
+        self.report(result);
+    }
+
+    /// Check a function definition.
+    fn fn_defn(&mut self, defn: &FnDefn) -> Result<(), TypeError> {
+        let fn_type = self.fn_signature(defn)?;
+        self.declare(defn.name, CType::Fn(Box::new(fn_type.clone())))?;
+
+        let Some(body) = &defn.body else {
+            return Ok(());
+        };
+        self.function = Some(defn.name);
+        self.ret = fn_type.ret.clone();
+        self.variadic = defn.variadic;
+        self.push_scope();
+        for (param, ty) in defn.params.iter().zip(&fn_type.params) {
+            if let Some(name) = param.binding.name() {
+                let result = self.declare(name, ty.clone());
+                self.report(result);
+            }
+        }
+        for stmt in body.stmts {
+            self.stmt(stmt);
+        }
+        for label in std::mem::take(&mut self.gotos) {
+            if !self.labels.contains(&label) {
+                let error = self.error(format!("undefined label `{label}`"));
+                self.errors.push(error);
+            }
+        }
+        self.labels.clear();
+        self.pop_scope();
+        self.function = None;
+        self.ret = CType::Void;
+        self.variadic = false;
+        Ok(())
+    }
+
+    /// Resolve the type of a function definition.
+    pub fn fn_signature(&mut self, defn: &FnDefn) -> Result<FnType, TypeError> {
+        let ret = self.resolve_base(defn.return_type)?;
+        self.fn_type(ret, defn.params, defn.variadic)
+    }
+
+    /// Check a variable definition.
+    fn var_defn(&mut self, defn: &VarDefn) -> Result<(), TypeError> {
+        let base = self.resolve_base(defn.base)?;
+        let file_scope = self.scopes.len() == 1;
+        for (binding, init) in defn.bindings {
+            let result = self
+                .var_binding(&base, binding, init.as_ref())
+                .and_then(|ty| {
+                    // A tentative definition of an array may omit its length.
+                    let defined = defn.attrs.storage != Some(Storage::Extern)
+                        && !matches!(ty, CType::Fn(_))
+                        && !(file_scope
+                            && matches!(ty, CType::Array { len: None, .. }));
+                    match binding.name() {
+                        Some(name) if defined && !self.is_complete(&ty) => {
+                            Err(self.error(format!(
+                                "variable `{name}` has incomplete type {ty}",
+                                ty = self.types.named(&ty)
+                            )))
+                        }
+                        _ => Ok(()),
+                    }
+                });
+            self.report(result);
+        }
+        Ok(())
+    }
+
+    /// Check a single binding of a variable definition.
+    ///
+    /// Returns the type of the variable, completed by its initializer.
+    pub fn var_binding(
+        &mut self,
+        base: &CType,
+        binding: &Binding,
+        init: Option<&Initializer>,
+    ) -> Result<CType, TypeError> {
+        let ty = self.apply(base.clone(), binding)?;
+        let Some(name) = binding.name() else {
+            return Ok(ty);
+        };
+        // The scope of a variable starts at its declarator, so it is declared
+        // before (and regardless of) its initializer.
+        self.declare(name, ty.clone())?;
+        if ty == CType::Void {
+            return Err(self.error(format!("variable `{name}` has void type")));
+        }
+        let Some(init) = init else {
+            return Ok(ty);
+        };
+        let complete = self.initializer(&ty, init)?;
+        if complete != ty {
+            self.complete(name, complete.clone())?;
+        }
+        Ok(complete)
+    }
+
+    /// Check a type definition.
+    pub fn type_defn(&mut self, defn: &TypeDefn) -> Result<(), TypeError> {
+        let base = self.resolve_base(defn.base)?;
+        for binding in defn.bindings {
+            let ty = self.apply(base.clone(), binding)?;
+            if let Some(name) = binding.name() {
+                self.scope().names.insert(name, Name::Typedef(ty));
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the error from a fallible check, if any.
+    fn report(&mut self, result: Result<impl Sized, TypeError>) {
+        if let Err(err) = result {
+            self.errors.push(err);
+        }
+    }
+
+    /// Construct an error in the current context.
+    fn error(&self, message: String) -> TypeError {
+        TypeError {
+            function: self.function.map(|name| name.to_string()),
+            span: self.span,
+            message,
+        }
+    }
+}
+
+//--- Scopes
+
+impl Checker {
+    /// Enter a new block scope.
+    pub fn push_scope(&mut self) {
+        self.scopes.push(Scope::default());
+    }
+
+    /// Leave the innermost block scope.
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Set aside the block scopes, leaving only file scope.
+    ///
+    /// This lets the body of a function be walked in the middle of another
+    /// one, as an interpreter does; see [`Self::restore_scopes()`].
+    pub fn take_scopes(&mut self) -> BlockScopes {
+        BlockScopes(self.scopes.split_off(1))
+    }
+
+    /// Bring back the block scopes set aside by [`Self::take_scopes()`],
+    /// replacing the current ones.
+    pub fn restore_scopes(&mut self, scopes: BlockScopes) {
+        self.scopes.truncate(1);
+        self.scopes.extend(scopes.0);
+    }
+
+    /// The innermost scope.
+    fn scope(&mut self) -> &mut Scope {
+        self.scopes.last_mut().unwrap()
+    }
+
+    /// Declare a variable or function in the innermost scope.
+    pub fn declare(
+        &mut self,
+        name: Symbol,
+        ty: CType,
+    ) -> Result<(), TypeError> {
+        let file_scope = self.scopes.len() == 1;
+        match self.scope().names.get(&name) {
+            // File-scope declarations may be repeated if they agree.
+            Some(Name::Var(prev)) if file_scope && *prev == ty => Ok(()),
+            Some(Name::Var(prev))
+                if file_scope && Self::completes(prev, &ty) =>
+            {
+                self.scope().names.insert(name, Name::Var(ty));
+                Ok(())
+            }
+            Some(Name::Var(prev))
+                if file_scope && Self::completes(&ty, prev) =>
+            {
+                Ok(())
+            }
+            Some(_) if file_scope => {
+                Err(self.error(format!("conflicting types for `{name}`")))
+            }
+            Some(_) => Err(self.error(format!("redefinition of `{name}`"))),
+            None => {
+                self.scope().names.insert(name, Name::Var(ty));
+                Ok(())
+            }
+        }
+    }
+
+    /// Complete the type of a variable just declared in the innermost scope.
+    fn complete(&mut self, name: Symbol, ty: CType) -> Result<(), TypeError> {
+        match self.scope().names.get(&name) {
+            Some(Name::Var(prev)) if *prev == ty => Ok(()),
+            Some(Name::Var(prev)) if Self::completes(prev, &ty) => {
+                self.scope().names.insert(name, Name::Var(ty));
+                Ok(())
+            }
+            _ => Err(self.error(format!("conflicting types for `{name}`"))),
+        }
+    }
+
+    /// Whether `new` is `prev` with its array length or its parameters
+    /// filled in.
+    fn completes(prev: &CType, new: &CType) -> bool {
+        match (prev, new) {
+            (
+                CType::Array { elem: a, len: None },
+                CType::Array { elem: b, .. },
+            ) => a == b,
+            (CType::Fn(a), CType::Fn(b)) => !a.prototype && a.ret == b.ret,
+            _ => false,
+        }
+    }
+
+    /// Look up the type of a variable or function.
+    pub fn lookup(&self, name: Symbol) -> Option<&CType> {
+        self.scopes.iter().rev().find_map(|scope| {
+            match scope.names.get(&name) {
+                Some(Name::Var(ty)) => Some(ty),
+                Some(Name::Typedef(_)) | None => None,
+            }
+        })
+    }
+
+    /// Look up a typedef name.
+    fn lookup_typedef(&self, name: Symbol) -> Option<&CType> {
+        self.scopes.iter().rev().find_map(|scope| {
+            match scope.names.get(&name) {
+                Some(Name::Typedef(ty)) => Some(ty),
+                Some(Name::Var(_)) | None => None,
+            }
+        })
+    }
+
+    /// Look up a struct tag.
+    fn lookup_tag(&self, name: Symbol) -> Option<StructId> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.tags.get(&name).copied())
+    }
+}
+
+//--- Resolving types
+
+impl Checker {
+    /// Resolve a base type.
+    pub fn resolve_base(&mut self, ty: &Type) -> Result<CType, TypeError> {
+        let int = |rank, signed: &Option<bool>| {
+            CType::Int(IntType {
+                rank,
+                signed: signed.unwrap_or(true),
+            })
+        };
+        Ok(match ty {
+            Type::Void => CType::Void,
+            Type::Char { signed } => int(IntRank::Char, signed),
+            Type::Short { signed } => int(IntRank::Short, signed),
+            Type::Int { signed } => int(IntRank::Int, signed),
+            Type::Long { signed } => int(IntRank::Long, signed),
+            Type::LongLong { signed } => int(IntRank::LongLong, signed),
+            Type::Float => CType::Float(FloatType::Float),
+            Type::Double => CType::Float(FloatType::Double),
+            Type::LongDouble => CType::Float(FloatType::LongDouble),
+            Type::VaList => self.va_list(),
+            Type::Struct { name, fields, .. } => {
+                CType::Struct(self.resolve_struct(*name, *fields)?)
+            }
+            Type::Ident(name) => match self.lookup_typedef(*name) {
+                Some(ty) => ty.clone(),
+                None => {
+                    return Err(self.error(format!("unknown type `{name}`")));
+                }
+            },
+        })
+    }
+
+    /// Resolve a struct type, defining it if fields are given.
+    fn resolve_struct(
+        &mut self,
+        name: Option<Symbol>,
+        fields: Option<&[MonoDecl]>,
+    ) -> Result<StructId, TypeError> {
+        let Some(fields) = fields else {
+            // A reference to a (possibly incomplete) struct.
+            let name = name.expect("anonymous structs always have fields");
+            if let Some(id) = self.lookup_tag(name) {
+                return Ok(id);
+            }
+            let id = self.types.add_struct(StructDef {
+                name: Some(name.to_string()),
+                fields: None,
+            });
+            self.scope().tags.insert(name, id);
+            return Ok(id);
+        };
+
+        // Complete a forward declaration in the same scope, if there is one.
+        let existing =
+            name.and_then(|name| self.scope().tags.get(&name).copied());
+        let id = match existing {
+            Some(id) if self.types.get(id).fields.is_some() => {
+                let name = name.unwrap();
+                return Err(
+                    self.error(format!("redefinition of `struct {name}`"))
+                );
+            }
+            Some(id) => id,
+            None => {
+                let id = self.types.add_struct(StructDef {
+                    name: name.map(|name| name.to_string()),
+                    fields: None,
+                });
+                if let Some(name) = name {
+                    self.scope().tags.insert(name, id);
+                }
+                id
+            }
+        };
+
+        let mut resolved: Vec<Field> = Vec::new();
+        for field in fields {
+            let ty = self.resolve(field.base, &field.binding)?;
+            let Some(name) = field.binding.name() else {
+                return Err(self.error("struct field without a name".into()));
+            };
+            if resolved.iter().any(|f| f.name == name.to_string()) {
+                return Err(self.error(format!("duplicate member `{name}`")));
+            }
+            if !self.is_complete(&ty) {
+                return Err(self.error(format!(
+                    "field `{name}` has incomplete type {ty}",
+                    ty = self.types.named(&ty)
+                )));
+            }
+            resolved.push(Field {
+                name: name.to_string(),
+                ty,
+            });
+        }
+        self.types.get_mut(id).fields = Some(resolved);
+        Ok(id)
+    }
+
+    /// The type of `va_list` on the target.
+    ///
+    /// On x86-64, it is an array of one `__va_list_tag`, the structure the
+    /// System V ABI walks the register save area with.  Elsewhere, it points
+    /// to the next argument.  The structure is only defined once used.
+    pub fn va_list(&mut self) -> CType {
+        if let Some(ty) = &self.va_list {
+            return ty.clone();
+        }
+        let ty = match self.target {
+            Target::X86_64 => {
+                let uint = CType::Int(IntType {
+                    rank: IntRank::Int,
+                    signed: false,
+                });
+                let ptr = CType::Void.pointer_to();
+                let fields = [
+                    ("gp_offset", uint.clone()),
+                    ("fp_offset", uint),
+                    ("overflow_arg_area", ptr.clone()),
+                    ("reg_save_area", ptr),
+                ];
+                let id = self.types.add_struct(StructDef {
+                    name: Some("__va_list_tag".into()),
+                    fields: Some(
+                        fields
+                            .map(|(name, ty)| Field {
+                                name: name.into(),
+                                ty,
+                            })
+                            .into(),
+                    ),
+                });
+                CType::Array {
+                    elem: Box::new(CType::Struct(id)),
+                    len: Some(1),
+                }
+            }
+            Target::Riscv64 | Target::Wasm32 => CType::Void.pointer_to(),
+        };
+        self.va_list = Some(ty.clone());
+        ty
+    }
+
+    /// Resolve a base type elaborated by a binding.
+    pub fn resolve(
+        &mut self,
+        base: &Type,
+        binding: &Binding,
+    ) -> Result<CType, TypeError> {
+        let base = self.resolve_base(base)?;
+        self.apply(base, binding)
+    }
+
+    /// Resolve a type name.
+    pub fn type_name(&mut self, ty: &TypeName) -> Result<CType, TypeError> {
+        self.resolve(ty.base, &ty.binding)
+    }
+
+    /// Elaborate a type with a binding.
+    pub fn apply(
+        &mut self,
+        base: CType,
+        binding: &Binding,
+    ) -> Result<CType, TypeError> {
+        match binding {
+            Binding::Fn {
+                inner,
+                params,
+                variadic,
+            } => {
+                let fn_type = self.fn_type(base, params, *variadic)?;
+                self.apply(CType::Fn(Box::new(fn_type)), inner)
+            }
+            Binding::Array { inner, size } => {
+                if matches!(base, CType::Fn(_)) || !self.is_complete(&base) {
+                    return Err(self.error(format!(
+                        "array of incomplete type {base}",
+                        base = self.types.named(&base)
+                    )));
+                }
+                let len = match size {
+                    Some(size) => match self.const_int(size) {
+                        Some(len) if len >= 0 => Some(len as u64),
+                        _ => {
+                            return Err(self.error(
+                                "array size is not a non-negative constant"
+                                    .into(),
+                            ));
+                        }
+                    },
+                    None => None,
+                };
+                let ty = CType::Array {
+                    elem: Box::new(base),
+                    len,
+                };
+                self.apply(ty, inner)
+            }
+            Binding::Pointer(inner) => self.apply(base.pointer_to(), inner),
+            Binding::Paren(inner) => self.apply(base, inner),
+            Binding::Ident(_) | Binding::Anonymous => Ok(base),
+        }
+    }
+
+    /// Resolve a function type.
+    fn fn_type(
+        &mut self,
+        ret: CType,
+        params: &[MonoDecl],
+        variadic: bool,
+    ) -> Result<FnType, TypeError> {
+        if matches!(ret, CType::Fn(_) | CType::Array { .. }) {
+            return Err(self.error(format!(
+                "function returning {ret}",
+                ret = self.types.named(&ret)
+            )));
+        }
+
+        // `(void)` declares a function without parameters.
+        if let [param] = params
+            && let Binding::Anonymous = param.binding.node
+            && let Type::Void = param.base
+        {
+            return Ok(FnType {
+                ret,
+                params: Vec::new(),
+                variadic,
+                prototype: true,
+            });
+        }
+
+        let mut resolved = Vec::new();
+        for param in params {
+            let ty = self.resolve(param.base, &param.binding)?;
+            if ty == CType::Void {
+                let name = param.binding.name();
+                let name = name.map_or("<anonymous>".into(), |n| n.to_string());
+                return Err(
+                    self.error(format!("parameter `{name}` has void type"))
+                );
+            }
+            resolved.push(ty.decay());
+        }
+        Ok(FnType {
+            ret,
+            params: resolved,
+            variadic,
+            prototype: !params.is_empty(),
+        })
+    }
+
+    /// Whether a type is complete (i.e. its size is known).
+    pub fn is_complete(&self, ty: &CType) -> bool {
+        match ty {
+            CType::Void | CType::Fn(_) => false,
+            CType::Array { elem, len } => {
+                len.is_some() && self.is_complete(elem)
+            }
+            CType::Struct(id) => self.types.get(*id).fields.is_some(),
+            _ => true,
+        }
+    }
+
+    /// Evaluate an integer constant expression.
+    pub fn const_int(&mut self, expr: &Expr) -> Option<i64> {
+        match expr {
+            Expr::Lit(Lit::Int { value, .. }) => Some(*value as i64),
+            Expr::Lit(Lit::Char(c)) => Some(*c as i8 as i64),
+            Expr::Bin(BinOp::And, lhs, rhs) => {
+                let lhs = self.const_int(lhs)?;
+                Some((lhs != 0 && self.const_int(rhs)? != 0) as i64)
+            }
+            Expr::Bin(BinOp::Or, lhs, rhs) => {
+                let lhs = self.const_int(lhs)?;
+                Some((lhs != 0 || self.const_int(rhs)? != 0) as i64)
+            }
+            Expr::Bin(op, lhs, rhs) => {
+                let lhs = self.const_int(lhs)?;
+                let rhs = self.const_int(rhs)?;
+                match op {
+                    BinOp::Add => lhs.checked_add(rhs),
+                    BinOp::Sub => lhs.checked_sub(rhs),
+                    BinOp::Mul => lhs.checked_mul(rhs),
+                    BinOp::Div => lhs.checked_div(rhs),
+                    BinOp::Rem => lhs.checked_rem(rhs),
+                    BinOp::Shl => lhs.checked_shl(rhs.try_into().ok()?),
+                    BinOp::Shr => lhs.checked_shr(rhs.try_into().ok()?),
+                    BinOp::BitAnd => Some(lhs & rhs),
+                    BinOp::BitOr => Some(lhs | rhs),
+                    BinOp::BitXor => Some(lhs ^ rhs),
+                    BinOp::Eq => Some((lhs == rhs) as i64),
+                    BinOp::Ne => Some((lhs != rhs) as i64),
+                    BinOp::Lt => Some((lhs < rhs) as i64),
+                    BinOp::Gt => Some((lhs > rhs) as i64),
+                    BinOp::Le => Some((lhs <= rhs) as i64),
+                    BinOp::Ge => Some((lhs >= rhs) as i64),
+                    _ => None,
+                }
+            }
+            Expr::Una(op, e) => {
+                let e = self.const_int(e)?;
+                match op {
+                    UnaOp::Neg => e.checked_neg(),
+                    UnaOp::Not => Some((e == 0) as i64),
+                    UnaOp::Inv => Some(!e),
+                    UnaOp::Plus => Some(e),
+                    _ => None,
+                }
+            }
+            Expr::Cond(cond, then, r#else) => {
+                if self.const_int(cond)? != 0 {
+                    self.const_int(then)
+                } else {
+                    self.const_int(r#else)
+                }
+            }
+            Expr::Cast(_, e) => self.const_int(e),
+            Expr::Sizeof(e) => {
+                let ty = self.expr(e).ok()?;
+                Some(self.types.size_of(&ty) as i64)
+            }
+            Expr::SizeofType(ty) => {
+                let ty = self.type_name(ty).ok()?;
+                Some(self.types.size_of(&ty) as i64)
+            }
+            Expr::Alignof(ty) => {
+                let ty = self.type_name(ty).ok()?;
+                Some(self.types.align_of(&ty) as i64)
+            }
+            _ => None,
+        }
+    }
+}
+
+//--- Checking statements
+
+impl Checker {
+    /// Check a statement.
+    fn stmt(&mut self, stmt: &Spanned<Stmt>) {
+        let outer = self.span.replace(stmt.span);
+        self.stmt_node(stmt);
+        self.span = outer;
+    }
+
+    /// Check a statement, without changing the span errors are reported at.
+    fn stmt_node(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Empty => {}
+            Stmt::Goto(label) => self.gotos.push(*label),
+            Stmt::Label(label, stmt) => {
+                if !self.labels.insert(*label) {
+                    let error =
+                        self.error(format!("duplicate label `{label}`"));
+                    self.errors.push(error);
+                }
+                self.stmt(stmt);
+            }
+            Stmt::Switch { cond, body } => {
+                let result = self.rvalue(cond).and_then(|ty| {
+                    if ty.is_integer() {
+                        Ok(())
+                    } else {
+                        Err(self.error(format!(
+                            "switch on {ty}",
+                            ty = self.types.named(&ty)
+                        )))
+                    }
+                });
+                self.report(result);
+                self.switches.push(SwitchCases::default());
+                self.stmt(body);
+                self.switches.pop();
+            }
+            Stmt::Case(value, stmt) => {
+                let result = self.case(value);
+                self.report(result);
+                self.stmt(stmt);
+            }
+            Stmt::Default(stmt) => {
+                let error = match self.switches.last_mut() {
+                    Some(cases) if cases.default => {
+                        Some("duplicate `default` label")
+                    }
+                    Some(cases) => {
+                        cases.default = true;
+                        None
+                    }
+                    None => Some("`default` label not in a switch"),
+                };
+                if let Some(error) = error {
+                    self.errors.push(self.error(error.into()));
+                }
+                self.stmt(stmt);
+            }
+            Stmt::Block { stmts } => {
+                self.push_scope();
+                for stmt in *stmts {
+                    self.stmt(stmt);
+                }
+                self.pop_scope();
+            }
+            Stmt::VarDefn(defn) => {
+                let result = self.var_defn(defn);
+                self.report(result);
+            }
+            Stmt::Type(defn) => {
+                let result = self.type_defn(defn);
+                self.report(result);
+            }
+            Stmt::Expr(expr) => {
+                let result = self.expr(expr);
+                self.report(result);
+            }
+            Stmt::If { cond, then, r#else } => {
+                self.cond(cond);
+                self.stmt(then);
+                if let Some(r#else) = r#else {
+                    self.stmt(r#else);
+                }
+            }
+            Stmt::For {
+                init,
+                cond,
+                step,
+                body,
+            } => {
+                if let Some(init) = init {
+                    let result = self.expr(init);
+                    self.report(result);
+                }
+                if let Some(cond) = cond {
+                    self.cond(cond);
+                }
+                if let Some(step) = step {
+                    let result = self.expr(step);
+                    self.report(result);
+                }
+                self.loop_body(body);
+            }
+            Stmt::While { cond, body } | Stmt::DoWhile { body, cond } => {
+                self.cond(cond);
+                self.loop_body(body);
+            }
+            Stmt::Break if !self.switches.is_empty() => {}
+            Stmt::Break | Stmt::Continue => {
+                if self.loops == 0 {
+                    let keyword = match stmt {
+                        Stmt::Break => "break",
+                        _ => "continue",
+                    };
+                    let message =
+                        format!("`{keyword}` statement not in a loop");
+                    self.errors.push(self.error(message));
+                }
+            }
+            Stmt::Return(expr) => {
+                let result = self.ret(expr.as_ref());
+                self.report(result);
+            }
+        }
+    }
+
+    /// Check a `case` label.
+    fn case(&mut self, value: &Spanned<Expr>) -> Result<(), TypeError> {
+        self.rvalue(value)?;
+        let Some(value) = self.const_int(value) else {
+            return Err(self.error("case label is not a constant".into()));
+        };
+        match self
+            .switches
+            .last_mut()
+            .map(|cases| cases.values.insert(value))
+        {
+            Some(true) => Ok(()),
+            Some(false) => {
+                Err(self.error(format!("duplicate case value {value}")))
+            }
+            None => Err(self.error("`case` label not in a switch".into())),
+        }
+    }
+
+    /// Check the body of a loop.
+    fn loop_body(&mut self, body: &Spanned<Stmt>) {
+        self.loops += 1;
+        self.stmt(body);
+        self.loops -= 1;
+    }
+
+    /// Check a controlling expression.
+    fn cond(&mut self, cond: &Spanned<Expr>) {
+        let result = self.rvalue(cond).and_then(|ty| {
+            if ty.is_scalar() {
+                Ok(())
+            } else {
+                Err(self.error(format!(
+                    "{ty} used as a condition",
+                    ty = self.types.named(&ty)
+                )))
+            }
+        });
+        self.report(result);
+    }
+
+    /// Check a return statement.
+    fn ret(&mut self, expr: Option<&Spanned<Expr>>) -> Result<(), TypeError> {
+        match expr {
+            Some(expr) if self.ret == CType::Void => {
+                self.rvalue(expr)?;
+                Err(self.error("void function returns a value".into()))
+            }
+            Some(expr) => {
+                let ret = self.ret.clone();
+                self.assign(&ret, expr)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Check an initializer for an object of the given type.
+    ///
+    /// Returns the type of the object, with the length of an array of
+    /// unknown size filled in.
+    pub fn initializer(
+        &mut self,
+        ty: &CType,
+        init: &Initializer,
+    ) -> Result<CType, TypeError> {
+        match (ty, init) {
+            // A string literal can initialize a character array.
+            (
+                CType::Array { elem, len },
+                Initializer::Expr(Spanned {
+                    node: Expr::Lit(Lit::Str(s)),
+                    ..
+                }),
+            ) if matches!(
+                **elem,
+                CType::Int(IntType {
+                    rank: IntRank::Char,
+                    ..
+                })
+            ) =>
+            {
+                Ok(CType::Array {
+                    elem: elem.clone(),
+                    len: len.or(Some(s.len() as u64 + 1)),
+                })
+            }
+            (CType::Array { elem, len }, Initializer::List(items)) => {
+                let mut next = 0;
+                let mut max = 0;
+                for item in *items {
+                    match item.designators.first() {
+                        Some(Designator::Index(index)) => {
+                            next = match self.const_int(index) {
+                                Some(index) if index >= 0 => index as u64,
+                                _ => {
+                                    return Err(self.error(
+                                        "array designator is not a \
+                                         non-negative constant"
+                                            .into(),
+                                    ));
+                                }
+                            };
+                        }
+                        Some(Designator::Member(name)) => {
+                            return Err(self.error(format!(
+                                "member designator `.{name}` for an array"
+                            )));
+                        }
+                        None => {}
+                    }
+                    if len.is_some_and(|len| next >= len) {
+                        return Err(self.error(
+                            "excess elements in array initializer".into(),
+                        ));
+                    }
+                    self.designated(elem, item.designators, &item.init)?;
+                    next += 1;
+                    max = max.max(next);
+                }
+                Ok(CType::Array {
+                    elem: elem.clone(),
+                    len: len.or(Some(max)),
+                })
+            }
+            (CType::Struct(id), Initializer::List(items)) => {
+                let Some(fields) = self.types.get(*id).fields.clone() else {
+                    return Err(self
+                        .error("initializer for an incomplete struct".into()));
+                };
+                let mut next = 0;
+                for item in *items {
+                    match item.designators.first() {
+                        Some(Designator::Member(name)) => {
+                            let name = &name.to_string();
+                            let Some((index, _)) = self.types.field(*id, name)
+                            else {
+                                return Err(self.no_member(*id, name));
+                            };
+                            next = index;
+                        }
+                        Some(Designator::Index(_)) => {
+                            return Err(self.error(
+                                "array designator for a struct".into(),
+                            ));
+                        }
+                        None => {}
+                    }
+                    let Some(field) = fields.get(next) else {
+                        return Err(self.error(
+                            "excess elements in struct initializer".into(),
+                        ));
+                    };
+                    self.designated(&field.ty, item.designators, &item.init)?;
+                    next += 1;
+                }
+                Ok(ty.clone())
+            }
+            // A scalar may be initialized by a braced expression.
+            (_, Initializer::List(items)) if ty.is_scalar() => match &items[..]
+            {
+                [item] if item.designators.is_empty() => {
+                    self.initializer(ty, &item.init)
+                }
+                _ => Err(self.error(format!(
+                    "invalid initializer for {ty}",
+                    ty = self.types.named(ty)
+                ))),
+            },
+            (_, Initializer::List(_)) => Err(self.error(format!(
+                "invalid initializer for {ty}",
+                ty = self.types.named(ty)
+            ))),
+            (_, Initializer::Expr(expr)) => {
+                self.assign(ty, expr)?;
+                Ok(ty.clone())
+            }
+        }
+    }
+
+    /// Check a designated subobject initializer.
+    ///
+    /// The first designator has already selected `ty`; the rest are applied
+    /// here.
+    fn designated(
+        &mut self,
+        ty: &CType,
+        designators: &[Designator],
+        init: &Initializer,
+    ) -> Result<(), TypeError> {
+        match designators.get(1..).unwrap_or_default() {
+            [] => self.initializer(ty, init).map(drop),
+            rest => {
+                // Rebuild the remaining designators as a one-element list.
+                let item = InitItem {
+                    designators: rest,
+                    init: *init,
+                };
+                let items = std::slice::from_ref(&item);
+                self.initializer(ty, &Initializer::List(items)).map(drop)
+            }
+        }
+    }
+}
+
+//--- Checking expressions
+
+impl Checker {
+    /// Determine the type of an expression.
+    ///
+    /// Array and function types are returned as-is; see [`Self::rvalue()`].
+    pub fn expr(&mut self, expr: &Spanned<Expr>) -> Result<CType, TypeError> {
+        let outer = self.span.replace(expr.span);
+        let result = self.expr_node(expr);
+        self.span = outer;
+        result
+    }
+
+    /// Determine the type of an expression, without changing the span errors
+    /// are reported at.
+    fn expr_node(&mut self, expr: &Expr) -> Result<CType, TypeError> {
+        match expr {
+            Expr::Debug => Ok(CType::INT),
+            Expr::Ref(e) => {
+                let ty = self.expr(e)?;
+                if !Self::is_lvalue(e) && !matches!(ty, CType::Fn(_)) {
+                    return Err(self
+                        .error(format!("cannot take the address of `{e:?}`")));
+                }
+                Ok(ty.pointer_to())
+            }
+            Expr::Bin(BinOp::Assign, lhs, rhs) => {
+                let ty = self.modifiable(lhs)?;
+                self.assign(&ty, rhs)?;
+                Ok(ty)
+            }
+            Expr::Bin(op, lhs, rhs) => {
+                let Some(op) = op.compound() else {
+                    let lhs = self.rvalue(lhs)?;
+                    let rhs = self.rvalue(rhs)?;
+                    return self.bin(*op, lhs, rhs);
+                };
+                let ty = self.modifiable(lhs)?;
+                let rhs = self.rvalue(rhs)?;
+                self.bin(op, ty.clone(), rhs)?;
+                Ok(ty)
+            }
+            Expr::Una(op @ (UnaOp::PreInc | UnaOp::PreDec), e) => {
+                let ty = self.modifiable(e)?;
+                if !ty.is_scalar() {
+                    return Err(self.error(format!(
+                        "invalid operand {ty} to unary {op:?}",
+                        ty = self.types.named(&ty)
+                    )));
+                }
+                Ok(ty)
+            }
+            Expr::Una(op, e) => {
+                let ty = self.rvalue(e)?;
+                match op {
+                    UnaOp::Neg | UnaOp::Plus if ty.is_arithmetic() => {
+                        Ok(ty.promote())
+                    }
+                    UnaOp::Inv if ty.is_integer() => Ok(ty.promote()),
+                    UnaOp::Not if ty.is_scalar() => Ok(CType::INT),
+                    UnaOp::Deref => match ty.pointee() {
+                        Some(CType::Void) | None => Err(self.error(format!(
+                            "cannot dereference {ty}",
+                            ty = self.types.named(&ty)
+                        ))),
+                        Some(pointee) => Ok(pointee.clone()),
+                    },
+                    _ => Err(self.error(format!(
+                        "invalid operand {ty} to unary {op:?}",
+                        ty = self.types.named(&ty)
+                    ))),
+                }
+            }
+            Expr::Ident(name) => match self.lookup(*name) {
+                Some(ty) => Ok(ty.clone()),
+                None => {
+                    Err(self.error(format!("undeclared identifier `{name}`")))
+                }
+            },
+            Expr::Lit(lit) => self.lit(lit),
+            Expr::Cast(ty, e) => {
+                let to = self.type_name(ty)?;
+                let from = self.rvalue(e)?;
+                let valid = to == CType::Void
+                    || (to.is_scalar() && from.is_scalar())
+                        && !(to.is_pointer()
+                            && matches!(from, CType::Float(_)))
+                        && !(from.is_pointer()
+                            && matches!(to, CType::Float(_)));
+                if !valid {
+                    return Err(self.error(format!(
+                        "invalid cast from {from} to {to}",
+                        from = self.types.named(&from),
+                        to = self.types.named(&to)
+                    )));
+                }
+                Ok(to)
+            }
+            Expr::Sizeof(e) => {
+                let ty = self.expr(e)?;
+                self.sizeof(&ty)
+            }
+            Expr::SizeofType(ty) | Expr::Alignof(ty) => {
+                let ty = self.type_name(ty)?;
+                self.sizeof(&ty)
+            }
+            Expr::CompoundLit(ty, items) => {
+                let ty = self.type_name(ty)?;
+                self.initializer(&ty, &Initializer::List(items))
+            }
+            Expr::Call(callee, args) => self.call(callee, args),
+            Expr::Member(e, name) => {
+                let ty = self.expr(e)?;
+                self.member(&ty, &name.to_string())
+            }
+            Expr::Arrow(e, name) => {
+                let ty = self.rvalue(e)?;
+                match ty.pointee() {
+                    Some(pointee) => {
+                        self.member(&pointee.clone(), &name.to_string())
+                    }
+                    None => Err(self.error(format!(
+                        "member access `->{name}` on non-pointer {ty}",
+                        ty = self.types.named(&ty)
+                    ))),
+                }
+            }
+            Expr::Index(e, index) => {
+                let lhs = self.rvalue(e)?;
+                let rhs = self.rvalue(index)?;
+                match (&lhs, &rhs) {
+                    (CType::Pointer(elem), int)
+                    | (int, CType::Pointer(elem))
+                        if int.is_integer() =>
+                    {
+                        Ok((**elem).clone())
+                    }
+                    _ => Err(self.error(format!(
+                        "cannot subscript {lhs} with {rhs}",
+                        lhs = self.types.named(&lhs),
+                        rhs = self.types.named(&rhs)
+                    ))),
+                }
+            }
+            Expr::PostInc(e) | Expr::PostDec(e) => {
+                let ty = self.modifiable(e)?;
+                if !ty.is_scalar() {
+                    return Err(self.error(format!(
+                        "cannot increment or decrement `{e:?}`"
+                    )));
+                }
+                Ok(ty)
+            }
+            Expr::Cond(cond, then, r#else) => {
+                self.cond(cond);
+                let then_ty = self.rvalue(then)?;
+                let else_ty = self.rvalue(r#else)?;
+                let is_null = |e: &Expr| {
+                    matches!(e, Expr::Lit(Lit::Int { value: 0, .. }))
+                };
+                match (&then_ty, &else_ty) {
+                    (a, b) if a.is_arithmetic() && b.is_arithmetic() => {
+                        Ok(self.types.common(a, b))
+                    }
+                    (a, b) if a == b => Ok(then_ty),
+                    (CType::Pointer(a), CType::Pointer(b)) => {
+                        if **a == CType::Void || **b == CType::Void {
+                            Ok(CType::Void.pointer_to())
+                        } else {
+                            Ok(then_ty)
+                        }
+                    }
+                    (CType::Pointer(_), _) if is_null(r#else) => Ok(then_ty),
+                    (_, CType::Pointer(_)) if is_null(then) => Ok(else_ty),
+                    _ => Err(self.error(format!(
+                        "mismatched types {then_ty} and {else_ty} in \
+                         conditional expression",
+                        then_ty = self.types.named(&then_ty),
+                        else_ty = self.types.named(&else_ty)
+                    ))),
+                }
+            }
+            Expr::VaStart(ap, last) => {
+                // Later passes walk function bodies without checking the
+                // functions as a whole, and rely on this check having passed.
+                if self.function.is_some() && !self.variadic {
+                    return Err(self.error(
+                        "`va_start` outside a variadic function".into(),
+                    ));
+                }
+                self.va_list_operand(ap)?;
+                self.rvalue(last)?;
+                Ok(CType::Void)
+            }
+            Expr::VaArg(ap, ty) => {
+                self.va_list_operand(ap)?;
+                let ty = self.type_name(ty)?;
+                // Arguments of type `float` are passed as `double`.
+                if !ty.is_scalar() || ty == CType::Float(FloatType::Float) {
+                    return Err(self.error(format!(
+                        "`va_arg` of {ty} is not supported",
+                        ty = self.types.named(&ty)
+                    )));
+                }
+                Ok(ty)
+            }
+            Expr::VaEnd(ap) => {
+                self.va_list_operand(ap)?;
+                Ok(CType::Void)
+            }
+            Expr::VaCopy(dest, src) => {
+                self.va_list_operand(dest)?;
+                self.va_list_operand(src)?;
+                Ok(CType::Void)
+            }
+        }
+    }
+
+    /// Determine the type of an expression used as a value.
+    ///
+    /// This applies array-to-pointer and function-to-pointer decay.
+    pub fn rvalue(&mut self, expr: &Spanned<Expr>) -> Result<CType, TypeError> {
+        Ok(self.expr(expr)?.decay())
+    }
+
+    /// Check an operand of a `va_*` builtin, which must be a `va_list`.
+    ///
+    /// A `va_list` parameter has decayed to a pointer if `va_list` is an
+    /// array; otherwise, the operand must designate the object itself.
+    fn va_list_operand(&mut self, ap: &Spanned<Expr>) -> Result<(), TypeError> {
+        let ty = self.expr(ap)?;
+        let va_list = self.va_list();
+        let is_array = matches!(va_list, CType::Array { .. });
+        if ty.decay() != va_list.decay() || !(is_array || Self::is_lvalue(ap)) {
+            return Err(self.error(format!("`{ap:?}` is not a `va_list`")));
+        }
+        Ok(())
+    }
+
+    /// Determine the type of an expression that must be a modifiable lvalue.
+    fn modifiable(&mut self, expr: &Spanned<Expr>) -> Result<CType, TypeError> {
+        let ty = self.expr(expr)?;
+        if !Self::is_lvalue(expr)
+            || matches!(ty, CType::Array { .. } | CType::Fn(_))
+        {
+            return Err(self.error(format!("`{expr:?}` is not assignable")));
+        }
+        Ok(ty)
+    }
+
+    /// Whether an expression designates an object.
+    pub fn is_lvalue(expr: &Expr) -> bool {
+        matches!(
+            expr,
+            Expr::Ident(_)
+                | Expr::Una(UnaOp::Deref, _)
+                | Expr::Member(..)
+                | Expr::Arrow(..)
+                | Expr::Index(..)
+                | Expr::CompoundLit(..)
+                | Expr::Lit(Lit::Str(_))
+        )
+    }
+
+    /// Determine the type of a literal.
+    ///
+    /// An integer constant has the first type in its list (C11 6.4.4.1) that
+    /// can represent its value.  Unsuffixed octal and hexadecimal constants
+    /// may also be unsigned, decimal ones may not.
+    pub fn lit(&self, lit: &Lit) -> Result<CType, TypeError> {
+        match *lit {
+            Lit::Int {
+                value,
+                radix,
+                unsigned,
+                long,
+            } => {
+                let ranks: &[IntRank] = match long {
+                    0 => &[IntRank::Int, IntRank::Long, IntRank::LongLong],
+                    1 => &[IntRank::Long, IntRank::LongLong],
+                    _ => &[IntRank::LongLong],
+                };
+                let signs: &[bool] = if unsigned {
+                    &[false]
+                } else if radix == Radix::Decimal {
+                    &[true]
+                } else {
+                    &[true, false]
+                };
+                ranks
+                    .iter()
+                    .flat_map(|&rank| {
+                        signs
+                            .iter()
+                            .map(move |&signed| IntType { rank, signed })
+                    })
+                    .map(CType::Int)
+                    .find(|ty| {
+                        let bits = 8 * self.types.size_of(ty) as u32;
+                        let signed =
+                            matches!(ty, CType::Int(int) if int.signed);
+                        value <= u64::MAX >> (64 - bits + signed as u32)
+                    })
+                    .ok_or_else(|| {
+                        self.error(format!(
+                            "integer constant {lit} is too large for its type"
+                        ))
+                    })
+            }
+            Lit::Float { long: true, .. } => Err(self
+                .error(format!("long double constant {lit} is not supported"))),
+            Lit::Float { single: true, .. } => {
+                Ok(CType::Float(FloatType::Float))
+            }
+            Lit::Float { .. } => Ok(CType::DOUBLE),
+            Lit::Char(_) => Ok(CType::INT),
+            Lit::Str(s) => Ok(CType::Array {
+                elem: Box::new(CType::CHAR),
+                len: Some(s.len() as u64 + 1),
+            }),
+        }
+    }
+
+    /// Determine the type of a binary operation on rvalues.
+    ///
+    /// Assignment operators are not handled here.
+    pub fn bin(
+        &self,
+        op: BinOp,
+        lhs: CType,
+        rhs: CType,
+    ) -> Result<CType, TypeError> {
+        let arithmetic = lhs.is_arithmetic() && rhs.is_arithmetic();
+        let integer = lhs.is_integer() && rhs.is_integer();
+        let result = match (op, &lhs, &rhs) {
+            (BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div, l, r)
+                if arithmetic =>
+            {
+                Some(self.types.common(l, r))
+            }
+            (
+                BinOp::Rem | BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor,
+                l,
+                r,
+            ) if integer => Some(self.types.common(l, r)),
+            (BinOp::Shl | BinOp::Shr, l, _) if integer => {
+                Some(l.clone().promote())
+            }
+            (BinOp::Add | BinOp::Sub, CType::Pointer(_), int)
+                if int.is_integer() =>
+            {
+                Some(lhs.clone())
+            }
+            (BinOp::Add, int, CType::Pointer(_)) if int.is_integer() => {
+                Some(rhs.clone())
+            }
+            (BinOp::Sub, CType::Pointer(a), CType::Pointer(b)) if a == b => {
+                Some(CType::PTRDIFF_T)
+            }
+            (BinOp::And | BinOp::Or, l, r)
+                if l.is_scalar() && r.is_scalar() =>
+            {
+                Some(CType::INT)
+            }
+            (op, l, r) if op.is_comparison() => {
+                let pointers = l.is_pointer() && r.is_pointer();
+                // Pointers may be compared for equality with null constants.
+                let null = matches!(op, BinOp::Eq | BinOp::Ne)
+                    && ((l.is_pointer() && r.is_integer())
+                        || (l.is_integer() && r.is_pointer()));
+                (arithmetic || pointers || null).then_some(CType::INT)
+            }
+            (BinOp::Comma, _, _) => Some(rhs.clone()),
+            _ => None,
+        };
+        result.ok_or_else(|| {
+            self.error(format!(
+                "invalid operands {lhs} and {rhs} to binary {op:?}",
+                lhs = self.types.named(&lhs),
+                rhs = self.types.named(&rhs)
+            ))
+        })
+    }
+
+    /// Determine the type of a `sizeof` expression.
+    fn sizeof(&self, ty: &CType) -> Result<CType, TypeError> {
+        if self.is_complete(ty) {
+            Ok(CType::SIZE_T)
+        } else {
+            Err(self.error(format!(
+                "size of incomplete type {ty}",
+                ty = self.types.named(ty)
+            )))
+        }
+    }
+
+    /// Determine the type of a function call.
+    fn call(
+        &mut self,
+        callee: &Spanned<Expr>,
+        args: &[Spanned<Expr>],
+    ) -> Result<CType, TypeError> {
+        let ty = self.rvalue(callee)?;
+        let Some(fn_type) = ty.fn_type().cloned() else {
+            return Err(self.error(format!(
+                "called object `{callee:?}` has type {ty}",
+                ty = self.types.named(&ty)
+            )));
+        };
+
+        let arity = fn_type.params.len();
+        let fixed = !fn_type.variadic && fn_type.prototype;
+        if args.len() < arity || (args.len() > arity && fixed) {
+            return Err(self.error(format!(
+                "`{callee:?}` takes {arity} arguments but {} were given",
+                args.len()
+            )));
+        }
+        for (i, arg) in args.iter().enumerate() {
+            match fn_type.params.get(i) {
+                Some(param) => self.assign(param, arg)?,
+                None => {
+                    let ty = self.rvalue(arg)?;
+                    if !ty.is_scalar() && !matches!(ty, CType::Struct(_)) {
+                        return Err(self.error(format!(
+                            "invalid variadic argument of type {ty}",
+                            ty = self.types.named(&ty)
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(fn_type.ret)
+    }
+
+    /// Determine the type of a struct member.
+    fn member(&self, ty: &CType, name: &str) -> Result<CType, TypeError> {
+        let CType::Struct(id) = *ty else {
+            return Err(self.error(format!(
+                "member access `.{name}` on non-struct {ty}",
+                ty = self.types.named(ty)
+            )));
+        };
+        match self.types.field(id, name) {
+            Some((_, field)) => Ok(field.ty.clone()),
+            None => Err(self.no_member(id, name)),
+        }
+    }
+
+    /// Construct an error about a missing struct member.
+    fn no_member(&self, id: StructId, name: &str) -> TypeError {
+        let def = self.types.get(id);
+        let tag = def.name.as_deref().unwrap_or("<anonymous>");
+        match def.fields {
+            Some(_) => self
+                .error(format!("no member named `{name}` in `struct {tag}`")),
+            None => self.error(format!("`struct {tag}` is incomplete")),
+        }
+    }
+
+    /// Check that an expression can be assigned to an object of a type.
+    pub fn assign(
+        &mut self,
+        to: &CType,
+        expr: &Spanned<Expr>,
+    ) -> Result<(), TypeError> {
+        let from = self.rvalue(expr)?;
+        let null = matches!(**expr, Expr::Lit(Lit::Int { value: 0, .. }));
+        let valid = match (to, &from) {
+            (to, from) if to.is_arithmetic() && from.is_arithmetic() => true,
+            (CType::Pointer(_), CType::Pointer(_)) => true,
+            (CType::Pointer(_), from) if from.is_integer() => null,
+            (
+                CType::Int(IntType {
+                    rank: IntRank::Bool,
+                    ..
+                }),
+                from,
+            ) => from.is_scalar(),
+            (CType::Struct(a), CType::Struct(b)) => a == b,
+            _ => false,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(self.error(format!(
+                "cannot assign {from} to an object of type {to}",
+                from = self.types.named(&from),
+                to = self.types.named(to)
+            )))
+        }
+    }
+}
+
+//----------- TypeError --------------------------------------------------------
+
+/// A type checking failure.
+#[derive(Clone, Debug)]
+pub struct TypeError {
+    /// The function the error occurred in, if any.
+    pub function: Option<String>,
+
+    /// The source text the error is about, if known.
+    pub span: Option<Span>,
+
+    /// A description of the error.
+    pub message: String,
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.function {
+            Some(function) => write!(f, "in `{}`: {}", function, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Error for TypeError {}
@@ -0,0 +1,9 @@
+typedef struct node {
+    int value;
+    struct node *next;
+} node;
+
+static int table[3] = { 1, [2] = 3 }, *cursor;
+int (*handler)(int, char *, ...);
+
+extern int printf(char *format, ...);
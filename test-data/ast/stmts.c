@@ -0,0 +1,21 @@
+int sum(int *values, long n) {
+    long i;
+    int total = 0;
+    for (i = 0; i < n; i++)
+        total += values[i];
+    while (total > 100) {
+        if (total & 1) total--; else break;
+    }
+    do { total <<= 1; } while (!total);
+    switch (n) {
+    case 0: return -1;
+    default: ;
+    }
+    goto out;
+out:
+    return total ? total : (int) sizeof(long);
+}
+
+double scale(struct point { int x; } *p, double f) {
+    return p->x * f + 1.5f + 'a' + "x\ty\"z\n"[0] + ~0ul;
+}
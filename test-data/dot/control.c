@@ -0,0 +1,42 @@
+int puts(char *s);
+int abs2(int x);
+
+int fact(int n) {
+    int r = 1;
+    while (n > 1) {
+        r *= n;
+        n--;
+    }
+    return r;
+}
+
+int classify(int c) {
+    switch (c) {
+    case 0:
+        return 10;
+    case 1:
+    case 2:
+        c++;
+        break;
+    default:
+        puts("other");
+    }
+    int i;
+    for (i = 0; i < 3; i++) {
+        if (i == c)
+            continue;
+        c += fact(i);
+    }
+    do {
+        c--;
+        if (c < 0)
+            goto out;
+    } while (c > 5);
+out:
+    return c;
+}
+
+int main(void) {
+    int (*f)(int) = fact;
+    return classify(fact(3)) + f(2);
+}
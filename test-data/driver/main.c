@@ -0,0 +1,9 @@
+/* Prints twice `VALUE`, and `LIMIT`, which defaults to 7. */
+#include "twice.h"
+
+int printf(char *fmt, ...);
+
+int main(void) {
+    printf("%d %d\n", twice(VALUE), LIMIT);
+    return 0;
+}
@@ -0,0 +1,5 @@
+int twice(int x);
+
+#ifndef LIMIT
+#define LIMIT 7
+#endif
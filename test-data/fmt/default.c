@@ -0,0 +1,39 @@
+#define LIMIT 10
+
+int printf(char *format, ...);
+
+/* A counter. */
+int   count ; // how many
+int *p,q;
+// Twice a number.
+int twice(int x){return 2*x;}
+int thrice(int x) {
+    /* Three times. */
+    return 3*x;   // not 2
+  // Done.
+}
+long masks[] = { 0x1F, 0XffU, 1000000L, 017 , 0b101 };
+double scales[] = { 1e-3, .5F, 2.50, 0x1p-2 };
+char *greeting = "h\351llo" "\x41", *accent = "héllo";
+#ifdef LIMIT
+int limited(int x) {
+#if LIMIT
+    return x < LIMIT ? x :( LIMIT );
+#endif
+}
+#endif
+int sign(int x) {
+    if (x < 0) // negative
+        return -1;
+    else /* not negative */ {
+        return x > 0 ? (1) : 0; /* zero */
+    }
+    /* unreachable */
+}
+static char* names[ 2 ] = { "a","b" };
+int report(int first, int second, int third, int fourth, int fifth, int sixth);
+int main(void){ if(count>LIMIT) printf("%d\n",twice(count)); else {count++;}
+  report(count * 1000000, count * 2000000, count * 3000000, count * 4000000, 0, 0);
+  do count--; while (count > 0 && count < LIMIT * 1000 || count == LIMIT * 2000000);
+  return 0; }
+// The end.
@@ -0,0 +1,10 @@
+struct point { int x; int y; };
+int printf(char *format, ...);
+int sum(int *values, int n) {
+    int total = 0, i;
+    for (i = 0; i < n; i++) { if (values[i] > 0) total += values[i]; else total -= values[i]; }
+    do { total >>= 1; } while (total > 1000);
+    printf("the total of %d values is %d\n", n, total + values[0] * values[n - 1]);
+    return total;
+}
+int wide(struct point *p, struct point *q) { return p->x - p->y > q->x - q->y ? p->x : q->y; }
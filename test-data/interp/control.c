@@ -0,0 +1,96 @@
+int printf(char *fmt, ...);
+
+int counter() {
+    static int count = 10;
+    return count++;
+}
+
+int twice(int (*f)(int), int x) {
+    return f(f(x));
+}
+
+int inc(int x) {
+    return x + 1;
+}
+
+int classify(int x) {
+    int result = 0;
+    switch (x) {
+    case 0:
+        result = 100;
+    case 1:
+        result += 1;
+        break;
+    case 2: {
+        int i;
+        for (i = 0; i < 3; i++) {
+            if (i == 1)
+                continue;
+            result += 10;
+        }
+        break;
+    }
+    default:
+        result = -1;
+    }
+    return result;
+}
+
+int duff(int n) {
+    int total = 0;
+    int k = (n + 3) / 4;
+    switch (n % 4) {
+    case 0:
+        do {
+            total++;
+        case 3:
+            total++;
+        case 2:
+            total++;
+        case 1:
+            total++;
+        } while (--k > 0);
+    }
+    return total;
+}
+
+int skip() {
+    int x = 1;
+    goto inside;
+    while (x < 100) {
+        x *= 2;
+    inside:
+        x += 3;
+    }
+    return x;
+}
+
+int main() {
+    int i = 0;
+    int j;
+
+again:
+    i++;
+    if (i < 5)
+        goto again;
+    printf("i=%d\n", i);
+
+    for (j = 0; j < 4; j++)
+        printf("classify(%d)=%d\n", j, classify(j));
+
+    j = 0;
+    do {
+        j += 3;
+        if (j > 10)
+            goto done;
+    } while (1);
+done:
+    printf("j=%d\n", j);
+
+    counter();
+    printf("%d\n", counter());
+    printf("%d\n", twice(inc, 40));
+    printf("duff(7)=%d skip()=%d\n", duff(7), skip());
+    printf("%d %d\n", (char)300, (short)-65535);
+    return i + j;
+}
@@ -0,0 +1,44 @@
+void *malloc(long n);
+void free(void *p);
+void *memcpy(void *d, void *s, long n);
+long strlen(char *s);
+void exit(int code);
+int printf(char *fmt, ...);
+int puts(char *s);
+int putchar(int c);
+
+struct node {
+    int value;
+    struct node *next;
+};
+
+int main(int argc, char **argv) {
+    struct node *head = 0;
+    struct node *n;
+    char buf[16];
+    int i;
+
+    for (i = 0; i < 4; i++) {
+        n = malloc(sizeof(struct node));
+        n->value = i * i;
+        n->next = head;
+        head = n;
+    }
+    for (n = head; n; n = n->next)
+        printf("%d ", n->value);
+    putchar('\n');
+    while (head) {
+        n = head->next;
+        free(head);
+        head = n;
+    }
+
+    memcpy(buf, "copied", 7);
+    puts(buf);
+    printf("argc=%d len=%ld\n", argc, strlen(buf));
+    printf("[%5d|%-5d|%05d|%x|%#o|%+.2f|%e|%g|%c|%.3s|%%]\n", 42, 42, -42,
+           255, 8, 3.14159, 1234.5, 0.0001, 'z', "abcdef");
+    exit(3);
+    puts("not reached");
+    return 0;
+}
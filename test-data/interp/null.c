@@ -0,0 +1,12 @@
+struct point {
+    int x;
+    int y;
+};
+
+int get_y(struct point *p) {
+    return p->y;
+}
+
+int main() {
+    return get_y(0);
+}
@@ -0,0 +1,15 @@
+int printf(char *fmt, ...);
+
+int sum(int *a, int n) {
+    int total = 0;
+    int i;
+    for (i = 0; i <= n; i++)
+        total += a[i];
+    return total;
+}
+
+int main() {
+    int a[4] = {1, 2, 3, 4};
+    printf("sum: %d\n", sum(a, 4));
+    return 0;
+}
@@ -0,0 +1,11 @@
+void *malloc(long n);
+void free(void *p);
+int printf(char *fmt, ...);
+
+int main() {
+    int *p = malloc(sizeof(int));
+    *p = 1;
+    printf("before: %d\n", *p);
+    free(p);
+    return *p;
+}
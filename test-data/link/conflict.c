@@ -0,0 +1,11 @@
+struct point {
+    int x;
+    long y;
+};
+
+extern struct point origin;
+char norm1(void);
+
+int main(void) {
+    return norm1();
+}
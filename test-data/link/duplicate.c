@@ -0,0 +1,10 @@
+struct point {
+    int x;
+    int y;
+};
+
+struct point origin;
+
+int main(void) {
+    return origin.x;
+}
@@ -0,0 +1,22 @@
+int printf(char *format, ...);
+
+struct point {
+    int x;
+    int y;
+};
+
+extern struct point origin;
+int norm1(struct point *p);
+
+static int helper(int x) {
+    return x + 1;
+}
+
+static int unused(void) {
+    return helper(2);
+}
+
+int main(void) {
+    printf("%d\n", helper(norm1(&origin)));
+    return 0;
+}
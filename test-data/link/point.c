@@ -0,0 +1,10 @@
+struct point {
+    int x;
+    int y;
+};
+
+struct point origin = { 3, 4 };
+
+int norm1(struct point *p) {
+    return p->x + p->y;
+}
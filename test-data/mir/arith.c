@@ -0,0 +1,16 @@
+int mix(int a, long b, double c) {
+    long x = a * b + 3;
+    x <<= a;
+    c = c / x - -a;
+    return x % 7 + (int)c;
+}
+
+int compare(int a, int b) {
+    return (a < b) + (a == b) * 2 + !a;
+}
+
+char narrow(long x) {
+    char c = x;
+    c += 1;
+    return c;
+}
@@ -0,0 +1,40 @@
+int collatz(int n) {
+    int steps = 0;
+    while (n != 1) {
+        if (n % 2)
+            n = 3 * n + 1;
+        else
+            n = n / 2;
+        steps++;
+    }
+    return steps;
+}
+
+int classify(int c) {
+    switch (c) {
+    case 0:
+        return 10;
+    case 1:
+    case 2:
+        c = c * 2;
+        break;
+    default:
+        c = -1;
+    }
+    return c;
+}
+
+int search(int *xs, int n, int key) {
+    int i = 0;
+    do {
+        if (xs[i] == key && i > 0 || key < 0)
+            goto found;
+    } while (++i < n);
+    return -1;
+found:
+    return i;
+}
+
+int pick(int a, int b) {
+    return a && b ? a : b;
+}
@@ -0,0 +1,14 @@
+int printf(char *fmt, ...);
+
+int counter = 42;
+int table[5] = { 1, 2, [4] = 5 };
+char name[] = "sea";
+char *greeting = "hello";
+int *third = &table[2];
+double ratio = 1.5;
+
+int main() {
+    counter++;
+    printf("%s %s %d %f\n", greeting, name, *third, ratio);
+    return table[4];
+}
@@ -0,0 +1,65 @@
+int square(int x) {
+    return x * x;
+}
+
+int constants(void) {
+    int a = 6;
+    int b = a * 7;
+    int c = b / 2 - 1;
+    if (c > 10)
+        return c << 2;
+    return 0;
+}
+
+int dead_branch(int x) {
+    int debug = 0;
+    int y = x + 1;
+    if (debug)
+        y = square(y);
+    while (0)
+        y = y * 3;
+    return y;
+}
+
+int loop_constant(int n) {
+    int i;
+    int k = 3;
+    int sum = 0;
+    for (i = 0; i < n; i++) {
+        if (k != 3)
+            k = k + 1;
+        sum = sum + k;
+    }
+    return sum;
+}
+
+int common(int *p, int i, int j) {
+    int a = p[i] + i * j;
+    int b = j * i;
+    int c = a - b;
+    return c + i * j;
+}
+
+int select(int x) {
+    switch (2) {
+    case 1:
+        return x;
+    case 2:
+        return x + x;
+    default:
+        return 0;
+    }
+}
+
+double convert(void) {
+    float f = 1.5;
+    int n = f * 3;
+    return n + 0.25;
+}
+
+int unused(int x) {
+    int a = x * 2;
+    int b = a + 3;
+    int c = b / x;
+    return x;
+}
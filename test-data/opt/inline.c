@@ -0,0 +1,29 @@
+static int sq(int x) { return x * x; }
+static inline int abs_(int x) { if (x < 0) return -x; return x; }
+static int fact(int n) { if (n <= 1) return 1; return n * fact(n - 1); }
+static int big(int a) {
+    int s = 0;
+    int i;
+    for (i = 0; i < a; i++) { s = s + i * a; s = s ^ (s >> 3); s = s + 7; }
+    return s;
+}
+int even(int n);
+static int odd(int n) { if (n == 0) return 0; return even(n - 1); }
+int even(int n) { if (n == 0) return 1; return odd(n - 1); }
+int api(int a, int b) {
+    return sq(a) + abs_(b) + fact(a) + big(b);
+}
+
+static int unused_helper(int x) { return x + 1; }
+
+static int table[4];
+
+static int count(void) {
+    static int calls;
+    calls = calls + 1;
+    return calls;
+}
+
+int use_count(void) {
+    return count() + count();
+}
@@ -0,0 +1,27 @@
+int sum(int *a, int n, int k) {
+    int s = 0;
+    int i = 0;
+    while (i < n) {
+        s = s + a[i] * (k * 3);
+        i = i + 1;
+    }
+    return s;
+}
+
+int grid(int *m, int rows, int cols) {
+    int total = 0;
+    int r;
+    int c;
+    for (r = 0; r < rows; r = r + 1)
+        for (c = 0; c < cols; c = c + 1)
+            total = total + m[r * cols + c];
+    return total;
+}
+
+int scale(int n) {
+    int x = 0;
+    int i;
+    for (i = 0; i < n; i++)
+        x = x + i * 8;
+    return x;
+}
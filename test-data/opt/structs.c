@@ -0,0 +1,23 @@
+struct pair {
+    char tag;
+    long value;
+};
+
+struct pair make(char tag, long value) {
+    struct pair p = { .value = value, .tag = tag };
+    return p;
+}
+
+long sum(struct pair *ps, int n) {
+    long total = 0;
+    int i;
+    for (i = 0; i < n; i++)
+        total += ps[i].value;
+    return total;
+}
+
+void swap(struct pair *a, struct pair *b) {
+    struct pair tmp = *a;
+    *a = *b;
+    *b = tmp;
+}
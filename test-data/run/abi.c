@@ -0,0 +1,256 @@
+#include <stdarg.h>
+
+int printf(char *fmt, ...);
+int vprintf(char *fmt, va_list ap);
+
+struct ff {
+    float a;
+    float b;
+};
+
+struct df {
+    double d;
+    float f;
+};
+
+struct di {
+    double d;
+    int i;
+};
+
+struct id {
+    int i;
+    double d;
+};
+
+struct one {
+    double d;
+};
+
+struct three {
+    int a;
+    int b;
+    int c;
+};
+
+struct chars {
+    char a;
+    char b;
+    char c;
+};
+
+struct big {
+    long a;
+    long b;
+    long c;
+};
+
+struct ff make_ff(float a, float b) {
+    struct ff r;
+    r.a = a;
+    r.b = b;
+    return r;
+}
+
+double sum_ff(struct ff x) {
+    return x.a + x.b;
+}
+
+struct df make_df(double d, float f) {
+    struct df r;
+    r.d = d;
+    r.f = f;
+    return r;
+}
+
+double sum_df(struct df x) {
+    return x.d + x.f;
+}
+
+struct di make_di(double d, int i) {
+    struct di r;
+    r.d = d;
+    r.i = i;
+    return r;
+}
+
+long sum_di(struct di x) {
+    return (long)(x.d * 10) + x.i;
+}
+
+struct id make_id(int i, double d) {
+    struct id r;
+    r.i = i;
+    r.d = d;
+    return r;
+}
+
+long sum_id(struct id x) {
+    return (long)(x.d * 100) + x.i;
+}
+
+struct one make_one(double d) {
+    struct one r;
+    r.d = d;
+    return r;
+}
+
+struct three make_three(int a, int b, int c) {
+    struct three r;
+    r.a = a;
+    r.b = b;
+    r.c = c;
+    return r;
+}
+
+int sum_three(struct three x) {
+    return x.a + x.b * 10 + x.c * 100;
+}
+
+struct chars make_chars(char a, char b, char c) {
+    struct chars r;
+    r.a = a;
+    r.b = b;
+    r.c = c;
+    return r;
+}
+
+int sum_chars(struct chars x) {
+    return x.a + x.b + x.c;
+}
+
+long clobber(struct big x) {
+    long s = x.a + x.b + x.c;
+    x.a = 0;
+    x.b = 0;
+    x.c = 0;
+    return s;
+}
+
+long split(long a, long b, long c, long d, long e, long f, long g,
+           struct three x) {
+    return a + b + c + d + e + f + g + x.a * 10 + x.b * 100 + x.c * 1000;
+}
+
+double spill_f(double a, double b, double c, double d, double e, double f,
+               double g, double h, struct ff x, struct df y, double z) {
+    return a + b + c + d + e + f + g + h + x.a + x.b * 2 + y.d * 3
+        + y.f * 4 + z * 5;
+}
+
+double mixed(int a, double b, struct ff c, struct di d, long e, float f,
+             struct id g, struct big h, double i, char j) {
+    return a + b + c.a + c.b + d.d + d.i + e + f + g.i + g.d + h.a + h.b
+        + h.c + i + j;
+}
+
+long sum_ints(int n, ...) {
+    va_list ap;
+    long s = 0;
+    int i;
+    va_start(ap, n);
+    for (i = 0; i < n; i++)
+        s += va_arg(ap, int);
+    va_end(ap);
+    return s;
+}
+
+/* More doubles than there are argument registers, so that some of them are
+   on the stack, between integers, longs and pointers. */
+double sum_tagged(char *tags, ...) {
+    va_list ap;
+    double s = 0;
+    int i;
+    va_start(ap, tags);
+    for (i = 0; tags[i]; i++) {
+        if (tags[i] == 'd')
+            s += va_arg(ap, double);
+        else if (tags[i] == 'l')
+            s += va_arg(ap, long) * 100;
+        else if (tags[i] == 'p')
+            s += *va_arg(ap, int *) * 1000;
+        else
+            s += va_arg(ap, int) * 10;
+    }
+    va_end(ap);
+    return s;
+}
+
+/* The named parameters use up the argument registers. */
+long past_regs(long a, long b, long c, long d, long e, long f, long g,
+               long h, long i, ...) {
+    va_list ap;
+    long s;
+    va_start(ap, i);
+    s = a + b + c + d + e + f + g + h + i;
+    s += va_arg(ap, long) * 100;
+    s += va_arg(ap, long) * 1000;
+    va_end(ap);
+    return s;
+}
+
+int sum_list(int n, va_list ap) {
+    int s = 0;
+    while (n--)
+        s += va_arg(ap, int);
+    return s;
+}
+
+int twice(int n, ...) {
+    va_list ap;
+    va_list copy;
+    int a;
+    int b;
+    va_start(ap, n);
+    va_copy(copy, ap);
+    a = sum_list(n, ap);
+    b = sum_list(n, copy);
+    va_end(copy);
+    va_end(ap);
+    return a * 1000 + b;
+}
+
+int say(char *fmt, ...) {
+    va_list ap;
+    int n;
+    va_start(ap, fmt);
+    n = vprintf(fmt, ap);
+    va_end(ap);
+    return n;
+}
+
+int main(void) {
+    int seven = 7;
+    struct ff ff = make_ff(1.5f, 2.25f);
+    struct df df = make_df(3.5, 0.75f);
+    struct di di = make_di(1.25, 7);
+    struct id id = make_id(9, 2.5);
+    struct one one = make_one(6.5);
+    struct three three = make_three(1, 2, 3);
+    struct chars chars = make_chars('a', 'b', 'c');
+    struct big big;
+    big.a = 100;
+    big.b = 200;
+    big.c = 300;
+    printf("%f %f %f\n", ff.a, ff.b, sum_ff(ff));
+    printf("%f %f %f\n", df.d, df.f, sum_df(df));
+    printf("%f %d %ld\n", di.d, di.i, sum_di(di));
+    printf("%d %f %ld\n", id.i, id.d, sum_id(id));
+    printf("%f\n", one.d);
+    printf("%d %d %d %d\n", three.a, three.b, three.c, sum_three(three));
+    printf("%c%c%c %d\n", chars.a, chars.b, chars.c, sum_chars(chars));
+    printf("%ld %ld %ld %ld\n", clobber(big), big.a, big.b, big.c);
+    printf("%ld\n", split(1, 2, 3, 4, 5, 6, 7, three));
+    printf("%f\n", spill_f(1, 2, 3, 4, 5, 6, 7, 8, ff, df, 0.5));
+    printf("%f\n", mixed(1, 2.5, ff, di, 3, 0.5f, id, big, 4.0, 'x'));
+    printf("%d %f %ld %f %d %f %ld %f %f %f %c %f\n", 1, 1.5, 2L, 2.5, 3,
+           3.5, 4L, 4.5, 5.5, 6.5, 'z', 7.5);
+    printf("%ld\n", sum_ints(4, 1, 2, 3, 4));
+    printf("%f\n", sum_tagged("ddddddiddddlpd", 1.0, 2.0, 3.0, 4.0, 5.0,
+                              2.5f, 6, 7.0, 8.0, 9.0, 10.0, 11L, &seven,
+                              0.5));
+    printf("%ld\n", past_regs(1, 2, 3, 4, 5, 6, 7, 8, 9, 10L, 11L));
+    printf("%d\n", twice(3, 10, 20, 30));
+    printf("%d\n", say("%s %d %.2f %c\n", "say", 42, 3.25, 'q'));
+    return 0;
+}
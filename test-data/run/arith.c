@@ -0,0 +1,62 @@
+int printf(char *fmt, ...);
+
+int gcd(int a, int b) {
+    while (b != 0) {
+        int t = a % b;
+        a = b;
+        b = t;
+    }
+    return a;
+}
+
+long fact(int n) {
+    if (n <= 1)
+        return 1;
+    return n * fact(n - 1);
+}
+
+int bits(long x) {
+    int n = 0;
+    while (x) {
+        n += x & 1;
+        x = x >> 1;
+    }
+    return n;
+}
+
+void inc(int *p, char *q) {
+    *p = *p + 1;
+    *q = *q - 1;
+}
+
+int sum2();
+
+int main(void) {
+    int i;
+    char c = 100;
+    short s = -300;
+    long big = 1234567890123;
+    printf("%d %d\n", gcd(1071, 462), gcd(17, 5));
+    printf("%ld\n", fact(20));
+    printf("%d %d\n", bits(255), bits(big));
+    printf("%d %d %d\n", -7 / 2, -7 % 2, 7 << 3);
+    printf("%ld %ld\n", big / 1000, big % 1000);
+    c = c + c;
+    s = s * 200;
+    printf("%d %d\n", c, s);
+    printf("%d %d\n", c >> 2, s / 7);
+    for (i = 0; i < 5; i++)
+        printf("%d%c", i * i, i == 4 ? '\n' : ' ');
+    printf("%d %d %d\n", 3 < 4, 4 <= 3, !0);
+    inc(&i, &c);
+    printf("%d %d\n", i, c);
+    printf("%d\n", gcd(12, 18));
+    printf("%d %d\n", (int)sizeof(0xFFFFFFFF), 0xFFFFFFFF > -1);
+    printf("%d %d\n", (int)sizeof(4294967295), (int)sizeof(017777777777));
+    printf("%d\n", sum2(2, 3));
+    return 0;
+}
+
+int sum2(int a, int b) {
+    return a + b;
+}
@@ -0,0 +1,139 @@
+int printf(char *fmt, ...);
+
+int collatz(int n) {
+    int steps = 0;
+    while (n != 1) {
+        if (n % 2)
+            n = 3 * n + 1;
+        else
+            n = n / 2;
+        steps++;
+    }
+    return steps;
+}
+
+int classify(int c) {
+    int r = 0;
+    switch (c) {
+    case 0:
+        r = 10;
+    case 1:
+        r = r + 1;
+        break;
+    case 2:
+    case 3:
+        r = c * 2;
+        break;
+    case 7:
+        return -7;
+    default:
+        r = -1;
+    }
+    return r;
+}
+
+int primes(int limit) {
+    int count = 0;
+    int n;
+    int d;
+    for (n = 2; n < limit; n++) {
+        for (d = 2; d * d <= n; d++) {
+            if (n % d == 0)
+                break;
+        }
+        if (d * d <= n)
+            continue;
+        count++;
+    }
+    return count;
+}
+
+int search(int *xs, int n, int key) {
+    int i = 0;
+    do {
+        if (xs[i] == key)
+            goto found;
+    } while (++i < n);
+    return -1;
+found:
+    return i;
+}
+
+int tangle(int n, int odd) {
+    int a = 0;
+    int b = 0;
+    if (odd)
+        goto second;
+first:
+    a = a + n;
+    n = n - 1;
+    if (n <= 0)
+        goto done;
+second:
+    b = b + 2 * n;
+    n = n - 1;
+    if (n > 0)
+        goto first;
+done:
+    return a * 1000 + b;
+}
+
+int fib(int n) {
+    int a = 0;
+    int b = 1;
+    int t;
+    while (n > 0) {
+        t = a + b;
+        a = b;
+        b = t;
+        n--;
+    }
+    return a;
+}
+
+int swaps(int n) {
+    int x = 1;
+    int y = 2;
+    int t;
+    while (n > 0) {
+        t = x;
+        x = y;
+        y = t;
+        n--;
+    }
+    return x * 10 + y;
+}
+
+int nested(int n) {
+    int i;
+    int j;
+    int total = 0;
+    for (i = 0; i < n; i++) {
+        for (j = 0; j < n; j++) {
+            if (j > i)
+                break;
+            if ((i + j) % 3 == 0)
+                continue;
+            total = total + i * j;
+        }
+    }
+    return total;
+}
+
+int main(void) {
+    int xs[5];
+    int i;
+    for (i = 0; i < 5; i++)
+        xs[i] = i * i;
+    printf("%d %d %d\n", collatz(1), collatz(7), collatz(27));
+    for (i = -1; i < 9; i++)
+        printf("%d ", classify(i));
+    printf("\n%d %d\n", primes(10), primes(1000));
+    printf("%d %d %d\n", search(xs, 5, 9), search(xs, 5, 0), search(xs, 5, 2));
+    printf("%d %d %d %d\n", tangle(5, 0), tangle(5, 1), tangle(1, 0),
+           tangle(0, 1));
+    printf("%d %d %d\n", fib(0), fib(10), fib(30));
+    printf("%d %d\n", swaps(3), swaps(4));
+    printf("%d\n", nested(10));
+    return 0;
+}
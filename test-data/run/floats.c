@@ -0,0 +1,30 @@
+int printf(char *fmt, ...);
+
+double poly(double x) {
+    return 3.0 * x * x - 2.5 * x + 1.0;
+}
+
+float halve(float x) {
+    return x / 2;
+}
+
+double mix(int a, float b, double c, long d) {
+    return a + b + c + d;
+}
+
+int main(void) {
+    double x = 1.5;
+    float f = 2.25f;
+    double neg = -x;
+    int i = 7;
+    printf("%f %f\n", poly(x), poly(-2.0));
+    printf("%f %f\n", halve(f), (double)halve(3));
+    printf("%f\n", mix(1, 2.5f, 3.25, 4));
+    printf("%d %d %ld\n", (int)poly(3.0), (int)-2.75, (long)1e12);
+    printf("%f %f\n", neg, (double)i / 2);
+    printf("%d %d %d %d\n", x < 2.0, x > 2.0, x == 1.5, x != 1.5);
+    printf("%f %f\n", 0x1.8p1, 0x10p-2f);
+    if (f)
+        printf("nonzero\n");
+    return 0;
+}
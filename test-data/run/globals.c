@@ -0,0 +1,61 @@
+int printf(char *fmt, ...);
+int puts(char *s);
+
+int counter;
+int table[5] = { 1, 1, 2, 3, 5 };
+char *names[6] = { "zero", "one", "two", "few", "many", "negative" };
+static double ratio = 0.5;
+
+struct entry {
+    char *name;
+    int value;
+};
+
+struct entry entries[2] = { { "a", 1 }, { "b", 2 } };
+
+static int next(void) {
+    counter = counter + 1;
+    return 0;
+}
+
+int apply(int (*f)(int), int x) {
+    return f(x);
+}
+
+int twice(int x) {
+    return x * 2;
+}
+
+int describe(int n) {
+    switch (n) {
+    case 0:
+        return 0;
+    case 1:
+        return 1;
+    case 2:
+    case 3:
+        return 3;
+    case -1:
+        return 5;
+    default:
+        return 4;
+    }
+}
+
+int main(void) {
+    int i;
+    int total = 0;
+    next();
+    next();
+    for (i = 0; i < 5; i++)
+        total += table[i];
+    printf("%d %d\n", counter, total);
+    printf("%s %s\n", names[1], names[2]);
+    printf("%f\n", ratio * 3);
+    printf("%s=%d %s=%d\n", entries[0].name, entries[0].value,
+           entries[1].name, entries[1].value);
+    printf("%d\n", apply(twice, 21));
+    for (i = -1; i < 6; i++)
+        puts(names[describe(i)]);
+    return 0;
+}
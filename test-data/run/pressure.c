@@ -0,0 +1,97 @@
+int printf(char *fmt, ...);
+
+int id(int x) {
+    return x;
+}
+
+double fid(double x) {
+    return x;
+}
+
+int many(int n) {
+    int a = n + 1;
+    int b = n * 2;
+    int c = n - 3;
+    int d = n * n;
+    int e = a + b;
+    int f = c * d;
+    int g = e - f;
+    int h = a * b + c;
+    int i = d - e * 2;
+    int j = f + g + h;
+    int k = i * 3 - a;
+    int l = j + k + b;
+    int m = id(a) + id(b);
+    int o = id(c) * id(d);
+    int p = id(e + f) - id(g);
+    return a + b + c + d + e + f + g + h + i + j + k + l + m + o + p;
+}
+
+double across(double x, double y) {
+    double a = x * 2;
+    double b = y + 1;
+    double c = fid(a) + b;
+    double d = fid(c) * a - b;
+    return a + b + c + d;
+}
+
+int loop(int n) {
+    int s0 = 0;
+    int s1 = 1;
+    int s2 = 2;
+    int s3 = 3;
+    int s4 = 4;
+    int s5 = 5;
+    int s6 = 6;
+    int s7 = 7;
+    int s8 = 8;
+    int s9 = 9;
+    int s10 = 10;
+    int s11 = 11;
+    int s12 = 12;
+    int s13 = 13;
+    int s14 = 14;
+    int i;
+    for (i = 0; i < n; i++) {
+        s0 += s14 + i;
+        s1 += s0;
+        s2 += s1;
+        s3 += s2 ^ i;
+        s4 += s3;
+        s5 += s4 - i;
+        s6 += s5;
+        s7 += s6 % 7;
+        s8 += s7;
+        s9 += s8 & 255;
+        s10 += s9;
+        s11 += s10 >> 1;
+        s12 += s11;
+        s13 += s12 | i;
+        s14 = s13 / 3 + id(i);
+    }
+    return s0 + s1 + s2 + s3 + s4 + s5 + s6 + s7 + s8 + s9 + s10 + s11 + s12
+        + s13 + s14;
+}
+
+int swap(int n) {
+    int a = 1;
+    int b = 2;
+    int c = 3;
+    int t;
+    while (n > 0) {
+        t = a;
+        a = b;
+        b = c;
+        c = t;
+        n = n - 1;
+    }
+    return a * 100 + b * 10 + c;
+}
+
+int main(void) {
+    printf("%d %d\n", many(5), many(-7));
+    printf("%f\n", across(1.5, 2.5));
+    printf("%d %d\n", loop(10), loop(20));
+    printf("%d %d %d\n", swap(1), swap(2), swap(5));
+    return 0;
+}
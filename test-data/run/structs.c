@@ -0,0 +1,117 @@
+int printf(char *fmt, ...);
+
+struct point {
+    int x;
+    int y;
+};
+
+struct vec {
+    double x;
+    double y;
+};
+
+struct mixed {
+    long id;
+    double weight;
+};
+
+struct big {
+    long a;
+    long b;
+    long c;
+};
+
+struct small {
+    char c;
+    short s;
+};
+
+struct node {
+    int v;
+    struct node *next;
+};
+
+struct point make_point(int x, int y) {
+    struct point p;
+    p.x = x;
+    p.y = y;
+    return p;
+}
+
+struct vec add(struct vec a, struct vec b) {
+    struct vec r;
+    r.x = a.x + b.x;
+    r.y = a.y + b.y;
+    return r;
+}
+
+struct mixed scale(struct mixed m, double k) {
+    m.weight = m.weight * k;
+    m.id = m.id + 1;
+    return m;
+}
+
+struct big sum3(struct big a, struct big b) {
+    struct big r;
+    r.a = a.a + b.a;
+    r.b = a.b + b.b;
+    r.c = a.c + b.c;
+    return r;
+}
+
+struct small shrink(struct small s) {
+    s.c = s.c + 1;
+    s.s = s.s - 1;
+    return s;
+}
+
+long many(long a, long b, long c, long d, long e, long f, long g,
+          struct point p, long h) {
+    return a + b * 2 + c * 3 + d * 4 + e * 5 + f * 6 + g * 7 + p.x * 8
+        + p.y * 9 + h * 10;
+}
+
+double fmany(double a, double b, double c, double d, double e, double f,
+             double g, double h, double i, double j) {
+    return a + b + c + d + e + f + g + h + i * 2 + j * 3;
+}
+
+int main(void) {
+    struct point p = make_point(3, 4);
+    struct vec v;
+    struct vec w;
+    struct vec sum;
+    struct mixed m;
+    struct big a;
+    struct big b;
+    struct big c;
+    struct small s;
+    struct node n = {7, &n};
+    int size = sizeof size;
+    v.x = 1.5;
+    v.y = 2.5;
+    w.x = 0.25;
+    w.y = 0.5;
+    sum = add(v, w);
+    m.id = 41;
+    m.weight = 1.5;
+    m = scale(m, 3.0);
+    a.a = 1;
+    a.b = 2;
+    a.c = 3;
+    b = a;
+    b.c = 30;
+    c = sum3(a, b);
+    s.c = 'a';
+    s.s = 1000;
+    s = shrink(s);
+    printf("%d %d\n", p.x, p.y);
+    printf("%f %f\n", sum.x, sum.y);
+    printf("%ld %f\n", m.id, m.weight);
+    printf("%ld %ld %ld\n", c.a, c.b, c.c);
+    printf("%c %d\n", s.c, s.s);
+    printf("%ld\n", many(1, 2, 3, 4, 5, 6, 7, p, 8));
+    printf("%f\n", fmany(1, 2, 3, 4, 5, 6, 7, 8, 9, 10));
+    printf("%d %d\n", n.next->next->v, size);
+    return 0;
+}
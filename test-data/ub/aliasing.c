@@ -0,0 +1,8 @@
+int printf(char *fmt, ...);
+
+int main() {
+    float f = 1.0f;
+    int *bits = (int *)&f;
+    printf("%x\n", *bits);
+    return 0;
+}
@@ -0,0 +1,56 @@
+void *malloc(long n);
+void free(void *p);
+void *memcpy(void *d, void *s, long n);
+int printf(char *fmt, ...);
+
+struct pair {
+    char tag;
+    long value;
+};
+
+int id(int x) {
+    return x;
+}
+
+int main() {
+    struct pair *pairs = malloc(2 * sizeof(struct pair));
+    struct pair copy;
+    char *bytes = (char *)&copy;
+    long sum = 0;
+    int i = 0;
+    double *d;
+    int x = 0;
+
+    pairs[0].tag = 'a';
+    pairs[0].value = 2147483647;
+    pairs[1] = pairs[0];
+    pairs[1].value += 1;
+    memcpy(&copy, &pairs[1], sizeof copy);
+    for (i = 0; i < sizeof copy; i++)
+        sum += bytes[i] != 0;
+    printf("%c %ld %ld\n", copy.tag, copy.value, sum);
+
+    d = (double *)pairs;
+    *d = 0.5;
+    printf("%g\n", *d);
+    free(pairs);
+
+    i = 0;
+    i = i + 1, i++;
+    printf("%d\n", i > 0 && i++ < 5);
+    printf("%d\n", i);
+
+    /* Sequence points order the side effects of the operands of an
+       assignment before its store. */
+    x = (x++, x + 1);
+    printf("%d\n", x);
+    x = x++ ? 1 : 2;
+    printf("%d\n", x);
+    x = (x++ && 1);
+    printf("%d\n", x);
+    x = id(x++);
+    printf("%d\n", x);
+    x = (x = 1, x + 1);
+    printf("%d\n", x);
+    return 0;
+}
@@ -0,0 +1,10 @@
+void leak(int **out) {
+    int local = 42;
+    *out = &local;
+}
+
+int main() {
+    int *p;
+    leak(&p);
+    return *p;
+}
@@ -0,0 +1,14 @@
+void *malloc(long n);
+int printf(char *fmt, ...);
+
+int main() {
+    void *block = malloc(8);
+    int *i = block;
+    float *f = block;
+    *f = 2.0f;
+    printf("%g\n", *f);
+    *i = 1;
+    printf("%d\n", *i);
+    printf("%g\n", *f);
+    return 0;
+}
@@ -0,0 +1,8 @@
+void *malloc(long n);
+
+int main() {
+    char *bytes = malloc(16);
+    int *p = (int *)(bytes + 2);
+    *p = 1;
+    return 0;
+}
@@ -0,0 +1,16 @@
+int printf(char *fmt, ...);
+
+int factorial(int n) {
+    int result = 1;
+    int i;
+    for (i = 2; i <= n; i++)
+        result *= i;
+    return result;
+}
+
+int main() {
+    int n;
+    for (n = 10; n < 15; n++)
+        printf("%d! = %d\n", n, factorial(n));
+    return 0;
+}
@@ -0,0 +1,9 @@
+int main() {
+    int a[4] = {1, 2, 3, 4};
+    int *end = a + 4;
+    int *p = a;
+    while (p != end)
+        p++;
+    p = a - 1;
+    return *end;
+}
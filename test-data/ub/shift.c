@@ -0,0 +1,4 @@
+int main() {
+    int bits = 32;
+    return 1 << bits;
+}
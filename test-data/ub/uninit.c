@@ -0,0 +1,10 @@
+int printf(char *fmt, ...);
+
+int main() {
+    int total;
+    int i;
+    for (i = 0; i < 3; i++)
+        total += i;
+    printf("%d\n", total);
+    return 0;
+}
@@ -0,0 +1,11 @@
+int printf(char *fmt, ...);
+
+int main() {
+    int i = 0;
+    int a[4] = {0, 0, 0, 0};
+    i = i + 1;
+    a[i] = i;
+    i = i++ + 1;
+    printf("%d\n", i);
+    return 0;
+}
@@ -0,0 +1,69 @@
+//! Golden tests for the AST dumps produced by `sea --emit=ast-json` and
+//! `sea --emit=ast-sexp`.
+//!
+//! Every `test-data/ast/*.c` file is parsed and compared against the `.json`
+//! and `.sexp` files next to it.  Set `BLESS=1` to update the expected
+//! output.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{compare, sources, tmp_path};
+
+mod common;
+
+#[test]
+fn json() {
+    check("json");
+}
+
+#[test]
+fn sexp() {
+    check("sexp");
+}
+
+/// The spans of definitions, statements, expressions and bindings cover their
+/// source text.
+#[test]
+fn spans() {
+    let source = "int x;\n\nint main(void) {\n    return x;\n}\n";
+    let path = tmp_path("ast-spans.c");
+    fs::write(&path, source).unwrap();
+    let dump = dump(&path, "sexp");
+    let texts: Vec<_> = dump
+        .split("(Span :start ")
+        .skip(1)
+        .map(|rest| {
+            let (start, rest) = rest.split_once(" :end ").unwrap();
+            let end = rest.split_once(')').unwrap().0;
+            &source[start.parse().unwrap()..end.parse().unwrap()]
+        })
+        .collect();
+    let main = "int main(void) {\n    return x;\n}";
+    assert_eq!(texts, ["x", "int x;", "", "x", "return x;", main]);
+}
+
+/// Compare the dumps of every source file in a format.
+fn check(format: &str) {
+    compare(&sources("test-data/ast"), format, |source| {
+        dump(source, format)
+    });
+}
+
+/// Parse a source file, returning the AST dump in a format.
+fn dump(source: &Path, format: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_sea"))
+        .arg(format!("--emit=ast-{format}"))
+        .args(["-o", "-"])
+        .arg(source)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}: {}",
+        source.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
@@ -0,0 +1,91 @@
+//! Helpers shared by the integration tests.
+//!
+//! Every test crate includes this module, and uses only some of it.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Make a directory for the outputs of a test, unless a tool it needs is
+/// missing.
+pub fn setup(test: &str, tools: &[&str]) -> Option<PathBuf> {
+    for tool in tools {
+        if Command::new(tool).arg("--version").output().is_err() {
+            eprintln!("skipping: `{tool}` is not installed");
+            return None;
+        }
+    }
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(test);
+    fs::create_dir_all(&dir).unwrap();
+    Some(dir)
+}
+
+/// A command running the driver with some arguments.
+pub fn sea(args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_sea"));
+    command.args(args);
+    command
+}
+
+/// The standard error of a command.
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// Collect the C sources under a directory.
+pub fn find_sources(dir: &Path, sources: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            find_sources(&path, sources);
+        } else if path.extension().is_some_and(|ext| ext == "c") {
+            sources.push(path);
+        }
+    }
+}
+
+/// A path in the temporary directory of the tests.
+pub fn tmp_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+/// The C sources directly in a directory, in order.
+pub fn sources(dir: &str) -> Vec<PathBuf> {
+    let mut sources: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "c"))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty());
+    sources
+}
+
+/// Compare the output for every source against the file next to it with an
+/// extension, or update that file when `BLESS` is set.
+pub fn compare(
+    sources: &[PathBuf],
+    extension: &str,
+    mut output: impl FnMut(&Path) -> String,
+) {
+    let bless = std::env::var_os("BLESS").is_some();
+    let mut failures = Vec::new();
+    for source in sources {
+        let actual = output(source);
+        let expected_path = source.with_extension(extension);
+        if bless {
+            fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+        if actual != expected {
+            failures.push(format!(
+                "{}:\n--- expected\n{expected}\n--- actual\n{actual}",
+                source.display()
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,108 @@
+//! Golden tests for the Graphviz output of `sea --emit=dot-ast`,
+//! `sea --emit=dot-cfg` and `sea --emit=dot-callgraph`.
+//!
+//! Every `test-data/dot/*.c` file is drawn and compared against the
+//! `.ast.dot`, `.cfg.dot` and `.callgraph.dot` files next to it.  Set
+//! `BLESS=1` to update the expected output.
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use common::{compare, find_sources, sources};
+
+mod common;
+
+#[test]
+fn ast() {
+    check("ast");
+}
+
+#[test]
+fn cfg() {
+    check("cfg");
+}
+
+#[test]
+fn callgraph() {
+    check("callgraph");
+}
+
+/// Every edge of the graphs of the sources in `test-data` is between nodes
+/// of the graph.  Control-flow graphs are only drawn for the sources that
+/// type check.
+#[test]
+fn well_formed() {
+    let mut sources = Vec::new();
+    find_sources(Path::new("test-data"), &mut sources);
+    sources.sort();
+
+    let mut failures = Vec::new();
+    for source in &sources {
+        for graph in ["ast", "cfg", "callgraph"] {
+            let dot = if graph == "cfg" {
+                let output = run(source, graph);
+                if !output.status.success() {
+                    continue;
+                }
+                String::from_utf8(output.stdout).unwrap()
+            } else {
+                draw(source, graph)
+            };
+            let mut nodes = HashSet::new();
+            for line in dot.lines().map(str::trim) {
+                let mut words = line.split([' ', ';']);
+                match (words.next().unwrap(), words.next(), words.next()) {
+                    (from, Some("->"), Some(to))
+                        if !nodes.contains(from) || !nodes.contains(to) =>
+                    {
+                        failures.push(format!("{}: {line}", source.display()));
+                    }
+                    (node, Some(attrs), _) if attrs.starts_with("[label=") => {
+                        nodes.insert(node);
+                    }
+                    _ => {}
+                }
+            }
+            if !dot.ends_with("}\n") {
+                failures.push(format!("{}: unfinished", source.display()));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+/// Compare the graphs of a kind of every source file.
+fn check(graph: &str) {
+    compare(
+        &sources("test-data/dot"),
+        &format!("{graph}.dot"),
+        |source| draw(source, graph),
+    );
+}
+
+/// Draw a kind of graph of a source file.
+///
+/// Some sources have type errors, which are reported after the syntax tree
+/// or call graph; only parse errors, after which there is no output, fail.
+fn draw(source: &Path, graph: &str) -> String {
+    let output = run(source, graph);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        !stdout.is_empty(),
+        "{}: {}",
+        source.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    stdout
+}
+
+/// Run `sea` to draw a kind of graph of a source file.
+fn run(source: &Path, graph: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sea"))
+        .arg(format!("--emit=dot-{graph}"))
+        .args(["-o", "-"])
+        .arg(source)
+        .output()
+        .unwrap()
+}
@@ -0,0 +1,221 @@
+//! Tests of the compiler driver, used like `cc`.
+//!
+//! The programs in `test-data/driver` are preprocessed, compiled, linked
+//! and run, in one invocation or several.  The tests are skipped if `cc` or
+//! `cpp` is not installed.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use common::{sea, stderr};
+
+mod common;
+
+/// The program, which includes `twice.h`.
+const MAIN: &str = "test-data/driver/main.c";
+
+/// The function it calls.
+const TWICE: &str = "test-data/driver/twice.c";
+
+#[test]
+fn link() {
+    let Some(dir) = setup("driver-link") else {
+        return;
+    };
+    let exe = dir.join("prog");
+    let output = sea(&["-DVALUE=21", "-O2", "-g", "-Wall", MAIN, TWICE, "-o"])
+        .arg(&exe)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(run(&exe), "42 7\n");
+}
+
+#[test]
+fn separate() {
+    let Some(dir) = setup("driver-separate") else {
+        return;
+    };
+    let object = dir.join("twice.o");
+    let output = sea(&["-c", TWICE, "-o"]).arg(&object).output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let exe = dir.join("prog");
+    let output = sea(&["-DVALUE=1", "-DLIMIT=3", MAIN])
+        .arg(&object)
+        .arg("-o")
+        .arg(&exe)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(run(&exe), "2 3\n");
+}
+
+#[test]
+fn preprocess() {
+    if setup("driver-preprocess").is_none() {
+        return;
+    }
+    let output = sea(&["-E", "-DVALUE=5", MAIN]).output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains("int twice(int x);"), "{text}");
+    assert!(text.contains("twice(5), 7)"), "{text}");
+    assert!(!text.contains("/*"), "{text}");
+}
+
+#[test]
+fn stdin() {
+    let Some(dir) = setup("driver-stdin") else {
+        return;
+    };
+    let exe = dir.join("prog");
+    let mut child = sea(&["-", "-o"])
+        .arg(&exe)
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let source = b"int main(void) { return 3; }\n";
+    child.stdin.take().unwrap().write_all(source).unwrap();
+    assert!(child.wait().unwrap().success());
+    let status = Command::new(&exe).status().unwrap();
+    assert_eq!(status.code(), Some(3));
+}
+
+#[test]
+fn werror() {
+    let Some(dir) = setup("driver-werror") else {
+        return;
+    };
+    let object = dir.join("twice.o");
+    let output = sea(&["-c", TWICE, "-o"]).arg(&object).output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // Linker inputs are unused when not linking.
+    for (flags, ok, prefix) in [
+        (&[][..], true, "warning: "),
+        (&["-Werror"][..], false, "error: "),
+        (&["-w"][..], true, ""),
+    ] {
+        let output = sea(flags)
+            .args(["-S", TWICE])
+            .arg(&object)
+            .args(["-o", "-"])
+            .output()
+            .unwrap();
+        let stderr = stderr(&output);
+        assert_eq!(output.status.success(), ok, "{flags:?}: {stderr}");
+        assert_eq!(output.stdout.is_empty(), !ok, "{flags:?}");
+        assert!(stderr.starts_with(prefix), "{flags:?}: {stderr}");
+        assert_eq!(stderr.is_empty(), prefix.is_empty(), "{flags:?}");
+    }
+}
+
+#[test]
+fn line_markers() {
+    let Some(dir) = setup("driver-line-markers") else {
+        return;
+    };
+    let path = dir.join("comments.c");
+    let source =
+        "/* A comment\n   over lines. */\nint main(void) { return y; }\n";
+    fs::write(&path, source).unwrap();
+    let output = sea(&["-S", "-o", "-"]).arg(&path).output().unwrap();
+    let expected = format!("error: {}:3:25: ", path.display());
+    assert!(
+        stderr(&output).starts_with(&expected),
+        "{}",
+        stderr(&output)
+    );
+}
+
+/// The options of GCC in `CFLAGS` are accepted, and dependencies are
+/// written by the preprocessor.
+#[test]
+fn gcc_options() {
+    let Some(dir) = setup("driver-gcc-options") else {
+        return;
+    };
+    let flags = [
+        "-O2",
+        "-fPIC",
+        "-march=native",
+        "-pipe",
+        "-pedantic",
+        "-ansi",
+        "-std=gnu11",
+        "-pthread",
+        "-S",
+    ];
+    let output = sea(&flags).args([TWICE, "-o", "-"]).output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).is_empty(), "{}", stderr(&output));
+
+    let output = sea(&["-Wall", "-fPIC", "-S", TWICE, "-o", "-"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stderr(&output), "warning: ignoring option `-fPIC`\n");
+
+    let deps = dir.join("main.d");
+    let object = dir.join("main.o");
+    let output = sea(&["-DVALUE=1", "-MMD", "-MF"])
+        .arg(&deps)
+        .args(["-c", MAIN, "-o"])
+        .arg(&object)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let deps = fs::read_to_string(deps).unwrap();
+    assert!(deps.contains("twice.h"), "{deps}");
+
+    // A source without the extension of one.
+    let source = dir.join("twice.txt");
+    fs::copy(TWICE, &source).unwrap();
+    let output = sea(&["-S", "-x", "c"])
+        .arg(&source)
+        .args(["-o", "-"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("twice:"));
+}
+
+#[test]
+fn errors() {
+    let output = sea(&["-std=c2y", TWICE]).output().unwrap();
+    assert_eq!(stderr(&output), "error: unknown standard `c2y`\n");
+
+    let output = sea(&["--frobnicate", TWICE]).output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        stderr(&output),
+        "error: unrecognized option `--frobnicate`\n"
+    );
+
+    let output = sea(&["-S", MAIN, TWICE, "-o", "-"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("cannot specify `-o`"));
+
+    let output = sea(&["run", "-S", MAIN]).output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        stderr(&output),
+        "error: `-S` cannot be used with `sea run`\n"
+    );
+}
+
+/// Run a program, returning its output.
+fn run(exe: &Path) -> String {
+    let output = Command::new(exe).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Make a directory for the outputs of a test, unless `cc` or `cpp` is
+/// missing.
+fn setup(test: &str) -> Option<std::path::PathBuf> {
+    common::setup(test, &["cc", "cpp"])
+}
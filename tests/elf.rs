@@ -0,0 +1,93 @@
+//! Tests of the object files written by `sea -c`.
+//!
+//! An object file is inspected with `readelf`, which must find the expected
+//! sections, symbols and relocations in it, and is then linked by the system
+//! `ld`.  The tests are skipped if binutils is not installed; the programs
+//! built from object files are run by `tests/run.rs`.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use common::{sea, setup, stderr};
+
+mod common;
+
+#[test]
+fn readelf() {
+    let Some(object) = compile("test-data/run/globals.c", "readelf") else {
+        return;
+    };
+    let output = Command::new("readelf")
+        .args(["-W", "-S", "-s", "-r"])
+        .arg(&object)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+
+    for section in [".text", ".data", ".rodata", ".bss", ".symtab"] {
+        let header = format!("] {section} ");
+        assert!(text.contains(&header), "no {section} section:\n{text}");
+    }
+    assert!(text.contains(".rela.text"), "no text relocations:\n{text}");
+    assert!(text.contains(".rela.data"), "no data relocations:\n{text}");
+
+    // Symbols are bound by their storage class, and library functions are
+    // left undefined.
+    let symbols = [
+        ("next", "FUNC    LOCAL  DEFAULT    1"),
+        ("main", "FUNC    GLOBAL DEFAULT    1"),
+        ("ratio", "OBJECT  LOCAL  DEFAULT    2"),
+        ("table", "OBJECT  GLOBAL DEFAULT    2"),
+        ("counter", "OBJECT  GLOBAL DEFAULT    5"),
+        ("printf", "NOTYPE  GLOBAL DEFAULT  UND"),
+    ];
+    for (name, expected) in symbols {
+        let found = text.lines().any(|line| {
+            line.ends_with(&format!(" {name}")) && line.contains(expected)
+        });
+        assert!(found, "`{name}` is not `{expected}`:\n{text}");
+    }
+
+    // Calls to library functions go through the PLT, variables are
+    // addressed relative to `%rip`, and pointers in data are absolute.
+    for reloc in [
+        "R_X86_64_PLT32         0000000000000000 printf - 4",
+        "R_X86_64_PC32          0000000000000000 counter - 4",
+        "R_X86_64_64            0000000000000000 .str.0 + 0",
+    ] {
+        assert!(text.contains(reloc), "no `{reloc}`:\n{text}");
+    }
+}
+
+#[test]
+fn ld() {
+    let Some(object) = compile("test-data/run/globals.c", "ld") else {
+        return;
+    };
+    let linked = object.with_extension("ld.o");
+    let output = Command::new("ld")
+        .arg("-r")
+        .arg("-o")
+        .arg(&linked)
+        .arg(&object)
+        .output()
+        .unwrap();
+    let stderr = stderr(&output);
+    assert!(output.status.success(), "ld failed:\n{stderr}");
+    assert!(stderr.is_empty(), "ld warned:\n{stderr}");
+}
+
+/// Compile a source file to an object file for a test, unless binutils is
+/// missing.
+fn compile(source: &str, test: &str) -> Option<PathBuf> {
+    let dir = setup(&format!("elf-{test}"), &["readelf", "ld"])?;
+    let object = dir.join("out.o");
+    let output = sea(&["-c", "-o"])
+        .arg(&object)
+        .arg(source)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "sea failed:\n{}", stderr(&output));
+    Some(object)
+}
@@ -0,0 +1,145 @@
+//! Tests for the formatter (`sea fmt`).
+//!
+//! Every `test-data/fmt/**/*.c` file is formatted, in the style configured
+//! next to it, and compared against the `.out` file next to it.  Set
+//! `BLESS=1` to update the expected output.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Output, Stdio};
+
+use common::{compare, find_sources, stderr, tmp_path};
+
+mod common;
+
+/// Sources are formatted as expected, and formatting them again changes
+/// nothing.
+#[test]
+fn golden() {
+    let mut sources = Vec::new();
+    find_sources(Path::new("test-data/fmt"), &mut sources);
+    sources.sort();
+    assert!(!sources.is_empty());
+
+    compare(&sources, "out", |source| {
+        let dir = source.parent().unwrap();
+        format(dir, &fs::read_to_string(source).unwrap())
+    });
+    for source in &sources {
+        let dir = source.parent().unwrap();
+        let expected =
+            fs::read_to_string(source.with_extension("out")).unwrap();
+        assert_eq!(
+            format(dir, &expected),
+            expected,
+            "{}: not idempotent",
+            source.display()
+        );
+    }
+}
+
+/// `--check` reports unformatted files without touching them, and files are
+/// otherwise formatted in place.
+#[test]
+fn check() {
+    let path = tmp_path("fmt-check.c");
+    let source = "int  main(void){return 0;}\n";
+    fs::write(&path, source).unwrap();
+
+    let output = sea(&["fmt", "--check"], &path);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        path.to_str().unwrap()
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), source);
+
+    assert!(sea(&["fmt"], &path).status.success());
+    let formatted = fs::read_to_string(&path).unwrap();
+    assert_eq!(formatted, "int main(void) {\n    return 0;\n}\n");
+
+    let output = sea(&["fmt", "--check"], &path);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+/// Mistakes in the configuration are reported with their line.
+#[test]
+fn bad_config() {
+    let config = tmp_path("fmt-bad.toml");
+    fs::write(&config, "max_width = 80\nbrace_style = \"Sideways\"\n").unwrap();
+    let path = tmp_path("fmt-bad.c");
+    fs::write(&path, "int x;\n").unwrap();
+
+    let output = sea(&["fmt", "--config", config.to_str().unwrap()], &path);
+    assert!(!output.status.success());
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains("fmt-bad.toml:2: unknown brace style `Sideways`"),
+        "{stderr}"
+    );
+}
+
+/// Names that headers may declare as types are not taken for variables.
+#[test]
+fn header_types() {
+    let sources = [
+        ("fmt-file.c", "2:18", "FILE", "FILE *f = 0;"),
+        ("fmt-size.c", "2:27", "size_t", "long n = (size_t) -1;"),
+        ("fmt-call.c", "2:27", "T", "long n = (T)(0);"),
+    ];
+    for (name, at, ty, stmt) in sources {
+        let path = tmp_path(name);
+        let source =
+            format!("#include <stdio.h>\nint main(void) {{ {stmt} }}\n");
+        fs::write(&path, &source).unwrap();
+
+        let output = sea(&["fmt"], &path);
+        assert!(!output.status.success());
+        let stderr = stderr(&output);
+        assert!(
+            stderr.contains(&format!(
+                "{name}:{at}: `{ty}` is not declared and may be a type"
+            )),
+            "{stderr}"
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), source);
+    }
+
+    // Declared names are not types.
+    let path = tmp_path("fmt-declared.c");
+    let source = "int f(int a, int b) { a * b; return (a) - 1; }\n";
+    fs::write(&path, source).unwrap();
+    assert!(sea(&["fmt"], &path).status.success());
+}
+
+/// Format a source read from standard input, in the style of a directory.
+fn format(dir: &Path, source: &str) -> String {
+    let mut child = common::sea(&["fmt", "-"])
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}: {}",
+        dir.display(),
+        stderr(&output)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Run `sea` with some arguments on a file.
+fn sea(args: &[&str], path: &Path) -> Output {
+    common::sea(args).arg(path).output().unwrap()
+}
@@ -0,0 +1,73 @@
+//! Tests for the interpreter, `sea run`.
+//!
+//! Every `test-data/run/*.c` program must print its `.out` file and exit
+//! successfully, as it does when compiled, even with `--check-ub`.  Every
+//! `test-data/interp/*.c` program is compared against the `.out` file next
+//! to it, holding its output, its errors and its exit status;
+//! `test-data/ub/*.c` programs are compared after running them with
+//! `--check-ub`.  Set `BLESS=1` to update the latter.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use common::{compare, sources};
+
+mod common;
+
+#[test]
+fn compiled() {
+    let mut failures = Vec::new();
+    let runs = sources("test-data/run").into_iter().flat_map(|source| {
+        [(source.clone(), &[][..]), (source, &["--check-ub"][..])]
+    });
+    for (source, flags) in runs {
+        let output = run(&source, flags);
+        let expected = fs::read(source.with_extension("out")).unwrap();
+        if !output.status.success() || output.stdout != expected {
+            failures.push(format!(
+                "{} {:?}: {}\n--- expected\n{}\n--- actual\n{}{}",
+                source.display(),
+                flags,
+                output.status,
+                String::from_utf8_lossy(&expected),
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn golden() {
+    check("test-data/interp", &[]);
+}
+
+#[test]
+fn check_ub() {
+    check("test-data/ub", &["--check-ub"]);
+}
+
+/// Compare the results of every source file in a directory.
+fn check(dir: &str, flags: &[&str]) {
+    compare(&sources(dir), "out", |source| {
+        let output = run(source, flags);
+        format!(
+            "{}{}exit status: {}\n",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr),
+            output.status.code().unwrap()
+        )
+    });
+}
+
+/// Interpret a source file.
+fn run(source: &Path, flags: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sea"))
+        .arg("run")
+        .args(flags)
+        .arg(source)
+        .output()
+        .unwrap()
+}
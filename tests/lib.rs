@@ -0,0 +1,183 @@
+//! Tests for the library API.
+
+use std::path::Path;
+
+use sea::{Emit, Options, Severity, compile};
+
+/// Options for an output.
+fn emit(emit: Emit) -> Options {
+    Options {
+        emit,
+        ..Default::default()
+    }
+}
+
+/// A source is compiled to every output, without warnings.
+#[test]
+fn outputs() {
+    let source = "int twice(int x) { return x * 2; }\n";
+    for kind in [Emit::Ast, Emit::C, Emit::Hir, Emit::Mir, Emit::Asm] {
+        let output = compile(source, &emit(kind)).unwrap();
+        let text = String::from_utf8(output.contents).unwrap();
+        assert!(text.contains("twice"), "{kind:?}: {text}");
+        assert!(output.warnings.is_empty());
+    }
+    let object = compile(source, &emit(Emit::Object)).unwrap();
+    assert!(object.contents.starts_with(b"\x7fELF"));
+}
+
+/// The HIR is written as the resolved types of structures and names.
+#[test]
+fn hir() {
+    let source = "struct point { int x; int y; };\n\
+                  typedef struct point point;\n\
+                  long total, counts[4];\n\
+                  int norm(point *p);\n\
+                  int norm(point *p) { return p->x + p->y; }\n";
+    let output = compile(source, &emit(Emit::Hir)).unwrap();
+    assert_eq!(
+        String::from_utf8(output.contents).unwrap(),
+        "struct #0 point { x: int; y: int; }\n\
+         typedef point: struct #0\n\
+         total: long\n\
+         counts: long [4]\n\
+         norm: int (struct #0 *)\n"
+    );
+}
+
+/// Problems are reported with their positions, when they are known.
+#[test]
+fn diagnostics() {
+    let source = "int x;\n@";
+    let error = compile(source, &emit(Emit::Asm)).unwrap_err();
+    let [diagnostic] = &error.0[..] else {
+        panic!("{error}");
+    };
+    assert_eq!(diagnostic.severity, Severity::Error);
+    let rendered = diagnostic.render(Path::new("x.c"), source);
+    assert!(
+        rendered.starts_with("error: x.c:2:1: expected"),
+        "{rendered}"
+    );
+
+    // Parse errors are where the input stops making sense, rather than at
+    // the start of the declaration.
+    let source = "int x;\nint main(void) { int y = 1 return y; }";
+    let error = compile(source, &emit(Emit::Asm)).unwrap_err();
+    let rendered = error.render(Path::new("x.c"), source);
+    assert!(
+        rendered.starts_with("error: x.c:2:28: expected "),
+        "{rendered}"
+    );
+    assert!(rendered.contains("';'"), "{rendered}");
+
+    let source = "int main(void) { return y; }";
+    let error = compile(source, &emit(Emit::Hir)).unwrap_err();
+    let rendered = error.render(Path::new("x.c"), source);
+    assert!(
+        rendered.starts_with("error: x.c:1:25: in `main`: "),
+        "{rendered}"
+    );
+    assert!(rendered.contains("`y`"), "{rendered}");
+
+    // Line markers give the lines and paths of the lines after them.
+    let source = "# 1 \"x.c\"\n# 1 \"x.h\" 1\nint x;\n# 3 \"x.c\" 2\n\n\
+                  int main(void) { return y; }";
+    let error = compile(source, &emit(Emit::Hir)).unwrap_err();
+    let rendered = error.render(Path::new("-"), source);
+    assert!(rendered.starts_with("error: x.c:4:25: "), "{rendered}");
+
+    let mut options = emit(Emit::Object);
+    options.target = sea::codegen::Target::Wasm32;
+    assert!(compile("int x;", &options).is_err());
+}
+
+/// Variadic functions can be declared, called and defined, and `va_start` is
+/// only allowed in a variadic function.
+#[test]
+fn variadic_functions() {
+    let source = "int printf(char *fmt, ...);\n\
+                  int main(void) { return printf(\"%d\\n\", 1); }\n";
+    assert!(compile(source, &emit(Emit::Asm)).is_ok());
+
+    let source = "int first(int n, ...) {\n\
+                  __builtin_va_list ap;\n\
+                  __builtin_va_start(ap, n);\n\
+                  n = __builtin_va_arg(ap, int);\n\
+                  __builtin_va_end(ap);\n\
+                  return n;\n\
+                  }\n";
+    assert!(compile(source, &emit(Emit::Asm)).is_ok());
+
+    let source = "void f(int n) { __builtin_va_list ap; \
+                  __builtin_va_start(ap, n); }\n";
+    let error = compile(source, &emit(Emit::Asm)).unwrap_err();
+    assert!(error.to_string().contains("variadic"), "{error}");
+}
+
+/// A variable is in scope from its declarator on, even if its initializer
+/// is invalid, and objects must have complete types.
+#[test]
+fn declarations() {
+    let source = "int main(void) { int *p = 0; int x = p; return x; }";
+    let error = compile(source, &emit(Emit::Asm)).unwrap_err();
+    let [diagnostic] = &error.0[..] else {
+        panic!("{error}");
+    };
+    assert!(diagnostic.message.contains("int *"), "{error}");
+
+    let source = "struct Z;\nstruct Z z;\nextern struct Z y;\n";
+    let error = compile(source, &emit(Emit::Asm)).unwrap_err();
+    assert_eq!(error.0.len(), 1, "{error}");
+    assert!(error.to_string().contains("type struct Z"), "{error}");
+}
+
+/// Constants that have no type are errors.
+#[test]
+fn constants() {
+    for (source, message) in [
+        ("long x = 9223372036854775808;", "too large"),
+        ("long x = 0x10000000000000000;", "expected"),
+        ("double x = 1.0L;", "not supported"),
+    ] {
+        let error = compile(source, &emit(Emit::Asm)).unwrap_err();
+        assert!(error.to_string().contains(message), "{source}: {error}");
+    }
+    let source = "long x = 9223372036854775808u; double y = 0x1p-1;";
+    assert!(compile(source, &emit(Emit::Asm)).is_ok());
+}
+
+/// Long expressions are as deep as they are long, but do not overflow the
+/// stack of the thread compiling them.
+#[test]
+fn long_expressions() {
+    let terms = vec!["x"; 5000].join(" + ");
+    let source =
+        format!("int main(void) {{ int x = 1; return {terms} - 5000; }}\n");
+    for kind in [Emit::Ast, Emit::C, Emit::Hir, Emit::DotCfg, Emit::Mir] {
+        let output = compile(&source, &emit(kind));
+        assert!(output.is_ok(), "{kind:?}: {:?}", output.err());
+    }
+
+    let arena = sea::hir::Arena::default();
+    let program = sea::compile::parse(&arena, &source).unwrap();
+    sea::compile::check(&program, sea::codegen::Target::X86_64).unwrap();
+    let mut out = Vec::new();
+    let status = sea::interp::run(&program, &[], &mut out, true).unwrap();
+    assert_eq!(status, 0);
+}
+
+/// Parts of a program can be printed without entering its names, which
+/// prints symbols by number.
+#[test]
+fn detached_nodes() {
+    let arena = sea::hir::Arena::default();
+    let program = sea::compile::parse(&arena, "int x = 1;").unwrap();
+    let debug = format!("{:?}", program.decls[0]);
+    assert!(debug.contains("Symbol(0)"), "{debug}");
+    assert_eq!(program.decls[0].to_string(), "int Symbol(0) = 1;");
+    assert_eq!(
+        program.names.enter(|| program.decls[0].to_string()),
+        "int x = 1;"
+    );
+}
@@ -0,0 +1,105 @@
+//! Tests of the checks of linkage across translation units.
+//!
+//! The programs in `test-data/link` are compiled together, in one invocation
+//! of the driver, which must report the symbols whose declarations or
+//! definitions conflict across them, and the static functions that are never
+//! used.  Linking needs `cc`; the tests that link are skipped without it.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{sea, setup, stderr};
+
+mod common;
+
+/// The program, which uses the symbols of `point.c`.
+const MAIN: &str = "test-data/link/main.c";
+
+/// The symbols used by `main.c`.
+const POINT: &str = "test-data/link/point.c";
+
+/// A unit declaring the symbols of `point.c` with other types.
+const CONFLICT: &str = "test-data/link/conflict.c";
+
+/// A unit defining `main` and `origin` again.
+const DUPLICATE: &str = "test-data/link/duplicate.c";
+
+#[test]
+fn link() {
+    let Some(dir) = setup("link-link", &["cc"]) else {
+        return;
+    };
+    let exe = dir.join("prog");
+    let output = sea(&["-w", MAIN, POINT, "-o"]).arg(&exe).output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stderr(&output), "");
+    let output = Command::new(&exe).output().unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "8\n");
+}
+
+#[test]
+fn unused() {
+    let Some(dir) = setup("link-unused", &["cc"]) else {
+        return;
+    };
+    let exe = dir.join("prog");
+    let warning = "test-data/link/main.c:15:1: static function `unused` \
+                   defined but not used\n";
+    for (flags, ok, prefix) in [
+        (&[][..], true, "warning: "),
+        (&["-Werror"][..], false, "error: "),
+    ] {
+        let _ = fs::remove_file(&exe);
+        let output = sea(flags)
+            .args([MAIN, POINT, "-o"])
+            .arg(&exe)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.success(), ok, "{flags:?}");
+        assert_eq!(stderr(&output), format!("{prefix}{warning}"));
+        assert_eq!(exe.exists(), ok, "{flags:?}");
+    }
+
+    // Nor are objects written when the warning is an error.
+    let dir = dir.join("objects");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let output = sea(&["-Werror", "-c"])
+        .arg(root.join(MAIN))
+        .arg(root.join(POINT))
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+}
+
+#[test]
+fn conflict() {
+    let output = sea(&[POINT, CONFLICT]).output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        stderr(&output),
+        "error: conflicting types for `origin`: `struct point` has different \
+         fields in test-data/link/point.c:6:14 and \
+         test-data/link/conflict.c:6:21\n\
+         error: conflicting types for `norm1`: `int (struct point *)` in \
+         test-data/link/point.c:8:1, but `char (void)` in \
+         test-data/link/conflict.c:7:1\n"
+    );
+}
+
+#[test]
+fn duplicate() {
+    let output = sea(&["-w", MAIN, POINT, DUPLICATE]).output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        stderr(&output),
+        "error: multiple definitions of `main`, in \
+         test-data/link/main.c:19:1 and test-data/link/duplicate.c:8:1\n\
+         error: multiple definitions of `origin`, in \
+         test-data/link/point.c:6:14 and test-data/link/duplicate.c:6:14\n"
+    );
+}
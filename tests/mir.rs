@@ -0,0 +1,44 @@
+//! Golden tests for the MIR dumps produced by `sea --emit=mir`.
+//!
+//! Every `test-data/mir/*.c` file is lowered and compared against the
+//! `.mir` file next to it; `test-data/opt/*.c` files are compared after
+//! optimizing with `-O1`.  Set `BLESS=1` to update the expected output.
+
+use std::path::Path;
+use std::process::Command;
+
+use common::{compare, sources};
+
+mod common;
+
+#[test]
+fn golden() {
+    check("test-data/mir", &[]);
+}
+
+#[test]
+fn optimized() {
+    check("test-data/opt", &["-O1"]);
+}
+
+/// Compare the dumps of every source file in a directory.
+fn check(dir: &str, flags: &[&str]) {
+    compare(&sources(dir), "mir", |source| lower(source, flags));
+}
+
+/// Lower a source file, returning the MIR dump.
+fn lower(source: &Path, flags: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_sea"))
+        .args(["--emit=mir", "-o", "-"])
+        .args(flags)
+        .arg(source)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}: {}",
+        source.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
@@ -0,0 +1,183 @@
+//! Round-trip tests for the C printer (`sea --emit=c`).
+//!
+//! Printing a program and parsing the output must give back the same AST,
+//! which is checked by comparing the S-expression dumps (without spans) of
+//! the source and of its printed form.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{find_sources, tmp_path};
+
+mod common;
+
+/// Every source in `test-data` round-trips, and printing is idempotent.
+#[test]
+fn round_trip() {
+    let mut sources = Vec::new();
+    find_sources(Path::new("test-data"), &mut sources);
+    sources.sort();
+    assert!(!sources.is_empty());
+
+    let mut failures = Vec::new();
+    for source in &sources {
+        let name = source.to_str().unwrap().replace(['/', '\\'], "-");
+        let printed = tmp_path(&name);
+        let text = print(source);
+        fs::write(&printed, &text).unwrap();
+        if sexp(source) != sexp(&printed) {
+            failures.push(format!("{}:\n{text}", source.display()));
+        } else if print(&printed) != text {
+            failures.push(format!("{}: not idempotent", source.display()));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+/// Random expressions round-trip, whatever parentheses they need.
+#[test]
+fn expressions() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    let mut source =
+        String::from("int g(int x, int y);\n\nint f(int a, int b, int c) {\n");
+    for _ in 0..200 {
+        let mut e = String::new();
+        rng.expr(&mut e, 4);
+        source.push_str(&format!("    {e};\n"));
+    }
+    source.push_str("    return 0;\n}\n");
+
+    let path = tmp_path("print-expressions.c");
+    fs::write(&path, &source).unwrap();
+    let printed = tmp_path("print-expressions-printed.c");
+    fs::write(&printed, print(&path)).unwrap();
+    assert_eq!(sexp(&path), sexp(&printed));
+}
+
+/// A xorshift generator of random expressions.
+struct Rng(u64);
+
+impl Rng {
+    /// The next random number below a bound.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+
+    /// Pick a random element of a slice.
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len() as u64) as usize]
+    }
+
+    /// Write out a fully parenthesized random expression, nested up to a
+    /// depth.
+    fn expr(&mut self, out: &mut String, depth: u32) {
+        const VARS: &[&str] = &["a", "b", "c"];
+        if depth == 0 {
+            match self.below(2) {
+                0 => out.push_str(self.pick(VARS)),
+                _ => out.push_str(&self.below(100).to_string()),
+            }
+            return;
+        }
+        out.push('(');
+        match self.below(9) {
+            0..=2 => {
+                const OPS: &[&str] = &[
+                    "+", "-", "*", "/", "%", "<<", ">>", "<", ">", "<=", ">=",
+                    "==", "!=", "&", "^", "|", "&&", "||",
+                ];
+                self.expr(out, depth - 1);
+                out.push_str(&format!(" {} ", self.pick(OPS)));
+                self.expr(out, depth - 1);
+            }
+            3 => {
+                out.push_str(self.pick(&["-", "!", "~", "+"]));
+                self.expr(out, depth - 1);
+            }
+            4 => {
+                const OPS: &[&str] = &["=", "+=", "-=", "*=", "&=", "|="];
+                out.push_str(&format!(
+                    "{} {} ",
+                    self.pick(VARS),
+                    self.pick(OPS)
+                ));
+                self.expr(out, depth - 1);
+            }
+            5 => {
+                self.expr(out, depth - 1);
+                out.push_str(" ? ");
+                self.expr(out, depth - 1);
+                out.push_str(" : ");
+                self.expr(out, depth - 1);
+            }
+            6 => {
+                self.expr(out, depth - 1);
+                out.push_str(", ");
+                self.expr(out, depth - 1);
+            }
+            7 => {
+                out.push_str("(long)");
+                self.expr(out, depth - 1);
+            }
+            _ => match self.below(3) {
+                0 => {
+                    let op = self.pick(&["++", "--"]);
+                    out.push_str(&format!("{op}{}", self.pick(VARS)));
+                }
+                1 => {
+                    let op = self.pick(&["++", "--"]);
+                    out.push_str(&format!("{}{op}", self.pick(VARS)));
+                }
+                _ => {
+                    out.push_str("g(");
+                    self.expr(out, depth - 1);
+                    out.push_str(", ");
+                    self.expr(out, depth - 1);
+                    out.push(')');
+                }
+            },
+        }
+        out.push(')');
+    }
+}
+
+/// Print a source file back as C.
+fn print(source: &Path) -> String {
+    run(source, "--emit=c")
+}
+
+/// Dump the AST of a source file as an S-expression, without spans.
+fn sexp(source: &Path) -> String {
+    let mut dump = run(source, "--emit=ast-sexp");
+    while let Some(start) = dump.find(":span (Span") {
+        let end = start + dump[start..].find(')').unwrap() + 1;
+        let start = dump[..start].trim_end().len();
+        dump.replace_range(start..end, "");
+    }
+    dump
+}
+
+/// Run the front end on a source file, returning its output.
+///
+/// Some sources have type errors, which are reported after the output; only
+/// parse errors, after which there is no output, fail.
+fn run(source: &Path, emit: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_sea"))
+        .arg(emit)
+        .args(["-o", "-"])
+        .arg(source)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        !stdout.is_empty(),
+        "{}: {}",
+        source.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    stdout
+}
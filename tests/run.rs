@@ -0,0 +1,278 @@
+//! End-to-end tests of the assembly produced by `sea -S`.
+//!
+//! Every `test-data/run/*.c` file is compiled to assembly, assembled and
+//! linked with a C compiler, and run; its output must match the `.out` file
+//! next to it, which was produced by building the same program with GCC.
+//! Where `long` is 32 bits wide, as on WebAssembly, a program may print
+//! something else, which is in a `.wasm32.out` file if so.
+//! Each register allocator is tried, and so is going through LLVM IR
+//! (`sea --emit=llvm`) compiled by `llc`.  On x86-64, programs are also
+//! compiled straight to object files (`sea -c`), which are only linked.
+//!
+//! x86-64 programs are linked with `cc` and run natively.  RISC-V programs
+//! are linked statically with `$RISCV_CC` (by default
+//! `riscv64-linux-gnu-gcc`) and run under `qemu-riscv64`.  WebAssembly
+//! programs are assembled with `wat2wasm` (from WABT) and run by Node.js,
+//! with `tests/wasm32.mjs` providing the C library functions they import.
+//! The tests of a target are skipped if its tools are not installed, and so
+//! are those through LLVM if `llc` is not.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{sea, setup, sources, stderr};
+
+mod common;
+
+#[test]
+fn x86_64_o0() {
+    check(&X86_64, &["-O0"]);
+}
+
+#[test]
+fn x86_64_o1() {
+    check(&X86_64, &["-O1"]);
+}
+
+#[test]
+fn x86_64_naive() {
+    check(&X86_64, &["-O1", "--regalloc=naive"]);
+}
+
+#[test]
+fn x86_64_graph() {
+    check(&X86_64, &["-O1", "--regalloc=graph"]);
+}
+
+#[test]
+fn x86_64_object_o0() {
+    check(&X86_64, &["-c", "-O0"]);
+}
+
+#[test]
+fn x86_64_object_o1() {
+    check(&X86_64, &["-c", "-O1"]);
+}
+
+#[test]
+fn x86_64_llvm_o0() {
+    check(&X86_64, &["--emit=llvm", "-O0"]);
+}
+
+#[test]
+fn x86_64_llvm_o1() {
+    check(&X86_64, &["--emit=llvm", "-O1"]);
+}
+
+#[test]
+fn riscv64_o0() {
+    check(&RISCV64, &["-O0"]);
+}
+
+#[test]
+fn riscv64_o1() {
+    check(&RISCV64, &["-O1"]);
+}
+
+#[test]
+fn riscv64_naive() {
+    check(&RISCV64, &["-O1", "--regalloc=naive"]);
+}
+
+#[test]
+fn riscv64_graph() {
+    check(&RISCV64, &["-O1", "--regalloc=graph"]);
+}
+
+#[test]
+fn riscv64_llvm() {
+    check(&RISCV64, &["--emit=llvm", "-O1"]);
+}
+
+#[test]
+fn wasm32_o0() {
+    check(&WASM32, &["-O0"]);
+}
+
+#[test]
+fn wasm32_o1() {
+    check(&WASM32, &["-O1"]);
+}
+
+/// How to build and run programs for one target.
+struct Target {
+    /// The value of `sea --target`.
+    name: &'static str,
+    /// The extension of the assembly files.
+    asm: &'static str,
+    /// The environment variable that overrides the C compiler.
+    cc_var: Option<&'static str>,
+    /// The default C compiler.
+    cc: &'static str,
+    /// Extra flags for the C compiler.
+    cc_flags: &'static [&'static str],
+    /// The command that runs the programs, if they cannot run natively.
+    runner: &'static [&'static str],
+}
+
+/// The host.
+const X86_64: Target = Target {
+    name: "x86_64",
+    asm: "s",
+    cc_var: None,
+    cc: "cc",
+    cc_flags: &[],
+    runner: &[],
+};
+
+/// RISC-V, through a cross compiler and qemu.
+const RISCV64: Target = Target {
+    name: "riscv64",
+    asm: "s",
+    cc_var: Some("RISCV_CC"),
+    cc: "riscv64-linux-gnu-gcc",
+    cc_flags: &["-static"],
+    runner: &["qemu-riscv64"],
+};
+
+/// WebAssembly, through WABT and Node.js.
+const WASM32: Target = Target {
+    name: "wasm32",
+    asm: "wat",
+    cc_var: None,
+    cc: "wat2wasm",
+    cc_flags: &[],
+    runner: &["node", "tests/wasm32.mjs"],
+};
+
+impl Target {
+    /// The C compiler to use.
+    fn cc(&self) -> String {
+        self.cc_var
+            .and_then(|var| std::env::var(var).ok())
+            .unwrap_or_else(|| self.cc.to_string())
+    }
+}
+
+/// Compile and run every test program for a target with some flags.
+fn check(target: &Target, flags: &[&str]) {
+    let cc = target.cc();
+    let mut tools = vec![cc.as_str()];
+    tools.extend(target.runner.first());
+    if flags.contains(&"--emit=llvm") {
+        tools.push("llc");
+    }
+    let test = format!("run-{}{}", target.name, flags.concat());
+    let Some(dir) = setup(&test, &tools) else {
+        return;
+    };
+    let mut failures = Vec::new();
+
+    for source in &sources("test-data/run") {
+        // Programs print something else where `long` is narrower.
+        let own = source.with_extension(format!("{}.out", target.name));
+        let expected = fs::read_to_string(own)
+            .or_else(|_| fs::read_to_string(source.with_extension("out")))
+            .unwrap();
+        match run(target, &dir, source, flags) {
+            Ok(actual) if actual == expected => {}
+            Ok(actual) => failures.push(format!(
+                "{}:\n--- expected\n{}\n--- actual\n{}",
+                source.display(),
+                expected,
+                actual
+            )),
+            Err(error) => {
+                failures.push(format!("{}: {}", source.display(), error))
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+/// Build and run a program in a directory, returning its output.
+fn run(
+    target: &Target,
+    dir: &Path,
+    source: &Path,
+    flags: &[&str],
+) -> Result<String, String> {
+    let base = dir.join(source.file_stem().unwrap());
+    // `-c` overrides `-S`.
+    let object = flags.contains(&"-c");
+    let asm = base.with_extension(if object { "o" } else { target.asm });
+    let llvm = flags.contains(&"--emit=llvm");
+    let out = if llvm {
+        base.with_extension("ll")
+    } else {
+        asm.clone()
+    };
+
+    let output = sea(&["-S"])
+        .arg(format!("--target={}", target.name))
+        .args(flags)
+        .arg("-o")
+        .arg(&out)
+        .arg(source)
+        .output()
+        .unwrap();
+    if !output.status.success() {
+        return Err(format!("sea failed:\n{}", stderr(&output)));
+    }
+
+    if llvm {
+        let output = Command::new("llc")
+            .args(llc_flags())
+            .arg("-relocation-model=pic")
+            .arg("-o")
+            .arg(&asm)
+            .arg(&out)
+            .output()
+            .unwrap();
+        if !output.status.success() {
+            return Err(format!("llc failed:\n{}", stderr(&output)));
+        }
+    }
+
+    let cc = target.cc();
+    let output = Command::new(&cc)
+        .args(target.cc_flags)
+        .arg("-o")
+        .arg(&base)
+        .arg(&asm)
+        .output()
+        .unwrap();
+    if !output.status.success() {
+        return Err(format!("{cc} failed:\n{}", stderr(&output)));
+    }
+
+    let output = match target.runner {
+        [runner, args @ ..] => {
+            Command::new(runner).args(args).arg(&base).output()
+        }
+        [] => Command::new(&base).output(),
+    }
+    .unwrap();
+    if !output.status.success() {
+        return Err(format!("exited with {}", output.status));
+    }
+    Ok(String::from_utf8(output.stdout).unwrap())
+}
+
+/// The flags `llc` needs to read the IR: versions before 15 only accept
+/// opaque pointers when asked to.
+fn llc_flags() -> &'static [&'static str] {
+    let output = Command::new("llc").arg("--version").output().unwrap();
+    let version = String::from_utf8_lossy(&output.stdout);
+    let major = version
+        .split("LLVM version ")
+        .nth(1)
+        .and_then(|rest| rest.split('.').next())
+        .and_then(|major| major.parse::<u32>().ok());
+    match major {
+        Some(major) if major < 15 => &["-opaque-pointers"],
+        _ => &[],
+    }
+}
@@ -0,0 +1,129 @@
+//! Tests for the HIR passes.
+
+use std::fs;
+
+use sea::compile::parse;
+use sea::hir::fold::Fold;
+use sea::hir::visit::{self, Visit};
+use sea::hir::visit_mut::{self, VisitMut};
+use sea::hir::{Arena, Expr, FnDefn, Lit, Spanned, Stmt, Symbol, TopDefn};
+
+/// The sources that the passes are run over.
+const SOURCES: [&str; 3] = [
+    "test-data/ast/decls.c",
+    "test-data/ast/stmts.c",
+    "test-data/dot/control.c",
+];
+
+/// Count the literals reached.
+#[derive(Default)]
+struct Lits(usize);
+
+impl<'a> Visit<'a> for Lits {
+    fn visit_lit(&mut self, _lit: &'a Lit) {
+        self.0 += 1;
+    }
+}
+
+/// Replace every identifier in an expression with another.
+struct Rename<'hir>(&'hir Arena<'hir>, Symbol);
+
+impl<'hir> VisitMut<'hir> for Rename<'hir> {
+    fn arena(&self) -> &'hir Arena<'hir> {
+        self.0
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr<'hir>) {
+        if let Expr::Ident(name) = expr {
+            *name = self.1;
+        }
+        visit_mut::walk_expr(self, expr);
+    }
+}
+
+/// Rebuild every node as it was.
+struct Identity<'hir>(&'hir Arena<'hir>);
+
+impl<'hir> Fold<'hir> for Identity<'hir> {
+    fn arena(&self) -> &'hir Arena<'hir> {
+        self.0
+    }
+}
+
+/// Literals are reached wherever they are: in array sizes, initializer
+/// lists, designators, casts and the fields of structures.
+#[test]
+fn visit_reaches_every_literal() {
+    let arena = Arena::default();
+    let program = parse(
+        &arena,
+        "struct s { int a[1]; };\n\
+         int x[2] = { [3] = 4, 5 };\n\
+         int f(void) { return (int) 6 + sizeof(char[7]); }\n",
+    )
+    .unwrap();
+    let mut lits = Lits::default();
+    lits.visit_program(&program);
+    assert_eq!(lits.0, 7);
+
+    let mut lits = Lits::default();
+    visit::walk_program(&mut lits, &program);
+    assert_eq!(lits.0, 7);
+}
+
+/// Identifiers are renamed in nested statements and initializers.
+#[test]
+fn visit_mut_renames() {
+    let arena = Arena::default();
+    let mut program = parse(
+        &arena,
+        "int f(int x) { int y = x; if (x) { while (y) y = x; } return y; }",
+    )
+    .unwrap();
+    let z = program.intern("z");
+    Rename(&arena, z).visit_program_mut(&mut program);
+    let text = program.to_string();
+    assert!(!text.contains("= x"), "{text}");
+    assert!(text.contains("while (z)"), "{text}");
+    assert!(text.contains("return z;"), "{text}");
+}
+
+/// Only the nodes on the way to a change are allocated again.
+#[test]
+fn visit_mut_keeps_unchanged_nodes() {
+    let arena = Arena::default();
+    let mut program = parse(
+        &arena,
+        "int f(int x) { return x; }\nint g(void) { return 1; }\n",
+    )
+    .unwrap();
+    let before: Vec<_> = program.decls.iter().map(body).collect();
+    let z = program.intern("z");
+    Rename(&arena, z).visit_program_mut(&mut program);
+    let after: Vec<_> = program.decls.iter().map(body).collect();
+    assert!(!std::ptr::eq(before[0], after[0]));
+    assert!(std::ptr::eq(before[1], after[1]));
+}
+
+/// The statements in the body of a function definition.
+fn body<'hir>(defn: &TopDefn<'hir>) -> &'hir [Spanned<Stmt<'hir>>] {
+    match defn {
+        TopDefn::Fn(FnDefn {
+            body: Some(body), ..
+        }) => body.stmts,
+        _ => panic!("not a function definition"),
+    }
+}
+
+/// Folding without changing anything gives back the same program.
+#[test]
+fn fold_identity() {
+    for path in SOURCES {
+        let source = fs::read_to_string(path).unwrap();
+        let arena = Arena::default();
+        let program = parse(&arena, &source).unwrap();
+        let expected = format!("{program:#?}");
+        let folded = Identity(&arena).fold_program(program);
+        assert_eq!(format!("{folded:#?}"), expected, "{path}");
+    }
+}
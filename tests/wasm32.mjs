@@ -0,0 +1,202 @@
+// Runs a WebAssembly module compiled by `sea --target=wasm32` under Node.js:
+//
+//     node tests/wasm32.mjs program.wasm
+//
+// The module's `main` is called, and its result is the exit status.  The
+// host provides the few C library functions the test programs import, which
+// read their variadic arguments from the buffer whose address is passed
+// after the named ones.  Types are laid out in the ILP32 data model, so that
+// `long` and pointers take up 4 bytes.
+
+import { readFileSync } from "node:fs";
+
+const out = [];
+let memory;
+
+const bytes = (address) => new Uint8Array(memory.buffer, address);
+const view = () => new DataView(memory.buffer);
+
+function cstring(address) {
+  const mem = bytes(address);
+  const end = mem.indexOf(0);
+  return Buffer.from(mem.subarray(0, end)).toString("latin1");
+}
+
+function write(text) {
+  out.push(Buffer.from(text, "latin1"));
+  return text.length;
+}
+
+// The variadic arguments in a buffer, read in order.
+class VaList {
+  constructor(address) {
+    this.address = address;
+  }
+
+  next(size) {
+    this.address = Math.ceil(this.address / size) * size;
+    const address = this.address;
+    this.address += size;
+    return address;
+  }
+
+  int(size, signed) {
+    const address = this.next(size);
+    if (size === 8) {
+      return signed
+        ? view().getBigInt64(address, true)
+        : view().getBigUint64(address, true);
+    }
+    const value = view().getUint32(address, true);
+    return BigInt(signed ? value | 0 : value);
+  }
+
+  double() {
+    return view().getFloat64(this.next(8), true);
+  }
+}
+
+function pad(text, flags, width) {
+  if (text.length >= width) {
+    return text;
+  }
+  if (flags.includes("-")) {
+    return text.padEnd(width);
+  }
+  if (flags.includes("0")) {
+    const sign = /^[-+ ]/.test(text) ? text[0] : "";
+    return sign + text.slice(sign.length).padStart(width - sign.length, "0");
+  }
+  return text.padStart(width);
+}
+
+function exponent(value, precision, upper) {
+  let text = value.toExponential(precision);
+  text = text.replace(/e([+-])(\d)$/, "e$10$2");
+  return upper ? text.toUpperCase() : text;
+}
+
+function general(value, precision, flags, upper) {
+  const p = precision === 0 ? 1 : precision;
+  const e = value === 0 ? 0 : Math.floor(Math.log10(Math.abs(value)));
+  let text =
+    p > e && e >= -4
+      ? value.toFixed(Math.max(p - 1 - e, 0))
+      : exponent(value, p - 1, upper);
+  if (!flags.includes("#")) {
+    text = text
+      .replace(/(\.\d*?)0+(?=$|[eE])/, "$1")
+      .replace(/\.(?=$|[eE])/, "");
+  }
+  return text;
+}
+
+function format(fmt, args) {
+  let result = "";
+  const spec = /%([-+ #0]*)(\*|\d+)?(?:\.(\*|\d*))?(hh|h|ll|l|z|j|t)?([diouxXcsfFeEgGp%])/g;
+  let last = 0;
+  for (const match of fmt.matchAll(spec)) {
+    result += fmt.slice(last, match.index);
+    last = match.index + match[0].length;
+    let [, flags, width, precision, length, conv] = match;
+    width = width === "*" ? Number(args.int(4, true)) : Number(width ?? 0);
+    if (precision === "*") {
+      precision = Number(args.int(4, true));
+    } else if (precision !== undefined) {
+      precision = Number(precision || 0);
+    }
+    const size = ["ll", "j"].includes(length) ? 8 : 4;
+    let text;
+    switch (conv) {
+      case "%":
+        text = "%";
+        break;
+      case "c":
+        text = String.fromCharCode(Number(args.int(4, false)) & 0xff);
+        break;
+      case "s":
+        text = cstring(Number(args.int(4, false)));
+        if (precision !== undefined) {
+          text = text.slice(0, precision);
+        }
+        break;
+      case "p":
+        text = "0x" + args.int(4, false).toString(16);
+        break;
+      case "d":
+      case "i":
+      case "o":
+      case "u":
+      case "x":
+      case "X": {
+        const signed = conv === "d" || conv === "i";
+        let value = args.int(size, signed);
+        if (length === "hh") {
+          value = signed ? BigInt.asIntN(8, value) : BigInt.asUintN(8, value);
+        } else if (length === "h") {
+          value = signed ? BigInt.asIntN(16, value) : BigInt.asUintN(16, value);
+        }
+        const radix = { o: 8, x: 16, X: 16 }[conv] ?? 10;
+        const negative = value < 0n;
+        text = (negative ? -value : value).toString(radix);
+        if (precision !== undefined) {
+          text = text.padStart(precision, "0");
+        }
+        if (flags.includes("#") && value !== 0n && radix !== 10) {
+          text = (radix === 8 ? "0" : "0x") + text;
+        }
+        if (conv === "X") {
+          text = text.toUpperCase();
+        }
+        if (negative) {
+          text = "-" + text;
+        } else if (signed && flags.includes("+")) {
+          text = "+" + text;
+        } else if (signed && flags.includes(" ")) {
+          text = " " + text;
+        }
+        break;
+      }
+      default: {
+        const value = args.double();
+        const upper = conv === conv.toUpperCase();
+        const p = precision ?? 6;
+        if (!Number.isFinite(value)) {
+          text = Number.isNaN(value) ? "nan" : value < 0 ? "-inf" : "inf";
+          text = upper ? text.toUpperCase() : text;
+        } else if (conv === "f" || conv === "F") {
+          text = value.toFixed(p);
+        } else if (conv === "e" || conv === "E") {
+          text = exponent(value, p, upper);
+        } else {
+          text = general(value, p, flags, upper);
+        }
+        if (value >= 0 && flags.includes("+")) {
+          text = "+" + text;
+        } else if (value >= 0 && flags.includes(" ")) {
+          text = " " + text;
+        }
+      }
+    }
+    result += pad(text, flags, width);
+  }
+  return result + fmt.slice(last);
+}
+
+const env = {
+  printf: (fmt, va) => write(format(cstring(fmt), new VaList(va))),
+  vprintf: (fmt, ap) => write(format(cstring(fmt), new VaList(ap))),
+  puts: (s) => write(cstring(s) + "\n"),
+  putchar: (c) => {
+    write(String.fromCharCode(c & 0xff));
+    return c;
+  },
+};
+
+const module = new WebAssembly.Module(readFileSync(process.argv[2]));
+const instance = new WebAssembly.Instance(module, { env });
+memory = instance.exports.memory;
+const { main } = instance.exports;
+const status = main.length === 0 ? main() : main(0, 0);
+process.stdout.write(Buffer.concat(out));
+process.exitCode = status & 0xff;